@@ -0,0 +1,10 @@
+//! C library build of ambers: `libambers_capi.so` (`.dylib`, `.dll`) and
+//! `libambers_capi.a`, exporting the functions declared in
+//! `include/ambers.h`.
+//!
+//! ```text
+//! cargo build --release -p ambers-capi
+//! cc app.c -Iinclude -Ltarget/release -lambers_capi
+//! ```
+
+pub use ambers::capi::*;
@@ -0,0 +1,79 @@
+//! Compile `tests/smoke.c` against the built library and run it.
+
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use ambers::SpssMetadata;
+use arrow::array::{Float64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+/// Build the library (`cargo test` only builds what the tests link) and
+/// return the directory holding it: the test binary lives in its `deps`.
+fn library_dir() -> PathBuf {
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--package", "ambers-capi"]);
+    if !cfg!(debug_assertions) {
+        build.arg("--release");
+    }
+    assert!(
+        build.status().unwrap().success(),
+        "building ambers-capi failed"
+    );
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+fn write_sample(path: &Path) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("age", DataType::Float64, true),
+        Field::new("city", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(Float64Array::from_iter_values((0..250).map(f64::from))),
+            Arc::new(StringArray::from_iter_values(
+                (0..250).map(|i| format!("city {i}")),
+            )),
+        ],
+    )
+    .unwrap();
+    ambers::write_sav(path, &batch, &SpssMetadata::default()).unwrap();
+}
+
+#[test]
+fn test_c_smoke() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = library_dir();
+    let dir = tempfile::tempdir().unwrap();
+    let exe = dir.path().join("smoke");
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .arg(manifest.join("tests/smoke.c"))
+        .arg("-I")
+        .arg(manifest.join("../include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lambers_capi")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("a C compiler is needed for this test (set CC)");
+    assert!(status.success(), "compiling smoke.c failed");
+
+    let sav = dir.path().join("people.sav");
+    write_sample(&sav);
+    let output = Command::new(&exe).arg(&sav).arg("city").output().unwrap();
+    assert!(
+        output.status.success(),
+        "smoke test failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "1 250 250");
+}
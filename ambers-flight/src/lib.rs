@@ -0,0 +1,322 @@
+//! Arrow Flight service over a directory of SPSS files.
+//!
+//! `SavFlightService` serves every .sav/.zsav file under a root directory:
+//!
+//! - `ListFlights` lists the files, with their schemas and row counts.
+//! - `GetFlightInfo` describes one file, given its path as the descriptor.
+//! - `DoGet` streams a file. The ticket is the file's path relative to the
+//!   root, optionally followed by a newline and a comma-separated list of
+//!   columns to read (see `ticket`).
+//!
+//! Paths that resolve outside the root are rejected.
+//!
+//! # Example
+//! ```no_run
+//! use ambers_flight::SavFlightService;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let service = SavFlightService::new("/data/surveys")?;
+//! tonic::transport::Server::builder()
+//!     .add_service(service.into_server())
+//!     .serve("0.0.0.0:50051".parse()?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use ambers::error::{Result, SpssError};
+use ambers::scanner::SavScanner;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Build a `DoGet` ticket for `path` (relative to the served root),
+/// reading only `columns` when it is not empty.
+pub fn ticket(path: &str, columns: &[&str]) -> Ticket {
+    let mut text = path.to_string();
+    if !columns.is_empty() {
+        text.push('\n');
+        text.push_str(&columns.join(","));
+    }
+    Ticket::new(text)
+}
+
+/// Arrow Flight service serving the SPSS files under a root directory.
+#[derive(Debug, Clone)]
+pub struct SavFlightService {
+    root: PathBuf,
+}
+
+impl SavFlightService {
+    /// Serve the files under `root`.
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        Ok(SavFlightService {
+            root: root.as_ref().canonicalize()?,
+        })
+    }
+
+    /// Wrap the service in a tonic server, ready for
+    /// `tonic::transport::Server::add_service`.
+    pub fn into_server(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+
+    /// Resolve a path relative to the root, refusing anything outside it.
+    fn resolve(&self, relative: &str) -> std::result::Result<PathBuf, Status> {
+        let path = self
+            .root
+            .join(relative)
+            .canonicalize()
+            .map_err(|_| Status::not_found(format!("no such file: {relative:?}")))?;
+        if !path.starts_with(&self.root) || !path.is_file() {
+            return Err(Status::not_found(format!("no such file: {relative:?}")));
+        }
+        Ok(path)
+    }
+
+    fn open(&self, relative: &str) -> std::result::Result<SavScanner<BufReader<File>>, Status> {
+        ambers::scan_sav(self.resolve(relative)?).map_err(to_status)
+    }
+
+    fn flight_info(&self, relative: &str) -> std::result::Result<FlightInfo, Status> {
+        let scanner = self.open(relative)?;
+        let mut info = FlightInfo::new()
+            .try_with_schema(&scanner.schema())
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_descriptor(FlightDescriptor::new_path(vec![relative.to_string()]))
+            .with_endpoint(FlightEndpoint::new().with_ticket(ticket(relative, &[])));
+        if let Some(rows) = scanner.metadata().number_rows {
+            info = info.with_total_records(rows);
+        }
+        Ok(info)
+    }
+
+    /// Paths of the .sav/.zsav files under the root, relative to it.
+    fn files(&self) -> std::result::Result<Vec<String>, Status> {
+        let mut files = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let entries = std::fs::read_dir(&dir).map_err(|e| Status::internal(e.to_string()))?;
+            for entry in entries {
+                let path = entry.map_err(|e| Status::internal(e.to_string()))?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if is_sav(&path)
+                    && let Ok(relative) = path.strip_prefix(&self.root)
+                {
+                    files.push(relative.to_string_lossy().into_owned());
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+fn is_sav(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("sav") || e.eq_ignore_ascii_case("zsav"))
+}
+
+fn to_status(e: SpssError) -> Status {
+    match e {
+        SpssError::InvalidVariable(message) => Status::invalid_argument(message),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+/// The file path named by a descriptor.
+fn descriptor_path(descriptor: &FlightDescriptor) -> std::result::Result<&str, Status> {
+    match descriptor.path.as_slice() {
+        [path] => Ok(path),
+        _ => Err(Status::invalid_argument(
+            "descriptor must name exactly one file path",
+        )),
+    }
+}
+
+/// Pull batches from `scanner` on the blocking thread pool.
+fn batches(
+    scanner: SavScanner<BufReader<File>>,
+) -> impl futures::Stream<Item = std::result::Result<RecordBatch, FlightError>> {
+    stream::try_unfold(scanner, |mut scanner| async move {
+        tokio::task::spawn_blocking(move || {
+            let batch = scanner.next_batch()?;
+            Ok::<_, SpssError>(batch.map(|b| (b, scanner)))
+        })
+        .await
+        .map_err(|e| FlightError::ExternalError(Box::new(e)))?
+        .map_err(|e| FlightError::ExternalError(Box::new(e)))
+    })
+}
+
+#[tonic::async_trait]
+impl FlightService for SavFlightService {
+    type HandshakeStream = BoxStream<'static, std::result::Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, std::result::Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, std::result::Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, std::result::Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, std::result::Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, std::result::Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, std::result::Result<FlightData, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        let infos = self
+            .files()?
+            .iter()
+            .map(|file| self.flight_info(file))
+            .collect::<Vec<_>>();
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let path = descriptor_path(request.get_ref())?;
+        Ok(Response::new(self.flight_info(path)?))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info is not supported"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented(
+            "get_schema is not supported; use get_flight_info",
+        ))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        let text = std::str::from_utf8(&request.get_ref().ticket)
+            .map_err(|_| Status::invalid_argument("ticket is not UTF-8"))?;
+        let (path, columns) = match text.split_once('\n') {
+            Some((path, columns)) => (path, columns.split(',').map(str::trim).collect()),
+            None => (text, Vec::new()),
+        };
+        let mut scanner = self.open(path)?;
+        if !columns.is_empty() {
+            scanner.select(&columns).map_err(to_status)?;
+        }
+        let schema = scanner.schema().into();
+        let data = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches(scanner))
+            .map_err(Status::from);
+        Ok(Response::new(data.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("the service is read-only"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("no actions are supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("the service is read-only"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow_flight::decode::FlightRecordBatchStream;
+
+    use super::*;
+    use ambers::SpssMetadata;
+
+    #[tokio::test]
+    async fn test_do_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Float64, true),
+            Field::new("b", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+                Arc::new(Float64Array::from(vec![4.0, 5.0, 6.0])),
+            ],
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("wave1")).unwrap();
+        ambers::write_sav(
+            dir.path().join("wave1/x.sav"),
+            &batch,
+            &SpssMetadata::default(),
+        )
+        .unwrap();
+
+        let service = SavFlightService::new(dir.path()).unwrap();
+        assert_eq!(service.files().unwrap(), ["wave1/x.sav"]);
+
+        let response = service
+            .do_get(Request::new(ticket("wave1/x.sav", &["b"])))
+            .await
+            .unwrap();
+        let stream = response.into_inner().map_err(FlightError::from);
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(stream)
+            .try_collect()
+            .await
+            .unwrap();
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 3);
+        assert_eq!(batches[0].schema().field(0).name(), "b");
+
+        let outside = service.do_get(Request::new(ticket("../x.sav", &[]))).await;
+        assert_eq!(outside.err().unwrap().code(), tonic::Code::NotFound);
+    }
+}
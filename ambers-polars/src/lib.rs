@@ -0,0 +1,317 @@
+//! Polars LazyFrame source for SPSS .sav and .zsav files.
+//!
+//! `scan_sav` registers an ambers scanner as a Polars anonymous scan, so a
+//! file can be queried with the lazy API directly instead of going through
+//! pyarrow or an intermediate `RecordBatch`:
+//!
+//! ```no_run
+//! use polars::prelude::*;
+//!
+//! let df = ambers_polars::scan_sav("survey.sav")
+//!     .unwrap()
+//!     .filter(col("age").gt_eq(lit(18.0)))
+//!     .select([col("region"), col("age")])
+//!     .limit(1000)
+//!     .collect()
+//!     .unwrap();
+//! ```
+//!
+//! Projections are pushed down to the scanner's column selection and row
+//! limits to its row limit. Predicates are applied by Polars after each
+//! file is read, but simple comparisons of a numeric or string column with
+//! a literal (and conjunctions of them) are also handed to the scanner's
+//! row filter, so rows that cannot match are never decoded.
+
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ambers::filter::{CompareOp, Predicate};
+use ambers::{ScanOptions, SpssMetadata};
+use arrow::array::Array;
+use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use polars::prelude::*;
+
+/// Lazily scan a .sav or .zsav file with default `ScanOptions`.
+pub fn scan_sav(path: impl AsRef<Path>) -> PolarsResult<LazyFrame> {
+    scan_sav_with(path, ScanOptions::default())
+}
+
+/// Lazily scan a .sav or .zsav file configured by `ScanOptions`.
+///
+/// The options shape the schema (missing-value policy, labels, integers,
+/// column renaming, ...) exactly as they do for `ambers::scan_sav_with`.
+/// Column selection and row limits are taken from the lazy query, so
+/// `columns` and `n_rows` in `options` are best left unset.
+pub fn scan_sav_with(path: impl AsRef<Path>, options: ScanOptions) -> PolarsResult<LazyFrame> {
+    let path = path.as_ref().to_path_buf();
+    let scanner = ambers::scan_sav_with(&path, &options).map_err(to_polars)?;
+    let schema = Arc::new(polars_schema(&scanner.schema())?);
+    let source = SavSource {
+        path,
+        options,
+        metadata: scanner.metadata().clone(),
+        schema: schema.clone(),
+    };
+    let args = ScanArgsAnonymous {
+        schema: Some(schema),
+        name: "SAV SCAN",
+        ..Default::default()
+    };
+    LazyFrame::anonymous_scan(Arc::new(source), args)
+}
+
+struct SavSource {
+    path: PathBuf,
+    options: ScanOptions,
+    metadata: SpssMetadata,
+    schema: SchemaRef,
+}
+
+impl SavSource {
+    /// File variable name for an output column, or `None` for columns the
+    /// scanner derives itself (case numbers, missing reasons).
+    fn variable_name<'a>(&'a self, column: &'a str) -> Option<&'a str> {
+        if let Some(original) = self.metadata.renamed_columns.get(column) {
+            return Some(original);
+        }
+        self.metadata
+            .variable_names
+            .iter()
+            .any(|name| name == column)
+            .then_some(column)
+    }
+
+    /// Translate the parts of a Polars predicate the scanner's row filter
+    /// can check. Every returned predicate is implied by `expr`, so the
+    /// scanner only drops rows Polars would drop too.
+    fn pushdown(&self, expr: &Expr, out: &mut Vec<Predicate>) {
+        let Expr::BinaryExpr { left, op, right } = expr else {
+            return;
+        };
+        match op {
+            Operator::And | Operator::LogicalAnd => {
+                self.pushdown(left, out);
+                self.pushdown(right, out);
+            }
+            _ => {
+                if let Some(predicate) = self.comparison(left, *op, right) {
+                    out.push(predicate);
+                }
+            }
+        }
+    }
+
+    fn comparison(&self, left: &Expr, op: Operator, right: &Expr) -> Option<Predicate> {
+        let (name, scalar, op) = match (left, right) {
+            (Expr::Column(name), Expr::Literal(LiteralValue::Scalar(s))) => (name, s, op),
+            (Expr::Literal(LiteralValue::Scalar(s)), Expr::Column(name)) => {
+                (name, s, op.swap_operands())
+            }
+            _ => return None,
+        };
+        let op = match op {
+            Operator::Eq => CompareOp::Eq,
+            Operator::NotEq => CompareOp::Ne,
+            Operator::Lt => CompareOp::Lt,
+            Operator::LtEq => CompareOp::Le,
+            Operator::Gt => CompareOp::Gt,
+            Operator::GtEq => CompareOp::Ge,
+            _ => return None,
+        };
+        let column = self.variable_name(name)?.to_string();
+        // Only columns that hold the raw file values compare the same way
+        // in both places: dates, casts and labels change the values, and
+        // padded strings keep spaces the scanner's filter trims.
+        let value = match self.schema.get(name)? {
+            DataType::Float64 => scalar.value().extract::<f64>()?.into(),
+            DataType::String if !self.options.keep_trailing_spaces => {
+                scalar.value().extract_str()?.into()
+            }
+            _ => return None,
+        };
+        Some(Predicate::Compare { column, op, value })
+    }
+}
+
+impl AnonymousScan for SavSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
+        let mut scanner = ambers::scan_sav_with(&self.path, &self.options).map_err(to_polars)?;
+
+        if let Some(columns) = &scan_opts.with_columns {
+            let names: Vec<&str> = columns
+                .iter()
+                .filter_map(|c| self.variable_name(c.as_str()))
+                .collect();
+            if !names.is_empty() {
+                scanner.select(&names).map_err(to_polars)?;
+            }
+        }
+        if let Some(predicate) = &scan_opts.predicate {
+            let mut pushed = Vec::new();
+            self.pushdown(predicate, &mut pushed);
+            for p in pushed {
+                // A comparison the filter rejects (e.g. a type mismatch) is
+                // left to Polars.
+                let _ = scanner.filter(p);
+            }
+        } else if let Some(n) = scan_opts.n_rows {
+            scanner.limit(n);
+        }
+
+        let mut df = DataFrame::empty();
+        while let Some(batch) = scanner.next_batch().map_err(to_polars)? {
+            let chunk = to_dataframe(&batch)?;
+            if df.width() == 0 {
+                df = chunk;
+            } else {
+                df.vstack_mut_owned(chunk)?;
+            }
+        }
+        if df.width() == 0 {
+            df = DataFrame::empty_with_schema(&self.schema);
+        }
+        if let Some(predicate) = scan_opts.predicate {
+            df = df.lazy().filter(predicate).collect()?;
+            if let Some(n) = scan_opts.n_rows {
+                df = df.head(Some(n));
+            }
+        }
+        if let Some(columns) = &scan_opts.with_columns {
+            df = df.select(columns.iter().cloned())?;
+        }
+        Ok(df)
+    }
+
+    fn schema(&self, _infer_schema_length: Option<usize>) -> PolarsResult<SchemaRef> {
+        Ok(self.schema.clone())
+    }
+
+    fn allows_predicate_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_projection_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_slice_pushdown(&self) -> bool {
+        true
+    }
+}
+
+fn to_polars(e: ambers::error::SpssError) -> PolarsError {
+    PolarsError::ComputeError(e.to_string().into())
+}
+
+/// Move an arrow-rs field into polars-arrow through the C Data Interface.
+fn import_field(field: &arrow::datatypes::Field) -> PolarsResult<polars_arrow::datatypes::Field> {
+    let schema = FFI_ArrowSchema::try_from(field)
+        .map_err(|e| PolarsError::ComputeError(e.to_string().into()))?;
+    // Both structs are the `ArrowSchema` of the C Data Interface.
+    let schema: polars_arrow::ffi::ArrowSchema = unsafe { std::mem::transmute(schema) };
+    unsafe { polars_arrow::ffi::import_field_from_c(&schema) }
+}
+
+fn polars_schema(schema: &arrow::datatypes::Schema) -> PolarsResult<Schema> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| {
+            let field = import_field(f)?;
+            Ok(Field::new(
+                f.name().into(),
+                DataType::from_arrow_field(&field),
+            ))
+        })
+        .collect()
+}
+
+fn to_dataframe(batch: &RecordBatch) -> PolarsResult<DataFrame> {
+    let schema = batch.schema();
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(f, array)| {
+            let field = import_field(f)?;
+            let array = FFI_ArrowArray::new(&array.to_data());
+            // Both structs are the `ArrowArray` of the C Data Interface;
+            // the imported array takes over its release callback.
+            let array: polars_arrow::ffi::ArrowArray = unsafe { std::mem::transmute(array) };
+            let array =
+                unsafe { polars_arrow::ffi::import_array_from_c(array, field.dtype().clone())? };
+            Ok(Series::from_arrow(f.name().into(), array)?.into_column())
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+    DataFrame::new(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType as ArrowType, Field as ArrowField, Schema as ArrowSchema};
+
+    use super::*;
+
+    fn write_sample(dir: &Path) -> PathBuf {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("age", ArrowType::Float64, true),
+            ArrowField::new("city", ArrowType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(15.0),
+                    Some(42.0),
+                    None,
+                    Some(67.0),
+                ])),
+                Arc::new(StringArray::from(vec!["Oslo", "Rome", "Oslo", "Lima"])),
+            ],
+        )
+        .unwrap();
+        let path = dir.join("people.sav");
+        ambers::write_sav(&path, &batch, &SpssMetadata::default()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_scan_sav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(dir.path());
+
+        let df = scan_sav(&path).unwrap().collect().unwrap();
+        assert_eq!(df.shape(), (4, 2));
+        assert_eq!(df.column("age").unwrap().dtype(), &DataType::Float64);
+        assert_eq!(df.column("city").unwrap().dtype(), &DataType::String);
+        assert_eq!(df.column("age").unwrap().null_count(), 1);
+    }
+
+    #[test]
+    fn test_scan_sav_pushdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(dir.path());
+
+        let df = scan_sav(&path)
+            .unwrap()
+            .filter(col("age").gt(lit(20.0)).and(col("city").neq(lit("Lima"))))
+            .select([col("city")])
+            .collect()
+            .unwrap();
+        assert_eq!(df.get_column_names(), ["city"]);
+        let city = df.column("city").unwrap().str().unwrap();
+        assert_eq!(city.into_iter().collect::<Vec<_>>(), [Some("Rome")]);
+
+        let df = scan_sav(&path).unwrap().limit(3).collect().unwrap();
+        assert_eq!(df.height(), 3);
+    }
+}
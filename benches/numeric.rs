@@ -0,0 +1,74 @@
+//! Time decoding numeric columns, with and without system-missing values.
+//!
+//! Compare the scalar and vectorized null scans with:
+//!     cargo bench --bench numeric
+//!     cargo bench --bench numeric --features simd
+
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ambers::{Compression, SpssMetadata, WriteOptions};
+use arrow::array::{ArrayRef, Float64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+const ROWS: usize = 500_000;
+const COLUMNS: usize = 20;
+const RUNS: usize = 15;
+
+/// `COLUMNS` numeric columns; one value in `null_every` is missing.
+fn sav_bytes(null_every: Option<usize>) -> Vec<u8> {
+    let fields: Vec<Field> = (0..COLUMNS)
+        .map(|c| Field::new(format!("v{c}"), DataType::Float64, true))
+        .collect();
+    let columns: Vec<ArrayRef> = (0..COLUMNS)
+        .map(|c| {
+            let values = (0..ROWS).map(|i| match null_every {
+                Some(n) if (i + c) % n == 0 => None,
+                _ => Some((i * (c + 1)) as f64 * 0.5),
+            });
+            Arc::new(Float64Array::from_iter(values)) as ArrayRef
+        })
+        .collect();
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+    let options = WriteOptions {
+        compression: Compression::None,
+        ..WriteOptions::default()
+    };
+    let mut buf = Vec::new();
+    ambers::write_sav_to_writer_with(&mut buf, &batch, &SpssMetadata::default(), &options)
+        .unwrap();
+    buf
+}
+
+fn median_read_time(bytes: &[u8]) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let (batch, _) = ambers::read_sav_from_reader(Cursor::new(bytes)).unwrap();
+            assert_eq!(batch.num_rows(), ROWS);
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    println!("simd: {}", cfg!(feature = "simd"));
+    for (name, null_every) in [
+        ("no missing", None),
+        ("1 in 1000 missing", Some(1000)),
+        ("1 in 10 missing", Some(10)),
+    ] {
+        let bytes = sav_bytes(null_every);
+        let time = median_read_time(&bytes);
+        let cells = (ROWS * COLUMNS) as f64;
+        println!(
+            "{name:>18}: {:>8.2} ms ({:.2} ns/value)",
+            time.as_secs_f64() * 1e3,
+            time.as_secs_f64() * 1e9 / cells
+        );
+    }
+}
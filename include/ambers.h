@@ -0,0 +1,106 @@
+/*
+ * ambers C API: read SPSS .sav/.zsav files through the Arrow C Data and
+ * C Stream Interfaces. Build the shared and static libraries with
+ *
+ *     cargo build --release -p ambers-capi
+ *
+ * and link with -lambers_capi.
+ *
+ * Functions returning int return a negative value on error; the message is
+ * then available from ambers_last_error() on the same thread.
+ */
+#ifndef AMBERS_H
+#define AMBERS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Arrow C Data / C Stream Interface structs, as specified by Apache Arrow. */
+#ifndef ARROW_C_DATA_INTERFACE
+#define ARROW_C_DATA_INTERFACE
+
+#define ARROW_FLAG_DICTIONARY_ORDERED 1
+#define ARROW_FLAG_NULLABLE 2
+#define ARROW_FLAG_MAP_KEYS_SORTED 4
+
+struct ArrowSchema {
+  const char* format;
+  const char* name;
+  const char* metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema** children;
+  struct ArrowSchema* dictionary;
+  void (*release)(struct ArrowSchema*);
+  void* private_data;
+};
+
+struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void** buffers;
+  struct ArrowArray** children;
+  struct ArrowArray* dictionary;
+  void (*release)(struct ArrowArray*);
+  void* private_data;
+};
+
+#endif /* ARROW_C_DATA_INTERFACE */
+
+#ifndef ARROW_C_STREAM_INTERFACE
+#define ARROW_C_STREAM_INTERFACE
+
+struct ArrowArrayStream {
+  int (*get_schema)(struct ArrowArrayStream*, struct ArrowSchema* out);
+  int (*get_next)(struct ArrowArrayStream*, struct ArrowArray* out);
+  const char* (*get_last_error)(struct ArrowArrayStream*);
+  void (*release)(struct ArrowArrayStream*);
+  void* private_data;
+};
+
+#endif /* ARROW_C_STREAM_INTERFACE */
+
+typedef struct AmbersScanner AmbersScanner;
+
+/* Message of the last error on this thread, or NULL. Valid until the next
+ * failing call on the same thread. */
+const char* ambers_last_error(void);
+
+/* Open a .sav or .zsav file. Returns NULL on error. */
+AmbersScanner* ambers_open(const char* path);
+
+/* Only read the n named columns, in that order. Returns 0 on success. */
+int ambers_select(AmbersScanner* scanner, const char* const* names, size_t n);
+
+/* Stop after n rows. Returns 0 on success. */
+int ambers_limit(AmbersScanner* scanner, uint64_t n);
+
+/* Export the output schema as a struct-typed ArrowSchema. Returns 0 on
+ * success; the caller releases out. */
+int ambers_schema(AmbersScanner* scanner, struct ArrowSchema* out);
+
+/* Read the next chunk as a struct-typed ArrowArray. Returns 1 when a chunk
+ * was written, 0 at the end of the data, -1 on error. The caller releases
+ * out. */
+int ambers_next(AmbersScanner* scanner, struct ArrowArray* out);
+
+/* Hand the scanner over as an ArrowArrayStream. Returns 0 on success. The
+ * scanner is consumed either way and must not be used or closed
+ * afterwards. */
+int ambers_stream(AmbersScanner* scanner, struct ArrowArrayStream* out);
+
+/* Close a scanner and free its resources. NULL is ignored. */
+void ambers_close(AmbersScanner* scanner);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AMBERS_H */
@@ -1,4 +1,4 @@
-"""ambers: Pure Rust SPSS .sav/.zsav reader."""
+"""ambers: Pure Rust SPSS .sav/.zsav reader and writer."""
 
 from __future__ import annotations
 
@@ -8,12 +8,14 @@ from ambers._ambers import (
     _SavBatchReader,
     _read_sav,
     _read_sav_metadata,
+    _write_sav,
 )
 
 __all__ = [
     "read_sav",
     "read_sav_metadata",
     "scan_sav",
+    "write_sav",
     "SpssMetadata",
     "MetaDiff",
 ]
@@ -198,3 +200,41 @@ def scan_sav(
     if row_index_name is not None:
         lf = lf.with_row_index(row_index_name, offset=row_index_offset)
     return lf, meta
+
+
+def write_sav(
+    data,
+    metadata: SpssMetadata | None,
+    path: str,
+    *,
+    compression: str | None = None,
+    zlib_level: int = 6,
+    encoding: str = "UTF-8",
+) -> None:
+    """Write a table to an SPSS .sav or .zsav file.
+
+    Args:
+        data: Any object implementing the Arrow PyCapsule stream interface
+            (``__arrow_c_stream__``): a pyarrow Table or RecordBatch, a
+            polars DataFrame, etc. Data is written batch by batch.
+        metadata: Variable labels, value labels, formats, missing values
+            and other dictionary information, e.g. from read_sav(). Columns
+            without metadata get defaults. None writes defaults only.
+        path: Path of the file to create.
+        compression: "none", "bytecode" or "zlib". None (default) picks
+            "zlib" for a .zsav path and "none" otherwise.
+        zlib_level: zlib compression level (0-9) for .zsav output.
+        encoding: Character encoding of the file, e.g. "UTF-8" or
+            "windows-1252".
+    """
+    path = str(path)
+    if compression is None:
+        compression = "zlib" if path.lower().endswith(".zsav") else "none"
+    _write_sav(
+        data,
+        path,
+        metadata=metadata,
+        compression=compression,
+        zlib_level=zlib_level,
+        encoding=encoding,
+    )
@@ -40,24 +40,51 @@ class SpssMetadata:
     @property
     def variable_measure(self) -> dict[str, str]: ...
     @property
+    def variable_role(self) -> dict[str, str]: ...
+    @property
+    def file_attributes(self) -> dict[str, list[str]]: ...
+    @property
+    def variable_attributes(self) -> dict[str, dict[str, list[str]]]: ...
+    @property
+    def date_info(self) -> list[list[int]]: ...
+    @property
+    def unknown_records(self) -> list[tuple[int, bytes]]: ...
+    @property
     def variable_missing(self) -> dict[str, list[dict]]: ...
     @property
     def mr_sets(self) -> dict[str, dict]: ...
     @property
     def weight_variable(self) -> str | None: ...
     @property
+    def renamed_columns(self) -> dict[str, str]: ...
+    @property
+    def renamed_variables(self) -> dict[str, str]: ...
+    @property
+    def unknown_formats(self) -> dict[str, int]: ...
+    @property
+    def warnings(self) -> list[dict[str, str | None]]: ...
+    @property
+    def replaced_characters(self) -> dict[str, int]: ...
+    @property
+    def writer(self) -> dict: ...
+    @property
     def schema(self) -> dict: ...
 
     def label(self, name: str) -> str | None: ...
     def format(self, name: str) -> str | None: ...
     def measure(self, name: str) -> str | None: ...
+    def role(self, name: str) -> str | None: ...
     def value(self, name: str) -> dict[float | str, str] | None: ...
     def check_var(self, name: str) -> None: ...
     def summary(self) -> None: ...
     def describe(self, names: str | list[str]) -> None: ...
+    def validate(self) -> list[dict[str, str]]: ...
     def diff(
         self, other: SpssMetadata, print_output: bool = True
     ) -> MetaDiff: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(text: str) -> SpssMetadata: ...
 
 class MetaDiff:
     @property
@@ -103,3 +130,12 @@ def scan_sav(
     row_index_name: str | None = None,
     row_index_offset: int = 0,
 ) -> tuple[polars.LazyFrame, SpssMetadata]: ...
+def write_sav(
+    data: object,
+    metadata: SpssMetadata | None,
+    path: str,
+    *,
+    compression: str | None = None,
+    zlib_level: int = 6,
+    encoding: str = "UTF-8",
+) -> None: ...
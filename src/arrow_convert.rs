@@ -1,27 +1,311 @@
-use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::constants::{TemporalKind, VarType};
+use arrow::array::{Array, AsArray, Float64Array};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Int64Type, Schema, TimeUnit};
+
+use crate::constants::{
+    Compression, FormatType, SECONDS_PER_DAY, SPSS_EPOCH_OFFSET_SECONDS, SpssFormat, TemporalKind, VarType,
+};
+use crate::error::Result;
 use crate::dictionary::ResolvedDictionary;
+use crate::metadata::{MrType, SpssMetadata, Value};
+use crate::options::{LabelMode, OutputOptions, TemporalMode};
 use crate::variable::VariableRecord;
 
 /// Determine the Arrow DataType for a resolved SPSS variable.
 pub fn var_to_arrow_type(var: &VariableRecord) -> DataType {
     match &var.var_type {
-        VarType::Numeric => {
-            match var
-                .print_format
+        VarType::Numeric => numeric_arrow_type(
+            var.print_format
                 .as_ref()
-                .and_then(|f| f.format_type.temporal_kind())
+                .and_then(|f| f.format_type.temporal_kind()),
+        ),
+        VarType::String(_) => DataType::Utf8View,
+    }
+}
+
+/// The Arrow DataType a variable is returned as under `options`.
+pub fn output_arrow_type(
+    var: &VariableRecord,
+    metadata: &SpssMetadata,
+    options: &OutputOptions,
+) -> DataType {
+    match &var.var_type {
+        VarType::String(_) => options.string_type.data_type(),
+        VarType::Numeric if uses_labels(var, metadata, options) => {
+            match options.labels {
+                LabelMode::Labels => options.string_type.data_type(),
+                _ => DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            }
+        }
+        VarType::Numeric => {
+            if options.booleans && dichotomy(var, metadata).is_some() {
+                DataType::Boolean
+            } else if options.integers
+                && let Some(int_type) = integer_type(var)
             {
-                Some(TemporalKind::Date) => DataType::Date32,
-                Some(TemporalKind::Timestamp) => {
-                    DataType::Timestamp(TimeUnit::Microsecond, None)
+                int_type
+            } else if options.temporal == TemporalMode::Raw {
+                DataType::Float64
+            } else {
+                match var_to_arrow_type(var) {
+                    DataType::Float64 if options.float32.applies_to(&var.long_name) => {
+                        DataType::Float32
+                    }
+                    data_type => data_type,
                 }
-                Some(TemporalKind::Duration) => DataType::Duration(TimeUnit::Microsecond),
-                None => DataType::Float64,
             }
         }
-        VarType::String(_) => DataType::Utf8View,
+    }
+}
+
+/// How a numeric variable reads as a Boolean (`ScanOptions::booleans`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dichotomy {
+    /// Member of a multiple-dichotomy set: true where the value equals the
+    /// set's counted value, false elsewhere.
+    Counted(f64),
+    /// Labelled exactly {0, 1}: no other value is allowed.
+    ZeroOne,
+}
+
+/// Whether `var` is a 0/1 dichotomy, and of which kind.
+pub fn dichotomy(var: &VariableRecord, metadata: &SpssMetadata) -> Option<Dichotomy> {
+    if var_to_arrow_type(var) != DataType::Float64 {
+        return None;
+    }
+    let counted = metadata.mr_sets.values().find_map(|set| {
+        let member = set.mr_type == MrType::MultipleDichotomy
+            && set.variables.iter().any(|v| v == &var.long_name);
+        member
+            .then(|| set.counted_value.as_deref()?.trim().parse::<f64>().ok())
+            .flatten()
+    });
+    if let Some(counted) = counted {
+        return Some(Dichotomy::Counted(counted));
+    }
+    let labels = metadata.variable_value_labels.get(&var.long_name)?;
+    let mut codes: Vec<f64> = labels
+        .keys()
+        .map(|value| match value {
+            Value::Numeric(v) => *v,
+            Value::String(_) => f64::NAN,
+        })
+        .collect();
+    codes.sort_by(f64::total_cmp);
+    (codes == [0.0, 1.0]).then_some(Dichotomy::ZeroOne)
+}
+
+/// The integer type a whole-number variable (F or N format, no decimals)
+/// can be returned as: Int32 up to 9 digits wide, Int64 beyond.
+pub fn integer_type(var: &VariableRecord) -> Option<DataType> {
+    let format = var.print_format.as_ref()?;
+    if !matches!(format.format_type, FormatType::F | FormatType::N) || format.decimals != 0 {
+        return None;
+    }
+    Some(if format.width <= 9 {
+        DataType::Int32
+    } else {
+        DataType::Int64
+    })
+}
+
+/// The output schema for the variables at `projection` (all when `None`)
+/// under `options`, including any case-number and missing-reason columns.
+/// The schema's key/value metadata describes the file (see
+/// `schema_metadata`).
+pub fn output_schema(
+    dict: &ResolvedDictionary,
+    projection: Option<&[usize]>,
+    options: &OutputOptions,
+) -> Schema {
+    let indices: Vec<usize> = match projection {
+        Some(proj) => proj.to_vec(),
+        None => (0..dict.variables.len()).collect(),
+    };
+    let mut fields = Vec::with_capacity(indices.len() + 1);
+    if let Some(name) = &options.case_numbers {
+        fields.push(Field::new(name, DataType::UInt64, false));
+    }
+    for i in indices {
+        let var = &dict.variables[i];
+        let name = match &options.column_names {
+            Some(names) => &names[i],
+            None => &var.long_name,
+        };
+        let mut data_type = output_arrow_type(var, &dict.metadata, options);
+        if options.run_end_encoded.contains(&var.long_name) {
+            data_type = run_end_type(data_type);
+        }
+        fields.push(Field::new(name, data_type, true));
+        if options.missing_reasons && has_missing_reason(var, &dict.metadata) {
+            fields.push(Field::new(
+                format!("{name}_missing"),
+                DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8)),
+                true,
+            ));
+        }
+    }
+    Schema::new(fields).with_metadata(schema_metadata(&dict.metadata, options))
+}
+
+/// File facts stored in the schema's key/value metadata, so batches stay
+/// self-describing once they leave ambers: `spss.file_label`,
+/// `spss.encoding`, `spss.creation_time`, `spss.compression` (`none`,
+/// `bytecode` or `zlib`), `spss.weight_variable` and `spss.source_path`.
+/// Keys whose value is empty or unknown are left out.
+pub fn schema_metadata(
+    metadata: &SpssMetadata,
+    options: &OutputOptions,
+) -> HashMap<String, String> {
+    let creation_time = format!("{} {}", metadata.creation_time, metadata.modification_time);
+    let compression = match metadata.compression {
+        Compression::None => "none",
+        Compression::Bytecode => "bytecode",
+        Compression::Zlib => "zlib",
+    };
+    [
+        ("spss.file_label", Some(metadata.file_label.trim())),
+        ("spss.encoding", Some(metadata.file_encoding.as_str())),
+        ("spss.creation_time", Some(creation_time.trim())),
+        ("spss.compression", Some(compression)),
+        ("spss.weight_variable", metadata.weight_variable.as_deref()),
+        ("spss.source_path", options.source_path.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| match value {
+        Some(value) if !value.is_empty() => Some((key.to_string(), value.to_string())),
+        _ => None,
+    })
+    .collect()
+}
+
+/// The RunEndEncoded type (Int32 run ends) over `values`, as built by
+/// `RunArray::try_new`.
+pub fn run_end_type(values: DataType) -> DataType {
+    DataType::RunEndEncoded(
+        Arc::new(Field::new("run_ends", DataType::Int32, false)),
+        Arc::new(Field::new("values", values, true)),
+    )
+}
+
+/// Column names safe for SQL engines and Parquet (`ScanOptions::sanitize_names`).
+///
+/// Accented Latin letters are transliterated to ASCII; any other character
+/// outside `[A-Za-z0-9_]` becomes `_`, with runs collapsed and leading or
+/// trailing underscores dropped. Names starting with a digit get a leading
+/// `_`, empty names become `column`, and names that clash (ignoring case)
+/// with an earlier one get a `_2`, `_3`, ... suffix.
+pub fn sanitized_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut taken = std::collections::HashSet::new();
+    let mut out = Vec::new();
+    for name in names {
+        let mut clean = String::with_capacity(name.len());
+        for c in name.chars() {
+            match c {
+                'A'..='Z' | 'a'..='z' | '0'..='9' => clean.push(c),
+                _ => match transliterate(c) {
+                    Some(ascii) => clean.push_str(ascii),
+                    None if !clean.ends_with('_') => clean.push('_'),
+                    None => {}
+                },
+            }
+        }
+        let mut clean = clean.trim_matches('_').to_string();
+        if clean.is_empty() {
+            clean = "column".to_string();
+        } else if clean.starts_with(|c: char| c.is_ascii_digit()) {
+            clean.insert(0, '_');
+        }
+        let mut unique = clean.clone();
+        let mut suffix = 2;
+        while !taken.insert(unique.to_lowercase()) {
+            unique = format!("{clean}_{suffix}");
+            suffix += 1;
+        }
+        out.push(unique);
+    }
+    out
+}
+
+/// ASCII spelling of common accented Latin letters.
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ą' => "A",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'ď' | 'đ' | 'ð' => "d",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' | 'Ě' => "E",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'Ğ' => "G",
+        'ğ' => "g",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'İ' => "I",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'Ł' => "L",
+        'ł' => "l",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ř' => "R",
+        'ř' => "r",
+        'Ś' | 'Š' | 'Ş' => "S",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'Ť' => "T",
+        'ť' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' => "U",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'Ý' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+/// Whether `var` gets a missing-reason column: every numeric variable
+/// (which can be system-missing), and string variables with user-missing
+/// values.
+pub fn has_missing_reason(var: &VariableRecord, metadata: &SpssMetadata) -> bool {
+    match var.var_type {
+        VarType::Numeric => true,
+        VarType::String(_) => metadata
+            .variable_missing
+            .get(&var.long_name)
+            .is_some_and(|specs| !specs.is_empty()),
+    }
+}
+
+/// Whether `var` is returned through its value labels under `options`:
+/// a non-temporal numeric variable with at least one label, when a
+/// `LabelMode` other than `Codes` is set.
+pub fn uses_labels(var: &VariableRecord, metadata: &SpssMetadata, options: &OutputOptions) -> bool {
+    options.labels != LabelMode::Codes
+        && var_to_arrow_type(var) == DataType::Float64
+        && metadata
+            .variable_value_labels
+            .get(&var.long_name)
+            .is_some_and(|labels| !labels.is_empty())
+}
+
+/// Arrow DataType of a numeric variable with the given temporal category.
+fn numeric_arrow_type(temporal: Option<TemporalKind>) -> DataType {
+    match temporal {
+        Some(TemporalKind::Date) => DataType::Date32,
+        Some(TemporalKind::Timestamp) => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Some(TemporalKind::Duration) => DataType::Duration(TimeUnit::Microsecond),
+        None => DataType::Float64,
     }
 }
 
@@ -35,3 +319,99 @@ pub fn build_schema(dict: &ResolvedDictionary) -> Schema {
 
     Schema::new(fields)
 }
+
+/// Build the Arrow Schema a file with this metadata would be read as, using
+/// each variable's format string. Variables without a format are numeric.
+pub fn metadata_to_schema(metadata: &SpssMetadata) -> Schema {
+    let fields: Vec<Field> = metadata
+        .variable_names
+        .iter()
+        .map(|name| {
+            let data_type = match metadata.format(name).and_then(SpssFormat::parse) {
+                Some(f) if f.format_type.is_string() => DataType::Utf8View,
+                Some(f) => numeric_arrow_type(f.format_type.temporal_kind()),
+                None => DataType::Float64,
+            };
+            Field::new(name, data_type, true)
+        })
+        .collect();
+
+    Schema::new(fields)
+}
+
+/// The SPSS temporal category an Arrow type is written as (the inverse of
+/// `var_to_arrow_type`). Time-of-day types are written as elapsed time.
+pub fn arrow_temporal_kind(data_type: &DataType) -> Option<TemporalKind> {
+    match data_type {
+        DataType::Date32 | DataType::Date64 => Some(TemporalKind::Date),
+        DataType::Timestamp(_, _) => Some(TemporalKind::Timestamp),
+        DataType::Duration(_) | DataType::Time32(_) | DataType::Time64(_) => {
+            Some(TemporalKind::Duration)
+        }
+        _ => None,
+    }
+}
+
+/// Convert a numeric or temporal Arrow array to the doubles SPSS stores.
+///
+/// Dates and timestamps become seconds since 1582-10-14, durations and times
+/// of day plain seconds; other types are cast to Float64. Nulls are preserved.
+/// Timestamps with a time zone are written as UTC.
+pub fn to_spss_numeric(array: &dyn Array) -> Result<Float64Array> {
+    let unit_seconds = |unit: &TimeUnit| match unit {
+        TimeUnit::Second => 1.0,
+        TimeUnit::Millisecond => 1e-3,
+        TimeUnit::Microsecond => 1e-6,
+        TimeUnit::Nanosecond => 1e-9,
+    };
+    let (scale, offset) = match array.data_type() {
+        DataType::Date32 => (SECONDS_PER_DAY, SPSS_EPOCH_OFFSET_SECONDS),
+        DataType::Date64 => (1e-3, SPSS_EPOCH_OFFSET_SECONDS),
+        DataType::Timestamp(unit, _) => (unit_seconds(unit), SPSS_EPOCH_OFFSET_SECONDS),
+        DataType::Duration(unit) | DataType::Time32(unit) | DataType::Time64(unit) => {
+            (unit_seconds(unit), 0.0)
+        }
+        _ => return Ok(cast(array, &DataType::Float64)?.as_primitive().clone()),
+    };
+    // Time32 only casts to Int32 directly
+    let ints = match array.data_type() {
+        DataType::Time32(_) => cast(&cast(array, &DataType::Int32)?, &DataType::Int64)?,
+        _ => cast(array, &DataType::Int64)?,
+    };
+    Ok(ints
+        .as_primitive::<Int64Type>()
+        .unary(|v| v as f64 * scale + offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Date32Array, DurationMillisecondArray, TimestampSecondArray};
+
+    use super::*;
+
+    #[test]
+    fn test_to_spss_numeric() {
+        // 1970-01-01 is 12,219,379,200 seconds after the SPSS epoch
+        let dates = Date32Array::from(vec![Some(0), Some(1), None]);
+        let secs = to_spss_numeric(&dates).unwrap();
+        assert_eq!(secs.value(0), SPSS_EPOCH_OFFSET_SECONDS);
+        assert_eq!(secs.value(1), SPSS_EPOCH_OFFSET_SECONDS + SECONDS_PER_DAY);
+        assert!(secs.is_null(2));
+
+        let ts = TimestampSecondArray::from(vec![3600]).with_timezone("+02:00");
+        let secs = to_spss_numeric(&ts).unwrap();
+        assert_eq!(secs.value(0), SPSS_EPOCH_OFFSET_SECONDS + 3600.0);
+
+        let dur = DurationMillisecondArray::from(vec![1500]);
+        assert_eq!(to_spss_numeric(&dur).unwrap().value(0), 1.5);
+    }
+
+    #[test]
+    fn test_sanitized_names() {
+        let names = sanitized_names(["Größe", "q 1", "Q_1", "1st", "$@#", "Côté.2", "q_1"]);
+        assert_eq!(
+            names,
+            ["Grosse", "q_1", "Q_1_2", "_1st", "column", "Cote_2", "q_1_3"]
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Async streaming reader for tokio (feature `tokio`).
+//!
+//! `AsyncSavScanner` performs all I/O through `AsyncRead + AsyncSeek` and
+//! hands the bytes to the regular synchronous parser, so decoding behaves
+//! exactly like `SavScanner`:
+//!
+//! - Uncompressed files are fetched one batch at a time; only the current
+//!   batch's cases are held in memory.
+//! - .zsav files are fetched a few compressed blocks ahead of the decoder,
+//!   plus the block trailer at the end of the file.
+//! - Bytecode-compressed .sav files are fetched completely on open, as
+//!   `SavScanner` reads them into memory.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use arrow::datatypes::Schema;
+use arrow::record_batch::RecordBatch;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::constants::Compression;
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::io_utils::SavReader;
+use crate::metadata::SpssMetadata;
+use crate::scanner::SavScanner;
+
+/// Size of the fixed file header record.
+const HEADER_LEN: usize = 176;
+
+/// Initial prefix fetched when looking for the end of the dictionary.
+const DICTIONARY_PREFETCH: usize = 64 * 1024;
+
+/// Largest single read from the source.
+const MAX_FETCH: u64 = 64 * 1024 * 1024;
+
+/// The bytes fetched so far, exposed to the synchronous parser as a
+/// `Read + Seek` view of the file.
+#[derive(Default)]
+struct Window {
+    /// File offset of `data[0]`.
+    base: u64,
+    /// Bytes fetched sequentially from the front of the file.
+    data: Vec<u8>,
+    /// File offset of `tail[0]`.
+    tail_base: u64,
+    /// Bytes fetched from the end of the file (the .zsav trailer).
+    tail: Vec<u8>,
+    file_len: u64,
+    /// Read position (absolute file offset).
+    pos: u64,
+    /// Offset of the first read that fell outside the fetched bytes.
+    miss: Option<u64>,
+}
+
+#[derive(Clone, Default)]
+struct SharedWindow(Arc<Mutex<Window>>);
+
+impl SharedWindow {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Read for SharedWindow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut w = self.lock();
+        let pos = w.pos;
+        let data_end = w.base + w.data.len() as u64;
+        let tail_end = w.tail_base + w.tail.len() as u64;
+        let (bytes, start) = if pos >= w.base && pos < data_end {
+            (&w.data, (pos - w.base) as usize)
+        } else if pos >= w.tail_base && pos < tail_end {
+            (&w.tail, (pos - w.tail_base) as usize)
+        } else if pos >= w.file_len {
+            return Ok(0);
+        } else if pos < w.base {
+            return Err(io::Error::other(
+                "async scanner: read before fetched window",
+            ));
+        } else {
+            w.miss.get_or_insert(pos);
+            return Ok(0);
+        };
+        let n = buf.len().min(bytes.len() - start);
+        buf[..n].copy_from_slice(&bytes[start..start + n]);
+        w.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SharedWindow {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let mut w = self.lock();
+        let new = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::Current(d) => w.pos as i64 + d,
+            SeekFrom::End(d) => w.file_len as i64 + d,
+        };
+        if new < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before start of file",
+            ));
+        }
+        w.pos = new as u64;
+        Ok(w.pos)
+    }
+}
+
+/// An async streaming reader for SPSS .sav/.zsav files.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> ambers::error::Result<()> {
+/// let file = tokio::fs::File::open("survey.sav").await?;
+/// let mut scanner = ambers::AsyncSavScanner::open(file, 100_000).await?;
+/// scanner.select(&["age", "gender"])?;
+/// while let Some(batch) = scanner.next_batch().await? {
+///     println!("Batch: {} rows", batch.num_rows());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncSavScanner<R> {
+    source: R,
+    /// File offset of the next byte to fetch from `source`.
+    fetched_to: u64,
+    window: SharedWindow,
+    inner: SavScanner<SharedWindow>,
+    compression: Compression,
+    /// Bytes per case.
+    row_bytes: usize,
+    batch_size: usize,
+}
+
+impl<R> AsyncSavScanner<R> {
+    /// Record where the data came from (see `SavScanner::set_source_path`).
+    #[cfg_attr(not(feature = "object_store"), allow(dead_code))]
+    pub(crate) fn set_source_path(&mut self, path: String) {
+        self.inner.set_source_path(path);
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> AsyncSavScanner<R> {
+    /// Open a scanner, reading the header and dictionary.
+    pub async fn open(mut source: R, batch_size: usize) -> Result<Self> {
+        let window = SharedWindow::default();
+        window.lock().file_len = source.seek(SeekFrom::End(0)).await?;
+        source.seek(SeekFrom::Start(0)).await?;
+        let mut fetched_to = 0u64;
+
+        // The header tells us how the data is laid out.
+        fetch_to(&mut source, &window, &mut fetched_to, HEADER_LEN as u64).await?;
+        let header = {
+            let mut reader = SavReader::new(window.clone());
+            FileHeader::parse(&mut reader)?
+        };
+        if header.compression == Compression::Bytecode {
+            // SavScanner decodes bytecode data from memory.
+            fetch_to(&mut source, &window, &mut fetched_to, u64::MAX).await?;
+        }
+
+        // Fetch more until the whole dictionary (and, for .zsav, the block
+        // trailer) parses.
+        let mut want = DICTIONARY_PREFETCH as u64;
+        let inner = loop {
+            {
+                let mut w = window.lock();
+                w.pos = 0;
+                w.miss = None;
+            }
+            let result = SavScanner::open(window.clone(), batch_size);
+            let miss = window.lock().miss;
+            match (result, miss) {
+                (Ok(scanner), None) => break scanner,
+                (_, Some(at)) if at > fetched_to => {
+                    fetch_tail(&mut source, &window, fetched_to, at).await?;
+                }
+                (_, Some(_)) => {
+                    let target = fetched_to + want;
+                    fetch_to(&mut source, &window, &mut fetched_to, target).await?;
+                    want *= 2;
+                }
+                (Err(e), None) => return Err(e),
+            }
+        };
+
+        let mut scanner = AsyncSavScanner {
+            source,
+            fetched_to,
+            window,
+            inner,
+            compression: header.compression,
+            row_bytes: header.nominal_case_size.max(0) as usize * 8,
+            batch_size,
+        };
+        scanner.trim_window();
+        Ok(scanner)
+    }
+
+    /// Get a reference to the file metadata.
+    pub fn metadata(&self) -> &SpssMetadata {
+        self.inner.metadata()
+    }
+
+    /// Get the Arrow schema (respects column projection if set).
+    pub fn schema(&self) -> Schema {
+        self.inner.schema()
+    }
+
+    /// Set column projection — only these columns will be read and returned.
+    pub fn select(&mut self, columns: &[&str]) -> Result<()> {
+        self.inner.select(columns)
+    }
+
+    /// Set column projection, matching names case-insensitively. Returns the
+    /// canonical names the columns resolved to.
+    pub fn select_ci(&mut self, columns: &[&str]) -> Result<Vec<String>> {
+        self.inner.select_ci(columns)
+    }
+
+    /// Set column projection by position (0-based, in dictionary order).
+    pub fn select_by_index(&mut self, indices: &[usize]) -> Result<()> {
+        self.inner.select_by_index(indices)
+    }
+
+    /// Set column projection to every column whose name matches `pattern`.
+    pub fn select_matching(&mut self, pattern: &str) -> Result<()> {
+        self.inner.select_matching(pattern)
+    }
+
+    /// Set a row limit — stop reading after this many rows.
+    pub fn limit(&mut self, n: usize) {
+        self.inner.limit(n);
+    }
+
+    /// Discard the next `n` rows of the file without building them.
+    ///
+    /// Uncompressed files seek past the rows without fetching them;
+    /// compressed files fetch and step the decoder over them.
+    pub async fn skip(&mut self, n: usize) -> Result<()> {
+        if self.compression != Compression::None {
+            self.prefetch(n).await?;
+            self.inner.skip(n)?;
+            return self.finish_read();
+        }
+        self.inner.skip(n)?;
+        let pos = self.window.lock().pos;
+        if pos > self.fetched_to {
+            self.source.seek(SeekFrom::Start(pos)).await?;
+            self.fetched_to = pos;
+            let mut w = self.window.lock();
+            w.data.clear();
+            w.base = pos;
+        }
+        self.trim_window();
+        Ok(())
+    }
+
+    /// How many rows have been read so far.
+    pub fn rows_read(&self) -> usize {
+        self.inner.rows_read()
+    }
+
+    /// Read the next batch of rows. Returns `Ok(None)` at the end of the data.
+    pub async fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        self.prefetch(self.batch_size).await?;
+        let batch = self.inner.next_batch()?;
+        self.finish_read()?;
+        Ok(batch)
+    }
+
+    /// Read all remaining data as a single RecordBatch.
+    pub async fn collect_single(&mut self) -> Result<RecordBatch> {
+        self.prefetch(usize::MAX).await?;
+        let batch = self.inner.collect_single()?;
+        self.finish_read()?;
+        Ok(batch)
+    }
+
+    /// Read all remaining data as a Vec of RecordBatches.
+    pub async fn collect_all(&mut self) -> Result<Vec<RecordBatch>> {
+        let mut batches = Vec::new();
+        while let Some(batch) = self.next_batch().await? {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// Fetch everything the parser may read while decoding `rows` rows.
+    async fn prefetch(&mut self, rows: usize) -> Result<()> {
+        let target = match self.compression {
+            Compression::None => {
+                let pos = self.window.lock().pos;
+                pos.saturating_add(rows.saturating_mul(self.row_bytes) as u64)
+            }
+            Compression::Zlib => self.inner.compressed_range(rows).map_or(0, |r| r.end),
+            Compression::Bytecode => return Ok(()),
+        };
+        fetch_to(&mut self.source, &self.window, &mut self.fetched_to, target).await
+    }
+
+    /// Reject a read that ran past the prefetched bytes (rather than
+    /// returning a silently truncated batch), then trim the window.
+    fn finish_read(&mut self) -> Result<()> {
+        if let Some(at) = self.window.lock().miss.take() {
+            return Err(SpssError::Io(io::Error::other(format!(
+                "async scanner: data at offset {at} was read before it was fetched"
+            ))));
+        }
+        self.trim_window();
+        Ok(())
+    }
+
+    /// Drop fetched bytes the parser no longer needs.
+    fn trim_window(&mut self) {
+        let mut w = self.window.lock();
+        match self.compression {
+            // The parser copied the whole bytecode stream on open.
+            Compression::Bytecode => {
+                w.data = Vec::new();
+                w.base = self.fetched_to;
+            }
+            // Cases and .zsav blocks are read front to back.
+            Compression::None | Compression::Zlib => {
+                let keep_from = match self.inner.compressed_range(0) {
+                    Some(range) => range.start,
+                    None => w.pos,
+                };
+                let consumed = (keep_from.saturating_sub(w.base) as usize).min(w.data.len());
+                w.data.drain(..consumed);
+                w.base += consumed as u64;
+            }
+        }
+    }
+}
+
+/// Append bytes from `source` to the window until `target` (a file offset)
+/// or the end of the source is reached.
+async fn fetch_to<R: AsyncRead + Unpin>(
+    source: &mut R,
+    window: &SharedWindow,
+    fetched_to: &mut u64,
+    target: u64,
+) -> Result<()> {
+    let target = target.min(window.lock().file_len);
+    while *fetched_to < target {
+        let mut buf = vec![0u8; (target - *fetched_to).min(MAX_FETCH) as usize];
+        let mut filled = 0;
+        while filled < buf.len() {
+            let read = source.read(&mut buf[filled..]).await?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        buf.truncate(filled);
+        *fetched_to += filled as u64;
+        window.lock().data.extend_from_slice(&buf);
+        if filled == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Fetch everything from `at` to the end of the file into the window's
+/// tail, leaving `source` positioned at `resume`.
+async fn fetch_tail<R: AsyncRead + AsyncSeek + Unpin>(
+    source: &mut R,
+    window: &SharedWindow,
+    resume: u64,
+    at: u64,
+) -> Result<()> {
+    source.seek(SeekFrom::Start(at)).await?;
+    let mut tail = Vec::new();
+    source.read_to_end(&mut tail).await?;
+    source.seek(SeekFrom::Start(resume)).await?;
+    let mut w = window.lock();
+    w.tail_base = at;
+    w.tail = tail;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+
+    use super::*;
+    use crate::test_util;
+
+    fn sav_bytes(compression: Compression, n: usize) -> Vec<u8> {
+        test_util::sav_bytes(
+            vec![
+                (
+                    "id",
+                    Arc::new(Float64Array::from_iter_values((0..n).map(|i| i as f64))),
+                ),
+                (
+                    "text",
+                    Arc::new(StringArray::from_iter_values(
+                        (0..n).map(|i| "y".repeat(i % 300)),
+                    )),
+                ),
+            ],
+            compression,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_async_matches_sync() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let bytes = sav_bytes(compression, 500);
+            let expected = SavScanner::open(Cursor::new(bytes.clone()), usize::MAX)
+                .unwrap()
+                .collect_single()
+                .unwrap();
+
+            let mut scanner = AsyncSavScanner::open(Cursor::new(bytes), 64).await.unwrap();
+            assert_eq!(scanner.schema(), *expected.schema());
+            let batches = scanner.collect_all().await.unwrap();
+            assert_eq!(batches.len(), 8);
+            let all = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+            assert_eq!(all, expected);
+            assert_eq!(scanner.rows_read(), 500);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_file_streams_uncompressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.sav");
+        let bytes = sav_bytes(Compression::None, 1000);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let mut scanner = AsyncSavScanner::open(file, 100).await.unwrap();
+        scanner.select(&["id"]).unwrap();
+        scanner.limit(250);
+
+        let first = scanner.next_batch().await.unwrap().unwrap();
+        assert_eq!(first.num_rows(), 100);
+        // The file is fetched batch by batch, not up front.
+        assert!(scanner.fetched_to < bytes.len() as u64);
+        assert!(scanner.window.lock().data.len() < bytes.len() / 2);
+
+        let rest = scanner.collect_single().await.unwrap();
+        assert_eq!(rest.num_rows(), 150);
+        let ids = rest
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 100.0);
+        assert!(scanner.next_batch().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_skip() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let bytes = sav_bytes(compression, 500);
+            let mut scanner = AsyncSavScanner::open(Cursor::new(bytes), 64).await.unwrap();
+            scanner.skip(450).await.unwrap();
+            if compression == Compression::None {
+                // The skipped cases are never fetched.
+                assert!(scanner.window.lock().data.is_empty());
+            }
+            let rest = scanner.collect_single().await.unwrap();
+            assert_eq!(rest.num_rows(), 50);
+            let ids = rest
+                .column(0)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap();
+            assert_eq!(ids.value(0), 450.0);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! C API over the Arrow C Data and C Stream Interfaces.
+//!
+//! Lets a C or C++ host — a DuckDB extension, another query engine — read
+//! .sav/.zsav files without Rust bindings. Data crosses the boundary as
+//! Arrow C structs, so the host imports it with its own Arrow library.
+//!
+//! The `ambers-capi` crate builds this module as a shared and static
+//! library:
+//!
+//! ```text
+//! cargo build --release -p ambers-capi
+//! ```
+//!
+//! Include `include/ambers.h` and link with `-lambers_capi`. A typical
+//! session:
+//!
+//! ```text
+//! AmbersScanner *s = ambers_open("survey.sav");
+//! if (!s) { fprintf(stderr, "%s\n", ambers_last_error()); return; }
+//! struct ArrowSchema schema;
+//! ambers_schema(s, &schema);
+//! struct ArrowArray chunk;
+//! while (ambers_next(s, &chunk) == 1) { ...; chunk.release(&chunk); }
+//! schema.release(&schema);
+//! ambers_close(s);
+//! ```
+//!
+//! Functions returning `int` return a negative value on error; the message
+//! is then available from `ambers_last_error` on the same thread.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::fs::File;
+use std::io::BufReader;
+use std::ptr;
+
+use arrow::array::{Array, StructArray};
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
+
+use crate::error::{Result, SpssError};
+use crate::scanner::SavScanner;
+
+/// Opaque scanner handle returned by `ambers_open`.
+pub struct AmbersScanner {
+    scanner: SavScanner<BufReader<File>>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl std::fmt::Display) {
+    let message = message.to_string().replace('\0', " ");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(message).ok());
+}
+
+/// Run `f`, turning an error into `-1` and a stored message.
+fn status(f: impl FnOnce() -> Result<c_int>) -> c_int {
+    match f() {
+        Ok(code) => code,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Borrow a C string argument as UTF-8.
+unsafe fn c_str<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(SpssError::Unsupported("null string argument".to_string()));
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| SpssError::Unsupported("string argument is not UTF-8".to_string()))
+}
+
+unsafe fn handle<'a>(scanner: *mut AmbersScanner) -> Result<&'a mut AmbersScanner> {
+    unsafe { scanner.as_mut() }
+        .ok_or_else(|| SpssError::Unsupported("null scanner handle".to_string()))
+}
+
+/// Message of the last error on this thread, or null if there was none.
+///
+/// The pointer stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn ambers_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Open a .sav or .zsav file. Returns null on error.
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_open(path: *const c_char) -> *mut AmbersScanner {
+    let opened = unsafe { c_str(path) }.and_then(crate::scan_sav);
+    match opened {
+        Ok(scanner) => Box::into_raw(Box::new(AmbersScanner { scanner })),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Only read the `n` named columns, in that order. Returns 0 on success.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open`; `names` must point to `n` valid
+/// NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_select(
+    scanner: *mut AmbersScanner,
+    names: *const *const c_char,
+    n: usize,
+) -> c_int {
+    status(|| {
+        let handle = unsafe { handle(scanner) }?;
+        let names = if n == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(names, n) }
+        };
+        let columns = names
+            .iter()
+            .map(|&name| unsafe { c_str(name) })
+            .collect::<Result<Vec<_>>>()?;
+        handle.scanner.select(&columns)?;
+        Ok(0)
+    })
+}
+
+/// Stop after `n` rows. Returns 0 on success.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_limit(scanner: *mut AmbersScanner, n: u64) -> c_int {
+    status(|| {
+        unsafe { handle(scanner) }?.scanner.limit(n as usize);
+        Ok(0)
+    })
+}
+
+/// Export the output schema (after any `ambers_select`) as a struct-typed
+/// `ArrowSchema`. Returns 0 on success; the caller releases `out`.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open`; `out` must point to writable
+/// memory for an `ArrowSchema`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_schema(
+    scanner: *mut AmbersScanner,
+    out: *mut FFI_ArrowSchema,
+) -> c_int {
+    status(|| {
+        let schema = unsafe { handle(scanner) }?.scanner.schema();
+        let ffi = FFI_ArrowSchema::try_from(&schema)?;
+        unsafe { ptr::write(out, ffi) };
+        Ok(0)
+    })
+}
+
+/// Read the next chunk as a struct-typed `ArrowArray` matching
+/// `ambers_schema`. Returns 1 when a chunk was written to `out`, 0 at the
+/// end of the data and -1 on error. The caller releases `out`.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open`; `out` must point to writable
+/// memory for an `ArrowArray`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_next(
+    scanner: *mut AmbersScanner,
+    out: *mut FFI_ArrowArray,
+) -> c_int {
+    status(|| {
+        let Some(batch) = unsafe { handle(scanner) }?.scanner.next_batch()? else {
+            return Ok(0);
+        };
+        let array = StructArray::from(batch);
+        unsafe { ptr::write(out, FFI_ArrowArray::new(&array.to_data())) };
+        Ok(1)
+    })
+}
+
+/// Hand the scanner over as an `ArrowArrayStream`, the form most engines
+/// import directly. Returns 0 on success. The scanner is consumed either
+/// way and must not be used or closed afterwards; the caller releases
+/// `out`.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open`; `out` must point to writable
+/// memory for an `ArrowArrayStream`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_stream(
+    scanner: *mut AmbersScanner,
+    out: *mut FFI_ArrowArrayStream,
+) -> c_int {
+    status(|| {
+        unsafe { handle(scanner) }?;
+        let handle = unsafe { Box::from_raw(scanner) };
+        let reader = StreamReader {
+            schema: handle.scanner.schema().into(),
+            scanner: handle.scanner,
+        };
+        unsafe { ptr::write(out, FFI_ArrowArrayStream::new(Box::new(reader))) };
+        Ok(0)
+    })
+}
+
+/// Close a scanner and free its resources. Null is ignored.
+///
+/// # Safety
+/// `scanner` must come from `ambers_open` and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ambers_close(scanner: *mut AmbersScanner) {
+    if !scanner.is_null() {
+        drop(unsafe { Box::from_raw(scanner) });
+    }
+}
+
+struct StreamReader {
+    scanner: SavScanner<BufReader<File>>,
+    schema: SchemaRef,
+}
+
+impl Iterator for StreamReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scanner
+            .next_batch()
+            .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+            .transpose()
+    }
+}
+
+impl RecordBatchReader for StreamReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::ffi::from_ffi;
+    use arrow::ffi_stream::ArrowArrayStreamReader;
+
+    use super::*;
+    use crate::metadata::SpssMetadata;
+
+    fn write_sample(dir: &std::path::Path) -> CString {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("age", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![15.0, 42.0, 67.0])),
+                Arc::new(StringArray::from(vec!["Oslo", "Rome", "Lima"])),
+            ],
+        )
+        .unwrap();
+        let path = dir.join("people.sav");
+        crate::write_sav(&path, &batch, &SpssMetadata::default()).unwrap();
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_capi_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(dir.path());
+        unsafe {
+            let s = ambers_open(path.as_ptr());
+            assert!(!s.is_null());
+            let city = CString::new("city").unwrap();
+            assert_eq!(ambers_select(s, [city.as_ptr()].as_ptr(), 1), 0);
+            assert_eq!(ambers_limit(s, 2), 0);
+
+            let mut schema = FFI_ArrowSchema::empty();
+            assert_eq!(ambers_schema(s, &mut schema), 0);
+            let mut rows = 0;
+            loop {
+                let mut array = FFI_ArrowArray::empty();
+                match ambers_next(s, &mut array) {
+                    1 => {
+                        let data = from_ffi(array, &schema).unwrap();
+                        let chunk = StructArray::from(data);
+                        assert_eq!(chunk.num_columns(), 1);
+                        rows += chunk.len();
+                    }
+                    0 => break,
+                    code => panic!("ambers_next returned {code}"),
+                }
+            }
+            assert_eq!(rows, 2);
+            ambers_close(s);
+        }
+    }
+
+    #[test]
+    fn test_capi_stream_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_sample(dir.path());
+        unsafe {
+            let s = ambers_open(path.as_ptr());
+            let mut stream = FFI_ArrowArrayStream::empty();
+            assert_eq!(ambers_stream(s, &mut stream), 0);
+            let reader = ArrowArrayStreamReader::try_new(stream).unwrap();
+            assert_eq!(reader.schema().fields().len(), 2);
+            let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+            assert_eq!(rows, 3);
+
+            let missing = CString::new(dir.path().join("nope.sav").to_str().unwrap()).unwrap();
+            assert!(ambers_open(missing.as_ptr()).is_null());
+            assert!(!ambers_last_error().is_null());
+            let s = ambers_open(path.as_ptr());
+            let bad = CString::new("nope").unwrap();
+            assert_eq!(ambers_select(s, [bad.as_ptr()].as_ptr(), 1), -1);
+            let message = CStr::from_ptr(ambers_last_error()).to_str().unwrap();
+            assert!(message.contains("nope"), "{message}");
+            ambers_close(s);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Codebooks: the full dictionary of a file as a document.
+//!
+//! - [`to_codebook_markdown`] / [`to_codebook_html`] — one section per
+//!   variable with its properties and value table, then the multiple
+//!   response sets. Pass [`Frequencies`] from [`frequencies`] to add counts
+//!   and percentages to the value tables.
+//! - `to_codebook_xlsx` / `write_codebook` (feature `xlsx`) — an Excel
+//!   workbook with one sheet each for variables, value labels and MR sets.
+
+mod render;
+#[cfg(feature = "xlsx")]
+mod xlsx;
+
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use arrow::array::{Array, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type};
+use indexmap::IndexMap;
+
+use crate::constants::SpssFormat;
+use crate::error::Result;
+use crate::metadata::{MissingSpec, Value};
+use crate::scanner::SavScanner;
+
+pub use render::{to_codebook_html, to_codebook_markdown};
+#[cfg(feature = "xlsx")]
+pub use xlsx::{to_codebook_xlsx, write_codebook};
+
+/// Variables with more distinct values than this are not tabulated (IDs,
+/// free text, continuous measures).
+pub const MAX_DISTINCT: usize = 200;
+
+/// Value counts per variable, from [`frequencies`].
+#[derive(Debug, Clone, Default)]
+pub struct Frequencies {
+    /// Rows counted.
+    pub rows: usize,
+    /// {column name -> counts}, for the numeric and string columns with at
+    /// most `MAX_DISTINCT` distinct values.
+    pub variables: IndexMap<String, ValueCounts>,
+}
+
+/// How often each value of one variable occurs.
+#[derive(Debug, Clone, Default)]
+pub struct ValueCounts {
+    /// {value -> rows}, in ascending value order.
+    pub counts: IndexMap<Value, usize>,
+    /// Rows with no value (system-missing, or null under the scan options).
+    pub nulls: usize,
+}
+
+/// Count the values of every column of the scanner's remaining rows.
+///
+/// Uses the scanner's options, so values are counted as the batches hold
+/// them: with `MissingPolicy::Null`, user-missing values count as nulls.
+/// Columns of other types (dates, labels, Booleans) are skipped.
+///
+/// # Example
+/// ```no_run
+/// use ambers::codebook;
+///
+/// let mut scanner = ambers::scan_sav("survey.sav").unwrap();
+/// let freqs = codebook::frequencies(&mut scanner).unwrap();
+/// let md = codebook::to_codebook_markdown(scanner.metadata(), Some(&freqs));
+/// ```
+pub fn frequencies<R: Read + Seek>(scanner: &mut SavScanner<R>) -> Result<Frequencies> {
+    // Keyed by bit pattern (+0.0 for -0.0) while counting.
+    enum Counter {
+        Numeric(HashMap<u64, usize>),
+        String(HashMap<String, usize>),
+        TooMany,
+    }
+
+    let mut rows = 0;
+    let mut counters: IndexMap<String, (Counter, usize)> = IndexMap::new();
+    while let Some(batch) = scanner.next_batch()? {
+        rows += batch.num_rows();
+        for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+            if !counters.contains_key(field.name()) {
+                let counter = match field.data_type() {
+                    DataType::Float64 => Counter::Numeric(HashMap::new()),
+                    DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+                        Counter::String(HashMap::new())
+                    }
+                    _ => continue,
+                };
+                counters.insert(field.name().clone(), (counter, 0));
+            }
+            let (counter, nulls) = &mut counters[field.name()];
+            *nulls += column.null_count();
+            match counter {
+                Counter::Numeric(counts) => {
+                    for v in column.as_primitive::<Float64Type>().iter().flatten() {
+                        *counts.entry((v + 0.0).to_bits()).or_default() += 1;
+                    }
+                    if counts.len() > MAX_DISTINCT {
+                        *counter = Counter::TooMany;
+                    }
+                }
+                Counter::String(counts) => {
+                    let strings = cast(column, &DataType::Utf8View)?;
+                    for v in strings.as_string_view().iter().flatten() {
+                        match counts.get_mut(v) {
+                            Some(n) => *n += 1,
+                            None => {
+                                counts.insert(v.to_string(), 1);
+                            }
+                        }
+                    }
+                    if counts.len() > MAX_DISTINCT {
+                        *counter = Counter::TooMany;
+                    }
+                }
+                Counter::TooMany => {}
+            }
+        }
+    }
+
+    let variables = counters
+        .into_iter()
+        .filter_map(|(name, (counter, nulls))| {
+            let mut counts: Vec<(Value, usize)> = match counter {
+                Counter::Numeric(counts) => counts
+                    .into_iter()
+                    .map(|(bits, n)| (Value::Numeric(f64::from_bits(bits)), n))
+                    .collect(),
+                Counter::String(counts) => counts
+                    .into_iter()
+                    .map(|(s, n)| (Value::String(s), n))
+                    .collect(),
+                Counter::TooMany => return None,
+            };
+            counts.sort_by(|(a, _), (b, _)| match (a, b) {
+                (Value::Numeric(a), Value::Numeric(b)) => a.total_cmp(b),
+                (Value::String(a), Value::String(b)) => a.cmp(b),
+                _ => std::cmp::Ordering::Equal,
+            });
+            let counts = counts.into_iter().collect();
+            Some((name, ValueCounts { counts, nulls }))
+        })
+        .collect();
+    Ok(Frequencies { rows, variables })
+}
+
+/// Broad variable type shown in codebooks: numeric, string or date/time.
+fn variable_kind(format: &str) -> &'static str {
+    match SpssFormat::parse(format) {
+        Some(f) if f.format_type.is_string() => "string",
+        Some(f) if f.format_type.is_date_time() => "date/time",
+        _ => "numeric",
+    }
+}
+
+/// Missing values in SPSS syntax style, e.g. `9, 97 thru 99`.
+fn missing_text(specs: &[MissingSpec]) -> String {
+    let bound = |v: f64| {
+        if v <= -f64::MAX {
+            "LOWEST".to_string()
+        } else if v >= f64::MAX {
+            "HIGHEST".to_string()
+        } else {
+            Value::Numeric(v).to_string()
+        }
+    };
+    specs
+        .iter()
+        .map(|spec| match spec {
+            MissingSpec::Value(v) => bound(*v),
+            MissingSpec::Range { lo, hi } => format!("{} thru {}", bound(*lo), bound(*hi)),
+            MissingSpec::StringValue(s) => format!("\"{}\"", s.trim_end()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+    use crate::metadata::SpssMetadata;
+    use crate::writer::{WriteOptions, write_batch};
+
+    #[test]
+    fn test_frequencies() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(2.0),
+                    Some(1.0),
+                    None,
+                    Some(2.0),
+                ])),
+                Arc::new(StringArray::from(vec!["NY", "LA", "NY", "NY"])),
+            ],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        write_batch(
+            &mut buf,
+            &batch,
+            &SpssMetadata::default(),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+
+        let mut scanner = SavScanner::open(Cursor::new(buf), 2).unwrap();
+        let freqs = frequencies(&mut scanner).unwrap();
+        assert_eq!(freqs.rows, 4);
+        let q1 = &freqs.variables["q1"];
+        assert_eq!(q1.nulls, 1);
+        let counts: Vec<_> = q1.counts.iter().map(|(v, n)| (v.to_string(), *n)).collect();
+        assert_eq!(counts, [("1".to_string(), 1), ("2".to_string(), 2)]);
+        assert_eq!(
+            freqs.variables["city"].counts[&Value::String("NY".into())],
+            3
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Markdown and HTML codebooks.
+
+use super::{Frequencies, ValueCounts, missing_text, variable_kind};
+use crate::metadata::{MrType, SpssMetadata, Value};
+
+/// Render the codebook as Markdown.
+///
+/// One `##` section per variable with a property table (label, type,
+/// format, measure, role, missing values) and, for variables with value
+/// labels or counted values, a value table. With `freqs`, value tables
+/// get Count and Percent columns and a row for nulls.
+pub fn to_codebook_markdown(meta: &SpssMetadata, freqs: Option<&Frequencies>) -> String {
+    let mut out = format!("# {}\n\n{}\n", title(meta), overview(meta, freqs));
+    for name in &meta.variable_names {
+        out.push_str(&format!("\n## {}\n\n", md(name)));
+        out.push_str("| Property | Value |\n|---|---|\n");
+        for (key, value) in properties(meta, name) {
+            out.push_str(&format!("| {key} | {} |\n", md(&value)));
+        }
+        let rows = value_rows(meta, name, freqs);
+        if !rows.is_empty() {
+            out.push('\n');
+            let (header, rule) = if freqs.is_some() {
+                (
+                    "| Value | Label | Count | Percent |",
+                    "|---:|---|---:|---:|",
+                )
+            } else {
+                ("| Value | Label |", "|---:|---|")
+            };
+            out.push_str(&format!("{header}\n{rule}\n"));
+            for row in rows {
+                out.push_str(&format!("| {} | {} |", md(&row.value), md(&row.label)));
+                if let Some((count, percent)) = row.count {
+                    out.push_str(&format!(" {count} | {percent} |"));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    if !meta.mr_sets.is_empty() {
+        out.push_str("\n## Multiple response sets\n\n");
+        out.push_str("| Name | Label | Type | Variables |\n|---|---|---|---|\n");
+        for set in meta.mr_sets.values() {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                md(&set.name),
+                md(&set.label),
+                md(&mr_type(set.mr_type.clone(), set.counted_value.as_deref())),
+                md(&set.variables.join(", "))
+            ));
+        }
+    }
+    out
+}
+
+/// Render the codebook as a standalone HTML document, with the same
+/// content as [`to_codebook_markdown`]. Variable sections are anchored by
+/// name (`#q1`).
+pub fn to_codebook_html(meta: &SpssMetadata, freqs: Option<&Frequencies>) -> String {
+    let title = html(&title(meta));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\nbody {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 2px 8px; text-align: left; }}\n\
+         td.n {{ text-align: right; }}\n</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p>{}</p>\n",
+        html(&overview(meta, freqs))
+    );
+    for name in &meta.variable_names {
+        let id = html(name);
+        out.push_str(&format!("<h2 id=\"{id}\">{id}</h2>\n<table>\n"));
+        for (key, value) in properties(meta, name) {
+            out.push_str(&format!(
+                "<tr><th>{key}</th><td>{}</td></tr>\n",
+                html(&value)
+            ));
+        }
+        out.push_str("</table>\n");
+        let rows = value_rows(meta, name, freqs);
+        if !rows.is_empty() {
+            out.push_str("<table>\n<tr><th>Value</th><th>Label</th>");
+            if freqs.is_some() {
+                out.push_str("<th>Count</th><th>Percent</th>");
+            }
+            out.push_str("</tr>\n");
+            for row in rows {
+                out.push_str(&format!(
+                    "<tr><td class=\"n\">{}</td><td>{}</td>",
+                    html(&row.value),
+                    html(&row.label)
+                ));
+                if let Some((count, percent)) = row.count {
+                    out.push_str(&format!(
+                        "<td class=\"n\">{count}</td><td class=\"n\">{percent}</td>"
+                    ));
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+    }
+    if !meta.mr_sets.is_empty() {
+        out.push_str("<h2>Multiple response sets</h2>\n<table>\n");
+        out.push_str("<tr><th>Name</th><th>Label</th><th>Type</th><th>Variables</th></tr>\n");
+        for set in meta.mr_sets.values() {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                html(&set.name),
+                html(&set.label),
+                html(&mr_type(set.mr_type.clone(), set.counted_value.as_deref())),
+                html(&set.variables.join(", "))
+            ));
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// One line of a variable's value table.
+struct ValueRow {
+    value: String,
+    label: String,
+    /// Count and percent of rows, when frequencies were given.
+    count: Option<(usize, String)>,
+}
+
+fn title(meta: &SpssMetadata) -> String {
+    if meta.file_label.is_empty() {
+        "Codebook".to_string()
+    } else {
+        format!("Codebook: {}", meta.file_label)
+    }
+}
+
+fn overview(meta: &SpssMetadata, freqs: Option<&Frequencies>) -> String {
+    let rows = freqs
+        .map(|f| f.rows as i64)
+        .or(meta.number_rows)
+        .map_or_else(|| "unknown".to_string(), |n| n.to_string());
+    let mut text = format!(
+        "{} variables, {rows} rows, {} file",
+        meta.variable_names.len(),
+        meta.file_format
+    );
+    if let Some(weight) = &meta.weight_variable {
+        text.push_str(&format!(", weighted by {weight}"));
+    }
+    text.push('.');
+    text
+}
+
+/// The property table of one variable, describe()-style.
+fn properties(meta: &SpssMetadata, name: &str) -> Vec<(&'static str, String)> {
+    let format = meta.format(name).unwrap_or("");
+    let mut props = vec![
+        ("Label", meta.label(name).unwrap_or("(none)").to_string()),
+        ("Type", variable_kind(format).to_string()),
+        ("Format", format.to_string()),
+        (
+            "Measure",
+            meta.measure(name).map_or("?", |m| m.as_str()).to_string(),
+        ),
+    ];
+    if let Some(role) = meta.role(name) {
+        props.push(("Role", role.as_str().to_string()));
+    }
+    let missing = meta
+        .variable_missing
+        .get(name)
+        .filter(|specs| !specs.is_empty())
+        .map_or_else(|| "(none)".to_string(), |specs| missing_text(specs));
+    props.push(("Missing", missing));
+    props
+}
+
+/// Labelled values in label order, then unlabelled values found by the
+/// frequencies, then the null count.
+fn value_rows(meta: &SpssMetadata, name: &str, freqs: Option<&Frequencies>) -> Vec<ValueRow> {
+    let counts: Option<&ValueCounts> = freqs.and_then(|f| f.variables.get(name));
+    let total = freqs.map_or(0, |f| f.rows);
+    let count_of = |n: usize| {
+        freqs.map(|_| {
+            let percent = if total > 0 {
+                format!("{:.1}%", 100.0 * n as f64 / total as f64)
+            } else {
+                String::new()
+            };
+            (n, percent)
+        })
+    };
+    let lookup = |value: &Value| counts.and_then(|c| c.counts.get(value)).copied();
+
+    let mut rows = Vec::new();
+    let labels = meta.value_labels(name);
+    for (value, label) in labels.into_iter().flatten() {
+        rows.push(ValueRow {
+            value: value.to_string(),
+            label: label.clone(),
+            count: count_of(lookup(value).unwrap_or(0)),
+        });
+    }
+    if let Some(counts) = counts {
+        for (value, &n) in &counts.counts {
+            if labels.is_some_and(|l| l.contains_key(value)) {
+                continue;
+            }
+            rows.push(ValueRow {
+                value: value.to_string(),
+                label: String::new(),
+                count: count_of(n),
+            });
+        }
+        if counts.nulls > 0 {
+            rows.push(ValueRow {
+                value: String::new(),
+                label: "(missing)".to_string(),
+                count: count_of(counts.nulls),
+            });
+        }
+    }
+    rows
+}
+
+fn mr_type(mr_type: MrType, counted_value: Option<&str>) -> String {
+    match mr_type {
+        MrType::MultipleDichotomy => {
+            format!("dichotomy (counted value {})", counted_value.unwrap_or("?"))
+        }
+        MrType::MultipleCategory => "category".to_string(),
+    }
+}
+
+/// Escape text for a Markdown table cell.
+fn md(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', " ")
+}
+
+/// Escape text for HTML element content and attribute values.
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::constants::Measure;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            file_label: "Omnibus".into(),
+            file_format: "sav".into(),
+            variable_names: vec!["q1".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car | truck".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        meta.variable_measure.insert("q1".into(), Measure::Nominal);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes".to_string()),
+                (Value::Numeric(2.0), "No".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        meta
+    }
+
+    #[test]
+    fn test_to_codebook_markdown() {
+        let meta = metadata();
+        let text = to_codebook_markdown(&meta, None);
+        assert!(text.starts_with("# Codebook: Omnibus\n\n1 variables, unknown rows, sav file.\n"));
+        assert!(text.contains("\n## q1\n"));
+        assert!(text.contains("| Label | Owns a car \\| truck |\n"));
+        assert!(text.contains("| Value | Label |\n|---:|---|\n| 1 | Yes |\n| 2 | No |\n"));
+
+        let counts: IndexMap<Value, usize> = [(Value::Numeric(1.0), 3), (Value::Numeric(7.0), 1)]
+            .into_iter()
+            .collect();
+        let freqs = Frequencies {
+            rows: 5,
+            variables: [("q1".to_string(), ValueCounts { counts, nulls: 1 })]
+                .into_iter()
+                .collect(),
+        };
+        let text = to_codebook_markdown(&meta, Some(&freqs));
+        assert!(text.contains("| 1 | Yes | 3 | 60.0% |\n| 2 | No | 0 | 0.0% |\n"));
+        assert!(text.contains("| 7 |  | 1 | 20.0% |\n|  | (missing) | 1 | 20.0% |\n"));
+    }
+
+    #[test]
+    fn test_to_codebook_html() {
+        let text = to_codebook_html(&metadata(), None);
+        assert!(text.starts_with("<!DOCTYPE html>"));
+        assert!(text.contains("<h2 id=\"q1\">q1</h2>"));
+        assert!(text.contains("<tr><th>Label</th><td>Owns a car | truck</td></tr>"));
+        assert!(text.contains("<tr><td class=\"n\">1</td><td>Yes</td></tr>"));
+        assert!(text.ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Excel codebook export.
+//!
+//! Writes an .xlsx workbook describing a file's dictionary, the form survey
+//! clients usually ask for:
+//!
+//! - **Variables** — one row per variable: position, name, label, type,
+//!   format, measurement level and missing values
+//! - **Value Labels** — one row per labelled value
+//! - **MR Sets** — one row per multiple response set
+//!
+//! Numeric codes are written as numbers so they sort and filter as such in
+//! Excel; string codes are written as text.
+
+use std::path::Path;
+
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+
+use super::{missing_text, variable_kind};
+use crate::error::Result;
+use crate::metadata::{MrType, SpssMetadata, Value};
+
+/// Render the codebook as .xlsx file contents.
+pub fn to_codebook_xlsx(meta: &SpssMetadata) -> Result<Vec<u8>> {
+    Ok(build(meta)?.save_to_buffer()?)
+}
+
+/// Write the codebook as an .xlsx file.
+///
+/// # Example
+/// ```no_run
+/// let meta = ambers::read_sav_metadata("survey.sav").unwrap();
+/// ambers::write_codebook("survey_codebook.xlsx", &meta).unwrap();
+/// ```
+pub fn write_codebook(path: impl AsRef<Path>, meta: &SpssMetadata) -> Result<()> {
+    build(meta)?.save(path.as_ref())?;
+    Ok(())
+}
+
+fn build(meta: &SpssMetadata) -> std::result::Result<Workbook, XlsxError> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold();
+
+    let sheet = workbook.add_worksheet().set_name("Variables")?;
+    write_header(
+        sheet,
+        &header,
+        &[
+            "#",
+            "Name",
+            "Label",
+            "Type",
+            "Format",
+            "Measure",
+            "Missing Values",
+        ],
+    )?;
+    for (i, name) in meta.variable_names.iter().enumerate() {
+        let row = i as u32 + 1;
+        let format = meta.format(name).unwrap_or("");
+        let kind = variable_kind(format);
+        sheet.write_number(row, 0, (i + 1) as f64)?;
+        sheet.write_string(row, 1, name)?;
+        sheet.write_string(row, 2, meta.label(name).unwrap_or(""))?;
+        sheet.write_string(row, 3, kind)?;
+        sheet.write_string(row, 4, format)?;
+        if let Some(measure) = meta.measure(name) {
+            sheet.write_string(row, 5, measure.as_str())?;
+        }
+        if let Some(specs) = meta.variable_missing.get(name) {
+            sheet.write_string(row, 6, missing_text(specs))?;
+        }
+    }
+    sheet.autofit();
+
+    let sheet = workbook.add_worksheet().set_name("Value Labels")?;
+    write_header(sheet, &header, &["Variable", "Value", "Label"])?;
+    let mut row = 1;
+    for name in &meta.variable_names {
+        let Some(labels) = meta.value_labels(name) else {
+            continue;
+        };
+        for (value, label) in labels {
+            sheet.write_string(row, 0, name)?;
+            match value {
+                Value::Numeric(v) => sheet.write_number(row, 1, *v)?,
+                Value::String(s) => sheet.write_string(row, 1, s)?,
+            };
+            sheet.write_string(row, 2, label)?;
+            row += 1;
+        }
+    }
+    sheet.autofit();
+
+    let sheet = workbook.add_worksheet().set_name("MR Sets")?;
+    write_header(
+        sheet,
+        &header,
+        &["Name", "Label", "Type", "Counted Value", "Variables"],
+    )?;
+    for (i, set) in meta.mr_sets.values().enumerate() {
+        let row = i as u32 + 1;
+        let kind = match set.mr_type {
+            MrType::MultipleDichotomy => "dichotomy",
+            MrType::MultipleCategory => "category",
+        };
+        sheet.write_string(row, 0, &set.name)?;
+        sheet.write_string(row, 1, &set.label)?;
+        sheet.write_string(row, 2, kind)?;
+        sheet.write_string(row, 3, set.counted_value.as_deref().unwrap_or(""))?;
+        sheet.write_string(row, 4, set.variables.join(", "))?;
+    }
+    sheet.autofit();
+
+    Ok(workbook)
+}
+
+/// Bold header row, frozen so it stays visible while scrolling.
+fn write_header(
+    sheet: &mut Worksheet,
+    format: &Format,
+    titles: &[&str],
+) -> std::result::Result<(), XlsxError> {
+    for (col, title) in titles.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, format)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::Measure;
+    use crate::metadata::{MissingSpec, MrSet};
+
+    #[test]
+    fn test_missing_text() {
+        let specs = [
+            MissingSpec::Range {
+                lo: 97.0,
+                hi: f64::MAX,
+            },
+            MissingSpec::Value(-1.0),
+        ];
+        assert_eq!(missing_text(&specs), "97 thru HIGHEST, -1");
+        let specs = [MissingSpec::StringValue("NA  ".into())];
+        assert_eq!(missing_text(&specs), "\"NA\"");
+    }
+
+    #[test]
+    fn test_to_codebook_xlsx() {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "q2".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        meta.spss_variable_types.insert("city".into(), "A20".into());
+        meta.variable_measure.insert("q1".into(), Measure::Nominal);
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta.mr_sets.insert(
+            "$cars".into(),
+            MrSet {
+                name: "$cars".into(),
+                label: "Cars".into(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".into()),
+                variables: vec!["q1".into(), "q2".into()],
+            },
+        );
+
+        let bytes = to_codebook_xlsx(&meta).unwrap();
+        // An .xlsx file is a zip archive.
+        assert_eq!(&bytes[..2], b"PK");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("codebook.xlsx");
+        write_codebook(&path, &meta).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+    }
+}
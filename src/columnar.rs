@@ -8,28 +8,41 @@
 //! post-processing step. Never add new ColBuilder variants or match arms to
 //! the hot loops; it causes icache pressure that slows ALL columns.
 
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use arrow::array::{
-    Array, ArrayRef, Date32Array, DurationMicrosecondArray, Float64Array, Float64Builder,
-    StringViewBuilder, TimestampMicrosecondArray,
+    Array, ArrayRef, BooleanArray, Date32Array, DictionaryArray, DurationMicrosecondArray,
+    Float64Array, Float64Builder, Int32Array, Int8Array, StringArray, StringViewArray, StringViewBuilder,
+    RunArray, TimestampMicrosecondArray, UInt32Array, UInt64Array,
 };
-use arrow::datatypes::{Field, Schema};
+use arrow::compute::kernels::partition::partition;
+use arrow::compute::{cast, nullif, take};
+use arrow::datatypes::{DataType, Int32Type, Int8Type, Schema};
 use arrow::record_batch::RecordBatch;
 use encoding_rs::Encoding;
 use rayon::prelude::*;
 
-use crate::arrow_convert;
+use crate::arrow_convert::{self, Dichotomy};
 use crate::constants::{
-    is_sysmis, TemporalKind, VarType, MICROS_PER_SECOND, SECONDS_PER_DAY,
+    is_sysmis, FormatType, TemporalKind, VarType, MICROS_PER_SECOND, SECONDS_PER_DAY,
     SPSS_EPOCH_OFFSET_DAYS, SPSS_EPOCH_OFFSET_SECONDS,
 };
 use crate::dictionary::ResolvedDictionary;
+use crate::float_format::NumberFormat;
 use crate::encoding;
-use crate::error::Result;
+use crate::error::{Result, SpssError};
 use crate::io_utils;
+use crate::metadata::{MissingSpec, Value};
+use crate::options::{
+    in_pool, MissingPolicy, OutputOptions, StringType, TemporalMode, Unlabelled,
+};
+use crate::simd;
 use crate::variable::VariableRecord;
 
+/// A labelled column: index, `(code, label)` pairs, and output type.
+type LabelColumn = (usize, Vec<(f64, String)>, DataType);
+
 /// Row byte threshold for switching to tiled parallel column processing.
 /// When row_bytes exceeds this, the full-chunk column-at-a-time pattern
 /// thrashes L3 cache because each column pass scans the entire 256 MB chunk
@@ -67,6 +80,10 @@ struct ColumnMapping {
     n_segments: usize,
     /// Pre-computed VLS segment layout (empty for n_segments <= 1).
     vls_layout: Vec<VlsSegmentInfo>,
+    /// Encoding of string values (`ResolvedDictionary::value_encoding`).
+    encoding: &'static Encoding,
+    /// Variable name, for error messages.
+    name: String,
 }
 
 /// Arrow column builder (typed). Only two variants — keeps hot path icache small.
@@ -83,7 +100,6 @@ enum ColBuilder {
 pub struct ColumnarBatchBuilder {
     mappings: Vec<ColumnMapping>,
     builders: Vec<ColBuilder>,
-    file_encoding: &'static Encoding,
     /// The output schema (with temporal types for date/time columns).
     schema: Arc<Schema>,
     rows_appended: usize,
@@ -93,6 +109,53 @@ pub struct ColumnarBatchBuilder {
     /// Column indices that need temporal conversion in finish().
     /// Empty for files with no date/time columns — zero overhead.
     temporal_columns: Vec<(usize, TemporalKind)>,
+    /// Columns whose user-missing values become null in finish() (only
+    /// with `MissingPolicy::Null`).
+    missing_columns: Vec<(usize, Vec<MissingSpec>)>,
+    /// Labelled numeric columns returned as dictionaries or label strings
+    /// (`LabelMode`), with their `(code, label)` pairs and output type.
+    label_columns: Vec<LabelColumn>,
+    keep_unlabelled: bool,
+    /// Whole-number columns cast to Int32/Int64 in finish() (`integers`),
+    /// with their names for error messages.
+    integer_columns: Vec<(usize, DataType, String)>,
+    /// Dichotomies converted to Boolean in finish() (`booleans`).
+    boolean_columns: Vec<(usize, Dichotomy, String)>,
+    /// Columns narrowed to Float32 in finish() (`float32`).
+    float32_columns: Vec<usize>,
+    /// Columns followed by a missing-reason column (`missing_reasons`),
+    /// with their user-missing specs.
+    reason_columns: Vec<(usize, Vec<MissingSpec>)>,
+    /// String columns padded back to their declared byte width in finish()
+    /// (`keep_trailing_spaces`).
+    padded_columns: Vec<(usize, usize)>,
+    /// String columns whose blank values become null in finish()
+    /// (`blank_as_null`).
+    blank_columns: Vec<usize>,
+    /// Columns run-end encoded in finish() (`run_end_encoded`).
+    run_end_columns: Vec<usize>,
+    /// AHEX string columns, whose hex digits are decoded in finish().
+    hex_columns: Vec<usize>,
+    /// 1-based file row of each appended row, when a case-number column
+    /// is requested (`case_numbers`), or to report where text failed to
+    /// decode (`strict_encoding`). Filled by the scanner.
+    case_numbers: Option<Vec<u64>>,
+    /// Whether `case_numbers` becomes a column.
+    case_number_column: bool,
+    /// Fail on string values not valid in their encoding
+    /// (`strict_encoding`) rather than replacing the bad bytes with U+FFFD.
+    strict_encoding: bool,
+    /// Per column, the string values that did not decode.
+    bad_text: Vec<BadText>,
+    /// Normalize string values to NFC in finish() (`normalize_nfc`).
+    #[cfg(feature = "nfc")]
+    nfc: bool,
+    /// String columns are built as Utf8View and cast in finish() if needed.
+    string_type: StringType,
+    pool: Option<Arc<rayon::ThreadPool>>,
+    /// How the file stores numbers. Unless native, the hot loops read them
+    /// as little-endian IEEE and finish() converts them.
+    numbers: NumberFormat,
 }
 
 impl ColumnarBatchBuilder {
@@ -104,6 +167,16 @@ impl ColumnarBatchBuilder {
         dict: &ResolvedDictionary,
         projection: Option<&[usize]>,
         capacity: usize,
+    ) -> Self {
+        Self::with_options(dict, projection, capacity, &OutputOptions::default())
+    }
+
+    /// Create a builder producing columns as configured by `options`.
+    pub fn with_options(
+        dict: &ResolvedDictionary,
+        projection: Option<&[usize]>,
+        capacity: usize,
+        options: &OutputOptions,
     ) -> Self {
         let vars: Vec<&VariableRecord> = match projection {
             Some(proj) => proj.iter().map(|&i| &dict.variables[i]).collect(),
@@ -112,10 +185,38 @@ impl ColumnarBatchBuilder {
 
         let mut mappings = Vec::with_capacity(vars.len());
         let mut builders = Vec::with_capacity(vars.len());
-        let mut fields = Vec::with_capacity(vars.len());
         let mut temporal_columns = Vec::new();
+        let mut missing_columns = Vec::new();
+        let mut label_columns = Vec::new();
+        let mut integer_columns = Vec::new();
+        let mut boolean_columns = Vec::new();
+        let mut float32_columns = Vec::new();
+        let mut run_end_columns = Vec::new();
+        let mut padded_columns = Vec::new();
+        let mut blank_columns = Vec::new();
+        let mut reason_columns = Vec::new();
+        let mut hex_columns = Vec::new();
 
         for (col_idx, var) in vars.iter().enumerate() {
+            if options.missing_reasons && arrow_convert::has_missing_reason(var, &dict.metadata) {
+                let specs = dict
+                    .metadata
+                    .variable_missing
+                    .get(&var.long_name)
+                    .cloned()
+                    .unwrap_or_default();
+                reason_columns.push((col_idx, specs));
+            }
+
+            if options.missing == MissingPolicy::Null {
+                match dict.metadata.variable_missing.get(&var.long_name) {
+                    Some(specs) if !specs.is_empty() => {
+                        missing_columns.push((col_idx, specs.clone()));
+                    }
+                    _ => {}
+                }
+            }
+
             // Pre-compute VLS segment layout
             let vls_layout = if var.n_segments > 1 {
                 let width = match &var.var_type {
@@ -146,11 +247,33 @@ impl ColumnarBatchBuilder {
                 var_type: var.var_type.clone(),
                 n_segments: var.n_segments,
                 vls_layout,
+                encoding: dict.value_encoding(var),
+                name: var.long_name.clone(),
             });
 
             // Output schema uses temporal types; builders always use Float64.
-            let output_type = arrow_convert::var_to_arrow_type(var);
-            fields.push(Field::new(&var.long_name, output_type, true));
+            let output_type = arrow_convert::output_arrow_type(var, &dict.metadata, options);
+            if arrow_convert::uses_labels(var, &dict.metadata, options) {
+                let labels = dict.metadata.variable_value_labels[&var.long_name]
+                    .iter()
+                    .filter_map(|(value, label)| match value {
+                        Value::Numeric(code) => Some((*code, label.clone())),
+                        Value::String(_) => None,
+                    })
+                    .collect();
+                label_columns.push((col_idx, labels, output_type.clone()));
+            } else if output_type == DataType::Boolean
+                && let Some(kind) = arrow_convert::dichotomy(var, &dict.metadata)
+            {
+                boolean_columns.push((col_idx, kind, var.long_name.clone()));
+            } else if matches!(output_type, DataType::Int32 | DataType::Int64) {
+                integer_columns.push((col_idx, output_type, var.long_name.clone()));
+            } else if output_type == DataType::Float32 {
+                float32_columns.push(col_idx);
+            }
+            if options.run_end_encoded.contains(&var.long_name) {
+                run_end_columns.push(col_idx);
+            }
 
             match &var.var_type {
                 VarType::Numeric => {
@@ -159,13 +282,27 @@ impl ColumnarBatchBuilder {
                         .print_format
                         .as_ref()
                         .and_then(|f| f.format_type.temporal_kind())
+                        .filter(|_| options.temporal == TemporalMode::Arrow)
                     {
                         temporal_columns.push((col_idx, kind));
                     }
                     // ALL numerics use Float64Builder — keeps hot path minimal
                     builders.push(ColBuilder::Float64(Float64Builder::with_capacity(capacity)));
                 }
-                VarType::String(_) => {
+                VarType::String(width) => {
+                    let hex = var
+                        .print_format
+                        .as_ref()
+                        .is_some_and(|f| f.format_type == FormatType::Ahex);
+                    if hex {
+                        hex_columns.push(col_idx);
+                    } else if options.keep_trailing_spaces {
+                        // Decoded hex has no padding to restore
+                        padded_columns.push((col_idx, *width));
+                    }
+                    if options.blank_as_null {
+                        blank_columns.push(col_idx);
+                    }
                     // Only enable dedup for categorical columns (those with value labels).
                     // High-cardinality columns (IDs, free-text) pay hash overhead with no benefit.
                     let has_value_labels = dict
@@ -182,14 +319,35 @@ impl ColumnarBatchBuilder {
             }
         }
 
+        let n_columns = mappings.len();
         ColumnarBatchBuilder {
             mappings,
             builders,
-            file_encoding: dict.file_encoding,
-            schema: Arc::new(Schema::new(fields)),
+            schema: Arc::new(arrow_convert::output_schema(dict, projection, options)),
             rows_appended: 0,
             string_buf: Vec::with_capacity(1024),
             temporal_columns,
+            missing_columns,
+            label_columns,
+            keep_unlabelled: options.unlabelled == Unlabelled::Keep,
+            integer_columns,
+            boolean_columns,
+            float32_columns,
+            reason_columns,
+            padded_columns,
+            blank_columns,
+            run_end_columns,
+            hex_columns,
+            case_numbers: (options.case_numbers.is_some() || options.strict_encoding)
+                .then(|| Vec::with_capacity(capacity)),
+            case_number_column: options.case_numbers.is_some(),
+            strict_encoding: options.strict_encoding,
+            bad_text: vec![BadText::default(); n_columns],
+            #[cfg(feature = "nfc")]
+            nfc: options.normalize_nfc,
+            string_type: options.string_type,
+            pool: options.pool.clone(),
+            numbers: dict.numbers,
         }
     }
 
@@ -217,30 +375,38 @@ impl ColumnarBatchBuilder {
         }
 
         let mappings = &self.mappings;
-        let file_encoding = self.file_encoding;
+        let first_row = self.rows_appended;
 
         if num_rows >= 10_000 {
             // Parallel: each column processed by a separate rayon thread.
             // rayon splits builders into ~24 contiguous groups (one per core).
-            self.builders
-                .par_iter_mut()
-                .enumerate()
-                .for_each(|(i, builder)| {
-                    let mapping = &mappings[i];
-                    match (&mapping.var_type, builder) {
-                        (VarType::Numeric, ColBuilder::Float64(b)) => {
-                            process_numeric_rows(b, chunk, 0, num_rows, row_bytes, mapping.slot_index);
-                        }
-                        (VarType::String(_), ColBuilder::Str(b)) => {
-                            let mut local_buf = Vec::with_capacity(256);
-                            process_string_rows(
-                                b, &mut local_buf, chunk, 0, num_rows,
-                                row_bytes, slots_per_row, mapping, file_encoding,
-                            );
+            let builders = &mut self.builders;
+            let bad_text = &mut self.bad_text;
+            in_pool(self.pool.as_deref(), || {
+                builders
+                    .par_iter_mut()
+                    .zip(bad_text.par_iter_mut())
+                    .enumerate()
+                    .for_each(|(i, (builder, bad))| {
+                        let mapping = &mappings[i];
+                        match (&mapping.var_type, builder) {
+                            (VarType::Numeric, ColBuilder::Float64(b)) => {
+                                process_numeric_rows(b, chunk, 0, num_rows, row_bytes, mapping.slot_index);
+                            }
+                            (VarType::String(_), ColBuilder::Str(b)) => {
+                                let mut local_buf = Vec::with_capacity(256);
+                                bad.merge(
+                                    process_string_rows(
+                                        b, &mut local_buf, chunk, 0, num_rows,
+                                        row_bytes, slots_per_row, mapping,
+                                    ),
+                                    first_row,
+                                );
+                            }
+                            _ => unreachable!(),
                         }
-                        _ => unreachable!(),
-                    }
-                });
+                    });
+            });
         } else {
             // Sequential: small chunks (lazy head, small files).
             for (i, mapping) in mappings.iter().enumerate() {
@@ -249,10 +415,11 @@ impl ColumnarBatchBuilder {
                         process_numeric_rows(b, chunk, 0, num_rows, row_bytes, mapping.slot_index);
                     }
                     (VarType::String(_), ColBuilder::Str(b)) => {
-                        process_string_rows(
+                        let bad = process_string_rows(
                             b, &mut self.string_buf, chunk, 0, num_rows,
-                            row_bytes, slots_per_row, mapping, self.file_encoding,
+                            row_bytes, slots_per_row, mapping,
                         );
+                        self.bad_text[i].merge(bad, first_row);
                     }
                     _ => unreachable!(),
                 }
@@ -281,33 +448,41 @@ impl ColumnarBatchBuilder {
         let tile_rows = (L3_TILE_BYTES / row_bytes).max(64);
 
         let mappings = &self.mappings;
-        let file_encoding = self.file_encoding;
 
         let mut row_offset = 0;
         while row_offset < num_rows {
             let n = (num_rows - row_offset).min(tile_rows);
             let tile_start = row_offset * row_bytes;
+            let first_row = self.rows_appended + row_offset;
 
             // Parallel column processing within this L3-sized tile.
-            self.builders
-                .par_iter_mut()
-                .enumerate()
-                .for_each(|(i, builder)| {
-                    let mapping = &mappings[i];
-                    match (&mapping.var_type, builder) {
-                        (VarType::Numeric, ColBuilder::Float64(b)) => {
-                            process_numeric_rows(b, chunk, tile_start, n, row_bytes, mapping.slot_index);
-                        }
-                        (VarType::String(_), ColBuilder::Str(b)) => {
-                            let mut local_buf = Vec::with_capacity(256);
-                            process_string_rows(
-                                b, &mut local_buf, chunk, tile_start, n,
-                                row_bytes, slots_per_row, mapping, file_encoding,
-                            );
+            let builders = &mut self.builders;
+            let bad_text = &mut self.bad_text;
+            in_pool(self.pool.as_deref(), || {
+                builders
+                    .par_iter_mut()
+                    .zip(bad_text.par_iter_mut())
+                    .enumerate()
+                    .for_each(|(i, (builder, bad))| {
+                        let mapping = &mappings[i];
+                        match (&mapping.var_type, builder) {
+                            (VarType::Numeric, ColBuilder::Float64(b)) => {
+                                process_numeric_rows(b, chunk, tile_start, n, row_bytes, mapping.slot_index);
+                            }
+                            (VarType::String(_), ColBuilder::Str(b)) => {
+                                let mut local_buf = Vec::with_capacity(256);
+                                bad.merge(
+                                    process_string_rows(
+                                        b, &mut local_buf, chunk, tile_start, n,
+                                        row_bytes, slots_per_row, mapping,
+                                    ),
+                                    first_row,
+                                );
+                            }
+                            _ => unreachable!(),
                         }
-                        _ => unreachable!(),
-                    }
-                });
+                    });
+            });
 
             row_offset += n;
         }
@@ -320,17 +495,74 @@ impl ColumnarBatchBuilder {
     /// Temporal columns are converted from Float64 to their proper Arrow types
     /// here, outside the hot path. This keeps the read loops fast for all columns.
     pub fn finish(self) -> Result<RecordBatch> {
+        // Strict decoding: fail on the first value that did not decode
+        if let Some((row, col_idx)) = self
+            .bad_text
+            .iter()
+            .enumerate()
+            .filter_map(|(col_idx, bad)| bad.first_row.map(|row| (row, col_idx)))
+            .min()
+            .filter(|_| self.strict_encoding)
+        {
+            let mapping = &self.mappings[col_idx];
+            let at = match self.case_numbers.as_ref().and_then(|c| c.get(row)) {
+                Some(case) => format!("case {case}"),
+                None => format!("row {row} of the batch"),
+            };
+            return Err(SpssError::Encoding(format!(
+                "variable {:?}, {at}: text not valid in {}",
+                mapping.name,
+                mapping.encoding.name()
+            )));
+        }
+
+        let numbers = self.numbers;
         let mut columns: Vec<ArrayRef> = self
             .builders
             .into_iter()
             .map(|b| -> ArrayRef {
                 match b {
+                    ColBuilder::Float64(mut b) if !numbers.is_native() => {
+                        Arc::new(convert_float64(&b.finish(), numbers))
+                    }
                     ColBuilder::Float64(mut b) => Arc::new(b.finish()),
                     ColBuilder::Str(mut b) => Arc::new(b.finish()),
                 }
             })
             .collect();
 
+        // Post-process: compose decomposed text (`normalize_nfc`), before
+        // values are matched against the (normalized) dictionary.
+        #[cfg(feature = "nfc")]
+        if self.nfc {
+            for column in &mut columns {
+                *column = nfc_strings(column);
+            }
+        }
+
+        // Post-process: explain missing cells (`missing_reasons`), before
+        // user-missing values may be nulled below.
+        let mut reasons = Vec::with_capacity(self.reason_columns.len());
+        for (col_idx, specs) in &self.reason_columns {
+            reasons.push((*col_idx, missing_reasons(&columns[*col_idx], specs)?));
+        }
+
+        // Post-process: null out user-missing values (MissingPolicy::Null).
+        for (col_idx, specs) in &self.missing_columns {
+            columns[*col_idx] = null_user_missing(&columns[*col_idx], specs)?;
+        }
+
+        // Post-process: AHEX strings to the text they encode, after
+        // user-missing values (given as stored) are matched.
+        for &col_idx in &self.hex_columns {
+            let encoding = self.mappings[col_idx].encoding;
+            columns[col_idx] = decode_hex_strings(&columns[col_idx], encoding);
+            #[cfg(feature = "nfc")]
+            if self.nfc {
+                columns[col_idx] = nfc_strings(&columns[col_idx]);
+            }
+        }
+
         // Post-process: convert temporal Float64 columns to proper Arrow types.
         // This is O(n) per temporal column, typically 0-5 columns out of hundreds.
         for &(col_idx, kind) in &self.temporal_columns {
@@ -341,14 +573,133 @@ impl ColumnarBatchBuilder {
             columns[col_idx] = convert_float64_to_temporal(float_arr, kind);
         }
 
+        // Post-process: null out blank strings (`blank_as_null`).
+        for &col_idx in &self.blank_columns {
+            columns[col_idx] = null_blank(&columns[col_idx])?;
+        }
+
+        // Post-process: restore the space padding (`keep_trailing_spaces`).
+        for &(col_idx, width) in &self.padded_columns {
+            let encoding = self.mappings[col_idx].encoding;
+            columns[col_idx] = pad_strings(&columns[col_idx], width, encoding);
+        }
+
+        // Post-process: labelled codes to dictionaries or labels (LabelMode).
+        for (col_idx, labels, output_type) in &self.label_columns {
+            let codes = columns[*col_idx]
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .expect("labelled column should be Float64Array");
+            let dictionary = codes_to_dictionary(codes, labels, self.keep_unlabelled)?;
+            columns[*col_idx] = match output_type {
+                DataType::Dictionary(..) => dictionary,
+                string_type => cast(&dictionary, string_type)?,
+            };
+        }
+
+        // Post-process: whole-number columns to integers (`integers`).
+        for (col_idx, int_type, name) in &self.integer_columns {
+            columns[*col_idx] = float_to_integer(&columns[*col_idx], int_type, name)?;
+        }
+
+        // Post-process: dichotomies to Boolean (`booleans`).
+        for (col_idx, kind, name) in &self.boolean_columns {
+            columns[*col_idx] = float_to_boolean(&columns[*col_idx], *kind, name)?;
+        }
+
+        // Post-process: narrow to Float32 (`float32`).
+        for &col_idx in &self.float32_columns {
+            columns[col_idx] = cast(&columns[col_idx], &DataType::Float32)?;
+        }
+
+        // Post-process: cast string columns if another string type was asked for.
+        if self.string_type != StringType::Utf8View {
+            let target = self.string_type.data_type();
+            for column in &mut columns {
+                if column.data_type() == &arrow::datatypes::DataType::Utf8View {
+                    *column = cast(column, &target)?;
+                }
+            }
+        }
+
+        // Post-process: collapse runs of equal values (`run_end_encoded`).
+        for &col_idx in &self.run_end_columns {
+            columns[col_idx] = run_end_encode(&columns[col_idx])?;
+        }
+
+        // Each missing-reason column goes right after its data column.
+        if !reasons.is_empty() {
+            let mut reasons = reasons.into_iter().peekable();
+            let mut with_reasons = Vec::with_capacity(columns.len() + reasons.len());
+            for (col_idx, column) in columns.into_iter().enumerate() {
+                with_reasons.push(column);
+                if let Some((_, reason)) = reasons.next_if(|(i, _)| *i == col_idx) {
+                    with_reasons.push(reason);
+                }
+            }
+            columns = with_reasons;
+        }
+
+        // The case-number column goes first.
+        if let Some(case_numbers) = self.case_numbers
+            && self.case_number_column
+        {
+            columns.insert(0, Arc::new(UInt64Array::from(case_numbers)));
+        }
+
         let batch = RecordBatch::try_new(self.schema, columns)?;
         Ok(batch)
     }
 
+    /// Where to record the file row of each appended row, if a case-number
+    /// column was requested. Rows must be recorded in the order they are
+    /// pushed.
+    pub(crate) fn case_numbers_mut(&mut self) -> Option<&mut Vec<u64>> {
+        self.case_numbers.as_mut()
+    }
+
+    /// Record the case numbers of `n` consecutive file rows starting at
+    /// 0-based file row `first_row`.
+    pub(crate) fn push_case_range(&mut self, first_row: usize, n: usize) {
+        if let Some(case_numbers) = &mut self.case_numbers {
+            case_numbers.extend((first_row..first_row + n).map(|row| row as u64 + 1));
+        }
+    }
+
     /// Number of rows appended so far.
     pub fn len(&self) -> usize {
         self.rows_appended
     }
+
+    /// The replacement characters put into each string column so far in
+    /// place of bytes not valid in its encoding, with that encoding, for
+    /// the columns that have any.
+    pub fn replaced_characters(
+        &self,
+    ) -> impl Iterator<Item = (&str, &'static Encoding, u64)> {
+        self.mappings
+            .iter()
+            .zip(&self.bad_text)
+            .filter(|(_, bad)| bad.replaced > 0)
+            .map(|(mapping, bad)| (mapping.name.as_str(), mapping.encoding, bad.replaced))
+    }
+}
+
+/// The string values of a column that did not decode.
+#[derive(Debug, Clone, Copy, Default)]
+struct BadText {
+    /// The first such row, counted from the start of the batch.
+    first_row: Option<usize>,
+    /// U+FFFD characters put in place of the undecodable bytes.
+    replaced: u64,
+}
+
+impl BadText {
+    /// Add the tally of a chunk whose rows start at `first_row`.
+    fn merge(&mut self, chunk: BadText, first_row: usize) {
+        self.first_row = self.first_row.or(chunk.first_row.map(|row| first_row + row));
+        self.replaced += chunk.replaced;
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -369,15 +720,43 @@ fn process_numeric_rows(
     slot_index: usize,
 ) {
     let slot_offset = slot_index * 8;
-    for row in 0..num_rows {
+    let read = |row: usize| {
         let offset = base_offset + row * row_bytes + slot_offset;
         // SAFETY: offset + 8 <= chunk.len() because the caller guarantees
         // (base_offset + num_rows * row_bytes) <= chunk.len() and
         // slot_offset + 8 <= row_bytes. [u8; 8] has 1-byte alignment,
         // so any byte-aligned pointer from chunk is valid.
-        let val = f64::from_le_bytes(unsafe {
-            *(chunk.as_ptr().add(offset) as *const [u8; 8])
-        });
+        f64::from_le_bytes(unsafe { *(chunk.as_ptr().add(offset) as *const [u8; 8]) })
+    };
+
+    // With the `simd` feature: gather the column a block at a time on the
+    // stack and look for SYSMIS in one vectorized pass, so that blocks
+    // without it skip the per-value null check.
+    if simd::ENABLED {
+        const BLOCK: usize = 64;
+        let mut block = [0.0f64; BLOCK];
+        for start in (0..num_rows).step_by(BLOCK) {
+            let values = &mut block[..BLOCK.min(num_rows - start)];
+            for (i, v) in values.iter_mut().enumerate() {
+                *v = read(start + i);
+            }
+            if !simd::contains_sysmis(values) {
+                builder.append_slice(values);
+                continue;
+            }
+            for &val in values.iter() {
+                if is_sysmis(val) {
+                    builder.append_null();
+                } else {
+                    builder.append_value(val);
+                }
+            }
+        }
+        return;
+    }
+
+    for row in 0..num_rows {
+        let val = read(row);
         if is_sysmis(val) {
             builder.append_null();
         } else {
@@ -390,7 +769,9 @@ fn process_numeric_rows(
 ///
 /// Reads `num_rows` string values starting at `base_offset` in the chunk,
 /// assembling bytes from the appropriate slots per the column mapping.
+/// Returns the rows whose text did not decode, counted from `base_offset`.
 #[inline(always)]
+#[allow(clippy::too_many_arguments)]
 fn process_string_rows(
     builder: &mut StringViewBuilder,
     string_buf: &mut Vec<u8>,
@@ -400,12 +781,12 @@ fn process_string_rows(
     row_bytes: usize,
     slots_per_row: usize,
     mapping: &ColumnMapping,
-    file_encoding: &'static Encoding,
-) {
+) -> BadText {
     let width = match &mapping.var_type {
         VarType::String(w) => *w,
         _ => unreachable!(),
     };
+    let mut bad = BadText::default();
     for row in 0..num_rows {
         let row_start = base_offset + row * row_bytes;
         // SAFETY: row_start + slots_per_row * 8 <= chunk.len() (same caller
@@ -416,7 +797,7 @@ fn process_string_rows(
                 slots_per_row,
             )
         };
-        push_string_from_raw_slots(
+        let replaced = push_string_from_raw_slots(
             builder,
             string_buf,
             raw_slots,
@@ -424,9 +805,25 @@ fn process_string_rows(
             width,
             mapping.n_segments,
             &mapping.vls_layout,
-            file_encoding,
+            mapping.encoding,
         );
+        if replaced > 0 {
+            bad.first_row = bad.first_row.or(Some(row));
+            bad.replaced += replaced as u64;
+        }
     }
+    bad
+}
+
+/// Convert values read as little-endian IEEE from a big-endian or IBM/VAX
+/// file. Those that turn out to be system-missing become null.
+fn convert_float64(arr: &Float64Array, numbers: NumberFormat) -> Float64Array {
+    arr.iter()
+        .map(|v| {
+            v.map(|v| numbers.decode(v.to_bits().to_le_bytes()))
+                .filter(|v| !is_sysmis(*v))
+        })
+        .collect()
 }
 
 // ---------------------------------------------------------------------------
@@ -469,12 +866,263 @@ fn convert_float64_to_temporal(arr: &Float64Array, kind: TemporalKind) -> ArrayR
     }
 }
 
+/// Encode codes as a dictionary array over their labels. Unlabelled codes
+/// get their formatted value appended to the dictionary, or are null unless
+/// `keep_unlabelled`.
+#[inline(never)]
+pub(crate) fn codes_to_dictionary(
+    codes: &Float64Array,
+    labels: &[(f64, String)],
+    keep_unlabelled: bool,
+) -> Result<ArrayRef> {
+    // Keyed by bit pattern; +0.0 stands in for -0.0.
+    let key_of = |v: f64| (v + 0.0).to_bits();
+    let mut lookup: std::collections::HashMap<u64, i32> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, (code, _))| (key_of(*code), i as i32))
+        .collect();
+    let mut values: Vec<String> = labels.iter().map(|(_, label)| label.clone()).collect();
+    let keys: Int32Array = codes
+        .iter()
+        .map(|v| {
+            let v = v?;
+            if let Some(&key) = lookup.get(&key_of(v)) {
+                return Some(key);
+            }
+            if !keep_unlabelled {
+                return None;
+            }
+            values.push(v.to_string());
+            let key = values.len() as i32 - 1;
+            lookup.insert(key_of(v), key);
+            Some(key)
+        })
+        .collect();
+    let values: ArrayRef = Arc::new(StringArray::from(values));
+    Ok(Arc::new(DictionaryArray::<Int32Type>::try_new(keys, values)?))
+}
+
+/// Run-end encode a column: one value per run of equal values.
+#[inline(never)]
+fn run_end_encode(column: &ArrayRef) -> Result<ArrayRef> {
+    let ranges = partition(std::slice::from_ref(column))?.ranges();
+    let run_ends = Int32Array::from_iter_values(ranges.iter().map(|r| r.end as i32));
+    let starts = UInt32Array::from_iter_values(ranges.iter().map(|r| r.start as u32));
+    let values = take(column.as_ref(), &starts, None)?;
+    Ok(Arc::new(RunArray::<Int32Type>::try_new(&run_ends, values.as_ref())?))
+}
+
+/// Convert a dichotomy's codes to booleans.
+#[inline(never)]
+fn float_to_boolean(column: &ArrayRef, kind: Dichotomy, name: &str) -> Result<ArrayRef> {
+    let arr = column
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .expect("dichotomy column should be Float64Array");
+    let bools: BooleanArray = match kind {
+        Dichotomy::Counted(counted) => arr.iter().map(|v| v.map(|v| v == counted)).collect(),
+        Dichotomy::ZeroOne => {
+            if let Some(bad) = arr.iter().flatten().find(|&v| v != 0.0 && v != 1.0) {
+                return Err(SpssError::InvalidVariable(format!(
+                    "{name}: value {bad} cannot be read as Boolean"
+                )));
+            }
+            arr.iter().map(|v| v.map(|v| v == 1.0)).collect()
+        }
+    };
+    Ok(Arc::new(bools))
+}
+
+/// Cast a Float64 column of whole numbers to `int_type` (Int32 or Int64),
+/// failing on the first value that is fractional or out of range.
+#[inline(never)]
+fn float_to_integer(column: &ArrayRef, int_type: &DataType, name: &str) -> Result<ArrayRef> {
+    let (min, max) = match int_type {
+        DataType::Int32 => (i32::MIN as f64, i32::MAX as f64 + 1.0),
+        _ => (i64::MIN as f64, i64::MAX as f64),
+    };
+    let arr = column
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .expect("integer column should be Float64Array");
+    if let Some(bad) = arr
+        .iter()
+        .flatten()
+        .find(|v| v.fract() != 0.0 || *v < min || *v >= max)
+    {
+        return Err(SpssError::InvalidVariable(format!(
+            "{name}: value {bad} cannot be read as {int_type}"
+        )));
+    }
+    Ok(cast(column, int_type)?)
+}
+
+/// Whether a numeric value matches a user-missing spec.
+pub(crate) fn is_missing_f64(spec: &MissingSpec, v: f64) -> bool {
+    match spec {
+        MissingSpec::Value(m) => v == *m,
+        MissingSpec::Range { lo, hi } => v >= *lo && v <= *hi,
+        MissingSpec::StringValue(_) => false,
+    }
+}
+
+/// Whether a string value matches a user-missing spec (ignoring padding).
+pub(crate) fn is_missing_str(spec: &MissingSpec, v: &str) -> bool {
+    match spec {
+        MissingSpec::StringValue(m) => v.trim_end() == m.trim_end(),
+        _ => false,
+    }
+}
+
+/// Build a missing-reason column: `"system-missing"` for nulls in a numeric
+/// column, the matching user-missing value or range for user-missing
+/// cells, null otherwise.
+#[inline(never)]
+fn missing_reasons(column: &ArrayRef, specs: &[MissingSpec]) -> Result<ArrayRef> {
+    let bound = |v: f64| {
+        if v <= -f64::MAX {
+            "LOWEST".to_string()
+        } else if v >= f64::MAX {
+            "HIGHEST".to_string()
+        } else {
+            v.to_string()
+        }
+    };
+    let mut values = vec!["system-missing".to_string()];
+    values.extend(specs.iter().map(|spec| match spec {
+        MissingSpec::Value(v) => v.to_string(),
+        MissingSpec::Range { lo, hi } => format!("{} thru {}", bound(*lo), bound(*hi)),
+        MissingSpec::StringValue(s) => s.trim_end().to_string(),
+    }));
+    let key = |i: usize| Some(i as i8 + 1);
+    let keys: Int8Array = if let Some(arr) = column.as_any().downcast_ref::<Float64Array>() {
+        arr.iter()
+            .map(|v| match v {
+                None => Some(0),
+                Some(v) => specs.iter().position(|s| is_missing_f64(s, v)).and_then(key),
+            })
+            .collect()
+    } else if let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() {
+        arr.iter()
+            .map(|v| {
+                let v = v?;
+                specs.iter().position(|s| is_missing_str(s, v)).and_then(key)
+            })
+            .collect()
+    } else {
+        Int8Array::new_null(column.len())
+    };
+    let values: ArrayRef = Arc::new(StringArray::from(values));
+    Ok(Arc::new(DictionaryArray::<Int8Type>::try_new(keys, values)?))
+}
+
+/// Replace values matching any user-missing spec with nulls.
+#[inline(never)]
+pub(crate) fn null_user_missing(column: &ArrayRef, specs: &[MissingSpec]) -> Result<ArrayRef> {
+    let mask: BooleanArray = if let Some(arr) = column.as_any().downcast_ref::<Float64Array>() {
+        arr.iter()
+            .map(|v| v.map(|v| specs.iter().any(|spec| is_missing_f64(spec, v))))
+            .collect()
+    } else if let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() {
+        arr.iter()
+            .map(|v| v.map(|v| specs.iter().any(|spec| is_missing_str(spec, v))))
+            .collect()
+    } else {
+        return Ok(Arc::clone(column));
+    };
+    Ok(nullif(column, &mask)?)
+}
+
+/// Replace empty and all-blank strings with nulls.
+#[inline(never)]
+fn null_blank(column: &ArrayRef) -> Result<ArrayRef> {
+    let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() else {
+        return Ok(Arc::clone(column));
+    };
+    let mask: BooleanArray = arr
+        .iter()
+        .map(|v| v.map(|v| v.trim_end_matches(' ').is_empty()))
+        .collect();
+    Ok(nullif(column, &mask)?)
+}
+
+/// Normalize strings to Unicode NFC. Columns whose values are all NFC
+/// already (as ASCII text always is) are returned as they are.
+#[cfg(feature = "nfc")]
+#[inline(never)]
+fn nfc_strings(column: &ArrayRef) -> ArrayRef {
+    let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() else {
+        return Arc::clone(column);
+    };
+    if arr.iter().flatten().all(encoding::is_nfc) {
+        return Arc::clone(column);
+    }
+    let normalized: StringViewArray = arr.iter().map(|v| v.map(encoding::to_nfc)).collect();
+    Arc::new(normalized)
+}
+
+/// Decode AHEX strings: pairs of hex digits giving the bytes of the text
+/// in the file's encoding. Values that are not hex are kept as they are.
+#[inline(never)]
+fn decode_hex_strings(column: &ArrayRef, file_encoding: &'static Encoding) -> ArrayRef {
+    let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() else {
+        return Arc::clone(column);
+    };
+    let decoded: StringViewArray = arr
+        .iter()
+        .map(|v| {
+            v.map(|v| match hex_bytes(v.trim_end_matches(' ')) {
+                Some(bytes) => encoding::decode_str_lossy(&bytes, file_encoding)
+                    .trim_end_matches([' ', '\0'])
+                    .to_string(),
+                None => v.to_string(),
+            })
+        })
+        .collect();
+    Arc::new(decoded)
+}
+
+/// The bytes spelled by a string of hex digit pairs.
+fn hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |b: u8| (b as char).to_digit(16).unwrap() as u8;
+    Some(s.as_bytes().chunks_exact(2).map(|pair| digit(pair[0]) << 4 | digit(pair[1])).collect())
+}
+
+/// Pad each string with spaces to `width` bytes in the file's encoding.
+#[inline(never)]
+fn pad_strings(column: &ArrayRef, width: usize, file_encoding: &'static Encoding) -> ArrayRef {
+    let Some(arr) = column.as_any().downcast_ref::<StringViewArray>() else {
+        return Arc::clone(column);
+    };
+    let padded: StringViewArray = arr
+        .iter()
+        .map(|v| {
+            v.map(|v| {
+                let len = if file_encoding == encoding_rs::UTF_8 {
+                    v.len()
+                } else {
+                    file_encoding.encode(v).0.len()
+                };
+                format!("{v}{}", " ".repeat(width.saturating_sub(len)))
+            })
+        })
+        .collect();
+    Arc::new(padded)
+}
+
 // ---------------------------------------------------------------------------
 // String push helpers
 // ---------------------------------------------------------------------------
 
 /// Assemble a string from raw 8-byte slots and push directly into a StringViewBuilder.
+/// Returns the number of U+FFFD characters put in place of bytes not valid
+/// in the encoding.
 #[inline]
+#[allow(clippy::too_many_arguments)]
 fn push_string_from_raw_slots(
     builder: &mut StringViewBuilder,
     string_buf: &mut Vec<u8>,
@@ -484,11 +1132,11 @@ fn push_string_from_raw_slots(
     n_segments: usize,
     vls_layout: &[VlsSegmentInfo],
     file_encoding: &'static Encoding,
-) {
+) -> usize {
     string_buf.clear();
 
     if n_segments <= 1 {
-        let n_slots = (width + 7) / 8;
+        let n_slots = width.div_ceil(8);
         for i in 0..n_slots {
             let idx = start_slot + i;
             if idx < raw_slots.len() {
@@ -502,7 +1150,7 @@ fn push_string_from_raw_slots(
         let mut cumulative = 0;
         for seg_info in vls_layout {
             cumulative += seg_info.useful_bytes;
-            let slots_to_read = (seg_info.useful_bytes + 7) / 8;
+            let slots_to_read = seg_info.useful_bytes.div_ceil(8);
             for i in 0..slots_to_read {
                 if slot + i < raw_slots.len() {
                     string_buf.extend_from_slice(&raw_slots[slot + i]);
@@ -519,4 +1167,123 @@ fn push_string_from_raw_slots(
     let trimmed = io_utils::trim_trailing_padding(string_buf);
     let decoded = encoding::decode_str_lossy(trimmed, file_encoding);
     builder.append_value(&*decoded);
+    // Only a replacement character can mean the decoding was lossy, and
+    // valid UTF-8 is borrowed as it is
+    if matches!(decoded, Cow::Borrowed(_))
+        || !decoded.contains('\u{FFFD}')
+        || encoding::decode_str_checked(trimmed, file_encoding).is_some()
+    {
+        return 0;
+    }
+    decoded.matches('\u{FFFD}').count()
+}
+
+// ---------------------------------------------------------------------------
+// Slot layout for non-SAV sources (Stata, SAS transport)
+// ---------------------------------------------------------------------------
+
+/// Number of 8-byte slots a string of `width` occupies, using the SAV
+/// very-long-string layout (255-byte segments every 32 slots) above 255.
+pub(crate) fn string_slots(width: usize) -> usize {
+    if width == 0 {
+        1
+    } else if width <= 255 {
+        width.div_ceil(8)
+    } else {
+        let segments = width.div_ceil(255);
+        32 * (segments - 1) + (width - 255 * (segments - 1)).div_ceil(8)
+    }
+}
+
+/// Write a string value into the slots laid out by `string_slots`,
+/// space-padding it to `width`.
+pub(crate) fn write_string_slots(dest: &mut [u8], bytes: &[u8], width: usize) {
+    let bytes = &bytes[..bytes.len().min(width)];
+    if width <= 255 {
+        let area = &mut dest[..width.div_ceil(8) * 8];
+        area[..bytes.len()].copy_from_slice(bytes);
+        area[bytes.len()..].fill(b' ');
+        return;
+    }
+    let segments = width.div_ceil(255);
+    for seg in 0..segments {
+        let seg_len = if seg + 1 < segments {
+            255
+        } else {
+            width - 255 * seg
+        };
+        let area = &mut dest[seg * 256..seg * 256 + seg_len.div_ceil(8) * 8];
+        let part = bytes.get(seg * 255..).unwrap_or(&[]);
+        let part = &part[..part.len().min(seg_len)];
+        area[..part.len()].copy_from_slice(part);
+        area[part.len()..].fill(b' ');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_string_slots_very_long() {
+        let width = 600;
+        let mut case = vec![0u8; string_slots(width) * 8];
+        let text: Vec<u8> = (0..600).map(|i| b'a' + (i % 26) as u8).collect();
+        write_string_slots(&mut case, &text, width);
+        assert_eq!(string_slots(width), 32 * 2 + 12);
+        assert_eq!(&case[..255], &text[..255]);
+        assert_eq!(&case[256..256 + 255], &text[255..510]);
+        assert_eq!(&case[512..512 + 90], &text[510..]);
+    }
+
+    #[test]
+    fn test_numeric_rows_sysmis_across_blocks() {
+        use crate::constants::sysmis;
+
+        // Two slots per case, the column in the second. SYSMIS falls on the
+        // last value of the first block of 64, the first of the second, and
+        // deep in later blocks, with a clean block in between.
+        let rows = 300;
+        let missing = [63, 64, 250, 299];
+        let mut chunk = vec![0u8; 8 + rows * 16];
+        let mut expected = Vec::new();
+        for row in 0..rows {
+            let val = if missing.contains(&row) { sysmis() } else { row as f64 * 1.5 };
+            let at = 8 + row * 16 + 8;
+            chunk[at..at + 8].copy_from_slice(&val.to_le_bytes());
+            expected.push((!missing.contains(&row)).then_some(row as f64 * 1.5));
+        }
+
+        for (first, count) in [(0, rows), (1, 64), (64, 64), (100, 128), (10, 54)] {
+            let mut builder = Float64Builder::new();
+            process_numeric_rows(&mut builder, &chunk, 8 + first * 16, count, 16, 1);
+            let got: Vec<Option<f64>> = builder.finish().iter().collect();
+            assert_eq!(got, expected[first..first + count], "rows {first}..+{count}");
+        }
+    }
+
+    #[test]
+    fn test_ahex_strings_decoded() {
+        use std::io::Cursor;
+
+        use arrow::datatypes::Field;
+
+        use crate::metadata::SpssMetadata;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("code", DataType::Utf8, true)]));
+        let values = StringArray::from(vec![Some("4E59"), Some("caf\u{e9}"), Some("ABC"), None]);
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(values)]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // Turn the A print format into AHEX.
+        let name = buf.windows(8).position(|w| w == b"CODE    ").unwrap();
+        assert_eq!(buf[name - 6], FormatType::A as u8);
+        buf[name - 6] = FormatType::Ahex as u8;
+
+        let (batch, meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+        let column = batch.column(0).as_any().downcast_ref::<StringViewArray>().unwrap();
+        let read: Vec<_> = column.iter().collect();
+        assert_eq!(read, [Some("NY"), Some("caf\u{e9}"), Some("ABC"), Some("")]);
+        assert_eq!(meta.format("code"), Some("AHEX5"));
+    }
 }
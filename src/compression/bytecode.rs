@@ -1,24 +1,21 @@
 use crate::constants::*;
 use crate::error::{Result, SpssError};
+use crate::float_format::NumberFormat;
+use crate::options::BadRowPolicy;
 
-/// Raw byte representations for direct-to-buffer decompression.
+/// Raw byte representations of the values bytecodes stand for.
 const SYSMIS_RAW: [u8; 8] = SYSMIS_BITS.to_le_bytes();
 const SPACES_RAW: [u8; 8] = [0x20u8; 8];
 
-/// The result of decompressing one 8-byte slot from bytecode.
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code)]
-pub enum SlotValue {
-    /// A numeric value (bytecodes 1..=251: value = code - bias).
-    Numeric(f64),
-    /// Raw 8 bytes of uncompressed data (bytecode 253).
-    Raw([u8; 8]),
-    /// 8 ASCII spaces (bytecode 254).
-    Spaces,
-    /// System-missing value (bytecode 255).
-    Sysmis,
-    /// End of file (bytecode 252).
-    EndOfFile,
+/// A saved `BytecodeDecompressor` position, taken at a row boundary.
+///
+/// Control blocks run across rows, so resuming mid-stream needs the current
+/// control block and the index into it as well as the input offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderCheckpoint {
+    pub pos: usize,
+    pub control_bytes: [u8; 8],
+    pub control_idx: usize,
 }
 
 /// Stateful bytecode decompressor for SAV row-wise compression.
@@ -26,9 +23,6 @@ pub enum SlotValue {
 /// Maintains control block state across row boundaries, since SPSS control
 /// blocks do NOT align with row boundaries.
 pub struct BytecodeDecompressor {
-    /// Compression bias (typically 100.0). Used by test-only `decompress_row()`.
-    #[cfg_attr(not(test), allow(dead_code))]
-    bias: f64,
     /// Current position in the input buffer.
     pos: usize,
     /// Current control block (8 opcodes).
@@ -37,48 +31,151 @@ pub struct BytecodeDecompressor {
     control_idx: usize,
     /// Whether we've hit the end-of-file marker.
     eof: bool,
-    /// Pre-computed LE bytes for opcodes 1..=251: `bias_lut[code] = ((code as f64) - bias).to_le_bytes()`.
+    /// Pre-computed bytes for opcodes 1..=251: `bias_lut[code] = ((code as f64) - bias).to_le_bytes()`
+    /// (or as the file stores numbers, for big-endian and IBM/VAX files).
     /// 2 KB table fits in L1 cache; eliminates int→float + subtraction per opcode in hot loop.
     bias_lut: [[u8; 8]; 256],
+    /// System-missing as the file stores it.
+    sysmis_raw: [u8; 8],
+    /// What to do with a case cut short by an end-of-data code.
+    bad_rows: BadRowPolicy,
+    /// A case with every value missing, returned for a short one under
+    /// `BadRowPolicy::Null`.
+    blank_row: Vec<u8>,
+    /// Added to `pos` in `short_cases`, for input that is a window of a
+    /// longer stream.
+    base: usize,
+    /// Cases cut short and not yet taken: (stream offset, values decoded).
+    short_cases: Vec<(usize, usize)>,
+}
+
+/// Check a compressed file's bias before decoding with it. Any finite bias
+/// decodes (100 is usual, 0 is seen too); a NaN or infinite one means a
+/// damaged header, and every opcode value would come out as garbage.
+pub fn check_bias(bias: f64) -> Result<()> {
+    if bias.is_finite() {
+        Ok(())
+    } else {
+        Err(SpssError::InvalidBias(format!("the header's bias is {bias}")))
+    }
 }
 
 impl BytecodeDecompressor {
     pub fn new(bias: f64) -> Self {
+        Self::with_format(bias, NumberFormat::default())
+    }
+
+    /// A decompressor for a file storing numbers as `numbers` says. Values
+    /// decoded from opcodes are written the way the file stores them, like
+    /// the raw values copied through, so rows come out uniformly in the
+    /// file's format.
+    pub fn with_format(bias: f64, numbers: NumberFormat) -> Self {
         let mut bias_lut = [[0u8; 8]; 256];
         for code in 1u16..=251 {
-            bias_lut[code as usize] = ((code as f64) - bias).to_le_bytes();
+            bias_lut[code as usize] = numbers.encode((code as f64) - bias);
         }
         BytecodeDecompressor {
-            bias,
             pos: 0,
             control_bytes: [0u8; 8],
             control_idx: 8, // force reading a new control block on first use
             eof: false,
             bias_lut,
+            sysmis_raw: numbers.encode(f64::from_bits(SYSMIS_BITS)),
+            bad_rows: BadRowPolicy::Stop,
+            blank_row: Vec::new(),
+            base: 0,
+            short_cases: Vec::new(),
         }
     }
 
-    /// Decompress one row into SlotValue enum values (used by tests).
-    /// Production code uses `decompress_row_raw` which writes directly to byte buffers.
-    #[cfg(test)]
-    pub fn decompress_row(
-        &mut self,
-        input: &[u8],
-        slots_per_row: usize,
-        slots: &mut Vec<SlotValue>,
-    ) -> Result<()> {
-        slots.clear();
+    /// Handle cases cut short by an end-of-data code under `policy`.
+    /// `strings` says which slots of a case hold string bytes.
+    pub fn set_bad_rows(&mut self, policy: BadRowPolicy, strings: &[bool]) {
+        self.bad_rows = policy;
+        self.blank_row = strings
+            .iter()
+            .flat_map(|&string| if string { SPACES_RAW } else { self.sysmis_raw })
+            .collect();
+    }
+
+    pub fn bad_rows(&self) -> BadRowPolicy {
+        self.bad_rows
+    }
+
+    /// Set the stream offset of the start of the input, for the offsets
+    /// `take_short_cases` reports.
+    pub fn set_base(&mut self, base: usize) {
+        self.base = base;
+    }
+
+    /// The cases cut short since the last call, as (offset in the bytecode
+    /// stream, values decoded before the end-of-data code).
+    pub fn take_short_cases(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.short_cases)
+    }
+
+    /// Deal with an end-of-data code after `values` of a case's values:
+    /// returns whether to count the case (`Null`) or decode the next one
+    /// in its place (`Skip`). Decoding resumes at the next control block.
+    fn short_case(&mut self, values: usize, slots_per_row: usize) -> Result<bool> {
+        let offset = self.base + self.pos;
+        self.control_idx = 8;
+        if self.bad_rows == BadRowPolicy::Fail {
+            return Err(SpssError::ShortCase {
+                values,
+                expected: slots_per_row,
+                offset,
+            });
+        }
+        if !self.short_cases.contains(&(offset, values)) {
+            self.short_cases.push((offset, values));
+        }
+        Ok(self.bad_rows == BadRowPolicy::Null)
+    }
+
+    /// Current position, for resuming later with `restore`.
+    pub fn checkpoint(&self) -> DecoderCheckpoint {
+        DecoderCheckpoint {
+            pos: self.pos,
+            control_bytes: self.control_bytes,
+            control_idx: self.control_idx,
+        }
+    }
+
+    /// Resume decoding from a position saved with `checkpoint`.
+    pub fn restore(&mut self, checkpoint: &DecoderCheckpoint) {
+        self.pos = checkpoint.pos;
+        self.control_bytes = checkpoint.control_bytes;
+        self.control_idx = checkpoint.control_idx;
+        self.eof = false;
+    }
 
+    /// Current offset in the input buffer.
+    #[inline]
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Shift the input offset after the first `consumed` bytes of the input
+    /// buffer were dropped.
+    pub fn rebase(&mut self, consumed: usize) {
+        self.pos -= consumed;
+    }
+
+    /// Advance past one row without producing its values.
+    ///
+    /// Returns `true` if a complete row was skipped, `false` if EOF or
+    /// insufficient data.
+    pub fn skip_row(&mut self, input: &[u8], slots_per_row: usize) -> Result<bool> {
         if self.eof {
-            return Ok(());
+            return Ok(false);
         }
 
-        while slots.len() < slots_per_row {
-            // Need a new control block?
+        let mut slot = 0;
+        while slot < slots_per_row {
             if self.control_idx >= 8 {
                 if self.pos + 8 > input.len() {
-                    // Not enough data for another control block -- we're done
-                    return Ok(());
+                    return Ok(false);
                 }
                 self.control_bytes
                     .copy_from_slice(&input[self.pos..self.pos + 8]);
@@ -90,42 +187,32 @@ impl BytecodeDecompressor {
             self.control_idx += 1;
 
             match code {
-                // Hot path first: codes 1..=251 are small numeric values
-                1..=251 => {
-                    let value = (code as f64) - self.bias;
-                    slots.push(SlotValue::Numeric(value));
-                }
-                COMPRESS_SKIP => {
-                    // Padding byte at end of data -- skip, don't produce a slot
-                    continue;
-                }
+                COMPRESS_SKIP => {}
                 COMPRESS_RAW_FOLLOWS => {
-                    // Next 8 bytes are uncompressed data
                     if self.pos + 8 > input.len() {
                         return Err(truncated_err(self.pos + 8, input.len()));
                     }
-                    let mut raw = [0u8; 8];
-                    raw.copy_from_slice(&input[self.pos..self.pos + 8]);
                     self.pos += 8;
-                    slots.push(SlotValue::Raw(raw));
-                }
-                COMPRESS_EIGHT_SPACES => {
-                    slots.push(SlotValue::Spaces);
+                    slot += 1;
                 }
-                COMPRESS_SYSMIS => {
-                    slots.push(SlotValue::Sysmis);
+                COMPRESS_END_OF_FILE if slot > 0 && self.bad_rows != BadRowPolicy::Stop => {
+                    if self.short_case(slot, slots_per_row)? {
+                        return Ok(true);
+                    }
+                    slot = 0;
                 }
                 COMPRESS_END_OF_FILE => {
                     self.eof = true;
-                    return Ok(());
+                    return Ok(false);
                 }
+                _ => slot += 1,
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Decompress one row directly into a raw byte buffer, skipping SlotValue intermediates.
+    /// Decompress one row directly into a raw byte buffer.
     ///
     /// Writes `slots_per_row * 8` bytes into `output` starting at `out_offset`.
     /// Returns `true` if a complete row was written, `false` if EOF or insufficient data.
@@ -215,13 +302,26 @@ impl BytecodeDecompressor {
                     // SAFETY: same as above for dest.
                     unsafe {
                         std::ptr::copy_nonoverlapping(
-                            SYSMIS_RAW.as_ptr(),
+                            self.sysmis_raw.as_ptr(),
                             output.as_mut_ptr().add(dest_offset),
                             8,
                         );
                     }
                     slot += 1;
                 }
+                COMPRESS_END_OF_FILE if slot > 0 && self.bad_rows != BadRowPolicy::Stop => {
+                    if self.short_case(slot, slots_per_row)? {
+                        let row = &mut output[out_offset..out_offset + slots_per_row * 8];
+                        match self.blank_row.get(..row.len()) {
+                            Some(blank) => row.copy_from_slice(blank),
+                            None => row
+                                .chunks_exact_mut(8)
+                                .for_each(|slot| slot.copy_from_slice(&self.sysmis_raw)),
+                        }
+                        return Ok(true);
+                    }
+                    slot = 0;
+                }
                 COMPRESS_END_OF_FILE => {
                     self.eof = true;
                     return Ok(false);
@@ -233,6 +333,94 @@ impl BytecodeDecompressor {
     }
 }
 
+/// Stateful bytecode compressor, the inverse of `BytecodeDecompressor`.
+///
+/// Rows of raw 8-byte slots are appended to an internal buffer as control
+/// blocks of 8 opcodes, each followed by the raw data of its `253` opcodes.
+/// Like the decompressor, control blocks run across row boundaries.
+pub struct BytecodeCompressor {
+    bias: f64,
+    /// Compressed output.
+    buf: Vec<u8>,
+    /// Offset in `buf` of the current control block.
+    control_pos: usize,
+    /// Index into the current control block (0..8; 8 = block full).
+    control_idx: usize,
+}
+
+impl BytecodeCompressor {
+    pub fn new(bias: f64) -> Self {
+        BytecodeCompressor {
+            bias,
+            buf: Vec::new(),
+            control_pos: 0,
+            control_idx: 8, // force starting a new control block on first use
+        }
+    }
+
+    /// Compress one row of `row.len() / 8` slots.
+    pub fn compress_row(&mut self, row: &[u8]) {
+        for slot in row.chunks_exact(8) {
+            let raw: [u8; 8] = slot.try_into().unwrap();
+            let code = self.opcode(raw);
+            self.push_code(code);
+            if code == COMPRESS_RAW_FOLLOWS {
+                self.buf.extend_from_slice(&raw);
+            }
+        }
+    }
+
+    /// The opcode that reproduces `raw` exactly when decompressed.
+    #[inline]
+    fn opcode(&self, raw: [u8; 8]) -> u8 {
+        if raw == SYSMIS_RAW {
+            return COMPRESS_SYSMIS;
+        }
+        if raw == SPACES_RAW {
+            return COMPRESS_EIGHT_SPACES;
+        }
+        let shifted = f64::from_le_bytes(raw) + self.bias;
+        if (1.0..=251.0).contains(&shifted) && shifted.fract() == 0.0 {
+            let code = shifted as u8;
+            // Only bit-exact round-trips (rules out -0.0)
+            if ((code as f64) - self.bias).to_le_bytes() == raw {
+                return code;
+            }
+        }
+        COMPRESS_RAW_FOLLOWS
+    }
+
+    #[inline]
+    fn push_code(&mut self, code: u8) {
+        if self.control_idx == 8 {
+            self.control_pos = self.buf.len();
+            self.buf.extend_from_slice(&[COMPRESS_SKIP; 8]);
+            self.control_idx = 0;
+        }
+        self.buf[self.control_pos + self.control_idx] = code;
+        self.control_idx += 1;
+    }
+
+    /// Remove and return the compressed bytes that can no longer change:
+    /// everything before the current, partially filled control block.
+    pub fn take_complete(&mut self) -> Vec<u8> {
+        let end = if self.control_idx == 8 {
+            self.buf.len()
+        } else {
+            self.control_pos
+        };
+        let rest = self.buf.split_off(end);
+        self.control_pos -= end.min(self.control_pos);
+        std::mem::replace(&mut self.buf, rest)
+    }
+
+    /// Consume the compressor, returning all remaining compressed bytes.
+    /// Unused entries of the final control block are left as padding (`0`).
+    pub fn into_data(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
 /// Cold error path — kept out of the hot decompression loop to reduce icache pressure.
 #[cold]
 fn truncated_err(expected: usize, actual: usize) -> SpssError {
@@ -243,61 +431,90 @@ fn truncated_err(expected: usize, actual: usize) -> SpssError {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_numeric_bias_codes() {
+    /// Decompress one row of `slots` slots into 8-byte values.
+    fn decode_row(input: &[u8], slots: usize) -> Vec<[u8; 8]> {
         let mut decompressor = BytecodeDecompressor::new(100.0);
-        let mut slots = Vec::with_capacity(8);
+        let mut out = vec![0u8; slots * 8];
+        assert!(decompressor.decompress_row_raw(input, slots, &mut out, 0).unwrap());
+        out.chunks_exact(8).map(|c| c.try_into().unwrap()).collect()
+    }
 
+    #[test]
+    fn test_numeric_bias_codes() {
         // Control block: [101, 102, 0, 0, 0, 0, 0, 0]
         // code 101 = value 1.0, code 102 = value 2.0
         let input: Vec<u8> = vec![101, 102, 0, 0, 0, 0, 0, 0];
-
-        decompressor.decompress_row(&input, 2, &mut slots).unwrap();
-        assert_eq!(slots.len(), 2);
-
-        match slots[0] {
-            SlotValue::Numeric(v) => assert!((v - 1.0).abs() < f64::EPSILON),
-            _ => panic!("expected Numeric"),
-        }
-        match slots[1] {
-            SlotValue::Numeric(v) => assert!((v - 2.0).abs() < f64::EPSILON),
-            _ => panic!("expected Numeric"),
-        }
+        let slots = decode_row(&input, 2);
+        assert_eq!(f64::from_le_bytes(slots[0]), 1.0);
+        assert_eq!(f64::from_le_bytes(slots[1]), 2.0);
     }
 
     #[test]
     fn test_sysmis_and_spaces() {
-        let mut decompressor = BytecodeDecompressor::new(100.0);
-        let mut slots = Vec::with_capacity(8);
         let input: Vec<u8> = vec![255, 254, 0, 0, 0, 0, 0, 0];
-
-        decompressor.decompress_row(&input, 2, &mut slots).unwrap();
-        assert!(matches!(slots[0], SlotValue::Sysmis));
-        assert!(matches!(slots[1], SlotValue::Spaces));
+        assert_eq!(decode_row(&input, 2), [SYSMIS_RAW, SPACES_RAW]);
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_raw_follows() {
-        let mut decompressor = BytecodeDecompressor::new(100.0);
-        let mut slots = Vec::with_capacity(8);
-
         let mut input = Vec::new();
         // Control block: [253 (raw follows), 0, 0, 0, 0, 0, 0, 0]
         input.extend_from_slice(&[253, 0, 0, 0, 0, 0, 0, 0]);
         // Raw 8 bytes
         input.extend_from_slice(&3.14_f64.to_le_bytes());
+        assert_eq!(decode_row(&input, 1), [3.14_f64.to_le_bytes()]);
+    }
 
-        decompressor.decompress_row(&input, 1, &mut slots).unwrap();
-        assert_eq!(slots.len(), 1);
-        match slots[0] {
-            SlotValue::Raw(bytes) => {
-                let val = f64::from_le_bytes(bytes);
-                assert!((val - 3.14).abs() < 1e-10);
-            }
-            _ => panic!("expected Raw"),
+    #[test]
+    fn test_compress_roundtrip() {
+        let mut row = Vec::new();
+        row.extend_from_slice(&1.0f64.to_le_bytes()); // code 101
+        row.extend_from_slice(&SYSMIS_RAW); // code 255
+        row.extend_from_slice(&SPACES_RAW); // code 254
+        row.extend_from_slice(&2.75f64.to_le_bytes()); // raw
+        row.extend_from_slice(&(-0.0f64).to_le_bytes()); // raw (not bit-exact as code 100)
+
+        let mut compressor = BytecodeCompressor::new(100.0);
+        compressor.compress_row(&row);
+        compressor.compress_row(&row);
+        let data = compressor.into_data();
+        assert_eq!(&data[..8], &[101, 255, 254, 253, 253, 101, 255, 254]);
+
+        let mut decompressor = BytecodeDecompressor::new(100.0);
+        let mut out = vec![0u8; row.len()];
+        for _ in 0..2 {
+            assert!(decompressor.decompress_row_raw(&data, 5, &mut out, 0).unwrap());
+            assert_eq!(out, row);
         }
     }
 
+    #[test]
+    fn test_compress_take_complete() {
+        // 3 slots per row: rows 1-2 fill the first control block (6 codes) and
+        // spill into the second, so only the first block plus its raw data is complete.
+        let mut row = Vec::new();
+        row.extend_from_slice(&5.0f64.to_le_bytes());
+        row.extend_from_slice(&0.125f64.to_le_bytes());
+        row.extend_from_slice(&SYSMIS_RAW);
+
+        let mut compressor = BytecodeCompressor::new(100.0);
+        for _ in 0..3 {
+            compressor.compress_row(&row);
+        }
+        let mut data = compressor.take_complete();
+        assert_eq!(data.len(), 8 + 3 * 8);
+        data.extend(compressor.into_data());
+
+        let mut decompressor = BytecodeDecompressor::new(100.0);
+        let mut out = vec![0u8; row.len()];
+        for _ in 0..3 {
+            assert!(decompressor.decompress_row_raw(&data, 3, &mut out, 0).unwrap());
+            assert_eq!(out, row);
+        }
+        assert!(!decompressor.decompress_row_raw(&data, 3, &mut out, 0).unwrap());
+    }
+
     #[test]
     fn test_cross_block_rows() {
         // Test that control block state carries across rows.
@@ -306,21 +523,13 @@ mod tests {
         // Row 1 uses codes 101, 102, 103 (slots 1-3)
         // Row 2 uses codes 104, 105, 106 (slots 4-6, from SAME control block)
         let mut decompressor = BytecodeDecompressor::new(100.0);
-        let mut slots = Vec::with_capacity(8);
         let input: Vec<u8> = vec![101, 102, 103, 104, 105, 106, 0, 0];
+        let mut out = vec![0u8; 3 * 8];
 
-        decompressor.decompress_row(&input, 3, &mut slots).unwrap();
-        assert_eq!(slots.len(), 3);
-        match slots[0] {
-            SlotValue::Numeric(v) => assert!((v - 1.0).abs() < f64::EPSILON),
-            _ => panic!("expected 1.0"),
-        }
+        assert!(decompressor.decompress_row_raw(&input, 3, &mut out, 0).unwrap());
+        assert_eq!(f64::from_le_bytes(out[..8].try_into().unwrap()), 1.0);
 
-        decompressor.decompress_row(&input, 3, &mut slots).unwrap();
-        assert_eq!(slots.len(), 3);
-        match slots[0] {
-            SlotValue::Numeric(v) => assert!((v - 4.0).abs() < f64::EPSILON),
-            _ => panic!("expected 4.0"),
-        }
+        assert!(decompressor.decompress_row_raw(&input, 3, &mut out, 0).unwrap());
+        assert_eq!(f64::from_le_bytes(out[..8].try_into().unwrap()), 4.0);
     }
 }
@@ -1,10 +1,15 @@
-use std::io::{Read, Seek, SeekFrom};
+use std::borrow::Cow;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
 
 use flate2::Decompress;
+use flate2::write::ZlibEncoder;
+use rayon::ThreadPool;
 use rayon::prelude::*;
 
 use crate::error::{Result, SpssError};
-use crate::io_utils::SavReader;
+use crate::io_utils::{self, SavReader};
+use crate::options::in_pool;
 
 /// ZSAV zlib header: offsets to the trailer.
 #[derive(Debug, Clone)]
@@ -36,6 +41,79 @@ pub struct ZTrailerEntry {
     pub compressed_size: i32,
 }
 
+impl ZHeader {
+    /// Serialize the 24-byte zlib header.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.zheader_offset.to_le_bytes());
+        out.extend_from_slice(&self.ztrailer_offset.to_le_bytes());
+        out.extend_from_slice(&self.ztrailer_length.to_le_bytes());
+    }
+}
+
+impl ZTrailer {
+    /// Build the trailer for blocks written back-to-back right after a zlib
+    /// header at `zheader_offset`. `sizes` holds each block's
+    /// (uncompressed, compressed) size.
+    pub fn for_blocks(bias: f64, zheader_offset: i64, block_size: usize, sizes: &[(usize, usize)]) -> ZTrailer {
+        let mut uncompressed_offset = zheader_offset;
+        let mut compressed_offset = zheader_offset + 24;
+        let entries = sizes
+            .iter()
+            .map(|&(uncompressed, compressed)| {
+                let entry = ZTrailerEntry {
+                    uncompressed_offset,
+                    compressed_offset,
+                    uncompressed_size: uncompressed as i32,
+                    compressed_size: compressed as i32,
+                };
+                uncompressed_offset += uncompressed as i64;
+                compressed_offset += compressed as i64;
+                entry
+            })
+            .collect::<Vec<_>>();
+        ZTrailer {
+            bias: -(bias as i64),
+            zero: 0,
+            block_size: block_size as i32,
+            n_blocks: entries.len() as i32,
+            entries,
+        }
+    }
+
+    /// Check that the trailer repeats the header's bias (negated, as an
+    /// integer). Decoding uses the header's; a trailer that disagrees means
+    /// one of them is damaged.
+    pub fn check_bias(&self, header_bias: f64) -> Result<()> {
+        if self.bias.checked_neg() == Some(header_bias as i64) {
+            Ok(())
+        } else {
+            Err(SpssError::InvalidBias(format!(
+                "the zlib trailer's bias {} does not match the header's bias {header_bias}",
+                self.bias
+            )))
+        }
+    }
+
+    /// Total serialized length of the trailer in bytes.
+    pub fn serialized_len(&self) -> usize {
+        24 + 24 * self.entries.len()
+    }
+
+    /// Serialize the trailer.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.bias.to_le_bytes());
+        out.extend_from_slice(&self.zero.to_le_bytes());
+        out.extend_from_slice(&self.block_size.to_le_bytes());
+        out.extend_from_slice(&self.n_blocks.to_le_bytes());
+        for entry in &self.entries {
+            out.extend_from_slice(&entry.uncompressed_offset.to_le_bytes());
+            out.extend_from_slice(&entry.compressed_offset.to_le_bytes());
+            out.extend_from_slice(&entry.uncompressed_size.to_le_bytes());
+            out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        }
+    }
+}
+
 /// Read the ZSAV zlib header (24 bytes, immediately after the dictionary termination).
 pub fn read_zheader<R: Read>(reader: &mut SavReader<R>) -> Result<ZHeader> {
     Ok(ZHeader {
@@ -55,20 +133,38 @@ pub fn read_ztrailer<R: Read + Seek>(
     reader
         .inner_mut()
         .seek(SeekFrom::Start(zheader.ztrailer_offset as u64))?;
+    reader.set_offset(zheader.ztrailer_offset as u64);
 
     let bias = reader.read_i64()?;
     let zero = reader.read_i64()?;
     let block_size = reader.read_i32()?;
-    let n_blocks = reader.read_i32()?;
+    let count = reader.read_count(24)?;
+    let n_blocks = count as i32;
 
-    let mut entries = Vec::with_capacity(n_blocks as usize);
-    for _ in 0..n_blocks {
-        entries.push(ZTrailerEntry {
+    let mut entries = Vec::with_capacity(count.min(io_utils::MAX_PREALLOC));
+    let max_block = reader.limits().max_record_bytes;
+    if usize::try_from(block_size).is_ok_and(|size| size > max_block) {
+        return Err(SpssError::LimitExceeded(format!(
+            "zlib blocks of {block_size} bytes (the limit is {max_block})"
+        )));
+    }
+    for i in 0..count {
+        let entry = ZTrailerEntry {
             uncompressed_offset: reader.read_i64()?,
             compressed_offset: reader.read_i64()?,
             uncompressed_size: reader.read_i32()?,
             compressed_size: reader.read_i32()?,
-        });
+        };
+        if entry.uncompressed_offset < 0
+            || entry.compressed_offset < 0
+            || entry.compressed_size < 0
+            || !(0..=block_size).contains(&entry.uncompressed_size)
+        {
+            return Err(SpssError::Zlib(format!(
+                "zlib trailer entry {i} is invalid: {entry:?} (block size {block_size})"
+            )));
+        }
+        entries.push(entry);
     }
 
     Ok(ZTrailer {
@@ -80,67 +176,398 @@ pub fn read_ztrailer<R: Read + Seek>(
     })
 }
 
-/// Decompress all ZSAV blocks into a single pre-allocated byte buffer.
-///
-/// Each block is zlib-compressed. The decompressed blocks contain
-/// bytecode-compressed data that must be further processed by the
-/// bytecode decompressor.
+/// On-demand decompression of ZSAV blocks.
 ///
-/// Phase 1: Read all compressed blocks sequentially (I/O-bound).
-/// Phase 2: Decompress all blocks in parallel directly into the output buffer,
-///          avoiding per-block Vec allocations and the final concat copy.
-pub fn decompress_zsav_blocks<R: Read + Seek>(
-    reader: &mut SavReader<R>,
-    trailer: &ZTrailer,
-) -> Result<Vec<u8>> {
-    // Phase 1: Sequential I/O — read all compressed blocks + compute output offsets
-    let mut compressed_blocks: Vec<(Vec<u8>, usize, usize)> = Vec::with_capacity(trailer.entries.len());
-    let mut total_uncompressed: usize = 0;
-
-    for entry in &trailer.entries {
-        reader
-            .inner_mut()
-            .seek(SeekFrom::Start(entry.compressed_offset as u64))?;
-        let compressed = reader.read_bytes(entry.compressed_size as usize)?;
-        let uncompressed_size = entry.uncompressed_size as usize;
-        compressed_blocks.push((compressed, uncompressed_size, total_uncompressed));
-        total_uncompressed += uncompressed_size;
-    }
-
-    // Phase 2: Pre-allocate single output buffer, decompress blocks in parallel
-    // directly into non-overlapping slices — no per-block Vecs, no final concat.
-    let mut output = vec![0u8; total_uncompressed];
-    // Store as usize (Send+Sync) to avoid Rust 2024 disjoint field capture issues.
-    let base_addr = output.as_mut_ptr() as usize;
-
-    compressed_blocks
-        .par_iter()
-        .try_for_each(|(compressed, uncompressed_size, offset)| {
-            // SAFETY: Each block writes to [offset..offset+uncompressed_size],
-            // and these ranges are non-overlapping (offsets are cumulative sums).
-            // The output Vec lives longer than this par_iter scope.
-            let dest = unsafe {
-                std::slice::from_raw_parts_mut(
-                    (base_addr + *offset) as *mut u8,
-                    *uncompressed_size,
-                )
+/// Holds a window of inflated bytecode covering the scanner's current
+/// position. When the decoder runs low, consumed bytes are dropped and the
+/// next few blocks are read and inflated (in parallel, one block per rayon
+/// thread), so memory stays bounded by a handful of blocks regardless of
+/// file size.
+pub struct ZsavBlocks {
+    entries: Vec<ZTrailerEntry>,
+    /// Offset of each block within the concatenated inflated stream.
+    block_starts: Vec<usize>,
+    total_len: usize,
+    /// Index of the next block to inflate.
+    next_block: usize,
+    /// Inflated bytes, starting at offset `window_start` of the stream.
+    window: Vec<u8>,
+    window_start: usize,
+    /// Number of blocks inflated per refill.
+    blocks_per_fill: usize,
+    /// The whole file, when memory-mapped; blocks are inflated from slices
+    /// of it instead of being read.
+    mapped: Option<Box<dyn AsRef<[u8]> + Send + Sync>>,
+    /// Set when blocks are read in file order without the trailer.
+    inline: Option<InlineBlocks>,
+    /// Pool blocks are inflated on (rayon's global pool when `None`).
+    pool: Option<Arc<ThreadPool>>,
+}
+
+/// State for inflating blocks straight off a non-seekable stream: each
+/// block is a complete zlib stream, so the end of one marks the start of
+/// the next.
+struct InlineBlocks {
+    /// File offset of the first compressed byte not yet consumed.
+    offset: u64,
+    /// File offset of the trailer, where block data ends.
+    end: u64,
+    /// Bytes read past `offset` but not yet consumed.
+    pending: Vec<u8>,
+}
+
+/// Read size while inflating inline blocks.
+const INLINE_READ: usize = 64 * 1024;
+
+impl ZsavBlocks {
+    pub fn new(trailer: &ZTrailer) -> ZsavBlocks {
+        let mut block_starts = Vec::with_capacity(trailer.entries.len());
+        let mut total_len = 0;
+        for entry in &trailer.entries {
+            block_starts.push(total_len);
+            total_len += entry.uncompressed_size as usize;
+        }
+        ZsavBlocks {
+            entries: trailer.entries.clone(),
+            block_starts,
+            total_len,
+            next_block: 0,
+            window: Vec::new(),
+            window_start: 0,
+            blocks_per_fill: rayon::current_num_threads().max(1),
+            mapped: None,
+            inline: None,
+            pool: None,
+        }
+    }
+
+    /// Blocks read sequentially from a stream positioned at the first block
+    /// (file offset `start`), ending at the trailer offset `end`. The trailer
+    /// is never read, so `total_len` is unknown (0) and only forward reads
+    /// are possible.
+    pub fn inline(start: u64, end: u64) -> ZsavBlocks {
+        ZsavBlocks {
+            entries: Vec::new(),
+            block_starts: Vec::new(),
+            total_len: 0,
+            next_block: 0,
+            window: Vec::new(),
+            window_start: 0,
+            blocks_per_fill: 1,
+            mapped: None,
+            inline: Some(InlineBlocks {
+                offset: start,
+                end,
+                pending: Vec::new(),
+            }),
+            pool: None,
+        }
+    }
+
+    /// Inflate blocks from slices of `file` (the complete file contents)
+    /// rather than reading them.
+    #[cfg_attr(not(feature = "mmap"), allow(dead_code))]
+    pub fn with_mapped(mut self, file: Box<dyn AsRef<[u8]> + Send + Sync>) -> ZsavBlocks {
+        self.mapped = Some(file);
+        self
+    }
+
+    /// Inflate blocks on `pool`, one block per thread per refill.
+    pub fn set_pool(&mut self, pool: Option<Arc<ThreadPool>>) {
+        if self.inline.is_none() {
+            self.blocks_per_fill = match &pool {
+                Some(pool) => pool.current_num_threads().max(1),
+                None => rayon::current_num_threads().max(1),
             };
+        }
+        self.pool = pool;
+    }
+
+    /// Total length of the inflated bytecode stream.
+    pub fn total_len(&self) -> usize {
+        self.total_len
+    }
+
+    /// Inflated bytes currently held, starting at `window_start()`.
+    pub fn window(&self) -> &[u8] {
+        &self.window
+    }
 
-            let mut decompressor = Decompress::new(true);
-            match decompressor.decompress(
-                compressed,
-                dest,
-                flate2::FlushDecompress::Finish,
-            ) {
-                Ok(flate2::Status::Ok | flate2::Status::StreamEnd) => Ok(()),
-                Ok(flate2::Status::BufError) => {
-                    Err(SpssError::Zlib("decompression buffer too small".to_string()))
+    /// Stream offset of `window()[0]`.
+    pub fn window_start(&self) -> usize {
+        self.window_start
+    }
+
+    /// Drop the first `consumed` bytes of the window, then inflate further
+    /// blocks until at least `min_ahead` bytes are held or no blocks remain.
+    pub fn refill<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        consumed: usize,
+        min_ahead: usize,
+    ) -> Result<()> {
+        if consumed > 0 {
+            self.window.drain(..consumed);
+            self.window_start += consumed;
+        }
+        if let Some(inline) = &mut self.inline {
+            while self.window.len() < min_ahead && inline.offset < inline.end {
+                let block = inline.next_block(reader.inner_mut())?;
+                self.window.extend_from_slice(&block);
+            }
+            return Ok(());
+        }
+        while self.window.len() < min_ahead && self.next_block < self.entries.len() {
+            let end = (self.next_block + self.blocks_per_fill).min(self.entries.len());
+            for block in self.inflate_blocks(reader, self.next_block..end)? {
+                self.window.extend_from_slice(&block);
+            }
+            self.next_block = end;
+        }
+        Ok(())
+    }
+
+    /// Reposition the window at stream offset `pos`, returning the position
+    /// of `pos` within the new window.
+    pub fn seek<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        pos: usize,
+    ) -> Result<usize> {
+        if pos >= self.window_start && pos <= self.window_start + self.window.len() {
+            return Ok(pos - self.window_start);
+        }
+        if self.inline.is_some() {
+            return Err(SpssError::Unsupported(
+                "cannot seek backwards in a non-seekable .zsav stream".to_string(),
+            ));
+        }
+        let block = self.block_starts.partition_point(|&start| start <= pos).saturating_sub(1);
+        self.window.clear();
+        if pos >= self.total_len {
+            self.window_start = self.total_len;
+            self.next_block = self.entries.len();
+            return Ok(0);
+        }
+        self.window_start = self.block_starts[block];
+        self.next_block = block;
+        let rel = pos - self.window_start;
+        self.refill(reader, 0, rel + 1)?;
+        Ok(rel)
+    }
+
+    /// File offset of the next block a refill will read (the end of the
+    /// last block once all are read).
+    pub fn next_compressed_offset(&self) -> u64 {
+        if let Some(inline) = &self.inline {
+            return inline.offset;
+        }
+        match self.entries.get(self.next_block) {
+            Some(entry) => entry.compressed_offset as u64,
+            None => self.compressed_extent(0),
+        }
+    }
+
+    /// File offset just past the compressed blocks that refills would read
+    /// to hold `ahead` more inflated bytes than the window does now.
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub fn compressed_extent(&self, ahead: usize) -> u64 {
+        let target = (self.window_start + self.window.len()).saturating_add(ahead);
+        let mut end = self.next_block;
+        while end < self.entries.len() && self.block_starts[end] < target {
+            end = (end + self.blocks_per_fill).min(self.entries.len());
+        }
+        match end.checked_sub(1).map(|i| &self.entries[i]) {
+            Some(entry) => (entry.compressed_offset + entry.compressed_size as i64) as u64,
+            None => 0,
+        }
+    }
+
+    /// Whether every block has been inflated into the window.
+    pub fn exhausted(&self) -> bool {
+        match &self.inline {
+            Some(inline) => inline.offset >= inline.end,
+            None => self.next_block >= self.entries.len(),
+        }
+    }
+
+    /// Read the given blocks sequentially, then inflate them in parallel.
+    fn inflate_blocks<R: Read + Seek>(
+        &self,
+        reader: &mut SavReader<R>,
+        blocks: std::ops::Range<usize>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut compressed: Vec<(Cow<[u8]>, usize)> = Vec::with_capacity(blocks.len());
+        for entry in &self.entries[blocks] {
+            let start = entry.compressed_offset as usize;
+            let len = entry.compressed_size as usize;
+            let bytes = match &self.mapped {
+                Some(file) => {
+                    let file = (**file).as_ref();
+                    let block = file.get(start..start + len).ok_or(SpssError::TruncatedFile {
+                        expected: start + len,
+                        actual: file.len(),
+                    })?;
+                    Cow::Borrowed(block)
                 }
-                Err(e) => {
-                    Err(SpssError::Zlib(format!("zlib decompression error: {e}")))
+                None => {
+                    reader.inner_mut().seek(SeekFrom::Start(start as u64))?;
+                    Cow::Owned(reader.read_bytes(len)?)
                 }
+            };
+            compressed.push((bytes, entry.uncompressed_size as usize));
+        }
+
+        in_pool(self.pool.as_deref(), || {
+            compressed
+                .par_iter()
+                .map(|(bytes, uncompressed_size)| {
+                    let mut dest = vec![0u8; *uncompressed_size];
+                    let mut decompressor = Decompress::new(true);
+                    match decompressor.decompress(bytes, &mut dest, flate2::FlushDecompress::Finish)
+                    {
+                        Ok(flate2::Status::Ok | flate2::Status::StreamEnd) => Ok(dest),
+                        Ok(flate2::Status::BufError) => {
+                            Err(SpssError::Zlib("decompression buffer too small".to_string()))
+                        }
+                        Err(e) => Err(SpssError::Zlib(format!("zlib decompression error: {e}"))),
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+impl InlineBlocks {
+    /// Inflate the zlib stream starting at `offset`, reading from `reader`
+    /// (positioned at `offset + pending.len()`) as needed.
+    fn next_block<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>> {
+        let mut decompressor = Decompress::new(true);
+        let mut out = Vec::new();
+        loop {
+            let consumed = decompressor.total_in() as usize;
+            if consumed == self.pending.len() {
+                self.read_more(reader)?;
+            }
+            if out.capacity() - out.len() < INLINE_READ {
+                out.reserve(INLINE_READ.max(out.len()));
+            }
+            let status = decompressor
+                .decompress_vec(
+                    &self.pending[consumed..],
+                    &mut out,
+                    flate2::FlushDecompress::None,
+                )
+                .map_err(|e| SpssError::Zlib(format!("zlib decompression error: {e}")))?;
+            if status == flate2::Status::StreamEnd {
+                let used = decompressor.total_in() as usize;
+                self.pending.drain(..used);
+                self.offset += used as u64;
+                return Ok(out);
             }
-        })?;
+        }
+    }
+
+    /// Append up to `INLINE_READ` more bytes of block data to `pending`.
+    fn read_more<R: Read>(&mut self, reader: &mut R) -> Result<()> {
+        let read_to = self.offset + self.pending.len() as u64;
+        let want = (self.end.saturating_sub(read_to) as usize).min(INLINE_READ);
+        let start = self.pending.len();
+        self.pending.resize(start + want, 0);
+        let n = read_full(reader, &mut self.pending[start..])?;
+        self.pending.truncate(start + n);
+        if n == 0 {
+            return Err(SpssError::TruncatedFile {
+                expected: self.end as usize,
+                actual: read_to as usize,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Read as many bytes as possible into `buf`, handling partial reads.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut pos = 0;
+    while pos < buf.len() {
+        match reader.read(&mut buf[pos..]) {
+            Ok(0) => break,
+            Ok(n) => pos += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(pos)
+}
+
+/// Split bytecode data into `block_size` chunks and zlib-compress them in
+/// parallel at the given level (0-9). Returns the compressed blocks in order.
+pub fn compress_zsav_blocks(data: &[u8], block_size: usize, level: u32) -> Result<Vec<Vec<u8>>> {
+    data.par_chunks(block_size.max(1))
+        .map(|block| {
+            let mut encoder = ZlibEncoder::new(
+                Vec::with_capacity(block.len() / 2),
+                flate2::Compression::new(level),
+            );
+            encoder.write_all(block)?;
+            Ok(encoder.finish()?)
+        })
+        .collect()
+}
 
-    Ok(output)
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_zsav_blocks_roundtrip() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let blocks = compress_zsav_blocks(&data, 4096, 6).unwrap();
+        assert_eq!(blocks.len(), 3);
+
+        let sizes: Vec<(usize, usize)> = data
+            .chunks(4096)
+            .zip(&blocks)
+            .map(|(raw, z)| (raw.len(), z.len()))
+            .collect();
+        let trailer = ZTrailer::for_blocks(100.0, 0, 4096, &sizes);
+        let zheader = ZHeader {
+            zheader_offset: 0,
+            ztrailer_offset: 24 + blocks.iter().map(Vec::len).sum::<usize>() as i64,
+            ztrailer_length: trailer.serialized_len() as i64,
+        };
+
+        let mut file = Vec::new();
+        zheader.write(&mut file);
+        for block in &blocks {
+            file.extend_from_slice(block);
+        }
+        trailer.write(&mut file);
+
+        let mut reader = SavReader::new(Cursor::new(file));
+        let zheader = read_zheader(&mut reader).unwrap();
+        let trailer = read_ztrailer(&mut reader, &zheader).unwrap();
+        assert_eq!(trailer.n_blocks, 3);
+        let mut blocks = ZsavBlocks::new(&trailer);
+        assert_eq!(blocks.total_len(), data.len());
+
+        // Bounded window: consume in small steps, never holding everything.
+        blocks.blocks_per_fill = 1;
+        let mut out = Vec::new();
+        while !blocks.exhausted() || !blocks.window().is_empty() {
+            let take = blocks.window().len().min(1000);
+            out.extend_from_slice(&blocks.window()[..take]);
+            blocks.refill(&mut reader, take, 1000).unwrap();
+            assert!(blocks.window().len() <= 4096 + 1000);
+        }
+        assert_eq!(out, data);
+
+        // Seek back into the middle of block 1.
+        let rel = blocks.seek(&mut reader, 5000).unwrap();
+        blocks.refill(&mut reader, 0, rel + 10).unwrap();
+        assert_eq!(blocks.window_start(), 4096);
+        assert_eq!(&blocks.window()[rel..rel + 10], &data[5000..5010]);
+    }
 }
@@ -38,9 +38,14 @@ pub const RECORD_TYPE_DICT_TERMINATION: i32 = 999;
 pub const INFO_MR_SETS: i32 = 7;
 pub const INFO_INTEGER: i32 = 3;
 pub const INFO_FLOAT: i32 = 4;
+pub const INFO_DATE_INFO: i32 = 6;
 pub const INFO_VAR_DISPLAY: i32 = 11;
 pub const INFO_LONG_NAMES: i32 = 13;
 pub const INFO_VERY_LONG_STRINGS: i32 = 14;
+pub const INFO_EXTENDED_NCASES: i32 = 16;
+pub const INFO_FILE_ATTRIBUTES: i32 = 17;
+pub const INFO_VAR_ATTRIBUTES: i32 = 18;
+pub const INFO_EXTENDED_MR_SETS: i32 = 19;
 pub const INFO_ENCODING: i32 = 20;
 pub const INFO_LONG_STRING_LABELS: i32 = 21;
 pub const INFO_LONG_STRING_MISSING: i32 = 22;
@@ -64,6 +69,14 @@ impl Compression {
             _ => None,
         }
     }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Compression::None => 0,
+            Compression::Bytecode => 1,
+            Compression::Zlib => 2,
+        }
+    }
 }
 
 /// Variable measurement level.
@@ -85,6 +98,16 @@ impl Measure {
         }
     }
 
+    /// Subtype 11 code for this level (0 for unknown).
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Measure::Unknown => 0,
+            Measure::Nominal => 1,
+            Measure::Ordinal => 2,
+            Measure::Scale => 3,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Measure::Unknown => "unknown",
@@ -95,6 +118,55 @@ impl Measure {
     }
 }
 
+/// Variable role, stored as the `$@Role` variable attribute (subtype 18).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Input,
+    Target,
+    Both,
+    None,
+    Partition,
+    Split,
+}
+
+impl Role {
+    /// Role for a `$@Role` attribute value; `None` if it is not a known code.
+    pub fn from_code(code: &str) -> Option<Role> {
+        match code.trim() {
+            "0" => Some(Role::Input),
+            "1" => Some(Role::Target),
+            "2" => Some(Role::Both),
+            "3" => Some(Role::None),
+            "4" => Some(Role::Partition),
+            "5" => Some(Role::Split),
+            _ => None,
+        }
+    }
+
+    /// `$@Role` attribute value for this role.
+    pub fn code(self) -> &'static str {
+        match self {
+            Role::Input => "0",
+            Role::Target => "1",
+            Role::Both => "2",
+            Role::None => "3",
+            Role::Partition => "4",
+            Role::Split => "5",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Input => "input",
+            Role::Target => "target",
+            Role::Both => "both",
+            Role::None => "none",
+            Role::Partition => "partition",
+            Role::Split => "split",
+        }
+    }
+}
+
 /// Variable alignment.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Alignment {
@@ -114,6 +186,15 @@ impl Alignment {
         }
     }
 
+    /// Subtype 11 code for this alignment (unknown is written as left).
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Alignment::Unknown | Alignment::Left => 0,
+            Alignment::Right => 1,
+            Alignment::Center => 2,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Alignment::Unknown => "unknown",
@@ -140,6 +221,10 @@ pub const SPSS_EPOCH_OFFSET_DAYS: i64 = 141_428;
 /// Seconds from SPSS epoch (1582-10-14) to Unix epoch (1970-01-01).
 pub const SPSS_EPOCH_OFFSET_SECONDS: f64 = 12_219_379_200.0;
 
+/// Days from SPSS epoch (1582-10-14) to 1960-01-01, the epoch of Stata and
+/// SAS dates.
+pub const EPOCH_1960_OFFSET_DAYS: i64 = SPSS_EPOCH_OFFSET_DAYS - 3653;
+
 /// Microseconds per second.
 pub const MICROS_PER_SECOND: f64 = 1_000_000.0;
 
@@ -286,6 +371,13 @@ impl FormatType {
         }
     }
 
+    /// Look up a format type by its SPSS prefix (e.g. "F", "DATETIME"), case-insensitively.
+    pub fn from_prefix(prefix: &str) -> Option<FormatType> {
+        (1u8..=41)
+            .filter_map(FormatType::from_u8)
+            .find(|t| t.prefix().eq_ignore_ascii_case(prefix))
+    }
+
     /// Whether this format type represents a string variable.
     pub fn is_string(&self) -> bool {
         matches!(self, FormatType::A | FormatType::Ahex)
@@ -361,6 +453,58 @@ impl SpssFormat {
         })
     }
 
+    /// A stand-in for a packed specification whose type is unknown: F with
+    /// its width and decimals for numeric variables (F8.2 if they make no
+    /// sense for F), A of the variable's width for strings.
+    pub fn guess(packed: i32, var_type: &VarType) -> SpssFormat {
+        let raw = packed as u32;
+        let width = ((raw >> 8) & 0xFF) as u8;
+        let decimals = (raw & 0xFF) as u8;
+        match var_type {
+            VarType::String(w) => SpssFormat {
+                format_type: FormatType::A,
+                width: (*w).min(255) as u8,
+                decimals: 0,
+            },
+            VarType::Numeric if (1..=40).contains(&width) && decimals < width => SpssFormat {
+                format_type: FormatType::F,
+                width,
+                decimals,
+            },
+            VarType::Numeric => SpssFormat {
+                format_type: FormatType::F,
+                width: 8,
+                decimals: 2,
+            },
+        }
+    }
+
+    /// Encode as a packed i32 format specification (inverse of `from_packed`).
+    pub fn to_packed(&self) -> i32 {
+        ((self.format_type as i32) << 16) | ((self.width as i32) << 8) | self.decimals as i32
+    }
+
+    /// Parse an SPSS format string like "F8.2", "A50" or "DATETIME20".
+    ///
+    /// Widths above 255 (very long strings, e.g. "A1000") are clamped to 255,
+    /// since the packed format only has one byte for the width.
+    pub fn parse(s: &str) -> Option<SpssFormat> {
+        let s = s.trim();
+        let digits_at = s.find(|c: char| c.is_ascii_digit())?;
+        let format_type = FormatType::from_prefix(&s[..digits_at])?;
+        let (width_str, decimals_str) = match s[digits_at..].split_once('.') {
+            Some((w, d)) => (w, d),
+            None => (&s[digits_at..], "0"),
+        };
+        let width: usize = width_str.parse().ok()?;
+        let decimals: u8 = decimals_str.parse().ok()?;
+        Some(SpssFormat {
+            format_type,
+            width: width.min(255) as u8,
+            decimals,
+        })
+    }
+
     /// Render as a human-readable SPSS format string like "F8.2" or "A50".
     pub fn to_spss_string(&self) -> String {
         if self.format_type.is_string() || self.format_type.is_date_time() {
@@ -423,12 +567,29 @@ mod tests {
     #[test]
     fn test_format_string_type() {
         // A50 = type 1, width 50, decimals 0
-        let packed = (1 << 16) | (50 << 8) | 0;
+        let packed = (1 << 16) | (50 << 8);
         let fmt = SpssFormat::from_packed(packed).unwrap();
         assert_eq!(fmt.format_type, FormatType::A);
         assert_eq!(fmt.to_spss_string(), "A50");
     }
 
+    #[test]
+    fn test_format_parse_roundtrip() {
+        let fmt = SpssFormat::parse("F8.2").unwrap();
+        assert_eq!(fmt.format_type, FormatType::F);
+        assert_eq!((fmt.width, fmt.decimals), (8, 2));
+        assert_eq!(SpssFormat::from_packed(fmt.to_packed()), Some(fmt));
+
+        let fmt = SpssFormat::parse("datetime20").unwrap();
+        assert_eq!(fmt.format_type, FormatType::DateTime);
+        assert_eq!(fmt.to_spss_string(), "DATETIME20");
+
+        assert_eq!(SpssFormat::parse("A1000").unwrap().width, 255);
+        assert_eq!(SpssFormat::parse("PIBHEX4").unwrap().format_type, FormatType::PibHex);
+        assert!(SpssFormat::parse("XYZ8").is_none());
+        assert!(SpssFormat::parse("F").is_none());
+    }
+
     #[test]
     fn test_compression_from_i32() {
         assert_eq!(Compression::from_i32(0), Some(Compression::None));
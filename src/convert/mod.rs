@@ -0,0 +1,132 @@
+//! Converting SPSS files to other formats without materializing them.
+//!
+//! Every converter streams batches from a scanner straight into its
+//! writer, so memory stays bounded by the batch size however large the
+//! input is. The text formats render numbers through the variables' SPSS
+//! display formats (see `TextColumn`), optionally with value labels.
+
+mod csv;
+mod ndjson;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, Float64Type, Schema, UInt64Type};
+use indexmap::IndexMap;
+
+use crate::constants::SpssFormat;
+use crate::display;
+use crate::error::Result;
+use crate::metadata::{SpssMetadata, Value};
+use crate::options::{Float32Mode, LabelMode, ScanOptions, StringType, TemporalMode};
+use crate::scanner::SavScanner;
+
+pub use self::csv::{CsvOptions, sav_to_csv, write_csv};
+pub use self::ndjson::{NdjsonOptions, sav_to_ndjson, write_ndjson};
+#[cfg(feature = "parquet")]
+pub use self::parquet::{ParquetOptions, sav_to_parquet};
+
+/// Open `input` for a text export: the columns, rows and missing-value
+/// handling of `scan` apply, while numbers, dates and codes are read raw
+/// (the exporters render them from the SPSS formats).
+pub(crate) fn text_scanner(
+    input: impl AsRef<Path>,
+    scan: &ScanOptions,
+) -> Result<SavScanner<BufReader<File>>> {
+    let scan = ScanOptions {
+        string_type: StringType::Utf8View,
+        temporal: TemporalMode::Raw,
+        labels: LabelMode::Codes,
+        integers: false,
+        missing_reasons: false,
+        booleans: false,
+        float32: Float32Mode::Off,
+        run_end_encoded: Vec::new(),
+        ..scan.clone()
+    };
+    crate::scan_sav_with(input, &scan)
+}
+
+/// A cell as read from a text-export batch.
+pub(crate) enum Cell<'a> {
+    Null,
+    Number(f64),
+    Text(&'a str),
+}
+
+/// How one output column is rendered as text.
+pub(crate) struct TextColumn {
+    pub name: String,
+    /// Print format of the variable (none for case numbers).
+    pub format: Option<SpssFormat>,
+    /// Value labels to write instead of codes, when requested.
+    labels: Option<IndexMap<Value, String>>,
+}
+
+impl TextColumn {
+    /// One entry per field of `schema`, looked up in `metadata` by the
+    /// variable's name in the file.
+    pub fn for_schema(schema: &Schema, metadata: &SpssMetadata, value_labels: bool) -> Vec<Self> {
+        schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let var = metadata
+                    .renamed_columns
+                    .get(field.name())
+                    .unwrap_or(field.name());
+                TextColumn {
+                    name: field.name().clone(),
+                    format: metadata.format(var).and_then(SpssFormat::parse),
+                    labels: value_labels
+                        .then(|| metadata.value_labels(var).cloned())
+                        .flatten(),
+                }
+            })
+            .collect()
+    }
+
+    /// The cell at `row` of `array`, with its value label if it has one.
+    pub fn cell<'a>(&'a self, array: &'a dyn Array, row: usize) -> Cell<'a> {
+        if array.is_null(row) {
+            return Cell::Null;
+        }
+        let value = match array.data_type() {
+            DataType::Float64 => Value::Numeric(array.as_primitive::<Float64Type>().value(row)),
+            DataType::UInt64 => {
+                return Cell::Number(array.as_primitive::<UInt64Type>().value(row) as f64);
+            }
+            DataType::Utf8View => {
+                let text = array.as_string_view().value(row);
+                match self
+                    .labels
+                    .as_ref()
+                    .and_then(|l| l.get(&Value::String(text.into())))
+                {
+                    Some(label) => return Cell::Text(label),
+                    None => return Cell::Text(text),
+                }
+            }
+            _ => return Cell::Null,
+        };
+        match self.labels.as_ref().and_then(|l| l.get(&value)) {
+            Some(label) => Cell::Text(label),
+            None => match value {
+                Value::Numeric(v) => Cell::Number(v),
+                Value::String(_) => Cell::Null,
+            },
+        }
+    }
+
+    /// `value` as SPSS displays it under this column's format.
+    pub fn display(&self, value: f64) -> String {
+        match &self.format {
+            Some(format) => display::format_value(value, format),
+            None => value.to_string(),
+        }
+    }
+}
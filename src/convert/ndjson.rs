@@ -0,0 +1,212 @@
+//! Newline-delimited JSON export.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::constants::TemporalKind;
+use crate::convert::{Cell, TextColumn, text_scanner};
+use crate::display;
+use crate::error::Result;
+use crate::options::{MissingPolicy, ScanOptions};
+
+/// Options for `sav_to_ndjson` / `write_ndjson`.
+#[derive(Debug, Clone)]
+pub struct NdjsonOptions {
+    /// Columns, rows and missing-value handling of the input (user-missing
+    /// values are null by default). Options that change how values are
+    /// represented (labels, temporal, integers, ...) do not apply.
+    pub scan: ScanOptions,
+    /// Write value labels (as strings) instead of codes where a code has
+    /// one.
+    pub value_labels: bool,
+}
+
+impl Default for NdjsonOptions {
+    fn default() -> Self {
+        NdjsonOptions {
+            scan: ScanOptions::new().missing(MissingPolicy::Null),
+            value_labels: false,
+        }
+    }
+}
+
+/// Convert an SPSS .sav/.zsav file to newline-delimited JSON, one object
+/// per case, returning the number of rows written.
+///
+/// Numbers are JSON numbers, missing values are `null`, dates and
+/// date-times are ISO 8601 strings (`"2024-10-14"`,
+/// `"2024-10-14T13:45:30"`), and time durations are seconds.
+///
+/// # Example
+/// ```no_run
+/// use ambers::convert::{NdjsonOptions, sav_to_ndjson};
+///
+/// let options = NdjsonOptions {
+///     value_labels: true,
+///     ..NdjsonOptions::default()
+/// };
+/// sav_to_ndjson("survey.sav", "survey.ndjson", &options).unwrap();
+/// ```
+pub fn sav_to_ndjson(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &NdjsonOptions,
+) -> Result<usize> {
+    let file = BufWriter::with_capacity(8 * 1024 * 1024, File::create(output)?);
+    write_ndjson(input, file, options)
+}
+
+/// Write an SPSS file as newline-delimited JSON to any writer (see
+/// `sav_to_ndjson`).
+pub fn write_ndjson<W: Write>(
+    input: impl AsRef<Path>,
+    mut writer: W,
+    options: &NdjsonOptions,
+) -> Result<usize> {
+    let mut scanner = text_scanner(input, &options.scan)?;
+    let columns =
+        TextColumn::for_schema(&scanner.schema(), scanner.metadata(), options.value_labels);
+    // Keys are the same on every line: escape them once.
+    let keys: Vec<String> = columns
+        .iter()
+        .map(|c| {
+            let mut key = String::new();
+            push_json_string(&mut key, &c.name);
+            key
+        })
+        .collect();
+
+    let mut line = String::new();
+    let mut rows = 0;
+    while let Some(batch) = scanner.next_batch()? {
+        for row in 0..batch.num_rows() {
+            line.clear();
+            line.push('{');
+            for (i, column) in columns.iter().enumerate() {
+                if i > 0 {
+                    line.push(',');
+                }
+                line.push_str(&keys[i]);
+                line.push(':');
+                match column.cell(batch.column(i).as_ref(), row) {
+                    Cell::Null => line.push_str("null"),
+                    Cell::Number(v) => push_json_number(&mut line, column, v),
+                    Cell::Text(text) => push_json_string(&mut line, text),
+                }
+            }
+            line.push_str("}\n");
+            writer.write_all(line.as_bytes())?;
+        }
+        rows += batch.num_rows();
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+/// A number, or an ISO 8601 string for dates and date-times.
+fn push_json_number(line: &mut String, column: &TextColumn, value: f64) {
+    let kind = column
+        .format
+        .as_ref()
+        .and_then(|f| f.format_type.temporal_kind());
+    match kind {
+        Some(TemporalKind::Date) | Some(TemporalKind::Timestamp) => {
+            let iso = display::iso_date_time(value, kind == Some(TemporalKind::Timestamp));
+            push_json_string(line, &iso);
+        }
+        _ if value.is_finite() => {
+            let _ = write!(line, "{value}");
+        }
+        _ => line.push_str("null"),
+    }
+}
+
+/// A quoted JSON string with the required escapes.
+fn push_json_string(line: &mut String, text: &str) {
+    line.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(line, "\\u{:04x}", u32::from(c));
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Date32Array, Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+
+    use super::*;
+    use crate::metadata::{MissingSpec, SpssMetadata, Value};
+
+    #[test]
+    fn test_write_ndjson() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("born", DataType::Date32, true),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![Some(1.0), Some(9.0), None])),
+                Arc::new(Date32Array::from(vec![Some(0), None, Some(19_000)])),
+                Arc::new(StringArray::from(vec![
+                    "plain",
+                    "say \"hi\"\n",
+                    "tab\there",
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let sav = dir.path().join("data.sav");
+        crate::write_sav(&sav, &batch, &meta).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(
+            write_ndjson(&sav, &mut out, &NdjsonOptions::default()).unwrap(),
+            3
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"q1\":1,\"born\":\"1970-01-01\",\"note\":\"plain\"}\n\
+             {\"q1\":null,\"born\":null,\"note\":\"say \\\"hi\\\"\\n\"}\n\
+             {\"q1\":null,\"born\":\"2022-01-08\",\"note\":\"tab\\there\"}\n"
+        );
+
+        let mut out = Vec::new();
+        let options = NdjsonOptions {
+            scan: ScanOptions::new().columns(&["q1"]).limit(2),
+            value_labels: true,
+        };
+        write_ndjson(&sav, &mut out, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"q1\":\"Yes\"}\n{\"q1\":9}\n"
+        );
+    }
+}
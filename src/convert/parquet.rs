@@ -0,0 +1,157 @@
+//! Streaming SAV → Parquet conversion.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::datatypes::{Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::error::Result;
+use crate::metadata::SpssMetadata;
+use crate::options::ScanOptions;
+
+/// Options for `sav_to_parquet`.
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    /// How the input is read (columns, rows, output types, ...).
+    pub scan: ScanOptions,
+    /// Maximum rows per Parquet row group.
+    pub row_group_size: usize,
+    /// Column chunk compression.
+    pub compression: Compression,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            scan: ScanOptions::default(),
+            row_group_size: 1024 * 1024,
+            compression: Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+/// Convert an SPSS .sav/.zsav file to Parquet, returning the number of rows
+/// written.
+///
+/// The schema metadata (file label, encoding, ...) is kept, and each field
+/// carries its variable's label, SPSS format and measure level as
+/// `spss.label`, `spss.format` and `spss.measure`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::convert::{ParquetOptions, sav_to_parquet};
+///
+/// let options = ParquetOptions {
+///     row_group_size: 250_000,
+///     ..ParquetOptions::default()
+/// };
+/// let rows = sav_to_parquet("survey.sav", "survey.parquet", &options).unwrap();
+/// println!("{rows} rows written");
+/// ```
+pub fn sav_to_parquet(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: &ParquetOptions,
+) -> Result<usize> {
+    let mut scanner = crate::scan_sav_with(input, &options.scan)?;
+    let schema = Arc::new(with_field_metadata(&scanner.schema(), scanner.metadata()));
+    let props = WriterProperties::builder()
+        .set_compression(options.compression)
+        .set_max_row_group_size(options.row_group_size)
+        .build();
+    let file = BufWriter::with_capacity(8 * 1024 * 1024, File::create(output)?);
+    let mut writer = ArrowWriter::try_new(file, Arc::clone(&schema), Some(props))?;
+    let mut rows = 0;
+    while let Some(batch) = scanner.next_batch()? {
+        rows += batch.num_rows();
+        writer.write(&batch.with_schema(Arc::clone(&schema))?)?;
+    }
+    writer.close()?;
+    Ok(rows)
+}
+
+/// `schema` with each variable's label, format and measure attached to its
+/// field. Columns that are not variables (case numbers, missing reasons)
+/// are left as they are.
+fn with_field_metadata(schema: &Schema, metadata: &SpssMetadata) -> Schema {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = metadata
+                .renamed_columns
+                .get(field.name())
+                .unwrap_or(field.name());
+            let mut facts = HashMap::new();
+            if let Some(label) = metadata.label(name).filter(|l| !l.is_empty()) {
+                facts.insert("spss.label".to_string(), label.to_string());
+            }
+            if let Some(format) = metadata.format(name) {
+                facts.insert("spss.format".to_string(), format.to_string());
+            }
+            if let Some(measure) = metadata.measure(name) {
+                facts.insert("spss.measure".to_string(), measure.as_str().to_string());
+            }
+            field.as_ref().clone().with_metadata(facts)
+        })
+        .collect();
+    Schema::new(fields).with_metadata(schema.metadata().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{ArrayRef, Float64Array, StringArray};
+    use arrow::datatypes::DataType;
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_sav_to_parquet() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Float64Array::from_iter_values((0..10).map(f64::from))),
+            Arc::new(StringArray::from_iter_values(
+                (0..10).map(|i| format!("c{i}")),
+            )),
+        ];
+        let batch = RecordBatch::try_new(schema, columns).unwrap();
+        let mut meta = SpssMetadata {
+            file_label: "Wave 1".into(),
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("id".into(), "Respondent".into());
+        let dir = tempfile::tempdir().unwrap();
+        let sav = dir.path().join("wave1.sav");
+        crate::write_sav(&sav, &batch, &meta).unwrap();
+
+        let parquet = dir.path().join("wave1.parquet");
+        let options = ParquetOptions {
+            scan: ScanOptions::new().batch_size(4),
+            row_group_size: 5,
+            ..ParquetOptions::default()
+        };
+        assert_eq!(sav_to_parquet(&sav, &parquet, &options).unwrap(), 10);
+
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&parquet).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        let schema = reader.schema().clone();
+        assert_eq!(schema.metadata()["spss.file_label"], "Wave 1");
+        assert_eq!(schema.field(0).metadata()["spss.label"], "Respondent");
+        assert_eq!(schema.field(1).metadata()["spss.format"], "A2");
+        let rows: usize = reader.build().unwrap().map(|b| b.unwrap().num_rows()).sum();
+        assert_eq!(rows, 10);
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
 
 use indexmap::IndexMap;
@@ -8,12 +9,16 @@ use encoding_rs::Encoding;
 use crate::constants::*;
 use crate::encoding;
 use crate::error::{Result, SpssError};
+use crate::float_format::{FloatFormat, NumberFormat};
 use crate::header::FileHeader;
-use crate::info_records::{self, InfoRecord, InfoRecordHeader};
+use crate::info_records::{self, InfoRecord, InfoRecordHeader, attributes};
 use crate::io_utils::SavReader;
 use crate::metadata::{self, MissingSpec, SpssMetadata, Value};
+use crate::options::ReadLimits;
+use crate::product::{Quirk, WriterProfile};
 use crate::value_labels::{self, RawValue, ValueLabelSet};
 use crate::variable::VariableRecord;
+use crate::warning::{SpssWarning, WarningKind};
 use crate::{document, value_labels as vl};
 
 /// All parsed dictionary data before resolution.
@@ -32,6 +37,11 @@ pub struct RawDictionary {
     pub long_string_labels: Vec<crate::info_records::long_string_labels::LongStringLabelSet>,
     pub long_string_missing: Vec<crate::info_records::long_string_missing::LongStringMissingEntry>,
     pub mr_sets: Vec<crate::info_records::mr_sets::RawMrSet>,
+    pub file_attributes: Vec<u8>,
+    pub variable_attributes: Vec<u8>,
+    pub date_info: Vec<[i32; 3]>,
+    pub extended_ncases: Option<i64>,
+    pub unknown_records: Vec<(i32, Vec<u8>)>,
 }
 
 /// The resolved dictionary ready for data reading.
@@ -41,8 +51,31 @@ pub struct ResolvedDictionary {
     pub variables: Vec<VariableRecord>,
     /// The file's character encoding.
     pub file_encoding: &'static Encoding,
+    /// How the file stores numbers. Dictionary values are converted to
+    /// native IEEE already, and so is `header.bias`; data slots are not.
+    pub numbers: NumberFormat,
     /// Assembled metadata.
     pub metadata: SpssMetadata,
+    /// Number of cases, when the writer recorded it: the header's count,
+    /// or the subtype 16 count when the header's is unknown or saturated.
+    pub ncases: Option<usize>,
+    /// String variables whose values are in another encoding than the
+    /// file's (`ScanOptions::encodings`).
+    pub encodings: Vec<(String, &'static Encoding)>,
+    /// Variables named by their upper-cased short name, for want of a long
+    /// name, whose short name is stored in another case: {name given ->
+    /// short name as stored}. See `ScanOptions::names_as_stored`.
+    pub stored_names: IndexMap<String, String>,
+}
+
+impl ResolvedDictionary {
+    /// Encoding of the values of `var`.
+    pub fn value_encoding(&self, var: &VariableRecord) -> &'static Encoding {
+        self.encodings
+            .iter()
+            .find(|(name, _)| name == &var.long_name)
+            .map_or(self.file_encoding, |&(_, encoding)| encoding)
+    }
 }
 
 /// Parse the entire dictionary section of a SAV file.
@@ -53,29 +86,85 @@ pub fn parse_dictionary<R: Read>(
     reader: &mut SavReader<R>,
     header: &FileHeader,
 ) -> Result<RawDictionary> {
-    let mut variables = Vec::new();
-    let mut value_label_sets = Vec::new();
-    let mut document_lines = Vec::new();
-    let mut integer_info = None;
-    let mut float_info = None;
-    let mut var_display = Vec::new();
-    let mut long_names = Vec::new();
-    let mut very_long_strings = Vec::new();
-    let mut encoding_name = None;
-    let mut long_string_labels = Vec::new();
-    let mut long_string_missing = Vec::new();
-    let mut mr_sets = Vec::new();
-
-    let mut slot_index = 0;
-
+    let mut raw = RawDictionary::new(header);
     loop {
         let record_type = reader.read_i32()?;
+        if !raw.read_record(reader, record_type)? {
+            return Ok(raw);
+        }
+    }
+}
+
+impl RawDictionary {
+    /// An empty dictionary for a file with `header`.
+    pub fn new(header: &FileHeader) -> RawDictionary {
+        RawDictionary {
+            header: header.clone(),
+            variables: Vec::new(),
+            value_label_sets: Vec::new(),
+            document_lines: Vec::new(),
+            integer_info: None,
+            float_info: None,
+            var_display: Vec::new(),
+            long_names: Vec::new(),
+            very_long_strings: Vec::new(),
+            encoding_name: None,
+            long_string_labels: Vec::new(),
+            long_string_missing: Vec::new(),
+            mr_sets: Vec::new(),
+            file_attributes: Vec::new(),
+            variable_attributes: Vec::new(),
+            date_info: Vec::new(),
+            extended_ncases: None,
+            unknown_records: Vec::new(),
+        }
+    }
+
+    /// Read the body of one record, whose type has already been read.
+    ///
+    /// Returns false after the termination record (type 999). A record that
+    /// fails to parse leaves the dictionary unchanged, and the error says
+    /// where it starts (`SpssError::InvalidRecord`).
+    pub fn read_record<R: Read>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        record_type: i32,
+    ) -> Result<bool> {
+        let offset = reader.offset().saturating_sub(4);
+        let mut subtype = None;
+        self.read_record_body(reader, record_type, &mut subtype)
+            .map_err(|source| match source {
+                SpssError::UnexpectedRecordType { .. } => source,
+                source => SpssError::InvalidRecord {
+                    record_type,
+                    subtype,
+                    offset,
+                    variable: self.variables.last().map(|v| v.short_name.clone()),
+                    source: Box::new(source),
+                },
+            })
+    }
 
+    fn read_record_body<R: Read>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        record_type: i32,
+        subtype: &mut Option<i32>,
+    ) -> Result<bool> {
         match record_type {
             RECORD_TYPE_VARIABLE => {
-                let var = VariableRecord::parse(reader, slot_index)?;
-                slot_index += 1;
-                variables.push(var);
+                let limits = *reader.limits();
+                if self.variables.len() >= limits.max_columns {
+                    return Err(SpssError::LimitExceeded(format!(
+                        "more than {} variable records",
+                        limits.max_columns
+                    )));
+                }
+                let var = VariableRecord::parse(reader, self.variables.len())?;
+                if let VarType::String(width) = var.var_type {
+                    check_string_width(&var.short_name, width, &limits)?;
+                }
+                self.variables.push(var);
             }
 
             RECORD_TYPE_VALUE_LABEL => {
@@ -88,7 +177,7 @@ pub fn parse_dictionary<R: Read>(
                     )));
                 }
                 let indices = vl::parse_value_label_variables(reader)?;
-                value_label_sets.push(ValueLabelSet {
+                self.value_label_sets.push(ValueLabelSet {
                     labels,
                     variable_indices: indices,
                 });
@@ -96,69 +185,145 @@ pub fn parse_dictionary<R: Read>(
 
             RECORD_TYPE_DOCUMENT => {
                 let lines = document::parse_document(reader)?;
-                document_lines.extend(lines);
+                self.document_lines.extend(lines);
             }
 
             RECORD_TYPE_INFO => {
                 let info_header = InfoRecordHeader::parse(reader)?;
+                *subtype = Some(info_header.subtype);
                 let record = info_records::parse_info_record(reader, &info_header)?;
                 match record {
-                    InfoRecord::IntegerInfo(info) => integer_info = Some(info),
-                    InfoRecord::FloatInfo(info) => float_info = Some(info),
-                    InfoRecord::VarDisplay(entries) => var_display = entries,
-                    InfoRecord::LongNames(names) => long_names = names,
-                    InfoRecord::VeryLongStrings(entries) => very_long_strings = entries,
-                    InfoRecord::Encoding(name) => encoding_name = Some(name),
-                    InfoRecord::LongStringLabels(labels) => long_string_labels = labels,
-                    InfoRecord::LongStringMissing(entries) => long_string_missing = entries,
-                    InfoRecord::MrSets(sets) => mr_sets = sets,
-                    InfoRecord::Unknown { .. } => {} // skip
+                    InfoRecord::IntegerInfo(info) => self.integer_info = Some(info),
+                    InfoRecord::FloatInfo(info) => self.float_info = Some(info),
+                    InfoRecord::VarDisplay(entries) => self.var_display = entries,
+                    InfoRecord::LongNames(names) => self.long_names = names,
+                    InfoRecord::VeryLongStrings(entries) => {
+                        for (name, width) in &entries {
+                            check_string_width(name, *width, reader.limits())?;
+                        }
+                        self.very_long_strings = entries;
+                    }
+                    InfoRecord::Encoding(name) => self.encoding_name = Some(name),
+                    InfoRecord::LongStringLabels(labels) => self.long_string_labels = labels,
+                    InfoRecord::LongStringMissing(entries) => self.long_string_missing = entries,
+                    InfoRecord::MrSets(sets) => self.mr_sets.extend(sets),
+                    InfoRecord::FileAttributes(data) => self.file_attributes = data,
+                    InfoRecord::VariableAttributes(data) => self.variable_attributes = data,
+                    InfoRecord::DateInfo(groups) => self.date_info = groups,
+                    InfoRecord::ExtendedNcases(n) => self.extended_ncases = n,
+                    InfoRecord::Unknown { subtype, data } => {
+                        self.unknown_records.push((subtype, data))
+                    }
                 }
             }
 
             RECORD_TYPE_DICT_TERMINATION => {
                 // Read the filler int
                 let _filler = reader.read_i32()?;
-                break;
+                return Ok(false);
             }
 
             _ => {
                 return Err(SpssError::UnexpectedRecordType {
                     record_type,
-                    offset: 0, // we don't track offset in the stream
+                    offset: reader.offset() - 4,
                 });
             }
         }
+        Ok(true)
     }
+}
 
-    Ok(RawDictionary {
-        header: header.clone(),
-        variables,
-        value_label_sets,
-        document_lines,
-        integer_info,
-        float_info,
-        var_display,
-        long_names,
-        very_long_strings,
-        encoding_name,
-        long_string_labels,
-        long_string_missing,
-        mr_sets,
-    })
+/// Fail if a string variable is wider than `limits` allow.
+fn check_string_width(name: &str, width: usize, limits: &ReadLimits) -> Result<()> {
+    if width > limits.max_string_width {
+        return Err(SpssError::LimitExceeded(format!(
+            "variable {name} is {width} bytes wide (the limit is {})",
+            limits.max_string_width
+        )));
+    }
+    Ok(())
+}
+
+/// Rename variables whose long names clash, ignoring case as SPSS does,
+/// with an earlier variable's: a subtype 13 record mapping two short names
+/// to one long name. The first keeps the name; each later one takes its
+/// short name if that is free, else the long name with a `_2`, `_3`, ...
+/// suffix. Returns {new name -> long name in the file}.
+fn deduplicate_names(variables: &mut [VariableRecord]) -> IndexMap<String, String> {
+    let mut taken = HashSet::new();
+    let mut clashing = Vec::new();
+    for (i, var) in variables.iter().enumerate() {
+        if !var.is_ghost && !taken.insert(var.long_name.to_lowercase()) {
+            clashing.push(i);
+        }
+    }
+    let mut renamed = IndexMap::new();
+    for i in clashing {
+        let var = &mut variables[i];
+        let mut name = var.short_name.to_uppercase();
+        let mut suffix = 2;
+        while !taken.insert(name.to_lowercase()) {
+            name = format!("{}_{suffix}", var.long_name);
+            suffix += 1;
+        }
+        renamed.insert(name.clone(), std::mem::replace(&mut var.long_name, name));
+    }
+    renamed
 }
 
 /// Resolve the raw dictionary into a fully processed dictionary with metadata.
+#[allow(
+    clippy::field_reassign_with_default,
+    clippy::manual_div_ceil,
+    clippy::manual_pattern_char_comparison
+)]
 pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
     let mut variables = raw.variables;
+    let mut warnings = Vec::new();
+
+    // 1. Recognize the writer, then determine character encoding
+    let mut writer = WriterProfile::detect(&raw.header.product, raw.integer_info.as_ref());
+    let file_encoding = determine_encoding(&raw.encoding_name, &raw.integer_info, &mut writer);
+
+    // Labels are padded with blanks (and by some writers NULs) up to their
+    // stated length
+    let trim_nuls = writer.nul_padded_labels();
+    let nul_padded = Cell::new(false);
+    let label_text = |bytes: &[u8]| {
+        let text = encoding::decode_str_lossy(bytes, file_encoding);
+        let mut label = text.trim_end_matches(|c: char| c == ' ' || c == '\u{FFFD}');
+        if trim_nuls && label.ends_with('\0') {
+            nul_padded.set(true);
+            label = label.trim_end_matches(|c: char| c == ' ' || c == '\0' || c == '\u{FFFD}');
+        }
+        label.to_string()
+    };
 
-    // 1. Determine character encoding
-    let file_encoding = determine_encoding(&raw.encoding_name, &raw.integer_info);
+    // 1b. Numbers were read as IEEE; convert those of IBM and VAX files
+    let mut numbers = NumberFormat {
+        bswap: raw.header.bswap,
+        float: raw.integer_info.as_ref().map_or(FloatFormat::Ieee, |info| {
+            FloatFormat::from_code(info.floating_point_rep)
+        }),
+        specials: None,
+    };
+    if let Some(info) = &raw.float_info {
+        numbers = numbers.with_specials([info.sysmis, info.highest, info.lowest]);
+    }
+    for var in &mut variables {
+        var.missing_values.map_numbers(|v| numbers.convert(v));
+    }
 
-    // 2. Apply long variable names (subtype 13)
-    let long_name_map: HashMap<String, String> = raw.long_names.into_iter().collect();
+    // 2. Apply long variable names (subtype 13). Short names are matched
+    // ignoring case: writers do not agree on the case they store them in.
+    let long_name_map: HashMap<String, String> = raw
+        .long_names
+        .into_iter()
+        .map(|(short, long)| (short.to_uppercase(), long))
+        .collect();
     for var in &mut variables {
-        if let Some(long_name) = long_name_map.get(&var.short_name) {
+        if let Some(long_name) = long_name_map.get(&var.short_name.to_uppercase()) {
             var.long_name = long_name.clone();
         }
     }
@@ -170,9 +335,13 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
     // (except the last which may be shorter), followed by type=-1 continuation
     // records. The type=-1 records are already marked as ghosts, but the named
     // segment records (segments 2+) need to be marked as ghosts too.
-    let vls_map: HashMap<String, usize> = raw.very_long_strings.into_iter().collect();
+    let vls_map: HashMap<String, usize> = raw
+        .very_long_strings
+        .iter()
+        .map(|(name, width)| (name.to_uppercase(), *width))
+        .collect();
     for i in 0..variables.len() {
-        let lookup_name = variables[i].short_name.clone();
+        let lookup_name = variables[i].short_name.to_uppercase();
         if let Some(&true_width) = vls_map.get(&lookup_name) {
             variables[i].var_type = VarType::String(true_width);
             let n_segments = (true_width + 251) / 252;
@@ -190,9 +359,48 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
                     }
                     j += 1;
                 }
+                if segments_found < n_segments {
+                    warnings.push(SpssWarning::new(
+                        WarningKind::VeryLongStringSegments,
+                        Some(&variables[i].long_name),
+                        format!(
+                            "width {true_width} needs {n_segments} segment variables, \
+                             but only {segments_found} follow"
+                        ),
+                    ));
+                }
             }
         }
     }
+    for (name, _) in &raw.very_long_strings {
+        let name_key = name.to_uppercase();
+        if !variables.iter().any(|v| v.short_name.to_uppercase() == name_key) {
+            warnings.push(SpssWarning::new(
+                WarningKind::VeryLongStringSegments,
+                None,
+                format!("the very long string record names {name:?}, which is not in the file"),
+            ));
+        }
+    }
+
+    // 3b. Make names unique
+    let renamed_variables = deduplicate_names(&mut variables);
+    for (given, original) in &renamed_variables {
+        warnings.push(SpssWarning::new(
+            WarningKind::RenamedVariable,
+            Some(given),
+            format!("renamed: {original:?} is the long name of an earlier variable"),
+        ));
+    }
+    let stored_names: IndexMap<String, String> = variables
+        .iter()
+        .filter(|v| !v.is_ghost && v.long_name != v.short_name)
+        .filter(|v| {
+            let key = v.short_name.to_uppercase();
+            v.long_name == key && long_name_map.get(&key) != Some(&v.long_name)
+        })
+        .map(|v| (v.long_name.clone(), v.short_name.clone()))
+        .collect();
 
     // 4. Apply variable display info (subtype 11)
     //
@@ -221,6 +429,16 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
         display_idx += 1;
         var_idx += 1;
     }
+    if !raw.var_display.is_empty() && raw.var_display.len() != display_idx {
+        warnings.push(SpssWarning::new(
+            WarningKind::DisplayEntries,
+            None,
+            format!(
+                "the display record has {} entries for {display_idx} variable records",
+                raw.var_display.len()
+            ),
+        ));
+    }
 
     // 5. Build metadata
     let mut meta = SpssMetadata::default();
@@ -229,16 +447,13 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
     meta.compression = raw.header.compression;
     meta.creation_time = raw.header.creation_date.clone();
     meta.modification_time = raw.header.creation_time.clone();
-    meta.number_rows = if raw.header.ncases >= 0 {
-        Some(raw.header.ncases as i64)
-    } else {
-        None
-    };
+    meta.number_rows = case_count(raw.header.ncases, raw.extended_ncases);
     meta.file_format = if raw.header.compression == Compression::Zlib {
         "zsav".to_string()
     } else {
         "sav".to_string()
     };
+    meta.renamed_variables = renamed_variables;
 
     // Document lines -> notes
     meta.notes = raw
@@ -257,9 +472,7 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
 
         // Variable label
         if let Some(ref label_bytes) = var.label {
-            let label = encoding::decode_str_lossy(label_bytes, file_encoding)
-                .trim_end_matches(|c: char| c == ' ' || c == '\u{FFFD}')
-                .to_string();
+            let label = label_text(label_bytes);
             if !label.is_empty() {
                 meta.variable_labels.insert(name.clone(), label);
             }
@@ -277,6 +490,18 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
             meta.spss_variable_types
                 .insert(name.clone(), format_str);
         }
+        if let Some(code) = var.unknown_format {
+            meta.unknown_formats.insert(name.clone(), code);
+            warnings.push(SpssWarning::new(
+                WarningKind::UnknownFormat,
+                Some(&name),
+                format!(
+                    "print format type {} is unknown; read as {}",
+                    (code as u32 >> 16) & 0xFF,
+                    meta.spss_variable_types.get(&name).map_or("", String::as_str)
+                ),
+            ));
+        }
 
         // Rust type
         let rust_type = match &var.var_type {
@@ -345,6 +570,7 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
         .map(|v| (v.slot_index, &v.var_type))
         .collect();
 
+    let bswap = raw.header.bswap;
     for label_set in &raw.value_label_sets {
         // Determine if these are string or numeric labels based on first linked variable
         let is_string = label_set
@@ -360,7 +586,8 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
                 let value = if is_string {
                     match raw_val {
                         RawValue::Numeric(v) => {
-                            let bytes = v.to_le_bytes();
+                            // Back to the bytes as stored.
+                            let bytes = if bswap { v.to_be_bytes() } else { v.to_le_bytes() };
                             let s = encoding::decode_str_lossy(
                                 crate::io_utils::trim_trailing_padding(&bytes),
                                 file_encoding,
@@ -376,13 +603,11 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
                     }
                 } else {
                     match raw_val {
-                        RawValue::Numeric(v) => Value::Numeric(*v),
+                        RawValue::Numeric(v) => Value::Numeric(numbers.convert(*v)),
                         RawValue::String(_) => Value::Numeric(0.0),
                     }
                 };
-                let label = encoding::decode_str_lossy(label_bytes, file_encoding)
-                    .trim_end_matches(|c: char| c == ' ' || c == '\u{FFFD}')
-                    .to_string();
+                let label = label_text(label_bytes);
                 (value, label)
             })
             .collect();
@@ -406,9 +631,7 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
                     crate::io_utils::trim_trailing_padding(value_bytes),
                     file_encoding,
                 ).into_owned());
-                let label = encoding::decode_str_lossy(label_bytes, file_encoding)
-                    .trim_end_matches(|c: char| c == ' ' || c == '\u{FFFD}')
-                    .to_string();
+                let label = label_text(label_bytes);
                 (value, label)
             })
             .collect();
@@ -457,7 +680,7 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
     let short_to_long: HashMap<String, String> = variables
         .iter()
         .filter(|v| !v.is_ghost)
-        .map(|v| (v.short_name.clone(), v.long_name.clone()))
+        .map(|v| (v.short_name.to_uppercase(), v.long_name.clone()))
         .collect();
     for raw_mr in &raw.mr_sets {
         let resolved_vars: Vec<String> = raw_mr
@@ -482,31 +705,158 @@ pub fn resolve_dictionary(raw: RawDictionary) -> Result<ResolvedDictionary> {
         }
     }
 
+    // 10. Resolve file and variable attributes (subtypes 17 and 18)
+    // Variable sets name long variables; the $@Role attribute becomes the
+    // variable's role.
+    if !raw.file_attributes.is_empty() {
+        let text = encoding::decode_str_lossy(&raw.file_attributes, file_encoding);
+        meta.file_attributes = attributes::parse_file_attributes(&text);
+    }
+    if !raw.variable_attributes.is_empty() {
+        let text = encoding::decode_str_lossy(&raw.variable_attributes, file_encoding);
+        for (name, mut set) in attributes::parse_variable_attributes(&text) {
+            let Some(name) = meta
+                .variable_names
+                .iter()
+                .find(|n| n.eq_ignore_ascii_case(&name))
+                .cloned()
+            else {
+                continue;
+            };
+            if let Some(role) = set
+                .get("$@Role")
+                .and_then(|values| values.first())
+                .and_then(|code| Role::from_code(code))
+            {
+                set.shift_remove("$@Role");
+                meta.variable_role.insert(name.clone(), role);
+            }
+            if !set.is_empty() {
+                meta.variable_attributes.insert(name, set);
+            }
+        }
+    }
+
+    // 11. TRENDS date info (subtype 6), kept as written
+    meta.date_info = raw.date_info;
+
+    // 12. Info records ambers does not interpret, kept for round-tripping
+    for (subtype, data) in &raw.unknown_records {
+        warnings.push(SpssWarning::new(
+            WarningKind::UnknownRecord,
+            None,
+            format!("record subtype {subtype} ({} bytes) is not interpreted", data.len()),
+        ));
+    }
+    meta.unknown_records = raw.unknown_records;
+
+    // 13. Text that did not decode cleanly. Labels may end in a character
+    // cut short by SPSS's length limit; that is trimmed above, not lossy.
+    lossy_text_warnings(&meta, &mut warnings);
+    meta.warnings = warnings;
+    if nul_padded.get() {
+        writer.note(Quirk::NulPaddedLabels);
+    }
+    meta.writer = writer;
+
     // Filter to visible (non-ghost) variables
     let visible_variables: Vec<VariableRecord> =
         variables.into_iter().filter(|v| !v.is_ghost).collect();
+    // Cases without variables cannot be read back as rows, so the file is
+    // reported as holding none, matching the empty batches the scanners return.
+    if visible_variables.is_empty() {
+        meta.number_rows = Some(0);
+    }
 
+    let mut header = raw.header;
+    header.bias = numbers.convert(header.bias);
     Ok(ResolvedDictionary {
-        header: raw.header,
+        header,
         variables: visible_variables,
         file_encoding,
+        numbers,
+        ncases: meta.number_rows.and_then(|n| usize::try_from(n).ok()),
         metadata: meta,
+        encodings: Vec::new(),
+        stored_names,
     })
 }
 
+/// Warn about each variable whose label, value labels or user-missing
+/// strings hold U+FFFD, and about lossy notes.
+fn lossy_text_warnings(meta: &SpssMetadata, warnings: &mut Vec<SpssWarning>) {
+    let lossy = |s: &str| s.contains('\u{FFFD}');
+    let lossy_value = |v: &Value| matches!(v, Value::String(s) if lossy(s));
+    for name in &meta.variable_names {
+        let mut parts = Vec::new();
+        if meta.variable_labels.get(name).is_some_and(|l| lossy(l)) {
+            parts.push("label");
+        }
+        if meta
+            .variable_value_labels
+            .get(name)
+            .is_some_and(|labels| labels.iter().any(|(v, l)| lossy_value(v) || lossy(l)))
+        {
+            parts.push("value labels");
+        }
+        if meta.variable_missing.get(name).is_some_and(|specs| {
+            specs
+                .iter()
+                .any(|s| matches!(s, MissingSpec::StringValue(v) if lossy(v)))
+        }) {
+            parts.push("missing values");
+        }
+        if !parts.is_empty() {
+            warnings.push(SpssWarning::new(
+                WarningKind::LossyText,
+                Some(name),
+                format!("{} not valid in {}", parts.join(" and "), meta.file_encoding),
+            ));
+        }
+    }
+    if meta.notes.iter().any(|n| lossy(n)) {
+        warnings.push(SpssWarning::new(
+            WarningKind::LossyText,
+            None,
+            format!("notes not valid in {}", meta.file_encoding),
+        ));
+    }
+}
+
+/// The file's case count: the header's i32 count, unless it is -1
+/// (unknown) or `i32::MAX` (saturated) and subtype 16 recorded the real one.
+fn case_count(header_ncases: i32, extended_ncases: Option<i64>) -> Option<i64> {
+    match (header_ncases, extended_ncases) {
+        (-1 | i32::MAX, Some(n)) => Some(n),
+        (n, _) if n >= 0 => Some(n as i64),
+        _ => None,
+    }
+}
+
 /// Determine the character encoding from available info records.
 fn determine_encoding(
     encoding_name: &Option<String>,
     integer_info: &Option<crate::info_records::integer_info::IntegerInfo>,
+    writer: &mut WriterProfile,
 ) -> &'static Encoding {
     // Priority 1: Subtype 20 encoding name
     if let Some(name) = encoding_name {
         return encoding::encoding_from_name(name);
     }
 
-    // Priority 2: Subtype 3 character code
-    if let Some(info) = integer_info {
-        return encoding::encoding_from_code_page(info.character_code);
+    // Priority 2: Subtype 3 character code; 1 to 4 name character sets
+    // (EBCDIC, 7-bit and 8-bit ASCII, DEC Kanji) rather than code pages
+    let code_page = integer_info.as_ref().map(|info| info.character_code);
+    if let Some(code_page) = code_page
+        && !(writer.utf8_only() && code_page <= 4)
+    {
+        return encoding::encoding_from_code_page(code_page);
+    }
+
+    // Writers that only produce UTF-8 do not always say so
+    if writer.utf8_only() {
+        writer.note(Quirk::AssumedUtf8);
+        return encoding_rs::UTF_8;
     }
 
     // Default: windows-1252 (historical SPSS default on Windows)
@@ -0,0 +1,295 @@
+//! Rendering numeric values the way SPSS displays them under a print format.
+//!
+//! Used by the text exporters, where raw doubles would print dates as
+//! seconds since 1582 and drop the decimals a format asks for.
+
+use crate::constants::{FormatType, SPSS_EPOCH_OFFSET_DAYS, SpssFormat};
+
+const MONTHS: [&str; 12] = [
+    "JANUARY",
+    "FEBRUARY",
+    "MARCH",
+    "APRIL",
+    "MAY",
+    "JUNE",
+    "JULY",
+    "AUGUST",
+    "SEPTEMBER",
+    "OCTOBER",
+    "NOVEMBER",
+    "DECEMBER",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "SUNDAY",
+    "MONDAY",
+    "TUESDAY",
+    "WEDNESDAY",
+    "THURSDAY",
+    "FRIDAY",
+    "SATURDAY",
+];
+
+/// Render `value` as SPSS shows it under `format`, without padding to the
+/// format width: decimals as declared, grouping for COMMA/DOT/DOLLAR,
+/// and dates and times in the format's layout (`DATE11` → `14-OCT-2024`,
+/// `TIME8` → `13:45:00`, ...).
+pub fn format_value(value: f64, format: &SpssFormat) -> String {
+    let width = usize::from(format.width);
+    let decimals = usize::from(format.decimals);
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    match format.format_type {
+        FormatType::Comma => grouped(value, decimals, ',', '.'),
+        FormatType::Dot => grouped(value, decimals, '.', ','),
+        FormatType::Dollar => {
+            let digits = grouped(value.abs(), decimals, ',', '.');
+            if value < 0.0 {
+                format!("-${digits}")
+            } else {
+                format!("${digits}")
+            }
+        }
+        FormatType::Pct => format!("{value:.decimals$}%"),
+        FormatType::N => format!("{value:0width$.0}"),
+        FormatType::E => scientific(value, decimals),
+        FormatType::Date
+        | FormatType::ADate
+        | FormatType::EDate
+        | FormatType::SDate
+        | FormatType::JDate
+        | FormatType::Moyr
+        | FormatType::Qyr
+        | FormatType::Wkyr => date(value, format.format_type, width),
+        FormatType::DateTime | FormatType::YmDhms => {
+            let (secs, fraction) = split_seconds(value, decimals);
+            let day = secs.div_euclid(86_400);
+            let layout = if format.format_type == FormatType::DateTime {
+                FormatType::Date
+            } else {
+                FormatType::SDate
+            };
+            let mut out = date(day as f64 * 86_400.0, layout, 11);
+            if layout == FormatType::SDate {
+                out = out.replace('/', "-");
+            }
+            let long = if layout == FormatType::Date { 20 } else { 19 };
+            out.push(' ');
+            out.push_str(&clock(secs.rem_euclid(86_400), &fraction, width >= long));
+            out
+        }
+        FormatType::Time => signed_duration(value, decimals, |secs, fraction| {
+            clock(secs, fraction, width >= 8)
+        }),
+        FormatType::DTime => signed_duration(value, decimals, |secs, fraction| {
+            let time = clock(secs % 86_400, fraction, width >= 11);
+            format!("{:02} {time}", secs / 86_400)
+        }),
+        FormatType::MTime => signed_duration(value, decimals, |secs, fraction| {
+            format!("{:02}:{:02}{fraction}", secs / 60, secs % 60)
+        }),
+        FormatType::Wkday => name(value, &WEEKDAYS, width),
+        FormatType::Month => name(value, &MONTHS, width),
+        _ => format!("{value:.decimals$}"),
+    }
+}
+
+/// An SPSS date (seconds since 1582-10-14) as ISO 8601: `2024-10-14`, or
+/// with `time` `2024-10-14T13:45:30` (plus milliseconds when not whole).
+pub fn iso_date_time(value: f64, time: bool) -> String {
+    let spss_days = (value / 86_400.0).floor() as i64;
+    let (y, m, d) = civil_from_days(spss_days - SPSS_EPOCH_OFFSET_DAYS);
+    if !time {
+        return format!("{y:04}-{m:02}-{d:02}");
+    }
+    let millis = ((value - spss_days as f64 * 86_400.0) * 1000.0).round() as i64;
+    let clock = clock(millis / 1000, "", true);
+    match millis % 1000 {
+        0 => format!("{y:04}-{m:02}-{d:02}T{clock}"),
+        ms => format!("{y:04}-{m:02}-{d:02}T{clock}.{ms:03}"),
+    }
+}
+
+/// Fixed-point with thousands grouping.
+fn grouped(value: f64, decimals: usize, group: char, point: char) -> String {
+    let plain = format!("{:.decimals$}", value.abs());
+    let (int, frac) = plain.split_once('.').unwrap_or((&plain, ""));
+    let mut out = String::with_capacity(plain.len() + int.len() / 3 + 1);
+    if value < 0.0 && plain.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+        out.push('-');
+    }
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            out.push(group);
+        }
+        out.push(c);
+    }
+    if !frac.is_empty() {
+        out.push(point);
+        out.push_str(frac);
+    }
+    out
+}
+
+/// SPSS-style scientific notation: `1.235E+003`.
+fn scientific(value: f64, decimals: usize) -> String {
+    let plain = format!("{value:.decimals$e}");
+    let (mantissa, exponent) = plain.split_once('e').unwrap_or((&plain, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}E{sign}{:03}", exponent.abs())
+}
+
+/// Whole seconds and the `.dd` fraction string, rounded to `decimals`.
+fn split_seconds(value: f64, decimals: usize) -> (i64, String) {
+    let scale = 10_i64.pow(decimals as u32);
+    let units = (value * scale as f64).round() as i64;
+    let fraction = if decimals > 0 {
+        format!(".{:0decimals$}", units.rem_euclid(scale))
+    } else {
+        String::new()
+    };
+    (units.div_euclid(scale), fraction)
+}
+
+/// `hh:mm:ss` (or `hh:mm` when `with_seconds` is false).
+fn clock(secs: i64, fraction: &str, with_seconds: bool) -> String {
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if with_seconds {
+        format!("{h:02}:{m:02}:{s:02}{fraction}")
+    } else {
+        format!("{h:02}:{m:02}")
+    }
+}
+
+fn signed_duration(value: f64, decimals: usize, render: impl Fn(i64, &str) -> String) -> String {
+    let (secs, fraction) = split_seconds(value.abs(), decimals);
+    let out = render(secs, &fraction);
+    if value < 0.0 { format!("-{out}") } else { out }
+}
+
+/// A date in one of the SPSS date layouts; years take two digits when the
+/// format is too narrow for four.
+fn date(value: f64, layout: FormatType, width: usize) -> String {
+    let spss_days = (value / 86_400.0).floor() as i64;
+    let (y, m, d) = civil_from_days(spss_days - SPSS_EPOCH_OFFSET_DAYS);
+    let month = &MONTHS[(m - 1) as usize][..3];
+    let year = |full: usize| {
+        if width >= full {
+            format!("{y:04}")
+        } else {
+            format!("{:02}", y.rem_euclid(100))
+        }
+    };
+    match layout {
+        FormatType::Date => format!("{d:02}-{month}-{}", year(11)),
+        FormatType::ADate => format!("{m:02}/{d:02}/{}", year(10)),
+        FormatType::EDate => format!("{d:02}.{m:02}.{}", year(10)),
+        FormatType::SDate => format!("{}/{m:02}/{d:02}", year(10)),
+        FormatType::JDate => {
+            let day_of_year = spss_days - days_before_year(y);
+            format!("{}{day_of_year:03}", year(7))
+        }
+        FormatType::Moyr => format!("{month} {}", year(8)),
+        FormatType::Qyr => format!("{} Q {}", (m - 1) / 3 + 1, year(8)),
+        FormatType::Wkyr => {
+            let week = (spss_days - days_before_year(y) - 1) / 7 + 1;
+            format!("{week:02} WK {}", year(10))
+        }
+        _ => unreachable!("not a date layout"),
+    }
+}
+
+/// SPSS day number of 31 December of the year before `year`.
+fn days_before_year(year: i64) -> i64 {
+    days_from_civil(year, 1, 1) + SPSS_EPOCH_OFFSET_DAYS - 1
+}
+
+/// A weekday or month name from its 1-based code, cut to the format width.
+fn name(value: f64, names: &[&str], width: usize) -> String {
+    let code = value.round() as i64;
+    match usize::try_from(code - 1).ok().and_then(|i| names.get(i)) {
+        Some(full) => full[..width.clamp(2, full.len())].to_string(),
+        None => code.to_string(),
+    }
+}
+
+/// (year, month, day) of a day count relative to 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+/// Day count relative to 1970-01-01 of a civil date.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(value: f64, format: &str) -> String {
+        format_value(value, &SpssFormat::parse(format).unwrap())
+    }
+
+    #[test]
+    fn test_numeric_formats() {
+        assert_eq!(show(1.23456, "F8.2"), "1.23");
+        assert_eq!(show(2.0, "F3.0"), "2");
+        assert_eq!(show(1234567.5, "COMMA12.1"), "1,234,567.5");
+        assert_eq!(show(-1234.5, "DOT10.2"), "-1.234,50");
+        assert_eq!(show(-12.5, "DOLLAR8.2"), "-$12.50");
+        assert_eq!(show(42.0, "N5"), "00042");
+        assert_eq!(show(12.5, "PCT6.1"), "12.5%");
+        assert_eq!(show(1234.5, "E10.3"), "1.234E+003");
+    }
+
+    #[test]
+    fn test_date_formats() {
+        // 2024-10-14 13:45:30
+        let days = days_from_civil(2024, 10, 14) + SPSS_EPOCH_OFFSET_DAYS;
+        let stamp = days as f64 * 86_400.0 + 13.0 * 3600.0 + 45.0 * 60.0 + 30.0;
+        assert_eq!(show(stamp, "DATE11"), "14-OCT-2024");
+        assert_eq!(show(stamp, "DATE9"), "14-OCT-24");
+        assert_eq!(show(stamp, "ADATE10"), "10/14/2024");
+        assert_eq!(show(stamp, "EDATE10"), "14.10.2024");
+        assert_eq!(show(stamp, "SDATE10"), "2024/10/14");
+        assert_eq!(show(stamp, "JDATE7"), "2024288");
+        assert_eq!(show(stamp, "MOYR8"), "OCT 2024");
+        assert_eq!(show(stamp, "QYR8"), "4 Q 2024");
+        assert_eq!(show(stamp, "WKYR10"), "42 WK 2024");
+        assert_eq!(show(stamp, "DATETIME20"), "14-OCT-2024 13:45:30");
+        assert_eq!(show(stamp, "DATETIME17"), "14-OCT-2024 13:45");
+        assert_eq!(show(stamp, "YMDHMS19"), "2024-10-14 13:45:30");
+        assert_eq!(show(0.0, "DATE11"), "14-OCT-1582");
+        assert_eq!(iso_date_time(stamp, false), "2024-10-14");
+        assert_eq!(iso_date_time(stamp + 0.25, true), "2024-10-14T13:45:30.250");
+    }
+
+    #[test]
+    fn test_time_formats() {
+        assert_eq!(show(49_530.0, "TIME8"), "13:45:30");
+        assert_eq!(show(49_530.0, "TIME5"), "13:45");
+        assert_eq!(show(30.256, "TIME11.2"), "00:00:30.26");
+        assert_eq!(show(-90.0, "MTIME5"), "-01:30");
+        assert_eq!(show(90_000.0, "DTIME11"), "01 01:00:00");
+        assert_eq!(show(2.0, "WKDAY3"), "MON");
+        assert_eq!(show(10.0, "MONTH9"), "OCTOBER");
+    }
+}
@@ -0,0 +1,1155 @@
+//! Reader for Stata .dta files (formats 113–119).
+//!
+//! A .dta dictionary is translated into the same variable records and value
+//! label sets a .sav dictionary produces, so metadata comes out as an
+//! `SpssMetadata` and data flows through the shared `ColumnarBatchBuilder`:
+//! each Stata row is re-laid into 8-byte slots exactly as a SAV case would be.
+//!
+//! Stata types map to Float64 (byte/int/long/float/double) or String
+//! (strN/strL), with `%td` and `%tc` variables becoming Date32 and Timestamp
+//! columns. The system missing value and the extended missing values
+//! `.a`–`.z` are all read as null, and value labels attached to extended
+//! missing values are dropped.
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use arrow::datatypes::{Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+use crate::arrow_convert;
+use crate::columnar::{self, ColumnarBatchBuilder};
+use crate::constants::{
+    Alignment, Compression, FormatType, Measure, SECONDS_PER_DAY, EPOCH_1960_OFFSET_DAYS,
+    SYSMIS_BITS, SpssFormat, VarType,
+};
+use crate::dictionary::{self, RawDictionary, ResolvedDictionary};
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::metadata::SpssMetadata;
+use crate::value_labels::{RawValue, ValueLabelSet};
+use crate::variable::{MissingValues, VariableRecord};
+
+/// Target size of one chunk of raw rows read from the data section.
+const CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Storage type of one Stata variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DtaType {
+    Byte,
+    Int,
+    Long,
+    Float,
+    Double,
+    Str(usize),
+    StrL,
+}
+
+impl DtaType {
+    /// Decode a type code from the typlist of a 113–115 file.
+    fn from_old_code(code: u8) -> Result<DtaType> {
+        match code {
+            1..=244 => Ok(DtaType::Str(code as usize)),
+            251 => Ok(DtaType::Byte),
+            252 => Ok(DtaType::Int),
+            253 => Ok(DtaType::Long),
+            254 => Ok(DtaType::Float),
+            255 => Ok(DtaType::Double),
+            _ => Err(SpssError::InvalidDta(format!(
+                "unknown variable type {code}"
+            ))),
+        }
+    }
+
+    /// Decode a type code from the <variable_types> of a 117–119 file.
+    fn from_code(code: u16) -> Result<DtaType> {
+        match code {
+            1..=2045 => Ok(DtaType::Str(code as usize)),
+            32768 => Ok(DtaType::StrL),
+            65526 => Ok(DtaType::Double),
+            65527 => Ok(DtaType::Float),
+            65528 => Ok(DtaType::Long),
+            65529 => Ok(DtaType::Int),
+            65530 => Ok(DtaType::Byte),
+            _ => Err(SpssError::InvalidDta(format!(
+                "unknown variable type {code}"
+            ))),
+        }
+    }
+
+    /// Bytes the type occupies in a data row.
+    fn size(self) -> usize {
+        match self {
+            DtaType::Byte => 1,
+            DtaType::Int => 2,
+            DtaType::Long | DtaType::Float => 4,
+            DtaType::Double | DtaType::StrL => 8,
+            DtaType::Str(w) => w,
+        }
+    }
+
+    fn is_string(self) -> bool {
+        matches!(self, DtaType::Str(_) | DtaType::StrL)
+    }
+}
+
+/// Stata date/time encodings that are converted to SPSS temporal values.
+#[derive(Debug, Clone, Copy)]
+enum StataTime {
+    /// `%td`: days since 1960-01-01.
+    Days,
+    /// `%tc`/`%tC`: milliseconds since 1960-01-01 00:00:00.
+    Millis,
+}
+
+/// Where a variable lives in a Stata row and how to convert it.
+struct DtaColumn {
+    dta_type: DtaType,
+    /// Byte offset within a Stata row.
+    offset: usize,
+    /// First slot of the converted SAV-style case.
+    slot_index: usize,
+    /// Width of the converted string (0 for numerics).
+    width: usize,
+    time: Option<StataTime>,
+}
+
+/// Byte order and layout details that vary by format version.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    release: u16,
+    big_endian: bool,
+}
+
+impl Layout {
+    fn uint(&self, bytes: &[u8]) -> u64 {
+        let fold = |acc: u64, &b: &u8| (acc << 8) | b as u64;
+        if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        }
+    }
+
+    fn name_len(&self) -> usize {
+        if self.release >= 118 { 129 } else { 33 }
+    }
+
+    fn format_len(&self) -> usize {
+        match self.release {
+            113 => 12,
+            114..=117 => 49,
+            _ => 57,
+        }
+    }
+
+    fn label_len(&self) -> usize {
+        if self.release >= 118 { 321 } else { 81 }
+    }
+
+    /// Files before 118 are in the Windows code page; later ones are UTF-8.
+    fn encoding_name(&self) -> &'static str {
+        if self.release >= 118 {
+            "UTF-8"
+        } else {
+            "windows-1252"
+        }
+    }
+
+    /// Decode a numeric value, or None for any of Stata's missing values.
+    fn numeric(&self, dta_type: DtaType, bytes: &[u8]) -> Option<f64> {
+        let raw = self.uint(&bytes[..dta_type.size()]);
+        match dta_type {
+            DtaType::Byte => Some(raw as u8 as i8).filter(|&v| v <= 100).map(f64::from),
+            DtaType::Int => Some(raw as u16 as i16)
+                .filter(|&v| v <= 32_740)
+                .map(f64::from),
+            DtaType::Long => Some(raw as u32 as i32)
+                .filter(|&v| v <= 2_147_483_620)
+                .map(f64::from),
+            DtaType::Float => Some(f32::from_bits(raw as u32))
+                .filter(|v| !v.is_nan() && *v < f32::from_bits(0x7f00_0000))
+                .map(f64::from),
+            DtaType::Double => Some(f64::from_bits(raw))
+                .filter(|v| !v.is_nan() && *v < f64::from_bits(0x7fe0_0000_0000_0000)),
+            DtaType::Str(_) | DtaType::StrL => None,
+        }
+    }
+
+    /// Split an in-row strL reference into its (variable, observation) key.
+    fn strl_key(&self, bytes: &[u8]) -> (u64, u64) {
+        let v_len = match self.release {
+            117 => 4,
+            118 => 2,
+            _ => 3,
+        };
+        let packed = self.uint(&bytes[..8]);
+        if self.big_endian {
+            (
+                packed >> ((8 - v_len) * 8),
+                packed & (u64::MAX >> (v_len * 8)),
+            )
+        } else {
+            (packed & ((1 << (v_len * 8)) - 1), packed >> (v_len * 8))
+        }
+    }
+}
+
+/// The dictionary of a .dta file, before translation.
+struct DtaHeader {
+    layout: Layout,
+    nvar: usize,
+    nobs: u64,
+    data_label: Vec<u8>,
+    timestamp: Vec<u8>,
+    types: Vec<DtaType>,
+    names: Vec<Vec<u8>>,
+    formats: Vec<String>,
+    label_names: Vec<Vec<u8>>,
+    labels: Vec<Vec<u8>>,
+    data_start: u64,
+    /// Offset of the <strls> section (117+ only).
+    strls_offset: Option<u64>,
+    value_labels_offset: u64,
+}
+
+/// A streaming reader for Stata .dta files.
+///
+/// Mirrors `SavScanner`: the dictionary is read on construction and data
+/// on demand via `next_batch()` or `collect_single()`, with column
+/// projection and row limits.
+pub struct DtaScanner<R: Read + Seek> {
+    reader: R,
+    dict: ResolvedDictionary,
+    layout: Layout,
+    columns: Vec<DtaColumn>,
+    row_len: usize,
+    slots_per_row: usize,
+    nobs: usize,
+    /// strL contents keyed by (variable, observation).
+    strls: HashMap<(u64, u64), Vec<u8>>,
+    batch_size: usize,
+    projection: Option<Vec<usize>>,
+    row_limit: Option<usize>,
+    rows_read: usize,
+}
+
+impl<R: Read + Seek> DtaScanner<R> {
+    /// Open a scanner from a reader. Parses the dictionary, value labels and
+    /// strLs immediately.
+    pub fn open(mut reader: R, batch_size: usize) -> Result<Self> {
+        let header = read_header(&mut reader)?;
+        let layout = header.layout;
+        let row_len: usize = header.types.iter().map(|t| t.size()).sum();
+
+        let strls = match header.strls_offset {
+            Some(offset) => read_strls(&mut reader, layout, offset)?,
+            None => HashMap::new(),
+        };
+        let strl_width = strls.values().map(Vec::len).max().unwrap_or(0).max(1);
+        let tables = read_value_labels(&mut reader, &header)?;
+
+        // Lay the variables out as SAV-style slots
+        let mut columns = Vec::with_capacity(header.nvar);
+        let mut variables = Vec::with_capacity(header.nvar);
+        let mut offset = 0;
+        let mut slot_index = 0;
+        for i in 0..header.nvar {
+            let dta_type = header.types[i];
+            let name = decode(&header.names[i], layout);
+            let format = &header.formats[i];
+            let time = if dta_type.is_string() {
+                None
+            } else if format.starts_with("%td") || format.starts_with("%d") {
+                Some(StataTime::Days)
+            } else if format.starts_with("%tc") || format.starts_with("%tC") {
+                Some(StataTime::Millis)
+            } else {
+                None
+            };
+            let width = match dta_type {
+                DtaType::Str(w) => w,
+                DtaType::StrL => strl_width,
+                _ => 0,
+            };
+            let var = variable_record(
+                &name,
+                dta_type,
+                width,
+                format,
+                time,
+                &header.labels[i],
+                slot_index,
+            );
+            columns.push(DtaColumn {
+                dta_type,
+                offset,
+                slot_index,
+                width,
+                time,
+            });
+            offset += dta_type.size();
+            slot_index += columnar::string_slots(width);
+            variables.push(var);
+        }
+
+        // Attach each value label table to the variables that name it
+        let mut value_label_sets = Vec::new();
+        for (table_name, labels) in tables {
+            let variable_indices: Vec<usize> = (0..header.nvar)
+                .filter(|&i| header.label_names[i] == table_name && !header.types[i].is_string())
+                .map(|i| columns[i].slot_index)
+                .collect();
+            if !variable_indices.is_empty() {
+                value_label_sets.push(ValueLabelSet {
+                    labels,
+                    variable_indices,
+                });
+            }
+        }
+
+        let nobs = usize::try_from(header.nobs)
+            .map_err(|_| SpssError::InvalidDta(format!("{} observations", header.nobs)))?;
+        let raw = RawDictionary {
+            header: FileHeader {
+                magic: *b"$FL2",
+                product: format!("Stata {}", layout.release),
+                layout_code: 2,
+                nominal_case_size: slot_index as i32,
+                compression: Compression::None,
+                weight_index: 0,
+                ncases: i32::try_from(nobs).unwrap_or(-1),
+                bias: 100.0,
+                creation_date: decode(&header.timestamp, layout),
+                creation_time: String::new(),
+                file_label: decode(&header.data_label, layout),
+                bswap: false,
+            },
+            variables,
+            value_label_sets,
+            document_lines: Vec::new(),
+            integer_info: None,
+            float_info: None,
+            var_display: Vec::new(),
+            long_names: Vec::new(),
+            very_long_strings: Vec::new(),
+            encoding_name: Some(layout.encoding_name().to_string()),
+            long_string_labels: Vec::new(),
+            long_string_missing: Vec::new(),
+            mr_sets: Vec::new(),
+            file_attributes: Vec::new(),
+            variable_attributes: Vec::new(),
+            date_info: Vec::new(),
+            extended_ncases: None,
+            unknown_records: Vec::new(),
+        };
+        let mut dict = dictionary::resolve_dictionary(raw)?;
+        dict.metadata.number_rows = Some(nobs as i64);
+        dict.ncases = Some(nobs as usize);
+        dict.metadata.file_format = "dta".to_string();
+
+        reader.seek(SeekFrom::Start(header.data_start))?;
+        Ok(DtaScanner {
+            reader,
+            dict,
+            layout,
+            columns,
+            row_len,
+            slots_per_row: slot_index,
+            nobs,
+            strls,
+            batch_size,
+            projection: None,
+            row_limit: None,
+            rows_read: 0,
+        })
+    }
+
+    /// Get a reference to the file metadata.
+    pub fn metadata(&self) -> &SpssMetadata {
+        &self.dict.metadata
+    }
+
+    /// Get the Arrow schema (respects column projection if set).
+    pub fn schema(&self) -> Schema {
+        match &self.projection {
+            Some(proj) => Schema::new(
+                proj.iter()
+                    .map(|&idx| {
+                        let var = &self.dict.variables[idx];
+                        Field::new(&var.long_name, arrow_convert::var_to_arrow_type(var), true)
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            None => arrow_convert::build_schema(&self.dict),
+        }
+    }
+
+    /// Set column projection — only these columns will be read and returned.
+    /// Returns an error if any column name is not found.
+    pub fn select(&mut self, columns: &[&str]) -> Result<()> {
+        let indices = columns
+            .iter()
+            .map(|&col| {
+                self.dict
+                    .variables
+                    .iter()
+                    .position(|v| v.long_name == col)
+                    .ok_or_else(|| SpssError::InvalidVariable(format!("column not found: {col:?}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.projection = Some(indices);
+        Ok(())
+    }
+
+    /// Set a row limit — stop reading after this many rows.
+    pub fn limit(&mut self, n: usize) {
+        self.row_limit = Some(n);
+    }
+
+    /// Read the next batch of rows, returning a RecordBatch.
+    /// Returns Ok(None) when no more data is available.
+    pub fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let n = self.remaining().min(self.batch_size);
+        if n == 0 {
+            return Ok(None);
+        }
+        self.read_batch(n).map(Some)
+    }
+
+    /// Read all remaining data as a single RecordBatch.
+    pub fn collect_single(&mut self) -> Result<RecordBatch> {
+        self.read_batch(self.remaining())
+    }
+
+    /// Read all remaining data as a Vec of RecordBatches.
+    pub fn collect_all(&mut self) -> Result<Vec<RecordBatch>> {
+        let mut batches = Vec::new();
+        while let Some(batch) = self.next_batch()? {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// How many rows have been read so far.
+    pub fn rows_read(&self) -> usize {
+        self.rows_read
+    }
+
+    /// Rows left to read, honouring the row limit.
+    fn remaining(&self) -> usize {
+        let end = self
+            .row_limit
+            .map_or(self.nobs, |limit| limit.min(self.nobs));
+        end.saturating_sub(self.rows_read)
+    }
+
+    /// Read `n` rows (all available) into a RecordBatch.
+    fn read_batch(&mut self, n: usize) -> Result<RecordBatch> {
+        if n == 0 {
+            return Ok(RecordBatch::new_empty(Arc::new(self.schema())));
+        }
+        let mut builder =
+            ColumnarBatchBuilder::new(&self.dict, self.projection.as_deref(), n.min(1_000_000));
+        let columns: Vec<&DtaColumn> = match &self.projection {
+            Some(proj) => proj.iter().map(|&i| &self.columns[i]).collect(),
+            None => self.columns.iter().collect(),
+        };
+        let case_bytes = self.slots_per_row * 8;
+        let chunk_rows = (CHUNK_BYTES / self.row_len.max(case_bytes).max(1)).clamp(1, n);
+        let mut raw = vec![0u8; chunk_rows * self.row_len];
+        let mut cases = vec![0u8; chunk_rows * case_bytes];
+
+        let mut done = 0;
+        while done < n {
+            let rows = chunk_rows.min(n - done);
+            let raw = &mut raw[..rows * self.row_len];
+            self.reader.read_exact(raw)?;
+            let cases = &mut cases[..rows * case_bytes];
+            for (row, case) in raw
+                .chunks_exact(self.row_len.max(1))
+                .zip(cases.chunks_exact_mut(case_bytes))
+            {
+                for col in &columns {
+                    self.convert(row, case, col);
+                }
+            }
+            builder.push_raw_chunk(cases, rows, self.slots_per_row);
+            done += rows;
+        }
+        self.rows_read += n;
+        builder.finish()
+    }
+
+    /// Copy one variable of a Stata row into its slots of a SAV-style case.
+    fn convert(&self, row: &[u8], case: &mut [u8], col: &DtaColumn) {
+        let field = &row[col.offset..col.offset + col.dta_type.size()];
+        let dest = &mut case[col.slot_index * 8..];
+        let bytes: &[u8] = match col.dta_type {
+            DtaType::Str(_) => {
+                let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+                &field[..end]
+            }
+            DtaType::StrL => self
+                .strls
+                .get(&self.layout.strl_key(field))
+                .map_or(&[], Vec::as_slice),
+            numeric => {
+                let value = self.layout.numeric(numeric, field).map(|v| match col.time {
+                    Some(StataTime::Days) => (v + EPOCH_1960_OFFSET_DAYS as f64) * SECONDS_PER_DAY,
+                    Some(StataTime::Millis) => {
+                        v / 1000.0 + EPOCH_1960_OFFSET_DAYS as f64 * SECONDS_PER_DAY
+                    }
+                    None => v,
+                });
+                let bits = value.map_or(SYSMIS_BITS, f64::to_bits);
+                dest[..8].copy_from_slice(&bits.to_le_bytes());
+                return;
+            }
+        };
+        columnar::write_string_slots(dest, bytes, col.width);
+    }
+}
+
+/// Build the variable record a SAV dictionary would hold for this variable.
+fn variable_record(
+    name: &str,
+    dta_type: DtaType,
+    width: usize,
+    stata_format: &str,
+    time: Option<StataTime>,
+    label: &[u8],
+    slot_index: usize,
+) -> VariableRecord {
+    let (var_type, format) = if dta_type.is_string() {
+        (
+            VarType::String(width),
+            SpssFormat {
+                format_type: FormatType::A,
+                width: width.min(255) as u8,
+                decimals: 0,
+            },
+        )
+    } else {
+        (
+            VarType::Numeric,
+            numeric_format(dta_type, stata_format, time),
+        )
+    };
+    let alignment = if dta_type.is_string() || stata_format.starts_with("%-") {
+        Alignment::Left
+    } else {
+        Alignment::Right
+    };
+    VariableRecord {
+        slot_index,
+        raw_type: if dta_type.is_string() {
+            width.min(255) as i32
+        } else {
+            0
+        },
+        short_name: name.to_string(),
+        long_name: name.to_string(),
+        label: (!label.is_empty()).then(|| label.to_vec()),
+        display_width: format.width as u32,
+        print_format: Some(format.clone()),
+        write_format: Some(format),
+        unknown_format: None,
+        missing_values: MissingValues::None,
+        var_type,
+        is_ghost: false,
+        measure: Measure::Unknown,
+        alignment,
+        n_segments: if width > 255 { width.div_ceil(255) } else { 1 },
+    }
+}
+
+/// The SPSS print format closest to a Stata display format such as
+/// "%9.0g", "%10.2fc" or "%td".
+fn numeric_format(dta_type: DtaType, stata_format: &str, time: Option<StataTime>) -> SpssFormat {
+    let (format_type, width, decimals) = match time {
+        Some(StataTime::Days) => (FormatType::Date, 11, 0),
+        Some(StataTime::Millis) => (FormatType::DateTime, 23, 3),
+        None => {
+            let spec = stata_format.trim_start_matches(['%', '-']);
+            let digits_end = spec
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(spec.len());
+            let (w, d) = spec[..digits_end]
+                .split_once('.')
+                .unwrap_or((&spec[..digits_end], ""));
+            let width: u8 = w.parse().unwrap_or(8);
+            let kind = &spec[digits_end..];
+            let integral = matches!(dta_type, DtaType::Byte | DtaType::Int | DtaType::Long);
+            let decimals: u8 = match d.parse() {
+                Ok(d) if kind.starts_with('f') || kind.starts_with('e') => d,
+                _ if integral => 0,
+                _ => 2,
+            };
+            let format_type = if kind.starts_with('e') {
+                FormatType::E
+            } else if kind.ends_with('c') {
+                FormatType::Comma
+            } else {
+                FormatType::F
+            };
+            (format_type, width.clamp(decimals + 2, 40), decimals.min(16))
+        }
+    };
+    SpssFormat {
+        format_type,
+        width,
+        decimals,
+    }
+}
+
+/// Decode a NUL-terminated text field.
+fn decode(bytes: &[u8], layout: Layout) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    crate::encoding::decode_str_lossy(
+        &bytes[..end],
+        crate::encoding::encoding_from_name(layout.encoding_name()),
+    )
+    .into_owned()
+}
+
+fn read_vec<R: Read>(reader: &mut R, n: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; n];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_uint<R: Read>(reader: &mut R, layout: Layout, n: usize) -> Result<u64> {
+    Ok(layout.uint(&read_vec(reader, n)?))
+}
+
+/// Consume an XML-style tag of a 117+ file.
+fn expect_tag<R: Read>(reader: &mut R, tag: &str) -> Result<()> {
+    let found = read_vec(reader, tag.len())?;
+    if found != tag.as_bytes() {
+        return Err(SpssError::InvalidDta(format!(
+            "expected {tag}, found {:?}",
+            String::from_utf8_lossy(&found)
+        )));
+    }
+    Ok(())
+}
+
+/// Read `n` fixed-width fields.
+fn read_fields<R: Read>(reader: &mut R, n: usize, len: usize) -> Result<Vec<Vec<u8>>> {
+    let buf = read_vec(reader, n * len)?;
+    Ok(buf
+        .chunks_exact(len.max(1))
+        .take(n)
+        .map(<[u8]>::to_vec)
+        .collect())
+}
+
+fn read_header<R: Read + Seek>(reader: &mut R) -> Result<DtaHeader> {
+    let mut first = [0u8; 1];
+    reader.read_exact(&mut first)?;
+    reader.seek(SeekFrom::Start(0))?;
+    match first[0] {
+        b'<' => read_header_xml(reader),
+        113..=115 => read_header_old(reader),
+        v => Err(SpssError::Unsupported(format!("Stata file format {v}"))),
+    }
+}
+
+/// The binary dictionary of format 113–115 files.
+fn read_header_old<R: Read + Seek>(reader: &mut R) -> Result<DtaHeader> {
+    let head = read_vec(reader, 4)?;
+    let layout = Layout {
+        release: head[0] as u16,
+        big_endian: head[1] == 1,
+    };
+    let nvar = read_uint(reader, layout, 2)? as usize;
+    let nobs = read_uint(reader, layout, 4)?;
+    let data_label = read_vec(reader, 81)?;
+    let timestamp = read_vec(reader, 18)?;
+
+    let types = read_vec(reader, nvar)?
+        .into_iter()
+        .map(DtaType::from_old_code)
+        .collect::<Result<Vec<_>>>()?;
+    let names = read_fields(reader, nvar, 33)?;
+    read_vec(reader, 2 * (nvar + 1))?; // sort order
+    let formats = read_fields(reader, nvar, layout.format_len())?
+        .iter()
+        .map(|f| decode(f, layout))
+        .collect();
+    let label_names = read_fields(reader, nvar, 33)?;
+    let labels = read_fields(reader, nvar, 81)?;
+
+    // Expansion fields: (type, length) pairs ending with (0, 0)
+    loop {
+        let kind = read_vec(reader, 1)?[0];
+        let len = read_uint(reader, layout, 4)?;
+        if kind == 0 && len == 0 {
+            break;
+        }
+        reader.seek(SeekFrom::Current(len as i64))?;
+    }
+
+    let data_start = reader.stream_position()?;
+    let row_len: u64 = types.iter().map(|t| t.size() as u64).sum();
+    Ok(DtaHeader {
+        layout,
+        nvar,
+        nobs,
+        data_label,
+        timestamp,
+        types,
+        names,
+        formats,
+        label_names: label_names.iter().map(|n| trim_nul(n)).collect(),
+        labels: labels.iter().map(|l| trim_nul(l)).collect(),
+        data_start,
+        strls_offset: None,
+        value_labels_offset: data_start + nobs * row_len,
+    })
+}
+
+/// The tagged dictionary of format 117–119 files.
+fn read_header_xml<R: Read + Seek>(reader: &mut R) -> Result<DtaHeader> {
+    expect_tag(reader, "<stata_dta><header><release>")?;
+    let release: u16 = String::from_utf8_lossy(&read_vec(reader, 3)?)
+        .parse()
+        .map_err(|_| SpssError::InvalidDta("unreadable release".to_string()))?;
+    if !(117..=119).contains(&release) {
+        return Err(SpssError::Unsupported(format!(
+            "Stata file format {release}"
+        )));
+    }
+    expect_tag(reader, "</release><byteorder>")?;
+    let big_endian = match &read_vec(reader, 3)?[..] {
+        b"MSF" => true,
+        b"LSF" => false,
+        other => {
+            return Err(SpssError::InvalidDta(format!(
+                "unknown byte order {:?}",
+                String::from_utf8_lossy(other)
+            )));
+        }
+    };
+    let layout = Layout {
+        release,
+        big_endian,
+    };
+    expect_tag(reader, "</byteorder><K>")?;
+    let nvar = read_uint(reader, layout, if release == 119 { 4 } else { 2 })? as usize;
+    expect_tag(reader, "</K><N>")?;
+    let nobs = read_uint(reader, layout, if release == 117 { 4 } else { 8 })?;
+    expect_tag(reader, "</N><label>")?;
+    let label_len = read_uint(reader, layout, if release == 117 { 1 } else { 2 })? as usize;
+    let data_label = read_vec(reader, label_len)?;
+    expect_tag(reader, "</label><timestamp>")?;
+    let ts_len = read_uint(reader, layout, 1)? as usize;
+    let timestamp = read_vec(reader, ts_len)?;
+    expect_tag(reader, "</timestamp></header><map>")?;
+    let map = (0..14)
+        .map(|_| read_uint(reader, layout, 8))
+        .collect::<Result<Vec<_>>>()?;
+
+    reader.seek(SeekFrom::Start(map[2]))?;
+    expect_tag(reader, "<variable_types>")?;
+    let types = (0..nvar)
+        .map(|_| DtaType::from_code(read_uint(reader, layout, 2)? as u16))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut section = |offset: u64, tag: &str, len: usize| -> Result<Vec<Vec<u8>>> {
+        reader.seek(SeekFrom::Start(offset))?;
+        expect_tag(reader, tag)?;
+        read_fields(reader, nvar, len)
+    };
+    let names = section(map[3], "<varnames>", layout.name_len())?;
+    let formats = section(map[5], "<formats>", layout.format_len())?;
+    let label_names = section(map[6], "<value_label_names>", layout.name_len())?;
+    let labels = section(map[7], "<variable_labels>", layout.label_len())?;
+
+    reader.seek(SeekFrom::Start(map[9]))?;
+    expect_tag(reader, "<data>")?;
+    Ok(DtaHeader {
+        layout,
+        nvar,
+        nobs,
+        data_label,
+        timestamp,
+        types,
+        names,
+        formats: formats.iter().map(|f| decode(f, layout)).collect(),
+        label_names: label_names.iter().map(|n| trim_nul(n)).collect(),
+        labels: labels.iter().map(|l| trim_nul(l)).collect(),
+        data_start: map[9] + "<data>".len() as u64,
+        strls_offset: Some(map[10]),
+        value_labels_offset: map[11],
+    })
+}
+
+fn trim_nul(bytes: &[u8]) -> Vec<u8> {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    bytes[..end].to_vec()
+}
+
+/// Read the GSO entries of the <strls> section.
+fn read_strls<R: Read + Seek>(
+    reader: &mut R,
+    layout: Layout,
+    offset: u64,
+) -> Result<HashMap<(u64, u64), Vec<u8>>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    expect_tag(reader, "<strls>")?;
+    let mut strls = HashMap::new();
+    loop {
+        match &read_vec(reader, 3)?[..] {
+            b"GSO" => {}
+            b"</s" => return Ok(strls),
+            other => {
+                return Err(SpssError::InvalidDta(format!(
+                    "expected GSO entry, found {:?}",
+                    String::from_utf8_lossy(other)
+                )));
+            }
+        }
+        let v = read_uint(reader, layout, 4)?;
+        let o = read_uint(reader, layout, if layout.release == 117 { 4 } else { 8 })?;
+        let kind = read_vec(reader, 1)?[0];
+        let len = read_uint(reader, layout, 4)? as usize;
+        let mut data = read_vec(reader, len)?;
+        // ASCII strLs (type 130) carry a trailing NUL
+        if kind == 130 && data.last() == Some(&0) {
+            data.pop();
+        }
+        strls.insert((v, o), data);
+    }
+}
+
+/// (value, label) pairs of one value label table.
+type LabelTable = Vec<(RawValue, Vec<u8>)>;
+
+/// Read every value label table, keyed by table name.
+fn read_value_labels<R: Read + Seek>(
+    reader: &mut R,
+    header: &DtaHeader,
+) -> Result<Vec<(Vec<u8>, LabelTable)>> {
+    let layout = header.layout;
+    reader.seek(SeekFrom::Start(header.value_labels_offset))?;
+    let xml = layout.release >= 117;
+    if xml {
+        expect_tag(reader, "<value_labels>")?;
+    }
+
+    let mut tables = Vec::new();
+    loop {
+        if xml {
+            match &read_vec(reader, 5)?[..] {
+                b"<lbl>" => {}
+                b"</val" => break,
+                other => {
+                    return Err(SpssError::InvalidDta(format!(
+                        "expected <lbl>, found {:?}",
+                        String::from_utf8_lossy(other)
+                    )));
+                }
+            }
+        }
+        // Pre-117 tables simply run to the end of the file
+        let len = match read_vec(reader, 4) {
+            Ok(bytes) => layout.uint(&bytes) as usize,
+            Err(SpssError::Io(e)) if !xml && e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let name = trim_nul(&read_vec(reader, layout.name_len())?);
+        read_vec(reader, 3)?; // padding
+        let table = read_vec(reader, len)?;
+        if xml {
+            expect_tag(reader, "</lbl>")?;
+        }
+        tables.push((name, parse_label_table(&table, layout)?));
+    }
+    Ok(tables)
+}
+
+/// Parse one value label table: n, text length, n offsets, n values, text.
+fn parse_label_table(table: &[u8], layout: Layout) -> Result<LabelTable> {
+    let truncated = || SpssError::InvalidDta("truncated value label table".to_string());
+    let word = |i: usize| -> Result<u64> {
+        table
+            .get(i * 4..i * 4 + 4)
+            .map(|b| layout.uint(b))
+            .ok_or_else(truncated)
+    };
+    let n = word(0)? as usize;
+    let text_len = word(1)? as usize;
+    let text_start = (2 + 2 * n) * 4;
+    let text = table
+        .get(text_start..text_start + text_len)
+        .ok_or_else(truncated)?;
+
+    let mut labels = Vec::with_capacity(n);
+    for i in 0..n {
+        let offset = word(2 + i)? as usize;
+        let value = word(2 + n + i)? as u32 as i32;
+        // Labels of the extended missing values .a–.z have no SPSS equivalent
+        if value > 2_147_483_620 {
+            continue;
+        }
+        let label = text.get(offset..).ok_or_else(truncated)?;
+        labels.push((RawValue::Numeric(value as f64), trim_nul(label)));
+    }
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{DataType, Date32Type, Float64Type};
+
+    use super::*;
+    use crate::metadata::Value;
+
+    fn padded(text: &str, len: usize) -> Vec<u8> {
+        let mut field = text.as_bytes().to_vec();
+        field.resize(len, 0);
+        field
+    }
+
+    /// A value label table mapping 1 -> "Male", 2 -> "Female", .a -> "Refused".
+    fn label_table(big_endian: bool) -> Vec<u8> {
+        let u32b = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let text = b"Male\0Female\0Refused\0";
+        let mut table = Vec::new();
+        for v in [3, text.len() as u32, 0, 5, 12, 1, 2, 2_147_483_622] {
+            table.extend_from_slice(&u32b(v));
+        }
+        table.extend_from_slice(text);
+        table
+    }
+
+    /// A little-endian format 118 file: sex (byte, labelled), score (double),
+    /// name (str6), note (strL) and born (%td long), with three rows.
+    fn dta_118() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(
+            b"<stata_dta><header><release>118</release><byteorder>LSF</byteorder><K>",
+        );
+        out.extend_from_slice(&5u16.to_le_bytes());
+        out.extend_from_slice(b"</K><N>");
+        out.extend_from_slice(&3u64.to_le_bytes());
+        out.extend_from_slice(b"</N><label>");
+        out.extend_from_slice(&7u16.to_le_bytes());
+        out.extend_from_slice("Ümfrag".as_bytes());
+        out.extend_from_slice(b"</label><timestamp>");
+        out.push(17);
+        out.extend_from_slice(b" 1 Jan 2024 10:00");
+        out.extend_from_slice(b"</timestamp></header>");
+
+        let map_at = out.len();
+        out.extend_from_slice(b"<map>");
+        out.extend_from_slice(&[0u8; 14 * 8]);
+        out.extend_from_slice(b"</map>");
+        let mut map = [0u64; 14];
+
+        map[2] = out.len() as u64;
+        out.extend_from_slice(b"<variable_types>");
+        for code in [65530u16, 65526, 6, 32768, 65528] {
+            out.extend_from_slice(&code.to_le_bytes());
+        }
+        out.extend_from_slice(b"</variable_types>");
+        map[3] = out.len() as u64;
+        out.extend_from_slice(b"<varnames>");
+        for name in ["sex", "score", "name", "note", "born"] {
+            out.extend_from_slice(&padded(name, 129));
+        }
+        out.extend_from_slice(b"</varnames>");
+        map[4] = out.len() as u64;
+        out.extend_from_slice(b"<sortlist>");
+        out.extend_from_slice(&[0u8; 12]);
+        out.extend_from_slice(b"</sortlist>");
+        map[5] = out.len() as u64;
+        out.extend_from_slice(b"<formats>");
+        for format in ["%8.0g", "%9.2f", "%-9s", "%9s", "%td"] {
+            out.extend_from_slice(&padded(format, 57));
+        }
+        out.extend_from_slice(b"</formats>");
+        map[6] = out.len() as u64;
+        out.extend_from_slice(b"<value_label_names>");
+        for name in ["sexlbl", "", "", "", ""] {
+            out.extend_from_slice(&padded(name, 129));
+        }
+        out.extend_from_slice(b"</value_label_names>");
+        map[7] = out.len() as u64;
+        out.extend_from_slice(b"<variable_labels>");
+        for label in [
+            "Sex of respondent",
+            "Test score",
+            "",
+            "Free text",
+            "Date of birth",
+        ] {
+            out.extend_from_slice(&padded(label, 321));
+        }
+        out.extend_from_slice(b"</variable_labels>");
+        map[8] = out.len() as u64;
+        out.extend_from_slice(b"<characteristics></characteristics>");
+
+        map[9] = out.len() as u64;
+        out.extend_from_slice(b"<data>");
+        let rows: [(u8, f64, &str, u64, u32); 3] = [
+            (1, 12.5, "Ann", 4 | (1 << 16), 0),
+            (2, f64::from_bits(0x7fe0_0000_0000_0000), "Bo", 0, 366),
+            (102, -3.0, "Claude", 4 | (3 << 16), 2_147_483_621),
+        ];
+        for (sex, score, name, strl, born) in rows {
+            out.push(sex);
+            out.extend_from_slice(&score.to_le_bytes());
+            out.extend_from_slice(&padded(name, 6));
+            out.extend_from_slice(&strl.to_le_bytes());
+            out.extend_from_slice(&born.to_le_bytes());
+        }
+        out.extend_from_slice(b"</data>");
+
+        map[10] = out.len() as u64;
+        out.extend_from_slice(b"<strls>");
+        for (o, text) in [(1u64, "first note"), (3, "a much longer third note")] {
+            out.extend_from_slice(b"GSO");
+            out.extend_from_slice(&4u32.to_le_bytes());
+            out.extend_from_slice(&o.to_le_bytes());
+            out.push(130);
+            out.extend_from_slice(&(text.len() as u32 + 1).to_le_bytes());
+            out.extend_from_slice(text.as_bytes());
+            out.push(0);
+        }
+        out.extend_from_slice(b"</strls>");
+
+        map[11] = out.len() as u64;
+        out.extend_from_slice(b"<value_labels><lbl>");
+        let table = label_table(false);
+        out.extend_from_slice(&(table.len() as u32).to_le_bytes());
+        out.extend_from_slice(&padded("sexlbl", 129));
+        out.extend_from_slice(&[0u8; 3]);
+        out.extend_from_slice(&table);
+        out.extend_from_slice(b"</lbl></value_labels>");
+        map[12] = out.len() as u64;
+        out.extend_from_slice(b"</stata_dta>");
+        map[13] = out.len() as u64;
+
+        for (i, offset) in map.iter().enumerate() {
+            let at = map_at + 5 + i * 8;
+            out[at..at + 8].copy_from_slice(&offset.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn test_read_dta_118() {
+        let mut scanner = DtaScanner::open(Cursor::new(dta_118()), 100).unwrap();
+        let meta = scanner.metadata().clone();
+        assert_eq!(meta.file_format, "dta");
+        assert_eq!(meta.file_encoding, "UTF-8");
+        assert_eq!(meta.number_rows, Some(3));
+        assert_eq!(meta.file_label, "Ümfrag");
+        assert_eq!(
+            meta.variable_names,
+            vec!["sex", "score", "name", "note", "born"]
+        );
+        assert_eq!(meta.label("sex"), Some("Sex of respondent"));
+        assert_eq!(meta.format("score"), Some("F9.2"));
+        assert_eq!(meta.format("name"), Some("A6"));
+        assert_eq!(meta.format("born"), Some("DATE11"));
+        assert_eq!(meta.variable_alignment["name"], Alignment::Left);
+        let labels = meta.value_labels("sex").unwrap();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[&Value::Numeric(2.0)], "Female");
+
+        let batch = scanner.collect_single().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let sex = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!(sex.value(1), 2.0);
+        assert!(sex.is_null(2));
+        let score = batch.column(1).as_primitive::<Float64Type>();
+        assert_eq!(score.value(2), -3.0);
+        assert!(score.is_null(1));
+        let name = batch.column(2).as_string_view();
+        assert_eq!(name.value(2), "Claude");
+        let note = batch.column(3).as_string_view();
+        assert_eq!(note.value(0), "first note");
+        assert_eq!(note.value(1), "");
+        assert_eq!(note.value(2), "a much longer third note");
+        assert_eq!(batch.column(4).data_type(), &DataType::Date32);
+        let born = batch.column(4).as_primitive::<Date32Type>();
+        assert_eq!(born.value(0), -3653);
+        assert_eq!(born.value(1), -3653 + 366);
+        assert!(born.is_null(2));
+    }
+
+    #[test]
+    fn test_select_and_limit() {
+        let mut scanner = DtaScanner::open(Cursor::new(dta_118()), 2).unwrap();
+        scanner.select(&["note", "sex"]).unwrap();
+        scanner.limit(3);
+        let batches = scanner.collect_all().unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert_eq!(
+            batches[1].column(0).as_string_view().value(0),
+            "a much longer third note"
+        );
+        assert!(scanner.select(&["missing"]).is_err());
+    }
+
+    /// A big-endian format 114 file with a labelled int and a str4.
+    #[test]
+    fn test_read_dta_114_big_endian() {
+        let mut out = vec![114, 1, 1, 0];
+        out.extend_from_slice(&2u16.to_be_bytes());
+        out.extend_from_slice(&2u32.to_be_bytes());
+        out.extend_from_slice(&padded("Old file", 81));
+        out.extend_from_slice(&padded("01 Jan 2000 00:00", 18));
+        out.extend_from_slice(&[252, 4]);
+        out.extend_from_slice(&padded("q1", 33));
+        out.extend_from_slice(&padded("city", 33));
+        out.extend_from_slice(&[0u8; 6]);
+        out.extend_from_slice(&padded("%8.0g", 49));
+        out.extend_from_slice(&padded("%4s", 49));
+        out.extend_from_slice(&padded("q1lbl", 33));
+        out.extend_from_slice(&padded("", 33));
+        out.extend_from_slice(&padded("Caf\u{e9}", 81)[..81]);
+        out.extend_from_slice(&padded("", 81));
+        out.extend_from_slice(&[0u8; 5]);
+        for (q1, city) in [(1i16, &b"Rome"[..]), (32_741, &b"Oslo"[..])] {
+            out.extend_from_slice(&q1.to_be_bytes());
+            out.extend_from_slice(city);
+        }
+        let table = label_table(true);
+        out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        out.extend_from_slice(&padded("q1lbl", 33));
+        out.extend_from_slice(&[0u8; 3]);
+        out.extend_from_slice(&table);
+
+        let mut scanner = DtaScanner::open(Cursor::new(out), 100).unwrap();
+        let meta = scanner.metadata().clone();
+        assert_eq!(meta.file_label, "Old file");
+        assert_eq!(
+            meta.value_labels("q1").unwrap()[&Value::Numeric(1.0)],
+            "Male"
+        );
+        assert_eq!(meta.format("q1"), Some("F8.0"));
+        let batch = scanner.collect_single().unwrap();
+        let q1 = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!(q1.value(0), 1.0);
+        assert!(q1.is_null(1));
+        assert_eq!(batch.column(1).as_string_view().value(1), "Oslo");
+    }
+}
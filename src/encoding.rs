@@ -54,6 +54,89 @@ pub fn encoding_from_name(name: &str) -> &'static Encoding {
     Encoding::for_label(normalized.as_bytes()).unwrap_or(encoding_rs::WINDOWS_1252)
 }
 
+/// Map an encoding_rs Encoding to the SPSS code page number written in the
+/// integer info record (subtype 3). Unknown encodings report 8-bit ASCII (3);
+/// readers then rely on the subtype 20 encoding name.
+pub fn code_page_from_encoding(encoding: &'static Encoding) -> i32 {
+    let code_pages: [(&Encoding, i32); 31] = [
+        (encoding_rs::UTF_8, 65001),
+        (encoding_rs::WINDOWS_874, 874),
+        (encoding_rs::SHIFT_JIS, 932),
+        (encoding_rs::GBK, 936),
+        (encoding_rs::EUC_KR, 949),
+        (encoding_rs::BIG5, 950),
+        (encoding_rs::WINDOWS_1250, 1250),
+        (encoding_rs::WINDOWS_1251, 1251),
+        (encoding_rs::WINDOWS_1252, 1252),
+        (encoding_rs::WINDOWS_1253, 1253),
+        (encoding_rs::WINDOWS_1254, 1254),
+        (encoding_rs::WINDOWS_1255, 1255),
+        (encoding_rs::WINDOWS_1256, 1256),
+        (encoding_rs::WINDOWS_1257, 1257),
+        (encoding_rs::WINDOWS_1258, 1258),
+        (encoding_rs::IBM866, 866),
+        (encoding_rs::KOI8_R, 20866),
+        (encoding_rs::KOI8_U, 21866),
+        (encoding_rs::ISO_8859_2, 28592),
+        (encoding_rs::ISO_8859_3, 28593),
+        (encoding_rs::ISO_8859_4, 28594),
+        (encoding_rs::ISO_8859_5, 28595),
+        (encoding_rs::ISO_8859_6, 28596),
+        (encoding_rs::ISO_8859_7, 28597),
+        (encoding_rs::ISO_8859_8, 28598),
+        (encoding_rs::ISO_8859_13, 28603),
+        (encoding_rs::ISO_8859_15, 28605),
+        (encoding_rs::ISO_2022_JP, 50220),
+        (encoding_rs::EUC_JP, 51932),
+        (encoding_rs::GB18030, 54936),
+        (encoding_rs::MACINTOSH, 10000),
+    ];
+    code_pages
+        .iter()
+        .find(|(e, _)| *e == encoding)
+        .map_or(3, |&(_, code_page)| code_page)
+}
+
+/// Encode a string for writing, failing on characters the encoding cannot
+/// represent. Returns `Cow::Borrowed` for UTF-8 output.
+pub fn encode_str<'a>(s: &'a str, encoding: &'static Encoding) -> Result<Cow<'a, [u8]>> {
+    if encoding == encoding_rs::UTF_8 {
+        return Ok(Cow::Borrowed(s.as_bytes()));
+    }
+    let (encoded, _, had_errors) = encoding.encode(s);
+    if had_errors {
+        return Err(SpssError::Encoding(format!(
+            "{s:?} cannot be represented in {}",
+            encoding.name()
+        )));
+    }
+    Ok(encoded)
+}
+
+/// Encode the longest prefix of `s` that fits in `max` bytes without
+/// splitting a character. Returns the encoded bytes and the length of the
+/// prefix in `s`.
+pub fn encode_prefix<'a>(
+    s: &'a str,
+    max: usize,
+    encoding: &'static Encoding,
+) -> Result<(Cow<'a, [u8]>, usize)> {
+    let encoded = encode_str(s, encoding)?;
+    if encoded.len() <= max {
+        return Ok((encoded, s.len()));
+    }
+    // Every character takes at least one byte, so the fitting prefix has at
+    // most `max` characters; shrink from there.
+    let mut end = s.char_indices().nth(max).map_or(s.len(), |(i, _)| i);
+    loop {
+        let encoded = encode_str(&s[..end], encoding)?;
+        if encoded.len() <= max {
+            return Ok((encoded, end));
+        }
+        end = s[..end].char_indices().next_back().map_or(0, |(i, _)| i);
+    }
+}
+
 /// Decode a byte slice using the given encoding, returning a UTF-8 String.
 #[allow(dead_code)]
 pub fn decode_str(bytes: &[u8], encoding: &'static Encoding) -> Result<String> {
@@ -84,15 +167,40 @@ pub fn decode_str(bytes: &[u8], encoding: &'static Encoding) -> Result<String> {
     }
 }
 
+/// Whether `text` is already in Unicode normalization form C.
+#[cfg(feature = "nfc")]
+pub fn is_nfc(text: &str) -> bool {
+    text.is_ascii() || icu_normalizer::ComposingNormalizerBorrowed::new_nfc().is_normalized(text)
+}
+
+/// `text` in Unicode normalization form C, borrowed if it already is.
+#[cfg(feature = "nfc")]
+pub fn to_nfc(text: &str) -> Cow<'_, str> {
+    if is_nfc(text) {
+        return Cow::Borrowed(text);
+    }
+    icu_normalizer::ComposingNormalizerBorrowed::new_nfc().normalize(text)
+}
+
+/// Decode a byte slice using the given encoding, or `None` if it holds
+/// sequences that are not valid in it.
+#[inline]
+pub fn decode_str_checked<'a>(
+    bytes: &'a [u8],
+    encoding: &'static Encoding,
+) -> Option<Cow<'a, str>> {
+    encoding.decode_without_bom_handling_and_without_replacement(bytes)
+}
+
 /// Decode a byte slice using the given encoding, never failing (lossy).
 /// Returns `Cow::Borrowed` for valid UTF-8, avoiding heap allocation.
 #[inline]
 pub fn decode_str_lossy<'a>(bytes: &'a [u8], encoding: &'static Encoding) -> Cow<'a, str> {
     if encoding == encoding_rs::UTF_8 {
         // Fast path: just validate UTF-8, zero-copy borrow
-        match std::str::from_utf8(bytes) {
-            Ok(s) => return Cow::Borrowed(s),
-            Err(_) => {} // fall through to encoding_rs for lossy decode
+        // (invalid UTF-8 falls through to encoding_rs for lossy decode)
+        if let Ok(s) = std::str::from_utf8(bytes) {
+            return Cow::Borrowed(s);
         }
     }
     let (decoded, _, _) = encoding.decode(bytes);
@@ -127,6 +235,19 @@ mod tests {
         assert_eq!(encoding_from_code_page(99999), encoding_rs::WINDOWS_1252);
     }
 
+    #[test]
+    fn test_encode_for_writing() {
+        let enc = encoding_rs::WINDOWS_1252;
+        assert_eq!(encode_str("Zürich", enc).unwrap().as_ref(), b"Z\xfcrich");
+        assert!(encode_str("東京", enc).is_err());
+        assert_eq!(code_page_from_encoding(enc), 1252);
+
+        let (bytes, used) = encode_prefix("héllo", 2, encoding_rs::UTF_8).unwrap();
+        assert_eq!((bytes.as_ref(), used), (&b"h"[..], 1));
+        let (bytes, used) = encode_prefix("東京都", 5, encoding_rs::SHIFT_JIS).unwrap();
+        assert_eq!((bytes.len(), used), (4, 6));
+    }
+
     #[test]
     fn test_encoding_from_name() {
         assert_eq!(encoding_from_name("UTF-8"), encoding_rs::UTF_8);
@@ -11,9 +11,33 @@ pub enum SpssError {
     #[error("unsupported compression type: {0}")]
     UnsupportedCompression(i32),
 
+    #[error("invalid compression bias: {0}")]
+    InvalidBias(String),
+
     #[error("unexpected record type {record_type} at offset {offset}")]
     UnexpectedRecordType { record_type: i32, offset: u64 },
 
+    /// A dictionary record that failed to parse: where it starts and the
+    /// last variable record before it, if any.
+    #[error(
+        "record type {record_type}{} at offset {offset}{}: {source}",
+        subtype.map(|s| format!(" subtype {s}")).unwrap_or_default(),
+        variable.as_ref().map(|v| format!(", after variable {v}")).unwrap_or_default()
+    )]
+    InvalidRecord {
+        record_type: i32,
+        subtype: Option<i32>,
+        offset: u64,
+        variable: Option<String>,
+        source: Box<SpssError>,
+    },
+
+    #[error("invalid length or count: {0}")]
+    InvalidLength(i64),
+
+    #[error("resource limit exceeded: {0}")]
+    LimitExceeded(String),
+
     #[error("invalid variable record: {0}")]
     InvalidVariable(String),
 
@@ -23,12 +47,28 @@ pub enum SpssError {
     #[error("zlib decompression failed: {0}")]
     Zlib(String),
 
+    #[error("invalid metadata JSON: {0}")]
+    MetadataJson(String),
+
+    #[error("conflicting metadata: {0}")]
+    MetadataConflict(String),
+
     #[error("arrow error: {0}")]
     Arrow(#[from] arrow::error::ArrowError),
 
     #[error("truncated file: expected {expected} bytes, got {actual}")]
     TruncatedFile { expected: usize, actual: usize },
 
+    #[error("a case ends after {values} of its {expected} values (bytecode offset {offset})")]
+    ShortCase {
+        values: usize,
+        expected: usize,
+        offset: usize,
+    },
+
+    #[error("the file declares {declared} cases but the data holds {actual}")]
+    CaseCountMismatch { declared: usize, actual: usize },
+
     #[error("invalid format specification: type={format_type}, width={width}, decimals={decimals}")]
     InvalidFormat {
         format_type: u8,
@@ -39,6 +79,30 @@ pub enum SpssError {
     #[error("invalid value label record: {0}")]
     InvalidValueLabel(String),
 
+    #[error("invalid Stata file: {0}")]
+    InvalidDta(String),
+
+    #[error("invalid SAS transport file: {0}")]
+    InvalidXpt(String),
+
+    #[error("syntax error on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("cannot decrypt file: {0}")]
+    Decryption(String),
+
+    #[cfg(feature = "object_store")]
+    #[error("object store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[cfg(feature = "parquet")]
+    #[error("parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[cfg(feature = "xlsx")]
+    #[error("xlsx error: {0}")]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+
     #[error("unsupported feature: {0}")]
     Unsupported(String),
 }
@@ -0,0 +1,644 @@
+//! Row predicate pushdown for `SavScanner`.
+//!
+//! Filters are evaluated on each decoded row's raw 8-byte slots, before any
+//! Arrow builder sees the row, so rows that do not match cost only the
+//! decompression and the comparison.
+//!
+//! Two forms are supported:
+//!
+//! - [`Predicate`]: simple `column op literal` comparisons combined with
+//!   `And` / `Or` / `Not`.
+//! - A closure over a [`RowView`] of selected columns (see
+//!   `SavScanner::filter_fn`).
+//!
+//! Row sampling (`SavScanner::every_nth` / `SavScanner::sample`) is applied
+//! the same way, by file row number.
+//!
+//! Comparisons follow SQL null semantics: a system-missing numeric value
+//! never satisfies a comparison (use [`Predicate::IsMissing`] to test for
+//! it). String values are compared with trailing padding removed.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use encoding_rs::Encoding;
+
+use crate::constants::{VarType, is_sysmis};
+use crate::dictionary::ResolvedDictionary;
+use crate::encoding;
+use crate::error::{Result, SpssError};
+use crate::float_format::NumberFormat;
+use crate::io_utils;
+use crate::metadata::Value;
+use crate::variable::VariableRecord;
+
+/// Comparison operator for a [`Predicate::Compare`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ord == Ordering::Equal,
+            CompareOp::Ne => ord != Ordering::Equal,
+            CompareOp::Lt => ord == Ordering::Less,
+            CompareOp::Le => ord != Ordering::Greater,
+            CompareOp::Gt => ord == Ordering::Greater,
+            CompareOp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+/// A row predicate over column values.
+///
+/// # Example
+/// ```no_run
+/// use ambers::filter::Predicate;
+///
+/// let mut scanner = ambers::scan_sav("survey.sav").unwrap();
+/// scanner
+///     .filter(Predicate::eq("region", 3.0).and(Predicate::ge("age", 18.0)))
+///     .unwrap();
+/// let adults_in_region = scanner.collect_single().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    /// Compare a column with a literal. Numeric columns need a
+    /// `Value::Numeric` literal, string columns a `Value::String`.
+    Compare {
+        column: String,
+        op: CompareOp,
+        value: Value,
+    },
+    /// True when a numeric column is system-missing or a string column is
+    /// blank.
+    IsMissing(String),
+    /// True when a column has one of the listed values.
+    In {
+        column: String,
+        values: Vec<Value>,
+    },
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    fn compare(column: &str, op: CompareOp, value: impl Into<Value>) -> Predicate {
+        Predicate::Compare {
+            column: column.to_string(),
+            op,
+            value: value.into(),
+        }
+    }
+
+    pub fn eq(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Eq, value)
+    }
+
+    pub fn ne(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Ne, value)
+    }
+
+    pub fn lt(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Lt, value)
+    }
+
+    pub fn le(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Le, value)
+    }
+
+    pub fn gt(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Gt, value)
+    }
+
+    pub fn ge(column: &str, value: impl Into<Value>) -> Predicate {
+        Predicate::compare(column, CompareOp::Ge, value)
+    }
+
+    pub fn is_missing(column: &str) -> Predicate {
+        Predicate::IsMissing(column.to_string())
+    }
+
+    pub fn is_in<V: Into<Value>>(column: &str, values: impl IntoIterator<Item = V>) -> Predicate {
+        Predicate::In {
+            column: column.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Both this predicate and `other` must hold.
+    pub fn and(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::And(mut preds) => {
+                preds.push(other);
+                Predicate::And(preds)
+            }
+            p => Predicate::And(vec![p, other]),
+        }
+    }
+
+    /// Either this predicate or `other` must hold.
+    pub fn or(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::Or(mut preds) => {
+                preds.push(other);
+                Predicate::Or(preds)
+            }
+            p => Predicate::Or(vec![p, other]),
+        }
+    }
+
+    /// Negate this predicate.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Predicate {
+        Predicate::Not(Box::new(self))
+    }
+}
+
+/// Where a column lives in a raw row.
+#[derive(Debug, Clone)]
+struct ColumnRef {
+    slot_index: usize,
+    /// `None` for numeric, `Some(width)` for strings.
+    string_width: Option<usize>,
+    n_segments: usize,
+    /// How the file stores numbers.
+    numbers: NumberFormat,
+    /// Encoding of string values.
+    encoding: &'static Encoding,
+}
+
+impl ColumnRef {
+    fn new(var: &VariableRecord, dict: &ResolvedDictionary) -> ColumnRef {
+        ColumnRef {
+            slot_index: var.slot_index,
+            string_width: match var.var_type {
+                VarType::Numeric => None,
+                VarType::String(w) => Some(w),
+            },
+            n_segments: var.n_segments,
+            numbers: dict.numbers,
+            encoding: dict.value_encoding(var),
+        }
+    }
+
+    #[inline]
+    fn numeric(&self, row: &[u8]) -> Option<f64> {
+        let at = self.slot_index * 8;
+        let v = self.numbers.decode(row[at..at + 8].try_into().unwrap());
+        if is_sysmis(v) { None } else { Some(v) }
+    }
+
+    /// Raw string bytes with trailing padding removed.
+    fn string_bytes<'a>(&self, row: &'a [u8]) -> Cow<'a, [u8]> {
+        let width = self.string_width.unwrap_or(0);
+        let start = self.slot_index * 8;
+        if self.n_segments <= 1 {
+            let end = (start + width).min(row.len());
+            return Cow::Borrowed(io_utils::trim_trailing_padding(&row[start..end]));
+        }
+        // Very long string: 255 useful bytes per 32-slot segment.
+        let mut buf = Vec::with_capacity(width);
+        for seg in 0..self.n_segments {
+            let seg_start = start + seg * 256;
+            let seg_len = if seg + 1 < self.n_segments {
+                255
+            } else {
+                width - 255 * (self.n_segments - 1)
+            };
+            let end = (seg_start + seg_len).min(row.len());
+            if seg_start < end {
+                buf.extend_from_slice(&row[seg_start..end]);
+            }
+        }
+        let trimmed = io_utils::trim_trailing_padding(&buf).len();
+        buf.truncate(trimmed);
+        Cow::Owned(buf)
+    }
+}
+
+/// A view of one row's selected columns, passed to `SavScanner::filter_fn`
+/// closures. Columns are addressed by their position in the name list given
+/// to `filter_fn`.
+pub struct RowView<'a> {
+    row: &'a [u8],
+    columns: &'a [ColumnRef],
+}
+
+impl<'a> RowView<'a> {
+    /// Numeric value of column `i`, or `None` if it is system-missing or a
+    /// string column.
+    pub fn numeric(&self, i: usize) -> Option<f64> {
+        let col = &self.columns[i];
+        match col.string_width {
+            None => col.numeric(self.row),
+            Some(_) => None,
+        }
+    }
+
+    /// String value of column `i` (trailing spaces removed), or `None` for a
+    /// numeric column.
+    pub fn string(&self, i: usize) -> Option<String> {
+        let col = &self.columns[i];
+        col.string_width?;
+        let bytes = col.string_bytes(self.row);
+        Some(encoding::decode_str_lossy(&bytes, col.encoding).into_owned())
+    }
+}
+
+/// Boxed row closure for `SavScanner::filter_fn`.
+pub(crate) type RowFn = Box<dyn Fn(&RowView) -> bool + Send + Sync>;
+
+/// A predicate resolved against a dictionary, ready to evaluate on raw rows.
+enum Compiled {
+    Num {
+        col: ColumnRef,
+        op: CompareOp,
+        value: f64,
+    },
+    Str {
+        col: ColumnRef,
+        op: CompareOp,
+        value: String,
+    },
+    NumIn {
+        col: ColumnRef,
+        values: Vec<f64>,
+    },
+    StrIn {
+        col: ColumnRef,
+        values: Vec<String>,
+    },
+    Missing(ColumnRef),
+    And(Vec<Compiled>),
+    Or(Vec<Compiled>),
+    Not(Box<Compiled>),
+}
+
+/// Selects rows by their position in the file.
+pub(crate) enum Sampling {
+    /// Rows 0, k, 2k, ...
+    EveryNth(usize),
+    /// Each row independently with probability `fraction`, decided by a
+    /// hash of the row number and `seed` so the sample is reproducible.
+    Random { fraction: f64, seed: u64 },
+}
+
+impl Sampling {
+    #[inline]
+    fn keeps(&self, file_row: usize) -> bool {
+        match *self {
+            Sampling::EveryNth(k) => file_row.is_multiple_of(k),
+            Sampling::Random { fraction, seed } => {
+                let hash = splitmix64(splitmix64(seed) ^ file_row as u64);
+                // Top 53 bits as a uniform value in [0, 1)
+                ((hash >> 11) as f64 / (1u64 << 53) as f64) < fraction
+            }
+        }
+    }
+}
+
+/// SplitMix64 finalizer: a fast, well-mixed 64-bit hash.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// All filters registered on a scanner; a row is kept when every one holds.
+pub(crate) struct RowFilter {
+    sampling: Vec<Sampling>,
+    predicates: Vec<Compiled>,
+    closures: Vec<(Vec<ColumnRef>, RowFn)>,
+}
+
+impl RowFilter {
+    pub(crate) fn new() -> RowFilter {
+        RowFilter {
+            sampling: Vec::new(),
+            predicates: Vec::new(),
+            closures: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_predicate(
+        &mut self,
+        dict: &ResolvedDictionary,
+        predicate: &Predicate,
+    ) -> Result<()> {
+        let compiled = compile(dict, predicate)?;
+        self.predicates.push(compiled);
+        Ok(())
+    }
+
+    pub(crate) fn add_closure(
+        &mut self,
+        dict: &ResolvedDictionary,
+        columns: &[&str],
+        f: RowFn,
+    ) -> Result<()> {
+        let refs = columns
+            .iter()
+            .map(|name| lookup(dict, name).map(|v| ColumnRef::new(v, dict)))
+            .collect::<Result<Vec<_>>>()?;
+        self.closures.push((refs, f));
+        Ok(())
+    }
+
+    pub(crate) fn add_sampling(&mut self, sampling: Sampling) {
+        self.sampling.push(sampling);
+    }
+
+    /// Whether the raw row (`slots_per_row * 8` bytes) at `file_row` passes
+    /// every filter.
+    #[inline]
+    pub(crate) fn matches(&self, row: &[u8], file_row: usize) -> bool {
+        self.sampling.iter().all(|s| s.keeps(file_row))
+            && self
+                .predicates
+                .iter()
+                .all(|p| eval(p, row))
+            && self
+                .closures
+                .iter()
+                .all(|(columns, f)| f(&RowView { row, columns }))
+    }
+
+    /// Keep only the matching rows of `buf` (whose first row is file row
+    /// `first_row`), moving them to the front. Returns the number of rows kept;
+    /// their 1-based case numbers are appended to `case_numbers` if given.
+    pub(crate) fn compact(
+        &self,
+        buf: &mut [u8],
+        n_rows: usize,
+        row_bytes: usize,
+        first_row: usize,
+        mut case_numbers: Option<&mut Vec<u64>>,
+    ) -> usize {
+        let mut kept = 0;
+        for i in 0..n_rows {
+            let start = i * row_bytes;
+            if self.matches(&buf[start..start + row_bytes], first_row + i) {
+                if kept != i {
+                    buf.copy_within(start..start + row_bytes, kept * row_bytes);
+                }
+                if let Some(case_numbers) = case_numbers.as_deref_mut() {
+                    case_numbers.push((first_row + i) as u64 + 1);
+                }
+                kept += 1;
+            }
+        }
+        kept
+    }
+}
+
+fn lookup<'a>(dict: &'a ResolvedDictionary, name: &str) -> Result<&'a VariableRecord> {
+    dict.variables
+        .iter()
+        .find(|v| v.long_name == name)
+        .ok_or_else(|| SpssError::InvalidVariable(format!("column not found: {name:?}")))
+}
+
+fn compile(dict: &ResolvedDictionary, predicate: &Predicate) -> Result<Compiled> {
+    let type_error = |column: &str| {
+        SpssError::InvalidVariable(format!(
+            "filter literal type does not match column {column:?}"
+        ))
+    };
+    Ok(match predicate {
+        Predicate::Compare { column, op, value } => {
+            let col = ColumnRef::new(lookup(dict, column)?, dict);
+            match (col.string_width, value) {
+                (None, Value::Numeric(v)) => Compiled::Num {
+                    col,
+                    op: *op,
+                    value: *v,
+                },
+                (Some(_), Value::String(s)) => Compiled::Str {
+                    col,
+                    op: *op,
+                    value: s.trim_end_matches(' ').to_string(),
+                },
+                _ => return Err(type_error(column)),
+            }
+        }
+        Predicate::In { column, values } => {
+            let col = ColumnRef::new(lookup(dict, column)?, dict);
+            if col.string_width.is_none() {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Numeric(n) => Ok(*n),
+                        Value::String(_) => Err(type_error(column)),
+                    })
+                    .collect::<Result<_>>()?;
+                Compiled::NumIn { col, values }
+            } else {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::String(s) => Ok(s.trim_end_matches(' ').to_string()),
+                        Value::Numeric(_) => Err(type_error(column)),
+                    })
+                    .collect::<Result<_>>()?;
+                Compiled::StrIn { col, values }
+            }
+        }
+        Predicate::IsMissing(column) => {
+            Compiled::Missing(ColumnRef::new(lookup(dict, column)?, dict))
+        }
+        Predicate::And(preds) => Compiled::And(
+            preds
+                .iter()
+                .map(|p| compile(dict, p))
+                .collect::<Result<_>>()?,
+        ),
+        Predicate::Or(preds) => Compiled::Or(
+            preds
+                .iter()
+                .map(|p| compile(dict, p))
+                .collect::<Result<_>>()?,
+        ),
+        Predicate::Not(p) => Compiled::Not(Box::new(compile(dict, p)?)),
+    })
+}
+
+fn eval(p: &Compiled, row: &[u8]) -> bool {
+    match p {
+        Compiled::Num { col, op, value } => col
+            .numeric(row)
+            .and_then(|v| v.partial_cmp(value))
+            .is_some_and(|ord| op.holds(ord)),
+        Compiled::Str { col, op, value } => {
+            let bytes = col.string_bytes(row);
+            let s = encoding::decode_str_lossy(&bytes, col.encoding);
+            op.holds(s.as_ref().cmp(value.as_str()))
+        }
+        Compiled::NumIn { col, values } => col.numeric(row).is_some_and(|v| values.contains(&v)),
+        Compiled::StrIn { col, values } => {
+            let bytes = col.string_bytes(row);
+            let s = encoding::decode_str_lossy(&bytes, col.encoding);
+            values.iter().any(|v| v == s.as_ref())
+        }
+        Compiled::Missing(col) => match col.string_width {
+            None => col.numeric(row).is_none(),
+            Some(_) => col.string_bytes(row).is_empty(),
+        },
+        Compiled::And(preds) => preds.iter().all(|p| eval(p, row)),
+        Compiled::Or(preds) => preds.iter().any(|p| eval(p, row)),
+        Compiled::Not(p) => !eval(p, row),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, Float64Array, StringArray, StringViewArray};
+    use arrow::record_batch::RecordBatch;
+
+    use super::*;
+    use crate::constants::Compression;
+    use crate::scanner::SavScanner;
+    use crate::test_util;
+
+    fn sav_bytes(compression: Compression) -> Vec<u8> {
+        let n = 1000;
+        let ids: Vec<Option<f64>> = (0..n)
+            .map(|i| if i % 10 == 9 { None } else { Some(i as f64) })
+            .collect();
+        let regions: Vec<&str> = (0..n).map(|i| ["north", "south", "east"][i % 3]).collect();
+        test_util::sav_bytes(
+            vec![
+                ("id", Arc::new(Float64Array::from(ids))),
+                ("region", Arc::new(StringArray::from(regions))),
+            ],
+            compression,
+        )
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<f64> {
+        batches
+            .iter()
+            .flat_map(|b| {
+                let col = b.column(0).as_any().downcast_ref::<Float64Array>().unwrap();
+                (0..col.len()).map(|i| col.value(i)).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_predicate_filter_all_compressions() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut scanner = SavScanner::open(Cursor::new(sav_bytes(compression)), 7).unwrap();
+            scanner
+                .filter(Predicate::lt("id", 40.0).and(Predicate::eq("region", "south")))
+                .unwrap();
+            let got = ids(&scanner.collect_all().unwrap());
+            // region == south when id % 3 == 1; id 19 is missing
+            assert_eq!(
+                got,
+                vec![
+                    1.0, 4.0, 7.0, 10.0, 13.0, 16.0, 22.0, 25.0, 28.0, 31.0, 34.0, 37.0
+                ]
+            );
+            assert_eq!(scanner.rows_read(), 12);
+        }
+    }
+
+    #[test]
+    fn test_filter_missing_and_limit() {
+        let mut scanner =
+            SavScanner::open(Cursor::new(sav_bytes(Compression::Bytecode)), 100).unwrap();
+        scanner.filter(Predicate::is_missing("id")).unwrap();
+        scanner.limit(3);
+        let batch = scanner.collect_single().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let region = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringViewArray>()
+            .unwrap();
+        assert_eq!(region.value(0), "north"); // row 9
+        assert_eq!(region.value(1), "south"); // row 19
+
+        let mut scanner = SavScanner::open(Cursor::new(sav_bytes(Compression::None)), 100).unwrap();
+        scanner
+            .filter(Predicate::is_in("region", ["east", "west"]).not())
+            .unwrap();
+        assert_eq!(scanner.collect_single().unwrap().num_rows(), 667);
+    }
+
+    #[test]
+    fn test_filter_fn_and_errors() {
+        let mut scanner = SavScanner::open(Cursor::new(sav_bytes(Compression::None)), 100).unwrap();
+        scanner
+            .filter_fn(&["region", "id"], |row| {
+                row.string(0).as_deref() == Some("east")
+                    && row.numeric(1).is_some_and(|v| v > 990.0)
+            })
+            .unwrap();
+        scanner.select(&["id"]).unwrap();
+        assert_eq!(
+            ids(&[scanner.collect_single().unwrap()]),
+            vec![992.0, 995.0, 998.0]
+        );
+
+        let mut scanner = SavScanner::open(Cursor::new(sav_bytes(Compression::None)), 100).unwrap();
+        assert!(scanner.filter(Predicate::eq("nope", 1.0)).is_err());
+        assert!(scanner.filter(Predicate::eq("region", 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_sampling() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let open = || SavScanner::open(Cursor::new(sav_bytes(compression)), 64).unwrap();
+
+            let mut scanner = open();
+            scanner.every_nth(100).unwrap();
+            assert_eq!(
+                ids(&scanner.collect_all().unwrap()),
+                (0..10).map(|i| i as f64 * 100.0).collect::<Vec<_>>()
+            );
+
+            let mut scanner = open();
+            scanner.sample(0.1, 42).unwrap();
+            let first = ids(&scanner.collect_all().unwrap());
+            assert!((60..140).contains(&first.len()), "{}", first.len());
+            // Same seed, same rows, regardless of batching or other filters.
+            let mut scanner = SavScanner::open(Cursor::new(sav_bytes(compression)), 5).unwrap();
+            scanner.sample(0.1, 42).unwrap();
+            scanner
+                .filter(Predicate::gt("id", 0.0).and(Predicate::lt("id", 500.0)))
+                .unwrap();
+            let second = ids(&scanner.collect_all().unwrap());
+            // Nulls read back as 0.0 in `first`
+            let expected: Vec<f64> = first
+                .iter()
+                .copied()
+                .filter(|&v| v > 0.0 && v < 500.0)
+                .collect();
+            assert_eq!(second, expected);
+
+            let mut scanner = open();
+            scanner.sample(0.1, 7).unwrap();
+            assert_ne!(ids(&scanner.collect_all().unwrap()), first);
+        }
+
+        let mut scanner = SavScanner::open(Cursor::new(sav_bytes(Compression::None)), 64).unwrap();
+        assert!(scanner.every_nth(0).is_err());
+        assert!(scanner.sample(1.5, 0).is_err());
+        scanner.sample(0.0, 0).unwrap();
+        assert!(scanner.next_batch().unwrap().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! Content fingerprints: a hash of what a file holds rather than of its
+//! bytes.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type};
+use serde_json::Value as Json;
+use sha2::{Digest, Sha256};
+
+use crate::error::Result;
+use crate::options::{ScanOptions, TemporalMode};
+use crate::scanner::SavScanner;
+
+/// Fields of the metadata JSON `file` object that describe how a file was
+/// saved rather than what it holds.
+const STORAGE_FIELDS: [&str; 5] = [
+    "compression",
+    "creation_time",
+    "modification_time",
+    "encoding",
+    "format",
+];
+
+/// Fingerprint an SPSS .sav or .zsav file.
+///
+/// Returns a SHA-256 hex digest of the dictionary and the decoded data, so
+/// that files with the same content match however they were stored: a .sav
+/// and the same data re-saved as .zsav or uncompressed, or saved in another
+/// encoding, get the same fingerprint. The dictionary is hashed in the
+/// `SpssMetadata::to_json` layout without the compression, timestamps,
+/// encoding and file format; values are hashed as read with
+/// `TemporalMode::Raw`, column by column, independent of batch sizes.
+///
+/// # Example
+/// ```no_run
+/// let a = ambers::fingerprint("wave1.sav").unwrap();
+/// let b = ambers::fingerprint("wave1.zsav").unwrap();
+/// assert_eq!(a, b);
+/// ```
+pub fn fingerprint(path: impl AsRef<Path>) -> Result<String> {
+    fingerprint_reader(BufReader::new(File::open(path)?))
+}
+
+/// Fingerprint an SPSS file from a reader (see [`fingerprint`]).
+pub fn fingerprint_reader<R: Read + Seek>(reader: R) -> Result<String> {
+    let options = ScanOptions::new().temporal(TemporalMode::Raw);
+    let mut scanner = SavScanner::open_with(reader, &options)?;
+
+    let mut doc: Json =
+        serde_json::from_str(&scanner.metadata().to_json()).expect("to_json writes valid JSON");
+    if let Some(file) = doc.get_mut("file").and_then(Json::as_object_mut) {
+        for field in STORAGE_FIELDS {
+            file.remove(field);
+        }
+    }
+    let dictionary = Sha256::digest(doc.to_string().as_bytes());
+
+    let mut columns: Vec<Sha256> = Vec::new();
+    while let Some(batch) = scanner.next_batch()? {
+        columns.resize_with(batch.num_columns(), Sha256::new);
+        for (hasher, column) in columns.iter_mut().zip(batch.columns()) {
+            hash_column(hasher, column)?;
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"ambers fingerprint 1\0");
+    hasher.update(dictionary);
+    for column in columns {
+        hasher.update(column.finalize());
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// Feed each cell: 0 for null, else 1 and the value (f64 bits, or length
+/// and UTF-8 bytes).
+fn hash_column(hasher: &mut Sha256, column: &ArrayRef) -> Result<()> {
+    if column.data_type().is_numeric() {
+        let values = cast(column, &DataType::Float64)?;
+        for v in values.as_primitive::<Float64Type>() {
+            match v {
+                None => hasher.update([0]),
+                Some(v) => {
+                    hasher.update([1]);
+                    hasher.update(v.to_bits().to_le_bytes());
+                }
+            }
+        }
+    } else {
+        let values = cast(column, &DataType::Utf8View)?;
+        for v in values.as_string_view() {
+            match v {
+                None => hasher.update([0]),
+                Some(v) => {
+                    hasher.update([1]);
+                    hasher.update((v.len() as u64).to_le_bytes());
+                    hasher.update(v.as_bytes());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::constants::Compression;
+    use crate::test_util;
+
+    #[test]
+    fn test_fingerprint_ignores_storage() {
+        let sav = test_util::survey_sav([1.0, 0.0, 2.0], Compression::None, 4096);
+        let plain = fingerprint_reader(Cursor::new(sav)).unwrap();
+        assert_eq!(plain.len(), 64);
+        for compression in [Compression::Bytecode, Compression::Zlib] {
+            let sav = test_util::survey_sav([1.0, 0.0, 2.0], compression, 64);
+            let other = fingerprint_reader(Cursor::new(sav)).unwrap();
+            assert_eq!(plain, other);
+        }
+        let sav = test_util::survey_sav([3.0, 0.0, 2.0], Compression::None, 4096);
+        let changed = fingerprint_reader(Cursor::new(sav)).unwrap();
+        assert_ne!(plain, changed);
+    }
+}
@@ -0,0 +1,278 @@
+//! Non-IEEE floating point: the IBM System/360 hexadecimal and DEC VAX D
+//! doubles that legacy mainframe and VAX files hold their numbers in.
+//!
+//! Files say which format they use in the machine integer info record
+//! (subtype 3). Values are converted to IEEE as they are read; the special
+//! values keep their meaning: the format's most negative number is
+//! system-missing, its largest HIGHEST, the next-to-most-negative LOWEST.
+//! A file may also declare other special values in its float info record
+//! (subtype 4); those are recognized too.
+
+use crate::constants::{HIGHEST_BITS, LOWEST_BITS, SYSMIS_BITS};
+
+/// Bit patterns of the special values, the same in both legacy formats
+/// (for VAX, after putting its 16-bit words in order).
+const LEGACY_SYSMIS: u64 = u64::MAX;
+const LEGACY_HIGHEST: u64 = 0x7FFF_FFFF_FFFF_FFFF;
+const LEGACY_LOWEST: u64 = 0xFFFF_FFFF_FFFF_FFFE;
+
+/// System-missing, HIGHEST and LOWEST as ambers holds them.
+const SPECIALS: [u64; 3] = [SYSMIS_BITS, HIGHEST_BITS, LOWEST_BITS];
+
+/// Declared special values smaller than this could be ordinary data, so
+/// they are not trusted. Beyond it there is nothing but the formats'
+/// largest numbers (VAX D tops out at 1.7e38).
+const SENTINEL_MAGNITUDE: f64 = 1e37;
+
+/// Floating point representation of a file's numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    #[default]
+    Ieee,
+    /// IBM System/360 hexadecimal: 7-bit base-16 exponent, 56-bit fraction.
+    Ibm,
+    /// DEC VAX D: 8-bit exponent, 55-bit fraction, stored as 16-bit
+    /// little-endian words in big-endian order.
+    Vax,
+}
+
+impl FloatFormat {
+    /// The format for the integer info record's `floating_point_rep`: 1 for
+    /// IEEE, 2 for IBM, 3 for VAX. Unknown codes are taken as IEEE.
+    pub fn from_code(code: i32) -> FloatFormat {
+        match code {
+            2 => FloatFormat::Ibm,
+            3 => FloatFormat::Vax,
+            _ => FloatFormat::Ieee,
+        }
+    }
+
+    /// Convert a number stored in this format (its bits, read in the file's
+    /// byte order) to IEEE.
+    pub fn decode(self, bits: u64) -> f64 {
+        let bits = match self {
+            FloatFormat::Ieee => return f64::from_bits(bits),
+            FloatFormat::Ibm => bits,
+            FloatFormat::Vax => swap_words(bits),
+        };
+        match bits {
+            LEGACY_SYSMIS => return f64::from_bits(SYSMIS_BITS),
+            LEGACY_HIGHEST => return f64::from_bits(HIGHEST_BITS),
+            LEGACY_LOWEST => return f64::from_bits(LOWEST_BITS),
+            _ => {}
+        }
+        let sign = if bits >> 63 == 1 { -1.0 } else { 1.0 };
+        if self == FloatFormat::Ibm {
+            let exponent = ((bits >> 56) & 0x7F) as i32 - 64;
+            let fraction = bits & 0x00FF_FFFF_FFFF_FFFF;
+            return sign * fraction as f64 * 2f64.powi(4 * exponent - 56);
+        }
+        let exponent = ((bits >> 55) & 0xFF) as i32;
+        if exponent == 0 {
+            // Zero, or with the sign set a "reserved operand": no number.
+            return if sign < 0.0 {
+                f64::from_bits(SYSMIS_BITS)
+            } else {
+                0.0
+            };
+        }
+        let mantissa = (1 << 55) | (bits & ((1 << 55) - 1));
+        sign * mantissa as f64 * 2f64.powi(exponent - 128 - 56)
+    }
+
+    /// Convert an IEEE number to this format. Values beyond the format's
+    /// range become its largest ordinary number, or zero.
+    pub fn encode(self, v: f64) -> u64 {
+        let bits = match v.to_bits() {
+            _ if self == FloatFormat::Ieee => return v.to_bits(),
+            SYSMIS_BITS => LEGACY_SYSMIS,
+            HIGHEST_BITS => LEGACY_HIGHEST,
+            LOWEST_BITS => LEGACY_LOWEST,
+            _ if v.is_nan() => LEGACY_SYSMIS,
+            _ if v == 0.0 => 0,
+            _ => self.encode_number(v),
+        };
+        if self == FloatFormat::Vax {
+            swap_words(bits)
+        } else {
+            bits
+        }
+    }
+
+    fn encode_number(self, v: f64) -> u64 {
+        let sign = (v.is_sign_negative() as u64) << 63;
+        let (fraction, exponent) = frexp(v.abs());
+        let (exponent, shift, mantissa_bits, exponents) = match self {
+            // 16^e16 with a fraction in [1/16, 1).
+            FloatFormat::Ibm => {
+                let e16 = (exponent + 3).div_euclid(4);
+                (e16 + 64, 56 - 4 * e16 + exponent, 56, 0..=127)
+            }
+            _ => (exponent + 128, 56, 55, 1..=255),
+        };
+        if v.is_infinite() || exponent > *exponents.end() {
+            sign | (LEGACY_HIGHEST - 2)
+        } else if exponent < *exponents.start() {
+            0
+        } else {
+            // Exact: IEEE has 53 significant bits, both formats more.
+            let mantissa = (fraction * 2f64.powi(shift)) as u64 & ((1 << mantissa_bits) - 1);
+            sign | (exponent as u64) << mantissa_bits | mantissa
+        }
+    }
+}
+
+/// How a file stores numbers: byte order, floating point format and
+/// special values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat {
+    /// Numbers are big-endian (`FileHeader::bswap`).
+    pub bswap: bool,
+    pub float: FloatFormat,
+    /// System-missing, HIGHEST and LOWEST as the file declares them (bits
+    /// in `float`, byte order applied), when they differ from the usual
+    /// ones.
+    pub specials: Option<[u64; 3]>,
+}
+
+impl NumberFormat {
+    /// Also recognize the special values a file declares in its float info
+    /// record (read as if IEEE). A declaration that repeats a value, or
+    /// names one that could be ordinary data, is ignored.
+    pub fn with_specials(mut self, declared: [f64; 3]) -> NumberFormat {
+        let bits = declared.map(f64::to_bits);
+        let usual = SPECIALS.map(|b| self.float.encode(f64::from_bits(b)));
+        let sentinel = |b: u64| {
+            let v = self.float.decode(b);
+            v.is_nan() || v.abs() >= SENTINEL_MAGNITUDE
+        };
+        let distinct = bits[0] != bits[1] && bits[0] != bits[2] && bits[1] != bits[2];
+        if bits != usual && distinct && bits.into_iter().all(sentinel) {
+            self.specials = Some(bits);
+        }
+        self
+    }
+
+    /// Whether numbers are little-endian IEEE with the usual special
+    /// values, needing no conversion.
+    pub fn is_native(&self) -> bool {
+        !self.bswap && self.float == FloatFormat::Ieee && self.specials.is_none()
+    }
+
+    /// The number stored as `bytes`.
+    pub fn decode(&self, bytes: [u8; 8]) -> f64 {
+        let bits = if self.bswap {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        };
+        self.decode_bits(bits)
+    }
+
+    /// `v` as the file stores it.
+    pub fn encode(&self, v: f64) -> [u8; 8] {
+        let special = SPECIALS.iter().position(|&b| b == v.to_bits());
+        let bits = match (self.specials, special) {
+            (Some(specials), Some(i)) => specials[i],
+            _ => self.float.encode(v),
+        };
+        if self.bswap {
+            bits.to_be_bytes()
+        } else {
+            bits.to_le_bytes()
+        }
+    }
+
+    /// Convert a number read as if it were IEEE (by `SavReader::read_f64`,
+    /// which already honors the byte order).
+    pub fn convert(&self, v: f64) -> f64 {
+        self.decode_bits(v.to_bits())
+    }
+
+    fn decode_bits(&self, bits: u64) -> f64 {
+        if let Some(specials) = self.specials
+            && let Some(i) = specials.iter().position(|&b| b == bits)
+        {
+            return f64::from_bits(SPECIALS[i]);
+        }
+        self.float.decode(bits)
+    }
+}
+
+/// Reverse the order of the four 16-bit words of a VAX double.
+fn swap_words(bits: u64) -> u64 {
+    (bits << 48)
+        | ((bits << 16) & 0x0000_FFFF_0000_0000)
+        | ((bits >> 16) & 0xFFFF_0000)
+        | (bits >> 48)
+}
+
+/// Split a positive finite `v` into a fraction in [0.5, 1) and a power of
+/// two.
+fn frexp(v: f64) -> (f64, i32) {
+    let bits = v.to_bits();
+    let exponent = (bits >> 52) as i32;
+    if exponent == 0 {
+        // Subnormal: scale into the normal range first.
+        let (fraction, exponent) = frexp(v * 2f64.powi(64));
+        return (fraction, exponent - 64);
+    }
+    let fraction = f64::from_bits((bits & !(0x7FF << 52)) | (1022 << 52));
+    (fraction, exponent - 1022)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ibm_and_vax_round_trip() {
+        // 1.0 in IBM is 0x41100000_00000000; in VAX D its first word is 0x4080.
+        assert_eq!(FloatFormat::Ibm.encode(1.0), 0x4110_0000_0000_0000);
+        assert_eq!(FloatFormat::Ibm.decode(0xC276_A000_0000_0000), -118.625);
+        assert_eq!(FloatFormat::Vax.encode(1.0), 0x4080);
+        assert_eq!(FloatFormat::Vax.decode(0x4080), 1.0);
+
+        let values = [0.1, -2.5, 100.0, 1e-30, 1e30, -123456.789, 0.0];
+        for format in [FloatFormat::Ibm, FloatFormat::Vax] {
+            for v in values {
+                assert_eq!(format.decode(format.encode(v)), v, "{format:?} {v}");
+            }
+            for bits in [SYSMIS_BITS, HIGHEST_BITS, LOWEST_BITS] {
+                let v = f64::from_bits(bits);
+                assert_eq!(format.decode(format.encode(v)).to_bits(), bits);
+            }
+        }
+        let saturated = FloatFormat::Vax.decode(FloatFormat::Vax.encode(-1e300));
+        assert!(saturated < -1e38 && !crate::constants::is_sysmis(saturated));
+
+        let big_endian_ibm = NumberFormat {
+            bswap: true,
+            float: FloatFormat::Ibm,
+            specials: None,
+        };
+        assert_eq!(big_endian_ibm.encode(1.0), [0x41, 0x10, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(big_endian_ibm.decode([0x41, 0x10, 0, 0, 0, 0, 0, 0]), 1.0);
+    }
+
+    #[test]
+    fn test_declared_specials() {
+        let usual = SPECIALS.map(f64::from_bits);
+        assert!(NumberFormat::default().with_specials(usual).is_native());
+        // Too ordinary to trust, or not distinct.
+        let zero = NumberFormat::default().with_specials([0.0, usual[1], usual[2]]);
+        assert!(zero.is_native());
+        let same = NumberFormat::default().with_specials([f64::NAN, f64::NAN, usual[2]]);
+        assert!(same.is_native());
+
+        let nan = f64::from_bits(0xFFF8_0000_0000_0001);
+        let numbers = NumberFormat::default().with_specials([nan, f64::INFINITY, usual[2]]);
+        assert!(!numbers.is_native());
+        assert_eq!(numbers.decode(nan.to_le_bytes()).to_bits(), SYSMIS_BITS);
+        assert_eq!(numbers.convert(f64::INFINITY).to_bits(), HIGHEST_BITS);
+        assert_eq!(numbers.encode(usual[0]), nan.to_le_bytes());
+        // The usual system-missing still reads as such.
+        assert_eq!(numbers.decode(usual[0].to_le_bytes()).to_bits(), SYSMIS_BITS);
+        assert_eq!(numbers.decode(1.5f64.to_le_bytes()), 1.5);
+    }
+}
@@ -1,3 +1,5 @@
+//! The fixed 176-byte header at the start of every .sav/.zsav file.
+
 use std::io::Read;
 
 use crate::constants::Compression;
@@ -6,7 +8,6 @@ use crate::io_utils::{self, SavReader};
 
 /// Parsed SAV file header.
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct FileHeader {
     /// Magic string: "$FL2" (standard) or "$FL3".
     pub magic: [u8; 4],
@@ -35,11 +36,24 @@ pub struct FileHeader {
 }
 
 impl FileHeader {
+    /// Byte offset of the `ncases` field from the start of the file.
+    pub(crate) const NCASES_OFFSET: u64 = 80;
+
+    /// Whether the file was written big-endian.
+    pub fn is_big_endian(&self) -> bool {
+        self.bswap
+    }
+
+    /// Number of cases, or `None` when the writer did not record it.
+    pub fn case_count(&self) -> Option<usize> {
+        usize::try_from(self.ncases).ok()
+    }
+
     /// Parse the SAV file header from a reader.
     ///
     /// After this call, the reader is positioned right after the header,
     /// ready to read variable records.
-    pub fn parse<R: Read>(reader: &mut SavReader<R>) -> Result<FileHeader> {
+    pub(crate) fn parse<R: Read>(reader: &mut SavReader<R>) -> Result<FileHeader> {
         // Magic: 4 bytes
         let mut magic = [0u8; 4];
         reader.read_exact(&mut magic)?;
@@ -107,6 +121,24 @@ impl FileHeader {
             bswap,
         })
     }
+
+    /// Serialize the header in little-endian layout (inverse of `parse`).
+    ///
+    /// String fields are space-padded or truncated to their fixed widths.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.magic);
+        io_utils::write_padded(out, self.product.as_bytes(), 60, b' ');
+        out.extend_from_slice(&self.layout_code.to_le_bytes());
+        out.extend_from_slice(&self.nominal_case_size.to_le_bytes());
+        out.extend_from_slice(&self.compression.to_i32().to_le_bytes());
+        out.extend_from_slice(&self.weight_index.to_le_bytes());
+        out.extend_from_slice(&self.ncases.to_le_bytes());
+        out.extend_from_slice(&self.bias.to_le_bytes());
+        io_utils::write_padded(out, self.creation_date.as_bytes(), 9, b' ');
+        io_utils::write_padded(out, self.creation_time.as_bytes(), 8, b' ');
+        io_utils::write_padded(out, self.file_label.as_bytes(), 64, b' ');
+        out.extend_from_slice(&[0u8; 3]);
+    }
 }
 
 #[cfg(test)]
@@ -166,6 +198,8 @@ mod tests {
         assert_eq!(header.creation_time, "14:30:00");
         assert_eq!(header.file_label, "Test file");
         assert!(!header.bswap);
+        assert!(!header.is_big_endian());
+        assert_eq!(header.case_count(), Some(100));
     }
 
     #[test]
@@ -0,0 +1,89 @@
+use indexmap::IndexMap;
+
+/// Custom attributes of the file or of one variable: {name -> values}.
+pub type AttributeSet = IndexMap<String, Vec<String>>;
+
+/// Parse subtype 17: file attributes.
+///
+/// Format: `name('value1'\n'value2'\n)name2('value'\n)...` — each attribute
+/// is its name followed by its values in parentheses, every value quoted and
+/// terminated by a newline.
+pub fn parse_file_attributes(text: &str) -> AttributeSet {
+    parse_set(text.trim_end_matches('\0')).0
+}
+
+/// Parse subtype 18: variable attributes.
+///
+/// Format: `varname:attributes/varname2:attributes...`, where each
+/// attribute set has the subtype 17 layout. Variables are named by their
+/// long names.
+pub fn parse_variable_attributes(text: &str) -> Vec<(String, AttributeSet)> {
+    let mut result = Vec::new();
+    let mut rest = text.trim_end_matches('\0');
+    while let Some((name, after)) = rest.split_once(':') {
+        let (set, after) = parse_set(after);
+        result.push((name.trim().to_string(), set));
+        match after.strip_prefix('/') {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    result
+}
+
+/// Parse attributes until the end of the text or a `/` separating variable
+/// sets, returning them and the unparsed remainder.
+fn parse_set(mut text: &str) -> (AttributeSet, &str) {
+    let mut set = AttributeSet::new();
+    loop {
+        text = text.trim_start_matches(['\n', '\r']);
+        if text.is_empty() || text.starts_with('/') {
+            return (set, text);
+        }
+        let Some((name, after)) = text.split_once('(') else {
+            return (set, "");
+        };
+        text = after;
+        let mut values = Vec::new();
+        // Values end at `'\n`; a quote followed by anything else is part of
+        // the value.
+        while let Some(after) = text.strip_prefix('\'') {
+            let (value, after) = match after.find("'\n") {
+                Some(end) => (&after[..end], &after[end + 2..]),
+                None => match after.find("')") {
+                    Some(end) => (&after[..end], &after[end + 1..]),
+                    None => (after, ""),
+                },
+            };
+            values.push(value.to_string());
+            text = after;
+        }
+        text = text.strip_prefix(')').unwrap_or(text);
+        set.insert(name.trim().to_string(), values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_attributes() {
+        let attrs = parse_file_attributes("Project('Omnibus'\n)Waves('1'\n'2'\n)\0\0");
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs["Project"], ["Omnibus"]);
+        assert_eq!(attrs["Waves"], ["1", "2"]);
+    }
+
+    #[test]
+    fn test_parse_variable_attributes() {
+        let text = "q1:$@Role('0'\n)Note('it's a/b test'\n)/Income:$@Role('1'\n)";
+        let vars = parse_variable_attributes(text);
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars[0].0, "q1");
+        assert_eq!(vars[0].1["$@Role"], ["0"]);
+        assert_eq!(vars[0].1["Note"], ["it's a/b test"]);
+        assert_eq!(vars[1].0, "Income");
+        assert_eq!(vars[1].1["$@Role"], ["1"]);
+    }
+}
@@ -0,0 +1,43 @@
+use std::io::Read;
+
+use crate::error::Result;
+use crate::io_utils::SavReader;
+
+/// Parse subtype 6: TRENDS date information.
+///
+/// Written by the Forecasting/TRENDS procedures for files with DEFINE
+/// DATES variables. The record is a list of i32 values in groups of three,
+/// one group per date component; SPSS does not document their meaning, so
+/// the groups are kept as-is. A trailing partial group is dropped.
+pub fn parse_date_info<R: Read>(
+    reader: &mut SavReader<R>,
+    size: i32,
+    count: i32,
+) -> Result<Vec<[i32; 3]>> {
+    if size != 4 {
+        reader.skip(size.max(0) as usize * count.max(0) as usize)?;
+        return Ok(Vec::new());
+    }
+    let values = (0..count.max(0))
+        .map(|_| reader.read_i32())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(values.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_date_info() {
+        let data: Vec<u8> = [1i32, 12, 0, 2, 4, 1, 9]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut reader = SavReader::new(Cursor::new(data));
+        let groups = parse_date_info(&mut reader, 4, 7).unwrap();
+        assert_eq!(groups, [[1, 12, 0], [2, 4, 1]]);
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::Read;
+
+use crate::error::Result;
+use crate::io_utils::SavReader;
+
+/// Parse subtype 16: extended case count.
+///
+/// Two i64 values: an unused field (always 1), then the number of cases,
+/// -1 when unknown. Written alongside the header's i32 count so files with
+/// more than `i32::MAX` cases still record their size. Returns `None` for
+/// an unknown count or an unexpected record shape.
+pub fn parse_extended_ncases<R: Read>(
+    reader: &mut SavReader<R>,
+    size: i32,
+    count: i32,
+) -> Result<Option<i64>> {
+    if size != 8 || count != 2 {
+        reader.skip(size.max(0) as usize * count.max(0) as usize)?;
+        return Ok(None);
+    }
+    let _unused = reader.read_i64()?;
+    let ncases = reader.read_i64()?;
+    Ok((ncases >= 0).then_some(ncases))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_extended_ncases() {
+        let data: Vec<u8> = [1i64, 3_000_000_000]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut reader = SavReader::new(Cursor::new(data));
+        assert_eq!(
+            parse_extended_ncases(&mut reader, 8, 2).unwrap(),
+            Some(3_000_000_000)
+        );
+
+        let data: Vec<u8> = [1i64, -1].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut reader = SavReader::new(Cursor::new(data));
+        assert_eq!(parse_extended_ncases(&mut reader, 8, 2).unwrap(), None);
+    }
+}
@@ -4,10 +4,12 @@ use crate::error::Result;
 use crate::io_utils::SavReader;
 
 /// Subtype 4: Machine floating point information.
+///
+/// Values are read as if IEEE; for IBM and VAX files their bits are in
+/// that format (see `NumberFormat::with_specials`).
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct FloatInfo {
-    /// System-missing value (as raw bit pattern).
+    /// System-missing value.
     pub sysmis: f64,
     /// Highest representable value.
     pub highest: f64,
@@ -1,4 +1,5 @@
 use crate::error::{Result, SpssError};
+use crate::io_utils;
 
 /// A set of value labels for a long string variable.
 #[derive(Debug, Clone)]
@@ -14,21 +15,24 @@ pub struct LongStringLabelSet {
 /// Format (for each variable):
 ///   4-byte var_name_length
 ///   var_name bytes
+///   4-byte var_width
 ///   4-byte label_count
 ///   For each label:
 ///     4-byte value_length
 ///     value bytes
 ///     4-byte label_length
 ///     label bytes
-pub fn parse_long_string_labels(data: &[u8]) -> Result<Vec<LongStringLabelSet>> {
+///
+/// The 4-byte integers are big-endian when `bswap`.
+pub fn parse_long_string_labels(data: &[u8], bswap: bool) -> Result<Vec<LongStringLabelSet>> {
     let mut result = Vec::new();
     let mut pos = 0;
 
     while pos + 4 <= data.len() {
         // Variable name
-        let name_len = read_i32_le(data, pos)? as usize;
+        let name_len = read_i32(data, pos, bswap)? as usize;
         pos += 4;
-        if pos + name_len > data.len() {
+        if name_len > data.len() - pos {
             break;
         }
         let var_name = String::from_utf8_lossy(&data[pos..pos + name_len])
@@ -36,22 +40,28 @@ pub fn parse_long_string_labels(data: &[u8]) -> Result<Vec<LongStringLabelSet>>
             .to_string();
         pos += name_len;
 
+        // Variable width (the variable record already gives it)
+        if pos + 4 > data.len() {
+            break;
+        }
+        pos += 4;
+
         // Label count
         if pos + 4 > data.len() {
             break;
         }
-        let label_count = read_i32_le(data, pos)? as usize;
+        let label_count = read_i32(data, pos, bswap)? as usize;
         pos += 4;
 
-        let mut labels = Vec::with_capacity(label_count);
+        let mut labels = Vec::with_capacity(label_count.min(io_utils::MAX_PREALLOC));
         for _ in 0..label_count {
             // Value length + value
             if pos + 4 > data.len() {
                 break;
             }
-            let value_len = read_i32_le(data, pos)? as usize;
+            let value_len = read_i32(data, pos, bswap)? as usize;
             pos += 4;
-            if pos + value_len > data.len() {
+            if value_len > data.len() - pos {
                 break;
             }
             let value = data[pos..pos + value_len].to_vec();
@@ -61,9 +71,9 @@ pub fn parse_long_string_labels(data: &[u8]) -> Result<Vec<LongStringLabelSet>>
             if pos + 4 > data.len() {
                 break;
             }
-            let label_len = read_i32_le(data, pos)? as usize;
+            let label_len = read_i32(data, pos, bswap)? as usize;
             pos += 4;
-            if pos + label_len > data.len() {
+            if label_len > data.len() - pos {
                 break;
             }
             let label = data[pos..pos + label_len].to_vec();
@@ -78,7 +88,7 @@ pub fn parse_long_string_labels(data: &[u8]) -> Result<Vec<LongStringLabelSet>>
     Ok(result)
 }
 
-fn read_i32_le(data: &[u8], pos: usize) -> Result<i32> {
+fn read_i32(data: &[u8], pos: usize, bswap: bool) -> Result<i32> {
     if pos + 4 > data.len() {
         return Err(SpssError::TruncatedFile {
             expected: pos + 4,
@@ -86,5 +96,32 @@ fn read_i32_le(data: &[u8], pos: usize) -> Result<i32> {
         });
     }
     let bytes: [u8; 4] = data[pos..pos + 4].try_into().unwrap();
-    Ok(i32::from_le_bytes(bytes))
+    Ok(if bswap {
+        i32::from_be_bytes(bytes)
+    } else {
+        i32::from_le_bytes(bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_long_string_labels() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&7i32.to_le_bytes());
+        data.extend_from_slice(b"comment");
+        data.extend_from_slice(&12i32.to_le_bytes()); // width
+        data.extend_from_slice(&1i32.to_le_bytes()); // one label
+        data.extend_from_slice(&12i32.to_le_bytes());
+        data.extend_from_slice(b"no comment  ");
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend_from_slice(b"None");
+
+        let sets = parse_long_string_labels(&data, false).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].var_name, "comment");
+        assert_eq!(sets[0].labels, vec![(b"no comment  ".to_vec(), b"None".to_vec())]);
+    }
 }
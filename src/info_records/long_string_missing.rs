@@ -21,15 +21,17 @@ pub struct LongStringMissingEntry {
 ///   4-byte value_length (per SPSS spec: the width)
 ///   For each missing value:
 ///     value_length bytes of value
-pub fn parse_long_string_missing(data: &[u8]) -> Result<Vec<LongStringMissingEntry>> {
+///
+/// The 4-byte integers are big-endian when `bswap`.
+pub fn parse_long_string_missing(data: &[u8], bswap: bool) -> Result<Vec<LongStringMissingEntry>> {
     let mut result = Vec::new();
     let mut pos = 0;
 
     while pos + 4 <= data.len() {
         // Variable name
-        let name_len = read_i32_le(data, pos)? as usize;
+        let name_len = read_i32(data, pos, bswap)? as usize;
         pos += 4;
-        if pos + name_len > data.len() {
+        if name_len > data.len() - pos {
             break;
         }
         let var_name = String::from_utf8_lossy(&data[pos..pos + name_len])
@@ -48,12 +50,12 @@ pub fn parse_long_string_missing(data: &[u8]) -> Result<Vec<LongStringMissingEnt
         if pos + 4 > data.len() {
             break;
         }
-        let value_len = read_i32_le(data, pos)? as usize;
+        let value_len = read_i32(data, pos, bswap)? as usize;
         pos += 4;
 
         let mut values = Vec::with_capacity(n_values as usize);
         for _ in 0..n_values {
-            if pos + value_len > data.len() {
+            if value_len > data.len() - pos {
                 break;
             }
             values.push(data[pos..pos + value_len].to_vec());
@@ -70,7 +72,7 @@ pub fn parse_long_string_missing(data: &[u8]) -> Result<Vec<LongStringMissingEnt
     Ok(result)
 }
 
-fn read_i32_le(data: &[u8], pos: usize) -> Result<i32> {
+fn read_i32(data: &[u8], pos: usize, bswap: bool) -> Result<i32> {
     if pos + 4 > data.len() {
         return Err(SpssError::TruncatedFile {
             expected: pos + 4,
@@ -78,5 +80,9 @@ fn read_i32_le(data: &[u8], pos: usize) -> Result<i32> {
         });
     }
     let bytes: [u8; 4] = data[pos..pos + 4].try_into().unwrap();
-    Ok(i32::from_le_bytes(bytes))
+    Ok(if bswap {
+        i32::from_be_bytes(bytes)
+    } else {
+        i32::from_le_bytes(bytes)
+    })
 }
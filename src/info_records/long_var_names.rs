@@ -16,7 +16,7 @@ pub fn parse_long_var_names(data: &[u8]) -> Vec<(String, String)> {
         }
         if let Some((short, long)) = pair.split_once('=') {
             result.push((
-                short.trim().to_uppercase(),
+                short.trim().to_string(),
                 long.trim().to_string(),
             ));
         }
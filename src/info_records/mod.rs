@@ -7,11 +7,14 @@ pub mod very_long_strings;
 pub mod encoding_record;
 pub mod long_string_labels;
 pub mod long_string_missing;
+pub mod attributes;
+pub mod date_info;
+pub mod extended_ncases;
 
 use std::io::Read;
 
 use crate::constants::*;
-use crate::error::Result;
+use crate::error::{Result, SpssError};
 use crate::io_utils::SavReader;
 
 /// Header for a type 7 (info) record.
@@ -35,9 +38,14 @@ impl InfoRecordHeader {
         })
     }
 
-    /// Total data bytes for this info record.
-    pub fn data_len(&self) -> usize {
-        (self.size as usize) * (self.count as usize)
+    /// Total data bytes for this info record. Fails if the size or count
+    /// is negative or their product overflows.
+    pub fn data_len(&self) -> Result<usize> {
+        let invalid = |n: i32| SpssError::InvalidLength(n.into());
+        let size = usize::try_from(self.size).map_err(|_| invalid(self.size))?;
+        let count = usize::try_from(self.count).map_err(|_| invalid(self.count))?;
+        size.checked_mul(count)
+            .ok_or(SpssError::InvalidLength(i64::from(self.size) * i64::from(self.count)))
     }
 }
 
@@ -54,7 +62,14 @@ pub enum InfoRecord {
     LongStringLabels(Vec<long_string_labels::LongStringLabelSet>),
     LongStringMissing(Vec<long_string_missing::LongStringMissingEntry>),
     MrSets(Vec<mr_sets::RawMrSet>),
-    Unknown { subtype: i32 },
+    DateInfo(Vec<[i32; 3]>),
+    ExtendedNcases(Option<i64>),
+    /// Subtype 17 text, still in the file encoding.
+    FileAttributes(Vec<u8>),
+    /// Subtype 18 text, still in the file encoding.
+    VariableAttributes(Vec<u8>),
+    /// A subtype ambers does not interpret, with its raw payload.
+    Unknown { subtype: i32, data: Vec<u8> },
 }
 
 /// Parse a type 7 info record based on its subtype.
@@ -62,10 +77,16 @@ pub fn parse_info_record<R: Read>(
     reader: &mut SavReader<R>,
     header: &InfoRecordHeader,
 ) -> Result<InfoRecord> {
-    let data_len = header.data_len();
+    let data_len = header.data_len()?;
+    let limit = reader.limits().max_record_bytes;
+    if data_len > limit {
+        return Err(SpssError::LimitExceeded(format!(
+            "a record of {data_len} bytes (the limit is {limit})"
+        )));
+    }
 
     match header.subtype {
-        INFO_MR_SETS => {
+        INFO_MR_SETS | INFO_EXTENDED_MR_SETS => {
             let data = reader.read_bytes(data_len)?;
             let sets = mr_sets::parse_mr_sets(&data);
             Ok(InfoRecord::MrSets(sets))
@@ -78,6 +99,10 @@ pub fn parse_info_record<R: Read>(
             let info = float_info::FloatInfo::parse(reader)?;
             Ok(InfoRecord::FloatInfo(info))
         }
+        INFO_DATE_INFO => {
+            let groups = date_info::parse_date_info(reader, header.size, header.count)?;
+            Ok(InfoRecord::DateInfo(groups))
+        }
         INFO_VAR_DISPLAY => {
             let entries = var_display::parse_var_display(reader, header.count)?;
             Ok(InfoRecord::VarDisplay(entries))
@@ -92,6 +117,10 @@ pub fn parse_info_record<R: Read>(
             let entries = very_long_strings::parse_very_long_strings(&data);
             Ok(InfoRecord::VeryLongStrings(entries))
         }
+        INFO_EXTENDED_NCASES => {
+            let ncases = extended_ncases::parse_extended_ncases(reader, header.size, header.count)?;
+            Ok(InfoRecord::ExtendedNcases(ncases))
+        }
         INFO_ENCODING => {
             let data = reader.read_bytes(data_len)?;
             let name = encoding_record::parse_encoding_record(&data);
@@ -99,20 +128,51 @@ pub fn parse_info_record<R: Read>(
         }
         INFO_LONG_STRING_LABELS => {
             let data = reader.read_bytes(data_len)?;
-            let labels = long_string_labels::parse_long_string_labels(&data)?;
+            let labels = long_string_labels::parse_long_string_labels(&data, reader.bswap())?;
             Ok(InfoRecord::LongStringLabels(labels))
         }
         INFO_LONG_STRING_MISSING => {
             let data = reader.read_bytes(data_len)?;
-            let entries = long_string_missing::parse_long_string_missing(&data)?;
+            let entries = long_string_missing::parse_long_string_missing(&data, reader.bswap())?;
             Ok(InfoRecord::LongStringMissing(entries))
         }
+        INFO_FILE_ATTRIBUTES => {
+            let data = reader.read_bytes(data_len)?;
+            Ok(InfoRecord::FileAttributes(data))
+        }
+        INFO_VAR_ATTRIBUTES => {
+            let data = reader.read_bytes(data_len)?;
+            Ok(InfoRecord::VariableAttributes(data))
+        }
         _ => {
-            // Unknown subtype -- skip the data
-            reader.skip(data_len)?;
+            // Unknown subtype -- keep the data as-is
+            let data = reader.read_bytes(data_len)?;
             Ok(InfoRecord::Unknown {
                 subtype: header.subtype,
+                data,
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_unknown_subtype_keeps_payload() {
+        let header = InfoRecordHeader {
+            subtype: 99,
+            size: 1,
+            count: 5,
+        };
+        let mut reader = SavReader::new(Cursor::new(b"hello".to_vec()));
+        let record = parse_info_record(&mut reader, &header).unwrap();
+        assert!(matches!(
+            record,
+            InfoRecord::Unknown { subtype: 99, data } if data == b"hello"
+        ));
+    }
+}
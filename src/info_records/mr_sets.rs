@@ -11,14 +11,17 @@ pub struct RawMrSet {
     pub var_names: Vec<String>,
 }
 
-/// Parse subtype 7 multiple response sets.
+/// Parse subtype 7 (or extended subtype 19) multiple response sets.
 ///
 /// Format: newline-separated set definitions. Each set is one line:
 ///   $NAME=Dn counted_value label_len label var1 var2 ...\n   (dichotomy)
+///   $NAME=E f n counted_value label_len label var1 ...\n     (dichotomy, subtype 19)
 ///   $NAME=C label_len label var1 var2 ...\n                   (category)
 ///
 /// Where n is the ASCII length of counted_value (can be multi-digit),
 /// and label_len is the ASCII length of the label string that follows.
+/// The `E` form, for sets labelled by their counted values, adds a label
+/// source flag `f` (1, or 11 for the first variable's label).
 pub fn parse_mr_sets(data: &[u8]) -> Vec<RawMrSet> {
     let text = String::from_utf8_lossy(data);
     let mut sets = Vec::new();
@@ -55,13 +58,18 @@ fn parse_one_mr_set(text: &str) -> Option<RawMrSet> {
 
     let (mr_type, counted_value, after_cv) = match type_char {
         'D' | 'E' => {
+            // Skip the label source flag of the E form
+            let rest = match type_char {
+                'E' => parse_number(rest.trim_start())?.1.trim_start(),
+                _ => rest,
+            };
             // Dichotomy: Dn counted_value ...
             // n is ASCII digits = length of counted value
             let (cv_len, after_len) = parse_number(rest)?;
             // Skip one space after the number
             let after_space = after_len.strip_prefix(' ').unwrap_or(after_len);
             // Read cv_len characters as counted_value
-            if after_space.len() < cv_len {
+            if !after_space.is_char_boundary(cv_len) {
                 return None;
             }
             let counted_value = after_space[..cv_len].to_string();
@@ -81,8 +89,9 @@ fn parse_one_mr_set(text: &str) -> Option<RawMrSet> {
     // Skip one space after label_len
     let after_space = after_label_len.strip_prefix(' ').unwrap_or(after_label_len);
 
-    // Read label_len characters as the label
-    if after_space.len() < label_len {
+    // Read label_len characters as the label (bytes of the file's
+    // encoding, which need not fall on a character boundary here)
+    if !after_space.is_char_boundary(label_len) {
         // Label extends to end of available text
         let label = after_space.trim().to_string();
         return Some(RawMrSet {
@@ -185,4 +194,21 @@ mod tests {
         assert_eq!(sets[0].label, "Label");
         assert_eq!(sets[0].var_names, vec!["V1", "V2"]);
     }
+    #[test]
+    fn test_parse_extended_dichotomy_set() {
+        let data = b"$ad=E 11 1 1 6 Brands V1 V2\n$x=E 1 2 10 0  V3\n";
+        let sets = parse_mr_sets(data);
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].mr_type, MrType::MultipleDichotomy);
+        assert_eq!(sets[0].counted_value.as_deref(), Some("1"));
+        assert_eq!(sets[0].label, "Brands");
+        assert_eq!(sets[0].var_names, vec!["V1", "V2"]);
+        assert_eq!(sets[1].counted_value.as_deref(), Some("10"));
+        assert_eq!(sets[1].label, "");
+        assert_eq!(sets[1].var_names, vec!["V3"]);
+
+        // A label length in bytes of another encoding
+        let sets = parse_mr_sets(b"$y=C 4 caf\xe9 V1\n");
+        assert_eq!(sets[0].name, "y");
+    }
 }
@@ -2,7 +2,7 @@ use std::io::Read;
 
 use crate::constants::{Alignment, Measure};
 use crate::error::Result;
-use crate::io_utils::SavReader;
+use crate::io_utils::{self, SavReader};
 
 /// A single variable display entry (from subtype 11).
 #[derive(Debug, Clone)]
@@ -16,7 +16,9 @@ pub struct VarDisplayEntry {
 ///
 /// The record contains `count` i32 values. If count is divisible by 3,
 /// each variable gets (measure, width, alignment). If not divisible by 3,
-/// each variable gets (measure, alignment) — no width field.
+/// each variable gets (measure, alignment) — no width field. `count` has
+/// been checked to be non-negative (`InfoRecordHeader::data_len`).
+#[allow(clippy::manual_is_multiple_of)]
 pub fn parse_var_display<R: Read>(
     reader: &mut SavReader<R>,
     count: i32,
@@ -25,7 +27,7 @@ pub fn parse_var_display<R: Read>(
     let has_width = count % 3 == 0;
 
     let n_vars = if has_width { count / 3 } else { count / 2 };
-    let mut entries = Vec::with_capacity(n_vars);
+    let mut entries = Vec::with_capacity(n_vars.min(io_utils::MAX_PREALLOC));
 
     for _ in 0..n_vars {
         let measure = Measure::from_i32(reader.read_i32()?);
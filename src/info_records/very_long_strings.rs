@@ -5,6 +5,7 @@ use crate::io_utils;
 /// Format: `VARNAME=WIDTH\0\tVARNAME2=WIDTH2\0\t...`
 ///
 /// Returns a vector of (variable_name, true_width) pairs.
+#[allow(clippy::collapsible_if, clippy::manual_pattern_char_comparison)]
 pub fn parse_very_long_strings(data: &[u8]) -> Vec<(String, usize)> {
     let text = io_utils::bytes_to_string_lossy(data);
     let mut result = Vec::new();
@@ -17,7 +18,7 @@ pub fn parse_very_long_strings(data: &[u8]) -> Vec<(String, usize)> {
         }
         if let Some((name, width_str)) = entry.split_once('=') {
             if let Ok(width) = width_str.trim().parse::<usize>() {
-                result.push((name.trim().to_uppercase(), width));
+                result.push((name.trim().to_string(), width));
             }
         }
     }
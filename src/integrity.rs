@@ -0,0 +1,1074 @@
+//! Structural checks of a .sav/.zsav file: the header, the dictionary
+//! records and the data, found by [`validate_sav`].
+//!
+//! Unlike `SpssMetadata::validate`, which lints a dictionary that was read
+//! successfully, these checks look at the file itself and report problems
+//! at the byte offset where they occur, going on past anything a reader
+//! could skip.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+
+use crate::compression::zlib;
+use crate::constants::*;
+use crate::encoding;
+use crate::error::Result;
+use crate::header::FileHeader;
+use crate::info_records::{self, InfoRecord, InfoRecordHeader};
+use crate::io_utils::{self, SavReader};
+use crate::options::{ScanOptions, TruncationPolicy};
+use crate::scanner::SavScanner;
+
+/// Byte offsets of the header fields that findings point at.
+const NOMINAL_CASE_SIZE_OFFSET: u64 = 68;
+const WEIGHT_INDEX_OFFSET: u64 = 76;
+const BIAS_OFFSET: u64 = 84;
+
+/// The part of a file an [`IntegrityFinding`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntegrityCheck {
+    /// Header fields contradict each other or the dictionary: the magic
+    /// and the compression, the weight index, the case count, the bias.
+    Header,
+    /// Records out of order: variable records after other records, a
+    /// value label record not followed by its variable list, records that
+    /// appear more than once.
+    RecordOrder,
+    /// A dictionary record cannot be read, or has impossible counts or
+    /// sizes.
+    Record,
+    /// The variable records do not add up to the header's nominal case
+    /// size, or string continuation records are missing or stray.
+    SlotCount,
+    /// A value label variable list names a slot that does not exist, a
+    /// continuation slot, or variables of different types.
+    ValueLabelIndex,
+    /// Character encoding records are missing, unknown or contradict each
+    /// other.
+    Encoding,
+    /// The .zsav zlib header, trailer or compressed blocks are
+    /// inconsistent.
+    ZsavTrailer,
+    /// The data cannot be decoded, or ends in the middle of a case.
+    Data,
+    /// The number of cases in the data differs from the declared count.
+    CaseCount,
+}
+
+impl IntegrityCheck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntegrityCheck::Header => "header",
+            IntegrityCheck::RecordOrder => "record_order",
+            IntegrityCheck::Record => "record",
+            IntegrityCheck::SlotCount => "slot_count",
+            IntegrityCheck::ValueLabelIndex => "value_label_index",
+            IntegrityCheck::Encoding => "encoding",
+            IntegrityCheck::ZsavTrailer => "zsav_trailer",
+            IntegrityCheck::Data => "data",
+            IntegrityCheck::CaseCount => "case_count",
+        }
+    }
+}
+
+/// How serious an [`IntegrityFinding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// The file breaks the format; readers may fail or lose data.
+    Error,
+    /// Unusual but readable.
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// One problem found by [`validate_sav`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityFinding {
+    pub check: IntegrityCheck,
+    pub severity: Severity,
+    /// Byte offset of the field, record or block concerned.
+    pub offset: u64,
+    pub message: String,
+}
+
+impl fmt::Display for IntegrityFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "offset {}: {}: {}: {}",
+            self.offset,
+            self.severity.as_str(),
+            self.check.as_str(),
+            self.message
+        )
+    }
+}
+
+/// The result of [`validate_sav`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// Problems in the order they were found: header, dictionary records
+    /// in file order, then the .zsav blocks and the data.
+    pub findings: Vec<IntegrityFinding>,
+    /// Cases declared by the header, or by subtype 16 when the header's
+    /// count is unknown.
+    pub declared_cases: Option<usize>,
+    /// Whole cases found in the data; `None` when the data could not be
+    /// read.
+    pub cases: Option<usize>,
+}
+
+impl IntegrityReport {
+    /// Whether no finding is an error (warnings allowed).
+    pub fn is_valid(&self) -> bool {
+        self.findings.iter().all(|f| f.severity != Severity::Error)
+    }
+
+    fn push(
+        &mut self,
+        check: IntegrityCheck,
+        severity: Severity,
+        offset: u64,
+        message: impl Into<String>,
+    ) {
+        self.findings.push(IntegrityFinding {
+            check,
+            severity,
+            offset,
+            message: message.into(),
+        });
+    }
+
+    fn error(&mut self, check: IntegrityCheck, offset: u64, message: impl Into<String>) {
+        self.push(check, Severity::Error, offset, message);
+    }
+
+    fn warning(&mut self, check: IntegrityCheck, offset: u64, message: impl Into<String>) {
+        self.push(check, Severity::Warning, offset, message);
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.findings.is_empty() {
+            return write!(f, "no problems found");
+        }
+        for (i, finding) in self.findings.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{finding}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the structure of an SPSS .sav or .zsav file.
+///
+/// Goes through the header and every dictionary record, checking field
+/// values, record order, slot counts against the nominal case size, value
+/// label variable indexes and the encoding records; for .zsav files, the
+/// zlib header, the trailer and every compressed block; and finally
+/// decodes the data to compare the number of cases with the declared
+/// count. Problems are reported with their byte offset rather than as an
+/// error, carrying on as far as the file can be followed.
+///
+/// Only failing to open or read the file at all is an `Err`.
+///
+/// # Example
+/// ```no_run
+/// let report = ambers::validate_sav("survey.sav").unwrap();
+/// if !report.is_valid() {
+///     eprintln!("{report}");
+/// }
+/// ```
+pub fn validate_sav(path: impl AsRef<Path>) -> Result<IntegrityReport> {
+    validate_sav_reader(BufReader::new(File::open(path)?))
+}
+
+/// Check the structure of an SPSS file from a reader (see
+/// [`validate_sav`]).
+pub fn validate_sav_reader<R: Read + Seek>(mut reader: R) -> Result<IntegrityReport> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut report = IntegrityReport::default();
+
+    let mut sav = SavReader::new(&mut reader);
+    let header = match FileHeader::parse(&mut sav) {
+        Ok(header) => header,
+        Err(e) => {
+            report.error(IntegrityCheck::Header, 0, e.to_string());
+            return Ok(report);
+        }
+    };
+    check_header(&header, &mut report);
+
+    let mut dict = Walked::default();
+    if let Err(e) = walk_dictionary(&mut sav, &header, len, &mut dict, &mut report) {
+        let at = sav.inner_mut().stream_position()?;
+        report.error(IntegrityCheck::Record, at, e.to_string());
+    }
+    let Some(data_start) = dict.data_start else {
+        return Ok(report);
+    };
+    check_dictionary(&header, &dict, &mut report);
+
+    if header.compression == Compression::Zlib
+        && !check_zsav(&mut sav, &header, data_start, len, &mut report)?
+    {
+        return Ok(report);
+    }
+
+    reader.seek(SeekFrom::Start(0))?;
+    check_data(&mut reader, &header, &dict, data_start, &mut report);
+    Ok(report)
+}
+
+/// What the walk over the dictionary learned, for the checks after it.
+#[derive(Default)]
+struct Walked {
+    /// Raw type (0 numeric, the width for strings, -1 for a continuation)
+    /// and short name of each case slot.
+    slots: Vec<(i32, String)>,
+    /// Offset and count of the subtype 11 record.
+    var_display: Option<(u64, i32)>,
+    /// Offset and character code of the subtype 3 record.
+    character_code: Option<(u64, i32)>,
+    /// Offset and name of the subtype 20 record.
+    encoding: Option<(u64, String)>,
+    /// Offset and case count of the subtype 16 record.
+    extended_ncases: Option<(u64, Option<i64>)>,
+    /// Offset of the termination record.
+    termination: u64,
+    /// Offset of the first byte after the dictionary; `None` when the
+    /// dictionary could not be followed to its end.
+    data_start: Option<u64>,
+}
+
+fn check_header(header: &FileHeader, report: &mut IntegrityReport) {
+    let zlib = header.compression == Compression::Zlib;
+    if (&header.magic == b"$FL3") != zlib {
+        report.error(
+            IntegrityCheck::Header,
+            0,
+            format!(
+                "magic {:?} does not match {:?} compression",
+                String::from_utf8_lossy(&header.magic),
+                header.compression
+            ),
+        );
+    }
+    if header.ncases < -1 {
+        report.error(
+            IntegrityCheck::Header,
+            FileHeader::NCASES_OFFSET,
+            format!("invalid case count {}", header.ncases),
+        );
+    }
+    if header.compression != Compression::None && header.bias != 100.0 {
+        report.warning(
+            IntegrityCheck::Header,
+            BIAS_OFFSET,
+            format!("compression bias {} instead of 100", header.bias),
+        );
+    }
+}
+
+/// Read the dictionary record by record up to the termination record,
+/// recording what later checks need in `dict`. Stops early (leaving
+/// `dict.data_start` unset) at a record whose extent cannot be known.
+fn walk_dictionary<R: Read + Seek>(
+    sav: &mut SavReader<R>,
+    header: &FileHeader,
+    len: u64,
+    dict: &mut Walked,
+    report: &mut IntegrityReport,
+) -> Result<()> {
+    let pos = |sav: &mut SavReader<R>| -> Result<u64> { Ok(sav.inner_mut().stream_position()?) };
+    // Bytes left after the current position.
+    let fits = |at: u64, n: i64| n >= 0 && at.saturating_add(n as u64) <= len;
+
+    let mut other_records = false;
+    let mut documents = false;
+    let mut subtypes = HashSet::new();
+    // Continuation records still expected after the last long string
+    // variable, and its name.
+    let mut continuations = 0;
+    let mut long_string = String::new();
+    // A record type read while checking the record before it.
+    let mut pending = None;
+    loop {
+        let (at, record_type) = match pending.take() {
+            Some(pending) => pending,
+            None => (pos(sav)?, sav.read_i32()?),
+        };
+        if record_type != RECORD_TYPE_VARIABLE {
+            if continuations > 0 {
+                report.error(
+                    IntegrityCheck::SlotCount,
+                    at,
+                    format!("string variable {long_string} lacks {continuations} continuations"),
+                );
+                continuations = 0;
+            }
+            other_records = true;
+        }
+
+        match record_type {
+            RECORD_TYPE_VARIABLE => {
+                if other_records {
+                    report.error(
+                        IntegrityCheck::RecordOrder,
+                        at,
+                        "variable record after other dictionary records",
+                    );
+                }
+                let raw_type = sav.read_i32()?;
+                let has_label = sav.read_i32()?;
+                let n_missing = sav.read_i32()?;
+                sav.skip(8)?; // print and write formats
+                let name = io_utils::bytes_to_string_lossy(io_utils::trim_trailing_padding(
+                    &sav.read_bytes(8)?,
+                ));
+                if !(-1..=255).contains(&raw_type) {
+                    report.error(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("variable {name}: invalid type {raw_type}"),
+                    );
+                }
+                if raw_type == -1 {
+                    if continuations == 0 {
+                        report.error(
+                            IntegrityCheck::SlotCount,
+                            at,
+                            "continuation record without a long string variable before it",
+                        );
+                    } else {
+                        continuations -= 1;
+                    }
+                } else if continuations > 0 {
+                    report.error(
+                        IntegrityCheck::SlotCount,
+                        at,
+                        format!(
+                            "string variable {long_string} lacks {continuations} continuations"
+                        ),
+                    );
+                    continuations = 0;
+                }
+                if raw_type > 8 {
+                    continuations = (raw_type as usize).div_ceil(8) - 1;
+                    long_string = name.clone();
+                }
+                match has_label {
+                    0 => {}
+                    1 => {
+                        let label_len = sav.read_i32()? as i64;
+                        let padded = io_utils::round_up(label_len.max(0) as usize, 4) as i64;
+                        if !fits(pos(sav)?, label_len) || !fits(pos(sav)?, padded) {
+                            report.error(
+                                IntegrityCheck::Record,
+                                at,
+                                format!("variable {name}: label of {label_len} bytes past the end"),
+                            );
+                            return Ok(());
+                        }
+                        sav.skip(padded as usize)?;
+                    }
+                    _ => report.error(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("variable {name}: label flag {has_label} is neither 0 nor 1"),
+                    ),
+                }
+                let valid_missing = match raw_type {
+                    0 => matches!(n_missing, -3..=3) && n_missing != -1,
+                    _ => matches!(n_missing, 0..=3),
+                };
+                if !valid_missing {
+                    report.error(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("variable {name}: invalid missing value count {n_missing}"),
+                    );
+                    if !matches!(n_missing, -3..=3) {
+                        return Ok(());
+                    }
+                }
+                sav.skip(n_missing.unsigned_abs() as usize * 8)?;
+                dict.slots.push((raw_type, name));
+            }
+
+            RECORD_TYPE_VALUE_LABEL => {
+                let count = sav.read_i32()?;
+                // At least 16 bytes per label.
+                if !fits(pos(sav)?, count as i64 * 16) {
+                    report.error(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("value label record with invalid count {count}"),
+                    );
+                    return Ok(());
+                }
+                for _ in 0..count {
+                    sav.skip(8)?;
+                    let label_len = sav.read_bytes(1)?[0] as usize;
+                    sav.skip(io_utils::round_up(label_len + 1, 8) - 1)?;
+                }
+                let next_at = pos(sav)?;
+                let next = sav.read_i32()?;
+                if next != RECORD_TYPE_VALUE_LABEL_VARS {
+                    report.error(
+                        IntegrityCheck::RecordOrder,
+                        at,
+                        format!(
+                            "value labels followed by a type {next} record, not their variables"
+                        ),
+                    );
+                    pending = Some((next_at, next));
+                } else if !check_label_variables(sav, at, len, dict, report)? {
+                    return Ok(());
+                }
+            }
+
+            RECORD_TYPE_VALUE_LABEL_VARS => {
+                report.error(
+                    IntegrityCheck::RecordOrder,
+                    at,
+                    "value label variable list without a value label record before it",
+                );
+                if !check_label_variables(sav, at, len, dict, report)? {
+                    return Ok(());
+                }
+            }
+
+            RECORD_TYPE_DOCUMENT => {
+                if documents {
+                    report.warning(IntegrityCheck::RecordOrder, at, "second document record");
+                }
+                documents = true;
+                let lines = sav.read_i32()?;
+                if !fits(pos(sav)?, lines as i64 * 80) {
+                    report.error(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("document record with invalid line count {lines}"),
+                    );
+                    return Ok(());
+                }
+                sav.skip(lines as usize * 80)?;
+            }
+
+            RECORD_TYPE_INFO => {
+                if !check_info_record(sav, at, header, len, &mut subtypes, dict, report)? {
+                    return Ok(());
+                }
+            }
+
+            RECORD_TYPE_DICT_TERMINATION => {
+                let filler = sav.read_i32()?;
+                if filler != 0 {
+                    report.warning(
+                        IntegrityCheck::Record,
+                        at,
+                        format!("termination record filler is {filler}, not 0"),
+                    );
+                }
+                dict.termination = at;
+                dict.data_start = Some(pos(sav)?);
+                return Ok(());
+            }
+
+            _ => {
+                report.error(
+                    IntegrityCheck::Record,
+                    at,
+                    format!("unknown record type {record_type}"),
+                );
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Check an info record (the record type already read). Returns false
+/// when its length is impossible, so the walk cannot go on.
+fn check_info_record<R: Read + Seek>(
+    sav: &mut SavReader<R>,
+    at: u64,
+    header: &FileHeader,
+    len: u64,
+    subtypes: &mut HashSet<i32>,
+    dict: &mut Walked,
+    report: &mut IntegrityReport,
+) -> Result<bool> {
+    let info = InfoRecordHeader::parse(sav)?;
+    let data_len = info.size as i64 * info.count as i64;
+    let start = sav.inner_mut().stream_position()?;
+    if info.size < 0 || info.count < 0 || start.saturating_add(data_len as u64) > len {
+        report.error(
+            IntegrityCheck::Record,
+            at,
+            format!(
+                "subtype {} record of {} x {} bytes runs past the end of the file",
+                info.subtype, info.count, info.size
+            ),
+        );
+        return Ok(false);
+    }
+    let data = sav.read_bytes(data_len as usize)?;
+    if !subtypes.insert(info.subtype) {
+        report.warning(
+            IntegrityCheck::RecordOrder,
+            at,
+            format!("second subtype {} record", info.subtype),
+        );
+    }
+    let expected_size = match info.subtype {
+        INFO_INTEGER | INFO_VAR_DISPLAY | INFO_DATE_INFO => Some(4),
+        INFO_FLOAT | INFO_EXTENDED_NCASES => Some(8),
+        INFO_LONG_NAMES | INFO_VERY_LONG_STRINGS | INFO_ENCODING => Some(1),
+        _ => None,
+    };
+    let expected_count = match info.subtype {
+        INFO_INTEGER => Some(8),
+        INFO_FLOAT => Some(3),
+        INFO_EXTENDED_NCASES => Some(2),
+        _ => None,
+    };
+    if expected_size.is_some_and(|size| size != info.size)
+        || expected_count.is_some_and(|count| count != info.count)
+    {
+        report.error(
+            IntegrityCheck::Record,
+            at,
+            format!(
+                "subtype {} record has {} elements of {} bytes",
+                info.subtype, info.count, info.size
+            ),
+        );
+        return Ok(true);
+    }
+    let mut payload = SavReader::new(Cursor::new(data));
+    payload.set_bswap(header.bswap);
+    match info_records::parse_info_record(&mut payload, &info) {
+        Ok(InfoRecord::IntegerInfo(integer)) => {
+            dict.character_code = Some((at, integer.character_code));
+            if (integer.endianness == 1) != header.bswap {
+                report.warning(
+                    IntegrityCheck::Header,
+                    at,
+                    format!(
+                        "integer info says endianness {} but the header is {}-endian",
+                        integer.endianness,
+                        if header.bswap { "big" } else { "little" }
+                    ),
+                );
+            }
+            if integer.floating_point_rep != 1 {
+                report.warning(
+                    IntegrityCheck::Header,
+                    at,
+                    format!(
+                        "floating-point representation {} is not IEEE 754",
+                        integer.floating_point_rep
+                    ),
+                );
+            }
+        }
+        Ok(InfoRecord::VarDisplay(_)) => dict.var_display = Some((at, info.count)),
+        Ok(InfoRecord::Encoding(name)) => dict.encoding = Some((at, name)),
+        Ok(InfoRecord::ExtendedNcases(n)) => dict.extended_ncases = Some((at, n)),
+        Ok(_) => {}
+        Err(e) => report.error(
+            IntegrityCheck::Record,
+            at,
+            format!("subtype {} record: {e}", info.subtype),
+        ),
+    }
+    Ok(true)
+}
+
+/// Check a value label variable list (the record type already read).
+/// Returns false when its length is impossible, so the walk cannot go on.
+fn check_label_variables<R: Read + Seek>(
+    sav: &mut SavReader<R>,
+    at: u64,
+    len: u64,
+    dict: &Walked,
+    report: &mut IntegrityReport,
+) -> Result<bool> {
+    let count = sav.read_i32()?;
+    let start = sav.inner_mut().stream_position()?;
+    if count <= 0 || start + count as u64 * 4 > len {
+        report.error(
+            IntegrityCheck::Record,
+            at,
+            format!("value label variable list with invalid count {count}"),
+        );
+        return Ok(false);
+    }
+    let mut first_is_string = None;
+    for i in 0..count as u64 {
+        let index_at = start + i * 4;
+        let index = sav.read_i32()?;
+        let slot = usize::try_from(index)
+            .ok()
+            .and_then(|index| dict.slots.get(index.checked_sub(1)?));
+        match slot {
+            None => report.error(
+                IntegrityCheck::ValueLabelIndex,
+                index_at,
+                format!(
+                    "variable index {index} is not a slot (1 to {})",
+                    dict.slots.len()
+                ),
+            ),
+            Some((-1, _)) => report.error(
+                IntegrityCheck::ValueLabelIndex,
+                index_at,
+                format!("variable index {index} is a string continuation slot"),
+            ),
+            Some((raw_type, name)) => {
+                let is_string = *raw_type > 0;
+                if *first_is_string.get_or_insert(is_string) != is_string {
+                    report.error(
+                        IntegrityCheck::ValueLabelIndex,
+                        index_at,
+                        format!(
+                            "variable {name} differs in type from the first variable of the list"
+                        ),
+                    );
+                }
+                if *raw_type > 8 {
+                    report.warning(
+                        IntegrityCheck::ValueLabelIndex,
+                        index_at,
+                        format!(
+                            "value labels for long string variable {name} belong in subtype 21"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Checks of the dictionary as a whole, once all records have been seen.
+fn check_dictionary(header: &FileHeader, dict: &Walked, report: &mut IntegrityReport) {
+    let slots = dict.slots.len();
+    if header.nominal_case_size == -1 {
+        report.warning(
+            IntegrityCheck::SlotCount,
+            NOMINAL_CASE_SIZE_OFFSET,
+            "nominal case size is unknown (-1)",
+        );
+    } else if usize::try_from(header.nominal_case_size) != Ok(slots) {
+        report.error(
+            IntegrityCheck::SlotCount,
+            NOMINAL_CASE_SIZE_OFFSET,
+            format!(
+                "nominal case size is {} but the variable records have {slots} slots",
+                header.nominal_case_size
+            ),
+        );
+    }
+
+    if header.weight_index != 0 {
+        let slot = usize::try_from(header.weight_index)
+            .ok()
+            .and_then(|index| dict.slots.get(index - 1));
+        match slot {
+            Some((0, _)) => {}
+            Some((_, name)) => report.error(
+                IntegrityCheck::Header,
+                WEIGHT_INDEX_OFFSET,
+                format!("weight variable {name} is not numeric"),
+            ),
+            None => report.error(
+                IntegrityCheck::Header,
+                WEIGHT_INDEX_OFFSET,
+                format!(
+                    "weight index {} is not a slot (1 to {slots})",
+                    header.weight_index
+                ),
+            ),
+        }
+    }
+
+    if let Some((at, count)) = dict.var_display {
+        let variables = dict.slots.iter().filter(|(t, _)| *t != -1).count() as i32;
+        if count != variables * 3 && count != variables * 2 {
+            report.warning(
+                IntegrityCheck::SlotCount,
+                at,
+                format!("display record has {count} entries for {variables} variables"),
+            );
+        }
+    }
+
+    match (&dict.encoding, dict.character_code) {
+        (None, None) => report.warning(
+            IntegrityCheck::Encoding,
+            dict.termination,
+            "no encoding record (subtype 20) or character code (subtype 3); assuming windows-1252",
+        ),
+        (Some((at, name)), code) => {
+            match encoding_rs::Encoding::for_label(name.trim().to_ascii_lowercase().as_bytes()) {
+                None => report.error(
+                    IntegrityCheck::Encoding,
+                    *at,
+                    format!("unknown encoding {name:?}"),
+                ),
+                // Codes 1 to 4 name character sets, not code pages.
+                Some(named) => {
+                    if let Some((code_at, code)) = code
+                        && code > 4
+                        && encoding::encoding_from_code_page(code) != named
+                    {
+                        report.warning(
+                            IntegrityCheck::Encoding,
+                            code_at,
+                            format!("character code {code} contradicts encoding {name:?}"),
+                        );
+                    }
+                }
+            }
+        }
+        (None, Some(_)) => {}
+    }
+}
+
+/// Check the .zsav zlib header, trailer and blocks. Returns false when the
+/// trailer cannot be found, so the data cannot be read.
+fn check_zsav<R: Read + Seek>(
+    sav: &mut SavReader<R>,
+    header: &FileHeader,
+    data_start: u64,
+    len: u64,
+    report: &mut IntegrityReport,
+) -> Result<bool> {
+    if data_start + 24 > len {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            data_start,
+            "file ends inside the zlib header",
+        );
+        return Ok(false);
+    }
+    sav.inner_mut().seek(SeekFrom::Start(data_start))?;
+    let zheader = zlib::read_zheader(sav)?;
+    if zheader.zheader_offset != data_start as i64 {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            data_start,
+            format!(
+                "zlib header offset is {} but the header is at {data_start}",
+                zheader.zheader_offset
+            ),
+        );
+    }
+    let trailer_at = zheader.ztrailer_offset;
+    let trailer_end = trailer_at.checked_add(zheader.ztrailer_length);
+    if trailer_at < data_start as i64 + 24
+        || zheader.ztrailer_length < 24
+        || trailer_end.is_none_or(|end| end > len as i64)
+    {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            data_start + 8,
+            format!(
+                "trailer at {trailer_at} ({} bytes) lies outside the data of a {len}-byte file",
+                zheader.ztrailer_length
+            ),
+        );
+        return Ok(false);
+    }
+    let trailer_at = trailer_at as u64;
+    if let Some(end) = trailer_end
+        && end < len as i64
+    {
+        report.warning(
+            IntegrityCheck::ZsavTrailer,
+            end as u64,
+            format!("{} bytes after the trailer", len as i64 - end),
+        );
+    }
+    sav.inner_mut().seek(SeekFrom::Start(trailer_at + 20))?;
+    let n_blocks = sav.read_i32()?;
+    if n_blocks < 0 || 24 + 24 * n_blocks as i64 != zheader.ztrailer_length {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            trailer_at + 20,
+            format!(
+                "trailer of {} bytes cannot hold {n_blocks} blocks",
+                zheader.ztrailer_length
+            ),
+        );
+        return Ok(false);
+    }
+    let trailer = zlib::read_ztrailer(sav, &zheader)?;
+    if trailer.bias != -(header.bias as i64) {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            trailer_at,
+            format!(
+                "trailer bias {} does not match the header's bias {}",
+                trailer.bias, header.bias
+            ),
+        );
+    }
+    if trailer.zero != 0 {
+        report.warning(
+            IntegrityCheck::ZsavTrailer,
+            trailer_at + 8,
+            format!("trailer zero field is {}", trailer.zero),
+        );
+    }
+    if trailer.block_size <= 0 {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            trailer_at + 16,
+            format!("invalid block size {}", trailer.block_size),
+        );
+    }
+
+    let mut uncompressed_offset = zheader.zheader_offset;
+    let mut compressed_offset = zheader.zheader_offset + 24;
+    for (i, entry) in trailer.entries.iter().enumerate() {
+        let entry_at = trailer_at + 24 + 24 * i as u64;
+        if entry.uncompressed_offset != uncompressed_offset
+            || entry.compressed_offset != compressed_offset
+        {
+            report.error(
+                IntegrityCheck::ZsavTrailer,
+                entry_at,
+                format!(
+                    "block {i} is at {} (uncompressed {}), not {compressed_offset} ({})",
+                    entry.compressed_offset, entry.uncompressed_offset, uncompressed_offset
+                ),
+            );
+        }
+        let last = i + 1 == trailer.entries.len();
+        if entry.uncompressed_size > trailer.block_size
+            || (!last && entry.uncompressed_size != trailer.block_size)
+        {
+            report.error(
+                IntegrityCheck::ZsavTrailer,
+                entry_at,
+                format!(
+                    "block {i} holds {} bytes but the block size is {}",
+                    entry.uncompressed_size, trailer.block_size
+                ),
+            );
+        }
+        uncompressed_offset = entry.uncompressed_offset + entry.uncompressed_size as i64;
+        compressed_offset = entry.compressed_offset + entry.compressed_size as i64;
+        if entry.compressed_offset < zheader.zheader_offset + 24
+            || entry.compressed_size < 0
+            || entry.uncompressed_size < 0
+            || compressed_offset > trailer_at as i64
+        {
+            report.error(
+                IntegrityCheck::ZsavTrailer,
+                entry_at,
+                format!("block {i} lies outside the compressed data"),
+            );
+            continue;
+        }
+
+        sav.inner_mut()
+            .seek(SeekFrom::Start(entry.compressed_offset as u64))?;
+        let compressed = sav.read_bytes(entry.compressed_size as usize)?;
+        let mut inflated = Vec::new();
+        let inflate = ZlibDecoder::new(&compressed[..])
+            .take(entry.uncompressed_size as u64 + 1)
+            .read_to_end(&mut inflated);
+        match inflate {
+            Err(e) => report.error(
+                IntegrityCheck::ZsavTrailer,
+                entry.compressed_offset as u64,
+                format!("block {i} does not inflate: {e}"),
+            ),
+            Ok(n) if n != entry.uncompressed_size as usize => report.error(
+                IntegrityCheck::ZsavTrailer,
+                entry.compressed_offset as u64,
+                format!(
+                    "block {i} inflates to {}{n} bytes, not {}",
+                    if n > entry.uncompressed_size as usize {
+                        "over "
+                    } else {
+                        ""
+                    },
+                    entry.uncompressed_size
+                ),
+            ),
+            Ok(_) => {}
+        }
+    }
+    if compressed_offset != trailer_at as i64 {
+        report.error(
+            IntegrityCheck::ZsavTrailer,
+            data_start + 8,
+            format!("blocks end at {compressed_offset} but the trailer starts at {trailer_at}"),
+        );
+    }
+    Ok(true)
+}
+
+/// Decode the data, reporting a case cut short and comparing the number of
+/// cases with the declared count.
+fn check_data<R: Read + Seek>(
+    reader: &mut R,
+    header: &FileHeader,
+    dict: &Walked,
+    data_start: u64,
+    report: &mut IntegrityReport,
+) {
+    let extended = dict.extended_ncases.and_then(|(at, n)| Some((at, n?)));
+    if let Some((at, n)) = extended
+        && header.ncases >= 0
+        && header.ncases != i32::MAX
+        && n != header.ncases as i64
+    {
+        report.error(
+            IntegrityCheck::Header,
+            at,
+            format!(
+                "subtype 16 declares {n} cases but the header {}",
+                header.ncases
+            ),
+        );
+    }
+    let (declared_at, declared) = match (header.ncases, extended) {
+        (-1 | i32::MAX, Some((at, n))) => (at, usize::try_from(n).ok()),
+        (n, _) => (FileHeader::NCASES_OFFSET, usize::try_from(n).ok()),
+    };
+    report.declared_cases = declared;
+
+    let options = ScanOptions::new().on_truncation(TruncationPolicy::Partial);
+    let scan = || -> Result<_> {
+        let mut scanner = SavScanner::open_with(reader, &options)?;
+        // One column is enough to count cases.
+        if let Some(first) = scanner.metadata().variable_names.first().cloned() {
+            scanner.select(&[&first])?;
+        }
+        let mut cases = 0;
+        while let Some(batch) = scanner.next_batch()? {
+            cases += batch.num_rows();
+        }
+        Ok((cases, scanner.truncation().cloned()))
+    };
+    let (cases, truncation) = match scan() {
+        Ok(scanned) => scanned,
+        Err(e) => {
+            report.error(IntegrityCheck::Data, data_start, e.to_string());
+            return;
+        }
+    };
+    report.cases = Some(cases);
+    if let Some(truncation) = truncation.filter(|t| t.mid_case) {
+        report.error(
+            IntegrityCheck::Data,
+            data_start,
+            format!("case {} is cut short: {}", cases + 1, truncation.message),
+        );
+    }
+    if let Some(declared) = declared
+        && declared != cases
+    {
+        report.error(
+            IntegrityCheck::CaseCount,
+            declared_at,
+            format!("{declared} cases declared but the data holds {cases}"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    fn checks(report: &IntegrityReport) -> Vec<(IntegrityCheck, u64)> {
+        report
+            .findings
+            .iter()
+            .map(|f| (f.check, f.offset))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_files() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let bytes = test_util::survey_sav((0..50).map(f64::from), compression, 256);
+            let report = validate_sav_reader(Cursor::new(bytes)).unwrap();
+            assert!(report.findings.is_empty(), "{compression:?}: {report}");
+            assert_eq!((report.declared_cases, report.cases), (Some(50), Some(50)));
+        }
+    }
+
+    #[test]
+    fn test_damaged_files() {
+        let mut bytes = test_util::survey_sav((0..50).map(f64::from), Compression::None, 256);
+        bytes.truncate(bytes.len() - 3);
+        bytes[68..72].copy_from_slice(&7_i32.to_le_bytes());
+        let report = validate_sav_reader(Cursor::new(bytes)).unwrap();
+        assert!(!report.is_valid());
+        let found = checks(&report);
+        assert!(found.contains(&(IntegrityCheck::SlotCount, 68)), "{report}");
+        assert!(
+            found
+                .iter()
+                .any(|(check, _)| *check == IntegrityCheck::CaseCount)
+        );
+
+        let mut bytes = test_util::survey_sav((0..50).map(f64::from), Compression::Bytecode, 256);
+        bytes.truncate(bytes.len() - 3);
+        let report = validate_sav_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(report.cases, Some(49));
+        let found: Vec<IntegrityCheck> = report.findings.iter().map(|f| f.check).collect();
+        assert_eq!(found, [IntegrityCheck::Data, IntegrityCheck::CaseCount]);
+        assert_eq!(report.findings[1].offset, FileHeader::NCASES_OFFSET);
+    }
+
+    #[test]
+    fn test_damaged_zsav_trailer() {
+        let mut bytes = test_util::survey_sav((0..50).map(f64::from), Compression::Zlib, 256);
+        let i64_at =
+            |bytes: &[u8], at: usize| i64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        // The zlib header starts with its own offset.
+        let zheader = (176..bytes.len())
+            .find(|&at| i64_at(&bytes, at) == at as i64)
+            .unwrap();
+        let trailer_at = i64_at(&bytes, zheader + 8) as usize;
+        // Grow the first block's compressed size by one byte.
+        let size_at = trailer_at + 24 + 20;
+        let size = i32::from_le_bytes(bytes[size_at..size_at + 4].try_into().unwrap());
+        bytes[size_at..size_at + 4].copy_from_slice(&(size + 1).to_le_bytes());
+
+        let report = validate_sav_reader(Cursor::new(bytes)).unwrap();
+        let found = checks(&report);
+        assert!(
+            found.contains(&(IntegrityCheck::ZsavTrailer, trailer_at as u64 + 48)),
+            "{report}"
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(report.cases, Some(50));
+    }
+}
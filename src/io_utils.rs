@@ -1,6 +1,11 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::error::{Result, SpssError};
+use crate::options::ReadLimits;
+
+/// Most items a Vec is given room for up front from a count read from a
+/// file; beyond that it grows as the items are actually read.
+pub const MAX_PREALLOC: usize = 4096;
 
 /// Endian-aware binary reader that wraps a `Read` source.
 ///
@@ -9,6 +14,9 @@ use crate::error::{Result, SpssError};
 pub struct SavReader<R: Read> {
     inner: R,
     bswap: bool,
+    /// Bytes read so far, for error messages.
+    offset: u64,
+    limits: ReadLimits,
 }
 
 impl<R: Read> SavReader<R> {
@@ -17,9 +25,32 @@ impl<R: Read> SavReader<R> {
         SavReader {
             inner,
             bswap: false,
+            offset: 0,
+            limits: ReadLimits::default(),
         }
     }
 
+    /// Bound the records read from here on.
+    pub fn set_limits(&mut self, limits: ReadLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &ReadLimits {
+        &self.limits
+    }
+
+    /// Position in the file: the bytes read through this reader, plus any
+    /// start set with `set_offset`. Reads and seeks made through
+    /// `inner_mut` are not counted.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Set the position `offset` reports, after seeking the inner reader.
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
     /// Enable or disable byte swapping.
     pub fn set_bswap(&mut self, bswap: bool) {
         self.bswap = bswap;
@@ -35,23 +66,55 @@ impl<R: Read> SavReader<R> {
         &mut self.inner
     }
 
-    /// Read exactly `n` bytes into a new Vec.
+    /// Read exactly `n` bytes into a new Vec. The Vec grows as the bytes
+    /// arrive, so a length field larger than the file costs no more memory
+    /// than the file; `n` beyond `max_record_bytes` fails outright.
     pub fn read_bytes(&mut self, n: usize) -> Result<Vec<u8>> {
-        let mut buf = vec![0u8; n];
-        self.inner.read_exact(&mut buf)?;
+        if n > self.limits.max_record_bytes {
+            return Err(SpssError::LimitExceeded(format!(
+                "a record of {n} bytes (the limit is {})",
+                self.limits.max_record_bytes
+            )));
+        }
+        let mut buf = Vec::with_capacity(n.min(64 * 1024));
+        (&mut self.inner).take(n as u64).read_to_end(&mut buf)?;
+        self.offset += buf.len() as u64;
+        if buf.len() < n {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
         Ok(buf)
     }
 
+    /// Read a count of items of `item_bytes` each (an i32), rejecting
+    /// negative counts and ones whose items would exceed
+    /// `max_record_bytes`.
+    pub fn read_count(&mut self, item_bytes: usize) -> Result<usize> {
+        let count = self.read_i32()?;
+        let n = usize::try_from(count).map_err(|_| SpssError::InvalidLength(count.into()))?;
+        match n.checked_mul(item_bytes) {
+            Some(bytes) if bytes <= self.limits.max_record_bytes => Ok(n),
+            _ => Err(SpssError::LimitExceeded(format!(
+                "{n} items of {item_bytes} bytes (the limit is {} bytes)",
+                self.limits.max_record_bytes
+            ))),
+        }
+    }
+
     /// Read exactly `n` bytes into an existing slice.
     pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.fill(buf)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> Result<()> {
         self.inner.read_exact(buf)?;
+        self.offset += buf.len() as u64;
         Ok(())
     }
 
     /// Read a 4-byte signed integer with endian handling.
     pub fn read_i32(&mut self) -> Result<i32> {
         let mut buf = [0u8; 4];
-        self.inner.read_exact(&mut buf)?;
+        self.fill(&mut buf)?;
         let val = if self.bswap {
             i32::from_be_bytes(buf)
         } else {
@@ -64,7 +127,7 @@ impl<R: Read> SavReader<R> {
     #[allow(dead_code)]
     pub fn read_u32(&mut self) -> Result<u32> {
         let mut buf = [0u8; 4];
-        self.inner.read_exact(&mut buf)?;
+        self.fill(&mut buf)?;
         let val = if self.bswap {
             u32::from_be_bytes(buf)
         } else {
@@ -76,7 +139,7 @@ impl<R: Read> SavReader<R> {
     /// Read an 8-byte signed integer with endian handling.
     pub fn read_i64(&mut self) -> Result<i64> {
         let mut buf = [0u8; 8];
-        self.inner.read_exact(&mut buf)?;
+        self.fill(&mut buf)?;
         let val = if self.bswap {
             i64::from_be_bytes(buf)
         } else {
@@ -88,7 +151,7 @@ impl<R: Read> SavReader<R> {
     /// Read an 8-byte float with endian handling.
     pub fn read_f64(&mut self) -> Result<f64> {
         let mut buf = [0u8; 8];
-        self.inner.read_exact(&mut buf)?;
+        self.fill(&mut buf)?;
         let val = if self.bswap {
             f64::from_be_bytes(buf)
         } else {
@@ -100,7 +163,7 @@ impl<R: Read> SavReader<R> {
     /// Read 8 raw bytes (no endian swap -- used for raw data slots).
     pub fn read_8_bytes(&mut self) -> Result<[u8; 8]> {
         let mut buf = [0u8; 8];
-        self.inner.read_exact(&mut buf)?;
+        self.fill(&mut buf)?;
         Ok(buf)
     }
 
@@ -117,7 +180,7 @@ impl<R: Read> SavReader<R> {
         let mut discard = [0u8; 4096];
         while remaining > 0 {
             let to_read = remaining.min(discard.len());
-            self.inner.read_exact(&mut discard[..to_read])?;
+            self.fill(&mut discard[..to_read])?;
             remaining -= to_read;
         }
         Ok(())
@@ -125,16 +188,17 @@ impl<R: Read> SavReader<R> {
 }
 
 /// Trim trailing spaces (0x20) and NUL bytes (0x00) from a byte slice.
-/// Uses reverse scan to find last non-padding byte.
+/// Uses reverse scan to find last non-padding byte (16 bytes at a time
+/// with the `simd` feature).
 pub fn trim_trailing_padding(buf: &[u8]) -> &[u8] {
-    // Scan backwards for last byte that isn't space or NUL.
-    // memchr doesn't help here (only finds specific bytes, not "not in set"),
-    // so we use a simple reverse loop which LLVM auto-vectorizes.
-    let mut end = buf.len();
-    while end > 0 && (buf[end - 1] == b' ' || buf[end - 1] == 0) {
-        end -= 1;
-    }
-    &buf[..end]
+    &buf[..crate::simd::trimmed_len(buf)]
+}
+
+/// Append `bytes` to `out`, truncated or padded with `pad` to exactly `len` bytes.
+pub fn write_padded(out: &mut Vec<u8>, bytes: &[u8], len: usize, pad: u8) {
+    let n = bytes.len().min(len);
+    out.extend_from_slice(&bytes[..n]);
+    out.resize(out.len() + (len - n), pad);
 }
 
 /// Round a length up to the next multiple of `alignment`.
@@ -158,7 +222,7 @@ pub fn bytes_to_string_lossy(bytes: &[u8]) -> String {
 /// Read a pascal-style string: 4-byte length prefix, then that many bytes.
 #[allow(dead_code)]
 pub fn read_pascal_string<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<u8>> {
-    let len = reader.read_i32()? as usize;
+    let len = reader.read_count(1)?;
     if len == 0 {
         return Ok(Vec::new());
     }
@@ -168,7 +232,7 @@ pub fn read_pascal_string<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<u8>>
 /// Read a pascal-style string and skip padding to align to 4 bytes.
 #[allow(dead_code)]
 pub fn read_pascal_string_aligned<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<u8>> {
-    let len = reader.read_i32()? as usize;
+    let len = reader.read_count(1)?;
     if len == 0 {
         return Ok(Vec::new());
     }
@@ -207,6 +271,14 @@ mod tests {
         assert_eq!(trim_trailing_padding(b""), b"");
     }
 
+    #[test]
+    fn test_write_padded() {
+        let mut out = Vec::new();
+        write_padded(&mut out, b"AGE", 8, b' ');
+        write_padded(&mut out, b"TOOLONGNAME", 8, b' ');
+        assert_eq!(&out[..], b"AGE     TOOLONGN");
+    }
+
     #[test]
     fn test_round_up() {
         assert_eq!(round_up(0, 4), 0);
@@ -237,6 +309,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_sav_reader_f64() {
         let data = 3.14_f64.to_le_bytes();
         let mut reader = SavReader::new(&data[..]);
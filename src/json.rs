@@ -0,0 +1,528 @@
+//! Canonical JSON representation of `SpssMetadata`.
+
+use indexmap::IndexMap;
+use serde_json::{Map, Value as Json, json};
+
+use crate::constants::{Alignment, Compression, Measure, Role};
+use crate::error::{Result, SpssError};
+use crate::metadata::{MissingSpec, MrSet, MrType, SpssMetadata, Value};
+
+/// Version written to `format_version`; bumped on incompatible changes.
+const FORMAT_VERSION: u64 = 1;
+
+impl SpssMetadata {
+    /// Serialize the metadata to JSON.
+    ///
+    /// The layout is stable across releases (`format_version` changes if it
+    /// ever has to break):
+    ///
+    /// ```text
+    /// {
+    ///   "format_version": 1,
+    ///   "file": {
+    ///     "label": str, "encoding": str, "format": "sav",
+    ///     "compression": "none" | "bytecode" | "zlib",
+    ///     "creation_time": str, "modification_time": str,
+    ///     "number_rows": int | null, "number_columns": int,
+    ///     "notes": [str], "weight_variable": str | null,
+    ///     "attributes": {name: [str]}
+    ///   },
+    ///   "variables": [{
+    ///     "name": str, "label": str | null, "format": str | null,
+    ///     "type": str | null, "storage_width": int | null,
+    ///     "display_width": int | null,
+    ///     "alignment": "left" | "right" | "center" | "unknown" | null,
+    ///     "measure": "nominal" | "ordinal" | "scale" | "unknown" | null,
+    ///     "role": "input" | "target" | "both" | "none" | "partition"
+    ///             | "split" | null,
+    ///     "missing": [{"value": num} | {"range": [num, num]} | {"string": str}],
+    ///     "value_labels": [{"value": num | str, "label": str}],
+    ///     "attributes": {name: [str]}
+    ///   }],
+    ///   "mr_sets": [{
+    ///     "name": str, "label": str, "type": "dichotomy" | "category",
+    ///     "counted_value": str | null, "variables": [str]
+    ///   }],
+    ///   "renamed_columns": {output name: file name},
+    ///   "renamed_variables": {name given: long name in the file},
+    ///   "unknown_formats": {name: packed format code}
+    /// }
+    /// ```
+    ///
+    /// Variables appear in file order; `type` is the Arrow-side type name
+    /// (`rust_variable_types`). LOWEST and HIGHEST range bounds are written
+    /// as the most negative and most positive finite doubles.
+    pub fn to_json(&self) -> String {
+        let variables: Vec<Json> = self
+            .variable_names
+            .iter()
+            .map(|name| {
+                let missing: Vec<Json> = self
+                    .variable_missing
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|spec| match spec {
+                        MissingSpec::Value(v) => json!({ "value": v }),
+                        MissingSpec::Range { lo, hi } => json!({ "range": [lo, hi] }),
+                        MissingSpec::StringValue(s) => json!({ "string": s }),
+                    })
+                    .collect();
+                let value_labels: Vec<Json> = self
+                    .variable_value_labels
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|(value, label)| json!({ "value": value_json(value), "label": label }))
+                    .collect();
+                let attributes = self.variable_attributes.get(name);
+                json!({
+                    "name": name,
+                    "label": self.variable_labels.get(name),
+                    "format": self.spss_variable_types.get(name),
+                    "type": self.rust_variable_types.get(name),
+                    "storage_width": self.variable_storage_width.get(name),
+                    "display_width": self.variable_display_width.get(name),
+                    "alignment": self.variable_alignment.get(name).map(|a| a.as_str()),
+                    "measure": self.variable_measure.get(name).map(|m| m.as_str()),
+                    "role": self.variable_role.get(name).map(|r| r.as_str()),
+                    "missing": missing,
+                    "value_labels": value_labels,
+                    "attributes": attributes.map_or(json!({}), attributes_json),
+                })
+            })
+            .collect();
+        let mr_sets: Vec<Json> = self
+            .mr_sets
+            .values()
+            .map(|set| {
+                json!({
+                    "name": set.name,
+                    "label": set.label,
+                    "type": match set.mr_type {
+                        MrType::MultipleDichotomy => "dichotomy",
+                        MrType::MultipleCategory => "category",
+                    },
+                    "counted_value": set.counted_value,
+                    "variables": set.variables,
+                })
+            })
+            .collect();
+        let doc = json!({
+            "format_version": FORMAT_VERSION,
+            "file": {
+                "label": self.file_label,
+                "encoding": self.file_encoding,
+                "format": self.file_format,
+                "compression": compression_name(self.compression),
+                "creation_time": self.creation_time,
+                "modification_time": self.modification_time,
+                "number_rows": self.number_rows,
+                "number_columns": self.number_columns,
+                "notes": self.notes,
+                "weight_variable": self.weight_variable,
+                "attributes": attributes_json(&self.file_attributes),
+            },
+            "variables": variables,
+            "mr_sets": mr_sets,
+            "renamed_columns": self
+                .renamed_columns
+                .iter()
+                .map(|(output, original)| (output.clone(), json!(original)))
+                .collect::<Map<_, _>>(),
+            "renamed_variables": self
+                .renamed_variables
+                .iter()
+                .map(|(given, original)| (given.clone(), json!(original)))
+                .collect::<Map<_, _>>(),
+            "unknown_formats": self
+                .unknown_formats
+                .iter()
+                .map(|(name, code)| (name.clone(), json!(code)))
+                .collect::<Map<_, _>>(),
+        });
+        serde_json::to_string_pretty(&doc).expect("JSON values always serialize")
+    }
+
+    /// Parse metadata written by `to_json`.
+    ///
+    /// Optional entries may be left out or null. Documents with a newer
+    /// `format_version` than this release understands are rejected.
+    pub fn from_json(text: &str) -> Result<SpssMetadata> {
+        let doc: Json = serde_json::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let doc = object(&doc, "document")?;
+        let version = doc
+            .get("format_version")
+            .and_then(Json::as_u64)
+            .ok_or_else(|| invalid("missing format_version"))?;
+        if version > FORMAT_VERSION {
+            return Err(invalid(format!("unsupported format_version {version}")));
+        }
+
+        let mut meta = SpssMetadata::default();
+        if let Some(file) = doc.get("file") {
+            let file = object(file, "file")?;
+            if let Some(s) = opt_str(file, "label")? {
+                meta.file_label = s;
+            }
+            if let Some(s) = opt_str(file, "encoding")? {
+                meta.file_encoding = s;
+            }
+            if let Some(s) = opt_str(file, "format")? {
+                meta.file_format = s;
+            }
+            if let Some(s) = opt_str(file, "compression")? {
+                meta.compression = match s.as_str() {
+                    "none" => Compression::None,
+                    "bytecode" => Compression::Bytecode,
+                    "zlib" => Compression::Zlib,
+                    _ => return Err(invalid(format!("unknown compression {s:?}"))),
+                };
+            }
+            if let Some(s) = opt_str(file, "creation_time")? {
+                meta.creation_time = s;
+            }
+            if let Some(s) = opt_str(file, "modification_time")? {
+                meta.modification_time = s;
+            }
+            meta.number_rows = file.get("number_rows").and_then(Json::as_i64);
+            meta.notes = strings(file.get("notes"), "notes")?;
+            meta.weight_variable = opt_str(file, "weight_variable")?;
+            meta.file_attributes = attributes(file.get("attributes"))?;
+        }
+
+        for var in array(doc.get("variables"), "variables")? {
+            let var = object(var, "variable")?;
+            let name = opt_str(var, "name")?.ok_or_else(|| invalid("variable without a name"))?;
+            if let Some(s) = opt_str(var, "label")? {
+                meta.variable_labels.insert(name.clone(), s);
+            }
+            if let Some(s) = opt_str(var, "format")? {
+                meta.spss_variable_types.insert(name.clone(), s);
+            }
+            if let Some(s) = opt_str(var, "type")? {
+                meta.rust_variable_types.insert(name.clone(), s);
+            }
+            if let Some(n) = var.get("storage_width").and_then(Json::as_u64) {
+                meta.variable_storage_width.insert(name.clone(), n as usize);
+            }
+            if let Some(n) = var.get("display_width").and_then(Json::as_u64) {
+                meta.variable_display_width.insert(name.clone(), n as u32);
+            }
+            if let Some(s) = opt_str(var, "alignment")? {
+                meta.variable_alignment.insert(name.clone(), alignment(&s)?);
+            }
+            if let Some(s) = opt_str(var, "measure")? {
+                meta.variable_measure.insert(name.clone(), measure(&s)?);
+            }
+            if let Some(s) = opt_str(var, "role")? {
+                meta.variable_role.insert(name.clone(), role(&s)?);
+            }
+            let specs = array(var.get("missing"), "missing")?
+                .iter()
+                .map(missing_spec)
+                .collect::<Result<Vec<_>>>()?;
+            if !specs.is_empty() {
+                meta.variable_missing.insert(name.clone(), specs);
+            }
+            let mut labels = IndexMap::new();
+            for entry in array(var.get("value_labels"), "value_labels")? {
+                let entry = object(entry, "value label")?;
+                let value = match entry.get("value") {
+                    Some(Json::String(s)) => Value::String(s.clone()),
+                    Some(v) => Value::Numeric(
+                        v.as_f64()
+                            .ok_or_else(|| invalid("value label without a value"))?,
+                    ),
+                    None => return Err(invalid("value label without a value")),
+                };
+                let label = opt_str(entry, "label")?.unwrap_or_default();
+                labels.insert(value, label);
+            }
+            if !labels.is_empty() {
+                meta.variable_value_labels.insert(name.clone(), labels);
+            }
+            let attrs = attributes(var.get("attributes"))?;
+            if !attrs.is_empty() {
+                meta.variable_attributes.insert(name.clone(), attrs);
+            }
+            meta.variable_names.push(name);
+        }
+        meta.number_columns = doc
+            .get("file")
+            .and_then(|f| f.get("number_columns"))
+            .and_then(Json::as_u64)
+            .map_or(meta.variable_names.len(), |n| n as usize);
+
+        for set in array(doc.get("mr_sets"), "mr_sets")? {
+            let set = object(set, "MR set")?;
+            let name = opt_str(set, "name")?.ok_or_else(|| invalid("MR set without a name"))?;
+            let mr_type = match opt_str(set, "type")?.as_deref() {
+                Some("dichotomy") => MrType::MultipleDichotomy,
+                Some("category") => MrType::MultipleCategory,
+                other => return Err(invalid(format!("unknown MR set type {other:?}"))),
+            };
+            let mr_set = MrSet {
+                name: name.clone(),
+                label: opt_str(set, "label")?.unwrap_or_default(),
+                mr_type,
+                counted_value: opt_str(set, "counted_value")?,
+                variables: strings(set.get("variables"), "variables")?,
+            };
+            meta.mr_sets.insert(name, mr_set);
+        }
+
+        if let Some(renamed) = doc.get("renamed_columns") {
+            for (output, original) in object(renamed, "renamed_columns")? {
+                let original = original
+                    .as_str()
+                    .ok_or_else(|| invalid("renamed_columns values must be strings"))?;
+                meta.renamed_columns
+                    .insert(output.clone(), original.to_string());
+            }
+        }
+        if let Some(renamed) = doc.get("renamed_variables") {
+            for (given, original) in object(renamed, "renamed_variables")? {
+                let original = original
+                    .as_str()
+                    .ok_or_else(|| invalid("renamed_variables values must be strings"))?;
+                meta.renamed_variables
+                    .insert(given.clone(), original.to_string());
+            }
+        }
+        if let Some(unknown) = doc.get("unknown_formats") {
+            for (name, code) in object(unknown, "unknown_formats")? {
+                let code = code
+                    .as_i64()
+                    .and_then(|c| i32::try_from(c).ok())
+                    .ok_or_else(|| invalid("unknown_formats values must be 32-bit integers"))?;
+                meta.unknown_formats.insert(name.clone(), code);
+            }
+        }
+        Ok(meta)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> SpssError {
+    SpssError::MetadataJson(message.into())
+}
+
+fn value_json(value: &Value) -> Json {
+    match value {
+        Value::Numeric(v) => json!(v),
+        Value::String(s) => json!(s),
+    }
+}
+
+fn attributes_json(attributes: &IndexMap<String, Vec<String>>) -> Json {
+    attributes
+        .iter()
+        .map(|(name, values)| (name.clone(), json!(values)))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "none",
+        Compression::Bytecode => "bytecode",
+        Compression::Zlib => "zlib",
+    }
+}
+
+fn alignment(s: &str) -> Result<Alignment> {
+    Ok(match s {
+        "left" => Alignment::Left,
+        "right" => Alignment::Right,
+        "center" => Alignment::Center,
+        "unknown" => Alignment::Unknown,
+        _ => return Err(invalid(format!("unknown alignment {s:?}"))),
+    })
+}
+
+fn measure(s: &str) -> Result<Measure> {
+    Ok(match s {
+        "nominal" => Measure::Nominal,
+        "ordinal" => Measure::Ordinal,
+        "scale" => Measure::Scale,
+        "unknown" => Measure::Unknown,
+        _ => return Err(invalid(format!("unknown measure {s:?}"))),
+    })
+}
+
+fn role(s: &str) -> Result<Role> {
+    Ok(match s {
+        "input" => Role::Input,
+        "target" => Role::Target,
+        "both" => Role::Both,
+        "none" => Role::None,
+        "partition" => Role::Partition,
+        "split" => Role::Split,
+        _ => return Err(invalid(format!("unknown role {s:?}"))),
+    })
+}
+
+fn missing_spec(spec: &Json) -> Result<MissingSpec> {
+    let spec = object(spec, "missing value")?;
+    if let Some(v) = spec.get("value") {
+        let v = v
+            .as_f64()
+            .ok_or_else(|| invalid("missing value must be a number"))?;
+        return Ok(MissingSpec::Value(v));
+    }
+    if let Some(range) = spec.get("range") {
+        return match range.as_array().map(Vec::as_slice) {
+            Some([lo, hi]) => match (lo.as_f64(), hi.as_f64()) {
+                (Some(lo), Some(hi)) => Ok(MissingSpec::Range { lo, hi }),
+                _ => Err(invalid("missing range bounds must be numbers")),
+            },
+            _ => Err(invalid("missing range must be [low, high]")),
+        };
+    }
+    match spec.get("string").and_then(Json::as_str) {
+        Some(s) => Ok(MissingSpec::StringValue(s.to_string())),
+        None => Err(invalid("missing value needs value, range or string")),
+    }
+}
+
+fn object<'a>(json: &'a Json, what: &str) -> Result<&'a Map<String, Json>> {
+    json.as_object()
+        .ok_or_else(|| invalid(format!("{what} must be an object")))
+}
+
+/// An optional array; absent or null is empty.
+fn array<'a>(json: Option<&'a Json>, what: &str) -> Result<&'a [Json]> {
+    match json {
+        None | Some(Json::Null) => Ok(&[]),
+        Some(Json::Array(items)) => Ok(items),
+        Some(_) => Err(invalid(format!("{what} must be an array"))),
+    }
+}
+
+fn opt_str(object: &Map<String, Json>, key: &str) -> Result<Option<String>> {
+    match object.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(invalid(format!("{key} must be a string"))),
+    }
+}
+
+fn strings(json: Option<&Json>, what: &str) -> Result<Vec<String>> {
+    array(json, what)?
+        .iter()
+        .map(|s| {
+            s.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(format!("{what} must hold strings")))
+        })
+        .collect()
+}
+
+fn attributes(json: Option<&Json>) -> Result<IndexMap<String, Vec<String>>> {
+    match json {
+        None | Some(Json::Null) => Ok(IndexMap::new()),
+        Some(json) => object(json, "attributes")?
+            .iter()
+            .map(|(name, values)| Ok((name.clone(), strings(Some(values), "attributes")?)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut meta = SpssMetadata {
+            file_label: "Wave 1".into(),
+            compression: Compression::Zlib,
+            number_rows: Some(2),
+            number_columns: 2,
+            variable_names: vec!["q1".into(), "city".into()],
+            weight_variable: Some("q1".into()),
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        meta.spss_variable_types.insert("city".into(), "A8".into());
+        meta.variable_measure.insert("q1".into(), Measure::Nominal);
+        meta.variable_alignment
+            .insert("city".into(), Alignment::Left);
+        meta.variable_missing.insert(
+            "q1".into(),
+            vec![
+                MissingSpec::Range {
+                    lo: -f64::MAX,
+                    hi: 0.0,
+                },
+                MissingSpec::Value(9.0),
+            ],
+        );
+        meta.variable_missing
+            .insert("city".into(), vec![MissingSpec::StringValue("NA".into())]);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.5), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta.variable_value_labels.insert(
+            "city".into(),
+            [(Value::String("OSL".into()), "Oslo".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta.variable_role.insert("q1".into(), Role::Partition);
+        meta.variable_attributes.insert(
+            "q1".into(),
+            [("Source".to_string(), vec!["wave 1".to_string()])]
+                .into_iter()
+                .collect(),
+        );
+        meta.mr_sets.insert(
+            "$cars".into(),
+            MrSet {
+                name: "$cars".into(),
+                label: "Cars".into(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".into()),
+                variables: vec!["q1".into()],
+            },
+        );
+
+        let json = meta.to_json();
+        let back = SpssMetadata::from_json(&json).unwrap();
+        assert_eq!(back.to_json(), json);
+        assert_eq!(back.file_label, "Wave 1");
+        assert_eq!(back.compression, Compression::Zlib);
+        assert_eq!(back.variable_names, meta.variable_names);
+        assert_eq!(back.measure("q1"), Some(Measure::Nominal));
+        assert_eq!(back.role("q1"), Some(Role::Partition));
+        assert!(matches!(
+            back.variable_missing["q1"][..],
+            [MissingSpec::Range { lo, hi: 0.0 }, MissingSpec::Value(9.0)] if lo == -f64::MAX
+        ));
+        assert_eq!(
+            back.value_labels("city").unwrap()[&Value::from("OSL")],
+            "Oslo"
+        );
+        assert_eq!(back.mr_sets["$cars"].variables, ["q1"]);
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        assert!(SpssMetadata::from_json("{").is_err());
+        assert!(SpssMetadata::from_json("{}").is_err());
+        assert!(SpssMetadata::from_json(r#"{"format_version": 99}"#).is_err());
+        let bad = r#"{"format_version": 1, "variables": [{"name": "x", "measure": "huge"}]}"#;
+        let err = SpssMetadata::from_json(bad).unwrap_err();
+        assert!(err.to_string().contains("huge"), "{err}");
+
+        let minimal = r#"{"format_version": 1, "variables": [{"name": "x"}]}"#;
+        let meta = SpssMetadata::from_json(minimal).unwrap();
+        assert_eq!(meta.variable_names, ["x"]);
+        assert_eq!(meta.number_columns, 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Mapping already-read columns through their value labels.
+//!
+//! `ScanOptions::labels` applies labels while scanning. [`decode_labels`]
+//! does the same for a column read as codes, e.g. to show labels for a few
+//! columns of a batch that is otherwise processed numerically.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, DictionaryArray, Float64Array, Int32Array, StringArray, StringViewArray,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Int32Type};
+
+use crate::columnar::{codes_to_dictionary, null_user_missing};
+use crate::error::{Result, SpssError};
+use crate::metadata::{SpssMetadata, Value};
+use crate::options::{MissingPolicy, Unlabelled};
+
+/// Map a column of variable `name` through its value labels.
+///
+/// Returns a `Dictionary(Int32, Utf8)` array laid out as
+/// `LabelMode::Dictionary` produces it: the variable's labels in definition
+/// order, then the formatted value of any unlabelled value found (or null
+/// for those with `Unlabelled::Null`). Cast it to `Utf8` for plain labels.
+///
+/// Numeric columns of any numeric type and string columns of any string
+/// type are accepted. With `MissingPolicy::Null`, user-missing values are
+/// null even when labelled. `name` may also be a column renamed by
+/// `ScanOptions::sanitize_names`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::{MissingPolicy, Unlabelled};
+///
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let column = batch.column_by_name("Q1").unwrap();
+/// let labels =
+///     ambers::decode_labels(column, "Q1", &meta, MissingPolicy::Keep, Unlabelled::Keep).unwrap();
+/// ```
+pub fn decode_labels(
+    column: &ArrayRef,
+    name: &str,
+    meta: &SpssMetadata,
+    missing: MissingPolicy,
+    unlabelled: Unlabelled,
+) -> Result<ArrayRef> {
+    let name = meta
+        .renamed_columns
+        .get(name)
+        .map(String::as_str)
+        .unwrap_or(name);
+    if !meta.variable_names.iter().any(|n| n == name) {
+        return Err(SpssError::InvalidVariable(format!("{name}: not in metadata")));
+    }
+    let labels = meta.variable_value_labels.get(name);
+    let specs = match missing {
+        MissingPolicy::Keep => None,
+        MissingPolicy::Null => meta.variable_missing.get(name),
+    };
+    let keep_unlabelled = unlabelled == Unlabelled::Keep;
+
+    match column.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+            let mut strings = cast(column, &DataType::Utf8View)?;
+            if let Some(specs) = specs {
+                strings = null_user_missing(&strings, specs)?;
+            }
+            let strings = strings
+                .as_any()
+                .downcast_ref::<StringViewArray>()
+                .expect("cast to Utf8View");
+            let labels: Vec<(&str, &str)> = labels
+                .into_iter()
+                .flatten()
+                .filter_map(|(value, label)| match value {
+                    Value::String(s) => Some((s.as_str(), label.as_str())),
+                    Value::Numeric(_) => None,
+                })
+                .collect();
+            strings_to_dictionary(strings, &labels, keep_unlabelled)
+        }
+        data_type if data_type.is_numeric() => {
+            let mut codes = cast(column, &DataType::Float64)?;
+            if let Some(specs) = specs {
+                codes = null_user_missing(&codes, specs)?;
+            }
+            let codes = codes
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .expect("cast to Float64");
+            let labels: Vec<(f64, String)> = labels
+                .into_iter()
+                .flatten()
+                .filter_map(|(value, label)| match value {
+                    Value::Numeric(code) => Some((*code, label.clone())),
+                    Value::String(_) => None,
+                })
+                .collect();
+            codes_to_dictionary(codes, &labels, keep_unlabelled)
+        }
+        other => Err(SpssError::InvalidVariable(format!(
+            "{name}: cannot decode labels of a {other} column"
+        ))),
+    }
+}
+
+/// `codes_to_dictionary` for string values, matched with trailing blanks
+/// removed.
+fn strings_to_dictionary(
+    strings: &StringViewArray,
+    labels: &[(&str, &str)],
+    keep_unlabelled: bool,
+) -> Result<ArrayRef> {
+    let mut lookup: HashMap<&str, i32> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, (value, _))| (value.trim_end_matches(' '), i as i32))
+        .collect();
+    let mut values: Vec<&str> = labels.iter().map(|(_, label)| *label).collect();
+    let keys: Int32Array = strings
+        .iter()
+        .map(|v| {
+            let v = v?.trim_end_matches(' ');
+            if let Some(&key) = lookup.get(v) {
+                return Some(key);
+            }
+            if !keep_unlabelled {
+                return None;
+            }
+            values.push(v);
+            let key = values.len() as i32 - 1;
+            lookup.insert(v, key);
+            Some(key)
+        })
+        .collect();
+    let values: ArrayRef = Arc::new(StringArray::from(values));
+    Ok(Arc::new(DictionaryArray::<Int32Type>::try_new(keys, values)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+
+    use super::*;
+    use crate::metadata::MissingSpec;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes".to_string()),
+                (Value::Numeric(2.0), "No".to_string()),
+                (Value::Numeric(9.0), "Refused".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+        meta.variable_value_labels.insert(
+            "city".into(),
+            [(Value::String("NY".into()), "New York".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta
+    }
+
+    fn labels(array: &ArrayRef) -> Vec<Option<String>> {
+        let plain = cast(array, &DataType::Utf8).unwrap();
+        plain
+            .as_string::<i32>()
+            .iter()
+            .map(|v| v.map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_numeric_labels() {
+        let meta = metadata();
+        let column: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), Some(3), Some(9), None]));
+        let out =
+            decode_labels(&column, "q1", &meta, MissingPolicy::Keep, Unlabelled::Keep).unwrap();
+        assert_eq!(
+            labels(&out),
+            [
+                Some("Yes".into()),
+                Some("3".into()),
+                Some("Refused".into()),
+                None
+            ]
+        );
+        let out =
+            decode_labels(&column, "q1", &meta, MissingPolicy::Null, Unlabelled::Null).unwrap();
+        assert_eq!(labels(&out), [Some("Yes".into()), None, None, None]);
+        assert!(
+            decode_labels(&column, "q9", &meta, MissingPolicy::Keep, Unlabelled::Keep).is_err()
+        );
+    }
+
+    #[test]
+    fn test_decode_string_labels() {
+        let meta = metadata();
+        let column: ArrayRef = Arc::new(StringArray::from(vec!["NY  ", "LA"]));
+        let out = decode_labels(
+            &column,
+            "city",
+            &meta,
+            MissingPolicy::Keep,
+            Unlabelled::Keep,
+        )
+        .unwrap();
+        assert_eq!(labels(&out), [Some("New York".into()), Some("LA".into())]);
+    }
+}
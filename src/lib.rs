@@ -20,37 +20,106 @@
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 pub(crate) mod arrow_convert;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "tokio")]
+pub mod async_scanner;
+pub mod codebook;
 pub(crate) mod columnar;
 pub(crate) mod compression;
 pub mod constants;
+pub mod convert;
 pub(crate) mod dictionary;
+pub(crate) mod display;
 pub(crate) mod document;
+pub(crate) mod encrypted;
+pub mod dta;
 pub(crate) mod encoding;
 pub mod error;
-pub(crate) mod header;
+pub mod filter;
+pub mod fingerprint;
+pub(crate) mod float_format;
+pub mod header;
 pub(crate) mod info_records;
+pub mod integrity;
 pub(crate) mod io_utils;
+mod json;
+pub mod labels;
+pub mod merge;
 pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multi;
+pub mod options;
+pub mod overlay;
+pub mod product;
+pub mod recovery;
+#[cfg(feature = "object_store")]
+pub mod remote;
+mod report;
+pub mod row_index;
 pub mod scanner;
+pub(crate) mod simd;
+pub mod sss;
+pub mod stats;
+pub mod syntax;
+#[cfg(test)]
+mod test_util;
 pub(crate) mod value_labels;
+pub mod validate;
 pub(crate) mod variable;
+pub mod warning;
+pub(crate) mod writer;
+pub mod xpt;
 
 #[cfg(feature = "python")]
 mod python;
 
 use std::fs::File;
-use std::io::{BufReader, Read, Seek};
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
 use std::path::Path;
 
 use arrow::record_batch::RecordBatch;
 
+use crate::dta::DtaScanner;
 use crate::error::Result;
 use crate::scanner::SavScanner;
+use crate::xpt::XptScanner;
 
 // Re-export key public types
-pub use crate::constants::{Alignment, Measure};
-pub use crate::metadata::{MissingSpec, MrSet, MrType, SpssMetadata, Value};
-pub use crate::scanner::SavScanner as Scanner;
+#[cfg(feature = "tokio")]
+pub use crate::async_scanner::AsyncSavScanner;
+#[cfg(feature = "xlsx")]
+pub use crate::codebook::{to_codebook_xlsx, write_codebook};
+pub use crate::constants::{Alignment, Compression, Measure, Role};
+pub use crate::filter::{CompareOp, Predicate, RowView};
+pub use crate::fingerprint::{fingerprint, fingerprint_reader};
+pub use crate::header::FileHeader;
+pub use crate::integrity::{validate_sav, validate_sav_reader};
+pub use crate::labels::decode_labels;
+pub use crate::merge::{ConflictKind, MergeConflict, MergeStrategy, MergedMetadata};
+pub use crate::multi::MultiScanner;
+pub use crate::metadata::{
+    ConflictPolicy, MetadataOverlay, MissingSpec, MrSet, MrType, SpssMetadata, Value,
+};
+pub use crate::options::{
+    BadRowPolicy, Float32Mode, LabelMode, MissingPolicy, ReadLimits, ScanOptions, StringType,
+    TemporalMode, TruncationPolicy, Unlabelled,
+};
+#[cfg(feature = "object_store")]
+pub use crate::remote::{scan_sav_object, scan_sav_url};
+pub use crate::product::{Product, Quirk, WriterProfile};
+pub use crate::overlay::{
+    parse_overlay_csv, parse_overlay_json, read_overlay_csv, read_overlay_json,
+};
+pub use crate::sss::{to_sss_xml, write_sss};
+pub use crate::syntax::{parse_sps, read_sps};
+pub use crate::recovery::{Recovered, recover_sav, recover_sav_from_reader};
+pub use crate::row_index::RowIndex;
+pub use crate::scanner::{SavScanner as Scanner, Truncation};
+pub use crate::validate::{Check, Finding};
+pub use crate::warning::{SpssWarning, WarningKind};
+pub use crate::writer::{SavWriter, WriteOptions};
 
 /// Read an SPSS .sav or .zsav file, returning all data as an Arrow RecordBatch
 /// plus the file's metadata.
@@ -59,16 +128,71 @@ pub use crate::scanner::SavScanner as Scanner;
 /// column projection, use `scan_sav()` instead.
 pub fn read_sav(path: impl AsRef<Path>) -> Result<(RecordBatch, SpssMetadata)> {
     let mut scanner = scan_sav(path)?;
+    let batch = scanner.collect_single()?;
     let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Read a whole SPSS file configured by `ScanOptions` (e.g. the Arrow
+/// string type, projection, or row range).
+///
+/// # Example
+/// ```no_run
+/// use ambers::{ScanOptions, StringType};
+///
+/// let opts = ScanOptions::new().string_type(StringType::LargeUtf8);
+/// let (batch, meta) = ambers::read_sav_with("survey.sav", &opts).unwrap();
+/// ```
+pub fn read_sav_with(
+    path: impl AsRef<Path>,
+    options: &ScanOptions,
+) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = scan_sav_with(path, options)?;
     let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
     Ok((batch, metadata))
 }
 
 /// Read an SPSS file from any reader that supports Read + Seek.
 pub fn read_sav_from_reader<R: Read + Seek>(reader: R) -> Result<(RecordBatch, SpssMetadata)> {
     let mut scanner = scan_sav_from_reader(reader, usize::MAX)?;
+    let batch = scanner.collect_single()?;
     let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Read an SPSS file from a reader that cannot seek (stdin, an HTTP body,
+/// a tar entry, ...).
+///
+/// .zsav blocks are inflated in file order without reading the block
+/// trailer, so the source is consumed strictly front to back.
+pub fn read_sav_from_stream<R: Read>(reader: R) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = scan_sav_stream(reader, usize::MAX)?;
     let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Read an SPSS file held in memory that may come from an untrusted
+/// source, such as a user upload or a fuzzer.
+///
+/// The dictionary must stay within `ReadLimits::untrusted()`; a file that
+/// asks for more fails with `SpssError::LimitExceeded` before anything
+/// that large is allocated. Malformed files fail with an error rather than
+/// a panic.
+pub fn read_sav_from_bytes(bytes: &[u8]) -> Result<(RecordBatch, SpssMetadata)> {
+    read_sav_from_bytes_with(bytes, &ReadLimits::untrusted())
+}
+
+/// Read an SPSS file held in memory within the given limits (see
+/// [`read_sav_from_bytes`]).
+pub fn read_sav_from_bytes_with(
+    bytes: &[u8],
+    limits: &ReadLimits,
+) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = SavScanner::open_with_limits(Cursor::new(bytes), usize::MAX, limits)?;
+    let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
     Ok((batch, metadata))
 }
 
@@ -83,6 +207,21 @@ pub fn read_sav_metadata(path: impl AsRef<Path>) -> Result<SpssMetadata> {
     Ok(scanner.metadata().clone())
 }
 
+/// Read only the fixed file header of an SPSS file: product, compression,
+/// bias, case count and byte order.
+///
+/// Reads the first 176 bytes, without parsing the dictionary.
+///
+/// # Example
+/// ```no_run
+/// let header = ambers::read_sav_header("survey.sav").unwrap();
+/// println!("{} ({:?})", header.product, header.compression);
+/// ```
+pub fn read_sav_header(path: impl AsRef<Path>) -> Result<FileHeader> {
+    let file = File::open(path)?;
+    FileHeader::parse(&mut io_utils::SavReader::new(BufReader::new(file)))
+}
+
 /// Create a streaming scanner for an SPSS .sav or .zsav file.
 ///
 /// Reads metadata immediately. Data is read on demand via `next_batch()`
@@ -101,9 +240,46 @@ pub fn read_sav_metadata(path: impl AsRef<Path>) -> Result<SpssMetadata> {
 /// }
 /// ```
 pub fn scan_sav(path: impl AsRef<Path>) -> Result<SavScanner<BufReader<File>>> {
-    let file = File::open(path)?;
+    let file = File::open(path.as_ref())?;
     let buf_reader = BufReader::with_capacity(64 * 1024 * 1024, file);
-    SavScanner::open(buf_reader, 100_000)
+    let mut scanner = SavScanner::open(buf_reader, 100_000)?;
+    scanner.set_source_path(path.as_ref().display().to_string());
+    Ok(scanner)
+}
+
+/// Create a streaming scanner configured by `ScanOptions`.
+///
+/// See `ScanOptions` for the available settings.
+pub fn scan_sav_with(
+    path: impl AsRef<Path>,
+    options: &ScanOptions,
+) -> Result<SavScanner<BufReader<File>>> {
+    let file = File::open(path.as_ref())?;
+    let buf_reader = BufReader::with_capacity(64 * 1024 * 1024, file);
+    let mut scanner = SavScanner::open_with(buf_reader, options)?;
+    scanner.set_source_path(path.as_ref().display().to_string());
+    Ok(scanner)
+}
+
+/// Create a streaming scanner over several .sav/.zsav files, read one after
+/// another with a unified schema (see `MultiScanner`).
+///
+/// Columns missing from some files are null there; a column whose type
+/// differs between files is an error. Default batch size: 100,000 rows.
+pub fn scan_sav_many<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<MultiScanner> {
+    MultiScanner::open(paths, 100_000)
+}
+
+/// Create a streaming scanner over a memory-mapped .sav or .zsav file.
+///
+/// Same as `scan_sav()`, but data is read from the mapping rather than
+/// through buffered reads; compressed data is decoded in place without
+/// first being copied into memory.
+#[cfg(feature = "mmap")]
+pub fn scan_sav_mmap(
+    path: impl AsRef<Path>,
+) -> Result<SavScanner<Cursor<crate::mmap::MmapSource>>> {
+    SavScanner::open_mmap(path, 100_000)
 }
 
 /// Create a streaming scanner from any Read+Seek source.
@@ -113,3 +289,266 @@ pub fn scan_sav_from_reader<R: Read + Seek>(
 ) -> Result<SavScanner<R>> {
     SavScanner::open(reader, batch_size)
 }
+
+/// Create a streaming scanner from a non-seekable source.
+///
+/// # Example
+/// ```no_run
+/// let stdin = std::io::stdin().lock();
+/// let mut scanner = ambers::scan_sav_stream(stdin, 100_000).unwrap();
+/// while let Some(batch) = scanner.next_batch().unwrap() {
+///     println!("Batch: {} rows", batch.num_rows());
+/// }
+/// ```
+pub fn scan_sav_stream<R: Read>(
+    reader: R,
+    batch_size: usize,
+) -> Result<SavScanner<scanner::StreamReader<R>>> {
+    SavScanner::open_stream(reader, batch_size)
+}
+
+/// Read a password-protected SPSS .sav file (SPSS 21+ "encrypted" save).
+///
+/// The file is decrypted into memory and then parsed like any other .sav
+/// or .zsav file. A wrong password yields `SpssError::Decryption`.
+pub fn read_sav_encrypted(
+    path: impl AsRef<Path>,
+    password: &str,
+) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = scan_sav_encrypted(path, password)?;
+    let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Create a streaming scanner for a password-protected SPSS .sav file.
+///
+/// The decrypted stream is held in memory; batches are produced from it
+/// exactly as with `scan_sav()`.
+pub fn scan_sav_encrypted(
+    path: impl AsRef<Path>,
+    password: &str,
+) -> Result<SavScanner<Cursor<Vec<u8>>>> {
+    let file = File::open(path)?;
+    let plain = encrypted::decrypt_sav(BufReader::new(file), password)?;
+    SavScanner::open(plain, 100_000)
+}
+
+/// Read a Stata .dta file (formats 113–119), returning all data as an Arrow
+/// RecordBatch plus its metadata.
+///
+/// Metadata uses the same `SpssMetadata` struct as .sav files, with Stata
+/// display formats mapped to their closest SPSS formats.
+pub fn read_dta(path: impl AsRef<Path>) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = scan_dta(path)?;
+    let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Read only the metadata from a Stata .dta file (no data).
+pub fn read_dta_metadata(path: impl AsRef<Path>) -> Result<SpssMetadata> {
+    let scanner = scan_dta(path)?;
+    Ok(scanner.metadata().clone())
+}
+
+/// Create a streaming scanner for a Stata .dta file.
+///
+/// Default batch size: 100,000 rows.
+pub fn scan_dta(path: impl AsRef<Path>) -> Result<DtaScanner<BufReader<File>>> {
+    let file = File::open(path)?;
+    let buf_reader = BufReader::with_capacity(8 * 1024 * 1024, file);
+    DtaScanner::open(buf_reader, 100_000)
+}
+
+/// Create a streaming scanner for Stata data from any Read+Seek source.
+pub fn scan_dta_from_reader<R: Read + Seek>(reader: R, batch_size: usize) -> Result<DtaScanner<R>> {
+    DtaScanner::open(reader, batch_size)
+}
+
+/// Read a SAS transport (.xpt) file, returning all data as an Arrow
+/// RecordBatch plus its metadata.
+///
+/// Both XPORT version 5 and version 8 files are supported. Only the first
+/// member of a multi-member library is read.
+pub fn read_xpt(path: impl AsRef<Path>) -> Result<(RecordBatch, SpssMetadata)> {
+    let mut scanner = scan_xpt(path)?;
+    let batch = scanner.collect_single()?;
+    let metadata = scanner.metadata().clone();
+    Ok((batch, metadata))
+}
+
+/// Read only the metadata from a SAS transport (.xpt) file.
+pub fn read_xpt_metadata(path: impl AsRef<Path>) -> Result<SpssMetadata> {
+    let scanner = scan_xpt(path)?;
+    Ok(scanner.metadata().clone())
+}
+
+/// Create a streaming scanner for a SAS transport (.xpt) file.
+///
+/// Default batch size: 100,000 rows.
+pub fn scan_xpt(path: impl AsRef<Path>) -> Result<XptScanner> {
+    XptScanner::open(BufReader::new(File::open(path)?), 100_000)
+}
+
+/// Create a streaming scanner for SAS transport data from any reader.
+pub fn scan_xpt_from_reader<R: Read>(reader: R, batch_size: usize) -> Result<XptScanner> {
+    XptScanner::open(reader, batch_size)
+}
+
+/// Write an Arrow RecordBatch plus metadata to an uncompressed SPSS .sav file.
+///
+/// Column names become variable names; labels, formats, value labels,
+/// missing values and display properties are taken from `metadata` where
+/// present. Numeric columns are stored as doubles (nulls become SYSMIS) and
+/// string columns as fixed-width strings sized to fit their longest value.
+pub fn write_sav(
+    path: impl AsRef<Path>,
+    batch: &RecordBatch,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    write_sav_with(path, batch, metadata, &WriteOptions::default())
+}
+
+/// Write an SPSS file with explicit options, e.g. zlib-compressed .zsav output:
+///
+/// ```no_run
+/// use ambers::{Compression, WriteOptions, read_sav, write_sav_with};
+///
+/// let (batch, meta) = read_sav("survey.sav").unwrap();
+/// let options = WriteOptions {
+///     compression: Compression::Zlib,
+///     zlib_level: 9,
+///     ..WriteOptions::default()
+/// };
+/// write_sav_with("survey.zsav", &batch, &meta, &options).unwrap();
+/// ```
+pub fn write_sav_with(
+    path: impl AsRef<Path>,
+    batch: &RecordBatch,
+    metadata: &SpssMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    let file = File::create(path)?;
+    let writer = BufWriter::with_capacity(8 * 1024 * 1024, file);
+    writer::write_batch(writer, batch, metadata, options)
+}
+
+/// Write a template .sav file: the full dictionary from `metadata` with zero
+/// cases, e.g. as a data-entry shell.
+///
+/// Variables are created in `metadata.variable_names` order. Formats decide
+/// the storage type: string formats (e.g. "A40") give string variables of
+/// that width, date/time formats date/time variables, anything else numeric.
+///
+/// ```no_run
+/// let meta = ambers::read_sav_metadata("survey.sav").unwrap();
+/// ambers::write_sav_template("shell.sav", &meta).unwrap();
+/// ```
+pub fn write_sav_template(path: impl AsRef<Path>, metadata: &SpssMetadata) -> Result<()> {
+    write_sav_template_with(path, metadata, &WriteOptions::default())
+}
+
+/// Write a template .sav file with explicit options.
+pub fn write_sav_template_with(
+    path: impl AsRef<Path>,
+    metadata: &SpssMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    let file = File::create(path)?;
+    writer::write_template(BufWriter::new(file), metadata, options)
+}
+
+/// Rewrite a .sav/.zsav file with edited metadata, copying its data unchanged.
+///
+/// Only the dictionary is regenerated, so this is fast even for large files:
+/// variables can be renamed (by position in `variable_names`) and given new
+/// labels, value labels, formats, missing values and display properties, but
+/// the variable types, string widths and order must stay the same. `output`
+/// may be the same path as `input`; the file is replaced once fully written.
+///
+/// ```no_run
+/// let mut meta = ambers::read_sav_metadata("survey.sav").unwrap();
+/// meta.variable_labels.insert("q1".into(), "Overall satisfaction".into());
+/// ambers::rewrite_sav("survey.sav", "survey.sav", &meta).unwrap();
+/// ```
+pub fn rewrite_sav(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    let output = output.as_ref();
+    let mut tmp_name = output.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp = std::path::PathBuf::from(tmp_name);
+
+    let src = File::open(input)?;
+    let dst = File::create(&tmp)?;
+    let written = writer::rewrite::rewrite_dictionary(
+        BufReader::with_capacity(8 * 1024 * 1024, src),
+        BufWriter::with_capacity(8 * 1024 * 1024, dst),
+        metadata,
+    );
+    match written {
+        Ok(()) => Ok(std::fs::rename(&tmp, output)?),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Rewrite the dictionary of a file read from `reader` into `writer`,
+/// copying the data section unchanged. See [`rewrite_sav`].
+pub fn rewrite_sav_to_writer<R: Read + Seek, W: Write>(
+    reader: R,
+    writer: W,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    writer::rewrite::rewrite_dictionary(reader, writer, metadata)
+}
+
+/// Write an uncompressed SPSS .sav file to any writer.
+pub fn write_sav_to_writer<W: Write>(
+    writer: W,
+    batch: &RecordBatch,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    writer::write_batch(writer, batch, metadata, &WriteOptions::default())
+}
+
+/// Write an SPSS file to any writer with explicit options.
+pub fn write_sav_to_writer_with<W: Write>(
+    writer: W,
+    batch: &RecordBatch,
+    metadata: &SpssMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    writer::write_batch(writer, batch, metadata, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn test_read_sav_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("survey.sav");
+        let bytes = test_util::survey_sav([1.0, 2.0, 3.0], Compression::Bytecode, 4096);
+
+        // Only the first 176 bytes are read, so a file cut before its
+        // dictionary still gives the header
+        std::fs::write(&path, &bytes[..176]).unwrap();
+        let header = read_sav_header(&path).unwrap();
+        assert!(header.product.contains("ambers"), "{}", header.product);
+        assert_eq!(header.compression, Compression::Bytecode);
+        assert_eq!(header.ncases, 3);
+        assert_eq!(header.bias, 100.0);
+        assert!(!header.bswap);
+
+        std::fs::write(&path, &bytes[..100]).unwrap();
+        assert!(read_sav_header(&path).is_err());
+    }
+}
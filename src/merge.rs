@@ -0,0 +1,417 @@
+//! Merging the dictionaries of several files, e.g. the waves of a tracking
+//! survey, before their data is stacked.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::constants::{Measure, SpssFormat};
+use crate::metadata::SpssMetadata;
+
+/// Which variables `SpssMetadata::merge` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Variables in any file, in order of first appearance.
+    #[default]
+    Union,
+    /// Only variables in every file, in the first file's order.
+    Intersection,
+}
+
+/// What a [`MergeConflict`] disagrees on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// Numeric in one file, string in another. Such files cannot be
+    /// stacked without converting the variable.
+    Type,
+    /// Numeric display formats differ (e.g. `F3.0` vs `F8.2`).
+    Format,
+    Label,
+    /// A value is labelled differently.
+    ValueLabel,
+    Missing,
+    Measure,
+}
+
+impl ConflictKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictKind::Type => "type",
+            ConflictKind::Format => "format",
+            ConflictKind::Label => "label",
+            ConflictKind::ValueLabel => "value_label",
+            ConflictKind::Missing => "missing",
+            ConflictKind::Measure => "measure",
+        }
+    }
+}
+
+/// A variable defined differently in two of the merged files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    pub variable: String,
+    pub kind: ConflictKind,
+    /// Index of the file whose definition was not kept; the merged
+    /// metadata has the definition of an earlier file.
+    pub file: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, file {}): {}",
+            self.variable,
+            self.kind.as_str(),
+            self.file,
+            self.message
+        )
+    }
+}
+
+/// Result of `SpssMetadata::merge`.
+#[derive(Debug, Clone)]
+pub struct MergedMetadata {
+    pub metadata: SpssMetadata,
+    /// Every disagreement found, by variable and then file order.
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl SpssMetadata {
+    /// Merge the dictionaries of `files` into one.
+    ///
+    /// Variables are matched by name, case-insensitively, and take the
+    /// spelling of the first file that has them. Each variable keeps the
+    /// definitions of the first file that defines them, except that value
+    /// labels are combined across files and string variables get the widest
+    /// width. Disagreements are reported as conflicts rather than errors, so
+    /// the dictionaries of all waves can be checked in one pass. File-level
+    /// fields come from the first file; `number_rows` is the total when every
+    /// file records one.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ambers::{MergeStrategy, SpssMetadata};
+    ///
+    /// let w1 = ambers::read_sav_metadata("wave1.sav").unwrap();
+    /// let w2 = ambers::read_sav_metadata("wave2.sav").unwrap();
+    /// let merged = SpssMetadata::merge(&[&w1, &w2], MergeStrategy::Union);
+    /// for conflict in &merged.conflicts {
+    ///     println!("{conflict}");
+    /// }
+    /// ```
+    pub fn merge(files: &[&SpssMetadata], strategy: MergeStrategy) -> MergedMetadata {
+        let Some(first) = files.first() else {
+            return MergedMetadata {
+                metadata: SpssMetadata::default(),
+                conflicts: Vec::new(),
+            };
+        };
+        // {lowercase name -> spelling} per file
+        let lookups: Vec<HashMap<String, &String>> = files
+            .iter()
+            .map(|meta| {
+                meta.variable_names
+                    .iter()
+                    .map(|name| (name.to_lowercase(), name))
+                    .collect()
+            })
+            .collect();
+
+        let mut names: IndexMap<String, String> = IndexMap::new();
+        for meta in files {
+            for name in &meta.variable_names {
+                let key = name.to_lowercase();
+                if strategy == MergeStrategy::Intersection
+                    && !lookups.iter().all(|lookup| lookup.contains_key(&key))
+                {
+                    continue;
+                }
+                names.entry(key).or_insert_with(|| name.clone());
+            }
+        }
+
+        let mut merged = SpssMetadata {
+            file_label: first.file_label.clone(),
+            file_encoding: first.file_encoding.clone(),
+            compression: first.compression,
+            creation_time: first.creation_time.clone(),
+            modification_time: first.modification_time.clone(),
+            notes: first.notes.clone(),
+            number_rows: files.iter().map(|meta| meta.number_rows).sum(),
+            number_columns: names.len(),
+            file_format: first.file_format.clone(),
+            variable_names: names.values().cloned().collect(),
+            weight_variable: first.weight_variable.clone(),
+            file_attributes: first.file_attributes.clone(),
+            ..SpssMetadata::default()
+        };
+        let mut conflicts = Vec::new();
+
+        for (key, name) in &names {
+            let mut conflict = |kind, file, message| {
+                conflicts.push(MergeConflict {
+                    variable: name.clone(),
+                    kind,
+                    file,
+                    message,
+                })
+            };
+            for (i, (meta, lookup)) in files.iter().zip(&lookups).enumerate() {
+                let Some(&src) = lookup.get(key) else {
+                    continue;
+                };
+
+                if let Some(format) = meta.format(src) {
+                    match merged.spss_variable_types.get(name) {
+                        None => {
+                            merged
+                                .spss_variable_types
+                                .insert(name.clone(), format.to_string());
+                            copy(
+                                &meta.rust_variable_types,
+                                src,
+                                &mut merged.rust_variable_types,
+                                name,
+                            );
+                            copy(
+                                &meta.variable_storage_width,
+                                src,
+                                &mut merged.variable_storage_width,
+                                name,
+                            );
+                        }
+                        Some(kept) if kept != format => {
+                            let kept_string = is_string(kept);
+                            if kept_string != is_string(format) {
+                                conflict(ConflictKind::Type, i, format!("{kept} vs {format}"));
+                            } else if kept_string {
+                                // Stacking needs the widest width.
+                                let width = meta.variable_storage_width.get(src).copied();
+                                let kept_width = merged.variable_storage_width.get(name).copied();
+                                if width > kept_width {
+                                    merged
+                                        .spss_variable_types
+                                        .insert(name.clone(), format.to_string());
+                                    copy(
+                                        &meta.variable_storage_width,
+                                        src,
+                                        &mut merged.variable_storage_width,
+                                        name,
+                                    );
+                                }
+                            } else {
+                                conflict(ConflictKind::Format, i, format!("{kept} vs {format}"));
+                            }
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if let Some(label) = meta.label(src) {
+                    match merged.label(name) {
+                        None => {
+                            merged
+                                .variable_labels
+                                .insert(name.clone(), label.to_string());
+                        }
+                        Some(kept) if kept != label => {
+                            conflict(ConflictKind::Label, i, format!("{kept:?} vs {label:?}"));
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if let Some(labels) = meta.value_labels(src) {
+                    let set = merged
+                        .variable_value_labels
+                        .entry(name.clone())
+                        .or_default();
+                    for (value, label) in labels {
+                        match set.get(value) {
+                            None => {
+                                set.insert(value.clone(), label.clone());
+                            }
+                            Some(kept) if kept != label => conflict(
+                                ConflictKind::ValueLabel,
+                                i,
+                                format!("{value}: {kept:?} vs {label:?}"),
+                            ),
+                            Some(_) => {}
+                        }
+                    }
+                }
+
+                if let Some(specs) = meta.variable_missing.get(src) {
+                    match merged.variable_missing.get(name) {
+                        None => {
+                            merged.variable_missing.insert(name.clone(), specs.clone());
+                        }
+                        Some(kept) if kept != specs => {
+                            conflict(
+                                ConflictKind::Missing,
+                                i,
+                                "missing values differ".to_string(),
+                            );
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if let Some(measure) = meta.measure(src).filter(|m| *m != Measure::Unknown) {
+                    match merged.measure(name) {
+                        None => {
+                            merged.variable_measure.insert(name.clone(), measure);
+                        }
+                        Some(kept) if kept != measure => conflict(
+                            ConflictKind::Measure,
+                            i,
+                            format!("{} vs {}", kept.as_str(), measure.as_str()),
+                        ),
+                        Some(_) => {}
+                    }
+                }
+
+                copy(
+                    &meta.variable_alignment,
+                    src,
+                    &mut merged.variable_alignment,
+                    name,
+                );
+                copy(
+                    &meta.variable_display_width,
+                    src,
+                    &mut merged.variable_display_width,
+                    name,
+                );
+                copy(&meta.variable_role, src, &mut merged.variable_role, name);
+                copy(
+                    &meta.variable_attributes,
+                    src,
+                    &mut merged.variable_attributes,
+                    name,
+                );
+            }
+        }
+
+        for meta in files {
+            for (set_name, set) in &meta.mr_sets {
+                if merged.mr_sets.contains_key(set_name) {
+                    continue;
+                }
+                let mut set = set.clone();
+                let members: Option<Vec<String>> = set
+                    .variables
+                    .iter()
+                    .map(|v| names.get(&v.to_lowercase()).cloned())
+                    .collect();
+                // Sets losing members to an intersection are dropped.
+                let Some(members) = members else {
+                    continue;
+                };
+                set.variables = members;
+                merged.mr_sets.insert(set_name.clone(), set);
+            }
+        }
+
+        MergedMetadata {
+            metadata: merged,
+            conflicts,
+        }
+    }
+}
+
+/// Copy `from[src]` to `to[name]` unless `to` already has `name`.
+fn copy<V: Clone>(from: &IndexMap<String, V>, src: &str, to: &mut IndexMap<String, V>, name: &str) {
+    if let Some(value) = from.get(src)
+        && !to.contains_key(name)
+    {
+        to.insert(name.to_string(), value.clone());
+    }
+}
+
+fn is_string(format: &str) -> bool {
+    SpssFormat::parse(format).is_some_and(|f| f.format_type.is_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Value;
+
+    fn wave(names: &[&str]) -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            number_rows: Some(10),
+            variable_names: names.iter().map(|n| n.to_string()).collect(),
+            ..SpssMetadata::default()
+        };
+        for name in names {
+            meta.spss_variable_types
+                .insert(name.to_string(), "F1.0".to_string());
+        }
+        meta
+    }
+
+    #[test]
+    fn test_merge_variable_sets() {
+        let w1 = wave(&["id", "q1", "q2"]);
+        let w2 = wave(&["ID", "q2", "q3"]);
+        let union = SpssMetadata::merge(&[&w1, &w2], MergeStrategy::Union);
+        assert_eq!(union.metadata.variable_names, ["id", "q1", "q2", "q3"]);
+        assert_eq!(union.metadata.number_rows, Some(20));
+        assert!(union.conflicts.is_empty());
+        let both = SpssMetadata::merge(&[&w1, &w2], MergeStrategy::Intersection);
+        assert_eq!(both.metadata.variable_names, ["id", "q2"]);
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let mut w1 = wave(&["q1", "city"]);
+        w1.spss_variable_types.insert("city".into(), "A10".into());
+        w1.variable_storage_width.insert("city".into(), 10);
+        w1.variable_labels.insert("q1".into(), "Owns a car".into());
+        w1.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let mut w2 = wave(&["q1", "city"]);
+        w2.spss_variable_types.insert("city".into(), "A20".into());
+        w2.variable_storage_width.insert("city".into(), 20);
+        w2.variable_labels.insert("q1".into(), "Has a car".into());
+        w2.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes!".to_string()),
+                (Value::Numeric(2.0), "No".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut w3 = wave(&["q1"]);
+        w3.spss_variable_types.insert("q1".into(), "A1".into());
+
+        let merged = SpssMetadata::merge(&[&w1, &w2, &w3], MergeStrategy::Union);
+        let found: Vec<(&str, ConflictKind, usize)> = merged
+            .conflicts
+            .iter()
+            .map(|c| (c.variable.as_str(), c.kind, c.file))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("q1", ConflictKind::Label, 1),
+                ("q1", ConflictKind::ValueLabel, 1),
+                ("q1", ConflictKind::Type, 2),
+            ]
+        );
+        let meta = &merged.metadata;
+        assert_eq!(meta.label("q1"), Some("Owns a car"));
+        assert_eq!(meta.value_labels("q1").unwrap().len(), 2);
+        assert_eq!(meta.format("city"), Some("A20"));
+        assert_eq!(meta.number_rows, Some(30));
+    }
+}
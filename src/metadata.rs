@@ -1,7 +1,12 @@
 use indexmap::IndexMap;
 
-use crate::constants::{Alignment, Compression, Measure};
+use crate::constants::{Alignment, Compression, Measure, Role};
+use crate::error::{Result, SpssError};
+use crate::product::WriterProfile;
 use crate::variable::MissingValues;
+use crate::warning::SpssWarning;
+#[cfg(feature = "nfc")]
+use crate::warning::WarningKind;
 
 /// A value that can be used as a key in value label maps.
 #[derive(Debug, Clone)]
@@ -75,8 +80,26 @@ impl std::fmt::Display for Value {
     }
 }
 
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Numeric(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
 /// A missing value specification for the public API.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MissingSpec {
     /// A single discrete missing value.
     Value(f64),
@@ -167,6 +190,7 @@ pub struct SpssMetadata {
     pub variable_storage_width: IndexMap<String, usize>,
     pub variable_display_width: IndexMap<String, u32>,
     pub variable_measure: IndexMap<String, Measure>,
+    pub variable_role: IndexMap<String, Role>,
 
     // Missing values
     pub variable_missing: IndexMap<String, Vec<MissingSpec>>,
@@ -174,6 +198,41 @@ pub struct SpssMetadata {
     // SPSS-specific
     pub mr_sets: IndexMap<String, MrSet>,
     pub weight_variable: Option<String>,
+
+    // Custom attributes: {attr_name -> values} for the file,
+    // {var_name -> {attr_name -> values}} per variable
+    pub file_attributes: IndexMap<String, Vec<String>>,
+    pub variable_attributes: IndexMap<String, IndexMap<String, Vec<String>>>,
+
+    // TRENDS date info (subtype 6): one i32 triple per DEFINE DATES
+    // component, in file order; SPSS does not document the fields
+    pub date_info: Vec<[i32; 3]>,
+
+    // Type 7 records of subtypes ambers does not interpret:
+    // (subtype, payload bytes) in file order
+    pub unknown_records: Vec<(i32, Vec<u8>)>,
+
+    // Output column renames: {output name -> name in the file}, for the
+    // columns renamed by `ScanOptions::sanitize_names`
+    pub renamed_columns: IndexMap<String, String>,
+
+    // Variable renames made on reading: {name given -> long name in the
+    // file}, for variables whose long names clash with an earlier one's
+    pub renamed_variables: IndexMap<String, String>,
+
+    // Print formats of a type ambers does not know: {var_name -> packed
+    // format code}; `spss_variable_types` holds a guess for these
+    pub unknown_formats: IndexMap<String, i32>,
+
+    // Anomalies met while reading the dictionary, in the order found
+    pub warnings: Vec<SpssWarning>,
+
+    // U+FFFD characters put into the data read so far in place of bytes
+    // not valid in the encoding: {var_name -> count}
+    pub replaced_characters: IndexMap<String, u64>,
+
+    // The program that wrote the file and the quirks worked around for it
+    pub writer: WriterProfile,
 }
 
 impl SpssMetadata {
@@ -197,6 +256,297 @@ impl SpssMetadata {
         self.variable_measure.get(name).copied()
     }
 
+    /// Get the role for a variable (the `$@Role` attribute).
+    pub fn role(&self, name: &str) -> Option<Role> {
+        self.variable_role.get(name).copied()
+    }
+
+    /// Normalize the dictionary's text to Unicode NFC (see
+    /// `ScanOptions::normalize_nfc`). String values whose labels become
+    /// equal once normalized keep the first label, with a
+    /// `WarningKind::MergedValueLabels` warning.
+    #[cfg(feature = "nfc")]
+    pub(crate) fn normalize_nfc(&mut self) {
+        let nfc = |text: &mut String| {
+            if let std::borrow::Cow::Owned(composed) = crate::encoding::to_nfc(text) {
+                *text = composed;
+            }
+        };
+        nfc(&mut self.file_label);
+        self.variable_labels.values_mut().for_each(nfc);
+        for (name, labels) in &mut self.variable_value_labels {
+            let mut normalized = IndexMap::with_capacity(labels.len());
+            for (mut value, mut label) in std::mem::take(labels) {
+                if let Value::String(s) = &mut value {
+                    nfc(s);
+                }
+                nfc(&mut label);
+                match normalized.entry(value) {
+                    indexmap::map::Entry::Vacant(entry) => {
+                        entry.insert(label);
+                    }
+                    indexmap::map::Entry::Occupied(entry) => {
+                        let Value::String(value) = entry.key() else {
+                            unreachable!("only string values change when normalized")
+                        };
+                        self.warnings.push(SpssWarning::new(
+                            WarningKind::MergedValueLabels,
+                            Some(name),
+                            format!(
+                                "two values are {value:?} in NFC; kept the label {:?}, \
+                                 dropped {label:?}",
+                                entry.get()
+                            ),
+                        ));
+                    }
+                }
+            }
+            *labels = normalized;
+        }
+        for specs in self.variable_missing.values_mut() {
+            for spec in specs {
+                if let MissingSpec::StringValue(s) = spec {
+                    nfc(s);
+                }
+            }
+        }
+        for set in self.mr_sets.values_mut() {
+            nfc(&mut set.label);
+            set.counted_value.iter_mut().for_each(nfc);
+        }
+    }
+
+    /// Rename variables everywhere they are named: {old name -> new name}.
+    pub(crate) fn rename_variables(&mut self, renames: &IndexMap<String, String>) {
+        if renames.is_empty() {
+            return;
+        }
+        let rename = |name: &mut String| {
+            if let Some(new) = renames.get(name.as_str()) {
+                *name = new.clone();
+            }
+        };
+        fn rekey<V>(map: &mut IndexMap<String, V>, renames: &IndexMap<String, String>) {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(name, v)| (renames.get(&name).cloned().unwrap_or(name), v))
+                .collect();
+        }
+        self.variable_names.iter_mut().for_each(rename);
+        rekey(&mut self.variable_labels, renames);
+        rekey(&mut self.spss_variable_types, renames);
+        rekey(&mut self.rust_variable_types, renames);
+        rekey(&mut self.variable_value_labels, renames);
+        rekey(&mut self.variable_alignment, renames);
+        rekey(&mut self.variable_storage_width, renames);
+        rekey(&mut self.variable_display_width, renames);
+        rekey(&mut self.variable_measure, renames);
+        rekey(&mut self.variable_role, renames);
+        rekey(&mut self.variable_missing, renames);
+        for set in self.mr_sets.values_mut() {
+            set.variables.iter_mut().for_each(rename);
+        }
+        self.weight_variable.iter_mut().for_each(rename);
+        rekey(&mut self.variable_attributes, renames);
+        rekey(&mut self.renamed_variables, renames);
+        rekey(&mut self.unknown_formats, renames);
+        rekey(&mut self.replaced_characters, renames);
+        for warning in &mut self.warnings {
+            warning.variable.iter_mut().for_each(rename);
+        }
+    }
+
+    /// Merge `overlay` into this metadata; the overlay wins wherever both
+    /// define something.
+    pub fn apply_overlay(&mut self, overlay: &MetadataOverlay) {
+        for (name, label) in &overlay.variable_labels {
+            self.variable_labels.insert(name.clone(), label.clone());
+        }
+        for (name, labels) in &overlay.variable_value_labels {
+            self.variable_value_labels
+                .insert(name.clone(), labels.clone());
+        }
+        for (name, labels) in &overlay.added_value_labels {
+            let existing = self.variable_value_labels.entry(name.clone()).or_default();
+            for (value, label) in labels {
+                existing.insert(value.clone(), label.clone());
+            }
+        }
+        for (name, specs) in &overlay.variable_missing {
+            if specs.is_empty() {
+                self.variable_missing.shift_remove(name);
+            } else {
+                self.variable_missing.insert(name.clone(), specs.clone());
+            }
+        }
+        for (name, measure) in &overlay.variable_measure {
+            self.variable_measure.insert(name.clone(), *measure);
+        }
+    }
+
+    /// Merge `overlay` into this metadata, resolving definitions that
+    /// differ from the existing ones by `policy`.
+    ///
+    /// With `ConflictPolicy::Error` nothing is applied if there is any
+    /// conflict; the error lists them all.
+    pub fn apply_overlay_with(
+        &mut self,
+        overlay: &MetadataOverlay,
+        policy: ConflictPolicy,
+    ) -> Result<()> {
+        match policy {
+            ConflictPolicy::Overlay => self.apply_overlay(overlay),
+            ConflictPolicy::Existing => {
+                let gaps = self.overlay_gaps(overlay);
+                self.apply_overlay(&gaps);
+            }
+            ConflictPolicy::Error => {
+                let conflicts = self.overlay_conflicts(overlay);
+                if !conflicts.is_empty() {
+                    return Err(SpssError::MetadataConflict(conflicts.join("; ")));
+                }
+                self.apply_overlay(overlay);
+            }
+        }
+        Ok(())
+    }
+
+    /// The parts of `overlay` this metadata does not define yet.
+    fn overlay_gaps(&self, overlay: &MetadataOverlay) -> MetadataOverlay {
+        let has_labels = |name: &str| self.value_labels(name).is_some_and(|l| !l.is_empty());
+        MetadataOverlay {
+            variable_labels: overlay
+                .variable_labels
+                .iter()
+                .filter(|(name, _)| self.label(name).is_none())
+                .map(|(name, label)| (name.clone(), label.clone()))
+                .collect(),
+            variable_value_labels: overlay
+                .variable_value_labels
+                .iter()
+                .filter(|(name, _)| !has_labels(name))
+                .map(|(name, labels)| (name.clone(), labels.clone()))
+                .collect(),
+            added_value_labels: overlay
+                .added_value_labels
+                .iter()
+                .map(|(name, labels)| {
+                    let existing = self.value_labels(name);
+                    let labels = labels
+                        .iter()
+                        .filter(|(value, _)| !existing.is_some_and(|e| e.contains_key(*value)))
+                        .map(|(value, label)| (value.clone(), label.clone()))
+                        .collect();
+                    (name.clone(), labels)
+                })
+                .collect(),
+            variable_missing: overlay
+                .variable_missing
+                .iter()
+                .filter(|(name, _)| self.variable_missing.get(*name).is_none_or(Vec::is_empty))
+                .map(|(name, specs)| (name.clone(), specs.clone()))
+                .collect(),
+            variable_measure: overlay
+                .variable_measure
+                .iter()
+                .filter(|(name, _)| self.measure(name).is_none_or(|m| m == Measure::Unknown))
+                .map(|(name, measure)| (name.clone(), *measure))
+                .collect(),
+        }
+    }
+
+    /// Where `overlay` redefines something this metadata defines differently.
+    fn overlay_conflicts(&self, overlay: &MetadataOverlay) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (name, label) in &overlay.variable_labels {
+            if let Some(existing) = self.label(name)
+                && existing != label
+            {
+                conflicts.push(format!("{name}: variable label {existing:?} vs {label:?}"));
+            }
+        }
+        for (name, labels) in &overlay.variable_value_labels {
+            if let Some(existing) = self.value_labels(name)
+                && !existing.is_empty()
+                && existing != labels
+            {
+                conflicts.push(format!("{name}: value labels differ"));
+            }
+        }
+        for (name, labels) in &overlay.added_value_labels {
+            let Some(existing) = self.value_labels(name) else {
+                continue;
+            };
+            for (value, label) in labels {
+                if let Some(old) = existing.get(value)
+                    && old != label
+                {
+                    conflicts.push(format!(
+                        "{name}: value {value} labelled {old:?} vs {label:?}"
+                    ));
+                }
+            }
+        }
+        for (name, specs) in &overlay.variable_missing {
+            if let Some(existing) = self.variable_missing.get(name)
+                && !existing.is_empty()
+                && existing != specs
+            {
+                conflicts.push(format!("{name}: missing values differ"));
+            }
+        }
+        for (name, measure) in &overlay.variable_measure {
+            if let Some(existing) = self.measure(name)
+                && existing != Measure::Unknown
+                && existing != *measure
+            {
+                conflicts.push(format!(
+                    "{name}: measure {} vs {}",
+                    existing.as_str(),
+                    measure.as_str()
+                ));
+            }
+        }
+        conflicts
+    }
+}
+
+/// What `SpssMetadata::apply_overlay_with` does where the overlay defines
+/// something the metadata already defines differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// The overlay's definition replaces the existing one.
+    #[default]
+    Overlay,
+    /// The existing definition is kept.
+    Existing,
+    /// Nothing is applied and the conflicts are returned as an error.
+    Error,
+}
+
+/// Dictionary changes to layer over an existing `SpssMetadata`, e.g. parsed
+/// from an SPSS syntax file.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataOverlay {
+    pub variable_labels: IndexMap<String, String>,
+    /// Value labels that replace a variable's existing set (VALUE LABELS).
+    pub variable_value_labels: IndexMap<String, IndexMap<Value, String>>,
+    /// Value labels added to a variable's existing set (ADD VALUE LABELS).
+    pub added_value_labels: IndexMap<String, IndexMap<Value, String>>,
+    /// Missing values replacing the existing ones; an empty list clears them.
+    pub variable_missing: IndexMap<String, Vec<MissingSpec>>,
+    pub variable_measure: IndexMap<String, Measure>,
+}
+
+impl MetadataOverlay {
+    /// True if the overlay changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.variable_labels.is_empty()
+            && self.variable_value_labels.is_empty()
+            && self.added_value_labels.is_empty()
+            && self.variable_missing.is_empty()
+            && self.variable_measure.is_empty()
+    }
 }
 
 impl Default for SpssMetadata {
@@ -220,9 +570,20 @@ impl Default for SpssMetadata {
             variable_storage_width: IndexMap::new(),
             variable_display_width: IndexMap::new(),
             variable_measure: IndexMap::new(),
+            variable_role: IndexMap::new(),
             variable_missing: IndexMap::new(),
             mr_sets: IndexMap::new(),
             weight_variable: None,
+            file_attributes: IndexMap::new(),
+            variable_attributes: IndexMap::new(),
+            date_info: Vec::new(),
+            unknown_records: Vec::new(),
+            renamed_columns: IndexMap::new(),
+            renamed_variables: IndexMap::new(),
+            unknown_formats: IndexMap::new(),
+            warnings: Vec::new(),
+            replaced_characters: IndexMap::new(),
+            writer: WriterProfile::default(),
         }
     }
 }
@@ -0,0 +1,96 @@
+//! Memory-mapped input (feature `mmap`).
+//!
+//! Mapping the file lets the scanner read uncompressed cases and compressed
+//! data straight from the page cache: bytecode is decoded from a slice of
+//! the mapping instead of being copied into a `Vec` first, and ZSAV blocks
+//! are inflated from mapped slices without intermediate reads.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::error::Result;
+
+/// A read-only memory map of a file, cheap to clone.
+///
+/// Used as the reader of a scanner (via `Cursor<MmapSource>`) and shared
+/// with its compressed-data state.
+#[derive(Clone)]
+pub struct MmapSource(Arc<Mmap>);
+
+impl MmapSource {
+    /// Map `path` into memory.
+    ///
+    /// As with any memory map, the file must not be truncated or modified
+    /// by another process while it is mapped.
+    pub fn open(path: impl AsRef<Path>) -> Result<MmapSource> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only; the caller is told not to
+        // modify the file while it is open (see the doc comment above).
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MmapSource(Arc::new(map)))
+    }
+}
+
+impl AsRef<[u8]> for MmapSource {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+
+    use crate::constants::Compression;
+    use crate::metadata::SpssMetadata;
+    use crate::writer::WriteOptions;
+
+    #[test]
+    fn test_mmap_matches_buffered_reads() {
+        let n = 2000;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from_iter_values(
+                    (0..n).map(|i| i as f64 / 4.0),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..n).map(|i| format!("row {i}")),
+                )),
+            ],
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let path = dir.path().join(format!("{compression:?}.sav"));
+            let options = WriteOptions {
+                compression,
+                zlib_block_size: 4096,
+                ..WriteOptions::default()
+            };
+            crate::write_sav_with(&path, &batch, &SpssMetadata::default(), &options).unwrap();
+
+            let (expected, _) = crate::read_sav(&path).unwrap();
+            let mut scanner = crate::scan_sav_mmap(&path).unwrap();
+            assert_eq!(scanner.collect_single().unwrap(), expected);
+
+            let mut scanner = crate::scan_sav_mmap(&path).unwrap();
+            assert_eq!(
+                scanner.read_rows(1500..1502).unwrap(),
+                expected.slice(1500, 2)
+            );
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Scanning several .sav/.zsav files as one table.
+//!
+//! `scan_sav_many` is meant for waves of the same survey: the files are
+//! read one after another, and their schemas are unified up front so every
+//! batch has the same columns. A column missing from some files is filled
+//! with nulls there; a column whose type differs between files is an error.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, StringViewArray, new_null_array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::{RecordBatch, RecordBatchOptions};
+
+use crate::error::{Result, SpssError};
+use crate::metadata::SpssMetadata;
+use crate::scanner::SavScanner;
+
+/// A streaming reader over several SPSS files with a unified schema.
+///
+/// # Example
+/// ```no_run
+/// let mut scanner = ambers::scan_sav_many(["wave1.sav", "wave2.sav"]).unwrap();
+/// scanner.source_column("wave");
+/// scanner.select(&["id", "q1", "wave"]).unwrap();
+/// while let Some(batch) = scanner.next_batch().unwrap() {
+///     println!("Batch: {} rows", batch.num_rows());
+/// }
+/// ```
+pub struct MultiScanner {
+    paths: Vec<PathBuf>,
+    metadata: Vec<SpssMetadata>,
+    /// Unified data columns, in order of first appearance.
+    fields: Vec<Field>,
+    projection: Option<Vec<usize>>,
+    source_column: Option<String>,
+    batch_size: usize,
+    row_limit: Option<usize>,
+    rows_read: usize,
+    /// The file being read and its index in `paths`.
+    current: Option<(usize, SavScanner<BufReader<File>>)>,
+    next_file: usize,
+}
+
+impl MultiScanner {
+    /// Open every file's dictionary and unify the schemas.
+    pub fn open<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+        batch_size: usize,
+    ) -> Result<Self> {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect();
+        if paths.is_empty() {
+            return Err(SpssError::Unsupported(
+                "scan_sav_many: no files given".to_string(),
+            ));
+        }
+        let mut metadata = Vec::with_capacity(paths.len());
+        let mut fields: Vec<Field> = Vec::new();
+        // File that each unified field was first seen in, for error messages.
+        let mut first_seen: Vec<usize> = Vec::new();
+        for (i, path) in paths.iter().enumerate() {
+            let scanner = open_file(path, 0)?;
+            for field in scanner.schema().fields() {
+                match fields.iter().position(|f| f.name() == field.name()) {
+                    Some(j) if fields[j].data_type() != field.data_type() => {
+                        return Err(SpssError::InvalidVariable(format!(
+                            "column {:?} is {} in {} but {} in {}",
+                            field.name(),
+                            fields[j].data_type(),
+                            paths[first_seen[j]].display(),
+                            field.data_type(),
+                            path.display(),
+                        )));
+                    }
+                    Some(_) => {}
+                    None => {
+                        fields.push(field.as_ref().clone().with_nullable(true));
+                        first_seen.push(i);
+                    }
+                }
+            }
+            metadata.push(scanner.metadata().clone());
+        }
+        Ok(MultiScanner {
+            paths,
+            metadata,
+            fields,
+            projection: None,
+            source_column: None,
+            batch_size,
+            row_limit: None,
+            rows_read: 0,
+            current: None,
+            next_file: 0,
+        })
+    }
+
+    /// The files being scanned, in reading order.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Metadata of each file, in the same order as `paths()`.
+    pub fn metadata(&self) -> &[SpssMetadata] {
+        &self.metadata
+    }
+
+    /// Get the unified Arrow schema (respects column projection).
+    pub fn schema(&self) -> Schema {
+        let mut fields: Vec<Field> = match &self.projection {
+            Some(proj) => proj.iter().map(|&i| self.fields[i].clone()).collect(),
+            None => self.fields.clone(),
+        };
+        if let Some(name) = &self.source_column {
+            fields.push(Field::new(name, DataType::Utf8View, false));
+        }
+        Schema::new(fields)
+    }
+
+    /// Append a column named `name` holding the path of the file each row
+    /// came from. It can then be named in `select()`.
+    pub fn source_column(&mut self, name: &str) {
+        self.source_column = Some(name.to_string());
+    }
+
+    /// Set column projection — only these columns will be read and returned.
+    /// Names are looked up in the unified schema; the source column, if
+    /// any, is always appended last.
+    pub fn select(&mut self, columns: &[&str]) -> Result<()> {
+        let mut indices = Vec::with_capacity(columns.len());
+        for &col in columns {
+            if self.source_column.as_deref() == Some(col) {
+                continue;
+            }
+            let idx = self
+                .fields
+                .iter()
+                .position(|f| f.name() == col)
+                .ok_or_else(|| SpssError::InvalidVariable(format!("column not found: {col:?}")))?;
+            indices.push(idx);
+        }
+        self.projection = Some(indices);
+        Ok(())
+    }
+
+    /// Set a row limit — stop reading after this many rows in total.
+    pub fn limit(&mut self, n: usize) {
+        self.row_limit = Some(n);
+    }
+
+    /// How many rows have been read so far, across all files.
+    pub fn rows_read(&self) -> usize {
+        self.rows_read
+    }
+
+    /// Read the next batch of rows. Returns `Ok(None)` after the last file.
+    ///
+    /// Batches never span two files.
+    pub fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        loop {
+            let remaining = match self.row_limit {
+                Some(limit) if self.rows_read >= limit => return Ok(None),
+                Some(limit) => limit - self.rows_read,
+                None => usize::MAX,
+            };
+            if self.current.is_none() {
+                if self.next_file == self.paths.len() {
+                    return Ok(None);
+                }
+                let scanner = self.open_next()?;
+                self.current = Some((self.next_file, scanner));
+                self.next_file += 1;
+            }
+            let Some((file, scanner)) = self.current.as_mut() else {
+                continue;
+            };
+            let file = *file;
+            scanner.limit(scanner.rows_read().saturating_add(remaining));
+            match scanner.next_batch()? {
+                Some(batch) => {
+                    self.rows_read += batch.num_rows();
+                    return self.unify(file, &batch).map(Some);
+                }
+                None => self.current = None,
+            }
+        }
+    }
+
+    /// Read all remaining data as a single RecordBatch.
+    pub fn collect_single(&mut self) -> Result<RecordBatch> {
+        let batches = self.collect_all()?;
+        let schema = Arc::new(self.schema());
+        Ok(arrow::compute::concat_batches(&schema, &batches)?)
+    }
+
+    /// Read all remaining data as a Vec of RecordBatches.
+    pub fn collect_all(&mut self) -> Result<Vec<RecordBatch>> {
+        let mut batches = Vec::new();
+        while let Some(batch) = self.next_batch()? {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// Open the next file, projected to the wanted columns it has.
+    fn open_next(&self) -> Result<SavScanner<BufReader<File>>> {
+        let mut scanner = open_file(&self.paths[self.next_file], self.batch_size)?;
+        if let Some(proj) = &self.projection {
+            let names = &self.metadata[self.next_file].variable_names;
+            let mut present: Vec<&str> = proj
+                .iter()
+                .map(|&i| self.fields[i].name().as_str())
+                .filter(|name| names.iter().any(|n| n == name))
+                .collect();
+            // Decode one column anyway so batches still carry row counts.
+            if present.is_empty()
+                && let Some(first) = names.first()
+            {
+                present.push(first);
+            }
+            scanner.select(&present)?;
+        }
+        Ok(scanner)
+    }
+
+    /// Reshape a batch from file `file` to the unified schema.
+    fn unify(&self, file: usize, batch: &RecordBatch) -> Result<RecordBatch> {
+        let schema = Arc::new(self.schema());
+        let rows = batch.num_rows();
+        let data_fields = schema.fields().len() - usize::from(self.source_column.is_some());
+        let mut columns: Vec<ArrayRef> = schema.fields()[..data_fields]
+            .iter()
+            .map(|field| match batch.column_by_name(field.name()) {
+                Some(col) => col.clone(),
+                None => new_null_array(field.data_type(), rows),
+            })
+            .collect();
+        if self.source_column.is_some() {
+            let path = self.paths[file].display().to_string();
+            columns.push(Arc::new(StringViewArray::from_iter_values(
+                std::iter::repeat_n(path.as_str(), rows),
+            )));
+        }
+        let options = RecordBatchOptions::new().with_row_count(Some(rows));
+        Ok(RecordBatch::try_new_with_options(
+            schema, columns, &options,
+        )?)
+    }
+}
+
+fn open_file(path: &Path, batch_size: usize) -> Result<SavScanner<BufReader<File>>> {
+    let file = File::open(path)?;
+    let mut scanner = SavScanner::open(BufReader::with_capacity(1024 * 1024, file), batch_size)?;
+    scanner.set_source_path(path.display().to_string());
+    Ok(scanner)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, Float64Array, StringArray};
+
+    use super::*;
+    use crate::scan_sav_many;
+
+    fn write_wave(dir: &Path, name: &str, ids: std::ops::Range<i32>, extra: bool) -> PathBuf {
+        let mut fields = vec![Field::new("id", DataType::Float64, true)];
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(Float64Array::from_iter_values(
+            ids.clone().map(f64::from),
+        ))];
+        if extra {
+            fields.push(Field::new("q9", DataType::Utf8, true));
+            columns.push(Arc::new(StringArray::from_iter_values(
+                ids.map(|i| format!("answer {i}")),
+            )));
+        }
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+        let path = dir.join(name);
+        crate::write_sav(&path, &batch, &SpssMetadata::default()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_scan_many_null_fills_and_tags_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let w1 = write_wave(dir.path(), "w1.sav", 0..5, false);
+        let w2 = write_wave(dir.path(), "w2.sav", 5..8, true);
+
+        let mut scanner = MultiScanner::open([&w1, &w2], 4).unwrap();
+        scanner.source_column("file");
+        let sizes: Vec<usize> = scanner
+            .collect_all()
+            .unwrap()
+            .iter()
+            .map(|b| b.num_rows())
+            .collect();
+        assert_eq!(sizes, vec![4, 1, 3]);
+
+        let mut scanner = scan_sav_many([&w1, &w2]).unwrap();
+        scanner.source_column("file");
+        let all = scanner.collect_single().unwrap();
+        assert_eq!(all.num_rows(), 8);
+        assert_eq!(all.schema().field(1).name(), "q9");
+        let q9 = all.column(1);
+        assert_eq!(q9.null_count(), 5);
+        let files = all
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringViewArray>()
+            .unwrap();
+        assert!(files.value(0).ends_with("w1.sav"));
+        assert!(files.value(7).ends_with("w2.sav"));
+    }
+
+    #[test]
+    fn test_scan_many_select_and_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let w1 = write_wave(dir.path(), "w1.sav", 0..5, false);
+        let w2 = write_wave(dir.path(), "w2.sav", 5..8, true);
+
+        let mut scanner = scan_sav_many([&w1, &w2]).unwrap();
+        scanner.select(&["q9"]).unwrap();
+        scanner.limit(6);
+        let all = scanner.collect_single().unwrap();
+        assert_eq!(all.num_columns(), 1);
+        assert_eq!(all.num_rows(), 6);
+        assert_eq!(all.column(0).null_count(), 5);
+        assert!(scanner.select(&["nope"]).is_err());
+    }
+
+    #[test]
+    fn test_scan_many_type_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let w1 = write_wave(dir.path(), "w1.sav", 0..5, true);
+        let schema = Arc::new(Schema::new(vec![Field::new("q9", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.0]))]).unwrap();
+        let w2 = dir.path().join("w2.sav");
+        crate::write_sav(&w2, &batch, &SpssMetadata::default()).unwrap();
+
+        assert!(matches!(
+            scan_sav_many([&w1, &w2]),
+            Err(SpssError::InvalidVariable(_))
+        ));
+        assert!(scan_sav_many(Vec::<PathBuf>::new()).is_err());
+    }
+}
@@ -0,0 +1,1343 @@
+//! Options controlling how .sav/.zsav data is read.
+
+use std::sync::Arc;
+
+use arrow::datatypes::DataType;
+use rayon::ThreadPool;
+
+use crate::error::{Result, SpssError};
+
+/// What to do with values declared as user-missing (`MISSING VALUES`).
+///
+/// System-missing values are always null.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingPolicy {
+    /// Return user-missing codes as ordinary values.
+    #[default]
+    Keep,
+    /// Return user-missing values as nulls.
+    Null,
+}
+
+/// What to do when the data section ends before the declared number of
+/// cases, or in the middle of a case (an interrupted transfer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TruncationPolicy {
+    /// Fail with `SpssError::TruncatedFile` when a case is cut short.
+    #[default]
+    Fail,
+    /// Return the cases decoded before the cut and record it as a warning
+    /// (see `SavScanner::truncation`).
+    Partial,
+}
+
+/// What to do with a compressed case cut short by an end-of-data code: a
+/// damaged opcode, after which the rest of the data is out of step.
+///
+/// Under `Null` and `Skip` decoding picks up at the next control block,
+/// and each such case is reported in `SpssMetadata::warnings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BadRowPolicy {
+    /// Take the code at its word: the data ends there.
+    #[default]
+    Stop,
+    /// Fail with `SpssError::ShortCase`.
+    Fail,
+    /// Return the case with every value missing: numbers null, strings
+    /// blank.
+    Null,
+    /// Leave the case out.
+    Skip,
+}
+
+/// Arrow type used for string columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StringType {
+    Utf8,
+    LargeUtf8,
+    #[default]
+    Utf8View,
+}
+
+impl StringType {
+    pub fn data_type(self) -> DataType {
+        match self {
+            StringType::Utf8 => DataType::Utf8,
+            StringType::LargeUtf8 => DataType::LargeUtf8,
+            StringType::Utf8View => DataType::Utf8View,
+        }
+    }
+}
+
+/// How date, datetime, and time variables are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TemporalMode {
+    /// Date32, Timestamp(µs), and Duration(µs) columns.
+    #[default]
+    Arrow,
+    /// The stored SPSS values (seconds since 1582-10-14, or elapsed
+    /// seconds for times) as Float64.
+    Raw,
+}
+
+/// How numeric variables with value labels are returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelMode {
+    /// The stored codes, as Float64.
+    #[default]
+    Codes,
+    /// `Dictionary(Int32, Utf8)` columns. The dictionary holds the
+    /// variable's labels in the order they are defined, followed by the
+    /// formatted value of any unlabelled code found in the batch.
+    Dictionary,
+    /// String columns holding each value's label (see `Unlabelled` for
+    /// values without one), of the configured `StringType`.
+    Labels,
+}
+
+/// What `LabelMode::Dictionary` and `LabelMode::Labels` do with values that
+/// have no label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unlabelled {
+    /// Use the formatted number (`"4"`, `"2.5"`).
+    #[default]
+    Keep,
+    /// Return them as nulls.
+    Null,
+}
+
+/// Which numeric columns are returned as Float32 instead of Float64.
+///
+/// Halves the memory of the returned batches; values keep about 7
+/// significant digits. Date/time and converted (labelled, integer, Boolean)
+/// columns are unaffected.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Float32Mode {
+    #[default]
+    Off,
+    All,
+    /// Only the named columns.
+    Columns(Vec<String>),
+}
+
+impl Float32Mode {
+    pub(crate) fn applies_to(&self, name: &str) -> bool {
+        match self {
+            Float32Mode::Off => false,
+            Float32Mode::All => true,
+            Float32Mode::Columns(columns) => columns.iter().any(|c| c == name),
+        }
+    }
+}
+
+/// Bounds on what a file's dictionary may ask the reader to allocate, for
+/// files from untrusted sources (see `read_sav_from_bytes`). A file beyond
+/// them fails with `SpssError::LimitExceeded`.
+///
+/// The default sets no bounds: counts and lengths are still checked, but
+/// a record may be as large as the file says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLimits {
+    /// Variable records (one per 8-byte slot of a case).
+    pub max_columns: usize,
+    /// Width of a string variable, in bytes.
+    pub max_string_width: usize,
+    /// Bytes held by one dictionary record: a label, a document, an info
+    /// record's payload.
+    pub max_record_bytes: usize,
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        ReadLimits {
+            max_columns: usize::MAX,
+            max_string_width: usize::MAX,
+            max_record_bytes: usize::MAX,
+        }
+    }
+}
+
+impl ReadLimits {
+    /// The limits `read_sav_from_bytes` applies: 100,000 slots, strings of
+    /// up to 32,767 bytes (the SPSS maximum), records of up to 16 MiB.
+    pub fn untrusted() -> Self {
+        ReadLimits {
+            max_columns: 100_000,
+            max_string_width: 32_767,
+            max_record_bytes: 16 << 20,
+        }
+    }
+
+    pub fn max_columns(mut self, n: usize) -> Self {
+        self.max_columns = n;
+        self
+    }
+
+    pub fn max_string_width(mut self, n: usize) -> Self {
+        self.max_string_width = n;
+        self
+    }
+
+    pub fn max_record_bytes(mut self, n: usize) -> Self {
+        self.max_record_bytes = n;
+        self
+    }
+}
+
+/// Options for `scan_sav_with` / `SavScanner::open_with`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::{MissingPolicy, ScanOptions};
+///
+/// let opts = ScanOptions::new()
+///     .columns(&["id", "q1", "q2"])
+///     .offset(1_000)
+///     .limit(500)
+///     .missing(MissingPolicy::Null);
+/// let mut scanner = ambers::scan_sav_with("survey.sav", &opts).unwrap();
+/// let batch = scanner.collect_single().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Rows per batch returned by `next_batch()`.
+    pub batch_size: usize,
+    /// Columns to read, by name (all columns when `None`).
+    pub columns: Option<Vec<String>>,
+    /// Maximum number of rows to return.
+    pub limit: Option<usize>,
+    /// Number of leading rows to skip.
+    pub offset: usize,
+    pub missing: MissingPolicy,
+    pub string_type: StringType,
+    pub temporal: TemporalMode,
+    pub labels: LabelMode,
+    pub unlabelled: Unlabelled,
+    /// Return whole-number variables (F and N formats with no decimals) as
+    /// Int32 (up to 9 digits wide) or Int64 instead of Float64. Reading
+    /// fails if such a variable holds a fractional value.
+    pub integers: bool,
+    /// Add a `<name>_missing` column after each numeric column (and each
+    /// string column with user-missing values) saying why a cell is
+    /// missing: `"system-missing"`, or the user-missing value or range it
+    /// matched. Cells that are not missing are null.
+    pub missing_reasons: bool,
+    /// Return dichotomies as Boolean: members of multiple-dichotomy sets
+    /// (true where the set's counted value is), and numeric variables whose
+    /// value labels are exactly the codes 0 and 1 (reading fails if such a
+    /// variable holds another value). Takes effect after `labels`.
+    pub booleans: bool,
+    pub float32: Float32Mode,
+    /// Prepend a UInt64 column with this name holding each row's 1-based
+    /// case number in the file (SPSS `$CASENUM`), which survives row
+    /// filters, sampling and offsets.
+    pub case_numbers: Option<String>,
+    /// Rename columns to names that are safe for SQL engines and Parquet
+    /// and unique (see `arrow_convert::sanitized_names`). The renames are
+    /// reported in `SpssMetadata::renamed_columns`; `columns` and other
+    /// options still take the names as stored in the file.
+    pub sanitize_names: bool,
+    /// Name variables that have no long name by their short name as stored
+    /// in the file, e.g. `q1`, rather than upper-cased (`Q1`) as SPSS shows
+    /// it. Applies to the metadata too, and `columns` and other options
+    /// take these names.
+    pub names_as_stored: bool,
+    /// Columns returned as RunEndEncoded arrays (Int32 run ends): each run
+    /// of equal values, nulls included, is stored once. Meant for weight,
+    /// stratum and similar columns that are constant or nearly so; a
+    /// column that changes on most rows takes more memory this way.
+    pub run_end_encoded: Vec<String>,
+    /// Keep string values padded with spaces to their declared width, as
+    /// stored in the file, instead of trimming trailing blanks.
+    pub keep_trailing_spaces: bool,
+    /// Return empty and all-blank string values as null rather than `""`.
+    pub blank_as_null: bool,
+    /// Fail with `SpssError::Encoding`, naming the variable and case, on
+    /// string values that are not valid in their encoding, instead of
+    /// replacing the bad bytes with U+FFFD. Dictionary text that does not
+    /// decode (see `WarningKind::LossyText`) fails when the file is opened.
+    pub strict_encoding: bool,
+    /// Normalize text to Unicode NFC: string values, and the file label,
+    /// variable labels, value labels, string missing values and multiple
+    /// response set labels. Files written on macOS often hold decomposed
+    /// (NFD) text, which does not compare equal to the same text composed.
+    /// Requires the `nfc` feature.
+    #[cfg(feature = "nfc")]
+    pub normalize_nfc: bool,
+    /// String variables decoded in another encoding than the file's, as
+    /// `(variable, encoding label)` pairs such as `("city", "windows-1251")`,
+    /// for files that mix encodings between variables. Only the values are
+    /// affected, not the dictionary text.
+    pub encodings: Vec<(String, String)>,
+    /// Threads used for parallel decoding (rayon's global pool when `None`).
+    pub threads: Option<usize>,
+    /// Profile the columns of every batch read (see
+    /// `SavScanner::statistics`).
+    pub statistics: bool,
+    pub on_truncation: TruncationPolicy,
+    pub bad_rows: BadRowPolicy,
+    /// Fail on problems that are otherwise repaired or reported as
+    /// warnings: long variable names that clash (see
+    /// `SpssMetadata::renamed_variables`), print formats of unknown type
+    /// (`SpssMetadata::unknown_formats`), and, on reaching the end of the
+    /// data, a case count other than the declared one (unless the data is
+    /// short and `on_truncation` is `Partial`).
+    pub strict: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            batch_size: 100_000,
+            columns: None,
+            limit: None,
+            offset: 0,
+            missing: MissingPolicy::Keep,
+            string_type: StringType::Utf8View,
+            temporal: TemporalMode::Arrow,
+            labels: LabelMode::Codes,
+            unlabelled: Unlabelled::Keep,
+            integers: false,
+            missing_reasons: false,
+            booleans: false,
+            float32: Float32Mode::Off,
+            case_numbers: None,
+            sanitize_names: false,
+            names_as_stored: false,
+            run_end_encoded: Vec::new(),
+            keep_trailing_spaces: false,
+            blank_as_null: false,
+            strict_encoding: false,
+            #[cfg(feature = "nfc")]
+            normalize_nfc: false,
+            encodings: Vec::new(),
+            threads: None,
+            statistics: false,
+            on_truncation: TruncationPolicy::Fail,
+            bad_rows: BadRowPolicy::Stop,
+            strict: false,
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn batch_size(mut self, n: usize) -> Self {
+        self.batch_size = n;
+        self
+    }
+
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    pub fn offset(mut self, n: usize) -> Self {
+        self.offset = n;
+        self
+    }
+
+    pub fn missing(mut self, policy: MissingPolicy) -> Self {
+        self.missing = policy;
+        self
+    }
+
+    pub fn string_type(mut self, string_type: StringType) -> Self {
+        self.string_type = string_type;
+        self
+    }
+
+    pub fn temporal(mut self, mode: TemporalMode) -> Self {
+        self.temporal = mode;
+        self
+    }
+
+    pub fn labels(mut self, mode: LabelMode) -> Self {
+        self.labels = mode;
+        self
+    }
+
+    pub fn unlabelled(mut self, policy: Unlabelled) -> Self {
+        self.unlabelled = policy;
+        self
+    }
+
+    pub fn integers(mut self, yes: bool) -> Self {
+        self.integers = yes;
+        self
+    }
+
+    pub fn missing_reasons(mut self, yes: bool) -> Self {
+        self.missing_reasons = yes;
+        self
+    }
+
+    pub fn booleans(mut self, yes: bool) -> Self {
+        self.booleans = yes;
+        self
+    }
+
+    pub fn float32(mut self, mode: Float32Mode) -> Self {
+        self.float32 = mode;
+        self
+    }
+
+    pub fn case_numbers(mut self, name: &str) -> Self {
+        self.case_numbers = Some(name.to_string());
+        self
+    }
+
+    pub fn sanitize_names(mut self, yes: bool) -> Self {
+        self.sanitize_names = yes;
+        self
+    }
+
+    pub fn names_as_stored(mut self, yes: bool) -> Self {
+        self.names_as_stored = yes;
+        self
+    }
+
+    pub fn run_end_encoded(mut self, columns: &[&str]) -> Self {
+        self.run_end_encoded = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn keep_trailing_spaces(mut self, yes: bool) -> Self {
+        self.keep_trailing_spaces = yes;
+        self
+    }
+
+    pub fn blank_as_null(mut self, yes: bool) -> Self {
+        self.blank_as_null = yes;
+        self
+    }
+
+    pub fn strict_encoding(mut self, yes: bool) -> Self {
+        self.strict_encoding = yes;
+        self
+    }
+
+    #[cfg(feature = "nfc")]
+    pub fn normalize_nfc(mut self, yes: bool) -> Self {
+        self.normalize_nfc = yes;
+        self
+    }
+
+    /// Decode the values of string variable `column` as `encoding` (a
+    /// WHATWG label such as `"windows-1251"`); see `encodings`.
+    pub fn encoding(mut self, column: &str, encoding: &str) -> Self {
+        self.encodings.push((column.to_string(), encoding.to_string()));
+        self
+    }
+
+    pub fn threads(mut self, n: usize) -> Self {
+        self.threads = Some(n);
+        self
+    }
+
+    pub fn statistics(mut self, yes: bool) -> Self {
+        self.statistics = yes;
+        self
+    }
+
+    pub fn on_truncation(mut self, policy: TruncationPolicy) -> Self {
+        self.on_truncation = policy;
+        self
+    }
+
+    pub fn bad_rows(mut self, policy: BadRowPolicy) -> Self {
+        self.bad_rows = policy;
+        self
+    }
+
+    pub fn strict(mut self, yes: bool) -> Self {
+        self.strict = yes;
+        self
+    }
+
+    /// The settings that shape the output batches.
+    pub(crate) fn output(&self) -> Result<OutputOptions> {
+        let pool = match self.threads {
+            Some(n) => Some(Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(n)
+                    .build()
+                    .map_err(|e| SpssError::Unsupported(format!("thread pool: {e}")))?,
+            )),
+            None => None,
+        };
+        Ok(OutputOptions {
+            missing: self.missing,
+            string_type: self.string_type,
+            temporal: self.temporal,
+            labels: self.labels,
+            unlabelled: self.unlabelled,
+            integers: self.integers,
+            missing_reasons: self.missing_reasons,
+            booleans: self.booleans,
+            float32: self.float32.clone(),
+            case_numbers: self.case_numbers.clone(),
+            column_names: None,
+            run_end_encoded: self.run_end_encoded.clone(),
+            keep_trailing_spaces: self.keep_trailing_spaces,
+            blank_as_null: self.blank_as_null,
+            strict_encoding: self.strict_encoding,
+            #[cfg(feature = "nfc")]
+            normalize_nfc: self.normalize_nfc,
+            source_path: None,
+            pool,
+        })
+    }
+}
+
+/// Per-scanner output settings handed to the batch builders.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OutputOptions {
+    pub missing: MissingPolicy,
+    pub string_type: StringType,
+    pub temporal: TemporalMode,
+    pub labels: LabelMode,
+    pub unlabelled: Unlabelled,
+    pub integers: bool,
+    pub missing_reasons: bool,
+    pub booleans: bool,
+    pub float32: Float32Mode,
+    pub case_numbers: Option<String>,
+    /// Output name of every variable in the dictionary, by index, when
+    /// they differ from the stored names (`sanitize_names`).
+    pub column_names: Option<Vec<String>>,
+    pub run_end_encoded: Vec<String>,
+    pub keep_trailing_spaces: bool,
+    pub blank_as_null: bool,
+    pub strict_encoding: bool,
+    #[cfg(feature = "nfc")]
+    pub normalize_nfc: bool,
+    /// Where the data was opened from, for the schema metadata.
+    pub source_path: Option<String>,
+    pub pool: Option<Arc<ThreadPool>>,
+}
+
+/// Run `f` on `pool`, or on rayon's global pool when there is none.
+pub(crate) fn in_pool<T: Send>(pool: Option<&ThreadPool>, f: impl FnOnce() -> T + Send) -> T {
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow::array::{Array, ArrayRef, AsArray, Date32Array, Float64Array, StringArray};
+    use arrow::compute::cast;
+    use arrow::datatypes::{Field, Float64Type, Schema};
+    use arrow::record_batch::RecordBatch;
+
+    use super::*;
+    use crate::metadata::{MissingSpec, MrSet, MrType, SpssMetadata, Value};
+    use crate::scanner::SavScanner;
+    use crate::test_util;
+    use crate::writer::WriteOptions;
+
+    fn sample_sav() -> Vec<u8> {
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("id", Arc::new(Float64Array::from_iter_values((0..10).map(f64::from)))),
+            (
+                "answer",
+                Arc::new(Float64Array::from_iter_values(
+                    (0..10).map(|i| if i % 3 == 0 { 99.0 } else { f64::from(i) }),
+                )),
+            ),
+            (
+                "city",
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|i| if i % 2 == 0 { "NA" } else { "Oslo" }),
+                )),
+            ),
+            ("born", Arc::new(Date32Array::from_iter_values((0..10).map(|i| 19_000 + i)))),
+        ];
+        let mut meta = SpssMetadata::default();
+        meta.variable_missing
+            .insert("answer".into(), vec![MissingSpec::Value(99.0)]);
+        meta.variable_missing
+            .insert("city".into(), vec![MissingSpec::StringValue("NA".into())]);
+        meta.spss_variable_types.insert("id".into(), "F8.0".into());
+        meta.spss_variable_types
+            .insert("answer".into(), "F12.0".into());
+        meta.variable_value_labels.insert(
+            "answer".into(),
+            [(1.0, "one"), (2.0, "two"), (99.0, "refused")]
+                .into_iter()
+                .map(|(v, l)| (Value::Numeric(v), l.to_string()))
+                .collect(),
+        );
+        test_util::sav_bytes_with(columns, &meta, &WriteOptions::default())
+    }
+
+    #[test]
+    fn test_scan_options_defaults_match_open() {
+        let bytes = sample_sav();
+        let plain = SavScanner::open(Cursor::new(bytes.clone()), 100)
+            .unwrap()
+            .collect_single()
+            .unwrap();
+        let with = SavScanner::open_with(Cursor::new(bytes), &ScanOptions::new())
+            .unwrap()
+            .collect_single()
+            .unwrap();
+        assert_eq!(plain, with);
+    }
+
+    #[test]
+    fn test_scan_options_applied() {
+        let opts = ScanOptions::new()
+            .columns(&["answer", "city", "born"])
+            .offset(2)
+            .limit(5)
+            .batch_size(2)
+            .missing(MissingPolicy::Null)
+            .string_type(StringType::Utf8)
+            .temporal(TemporalMode::Raw)
+            .threads(2);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        let schema = scanner.schema();
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(2).data_type(), &DataType::Float64);
+
+        let batches = scanner.collect_all().unwrap();
+        assert_eq!(batches.len(), 3);
+        let all = arrow::compute::concat_batches(&Arc::new(schema), &batches).unwrap();
+        assert_eq!(all.num_rows(), 5);
+
+        // Rows 2..7: answer 99 at rows 3 and 6, city "NA" at rows 2, 4, 6.
+        let answer = all
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        let nulls: Vec<bool> = (0..5).map(|i| answer.is_null(i)).collect();
+        assert_eq!(nulls, vec![false, true, false, false, true]);
+        assert_eq!(answer.value(0), 2.0);
+        let city = all
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let nulls: Vec<bool> = (0..5).map(|i| city.is_null(i)).collect();
+        assert_eq!(nulls, vec![true, false, true, false, true]);
+        // Raw temporal values are SPSS seconds.
+        let born = all
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(born.value(1) - born.value(0), 86_400.0);
+    }
+
+    #[test]
+    fn test_label_mode_dictionary() {
+        let opts = ScanOptions::new()
+            .columns(&["id", "answer"])
+            .labels(LabelMode::Dictionary)
+            .missing(MissingPolicy::Null);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        assert_eq!(scanner.schema().field(0).data_type(), &DataType::Float64);
+        assert_eq!(scanner.schema().field(1).data_type(), &dict_type);
+
+        let batch = scanner.collect_single().unwrap();
+        let answer = batch
+            .column(1)
+            .as_dictionary::<arrow::datatypes::Int32Type>();
+        let labels = answer.values().as_string::<i32>();
+        let decoded: Vec<Option<&str>> = answer
+            .keys()
+            .iter()
+            .map(|k| k.map(|k| labels.value(k as usize)))
+            .collect();
+        // 99 is user-missing, so null rather than "refused".
+        assert_eq!(
+            decoded,
+            vec![
+                None,
+                Some("one"),
+                Some("two"),
+                None,
+                Some("4"),
+                Some("5"),
+                None,
+                Some("7"),
+                Some("8"),
+                None
+            ]
+        );
+        assert_eq!(labels.value(0), "one");
+        assert_eq!(labels.value(2), "refused");
+    }
+
+    #[test]
+    fn test_label_mode_labels() {
+        let opts = ScanOptions::new()
+            .columns(&["answer"])
+            .labels(LabelMode::Labels)
+            .unlabelled(Unlabelled::Null)
+            .string_type(StringType::Utf8);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        assert_eq!(scanner.schema().field(0).data_type(), &DataType::Utf8);
+        let batch = scanner.collect_single().unwrap();
+        let answer = batch.column(0).as_string::<i32>();
+        let got: Vec<Option<&str>> = answer.iter().take(5).collect();
+        assert_eq!(
+            got,
+            vec![
+                Some("refused"),
+                Some("one"),
+                Some("two"),
+                Some("refused"),
+                None
+            ]
+        );
+
+        let opts = ScanOptions::new().labels(LabelMode::Labels);
+        let batch = SavScanner::open_with(Cursor::new(sample_sav()), &opts)
+            .unwrap()
+            .collect_single()
+            .unwrap();
+        // Unlabelled values keep their number; unlabelled columns are unchanged.
+        assert_eq!(batch.column(1).as_string_view().value(4), "4");
+        assert_eq!(batch.column(0).data_type(), &DataType::Float64);
+    }
+
+    #[test]
+    fn test_integer_downcasting() {
+        let opts = ScanOptions::new()
+            .columns(&["id", "answer", "born"])
+            .integers(true)
+            .missing(MissingPolicy::Null);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        let schema = scanner.schema();
+        assert_eq!(schema.field(0).data_type(), &DataType::Int32);
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(2).data_type(), &DataType::Date32);
+        let batch = scanner.collect_single().unwrap();
+        let id = batch
+            .column(0)
+            .as_primitive::<arrow::datatypes::Int32Type>();
+        assert_eq!(id.value(9), 9);
+        let answer = batch
+            .column(1)
+            .as_primitive::<arrow::datatypes::Int64Type>();
+        assert!(answer.is_null(0));
+        assert_eq!(answer.value(1), 1);
+
+        // A fractional value in a whole-number format is an error.
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.0, 2.5]))])
+                .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.spss_variable_types.insert("x".into(), "F3.0".into());
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+        let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts.columns(&["x"])).unwrap();
+        assert!(matches!(
+            scanner.collect_single(),
+            Err(SpssError::InvalidVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.sav");
+        std::fs::write(&path, sample_sav()).unwrap();
+        for string_type in [
+            StringType::Utf8,
+            StringType::LargeUtf8,
+            StringType::Utf8View,
+        ] {
+            let opts = ScanOptions::new().string_type(string_type);
+            let (batch, _) = crate::read_sav_with(&path, &opts).unwrap();
+            assert_eq!(batch.column(2).data_type(), &string_type.data_type());
+            let city = cast(batch.column(2), &DataType::Utf8).unwrap();
+            assert_eq!(city.as_string::<i32>().value(1), "Oslo");
+        }
+    }
+
+    #[test]
+    fn test_missing_reasons() {
+        let opts = ScanOptions::new()
+            .columns(&["id", "answer", "city"])
+            .missing(MissingPolicy::Null)
+            .missing_reasons(true);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        let names: Vec<String> = scanner
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "id",
+                "id_missing",
+                "answer",
+                "answer_missing",
+                "city",
+                "city_missing"
+            ]
+        );
+        let batch = scanner.collect_single().unwrap();
+        assert_eq!(batch.schema(), Arc::new(scanner.schema()));
+        let reasons = |i: usize| -> Vec<Option<String>> {
+            let dict = batch
+                .column(i)
+                .as_dictionary::<arrow::datatypes::Int8Type>();
+            let values = dict.values().as_string::<i32>();
+            dict.keys()
+                .iter()
+                .take(4)
+                .map(|k| k.map(|k| values.value(k as usize).to_string()))
+                .collect()
+        };
+        assert_eq!(batch.column(1).null_count(), 10);
+        assert_eq!(
+            reasons(3),
+            [Some("99".into()), None, None, Some("99".into())]
+        );
+        assert!(batch.column(2).is_null(0));
+        assert_eq!(
+            reasons(5),
+            [Some("NA".into()), None, Some("NA".into()), None]
+        );
+
+        // System-missing cells
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Float64, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Float64Array::from(vec![Some(1.0), None]))],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        let batch =
+            SavScanner::open_with(Cursor::new(buf), &ScanOptions::new().missing_reasons(true))
+                .unwrap()
+                .collect_single()
+                .unwrap();
+        let dict = batch
+            .column(1)
+            .as_dictionary::<arrow::datatypes::Int8Type>();
+        assert!(dict.is_null(0));
+        let key = dict.keys().value(1) as usize;
+        assert_eq!(
+            dict.values().as_string::<i32>().value(key),
+            "system-missing"
+        );
+    }
+
+    #[test]
+    fn test_booleans() {
+        let codes = |v: Vec<Option<f64>>| -> ArrayRef { Arc::new(Float64Array::from(v)) };
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1_1", DataType::Float64, true),
+            Field::new("q1_2", DataType::Float64, true),
+            Field::new("yn", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                codes(vec![Some(1.0), Some(0.0), None]),
+                codes(vec![Some(2.0), Some(1.0), Some(0.0)]),
+                codes(vec![Some(1.0), Some(0.0), Some(1.0)]),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.mr_sets.insert(
+            "grid".into(),
+            MrSet {
+                name: "grid".into(),
+                label: "Grid".into(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".into()),
+                variables: vec!["q1_1".into(), "q1_2".into()],
+            },
+        );
+        meta.variable_value_labels.insert(
+            "yn".into(),
+            [(1.0, "Yes"), (0.0, "No")]
+                .into_iter()
+                .map(|(v, l)| (Value::Numeric(v), l.to_string()))
+                .collect(),
+        );
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+
+        let opts = ScanOptions::new().booleans(true);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf.clone()), &opts).unwrap();
+        assert!(
+            scanner
+                .schema()
+                .fields()
+                .iter()
+                .all(|f| f.data_type() == &DataType::Boolean)
+        );
+        let batch = scanner.collect_single().unwrap();
+        let values = |i: usize| batch.column(i).as_boolean().iter().collect::<Vec<_>>();
+        assert_eq!(values(0), [Some(true), Some(false), None]);
+        assert_eq!(values(1), [Some(false), Some(true), Some(false)]);
+        assert_eq!(values(2), [Some(true), Some(false), Some(true)]);
+
+        // Labels take precedence over booleans.
+        let opts = opts.labels(LabelMode::Labels);
+        let batch = SavScanner::open_with(Cursor::new(buf), &opts)
+            .unwrap()
+            .collect_single()
+            .unwrap();
+        assert_eq!(batch.column(2).as_string_view().value(0), "Yes");
+        assert_eq!(batch.column(0).data_type(), &DataType::Boolean);
+    }
+
+    #[test]
+    fn test_float32() {
+        let opts = ScanOptions::new().float32(Float32Mode::All);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        let schema = scanner.schema();
+        assert_eq!(schema.field(0).data_type(), &DataType::Float32);
+        assert_eq!(schema.field(3).data_type(), &DataType::Date32);
+        let batch = scanner.collect_single().unwrap();
+        let id = batch
+            .column(0)
+            .as_primitive::<arrow::datatypes::Float32Type>();
+        assert_eq!(id.value(9), 9.0);
+
+        // Per column; converted columns keep their converted type.
+        let opts = ScanOptions::new()
+            .float32(Float32Mode::Columns(vec!["answer".into()]))
+            .integers(true);
+        let scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        assert_eq!(scanner.schema().field(0).data_type(), &DataType::Int32);
+        assert_eq!(scanner.schema().field(1).data_type(), &DataType::Int64);
+        let opts = ScanOptions::new().float32(Float32Mode::Columns(vec!["answer".into()]));
+        let scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        assert_eq!(scanner.schema().field(0).data_type(), &DataType::Float64);
+        assert_eq!(scanner.schema().field(1).data_type(), &DataType::Float32);
+
+        let opts = ScanOptions::new().float32(Float32Mode::Columns(vec!["nope".into()]));
+        assert!(matches!(
+            SavScanner::open_with(Cursor::new(sample_sav()), &opts),
+            Err(SpssError::InvalidVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_case_numbers() {
+        let sample = sample_sav();
+        let (batch, meta) = crate::read_sav_from_reader(Cursor::new(sample.clone())).unwrap();
+        let mut compressed = Vec::new();
+        let write = crate::WriteOptions {
+            compression: crate::Compression::Bytecode,
+            ..crate::WriteOptions::default()
+        };
+        crate::write_sav_to_writer_with(&mut compressed, &batch, &meta, &write).unwrap();
+
+        let opts = ScanOptions::new()
+            .columns(&["id"])
+            .case_numbers("case")
+            .offset(2)
+            .batch_size(3);
+        for buf in [sample, compressed] {
+            let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts).unwrap();
+            scanner.filter(crate::Predicate::gt("id", 4.0)).unwrap();
+            assert_eq!(scanner.schema().field(0).name(), "case");
+            assert_eq!(scanner.schema().field(0).data_type(), &DataType::UInt64);
+            let batches = scanner.collect_all().unwrap();
+            let cases: Vec<u64> = batches
+                .iter()
+                .flat_map(|b| {
+                    b.column(0)
+                        .as_primitive::<arrow::datatypes::UInt64Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect();
+            assert_eq!(cases, [6, 7, 8, 9, 10]);
+        }
+
+        let opts = ScanOptions::new().case_numbers("id");
+        assert!(matches!(
+            SavScanner::open_with(Cursor::new(sample_sav()), &opts),
+            Err(SpssError::InvalidVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_sanitize_names() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("größe", DataType::Float64, true),
+            Field::new("q.1", DataType::Float64, true),
+            Field::new("q_1", DataType::Float64, true),
+        ]));
+        let column = || -> ArrayRef { Arc::new(Float64Array::from(vec![1.0])) };
+        let batch = RecordBatch::try_new(schema, vec![column(), column(), column()]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+
+        let opts = ScanOptions::new()
+            .sanitize_names(true)
+            .columns(&["q_1", "größe"])
+            .missing_reasons(true);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts).unwrap();
+        let names: Vec<String> = scanner
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            ["q_1_2", "q_1_2_missing", "grosse", "grosse_missing"]
+        );
+        let renamed = &scanner.metadata().renamed_columns;
+        assert_eq!(renamed.len(), 3);
+        assert_eq!(renamed["grosse"], "größe");
+        assert_eq!(renamed["q_1"], "q.1");
+        assert_eq!(renamed["q_1_2"], "q_1");
+        assert_eq!(scanner.collect_single().unwrap().num_columns(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "nfc")]
+    fn test_normalize_nfc() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let city = StringArray::from(vec!["Cafe\u{301}", "Oslo"]);
+        let columns: Vec<ArrayRef> =
+            vec![Arc::new(Float64Array::from(vec![1.0, 2.0])), Arc::new(city)];
+        let batch = RecordBatch::try_new(schema, columns).unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels.insert("q1".into(), "Re\u{301}sume\u{301}".into());
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Cre\u{300}me".to_string())].into_iter().collect(),
+        );
+        // Two values that are distinct as stored but equal once composed
+        meta.variable_value_labels.insert(
+            "city".into(),
+            [("Cafe\u{301}", "decomposed"), ("Café", "composed"), ("Oslo", "capital")]
+                .into_iter()
+                .map(|(v, l)| (Value::String(v.into()), l.to_string()))
+                .collect(),
+        );
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+
+        let scanner = SavScanner::open(Cursor::new(buf.clone()), 10).unwrap();
+        assert_eq!(scanner.metadata().label("q1"), Some("Re\u{301}sume\u{301}"));
+        assert_eq!(scanner.metadata().variable_value_labels["city"].len(), 3);
+        assert!(scanner.metadata().warnings.is_empty());
+
+        let opts = ScanOptions::new().normalize_nfc(true).labels(LabelMode::Labels);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts).unwrap();
+        assert_eq!(scanner.metadata().label("q1"), Some("Résumé"));
+        let city_labels = &scanner.metadata().variable_value_labels["city"];
+        assert_eq!(city_labels.len(), 2);
+        assert_eq!(city_labels[&Value::String("Café".into())], "decomposed");
+        let warnings = &scanner.metadata().warnings;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, crate::warning::WarningKind::MergedValueLabels);
+        assert_eq!(warnings[0].variable.as_deref(), Some("city"));
+        assert!(warnings[0].message.ends_with("dropped \"composed\""), "{}", warnings[0].message);
+        let batch = scanner.collect_single().unwrap();
+        let q1 = cast(batch.column(0), &DataType::Utf8).unwrap();
+        assert_eq!(q1.as_string::<i32>().value(0), "Crème");
+        let city = batch.column(1).as_string_view();
+        assert_eq!(city.value(0), "Café");
+        assert_eq!(city.value(1), "Oslo");
+    }
+
+    #[test]
+    fn test_names_as_stored() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("City", DataType::Utf8, true),
+        ]));
+        let city = StringArray::from(vec!["x".repeat(300)]);
+        let columns: Vec<ArrayRef> = vec![Arc::new(Float64Array::from(vec![1.0])), Arc::new(city)];
+        let batch = RecordBatch::try_new(schema, columns).unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels.insert("q1".into(), "First".into());
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+        // Short names stored in lower case, and q1 left without a long name
+        for (from, to) in [(&b"Q1      "[..], &b"q1      "[..]), (b"CITY    ", b"city    ")] {
+            let at = buf.windows(8).position(|w| w == from).unwrap();
+            buf[at..at + 8].copy_from_slice(to);
+        }
+        let at = buf.windows(5).position(|w| w == b"Q1=q1").unwrap();
+        buf[at..at + 2].copy_from_slice(b"XX");
+
+        // The long name and the very long string width still apply to city
+        let scanner = SavScanner::open(Cursor::new(buf.clone()), 10).unwrap();
+        let meta = scanner.metadata();
+        assert_eq!(meta.variable_names, ["Q1", "City"]);
+        assert_eq!(meta.variable_storage_width["City"], 300);
+        assert_eq!(meta.label("Q1"), Some("First"));
+
+        let opts = ScanOptions::new().names_as_stored(true).columns(&["q1"]);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts).unwrap();
+        assert_eq!(scanner.metadata().variable_names, ["q1", "City"]);
+        assert_eq!(scanner.metadata().label("q1"), Some("First"));
+        let batch = scanner.collect_single().unwrap();
+        assert_eq!(batch.schema().field(0).name(), "q1");
+    }
+
+    #[test]
+    fn test_duplicate_long_names() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("Score_a", DataType::Float64, true),
+            Field::new("Score_b", DataType::Float64, true),
+        ]));
+        let column = || -> ArrayRef { Arc::new(Float64Array::from(vec![1.0])) };
+        let batch = RecordBatch::try_new(schema, vec![column(), column()]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // Map both short names to the first long name.
+        let at = buf
+            .windows(15)
+            .position(|w| w == b"SCORE_B=Score_b")
+            .unwrap();
+        buf[at + 14] = b'a';
+
+        let scanner = SavScanner::open(Cursor::new(buf.clone()), 10).unwrap();
+        assert_eq!(scanner.metadata().variable_names, ["Score_a", "SCORE_B"]);
+        let renamed = &scanner.metadata().renamed_variables;
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed["SCORE_B"], "Score_a");
+
+        let opts = ScanOptions::new().strict(true);
+        let err = SavScanner::open_with(Cursor::new(buf), &opts).err().unwrap();
+        assert!(err.to_string().contains("\"Score_a\""), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        let schema = Arc::new(Schema::new(vec![Field::new("q1", DataType::Float64, true)]));
+        let column: ArrayRef = Arc::new(Float64Array::from(vec![1.5]));
+        let batch = RecordBatch::try_new(schema, vec![column]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // The print format precedes the write format and the short name.
+        let at = buf.windows(8).position(|w| w == b"Q1      ").unwrap() - 8;
+        buf[at..at + 4].copy_from_slice(&0x63_0A03_i32.to_le_bytes());
+
+        let mut scanner = SavScanner::open(Cursor::new(buf.clone()), 10).unwrap();
+        assert_eq!(scanner.metadata().format("q1"), Some("F10.3"));
+        assert_eq!(scanner.metadata().unknown_formats["q1"], 0x63_0A03);
+        let q1 = scanner.collect_single().unwrap();
+        assert_eq!(q1.column(0).as_primitive::<Float64Type>().value(0), 1.5);
+
+        let opts = ScanOptions::new().strict(true);
+        let err = SavScanner::open_with(Cursor::new(buf), &opts).err().unwrap();
+        assert!(err.to_string().contains("\"q1\" has a print format of unknown type 99"));
+    }
+
+    #[test]
+    fn test_run_end_encoded() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("w", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![2.0; 5])),
+                Arc::new(StringArray::from(vec!["a", "a", "b", "b", "b"])),
+            ],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+
+        let opts = ScanOptions::new()
+            .run_end_encoded(&["w", "s"])
+            .batch_size(3);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf.clone()), &opts).unwrap();
+        assert!(matches!(
+            scanner.schema().field(0).data_type(),
+            DataType::RunEndEncoded(_, values) if values.data_type() == &DataType::Float64
+        ));
+        let batches = scanner.collect_all().unwrap();
+        let runs = |b: &RecordBatch, i: usize| {
+            b.column(i)
+                .as_run::<arrow::datatypes::Int32Type>()
+                .run_ends()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(runs(&batches[0], 0), [3]);
+        assert_eq!(runs(&batches[0], 1), [2, 3]);
+        assert_eq!(runs(&batches[1], 1), [2]);
+
+        let opts = ScanOptions::new().run_end_encoded(&["nope"]);
+        assert!(SavScanner::open_with(Cursor::new(buf), &opts).is_err());
+    }
+
+    #[test]
+    fn test_variable_encoding() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.0, 2.0])),
+                Arc::new(StringArray::from(vec!["@@@", "Kyiv"])),
+                Arc::new(StringArray::from(vec!["café", "ok"])),
+            ],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // A value pasted in from a windows-1251 file: "Мск"
+        let at = buf.windows(3).position(|w| w == b"@@@").unwrap();
+        buf[at..at + 3].copy_from_slice(&[0xCC, 0xF1, 0xEA]);
+
+        let opts = ScanOptions::new().encoding("city", "windows-1251");
+        let mut scanner = SavScanner::open_with(Cursor::new(buf.clone()), &opts).unwrap();
+        scanner.filter(crate::Predicate::eq("city", "Мск")).unwrap();
+        let batch = scanner.collect_single().unwrap();
+        let city = cast(batch.column(1), &DataType::Utf8).unwrap();
+        let note = cast(batch.column(2), &DataType::Utf8).unwrap();
+        assert_eq!(city.as_string::<i32>().value(0), "Мск");
+        assert_eq!(note.as_string::<i32>().value(0), "café");
+
+        for opts in [
+            ScanOptions::new().encoding("city", "klingon"),
+            ScanOptions::new().encoding("id", "windows-1251"),
+            ScanOptions::new().encoding("nope", "windows-1251"),
+        ] {
+            assert!(SavScanner::open_with(Cursor::new(buf.clone()), &opts).is_err());
+        }
+    }
+
+        #[test]
+    fn test_strict_encoding() {
+        let schema = Arc::new(Schema::new(vec![Field::new("city", DataType::Utf8, true)]));
+        let values: Vec<String> = (0..20_000).map(|i| format!("c{i}")).collect();
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(values))]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // Not UTF-8, which the file declares
+        let at = buf.windows(6).position(|w| w == b"c12345").unwrap();
+        buf[at + 1] = 0xFF;
+
+        let mut scanner = SavScanner::open(Cursor::new(buf.clone()), 100_000).unwrap();
+        let batch = scanner.collect_single().unwrap();
+        let city = cast(batch.column(0), &DataType::Utf8).unwrap();
+        assert_eq!(city.as_string::<i32>().value(12_345), "c\u{FFFD}2345");
+
+        let opts = ScanOptions::new().strict_encoding(true).offset(10);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf.clone()), &opts).unwrap();
+        let err = scanner.collect_single().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "encoding error: variable \"city\", case 12346: text not valid in UTF-8"
+        );
+        let opts = ScanOptions::new().strict_encoding(true).limit(100);
+        let mut scanner = SavScanner::open_with(Cursor::new(buf), &opts).unwrap();
+        assert_eq!(scanner.collect_single().unwrap().num_columns(), 1);
+    }
+
+        #[test]
+    fn test_keep_trailing_spaces() {
+        let opts = ScanOptions::new()
+            .columns(&["city"])
+            .keep_trailing_spaces(true);
+        let mut scanner = SavScanner::open_with(Cursor::new(sample_sav()), &opts).unwrap();
+        assert_eq!(scanner.metadata().format("city"), Some("A4"));
+        let batch = scanner.collect_single().unwrap();
+        let city = batch.column(0).as_string_view();
+        assert_eq!(city.value(0), "NA  ");
+        assert_eq!(city.value(1), "Oslo");
+
+        // Padded values still match their user-missing value.
+        let opts = opts.missing(MissingPolicy::Null);
+        let batch = SavScanner::open_with(Cursor::new(sample_sav()), &opts)
+            .unwrap()
+            .collect_single()
+            .unwrap();
+        assert_eq!(batch.column(0).null_count(), 5);
+    }
+
+    #[test]
+    fn test_blank_as_null() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec!["a", "", "   ", " b"]))],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+
+        for keep_trailing_spaces in [false, true] {
+            let opts = ScanOptions::new()
+                .blank_as_null(true)
+                .keep_trailing_spaces(keep_trailing_spaces)
+                .string_type(StringType::Utf8);
+            let batch = SavScanner::open_with(Cursor::new(buf.clone()), &opts)
+                .unwrap()
+                .collect_single()
+                .unwrap();
+            let s = batch.column(0).as_string::<i32>();
+            assert_eq!(s.null_count(), 2);
+            assert!(s.is_valid(0) && s.is_null(1) && s.is_null(2));
+            assert_eq!(s.value(3).trim_end(), " b");
+        }
+    }
+
+    #[test]
+    fn test_schema_metadata() {
+        let schema = Arc::new(Schema::new(vec![Field::new("w", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.5]))]).unwrap();
+        let meta = SpssMetadata {
+            file_label: "Wave 1".into(),
+            weight_variable: Some("w".into()),
+            ..SpssMetadata::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wave1.sav");
+        crate::write_sav(&path, &batch, &meta).unwrap();
+
+        let (batch, _) = crate::read_sav_with(&path, &ScanOptions::new()).unwrap();
+        let schema = batch.schema();
+        let facts = schema.metadata();
+        assert_eq!(facts["spss.file_label"], "Wave 1");
+        assert_eq!(facts["spss.encoding"], "UTF-8");
+        assert_eq!(facts["spss.compression"], "none");
+        assert_eq!(facts["spss.weight_variable"], "w");
+        assert!(facts["spss.source_path"].ends_with("wave1.sav"));
+        assert!(!facts["spss.creation_time"].is_empty());
+
+        let scanner = SavScanner::open(Cursor::new(std::fs::read(&path).unwrap()), 10).unwrap();
+        assert!(!scanner.schema().metadata().contains_key("spss.source_path"));
+    }
+}
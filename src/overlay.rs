@@ -0,0 +1,277 @@
+//! Label dictionaries shipped separately from the data, as CSV or JSON.
+//!
+//! Both forms are read into a `MetadataOverlay` of variable labels and
+//! value labels, to apply with `SpssMetadata::apply_overlay_with`. Value
+//! labels are added to a variable's existing set, value by value.
+//!
+//! CSV has a header row naming the columns `variable`, `value` and `label`
+//! (in any order). A row with an empty `value` labels the variable itself:
+//!
+//! ```text
+//! variable,value,label
+//! q1,,Owns a car
+//! q1,1,Yes
+//! q1,2,No
+//! ```
+//!
+//! JSON holds either map, or both:
+//!
+//! ```text
+//! {
+//!   "variable_labels": {"q1": "Owns a car"},
+//!   "value_labels": {"q1": {"1": "Yes", "2": "No"}}
+//! }
+//! ```
+//!
+//! Variable names are matched case-insensitively against the metadata and
+//! values are read as numbers for numeric variables. Referring to an
+//! unknown variable is an error.
+
+use std::path::Path;
+
+use serde_json::Value as Json;
+
+use crate::constants::SpssFormat;
+use crate::error::{Result, SpssError};
+use crate::metadata::{MetadataOverlay, SpssMetadata, Value};
+
+/// Read a CSV label dictionary against the variables of `metadata`.
+pub fn read_overlay_csv(
+    path: impl AsRef<Path>,
+    metadata: &SpssMetadata,
+) -> Result<MetadataOverlay> {
+    parse_overlay_csv(&std::fs::read_to_string(path)?, metadata)
+}
+
+/// Parse CSV label dictionary text against the variables of `metadata`.
+pub fn parse_overlay_csv(text: &str, metadata: &SpssMetadata) -> Result<MetadataOverlay> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = csv_records(text)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(MetadataOverlay::default());
+    };
+    let column = |title: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(title))
+            .ok_or_else(|| SpssError::Syntax {
+                line: 1,
+                message: format!("no {title} column"),
+            })
+    };
+    let (var_col, value_col, label_col) = (column("variable")?, column("value")?, column("label")?);
+
+    let mut overlay = MetadataOverlay::default();
+    for (line, record) in records {
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |i: usize| record.get(i).map(String::as_str).unwrap_or("");
+        let error = |message: String| SpssError::Syntax { line, message };
+        let name = variable(metadata, field(var_col).trim()).map_err(error)?;
+        let label = field(label_col).to_string();
+        let value = field(value_col);
+        if value.trim().is_empty() {
+            overlay.variable_labels.insert(name, label);
+        } else {
+            let value = parse_value(metadata, &name, value).map_err(error)?;
+            overlay
+                .added_value_labels
+                .entry(name)
+                .or_default()
+                .insert(value, label);
+        }
+    }
+    Ok(overlay)
+}
+
+/// Read a JSON label dictionary against the variables of `metadata`.
+pub fn read_overlay_json(
+    path: impl AsRef<Path>,
+    metadata: &SpssMetadata,
+) -> Result<MetadataOverlay> {
+    parse_overlay_json(&std::fs::read_to_string(path)?, metadata)
+}
+
+/// Parse JSON label dictionary text against the variables of `metadata`.
+pub fn parse_overlay_json(text: &str, metadata: &SpssMetadata) -> Result<MetadataOverlay> {
+    let error = SpssError::MetadataJson;
+    let doc: Json = serde_json::from_str(text).map_err(|e| error(e.to_string()))?;
+    let object = |key: &str| match doc.get(key) {
+        None => Ok(None),
+        Some(Json::Object(map)) => Ok(Some(map)),
+        Some(_) => Err(error(format!("{key} must be an object"))),
+    };
+    let string = |key: &str, value: &Json| match value {
+        Json::String(s) => Ok(s.clone()),
+        _ => Err(error(format!("label of {key} must be a string"))),
+    };
+
+    let mut overlay = MetadataOverlay::default();
+    for (name, label) in object("variable_labels")?.into_iter().flatten() {
+        let name = variable(metadata, name).map_err(error)?;
+        let label = string(&name, label)?;
+        overlay.variable_labels.insert(name, label);
+    }
+    for (name, labels) in object("value_labels")?.into_iter().flatten() {
+        let name = variable(metadata, name).map_err(error)?;
+        let Json::Object(labels) = labels else {
+            return Err(error(format!("value labels of {name} must be an object")));
+        };
+        let set = overlay.added_value_labels.entry(name.clone()).or_default();
+        for (value, label) in labels {
+            let value = parse_value(metadata, &name, value).map_err(error)?;
+            set.insert(value, string(&name, label)?);
+        }
+    }
+    Ok(overlay)
+}
+
+/// The metadata's spelling of variable `name`.
+fn variable(metadata: &SpssMetadata, name: &str) -> std::result::Result<String, String> {
+    metadata
+        .variable_names
+        .iter()
+        .find(|n| n.eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| format!("unknown variable {name:?}"))
+}
+
+/// A value of variable `name`: a number unless it is a string variable.
+fn parse_value(
+    metadata: &SpssMetadata,
+    name: &str,
+    text: &str,
+) -> std::result::Result<Value, String> {
+    let is_string = metadata
+        .format(name)
+        .and_then(SpssFormat::parse)
+        .is_some_and(|f| f.format_type.is_string());
+    if is_string {
+        return Ok(Value::String(text.trim_end().to_string()));
+    }
+    text.trim()
+        .parse()
+        .map(Value::Numeric)
+        .map_err(|_| format!("{name}: value {text:?} is not a number"))
+}
+
+/// Split CSV text into records of fields, each with its 1-based starting
+/// line. Fields may be quoted with `"`, doubling quotes inside; quoted
+/// fields may span lines.
+fn csv_records(text: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(SpssError::Syntax {
+            line: start,
+            message: "unterminated quoted field".to_string(),
+        });
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ConflictPolicy;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["Q1".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.spss_variable_types.insert("Q1".into(), "F1.0".into());
+        meta.spss_variable_types.insert("city".into(), "A20".into());
+        meta.variable_labels.insert("Q1".into(), "Car".into());
+        meta.variable_value_labels.insert(
+            "Q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta
+    }
+
+    #[test]
+    fn test_parse_overlay_csv() {
+        let meta = metadata();
+        let csv = "variable,value,label\r\nq1,,Owns a car\r\nq1,2,No\r\ncity,NY,\"New York, \"\"NY\"\"\"\r\n";
+        let overlay = parse_overlay_csv(csv, &meta).unwrap();
+        assert_eq!(overlay.variable_labels["Q1"], "Owns a car");
+        assert_eq!(overlay.added_value_labels["Q1"][&Value::Numeric(2.0)], "No");
+        assert_eq!(
+            overlay.added_value_labels["city"][&Value::String("NY".into())],
+            "New York, \"NY\""
+        );
+
+        let err = parse_overlay_csv("variable,value,label\nq1,x,Bad\n", &meta).unwrap_err();
+        assert!(matches!(err, SpssError::Syntax { line: 2, .. }));
+        assert!(parse_overlay_csv("variable,value,label\nq9,,Gone\n", &meta).is_err());
+    }
+
+    #[test]
+    fn test_parse_overlay_json_and_apply() {
+        let json = r#"{"variable_labels": {"q1": "Owns a car"},
+                       "value_labels": {"q1": {"1": "Yes!", "2": "No"}}}"#;
+        let overlay = parse_overlay_json(json, &metadata()).unwrap();
+
+        let mut meta = metadata();
+        let err = meta
+            .apply_overlay_with(&overlay, ConflictPolicy::Error)
+            .unwrap_err();
+        assert!(matches!(err, SpssError::MetadataConflict(_)));
+        assert_eq!(meta.label("Q1"), Some("Car"));
+
+        meta.apply_overlay_with(&overlay, ConflictPolicy::Existing)
+            .unwrap();
+        assert_eq!(meta.label("Q1"), Some("Car"));
+        let labels = meta.value_labels("Q1").unwrap();
+        assert_eq!(labels[&Value::Numeric(1.0)], "Yes");
+        assert_eq!(labels[&Value::Numeric(2.0)], "No");
+
+        meta.apply_overlay_with(&overlay, ConflictPolicy::Overlay)
+            .unwrap();
+        assert_eq!(meta.label("Q1"), Some("Owns a car"));
+        assert_eq!(
+            meta.value_labels("Q1").unwrap()[&Value::Numeric(1.0)],
+            "Yes!"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! The program that wrote a file, detected from the header's product string
+//! and the machine integer record (subtype 3), and the writer quirks ambers
+//! works around for it. Exposed as `SpssMetadata::writer`.
+
+use crate::info_records::integer_info::IntegerInfo;
+
+/// A program known to write .sav files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Product {
+    /// SPSS / IBM SPSS Statistics.
+    Spss,
+    /// GNU PSPP.
+    Pspp,
+    /// ReadStat, the library behind R's haven and Python's pyreadstat.
+    ReadStat,
+    /// The ambers writer.
+    Ambers,
+    /// A product string ambers does not recognize.
+    #[default]
+    Unknown,
+}
+
+impl Product {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Product::Spss => "spss",
+            Product::Pspp => "pspp",
+            Product::ReadStat => "readstat",
+            Product::Ambers => "ambers",
+            Product::Unknown => "unknown",
+        }
+    }
+}
+
+/// A writer quirk worked around while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quirk {
+    /// Variable or value labels padded with NUL bytes inside their stated
+    /// length, as C-string based writers do; the NULs were dropped.
+    NulPaddedLabels,
+    /// No encoding record (subtype 20) and no code page in the machine
+    /// integer record; the text was read as UTF-8, the only encoding the
+    /// writer produces, rather than windows-1252.
+    AssumedUtf8,
+}
+
+impl Quirk {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quirk::NulPaddedLabels => "nul_padded_labels",
+            Quirk::AssumedUtf8 => "assumed_utf8",
+        }
+    }
+}
+
+/// The program that wrote a file, as far as the file tells.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WriterProfile {
+    pub product: Product,
+    /// The header's product string, e.g.
+    /// `"@(#) IBM SPSS STATISTICS 64-bit MS Windows 26.0.0.0"`.
+    pub product_string: String,
+    /// Release (major, minor, revision) from the machine integer record.
+    /// SPSS and PSPP record their own version; other writers vary.
+    pub release: Option<[i32; 3]>,
+    /// Quirks worked around while reading, in the order first met.
+    pub quirks: Vec<Quirk>,
+}
+
+impl WriterProfile {
+    /// Recognize the writer from the header's product string, which every
+    /// writer starts with `@(#) SPSS DATA FILE` or the like.
+    pub(crate) fn detect(product_string: &str, integer_info: Option<&IntegerInfo>) -> Self {
+        let text = product_string.to_ascii_lowercase();
+        let rest = text
+            .strip_prefix("@(#) spss data file")
+            .unwrap_or(&text)
+            .trim();
+        let product = if text.contains("pspp") {
+            Product::Pspp
+        } else if text.contains("readstat") {
+            Product::ReadStat
+        } else if text.contains("ambers") {
+            Product::Ambers
+        } else if text.contains("ibm spss")
+            || ["windows", "release", "mac os", "unix", "linux", "solaris", "aix"]
+                .iter()
+                .any(|os| rest.contains(os))
+        {
+            Product::Spss
+        } else {
+            Product::Unknown
+        };
+        WriterProfile {
+            product,
+            product_string: product_string.to_string(),
+            release: integer_info
+                .map(|info| [info.version_major, info.version_minor, info.version_revision]),
+            quirks: Vec::new(),
+        }
+    }
+
+    /// Whether labels may carry NUL padding: everything but SPSS and ambers
+    /// is checked.
+    pub(crate) fn nul_padded_labels(&self) -> bool {
+        !matches!(self.product, Product::Spss | Product::Ambers)
+    }
+
+    /// Whether the writer only produces UTF-8 text.
+    pub(crate) fn utf8_only(&self) -> bool {
+        matches!(self.product, Product::ReadStat | Product::Ambers)
+    }
+
+    pub(crate) fn note(&mut self, quirk: Quirk) {
+        if !self.quirks.contains(&quirk) {
+            self.quirks.push(quirk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, RecordBatch};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::metadata::SpssMetadata;
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            ("@(#) IBM SPSS STATISTICS 64-bit MS Windows 26.0.0.0", Product::Spss),
+            ("@(#) SPSS DATA FILE MS WINDOWS Release 11.0", Product::Spss),
+            ("@(#) SPSS DATA FILE GNU pspp 1.4.1 - x86_64-pc-linux-gnu", Product::Pspp),
+            ("@(#) SPSS DATA FILE - https://github.com/WizardMac/ReadStat", Product::ReadStat),
+            ("@(#) SPSS DATA FILE ambers 0.4.0", Product::Ambers),
+            ("@(#) SPSS DATA FILE", Product::Unknown),
+            ("Stat/Transfer", Product::Unknown),
+        ];
+        for (text, product) in cases {
+            assert_eq!(WriterProfile::detect(text, None).product, product, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_readstat_quirks() {
+        let schema = Arc::new(Schema::new(vec![Field::new("q1", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.0]))]).unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels.insert("q1".into(), "Café~~".into());
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+        let (_, meta) = crate::read_sav_from_reader(Cursor::new(buf.clone())).unwrap();
+        assert_eq!(meta.writer.product, Product::Ambers);
+        assert!(meta.writer.quirks.is_empty());
+
+        // As written by ReadStat: NUL padded, with no encoding record and
+        // 7-bit ASCII for the character code.
+        let product = b"@(#) SPSS DATA FILE - https://github.com/WizardMac/ReadStat";
+        buf[4..4 + product.len()].copy_from_slice(product);
+        let at = buf.windows(2).position(|w| w == b"~~").unwrap();
+        buf[at..at + 2].fill(0);
+        let integer_info = [7, 3, 4, 8].map(i32::to_le_bytes).concat();
+        let at = buf.windows(16).position(|w| w == integer_info).unwrap() + 16 + 7 * 4;
+        buf[at..at + 4].copy_from_slice(&2i32.to_le_bytes());
+        let encoding = [7, 20, 1].map(i32::to_le_bytes).concat();
+        let at = buf.windows(12).position(|w| w == encoding).unwrap();
+        buf[at + 4..at + 8].copy_from_slice(&99i32.to_le_bytes());
+
+        let (_, meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(meta.writer.product, Product::ReadStat);
+        assert_eq!(meta.writer.quirks, [Quirk::AssumedUtf8, Quirk::NulPaddedLabels]);
+        assert_eq!(meta.file_encoding, "UTF-8");
+        assert_eq!(meta.label("q1"), Some("Café"));
+    }
+}
@@ -5,15 +5,17 @@ use std::io::BufReader;
 
 use indexmap::IndexMap;
 
-use arrow::ffi_stream::FFI_ArrowArrayStream;
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
 use arrow::record_batch::{RecordBatch, RecordBatchIterator, RecordBatchReader};
-use pyo3::exceptions::{PyIOError, PyKeyError};
+use pyo3::exceptions::{PyIOError, PyKeyError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::{PyCapsule, PyDict, PyList, PyTuple};
 
 use crate::constants::Compression;
 use crate::metadata::{MissingSpec, MrSet, MrType, SpssMetadata, Value};
+use crate::report::format_spss_datetime;
 use crate::scanner::SavScanner;
+use crate::writer::{SavWriter, WriteOptions};
 
 // ---------------------------------------------------------------------------
 // Error conversion
@@ -199,6 +201,35 @@ impl PySpssMetadata {
             .collect()
     }
 
+    #[getter]
+    fn variable_role(&self) -> IndexMap<String, String> {
+        self.inner
+            .variable_role
+            .iter()
+            .map(|(k, v)| (k.clone(), v.as_str().to_string()))
+            .collect()
+    }
+
+    #[getter]
+    fn file_attributes(&self) -> IndexMap<String, Vec<String>> {
+        self.inner.file_attributes.clone()
+    }
+
+    #[getter]
+    fn variable_attributes(&self) -> IndexMap<String, IndexMap<String, Vec<String>>> {
+        self.inner.variable_attributes.clone()
+    }
+
+    #[getter]
+    fn date_info(&self) -> Vec<[i32; 3]> {
+        self.inner.date_info.clone()
+    }
+
+    #[getter]
+    fn unknown_records(&self) -> Vec<(i32, Vec<u8>)> {
+        self.inner.unknown_records.clone()
+    }
+
     #[getter]
     fn variable_missing<'py>(&self, py: Python<'py>) -> PyResult<Py<PyAny>> {
         let outer = PyDict::new(py);
@@ -226,6 +257,53 @@ impl PySpssMetadata {
         self.inner.weight_variable.clone()
     }
 
+    #[getter]
+    fn renamed_columns(&self) -> IndexMap<String, String> {
+        self.inner.renamed_columns.clone()
+    }
+
+    #[getter]
+    fn renamed_variables(&self) -> IndexMap<String, String> {
+        self.inner.renamed_variables.clone()
+    }
+
+    #[getter]
+    fn unknown_formats(&self) -> IndexMap<String, i32> {
+        self.inner.unknown_formats.clone()
+    }
+
+    #[getter]
+    fn warnings<'py>(&self, py: Python<'py>) -> PyResult<Py<PyAny>> {
+        let list = PyList::empty(py);
+        for warning in &self.inner.warnings {
+            let d = PyDict::new(py);
+            d.set_item("kind", warning.kind.as_str())?;
+            d.set_item("variable", &warning.variable)?;
+            d.set_item("message", &warning.message)?;
+            list.append(d)?;
+        }
+        Ok(list.unbind().into_any())
+    }
+
+    #[getter]
+    fn replaced_characters(&self) -> IndexMap<String, u64> {
+        self.inner.replaced_characters.clone()
+    }
+
+    /// The program that wrote the file: product, product string, release
+    /// and the writer quirks worked around while reading.
+    #[getter]
+    fn writer<'py>(&self, py: Python<'py>) -> PyResult<Py<PyAny>> {
+        let writer = &self.inner.writer;
+        let d = PyDict::new(py);
+        d.set_item("product", writer.product.as_str())?;
+        d.set_item("product_string", &writer.product_string)?;
+        d.set_item("release", writer.release)?;
+        let quirks: Vec<&str> = writer.quirks.iter().map(|q| q.as_str()).collect();
+        d.set_item("quirks", quirks)?;
+        Ok(d.unbind().into_any())
+    }
+
     // -----------------------------------------------------------------------
     // Quick lookup methods
     // -----------------------------------------------------------------------
@@ -258,6 +336,12 @@ impl PySpssMetadata {
         Ok(self.inner.measure(name).map(|m| m.as_str().to_string()))
     }
 
+    /// Get the role for a variable (input, target, both, none, partition, split).
+    fn role(&self, name: &str) -> PyResult<Option<String>> {
+        self.check_var(name)?;
+        Ok(self.inner.role(name).map(|r| r.as_str().to_string()))
+    }
+
     /// Get the value labels dict for a variable. Returns None if no value labels exist.
     fn value<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Option<Py<PyAny>>> {
         self.check_var(name)?;
@@ -321,129 +405,7 @@ impl PySpssMetadata {
 
     /// Print a formatted summary of the metadata.
     fn summary(&self) {
-        use crate::constants::Measure;
-
-        let m = &self.inner;
-        let ncols = m.number_columns;
-        let rows_str = m
-            .number_rows
-            .map(|n| format_count(n as usize))
-            .unwrap_or_else(|| "unknown".into());
-        let datetime = format_spss_datetime(&m.creation_time, &m.modification_time);
-
-        println!("SPSS Metadata Summary");
-        println!("\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}\u{2550}");
-
-        // File section
-        println!();
-        println!("File");
-        println!(
-            "  Label:        {}",
-            if m.file_label.is_empty() {
-                "(none)"
-            } else {
-                &m.file_label
-            }
-        );
-        println!("  Format:       {}", m.file_format);
-        println!("  Encoding:     {}", m.file_encoding);
-        println!("  Created:      {}", datetime);
-        println!("  Rows:         {}", rows_str);
-        println!("  Columns:      {}", format_count(ncols));
-        println!(
-            "  Weight:       {}",
-            m.weight_variable.as_deref().unwrap_or("(none)")
-        );
-        if !m.notes.is_empty() {
-            let first_line = m.notes[0].trim();
-            let preview = if first_line.len() > 40 {
-                format!("{}...", &first_line[..40])
-            } else {
-                first_line.to_string()
-            };
-            println!(
-                "  Notes:        {} record(s) \u{2502} {}",
-                m.notes.len(),
-                preview
-            );
-        }
-
-        // Variables section
-        let mut n_numeric = 0usize;
-        let mut n_string = 0usize;
-        for fmt in m.spss_variable_types.values() {
-            if fmt.starts_with('A') {
-                n_string += 1;
-            } else {
-                n_numeric += 1;
-            }
-        }
-
-        println!();
-        println!("Variables");
-        let pct = |n: usize| -> String {
-            if ncols > 0 {
-                format!("{:>5.1}%", 100.0 * n as f64 / ncols as f64)
-            } else {
-                String::new()
-            }
-        };
-        println!(
-            "  Numeric       {:>5}    {}",
-            format_count(n_numeric),
-            pct(n_numeric)
-        );
-        println!(
-            "  String        {:>5}    {}",
-            format_count(n_string),
-            pct(n_string)
-        );
-
-        // Measure level distribution
-        let mut n_nominal = 0usize;
-        let mut n_ordinal = 0usize;
-        let mut n_scale = 0usize;
-        let mut n_unknown = 0usize;
-        for var in &m.variable_names {
-            match m.variable_measure.get(var) {
-                Some(Measure::Nominal) => n_nominal += 1,
-                Some(Measure::Ordinal) => n_ordinal += 1,
-                Some(Measure::Scale) => n_scale += 1,
-                _ => n_unknown += 1,
-            }
-        }
-        println!();
-        println!("  Nominal       {:>5}", format_count(n_nominal));
-        println!("  Ordinal       {:>5}", format_count(n_ordinal));
-        println!("  Scale         {:>5}", format_count(n_scale));
-        if n_unknown > 0 {
-            println!("  Unknown       {:>5}", format_count(n_unknown));
-        }
-
-        // Annotations section
-        let n_with_labels = m.variable_labels.len();
-        let n_with_values = m.variable_value_labels.len();
-        let n_with_missing = m.variable_missing.len();
-        let n_mr = m.mr_sets.len();
-
-        println!();
-        println!("Annotations");
-        let ratio = |n: usize| -> String {
-            if ncols > 0 && n > 0 {
-                format!(
-                    "{:>5} / {:<5}  {:>5.1}%",
-                    format_count(n),
-                    format_count(ncols),
-                    100.0 * n as f64 / ncols as f64
-                )
-            } else {
-                format!("{:>5} / {}", format_count(n), format_count(ncols))
-            }
-        };
-        println!("  Labeled:      {}", ratio(n_with_labels));
-        println!("  Value labels: {}", ratio(n_with_values));
-        println!("  Missing:      {}", ratio(n_with_missing));
-        println!("  MR sets:      {:>5}", format_count(n_mr));
+        print!("{}", self.inner.render_summary());
     }
 
     // -----------------------------------------------------------------------
@@ -464,82 +426,30 @@ impl PySpssMetadata {
             ));
         };
 
-        let m = &self.inner;
-
         // Validate all names before printing anything
         for name in &var_names {
             self.check_var(name)?;
         }
+        print!("{}", self.inner.render_describe(&var_names));
+        Ok(())
+    }
 
-        for (i, name) in var_names.iter().enumerate() {
-            if i > 0 {
-                println!();
-            }
-
-            let label = m.variable_labels.get(name).map(|s| s.as_str()).unwrap_or("(none)");
-            let fmt = m.spss_variable_types.get(name).map(|s| s.as_str()).unwrap_or("?");
-            let measure_str = m
-                .variable_measure
-                .get(name)
-                .map(|v| v.as_str())
-                .unwrap_or("?");
-            let align = m
-                .variable_alignment
-                .get(name)
-                .map(|v| v.as_str())
-                .unwrap_or("?");
-            let display_w = m
-                .variable_display_width
-                .get(name)
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "?".into());
-            let storage_w = m
-                .variable_storage_width
-                .get(name)
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "?".into());
-
-            let type_str = if fmt.starts_with('A') { "String" } else { "Numeric" };
-
-            println!("Variable: {name}");
-            println!("Label:    {label}");
-            println!("Format:   {fmt:<12}Measure: {measure_str}");
-            println!("Type:     {type_str:<12}Align:   {align}");
-            println!("Display:  {display_w:<12}Storage: {storage_w}");
-
-            // Missing values
-            if let Some(specs) = m.variable_missing.get(name) {
-                if specs.is_empty() {
-                    println!("Missing:  (none)");
-                } else {
-                    let parts: Vec<String> = specs
-                        .iter()
-                        .map(|s| match s {
-                            MissingSpec::Value(v) => format_f64(*v),
-                            MissingSpec::Range { lo, hi } => {
-                                format!("{} thru {}", format_f64(*lo), format_f64(*hi))
-                            }
-                            MissingSpec::StringValue(s) => format!("{s:?}"),
-                        })
-                        .collect();
-                    println!("Missing:  {}", parts.join(", "));
-                }
-            } else {
-                println!("Missing:  (none)");
-            }
+    // -----------------------------------------------------------------------
+    // validate() — dictionary lint checks
+    // -----------------------------------------------------------------------
 
-            // Value labels
-            if let Some(labels) = m.variable_value_labels.get(name) {
-                if !labels.is_empty() {
-                    println!();
-                    println!("Value Labels ({}):", labels.len());
-                    for (val, lbl) in labels {
-                        println!("  {:<8}{lbl}", val.to_string());
-                    }
-                }
-            }
+    /// Check the dictionary for common problems. Returns a list of dicts
+    /// with keys `check`, `variable` and `message`.
+    fn validate<'py>(&self, py: Python<'py>) -> PyResult<Py<PyAny>> {
+        let list = PyList::empty(py);
+        for finding in self.inner.validate() {
+            let d = PyDict::new(py);
+            d.set_item("check", finding.check.as_str())?;
+            d.set_item("variable", &finding.variable)?;
+            d.set_item("message", &finding.message)?;
+            list.append(d)?;
         }
-        Ok(())
+        Ok(list.unbind().into_any())
     }
 
     // -----------------------------------------------------------------------
@@ -656,6 +566,23 @@ impl PySpssMetadata {
         Ok(result)
     }
 
+    // -----------------------------------------------------------------------
+    // JSON import/export
+    // -----------------------------------------------------------------------
+
+    /// Serialize the metadata to the canonical JSON document.
+    fn to_json(&self) -> String {
+        self.inner.to_json()
+    }
+
+    /// Parse metadata from the canonical JSON document.
+    #[staticmethod]
+    fn from_json(text: &str) -> PyResult<Self> {
+        let inner =
+            SpssMetadata::from_json(text).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PySpssMetadata { inner })
+    }
+
     // -----------------------------------------------------------------------
     // __repr__ / __str__
     // -----------------------------------------------------------------------
@@ -891,54 +818,6 @@ fn list_len(py: Python<'_>, obj: &Py<PyAny>) -> usize {
         .unwrap_or(0)
 }
 
-/// Parse SPSS header date ("16 Feb 26") + time ("10:38:17") into "2026-02-16 10:38:17".
-fn format_spss_datetime(date_str: &str, time_str: &str) -> String {
-    let parts: Vec<&str> = date_str.split_whitespace().collect();
-    if parts.len() == 3 {
-        let day: u32 = parts[0].parse().unwrap_or(0);
-        let month = match parts[1].to_lowercase().as_str() {
-            "jan" => 1,
-            "feb" => 2,
-            "mar" => 3,
-            "apr" => 4,
-            "may" => 5,
-            "jun" => 6,
-            "jul" => 7,
-            "aug" => 8,
-            "sep" => 9,
-            "oct" => 10,
-            "nov" => 11,
-            "dec" => 12,
-            _ => 0,
-        };
-        let yy: u32 = parts[2].parse().unwrap_or(0);
-        let year = 2000 + yy;
-        if day > 0 && month > 0 {
-            return format!("{year:04}-{month:02}-{day:02} {time_str}");
-        }
-    }
-    // Fallback: just concatenate
-    format!("{date_str} {time_str}")
-}
-
-fn format_count(n: usize) -> String {
-    if n >= 1_000_000 {
-        format!("{},{:03},{:03}", n / 1_000_000, (n / 1000) % 1000, n % 1000)
-    } else if n >= 1_000 {
-        format!("{},{:03}", n / 1000, n % 1000)
-    } else {
-        n.to_string()
-    }
-}
-
-fn format_f64(v: f64) -> String {
-    if v.fract() == 0.0 && v.is_finite() {
-        format!("{}", v as i64)
-    } else {
-        format!("{v}")
-    }
-}
-
 /// Diff two IndexMap<String, String> on shared variables.
 fn diff_string_maps<'py>(
     py: Python<'py>,
@@ -1186,8 +1065,15 @@ impl PySavBatchReader {
             .map(|f| {
                 let dtype = match f.data_type() {
                     arrow::datatypes::DataType::Float64 => "Float64",
+                    arrow::datatypes::DataType::Float32 => "Float32",
+                    arrow::datatypes::DataType::Boolean => "Boolean",
+                    arrow::datatypes::DataType::Int32 => "Int32",
+                    arrow::datatypes::DataType::Int64 => "Int64",
+                    arrow::datatypes::DataType::UInt64 => "UInt64",
                     arrow::datatypes::DataType::Utf8
+                    | arrow::datatypes::DataType::LargeUtf8
                     | arrow::datatypes::DataType::Utf8View => "String",
+                    arrow::datatypes::DataType::Dictionary(_, _) => "Categorical",
                     arrow::datatypes::DataType::Date32 => "Date",
                     arrow::datatypes::DataType::Timestamp(_, _) => "Datetime",
                     arrow::datatypes::DataType::Duration(_) => "Duration",
@@ -1246,6 +1132,74 @@ fn _read_sav_metadata(path: &str) -> PyResult<PySpssMetadata> {
     Ok(PySpssMetadata { inner: meta })
 }
 
+// ---------------------------------------------------------------------------
+// #[pyfunction] write_sav
+// ---------------------------------------------------------------------------
+
+/// Import an object implementing the Arrow PyCapsule stream interface
+/// (pyarrow Table/RecordBatch, polars DataFrame, ...) as a batch reader.
+fn arrow_stream_reader(data: &Bound<'_, PyAny>) -> PyResult<ArrowArrayStreamReader> {
+    if !data.hasattr("__arrow_c_stream__")? {
+        return Err(PyTypeError::new_err(
+            "expected an object implementing __arrow_c_stream__ (e.g. a pyarrow Table or polars DataFrame)",
+        ));
+    }
+    let capsule = data
+        .call_method0("__arrow_c_stream__")?
+        .downcast_into::<PyCapsule>()?;
+    if capsule.name()? != Some(c"arrow_array_stream") {
+        return Err(PyTypeError::new_err(
+            "__arrow_c_stream__ did not return an arrow_array_stream capsule",
+        ));
+    }
+    // SAFETY: the capsule holds a valid FFI_ArrowArrayStream; from_raw moves
+    // it out and leaves a released stream behind for the capsule destructor.
+    unsafe { ArrowArrayStreamReader::from_raw(capsule.pointer() as *mut FFI_ArrowArrayStream) }
+        .map_err(|e| PyValueError::new_err(format!("invalid Arrow stream: {e}")))
+}
+
+/// Write Arrow data to an SPSS .sav/.zsav file, streaming batch by batch.
+#[pyfunction]
+#[pyo3(signature = (data, path, metadata=None, compression="none", zlib_level=6, encoding="UTF-8"))]
+fn _write_sav(
+    py: Python<'_>,
+    data: &Bound<'_, PyAny>,
+    path: &str,
+    metadata: Option<PyRef<'_, PySpssMetadata>>,
+    compression: &str,
+    zlib_level: u32,
+    encoding: &str,
+) -> PyResult<()> {
+    let compression = match compression {
+        "none" => Compression::None,
+        "bytecode" => Compression::Bytecode,
+        "zlib" => Compression::Zlib,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "unknown compression {other:?} (expected 'none', 'bytecode' or 'zlib')"
+            )));
+        }
+    };
+    let options = WriteOptions {
+        compression,
+        zlib_level,
+        encoding: encoding.to_string(),
+        ..WriteOptions::default()
+    };
+    let metadata = metadata.map(|m| m.inner.clone()).unwrap_or_default();
+    let reader = arrow_stream_reader(data)?;
+
+    py.detach(move || -> crate::error::Result<()> {
+        let mut writer = SavWriter::with_options(path, reader.schema(), &metadata, options)?;
+        for batch in reader {
+            writer.write_batch(&batch?)?;
+        }
+        writer.finish()?;
+        Ok(())
+    })
+    .map_err(spss_err)
+}
+
 // ---------------------------------------------------------------------------
 // #[pymodule]
 // ---------------------------------------------------------------------------
@@ -1254,6 +1208,7 @@ fn _read_sav_metadata(path: &str) -> PyResult<PySpssMetadata> {
 fn _ambers(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(_read_sav, m)?)?;
     m.add_function(wrap_pyfunction!(_read_sav_metadata, m)?)?;
+    m.add_function(wrap_pyfunction!(_write_sav, m)?)?;
     m.add_class::<PySpssMetadata>()?;
     m.add_class::<PyMetaDiff>()?;
     m.add_class::<PyArrowData>()?;
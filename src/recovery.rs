@@ -0,0 +1,771 @@
+//! Salvaging damaged .sav/.zsav files: [`recover_sav`] reads what it can,
+//! skips what it cannot and resynchronizes after each damaged region.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use arrow::record_batch::RecordBatch;
+use flate2::read::ZlibDecoder;
+
+use crate::columnar::ColumnarBatchBuilder;
+use crate::constants::*;
+use crate::dictionary::{self, RawDictionary};
+use crate::error::Result;
+use crate::float_format::NumberFormat;
+use crate::header::FileHeader;
+use crate::io_utils::SavReader;
+use crate::metadata::SpssMetadata;
+use crate::options::OutputOptions;
+
+/// Size of the fixed file header, where the dictionary starts.
+const HEADER_LEN: usize = 176;
+
+/// Cases that must decode cleanly after a candidate resynchronization
+/// point before it is trusted.
+const VERIFY_CASES: usize = 8;
+
+/// The part of a file a [`SkippedRegion`] lies in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Dictionary,
+    Data,
+}
+
+/// Bytes [`recover_sav`] could not read and skipped over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRegion {
+    pub section: Section,
+    /// Offset of the region in the file. In a .zsav, data regions are
+    /// whole compressed blocks: the ones the damage falls in.
+    pub offset: u64,
+    pub len: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for SkippedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self.section {
+            Section::Dictionary => "dictionary",
+            Section::Data => "data",
+        };
+        write!(
+            f,
+            "{section} bytes {}..{} skipped: {}",
+            self.offset,
+            self.offset + self.len,
+            self.reason
+        )
+    }
+}
+
+/// What [`recover_sav`] salvaged from a file.
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    /// The cases that could be read, in file order.
+    pub batch: RecordBatch,
+    /// The dictionary, from the records that could be read.
+    pub metadata: SpssMetadata,
+    /// What was skipped, in file order.
+    pub skipped: Vec<SkippedRegion>,
+}
+
+impl Recovered {
+    /// True when nothing was skipped: the file read as it would normally.
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+}
+
+/// Read as much as possible of a damaged SPSS .sav or .zsav file.
+///
+/// Where [`read_sav`](crate::read_sav) stops at the first error, this skips
+/// each damaged region and carries on from the next point that reads
+/// plausibly: the next well-formed dictionary record, the next case that
+/// decodes cleanly in compressed data, the next intact block of a .zsav.
+/// Everything skipped is listed in [`Recovered::skipped`]; a case that
+/// overlaps a damaged region is dropped whole.
+///
+/// Damage is spotted in compressed data by each slot's type, a string slot
+/// never holding a number and the other way round, and by values no writer
+/// produces: control characters in strings, numbers beyond 1e100. Damage
+/// that still reads as plausible values goes unnoticed, which makes files
+/// of numeric variables only, and uncompressed data, the least reliable
+/// to salvage. The file header must be intact. The file is read into
+/// memory whole.
+///
+/// # Example
+/// ```no_run
+/// let recovered = ambers::recover_sav("damaged.sav").unwrap();
+/// for region in &recovered.skipped {
+///     eprintln!("{region}");
+/// }
+/// println!("{} cases salvaged", recovered.batch.num_rows());
+/// ```
+pub fn recover_sav(path: impl AsRef<Path>) -> Result<Recovered> {
+    recover_sav_from_reader(File::open(path)?)
+}
+
+/// Read as much as possible of a damaged SPSS file from a reader (see
+/// [`recover_sav`]).
+pub fn recover_sav_from_reader<R: Read>(mut reader: R) -> Result<Recovered> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    recover(&data)
+}
+
+fn recover(data: &[u8]) -> Result<Recovered> {
+    let mut reader = SavReader::new(Cursor::new(data));
+    let header = FileHeader::parse(&mut reader)?;
+    let mut skipped = Vec::new();
+
+    let (raw, data_start) = salvage_dictionary(data, &header, &mut skipped);
+    // Whether each slot holds string bytes.
+    let strings: Vec<bool> = raw.variables.iter().map(|v| v.raw_type != 0).collect();
+    let slots = strings.len();
+    let dict = dictionary::resolve_dictionary(raw)?;
+
+    let mut rows = Vec::new();
+    if let Some(start) = data_start {
+        let declared = header.nominal_case_size;
+        if slots == 0 || (declared > 0 && declared as usize != slots) {
+            skipped.push(SkippedRegion {
+                section: Section::Data,
+                offset: start as u64,
+                len: (data.len() - start) as u64,
+                reason: format!(
+                    "the variable records give {slots} slots per case, the header {declared}"
+                ),
+            });
+        } else {
+            let mut out = Salvage {
+                strings: &strings,
+                numbers: Numbers {
+                    // A damaged bias would turn every opcode value into
+                    // garbage; the usual one is the best guess.
+                    bias: match dict.header.bias {
+                        bias if bias.is_finite() => bias,
+                        _ => DEFAULT_BIAS,
+                    },
+                    format: dict.numbers,
+                },
+                rows: &mut rows,
+                skipped: &mut skipped,
+            };
+            match header.compression {
+                Compression::None => out.uncompressed(data, start),
+                Compression::Bytecode => {
+                    let stream = &data[start..];
+                    let blocks = [Block::inline(start, stream.len())];
+                    out.bytecode(stream, &blocks, Some(0), false);
+                }
+                Compression::Zlib => out.zsav(data, start, header.bswap),
+            }
+        }
+    }
+
+    let n_rows = rows.len() / (slots * 8).max(1);
+    let mut builder =
+        ColumnarBatchBuilder::with_options(&dict, None, n_rows, &OutputOptions::default());
+    if n_rows > 0 {
+        builder.push_raw_chunk(&rows, n_rows, slots);
+    }
+    Ok(Recovered {
+        batch: builder.finish()?,
+        metadata: dict.metadata,
+        skipped,
+    })
+}
+
+/// Read the dictionary records one by one, skipping ahead to the next
+/// plausible record after one that cannot be read. Returns the records
+/// read and where the data starts, if the termination record was found.
+fn salvage_dictionary(
+    data: &[u8],
+    header: &FileHeader,
+    skipped: &mut Vec<SkippedRegion>,
+) -> (RawDictionary, Option<usize>) {
+    let bswap = header.bswap;
+    let mut raw = RawDictionary::new(header);
+    let mut pos = HEADER_LEN;
+    while pos < data.len() {
+        if let Some(record_type) = i32_at(data, pos, bswap)
+            && plausible_record(data, pos, bswap)
+        {
+            let mut reader = SavReader::new(Cursor::new(&data[pos + 4..]));
+            reader.set_bswap(bswap);
+            if let Ok(more) = raw.read_record(&mut reader, record_type) {
+                pos += 4 + reader.inner_mut().position() as usize;
+                if !more {
+                    return (raw, Some(pos));
+                }
+                continue;
+            }
+        }
+        let next = (pos + 1..data.len())
+            .find(|&p| plausible_record(data, p, bswap))
+            .unwrap_or(data.len());
+        let reason = match i32_at(data, pos, bswap) {
+            Some(record_type) => format!("unreadable record (type {record_type})"),
+            None => "unreadable record".to_string(),
+        };
+        skipped.push(SkippedRegion {
+            section: Section::Dictionary,
+            offset: pos as u64,
+            len: (next - pos) as u64,
+            reason,
+        });
+        pos = next;
+    }
+    skipped.push(SkippedRegion {
+        section: Section::Dictionary,
+        offset: data.len() as u64,
+        len: 0,
+        reason: "no dictionary termination record; the data cannot be located".to_string(),
+    });
+    (raw, None)
+}
+
+fn i32_at(data: &[u8], at: usize, bswap: bool) -> Option<i32> {
+    let bytes: [u8; 4] = data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+    Some(if bswap {
+        i32::from_be_bytes(bytes)
+    } else {
+        i32::from_le_bytes(bytes)
+    })
+}
+
+/// Whether a well-formed dictionary record appears to start at `at`: the
+/// counts and sizes of its fixed fields are in range and fit the file.
+fn plausible_record(data: &[u8], at: usize, bswap: bool) -> bool {
+    let int = |offset: usize| i32_at(data, at + offset, bswap);
+    let fits = |len: usize| at.checked_add(len).is_some_and(|end| end <= data.len());
+    match int(0) {
+        Some(RECORD_TYPE_VARIABLE) => {
+            let (Some(width), Some(has_label), Some(n_missing), Some(print), Some(write)) =
+                (int(4), int(8), int(12), int(16), int(20))
+            else {
+                return false;
+            };
+            let format_ok = |packed: i32| packed as u32 >> 24 == 0 && (packed >> 16) <= 41;
+            let name_ok = data
+                .get(at + 24..at + 32)
+                .is_some_and(|name| name.iter().all(|&b| b == b' ' || b.is_ascii_graphic()));
+            (-1..=255).contains(&width)
+                && (has_label == 0 || has_label == 1)
+                && (-3..=3).contains(&n_missing)
+                && format_ok(print)
+                && format_ok(write)
+                && name_ok
+        }
+        Some(RECORD_TYPE_VALUE_LABEL) => {
+            let Some(count) = int(4).filter(|&n| n > 0) else {
+                return false;
+            };
+            // Walk the labels to where the type 4 record must be.
+            let mut offset = 8;
+            for _ in 0..count {
+                let Some(&len) = data.get(at + offset + 8) else {
+                    return false;
+                };
+                offset += 8 + (len as usize + 1).next_multiple_of(8);
+            }
+            int(offset) == Some(RECORD_TYPE_VALUE_LABEL_VARS)
+                && int(offset + 4).is_some_and(|n| n > 0 && fits(offset + 8 + n as usize * 4))
+        }
+        Some(RECORD_TYPE_DOCUMENT) => int(4).is_some_and(|n| n > 0 && fits(8 + n as usize * 80)),
+        Some(RECORD_TYPE_INFO) => {
+            let (Some(subtype), Some(size), Some(count)) = (int(4), int(8), int(12)) else {
+                return false;
+            };
+            (1..=40).contains(&subtype)
+                && matches!(size, 1 | 4 | 8)
+                && count >= 0
+                && fits(16 + size as usize * count as usize)
+        }
+        Some(RECORD_TYPE_DICT_TERMINATION) => int(4) == Some(0),
+        _ => false,
+    }
+}
+
+/// Where a run of the data stream came from in the file.
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    /// Offset and length of the bytes in the file.
+    offset: usize,
+    len: usize,
+    /// Offset of the decompressed bytes in the data stream.
+    start: usize,
+}
+
+impl Block {
+    fn inline(offset: usize, len: usize) -> Block {
+        Block {
+            offset,
+            len,
+            start: 0,
+        }
+    }
+}
+
+/// How a file stores numbers: the compression bias and the format.
+#[derive(Debug, Clone, Copy)]
+struct Numbers {
+    bias: f64,
+    format: NumberFormat,
+}
+
+impl Numbers {
+    fn bytes(&self, v: f64) -> [u8; 8] {
+        self.format.encode(v)
+    }
+
+    fn value(&self, slot: &[u8]) -> f64 {
+        self.format.decode(slot.try_into().unwrap())
+    }
+}
+
+/// Salvaged cases, as raw slot bytes stored the file's way, and the
+/// regions skipped so far.
+struct Salvage<'a> {
+    strings: &'a [bool],
+    numbers: Numbers,
+    rows: &'a mut Vec<u8>,
+    skipped: &'a mut Vec<SkippedRegion>,
+}
+
+impl Salvage<'_> {
+    fn skip(&mut self, offset: usize, len: usize, reason: impl Into<String>) {
+        self.skipped.push(SkippedRegion {
+            section: Section::Data,
+            offset: offset as u64,
+            len: len as u64,
+            reason: reason.into(),
+        });
+    }
+
+    /// Uncompressed data cannot be told from damage: keep every whole case.
+    fn uncompressed(&mut self, data: &[u8], start: usize) {
+        let row_bytes = self.strings.len() * 8;
+        let whole = (data.len() - start) / row_bytes * row_bytes;
+        self.rows.extend_from_slice(&data[start..start + whole]);
+        if start + whole < data.len() {
+            self.skip(
+                start + whole,
+                data.len() - start - whole,
+                "data ends inside a case",
+            );
+        }
+    }
+
+    /// Decode a bytecode stream, resynchronizing after damage. `blocks`
+    /// map the stream back to the file; `align` is the stream's offset
+    /// modulo 8 in the whole data, when known. With `resync` the stream
+    /// follows a gap and starts at an unknown point in a case.
+    fn bytecode(&mut self, stream: &[u8], blocks: &[Block], align: Option<usize>, resync: bool) {
+        let row_bytes = self.strings.len() * 8;
+        let mut case = vec![0; row_bytes];
+        let mut from = 0;
+        let mut decoder = if resync {
+            None
+        } else {
+            Some(Decoder::at(stream, 0, self.numbers))
+        };
+        loop {
+            let Some(dec) = decoder.as_mut() else {
+                match resync_point(stream, from, align, self.strings, self.numbers) {
+                    Some((at, dec)) => {
+                        if at > from {
+                            self.skip_stream(blocks, from, at, "damaged compressed data");
+                        }
+                        decoder = Some(dec);
+                        continue;
+                    }
+                    None => {
+                        self.skip_stream(blocks, from, stream.len(), "damaged compressed data");
+                        return;
+                    }
+                }
+            };
+            let before = dec.pos;
+            match dec.decode(self.strings, &mut case) {
+                Decoded::Case if plausible_case(self.strings, &case, self.numbers) => {
+                    self.rows.extend_from_slice(&case)
+                }
+                // Anything but padding after the end code means it was
+                // damage that happened to read as one.
+                Decoded::End if stream[dec.pos..].iter().all(|&b| b == 0) => return,
+                Decoded::Cut => {
+                    self.skip_stream(blocks, before, stream.len(), "data ends inside a case");
+                    return;
+                }
+                Decoded::Case | Decoded::End | Decoded::Damaged => {
+                    from = before;
+                    decoder = None;
+                }
+            }
+        }
+    }
+
+    /// Skip `from..to` of a stream, reported as the blocks it falls in.
+    fn skip_stream(&mut self, blocks: &[Block], from: usize, to: usize, reason: &str) {
+        let first = blocks
+            .partition_point(|b| b.start <= from)
+            .saturating_sub(1);
+        let last = blocks.partition_point(|b| b.start < to).saturating_sub(1);
+        let (first, last) = (&blocks[first], &blocks[last.max(first)]);
+        if blocks.len() == 1 && first.start == 0 {
+            self.skip(first.offset + from, to - from, reason);
+        } else {
+            self.skip(first.offset, last.offset + last.len - first.offset, reason);
+        }
+    }
+
+    /// Inflate each block on its own and decode the runs of intact blocks,
+    /// resynchronizing after each gap.
+    fn zsav(&mut self, data: &[u8], start: usize, bswap: bool) {
+        let int = |at: usize| i32_at(data, at, bswap);
+        let long = |at: usize| -> Option<i64> {
+            let bytes: [u8; 8] = data.get(at..at + 8)?.try_into().ok()?;
+            Some(if bswap {
+                i64::from_be_bytes(bytes)
+            } else {
+                i64::from_le_bytes(bytes)
+            })
+        };
+
+        // (offset, compressed size, uncompressed size if known)
+        let mut blocks: Vec<(usize, usize, Option<usize>)> = Vec::new();
+        let trailer = long(start + 8).and_then(|t| usize::try_from(t).ok());
+        if let Some(trailer) = trailer
+            && let Some(n) = int(trailer + 20).and_then(|n| usize::try_from(n).ok())
+            && n > 0
+            && trailer
+                .checked_add(24 + n * 24)
+                .is_some_and(|end| end <= data.len())
+        {
+            for i in 0..n {
+                let entry = trailer + 24 + i * 24;
+                let (Some(offset), Some(usize_), Some(csize)) =
+                    (long(entry + 8), int(entry + 16), int(entry + 20))
+                else {
+                    continue;
+                };
+                if let (Ok(offset), Ok(len), Ok(size)) = (
+                    usize::try_from(offset),
+                    usize::try_from(csize),
+                    usize::try_from(usize_),
+                ) {
+                    blocks.push((offset, len, Some(size)));
+                }
+            }
+        } else {
+            self.skip(
+                start,
+                24,
+                "unreadable zlib header or trailer; blocks found by scanning",
+            );
+            let mut at = start + 24;
+            while at + 2 <= data.len() {
+                match inflate_at(data, at) {
+                    Some((len, _)) => {
+                        blocks.push((at, len, None));
+                        at += len;
+                    }
+                    None => at += 1,
+                }
+            }
+        }
+
+        let mut stream = Vec::new();
+        let mut run: Vec<Block> = Vec::new();
+        // Offset of the run in the whole data stream, when known.
+        let mut position = Some(0);
+        let mut resync = false;
+        let mut expected_offset = start + 24;
+        for (offset, len, size) in blocks {
+            let inflated = data
+                .get(offset..offset.saturating_add(len))
+                .and_then(|bytes| inflate(bytes, size));
+            let gap = offset != expected_offset && size.is_none();
+            if gap {
+                let reason = "unreadable bytes between compressed blocks";
+                self.skip(expected_offset, offset - expected_offset, reason);
+            }
+            expected_offset = offset + len;
+            if let (Some(bytes), false) = (&inflated, gap) {
+                run.push(Block {
+                    offset,
+                    len,
+                    start: stream.len(),
+                });
+                stream.extend_from_slice(bytes);
+                continue;
+            }
+            let run_len = stream.len();
+            self.flush_run(&mut stream, &mut run, position.map(|p| p % 8), resync);
+            position = position.zip(size).map(|(p, size)| p + run_len + size);
+            match inflated {
+                Some(bytes) => {
+                    // A scanned block after unreadable bytes.
+                    position = None;
+                    run.push(Block {
+                        offset,
+                        len,
+                        start: 0,
+                    });
+                    stream.extend_from_slice(&bytes);
+                }
+                None => {
+                    self.skip(offset, len, "compressed block does not inflate");
+                }
+            }
+            resync = true;
+        }
+        self.flush_run(&mut stream, &mut run, position.map(|p| p % 8), resync);
+    }
+
+    fn flush_run(
+        &mut self,
+        stream: &mut Vec<u8>,
+        run: &mut Vec<Block>,
+        align: Option<usize>,
+        resync: bool,
+    ) {
+        if !run.is_empty() {
+            self.bytecode(stream, run, align, resync);
+        }
+        stream.clear();
+        run.clear();
+    }
+}
+
+/// Inflate one zlib stream, which must come to `size` bytes if known.
+fn inflate(bytes: &[u8], size: Option<usize>) -> Option<Vec<u8>> {
+    let limit = size.map_or(u64::MAX, |s| s as u64 + 1);
+    let mut out = Vec::new();
+    ZlibDecoder::new(bytes)
+        .take(limit)
+        .read_to_end(&mut out)
+        .ok()?;
+    (size.is_none_or(|s| out.len() == s)).then_some(out)
+}
+
+/// Inflate a zlib stream that starts at `at`, returning its compressed
+/// length and contents.
+fn inflate_at(data: &[u8], at: usize) -> Option<(usize, Vec<u8>)> {
+    let (cmf, flg) = (*data.get(at)?, *data.get(at + 1)?);
+    if cmf != 0x78 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return None;
+    }
+    let mut decoder = ZlibDecoder::new(&data[at..]);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).ok()?;
+    Some((decoder.total_in() as usize, out))
+}
+
+/// The first point at or after `from` where the stream decodes as the
+/// rest of a case followed by [`VERIFY_CASES`] plausible cases. Returns
+/// that point and a decoder positioned at the next case boundary.
+fn resync_point<'a>(
+    stream: &'a [u8],
+    from: usize,
+    align: Option<usize>,
+    strings: &[bool],
+    numbers: Numbers,
+) -> Option<(usize, Decoder<'a>)> {
+    let mut case = vec![0; strings.len() * 8];
+    // Control blocks start on 8-byte boundaries of the whole data stream.
+    let (first, step) = match align {
+        Some(align) => ((from + align).next_multiple_of(8) - align, 8),
+        None => (from, 1),
+    };
+    for at in (first..stream.len()).step_by(step) {
+        for phase in 0..strings.len() {
+            let mut dec = Decoder::at(stream, at, numbers);
+            if phase > 0 && dec.decode(&strings[phase..], &mut case) != Decoded::Case {
+                continue;
+            }
+            let boundary = dec.clone();
+            let mut verified = 0;
+            let ok = loop {
+                if verified == VERIFY_CASES {
+                    break true;
+                }
+                match dec.decode(strings, &mut case) {
+                    Decoded::Case if plausible_case(strings, &case, numbers) => verified += 1,
+                    Decoded::End => break verified > 0,
+                    _ => break false,
+                }
+            };
+            if ok {
+                return Some((at, boundary));
+            }
+        }
+    }
+    None
+}
+
+/// Whether decoded slots look like real data: strings without control
+/// characters, numbers of sensible magnitude.
+fn plausible_case(strings: &[bool], case: &[u8], numbers: Numbers) -> bool {
+    strings
+        .iter()
+        .zip(case.chunks_exact(8))
+        .all(|(&string, slot)| {
+            if string {
+                slot.iter()
+                    .all(|&b| b >= 0x20 || matches!(b, b'\t' | b'\n' | b'\r'))
+            } else {
+                let v = numbers.value(slot);
+                v.is_finite() && (v.abs() <= 1e100 || v.abs() == f64::MAX)
+            }
+        })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decoded {
+    Case,
+    /// The end-of-data code, or the end of the stream, where a case starts.
+    End,
+    /// The stream ends inside a case.
+    Cut,
+    /// A code that cannot stand for its slot.
+    Damaged,
+}
+
+/// A bytecode decoder that knows each slot's type, so that damage shows
+/// as codes that do not fit.
+#[derive(Debug, Clone)]
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    control: [u8; 8],
+    next: usize,
+    numbers: Numbers,
+}
+
+impl<'a> Decoder<'a> {
+    /// A decoder whose next control block starts at `pos`.
+    fn at(data: &'a [u8], pos: usize, numbers: Numbers) -> Decoder<'a> {
+        Decoder {
+            data,
+            pos,
+            control: [0; 8],
+            next: 8,
+            numbers,
+        }
+    }
+
+    fn code(&mut self) -> Option<u8> {
+        loop {
+            if self.next == 8 {
+                self.control = self.data.get(self.pos..self.pos + 8)?.try_into().ok()?;
+                self.pos += 8;
+                self.next = 0;
+            }
+            let code = self.control[self.next];
+            self.next += 1;
+            if code != COMPRESS_SKIP {
+                return Some(code);
+            }
+        }
+    }
+
+    /// Decode one slot per entry of `strings` into `out`.
+    fn decode(&mut self, strings: &[bool], out: &mut [u8]) -> Decoded {
+        for (i, &string) in strings.iter().enumerate() {
+            let Some(code) = self.code() else {
+                return if i == 0 { Decoded::End } else { Decoded::Cut };
+            };
+            let value: [u8; 8] = match (code, string) {
+                (1..=251, false) => self.numbers.bytes((code as f64) - self.numbers.bias),
+                (COMPRESS_SYSMIS, false) => self.numbers.bytes(f64::from_bits(SYSMIS_BITS)),
+                (COMPRESS_EIGHT_SPACES, true) => [b' '; 8],
+                (COMPRESS_RAW_FOLLOWS, _) => match self.data.get(self.pos..self.pos + 8) {
+                    Some(raw) => {
+                        self.pos += 8;
+                        raw.try_into().unwrap()
+                    }
+                    None => return Decoded::Cut,
+                },
+                (COMPRESS_END_OF_FILE, _) if i == 0 => return Decoded::End,
+                _ => return Decoded::Damaged,
+            };
+            out[i * 8..i * 8 + 8].copy_from_slice(&value);
+        }
+        Decoded::Case
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+
+    use super::*;
+    use crate::test_util;
+
+    fn q1(recovered: &Recovered) -> Vec<f64> {
+        recovered
+            .batch
+            .column(0)
+            .as_primitive::<Float64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_recover_damaged_data() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let bytes = test_util::survey_sav((0..200).map(f64::from), compression, 512);
+            let recovered = recover_sav_from_reader(Cursor::new(bytes)).unwrap();
+            assert!(recovered.is_complete(), "{:?}", recovered.skipped);
+            assert_eq!(q1(&recovered), (0..200).map(f64::from).collect::<Vec<_>>());
+        }
+
+        for compression in [Compression::Bytecode, Compression::Zlib] {
+            let mut bytes = test_util::survey_sav((0..200).map(f64::from), compression, 512);
+            let middle = bytes.len() / 2;
+            bytes[middle..middle + 40].fill(0x01);
+            let recovered = recover_sav_from_reader(Cursor::new(bytes)).unwrap();
+            assert!(!recovered.is_complete());
+            assert!(
+                recovered.skipped.iter().all(|r| r.section == Section::Data),
+                "{:?}",
+                recovered.skipped
+            );
+            let values = q1(&recovered);
+            assert!(values.len() > 100 && values.len() < 200, "{compression:?}");
+            assert_eq!(values[0], 0.0);
+            assert_eq!(values.last(), Some(&199.0));
+            // Rows on both sides of the damage are intact.
+            let cities = recovered.batch.column(1).as_string_view();
+            for (v, city) in values.iter().zip(cities) {
+                assert_eq!(city, Some(format!("city number {v}").as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_recover_damaged_dictionary() {
+        let mut bytes = test_util::survey_sav((0..200).map(f64::from), Compression::Bytecode, 512);
+        // Break the value label record's type 4 marker.
+        let labels = (HEADER_LEN..bytes.len())
+            .find(|&at| i32_at(&bytes, at, false) == Some(RECORD_TYPE_VALUE_LABEL))
+            .unwrap();
+        bytes[labels + 24..labels + 28].copy_from_slice(&99_i32.to_le_bytes());
+
+        let recovered = recover_sav_from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(recovered.skipped.len(), 1);
+        let region = &recovered.skipped[0];
+        assert_eq!(
+            (region.section, region.offset),
+            (Section::Dictionary, labels as u64)
+        );
+        assert!(recovered.metadata.variable_value_labels.is_empty());
+        assert_eq!(recovered.metadata.variable_names, ["q1", "city"]);
+        assert_eq!(recovered.batch.num_rows(), 200);
+    }
+}
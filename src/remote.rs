@@ -0,0 +1,179 @@
+//! Reading .sav/.zsav files from object storage (feature `object_store`).
+//!
+//! Files are read with ranged GETs through the `object_store` crate: the
+//! dictionary is fetched first, then data is streamed as batches are read
+//! (see `AsyncSavScanner` for how much is held at a time), so a file never
+//! has to be downloaded before scanning starts.
+
+use std::sync::Arc;
+
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreScheme};
+use url::Url;
+
+use crate::async_scanner::AsyncSavScanner;
+use crate::error::{Result, SpssError};
+
+/// Async reader over an object, as used by `scan_sav_url`.
+pub type ObjectReader = object_store::buffered::BufReader;
+
+/// Create an async streaming scanner over a file in object storage.
+///
+/// Supports `s3://`, `gs://`, `az://` (and the other S3 and Azure forms
+/// `object_store` recognizes, such as `https://bucket.s3.amazonaws.com/...`),
+/// and `file://` URLs. Plain `https://` URLs on other hosts are not
+/// supported. Credentials and settings are read from the environment
+/// variables of the matching store only: `AWS_*` for S3, `GOOGLE_*` for
+/// Google Cloud Storage and `AZURE_*` for Azure.
+///
+/// Default batch size: 100,000 rows.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> ambers::error::Result<()> {
+/// let mut scanner = ambers::scan_sav_url("s3://bucket/survey.zsav").await?;
+/// scanner.select(&["age", "gender"])?;
+/// while let Some(batch) = scanner.next_batch().await? {
+///     println!("Batch: {} rows", batch.num_rows());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn scan_sav_url(url: &str) -> Result<AsyncSavScanner<ObjectReader>> {
+    let parsed =
+        Url::parse(url).map_err(|e| SpssError::Unsupported(format!("invalid URL {url:?}: {e}")))?;
+    let (scheme, _) = ObjectStoreScheme::parse(&parsed).map_err(object_store::Error::from)?;
+    let options = store_options(scheme, std::env::vars());
+    let (store, path) = object_store::parse_url_opts(&parsed, options)?;
+    scan_sav_object(Arc::from(store), &path, 100_000).await
+}
+
+/// The environment variables meant for the store behind `scheme`, with
+/// their keys lowercased as `object_store` expects.
+fn store_options(
+    scheme: ObjectStoreScheme,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Vec<(String, String)> {
+    let prefix = match scheme {
+        ObjectStoreScheme::AmazonS3 => "aws_",
+        ObjectStoreScheme::GoogleCloudStorage => "google_",
+        ObjectStoreScheme::MicrosoftAzure => "azure_",
+        _ => return Vec::new(),
+    };
+    vars.map(|(key, value)| (key.to_ascii_lowercase(), value))
+        .filter(|(key, _)| key.starts_with(prefix))
+        .collect()
+}
+
+/// Create an async streaming scanner over the object at `path` in an
+/// already configured store.
+pub async fn scan_sav_object(
+    store: Arc<dyn ObjectStore>,
+    path: &Path,
+    batch_size: usize,
+) -> Result<AsyncSavScanner<ObjectReader>> {
+    let meta = store.head(path).await?;
+    let reader = ObjectReader::new(store, &meta);
+    let mut scanner = AsyncSavScanner::open(reader, batch_size).await?;
+    scanner.set_source_path(path.to_string());
+    Ok(scanner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray, StringViewArray};
+    use object_store::memory::InMemory;
+
+    use super::*;
+    use crate::constants::Compression;
+    use crate::test_util;
+
+    fn sav_bytes(compression: Compression) -> Vec<u8> {
+        test_util::sav_bytes(
+            vec![
+                ("id", Arc::new(Float64Array::from_iter_values((0..300).map(f64::from)))),
+                (
+                    "name",
+                    Arc::new(StringArray::from_iter_values(
+                        (0..300).map(|i| format!("row {i}")),
+                    )),
+                ),
+            ],
+            compression,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_scan_in_memory_store() {
+        let store = Arc::new(InMemory::new());
+        for compression in [Compression::None, Compression::Zlib] {
+            let path = Path::from(format!("surveys/{compression:?}.sav"));
+            store
+                .put(&path, sav_bytes(compression).into())
+                .await
+                .unwrap();
+
+            let mut scanner = scan_sav_object(store.clone(), &path, 128).await.unwrap();
+            let batches = scanner.collect_all().await.unwrap();
+            assert_eq!(
+                batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+                vec![128, 128, 44]
+            );
+            let names = batches[2]
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringViewArray>()
+                .unwrap();
+            assert_eq!(names.value(43), "row 299");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_file_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("survey.zsav");
+        std::fs::write(&path, sav_bytes(Compression::Zlib)).unwrap();
+        let url = Url::from_file_path(&path).unwrap();
+
+        let mut scanner = scan_sav_url(url.as_str()).await.unwrap();
+        assert_eq!(scanner.metadata().variable_names, vec!["id", "name"]);
+        let batch = scanner.collect_single().await.unwrap();
+        assert_eq!(batch.num_rows(), 300);
+
+        assert!(matches!(
+            scan_sav_url("not a url").await,
+            Err(SpssError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_store_options() {
+        let vars = || {
+            [
+                ("AWS_REGION", "eu-west-1"),
+                ("GOOGLE_SERVICE_ACCOUNT", "key.json"),
+                ("AZURE_STORAGE_ACCOUNT_NAME", "acct"),
+                ("HOME", "/home/me"),
+                ("SECRET_TOKEN", "hunter2"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        };
+        let options = |scheme| store_options(scheme, vars());
+        assert_eq!(
+            options(ObjectStoreScheme::AmazonS3),
+            vec![("aws_region".to_string(), "eu-west-1".to_string())]
+        );
+        assert_eq!(
+            options(ObjectStoreScheme::GoogleCloudStorage),
+            vec![("google_service_account".to_string(), "key.json".to_string())]
+        );
+        assert_eq!(
+            options(ObjectStoreScheme::MicrosoftAzure),
+            vec![("azure_storage_account_name".to_string(), "acct".to_string())]
+        );
+        assert!(options(ObjectStoreScheme::Local).is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+//! Plain-text metadata reports: the file overview (`summary`) and the
+//! per-variable deep-dive (`describe`).
+
+use std::fmt;
+
+use crate::constants::Measure;
+use crate::metadata::{MissingSpec, SpssMetadata};
+
+impl SpssMetadata {
+    /// Render a formatted overview of the file: file info, variable type
+    /// and measure distribution, and how much of the dictionary is
+    /// annotated. Same text as the `Display` impl.
+    pub fn render_summary(&self) -> String {
+        self.to_string()
+    }
+
+    /// Render detailed metadata for each named variable, separated by
+    /// blank lines. Names not in the metadata are reported as such.
+    pub fn render_describe<S: AsRef<str>>(&self, names: &[S]) -> String {
+        Describe { meta: self, names }.to_string()
+    }
+}
+
+impl fmt::Display for SpssMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self;
+        let ncols = m.number_columns;
+        let rows_str = m
+            .number_rows
+            .map(|n| format_count(n as usize))
+            .unwrap_or_else(|| "unknown".into());
+        let datetime = format_spss_datetime(&m.creation_time, &m.modification_time);
+
+        writeln!(f, "SPSS Metadata Summary")?;
+        writeln!(f, "{}", "\u{2550}".repeat(42))?;
+
+        // File section
+        writeln!(f)?;
+        writeln!(f, "File")?;
+        writeln!(
+            f,
+            "  Label:        {}",
+            if m.file_label.is_empty() {
+                "(none)"
+            } else {
+                &m.file_label
+            }
+        )?;
+        writeln!(f, "  Format:       {}", m.file_format)?;
+        writeln!(f, "  Encoding:     {}", m.file_encoding)?;
+        writeln!(f, "  Created:      {datetime}")?;
+        writeln!(f, "  Rows:         {rows_str}")?;
+        writeln!(f, "  Columns:      {}", format_count(ncols))?;
+        writeln!(
+            f,
+            "  Weight:       {}",
+            m.weight_variable.as_deref().unwrap_or("(none)")
+        )?;
+        if let Some(first) = m.notes.first() {
+            let first_line = first.trim();
+            let preview = match first_line.char_indices().nth(40) {
+                Some((end, _)) => format!("{}...", &first_line[..end]),
+                None => first_line.to_string(),
+            };
+            writeln!(
+                f,
+                "  Notes:        {} record(s) \u{2502} {preview}",
+                m.notes.len()
+            )?;
+        }
+
+        // Variables section
+        let n_string = m
+            .spss_variable_types
+            .values()
+            .filter(|fmt| fmt.starts_with('A'))
+            .count();
+        let n_numeric = m.spss_variable_types.len() - n_string;
+        let pct = |n: usize| -> String {
+            if ncols > 0 {
+                format!("{:>5.1}%", 100.0 * n as f64 / ncols as f64)
+            } else {
+                String::new()
+            }
+        };
+        writeln!(f)?;
+        writeln!(f, "Variables")?;
+        writeln!(
+            f,
+            "  Numeric       {:>5}    {}",
+            format_count(n_numeric),
+            pct(n_numeric)
+        )?;
+        writeln!(
+            f,
+            "  String        {:>5}    {}",
+            format_count(n_string),
+            pct(n_string)
+        )?;
+
+        // Measure level distribution
+        let mut n_nominal = 0usize;
+        let mut n_ordinal = 0usize;
+        let mut n_scale = 0usize;
+        let mut n_unknown = 0usize;
+        for var in &m.variable_names {
+            match m.variable_measure.get(var) {
+                Some(Measure::Nominal) => n_nominal += 1,
+                Some(Measure::Ordinal) => n_ordinal += 1,
+                Some(Measure::Scale) => n_scale += 1,
+                _ => n_unknown += 1,
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "  Nominal       {:>5}", format_count(n_nominal))?;
+        writeln!(f, "  Ordinal       {:>5}", format_count(n_ordinal))?;
+        writeln!(f, "  Scale         {:>5}", format_count(n_scale))?;
+        if n_unknown > 0 {
+            writeln!(f, "  Unknown       {:>5}", format_count(n_unknown))?;
+        }
+
+        // Annotations section
+        let ratio = |n: usize| -> String {
+            if ncols > 0 && n > 0 {
+                format!(
+                    "{:>5} / {:<5}  {:>5.1}%",
+                    format_count(n),
+                    format_count(ncols),
+                    100.0 * n as f64 / ncols as f64
+                )
+            } else {
+                format!("{:>5} / {}", format_count(n), format_count(ncols))
+            }
+        };
+        writeln!(f)?;
+        writeln!(f, "Annotations")?;
+        writeln!(f, "  Labeled:      {}", ratio(m.variable_labels.len()))?;
+        writeln!(
+            f,
+            "  Value labels: {}",
+            ratio(m.variable_value_labels.len())
+        )?;
+        writeln!(f, "  Missing:      {}", ratio(m.variable_missing.len()))?;
+        writeln!(f, "  MR sets:      {:>5}", format_count(m.mr_sets.len()))
+    }
+}
+
+/// `render_describe` output for a list of variables.
+struct Describe<'a, S> {
+    meta: &'a SpssMetadata,
+    names: &'a [S],
+}
+
+impl<S: AsRef<str>> fmt::Display for Describe<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.meta;
+        for (i, name) in self.names.iter().enumerate() {
+            let name = name.as_ref();
+            if i > 0 {
+                writeln!(f)?;
+            }
+            if !m.variable_names.iter().any(|n| n == name) {
+                writeln!(f, "Variable: {name} (not found)")?;
+                continue;
+            }
+
+            let label = m.label(name).unwrap_or("(none)");
+            let fmt = m.format(name).unwrap_or("?");
+            let measure_str = m.measure(name).map(|v| v.as_str()).unwrap_or("?");
+            let align = m
+                .variable_alignment
+                .get(name)
+                .map(|v| v.as_str())
+                .unwrap_or("?");
+            let display_w = m
+                .variable_display_width
+                .get(name)
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "?".into());
+            let storage_w = m
+                .variable_storage_width
+                .get(name)
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "?".into());
+            let type_str = if fmt.starts_with('A') {
+                "String"
+            } else {
+                "Numeric"
+            };
+
+            writeln!(f, "Variable: {name}")?;
+            writeln!(f, "Label:    {label}")?;
+            writeln!(f, "Format:   {fmt:<12}Measure: {measure_str}")?;
+            writeln!(f, "Type:     {type_str:<12}Align:   {align}")?;
+            writeln!(f, "Display:  {display_w:<12}Storage: {storage_w}")?;
+
+            match m.variable_missing.get(name) {
+                Some(specs) if !specs.is_empty() => {
+                    let parts: Vec<String> = specs
+                        .iter()
+                        .map(|s| match s {
+                            MissingSpec::Value(v) => format_f64(*v),
+                            MissingSpec::Range { lo, hi } => {
+                                format!("{} thru {}", format_f64(*lo), format_f64(*hi))
+                            }
+                            MissingSpec::StringValue(s) => format!("{s:?}"),
+                        })
+                        .collect();
+                    writeln!(f, "Missing:  {}", parts.join(", "))?;
+                }
+                _ => writeln!(f, "Missing:  (none)")?,
+            }
+
+            if let Some(labels) = m.variable_value_labels.get(name)
+                && !labels.is_empty()
+            {
+                writeln!(f)?;
+                writeln!(f, "Value Labels ({}):", labels.len())?;
+                for (val, lbl) in labels {
+                    writeln!(f, "  {:<8}{lbl}", val.to_string())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse SPSS header date ("16 Feb 26") + time ("10:38:17") into "2026-02-16 10:38:17".
+pub(crate) fn format_spss_datetime(date_str: &str, time_str: &str) -> String {
+    let parts: Vec<&str> = date_str.split_whitespace().collect();
+    if parts.len() == 3 {
+        let day: u32 = parts[0].parse().unwrap_or(0);
+        let month = match parts[1].to_lowercase().as_str() {
+            "jan" => 1,
+            "feb" => 2,
+            "mar" => 3,
+            "apr" => 4,
+            "may" => 5,
+            "jun" => 6,
+            "jul" => 7,
+            "aug" => 8,
+            "sep" => 9,
+            "oct" => 10,
+            "nov" => 11,
+            "dec" => 12,
+            _ => 0,
+        };
+        let yy: u32 = parts[2].parse().unwrap_or(0);
+        let year = 2000 + yy;
+        if day > 0 && month > 0 {
+            return format!("{year:04}-{month:02}-{day:02} {time_str}");
+        }
+    }
+    // Fallback: just concatenate
+    format!("{date_str} {time_str}")
+}
+
+/// Thousands-separated count, e.g. `12,345`.
+fn format_count(n: usize) -> String {
+    if n >= 1_000_000 {
+        format!("{},{:03},{:03}", n / 1_000_000, (n / 1000) % 1000, n % 1000)
+    } else if n >= 1_000 {
+        format!("{},{:03}", n / 1000, n % 1000)
+    } else {
+        n.to_string()
+    }
+}
+
+fn format_f64(v: f64) -> String {
+    if v.fract() == 0.0 && v.is_finite() {
+        format!("{}", v as i64)
+    } else {
+        format!("{v}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Value;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            file_label: "Omnibus".into(),
+            file_format: "sav".into(),
+            creation_time: "16 Feb 26".into(),
+            modification_time: "10:38:17".into(),
+            number_rows: Some(1500),
+            number_columns: 2,
+            variable_names: vec!["q1".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        meta.spss_variable_types.insert("city".into(), "A20".into());
+        meta.variable_measure.insert("q1".into(), Measure::Nominal);
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta
+    }
+
+    #[test]
+    fn test_render_summary() {
+        let text = metadata().render_summary();
+        assert!(text.starts_with("SPSS Metadata Summary\n"));
+        assert!(text.contains("  Label:        Omnibus\n"));
+        assert!(text.contains("  Created:      2026-02-16 10:38:17\n"));
+        assert!(text.contains("  Rows:         1,500\n"));
+        assert!(text.contains("  Numeric           1     50.0%\n"));
+        assert!(text.contains("  Labeled:          1 / 2       50.0%\n"));
+        assert_eq!(text, metadata().to_string());
+    }
+
+    #[test]
+    fn test_render_describe() {
+        let meta = metadata();
+        let text = meta.render_describe(&["q1", "nope"]);
+        assert!(text.starts_with("Variable: q1\nLabel:    Owns a car\n"));
+        assert!(text.contains("Missing:  9\n"));
+        assert!(text.contains("Value Labels (1):\n  1       Yes\n"));
+        assert!(text.ends_with("\nVariable: nope (not found)\n"));
+    }
+}
@@ -0,0 +1,353 @@
+//! Row index for bytecode-compressed (.sav) and zlib-compressed (.zsav) data.
+//!
+//! Bytecode compression is a stateful stream: control blocks of 8 opcodes
+//! run across row boundaries, so row N can normally only be found by
+//! decoding rows 0..N. A `RowIndex` records the decoder state every
+//! `interval` rows. With it, `SavScanner` can jump close to any row, and
+//! large reads are split at checkpoints and decompressed in parallel.
+//!
+//! Building an index costs one pass over the compressed data (without
+//! materializing any values). It can be saved as a small sidecar file and
+//! loaded on later opens of the same file.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::compression::bytecode::{BytecodeDecompressor, DecoderCheckpoint};
+use crate::error::{Result, SpssError};
+use crate::float_format::NumberFormat;
+
+/// Magic bytes at the start of a sidecar index file.
+const INDEX_MAGIC: &[u8; 8] = b"AMBRIDX1";
+
+/// Checkpoints of a bytecode decoder at every `interval`-th row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowIndex {
+    interval: usize,
+    /// Length of the (decompressed, for .zsav) bytecode stream the index
+    /// was built from; used to reject an index built for another file.
+    data_len: usize,
+    n_rows: usize,
+    /// `checkpoints[i]` is the decoder state before row `i * interval`.
+    checkpoints: Vec<DecoderCheckpoint>,
+}
+
+impl RowIndex {
+    /// Build an index by walking the whole bytecode stream.
+    pub(crate) fn build(
+        data: &[u8],
+        bias: f64,
+        slots_per_row: usize,
+        interval: usize,
+    ) -> Result<RowIndex> {
+        if interval == 0 {
+            return Err(SpssError::Unsupported(
+                "row index interval must be at least 1".to_string(),
+            ));
+        }
+        let mut decompressor = BytecodeDecompressor::new(bias);
+        let mut checkpoints = Vec::new();
+        let mut n_rows = 0;
+        loop {
+            let checkpoint = decompressor.checkpoint();
+            if !decompressor.skip_row(data, slots_per_row)? {
+                break;
+            }
+            if n_rows % interval == 0 {
+                checkpoints.push(checkpoint);
+            }
+            n_rows += 1;
+        }
+        Ok(RowIndex {
+            interval,
+            data_len: data.len(),
+            n_rows,
+            checkpoints,
+        })
+    }
+
+    /// Assemble an index from checkpoints collected by the caller.
+    pub(crate) fn from_checkpoints(
+        interval: usize,
+        data_len: usize,
+        n_rows: usize,
+        checkpoints: Vec<DecoderCheckpoint>,
+    ) -> RowIndex {
+        RowIndex {
+            interval,
+            data_len,
+            n_rows,
+            checkpoints,
+        }
+    }
+
+    /// Rows between checkpoints.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Total number of rows in the indexed data.
+    pub fn n_rows(&self) -> usize {
+        self.n_rows
+    }
+
+    /// Number of checkpoints.
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// Whether this index was built from a bytecode stream of `data_len` bytes.
+    pub(crate) fn matches_data(&self, data_len: usize) -> bool {
+        self.data_len == data_len
+    }
+
+    /// The closest checkpoint at or before `row`, with the row it starts at.
+    pub(crate) fn checkpoint_for(&self, row: usize) -> Option<(usize, &DecoderCheckpoint)> {
+        let i = (row / self.interval).min(self.checkpoints.len().checked_sub(1)?);
+        Some((i * self.interval, &self.checkpoints[i]))
+    }
+
+    /// Decode rows `start..start + count` into raw row bytes, decompressing
+    /// each checkpoint interval on its own rayon task.
+    pub(crate) fn decode_rows_parallel(
+        &self,
+        data: &[u8],
+        bias: f64,
+        numbers: NumberFormat,
+        slots_per_row: usize,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<u8>> {
+        let end = (start + count).min(self.n_rows);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let row_bytes = slots_per_row * 8;
+        let first = start / self.interval;
+        let last = (end - 1) / self.interval;
+
+        let parts = (first..=last)
+            .into_par_iter()
+            .map(|seg| -> Result<Vec<u8>> {
+                let seg_start = seg * self.interval;
+                let from = start.max(seg_start);
+                let to = end.min(seg_start + self.interval);
+
+                let mut decompressor = BytecodeDecompressor::with_format(bias, numbers);
+                decompressor.restore(&self.checkpoints[seg]);
+                for _ in seg_start..from {
+                    decompressor.skip_row(data, slots_per_row)?;
+                }
+                let mut out = vec![0u8; (to - from) * row_bytes];
+                let mut rows = 0;
+                while rows < to - from
+                    && decompressor.decompress_row_raw(
+                        data,
+                        slots_per_row,
+                        &mut out,
+                        rows * row_bytes,
+                    )?
+                {
+                    rows += 1;
+                }
+                out.truncate(rows * row_bytes);
+                Ok(out)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(parts.concat())
+    }
+
+    /// Save the index as a sidecar file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load an index saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<RowIndex> {
+        RowIndex::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Serialize the index (little-endian, fixed-size records).
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(INDEX_MAGIC)?;
+        for v in [
+            self.interval,
+            self.data_len,
+            self.n_rows,
+            self.checkpoints.len(),
+        ] {
+            writer.write_all(&(v as u64).to_le_bytes())?;
+        }
+        for cp in &self.checkpoints {
+            writer.write_all(&(cp.pos as u64).to_le_bytes())?;
+            writer.write_all(&cp.control_bytes)?;
+            writer.write_all(&[cp.control_idx as u8])?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize an index written by `write_to`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<RowIndex> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(SpssError::Unsupported(
+                "not an ambers row index file".to_string(),
+            ));
+        }
+        let read_u64 = |reader: &mut R| -> Result<usize> {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf) as usize)
+        };
+        let interval = read_u64(&mut reader)?;
+        let data_len = read_u64(&mut reader)?;
+        let n_rows = read_u64(&mut reader)?;
+        let count = read_u64(&mut reader)?;
+        if interval == 0 || count != n_rows.div_ceil(interval) {
+            return Err(SpssError::Unsupported(
+                "corrupt row index: checkpoint count does not match row count".to_string(),
+            ));
+        }
+
+        let mut checkpoints = Vec::with_capacity(count);
+        for _ in 0..count {
+            let pos = read_u64(&mut reader)?;
+            let mut control_bytes = [0u8; 8];
+            reader.read_exact(&mut control_bytes)?;
+            let mut idx = [0u8; 1];
+            reader.read_exact(&mut idx)?;
+            if idx[0] > 8 || pos > data_len {
+                return Err(SpssError::Unsupported(
+                    "corrupt row index: checkpoint out of range".to_string(),
+                ));
+            }
+            checkpoints.push(DecoderCheckpoint {
+                pos,
+                control_bytes,
+                control_idx: idx[0] as usize,
+            });
+        }
+        Ok(RowIndex {
+            interval,
+            data_len,
+            n_rows,
+            checkpoints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, Float64Array, StringArray, StringViewArray};
+    use arrow::record_batch::RecordBatch;
+
+    use super::*;
+    use crate::constants::Compression;
+    use crate::scanner::SavScanner;
+    use crate::test_util;
+
+    fn sav_bytes(compression: Compression, n: usize) -> Vec<u8> {
+        test_util::sav_bytes(
+            vec![
+                (
+                    "id",
+                    Arc::new(Float64Array::from_iter_values(
+                        (0..n).map(|i| i as f64 * 1.5),
+                    )),
+                ),
+                (
+                    "text",
+                    Arc::new(StringArray::from_iter_values(
+                        (0..n).map(|i| "x".repeat(i % 13)),
+                    )),
+                ),
+            ],
+            compression,
+        )
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<f64> {
+        let col = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        (0..col.len()).map(|i| col.value(i)).collect()
+    }
+
+    #[test]
+    fn test_indexed_reads_match_sequential() {
+        for compression in [Compression::Bytecode, Compression::Zlib] {
+            let bytes = sav_bytes(compression, 1000);
+            let expected = SavScanner::open(Cursor::new(bytes.clone()), usize::MAX)
+                .unwrap()
+                .collect_single()
+                .unwrap();
+
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 300).unwrap();
+            let index = scanner.build_row_index(64).unwrap();
+            assert_eq!(index.n_rows(), 1000);
+            assert_eq!(index.len(), 16);
+
+            // Parallel batches reassemble into the same data.
+            let batches = scanner.collect_all().unwrap();
+            assert_eq!(batches.len(), 4);
+            let all = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+            assert_eq!(all, expected);
+
+            // Random access lands on the right rows, forwards and backwards.
+            assert_eq!(
+                ids(&scanner.read_rows(700..702).unwrap()),
+                vec![1050.0, 1051.5]
+            );
+            let back = scanner.read_rows(63..66).unwrap();
+            assert_eq!(ids(&back), vec![94.5, 96.0, 97.5]);
+            let text = back
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringViewArray>()
+                .unwrap();
+            assert_eq!(text.value(2), "x".repeat(65 % 13));
+        }
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip_and_mismatch() {
+        let bytes = sav_bytes(Compression::Bytecode, 200);
+        let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), 100).unwrap();
+        let index = scanner.build_row_index(50).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.sav.idx");
+        index.save(&path).unwrap();
+        let loaded = RowIndex::load(&path).unwrap();
+        assert_eq!(loaded, index);
+
+        let mut fresh = SavScanner::open(Cursor::new(bytes), 100).unwrap();
+        fresh.set_row_index(loaded.clone()).unwrap();
+        assert_eq!(ids(&fresh.read_rows(150..151).unwrap()), vec![225.0]);
+
+        let mut other =
+            SavScanner::open(Cursor::new(sav_bytes(Compression::Bytecode, 10)), 100).unwrap();
+        assert!(other.set_row_index(loaded).is_err());
+
+        let mut plain =
+            SavScanner::open(Cursor::new(sav_bytes(Compression::None, 10)), 100).unwrap();
+        assert!(plain.build_row_index(10).is_err());
+    }
+}
@@ -1,32 +1,219 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 
-use arrow::datatypes::{Field, Schema};
+use arrow::datatypes::Schema;
 use arrow::record_batch::RecordBatch;
+use encoding_rs::Encoding;
+use regex::Regex;
 
 use crate::arrow_convert;
 use crate::columnar::ColumnarBatchBuilder;
-use crate::compression::bytecode::BytecodeDecompressor;
-use crate::compression::zlib;
-use crate::constants::Compression;
+use crate::compression::bytecode::{self, BytecodeDecompressor, DecoderCheckpoint};
+use crate::compression::zlib::{self, ZsavBlocks};
+use crate::constants::{Compression, VarType};
 use crate::dictionary::{self, ResolvedDictionary};
 use crate::error::{Result, SpssError};
+use crate::filter::{Predicate, RowFilter, RowView, Sampling};
 use crate::header;
 use crate::io_utils::SavReader;
 use crate::metadata::SpssMetadata;
+#[cfg(feature = "mmap")]
+use crate::mmap::MmapSource;
+use crate::options::{
+    BadRowPolicy, Float32Mode, OutputOptions, ReadLimits, ScanOptions, TruncationPolicy, in_pool,
+};
+use crate::row_index::RowIndex;
+use crate::stats::ScanStatistics;
+use crate::warning::{SpssWarning, WarningKind};
 
 /// Compression-specific state for the scanner.
 enum ScanState {
     Uncompressed,
     Bytecode {
-        data: Vec<u8>,
+        data: ByteSource,
         decompressor: BytecodeDecompressor,
     },
+    /// ZSAV blocks are inflated lazily as rows are consumed; the decoder's
+    /// position is relative to the blocks' current window.
     Zlib {
-        data: Vec<u8>,
+        blocks: ZsavBlocks,
         decompressor: BytecodeDecompressor,
     },
 }
 
+/// Bytecode-compressed data: read into memory, or a slice of a memory map.
+enum ByteSource {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped { map: MmapSource, start: usize },
+}
+
+impl std::ops::Deref for ByteSource {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ByteSource::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            ByteSource::Mapped { map, start } => &map.as_ref()[*start..],
+        }
+    }
+}
+
+/// A memory map the scanner can decode from directly (never constructed
+/// without the `mmap` feature).
+#[cfg(feature = "mmap")]
+type MapHandle = MmapSource;
+#[cfg(not(feature = "mmap"))]
+type MapHandle = std::convert::Infallible;
+
+impl ScanState {
+    /// Decode the next row into `out[offset..]`. Returns false at the end
+    /// of the data. Must not be called on `Uncompressed`.
+    #[inline]
+    fn next_row<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        slots_per_row: usize,
+        out: &mut [u8],
+        offset: usize,
+    ) -> Result<bool> {
+        match self {
+            ScanState::Uncompressed => Ok(false),
+            ScanState::Bytecode { data, decompressor } => {
+                decompressor.decompress_row_raw(data, slots_per_row, out, offset)
+            }
+            ScanState::Zlib {
+                blocks,
+                decompressor,
+            } => {
+                ensure_window(blocks, decompressor, reader, slots_per_row)?;
+                decompressor.set_base(blocks.window_start());
+                decompressor.decompress_row_raw(blocks.window(), slots_per_row, out, offset)
+            }
+        }
+    }
+
+    /// Skip the next row without decoding its values.
+    fn skip_row<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        slots_per_row: usize,
+    ) -> Result<bool> {
+        match self {
+            ScanState::Uncompressed => Ok(false),
+            ScanState::Bytecode { data, decompressor } => {
+                decompressor.skip_row(data, slots_per_row)
+            }
+            ScanState::Zlib {
+                blocks,
+                decompressor,
+            } => {
+                ensure_window(blocks, decompressor, reader, slots_per_row)?;
+                decompressor.set_base(blocks.window_start());
+                decompressor.skip_row(blocks.window(), slots_per_row)
+            }
+        }
+    }
+
+    /// Decoder position as an offset into the whole bytecode stream.
+    fn checkpoint(&self) -> DecoderCheckpoint {
+        match self {
+            ScanState::Uncompressed => BytecodeDecompressor::new(0.0).checkpoint(),
+            ScanState::Bytecode { decompressor, .. } => decompressor.checkpoint(),
+            ScanState::Zlib {
+                blocks,
+                decompressor,
+            } => {
+                let mut checkpoint = decompressor.checkpoint();
+                checkpoint.pos += blocks.window_start();
+                checkpoint
+            }
+        }
+    }
+
+    /// Resume from a position taken with `checkpoint`.
+    fn restore<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        checkpoint: &DecoderCheckpoint,
+    ) -> Result<()> {
+        match self {
+            ScanState::Uncompressed => {}
+            ScanState::Bytecode { decompressor, .. } => decompressor.restore(checkpoint),
+            ScanState::Zlib {
+                blocks,
+                decompressor,
+            } => {
+                let pos = blocks.seek(reader, checkpoint.pos)?;
+                decompressor.restore(&DecoderCheckpoint { pos, ..*checkpoint });
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of the (inflated) bytecode stream.
+    fn data_len(&self) -> usize {
+        match self {
+            ScanState::Uncompressed => 0,
+            ScanState::Bytecode { data, .. } => data.len(),
+            ScanState::Zlib { blocks, .. } => blocks.total_len(),
+        }
+    }
+
+    /// Move the decoder from file row `*file_row` to `target`, starting from
+    /// the nearest row index checkpoint when that saves decoding (and
+    /// rewinding to the start when going backwards without an index).
+    fn seek_row<R: Read + Seek>(
+        &mut self,
+        reader: &mut SavReader<R>,
+        index: Option<&RowIndex>,
+        file_row: &mut usize,
+        target: usize,
+        slots_per_row: usize,
+    ) -> Result<()> {
+        match index.and_then(|idx| idx.checkpoint_for(target)) {
+            Some((cp_row, checkpoint)) if target < *file_row || cp_row > *file_row => {
+                self.restore(reader, checkpoint)?;
+                *file_row = cp_row;
+            }
+            None if target < *file_row => {
+                let start = BytecodeDecompressor::new(0.0).checkpoint();
+                self.restore(reader, &start)?;
+                *file_row = 0;
+            }
+            _ => {}
+        }
+        while *file_row < target {
+            if !self.skip_row(reader, slots_per_row)? {
+                break;
+            }
+            *file_row += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Make sure the ZSAV window holds enough inflated bytes for a whole row,
+/// dropping the bytes already decoded.
+#[inline]
+fn ensure_window<R: Read + Seek>(
+    blocks: &mut ZsavBlocks,
+    decompressor: &mut BytecodeDecompressor,
+    reader: &mut SavReader<R>,
+    slots_per_row: usize,
+) -> Result<()> {
+    // Worst case per row: every slot raw (8 data bytes + 1 opcode), plus a
+    // partially used control block carried over from the previous row.
+    let max_row_bytes = slots_per_row * 9 + 16;
+    let pos = decompressor.pos();
+    if blocks.window().len() - pos < max_row_bytes && !blocks.exhausted() {
+        blocks.refill(reader, pos, max_row_bytes)?;
+        decompressor.rebase(pos);
+    }
+    Ok(())
+}
+
 /// A streaming reader for SPSS .sav/.zsav files.
 ///
 /// Reads metadata immediately on construction. Data is read on demand
@@ -38,60 +225,245 @@ pub struct SavScanner<R: Read + Seek> {
     batch_size: usize,
     projection: Option<Vec<usize>>,
     row_limit: Option<usize>,
+    filter: Option<RowFilter>,
+    row_index: Option<RowIndex>,
     rows_read: usize,
+    /// Index of the next row in the file (the decoder position). Differs
+    /// from `rows_read` when filtering or after `read_rows`.
+    file_row: usize,
+    /// Byte offset of the first case (the start of the data).
+    data_start: u64,
+    /// Cases estimated from the size of the data, for files that do not
+    /// declare a case count.
+    rows_hint: Option<usize>,
     state: ScanState,
     eof: bool,
+    progress: Option<ProgressFn>,
+    output: OutputOptions,
+    statistics: Option<ScanStatistics>,
+    on_truncation: TruncationPolicy,
+    truncation: Option<Truncation>,
+    strict: bool,
+    /// Whether the case count has been checked against the declared one.
+    case_count_checked: bool,
+}
+
+/// Where the data of a file read with `TruncationPolicy::Partial` ended
+/// early.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Truncation {
+    /// Whole cases decoded before the data ended.
+    pub rows: usize,
+    /// Cases declared in the header, when known.
+    pub expected: Option<usize>,
+    /// Whether the data stops partway through a case, rather than after
+    /// the last whole one.
+    pub mid_case: bool,
+    /// What was wrong with the rest of the data.
+    pub message: String,
+}
+
+impl std::fmt::Display for Truncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.expected {
+            Some(expected) => write!(f, "data ends after {} of {expected} cases", self.rows)?,
+            None => write!(f, "data ends after {} cases", self.rows)?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Which slots of a case hold string bytes.
+fn string_slots(dict: &ResolvedDictionary) -> Vec<bool> {
+    let mut strings = vec![false; dict.header.nominal_case_size.max(0) as usize];
+    let mut vars = dict.variables.iter().peekable();
+    while let Some(var) = vars.next() {
+        if let VarType::String(_) = var.var_type {
+            let end = vars.peek().map_or(strings.len(), |next| next.slot_index);
+            if let Some(slots) = strings.get_mut(var.slot_index..end) {
+                slots.fill(true);
+            }
+        }
+    }
+    strings
 }
 
+/// Whether `err` means the data ran out, as opposed to being malformed.
+fn is_truncation(err: &SpssError) -> bool {
+    match err {
+        SpssError::TruncatedFile { .. } => true,
+        SpssError::Io(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+/// Handle the data running out after `rows` cases, cut in the middle of a
+/// case when there is an `err`. Fails with `err` under
+/// `TruncationPolicy::Fail`; otherwise reports where the data ended when
+/// that is short of the `expected` cases.
+fn end_of_data(
+    policy: TruncationPolicy,
+    rows: usize,
+    expected: Option<usize>,
+    err: Option<SpssError>,
+) -> Result<Option<Truncation>> {
+    let mid_case = err.is_some();
+    let message = match err {
+        Some(err) if policy == TruncationPolicy::Fail => return Err(err),
+        Some(err) => err.to_string(),
+        None if policy == TruncationPolicy::Partial && expected.is_some_and(|n| rows < n) => {
+            "end of file".to_string()
+        }
+        None => return Ok(None),
+    };
+    Ok(Some(Truncation {
+        rows,
+        expected,
+        mid_case,
+        message,
+    }))
+}
+
+/// Progress callback: `(rows_read, total_rows, bytes_read)`.
+type ProgressFn = Box<dyn FnMut(usize, Option<usize>, u64) + Send + Sync>;
+
 impl<R: Read + Seek> SavScanner<R> {
     /// Open a scanner from a reader. Parses the header and dictionary immediately.
     pub fn open(reader: R, batch_size: usize) -> Result<Self> {
+        Self::open_inner(reader, batch_size, &ReadLimits::default(), None, false)
+    }
+
+    /// Open a scanner on a file that may be hostile: the dictionary must
+    /// stay within `limits`.
+    pub fn open_with_limits(reader: R, batch_size: usize, limits: &ReadLimits) -> Result<Self> {
+        Self::open_inner(reader, batch_size, limits, None, false)
+    }
+
+    /// Open a scanner configured by `options`.
+    pub fn open_with(reader: R, options: &ScanOptions) -> Result<Self> {
+        let mut scanner = Self::open(reader, options.batch_size)?;
+        scanner.apply_options(options)?;
+        Ok(scanner)
+    }
+
+    /// Open a scanner; with `map`, compressed data is decoded from the
+    /// mapping instead of being read into memory. With `stream`, .zsav
+    /// blocks are inflated in file order without reading the trailer, so the
+    /// reader only ever moves forward.
+    fn open_inner(
+        reader: R,
+        batch_size: usize,
+        limits: &ReadLimits,
+        map: Option<MapHandle>,
+        stream: bool,
+    ) -> Result<Self> {
         let mut sav_reader = SavReader::new(reader);
+        sav_reader.set_limits(*limits);
 
         let file_header = header::FileHeader::parse(&mut sav_reader)?;
-        let raw_dict = dictionary::parse_dictionary(&mut sav_reader, &file_header)?;
+        let mut raw_dict = dictionary::parse_dictionary(&mut sav_reader, &file_header)?;
+        // Cases hold one slot per variable record, whatever the header says
+        // (some writers leave it -1).
+        let slots_per_row = raw_dict.variables.len();
+        raw_dict.header.nominal_case_size = i32::try_from(slots_per_row)
+            .map_err(|_| SpssError::LimitExceeded(format!("{slots_per_row} variable records")))?;
         let compression = raw_dict.header.compression;
-        let bias = raw_dict.header.bias;
-        let slots_per_row = raw_dict.header.nominal_case_size as usize;
-        let ncases = if raw_dict.header.ncases >= 0 {
-            Some(raw_dict.header.ncases as usize)
-        } else {
-            None
-        };
         let dict = dictionary::resolve_dictionary(raw_dict)?;
+        let (bias, numbers) = (dict.header.bias, dict.numbers);
+        let ncases = dict.ncases;
+
+        let data_start = sav_reader.inner_mut().stream_position()?;
+        if compression != Compression::None {
+            bytecode::check_bias(bias)?;
+        }
 
         // Set up compression-specific state
         let state = match compression {
             Compression::None => ScanState::Uncompressed,
             Compression::Bytecode => {
-                let estimated_size = ncases.unwrap_or(1000) * slots_per_row * 8;
-                let mut compressed_data = Vec::with_capacity(estimated_size);
-                sav_reader.inner_mut().read_to_end(&mut compressed_data)?;
+                let data = match map {
+                    #[cfg(feature = "mmap")]
+                    Some(map) => ByteSource::Mapped {
+                        map,
+                        start: data_start as usize,
+                    },
+                    #[cfg(not(feature = "mmap"))]
+                    Some(never) => match never {},
+                    None => {
+                        let estimated_size = ncases
+                            .unwrap_or(1000)
+                            .saturating_mul(slots_per_row * 8)
+                            .min(1 << 30);
+                        let mut compressed_data = Vec::with_capacity(estimated_size);
+                        sav_reader.inner_mut().read_to_end(&mut compressed_data)?;
+                        ByteSource::Owned(compressed_data)
+                    }
+                };
                 ScanState::Bytecode {
-                    data: compressed_data,
-                    decompressor: BytecodeDecompressor::new(bias),
+                    data,
+                    decompressor: BytecodeDecompressor::with_format(bias, numbers),
                 }
             }
             Compression::Zlib => {
                 let zheader = zlib::read_zheader(&mut sav_reader)?;
-                let ztrailer = zlib::read_ztrailer(&mut sav_reader, &zheader)?;
-                let bytecode_data = zlib::decompress_zsav_blocks(&mut sav_reader, &ztrailer)?;
+                let blocks = if stream {
+                    let start = sav_reader.inner_mut().stream_position()?;
+                    ZsavBlocks::inline(start, zheader.ztrailer_offset as u64)
+                } else {
+                    let ztrailer = zlib::read_ztrailer(&mut sav_reader, &zheader)?;
+                    ztrailer.check_bias(bias)?;
+                    match map {
+                        #[cfg(feature = "mmap")]
+                        Some(map) => ZsavBlocks::new(&ztrailer).with_mapped(Box::new(map)),
+                        #[cfg(not(feature = "mmap"))]
+                        Some(never) => match never {},
+                        None => ZsavBlocks::new(&ztrailer),
+                    }
+                };
                 ScanState::Zlib {
-                    data: bytecode_data,
-                    decompressor: BytecodeDecompressor::new(bias),
+                    blocks,
+                    decompressor: BytecodeDecompressor::with_format(bias, numbers),
                 }
             }
         };
 
+        // Without a declared case count, size batches from the data itself
+        let row_bytes = (slots_per_row as u64 * 8).max(1);
+        let data_len = match &state {
+            _ if ncases.is_some() => None,
+            ScanState::Uncompressed if !stream => {
+                let end = sav_reader.inner_mut().seek(SeekFrom::End(0))?;
+                sav_reader.inner_mut().seek(SeekFrom::Start(data_start))?;
+                Some(end.saturating_sub(data_start))
+            }
+            ScanState::Uncompressed => None,
+            ScanState::Bytecode { data, .. } => Some(data.len() as u64),
+            ScanState::Zlib { .. } if stream => None,
+            ScanState::Zlib { blocks, .. } => Some(blocks.total_len() as u64),
+        };
+        let rows_hint = data_len.map(|len| usize::try_from(len / row_bytes).unwrap_or(usize::MAX));
+
         Ok(SavScanner {
             sav_reader,
             dict,
             batch_size,
             projection: None,
             row_limit: None,
+            filter: None,
+            row_index: None,
             rows_read: 0,
+            file_row: 0,
+            data_start,
+            rows_hint,
             state,
             eof: false,
+            progress: None,
+            output: OutputOptions::default(),
+            statistics: None,
+            on_truncation: TruncationPolicy::Fail,
+            truncation: None,
+            strict: false,
+            case_count_checked: false,
         })
     }
 
@@ -100,20 +472,140 @@ impl<R: Read + Seek> SavScanner<R> {
         &self.dict.metadata
     }
 
-    /// Get the Arrow schema (respects column projection if set).
+    /// Record where the data came from, reported as `spss.source_path` in
+    /// the schema metadata.
+    pub(crate) fn set_source_path(&mut self, path: String) {
+        self.output.source_path = Some(path);
+    }
+
+    /// Get the Arrow schema (respects column projection and scan options).
     pub fn schema(&self) -> Schema {
-        if let Some(ref proj) = self.projection {
-            let fields: Vec<Field> = proj
+        arrow_convert::output_schema(&self.dict, self.projection.as_deref(), &self.output)
+    }
+
+    /// Apply `options` to a freshly opened scanner: batch size, projection,
+    /// limit, offset, and the output settings (missing values, string type,
+    /// temporal handling, thread count).
+    pub fn apply_options(&mut self, options: &ScanOptions) -> Result<()> {
+        if options.strict
+            && let Some((given, original)) = self.dict.metadata.renamed_variables.first()
+        {
+            return Err(SpssError::InvalidVariable(format!(
+                "two variables are named {original:?}; the second was read as {given:?}"
+            )));
+        }
+        if options.strict
+            && let Some((name, code)) = self.dict.metadata.unknown_formats.first()
+        {
+            let code = *code as u32;
+            return Err(SpssError::InvalidVariable(format!(
+                "variable {name:?} has a print format of unknown type {} (width {}, decimals {})",
+                (code >> 16) & 0xFF,
+                (code >> 8) & 0xFF,
+                code & 0xFF
+            )));
+        }
+        if options.names_as_stored {
+            let renames = std::mem::take(&mut self.dict.stored_names);
+            for var in &mut self.dict.variables {
+                if let Some(name) = renames.get(&var.long_name) {
+                    var.long_name = name.clone();
+                }
+            }
+            self.dict.metadata.rename_variables(&renames);
+        }
+        #[cfg(feature = "nfc")]
+        if options.normalize_nfc {
+            self.dict.metadata.normalize_nfc();
+        }
+        self.batch_size = options.batch_size;
+        if let Some(columns) = &options.columns {
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            self.select(&columns)?;
+        }
+        self.row_limit = options.limit;
+        if let Float32Mode::Columns(columns) = &options.float32
+            && let Some(col) = columns
+                .iter()
+                .find(|c| !self.dict.variables.iter().any(|v| &v.long_name == *c))
+        {
+            return Err(SpssError::InvalidVariable(format!("column not found: {col:?}")));
+        }
+        if let Some(col) = options
+            .run_end_encoded
+            .iter()
+            .find(|c| !self.dict.variables.iter().any(|v| &v.long_name == *c))
+        {
+            return Err(SpssError::InvalidVariable(format!("column not found: {col:?}")));
+        }
+        if options.strict_encoding
+            && let Some(warning) = self
+                .dict
+                .metadata
+                .warnings
+                .iter()
+                .find(|w| w.kind == WarningKind::LossyText)
+        {
+            return Err(SpssError::Encoding(warning.to_string()));
+        }
+        let mut encodings = Vec::with_capacity(options.encodings.len());
+        for (col, label) in &options.encodings {
+            match self.dict.variables.iter().find(|v| &v.long_name == col) {
+                None => {
+                    return Err(SpssError::InvalidVariable(format!("column not found: {col:?}")));
+                }
+                Some(var) if var.var_type == VarType::Numeric => {
+                    return Err(SpssError::InvalidVariable(format!(
+                        "{col:?} is numeric; only string variables take an encoding"
+                    )));
+                }
+                Some(_) => {}
+            }
+            let encoding = Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+                SpssError::Encoding(format!("unknown encoding {label:?} for {col:?}"))
+            })?;
+            encodings.push((col.clone(), encoding));
+        }
+        self.dict.encodings = encodings;
+        if let Some(name) = &options.case_numbers
+            && self.dict.variables.iter().any(|v| &v.long_name == name)
+        {
+            return Err(SpssError::InvalidVariable(format!(
+                "case-number column {name:?} clashes with a variable"
+            )));
+        }
+        self.output = options.output()?;
+        if options.sanitize_names {
+            let names = arrow_convert::sanitized_names(
+                self.dict.variables.iter().map(|v| v.long_name.as_str()),
+            );
+            self.dict.metadata.renamed_columns = self
+                .dict
+                .variables
                 .iter()
-                .map(|&idx| {
-                    let var = &self.dict.variables[idx];
-                    Field::new(&var.long_name, arrow_convert::var_to_arrow_type(var), true)
-                })
+                .zip(&names)
+                .filter(|(var, name)| &var.long_name != *name)
+                .map(|(var, name)| (name.clone(), var.long_name.clone()))
                 .collect();
-            Schema::new(fields)
-        } else {
-            arrow_convert::build_schema(&self.dict)
+            self.output.column_names = Some(names);
+        }
+        if let ScanState::Zlib { blocks, .. } = &mut self.state {
+            blocks.set_pool(self.output.pool.clone());
+        }
+        if options.statistics {
+            self.collect_statistics();
+        }
+        self.on_truncation = options.on_truncation;
+        if let ScanState::Bytecode { decompressor, .. } | ScanState::Zlib { decompressor, .. } =
+            &mut self.state
+        {
+            decompressor.set_bad_rows(options.bad_rows, &string_slots(&self.dict));
         }
+        self.strict = options.strict;
+        if options.offset > 0 {
+            self.skip(options.offset)?;
+        }
+        Ok(())
     }
 
     /// Set column projection — only these columns will be read and returned.
@@ -135,11 +627,218 @@ impl<R: Read + Seek> SavScanner<R> {
         Ok(())
     }
 
+    /// Set column projection, matching names case-insensitively as SPSS
+    /// does. Returns the canonical names the columns resolved to, in the
+    /// order given. An exact match wins if names differ only in case.
+    pub fn select_ci(&mut self, columns: &[&str]) -> Result<Vec<String>> {
+        let variables = &self.dict.variables;
+        let mut indices = Vec::with_capacity(columns.len());
+        for &col in columns {
+            let idx = match variables.iter().position(|v| v.long_name == col) {
+                Some(idx) => idx,
+                None => {
+                    let lower = col.to_lowercase();
+                    let mut found = variables
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, v)| v.long_name.to_lowercase() == lower)
+                        .map(|(i, _)| i);
+                    match (found.next(), found.next()) {
+                        (Some(idx), None) => idx,
+                        (Some(_), Some(_)) => {
+                            return Err(SpssError::InvalidVariable(format!(
+                                "column name is ambiguous: {col:?}"
+                            )));
+                        }
+                        (None, _) => {
+                            return Err(SpssError::InvalidVariable(format!(
+                                "column not found: {col:?}"
+                            )));
+                        }
+                    }
+                }
+            };
+            indices.push(idx);
+        }
+        let names = indices
+            .iter()
+            .map(|&i| variables[i].long_name.clone())
+            .collect();
+        self.projection = Some(indices);
+        Ok(names)
+    }
+
+    /// Set column projection by position (0-based, in dictionary order).
+    /// Returns an error if any index is out of range.
+    pub fn select_by_index(&mut self, indices: &[usize]) -> Result<()> {
+        let n = self.dict.variables.len();
+        if let Some(&bad) = indices.iter().find(|&&i| i >= n) {
+            return Err(SpssError::InvalidVariable(format!(
+                "column index {bad} out of range ({n} columns)"
+            )));
+        }
+        self.projection = Some(indices.to_vec());
+        Ok(())
+    }
+
+    /// Set column projection to every column whose name matches the regular
+    /// expression `pattern`, in dictionary order. Returns an error if the
+    /// pattern is invalid or matches no columns.
+    pub fn select_matching(&mut self, pattern: &str) -> Result<()> {
+        let re = Regex::new(pattern)
+            .map_err(|e| SpssError::InvalidVariable(format!("invalid pattern {pattern:?}: {e}")))?;
+        let indices: Vec<usize> = self
+            .dict
+            .variables
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| re.is_match(&v.long_name))
+            .map(|(i, _)| i)
+            .collect();
+        if indices.is_empty() {
+            return Err(SpssError::InvalidVariable(format!(
+                "no columns match {pattern:?}"
+            )));
+        }
+        self.projection = Some(indices);
+        Ok(())
+    }
+
     /// Set a row limit — stop reading after this many rows.
     pub fn limit(&mut self, n: usize) {
         self.row_limit = Some(n);
     }
 
+    /// Discard the next `n` rows of the file without building them.
+    ///
+    /// Uncompressed files seek past the rows; compressed files step the
+    /// decoder over them (starting from the nearest row index checkpoint
+    /// when one is attached). Skipped rows are file rows, whether or not
+    /// they match a filter, and do not count towards `rows_read()` or the
+    /// row limit. Skipping past the end leaves the scanner at the end.
+    pub fn skip(&mut self, n: usize) -> Result<()> {
+        let mut target = self.file_row.saturating_add(n);
+        if let Some(ncases) = self.dict.ncases {
+            target = target.min(ncases);
+        }
+        self.seek_row(target)
+    }
+
+    /// Only return rows matching `predicate`.
+    ///
+    /// Rows are tested on their raw slots before any Arrow conversion, so
+    /// non-matching rows are never materialized. Calling `filter` (or
+    /// `filter_fn`) again adds a further condition; all must hold. Row
+    /// limits and batch sizes count matching rows.
+    pub fn filter(&mut self, predicate: Predicate) -> Result<()> {
+        let filter = self.filter.get_or_insert_with(RowFilter::new);
+        filter.add_predicate(&self.dict, &predicate)
+    }
+
+    /// Only return every `k`-th row of the file: rows 0, k, 2k, ...
+    ///
+    /// Rows are counted by their position in the file, so the sample does
+    /// not depend on other filters; combined with them, a row must be both
+    /// sampled and matching.
+    pub fn every_nth(&mut self, k: usize) -> Result<()> {
+        if k == 0 {
+            return Err(SpssError::Unsupported(
+                "every_nth: k must be at least 1".to_string(),
+            ));
+        }
+        self.filter
+            .get_or_insert_with(RowFilter::new)
+            .add_sampling(Sampling::EveryNth(k));
+        Ok(())
+    }
+
+    /// Only return a random sample of about `fraction` of the rows.
+    ///
+    /// Each row is kept independently, based on a hash of its row number
+    /// and `seed`, so the same seed selects the same rows on every read.
+    pub fn sample(&mut self, fraction: f64, seed: u64) -> Result<()> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(SpssError::Unsupported(format!(
+                "sample: fraction must be between 0 and 1, got {fraction}"
+            )));
+        }
+        self.filter
+            .get_or_insert_with(RowFilter::new)
+            .add_sampling(Sampling::Random { fraction, seed });
+        Ok(())
+    }
+
+    /// Only return rows for which `f` returns true.
+    ///
+    /// `columns` names the variables the closure needs; inside the closure
+    /// they are read by position with `RowView::numeric(i)` and
+    /// `RowView::string(i)`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let mut scanner = ambers::scan_sav("survey.sav").unwrap();
+    /// scanner
+    ///     .filter_fn(&["income", "household"], |row| {
+    ///         match (row.numeric(0), row.numeric(1)) {
+    ///             (Some(income), Some(size)) => income / size > 20_000.0,
+    ///             _ => false,
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn filter_fn<F>(&mut self, columns: &[&str], f: F) -> Result<()>
+    where
+        F: Fn(&RowView) -> bool + Send + Sync + 'static,
+    {
+        let filter = self.filter.get_or_insert_with(RowFilter::new);
+        filter.add_closure(&self.dict, columns, Box::new(f))
+    }
+
+    /// Register a callback invoked after each batch with
+    /// `(rows_read, total_rows, bytes_read)`. `total_rows` is the case count
+    /// from the file header, when the writer recorded one; `bytes_read` is
+    /// the offset reached in the file (see `bytes_read()`).
+    ///
+    /// # Example
+    /// ```no_run
+    /// let mut scanner = ambers::scan_sav("survey.sav").unwrap();
+    /// scanner.on_progress(|rows, total, _bytes| {
+    ///     if let Some(total) = total {
+    ///         eprint!("\r{:.0}%", rows as f64 * 100.0 / total as f64);
+    ///     }
+    /// });
+    /// let batches = scanner.collect_all().unwrap();
+    /// ```
+    pub fn on_progress<F>(&mut self, f: F)
+    where
+        F: FnMut(usize, Option<usize>, u64) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(f));
+    }
+
+    /// How far into the file the scanner has consumed data, in bytes
+    /// (compressed bytes for .zsav and compressed .sav files).
+    pub fn bytes_read(&self) -> u64 {
+        match &self.state {
+            ScanState::Uncompressed => {
+                let row_bytes = self.dict.header.nominal_case_size as u64 * 8;
+                self.data_start + self.file_row as u64 * row_bytes
+            }
+            ScanState::Bytecode { decompressor, .. } => {
+                self.data_start + decompressor.pos() as u64
+            }
+            ScanState::Zlib { blocks, .. } => blocks.next_compressed_offset(),
+        }
+    }
+
+    fn report_progress(&mut self) {
+        let bytes_read = self.bytes_read();
+        let total = self.dict.ncases;
+        if let Some(progress) = &mut self.progress {
+            progress(self.rows_read, total, bytes_read);
+        }
+    }
+
     /// Read the next batch of rows, returning a RecordBatch.
     /// Returns Ok(None) when no more data is available.
     pub fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
@@ -155,7 +854,7 @@ impl<R: Read + Seek> SavScanner<R> {
         };
         let n_rows = remaining.min(self.batch_size);
 
-        let batch = self.read_batch_columnar(n_rows)?;
+        let batch = self.read_batch_columnar(n_rows, usize::MAX)?;
         match batch {
             Some(ref b) => {
                 let num_rows = b.num_rows();
@@ -164,6 +863,10 @@ impl<R: Read + Seek> SavScanner<R> {
                     return Ok(None);
                 }
                 self.rows_read += num_rows;
+                if let Some(stats) = &mut self.statistics {
+                    stats.update(b)?;
+                }
+                self.report_progress();
             }
             None => {
                 self.eof = true;
@@ -182,31 +885,19 @@ impl<R: Read + Seek> SavScanner<R> {
             None => usize::MAX,
         };
 
-        match self.read_batch_columnar(remaining)? {
+        match self.read_batch_columnar(remaining, usize::MAX)? {
             Some(batch) => {
                 self.rows_read += batch.num_rows();
                 self.eof = true;
+                if let Some(stats) = &mut self.statistics {
+                    stats.update(&batch)?;
+                }
+                self.report_progress();
                 Ok(batch)
             }
             None => {
                 self.eof = true;
-                let schema = if let Some(ref proj) = self.projection {
-                    let fields: Vec<Field> = proj
-                        .iter()
-                        .map(|&idx| {
-                            let var = &self.dict.variables[idx];
-                            Field::new(
-                                &var.long_name,
-                                arrow_convert::var_to_arrow_type(var),
-                                true,
-                            )
-                        })
-                        .collect();
-                    Schema::new(fields)
-                } else {
-                    arrow_convert::build_schema(&self.dict)
-                };
-                Ok(RecordBatch::new_empty(std::sync::Arc::new(schema)))
+                Ok(RecordBatch::new_empty(std::sync::Arc::new(self.schema())))
             }
         }
     }
@@ -220,32 +911,316 @@ impl<R: Read + Seek> SavScanner<R> {
         Ok(batches)
     }
 
+    /// Read the file rows `rows` (0-based, end-exclusive) as one RecordBatch.
+    ///
+    /// Uncompressed files seek straight to the first row. Compressed files
+    /// decode forward from the current position, rewinding to the start of
+    /// the data first if `rows.start` lies behind it.
+    ///
+    /// Column projection and row filters apply; the row limit does not, and
+    /// the rows do not count towards `rows_read()`. Afterwards the scanner
+    /// is positioned at `rows.end`, so `next_batch()` continues from there.
+    /// A range past the end of the file yields fewer (or zero) rows.
+    pub fn read_rows(&mut self, rows: Range<usize>) -> Result<RecordBatch> {
+        self.seek_row(rows.start)?;
+        self.eof = false;
+        let len = rows.end.saturating_sub(rows.start);
+        match self.read_batch_columnar(usize::MAX, len)? {
+            Some(batch) => Ok(batch),
+            None => Ok(RecordBatch::new_empty(std::sync::Arc::new(self.schema()))),
+        }
+    }
+
+    /// Rewind to the first row so the data can be read again without
+    /// reopening the file and re-parsing the dictionary.
+    ///
+    /// Resets `rows_read()`, the end-of-data state (including `truncation()`)
+    /// and any collected statistics; column projection, filters, the row
+    /// limit and any row index are kept.
+    pub fn reset(&mut self) -> Result<()> {
+        self.seek_row(0)?;
+        self.rows_read = 0;
+        self.eof = false;
+        self.truncation = None;
+        self.case_count_checked = false;
+        if let Some(stats) = &mut self.statistics {
+            *stats = ScanStatistics::default();
+        }
+        Ok(())
+    }
+
+    /// Read the last `n` rows of the file.
+    ///
+    /// Uncompressed files seek straight to the first of them. Compressed
+    /// files step the decoder over the earlier rows without building them,
+    /// or, with a row index attached (see `build_row_index`), jump to the
+    /// checkpoint before them so only the final blocks are decoded.
+    ///
+    /// Like `read_rows`, column projection and row filters apply and the
+    /// scanner is left at the end of the file.
+    pub fn tail(&mut self, n: usize) -> Result<RecordBatch> {
+        let total = self.total_rows()?;
+        self.read_rows(total.saturating_sub(n)..total)
+    }
+
+    /// Number of rows in the file: the header's case count when the writer
+    /// recorded one, else measured (which for compressed files without a
+    /// row index means decoding to the end).
+    fn total_rows(&mut self) -> Result<usize> {
+        if let Some(ncases) = self.dict.ncases {
+            return Ok(ncases);
+        }
+        if let Some(index) = &self.row_index {
+            return Ok(index.n_rows());
+        }
+        if let ScanState::Uncompressed = self.state {
+            let row_bytes = self.dict.header.nominal_case_size as u64 * 8;
+            let end = self.sav_reader.inner_mut().seek(SeekFrom::End(0))?;
+            let rows = end.saturating_sub(self.data_start) / row_bytes.max(1);
+            self.seek_row(self.file_row)?;
+            return Ok(rows as usize);
+        }
+        self.seek_row(usize::MAX)?;
+        Ok(self.file_row)
+    }
+
+    /// Position the decoder at file row `row`.
+    fn seek_row(&mut self, row: usize) -> Result<()> {
+        let slots_per_row = self.dict.header.nominal_case_size as usize;
+        match &mut self.state {
+            ScanState::Uncompressed => {
+                // Without a case count, stop at the end of the data rather
+                // than past it
+                let row = self.rows_hint.map_or(row, |rows| row.min(rows));
+                let offset = (row as u64)
+                    .saturating_mul(slots_per_row as u64 * 8)
+                    .saturating_add(self.data_start);
+                self.sav_reader.inner_mut().seek(SeekFrom::Start(offset))?;
+                self.file_row = row;
+            }
+            state => state.seek_row(
+                &mut self.sav_reader,
+                self.row_index.as_ref(),
+                &mut self.file_row,
+                row,
+                slots_per_row,
+            )?,
+        }
+        Ok(())
+    }
+
+    /// Build a row index for a compressed file, with a checkpoint every
+    /// `interval` rows, and attach it to this scanner.
+    ///
+    /// The index makes `read_rows` jump to the nearest checkpoint instead of
+    /// decoding from the top, and lets large reads decompress checkpoint
+    /// intervals in parallel. It is returned so it can be saved with
+    /// `RowIndex::save` and reattached later with `set_row_index`.
+    ///
+    /// Uncompressed files are already randomly accessible and return
+    /// `SpssError::Unsupported`.
+    pub fn build_row_index(&mut self, interval: usize) -> Result<RowIndex> {
+        let slots_per_row = self.dict.header.nominal_case_size as usize;
+        let index = match &mut self.state {
+            ScanState::Uncompressed => {
+                return Err(SpssError::Unsupported(
+                    "row index is only needed for compressed files".to_string(),
+                ));
+            }
+            ScanState::Bytecode { data, .. } => {
+                RowIndex::build(data, self.dict.header.bias, slots_per_row, interval)?
+            }
+            state => {
+                // ZSAV: walk the blocks through the lazy window, then return
+                // to where the scanner was.
+                if interval == 0 {
+                    return Err(SpssError::Unsupported(
+                        "row index interval must be at least 1".to_string(),
+                    ));
+                }
+                let resume = state.checkpoint();
+                let start = BytecodeDecompressor::new(0.0).checkpoint();
+                state.restore(&mut self.sav_reader, &start)?;
+                let mut checkpoints = Vec::new();
+                let mut n_rows = 0;
+                loop {
+                    let checkpoint = state.checkpoint();
+                    if !state.skip_row(&mut self.sav_reader, slots_per_row)? {
+                        break;
+                    }
+                    if n_rows % interval == 0 {
+                        checkpoints.push(checkpoint);
+                    }
+                    n_rows += 1;
+                }
+                state.restore(&mut self.sav_reader, &resume)?;
+                RowIndex::from_checkpoints(interval, state.data_len(), n_rows, checkpoints)
+            }
+        };
+        self.row_index = Some(index.clone());
+        Ok(index)
+    }
+
+    /// Attach a previously built (e.g. loaded from a sidecar file) row index.
+    ///
+    /// Returns an error if the index was built for different data.
+    pub fn set_row_index(&mut self, index: RowIndex) -> Result<()> {
+        match &self.state {
+            ScanState::Uncompressed => Err(SpssError::Unsupported(
+                "row index is only needed for compressed files".to_string(),
+            )),
+            state if index.matches_data(state.data_len()) => {
+                self.row_index = Some(index);
+                Ok(())
+            }
+            _ => Err(SpssError::Unsupported(
+                "row index does not match this file's compressed data".to_string(),
+            )),
+        }
+    }
+
+    /// The attached row index, if any.
+    pub fn row_index(&self) -> Option<&RowIndex> {
+        self.row_index.as_ref()
+    }
+
     /// How many rows have been read so far.
     pub fn rows_read(&self) -> usize {
         self.rows_read
     }
 
-    /// Reasonable capacity hint, avoiding usize::MAX overflow.
-    fn capacity_hint(&self, n: usize) -> usize {
-        let ncases = if self.dict.header.ncases >= 0 {
-            self.dict.header.ncases as usize
-        } else {
-            1000
+    /// Profile the columns of the batches returned from now on by
+    /// `next_batch()` and `collect_single()`, so one pass over the file
+    /// yields both the data and `statistics()`.
+    pub fn collect_statistics(&mut self) {
+        self.statistics.get_or_insert_with(ScanStatistics::default);
+    }
+
+    /// Column statistics of the batches read so far, when collected (see
+    /// `collect_statistics` and `ScanOptions::statistics`).
+    pub fn statistics(&self) -> Option<&ScanStatistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Where the data ended early, when it was read with
+    /// `TruncationPolicy::Partial` and found to be cut short: the cases
+    /// before the cut were returned instead of an error.
+    pub fn truncation(&self) -> Option<&Truncation> {
+        self.truncation.as_ref()
+    }
+
+    /// For .zsav files, the span of the file from which compressed blocks
+    /// are read while decoding the next `rows` rows. `None` for other layouts.
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn compressed_range(&self, rows: usize) -> Option<Range<u64>> {
+        match &self.state {
+            ScanState::Zlib { blocks, .. } => {
+                let max_row_bytes = self.dict.header.nominal_case_size as usize * 9 + 16;
+                let ahead = rows.saturating_add(1).saturating_mul(max_row_bytes);
+                Some(blocks.next_compressed_offset()..blocks.compressed_extent(ahead))
+            }
+            _ => None,
+        }
+    }
+
+    /// Handle the data ending at `file_row`, cut in the middle of a case when
+    /// there is a `cut` error, and check the case count against the
+    /// declared one.
+    fn end_of_data(&mut self, cut: Option<SpssError>) -> Result<()> {
+        self.report_short_cases();
+        self.truncation = end_of_data(self.on_truncation, self.file_row, self.dict.ncases, cut)?;
+        let Some(declared) = self.dict.ncases else {
+            // The data ended cleanly, so now the case count is known
+            if self.truncation.is_none() {
+                self.dict.ncases = Some(self.file_row);
+                self.dict.metadata.number_rows = Some(self.file_row as i64);
+            }
+            return Ok(());
+        };
+        let actual = self.file_row;
+        if actual == declared || self.case_count_checked {
+            return Ok(());
+        }
+        self.case_count_checked = true;
+        let mismatch = SpssError::CaseCountMismatch { declared, actual };
+        if self.strict && self.truncation.is_none() {
+            return Err(mismatch);
+        }
+        // Found again when the data is re-read after a reset
+        let warning = SpssWarning::new(WarningKind::CaseCount, None, mismatch.to_string());
+        if !self.dict.metadata.warnings.contains(&warning) {
+            self.dict.metadata.warnings.push(warning);
+        }
+        Ok(())
+    }
+
+    /// Report the cases the decoder found cut short as warnings.
+    fn report_short_cases(&mut self) {
+        let (ScanState::Bytecode { decompressor, .. } | ScanState::Zlib { decompressor, .. }) =
+            &mut self.state
+        else {
+            return;
+        };
+        let action = match decompressor.bad_rows() {
+            BadRowPolicy::Null => "returned with every value missing",
+            _ => "skipped",
         };
-        n.min(ncases).min(1_000_000)
+        let slots = self.dict.header.nominal_case_size;
+        for (offset, values) in decompressor.take_short_cases() {
+            let warning = SpssWarning::new(
+                WarningKind::ShortCase,
+                None,
+                format!(
+                    "the case at bytecode offset {offset} ends after {values} of its {slots} \
+                     values; {action}"
+                ),
+            );
+            if !self.dict.metadata.warnings.contains(&warning) {
+                self.dict.metadata.warnings.push(warning);
+            }
+        }
+    }
+
+    /// Reasonable capacity hint, avoiding usize::MAX overflow. At least one
+    /// row, since the data may hold more cases than the file declares (or
+    /// than its size suggests, for compressed files without a count).
+    fn capacity_hint(&self, n: usize) -> usize {
+        let ncases = self.dict.ncases.or(self.rows_hint).unwrap_or(1000);
+        n.min(ncases).clamp(1, 1_000_000)
     }
 
-    /// Read up to `n` rows directly into a columnar Arrow RecordBatch.
-    fn read_batch_columnar(&mut self, n: usize) -> Result<Option<RecordBatch>> {
-        if n == 0 {
+    /// Read up to `n` (matching) rows directly into a columnar Arrow
+    /// RecordBatch, decoding at most `max_file_rows` rows of the file.
+    fn read_batch_columnar(
+        &mut self,
+        n: usize,
+        max_file_rows: usize,
+    ) -> Result<Option<RecordBatch>> {
+        let batch = self.decode_batch(n, max_file_rows);
+        self.report_short_cases();
+        batch
+    }
+
+    fn decode_batch(&mut self, n: usize, max_file_rows: usize) -> Result<Option<RecordBatch>> {
+        // Without visible variables there are no columns to fill (and
+        // without any variables a case takes no bytes).
+        if n == 0 || max_file_rows == 0 || self.dict.variables.is_empty() {
+            return Ok(None);
+        }
+        if self
+            .truncation
+            .as_ref()
+            .is_some_and(|t| self.file_row >= t.rows)
+        {
             return Ok(None);
         }
 
-        let cap = self.capacity_hint(n);
-        let mut builder = ColumnarBatchBuilder::new(
+        let cap = self.capacity_hint(n.min(max_file_rows));
+        let mut builder = ColumnarBatchBuilder::with_options(
             &self.dict,
             self.projection.as_deref(),
             cap,
+            &self.output,
         );
 
         match &mut self.state {
@@ -262,31 +1237,102 @@ impl<R: Read + Seek> SavScanner<R> {
                 let mut chunk_buf = vec![0u8; chunk_bytes];
 
                 let mut rows_remaining = n;
-                while rows_remaining > 0 {
-                    let to_read = chunk_rows.min(rows_remaining);
+                let mut file_rows_left = max_file_rows;
+                while rows_remaining > 0 && file_rows_left > 0 {
+                    let to_read = chunk_rows.min(rows_remaining).min(file_rows_left);
                     let read_bytes = to_read * row_bytes;
                     let actual = read_full(&mut self.sav_reader, &mut chunk_buf[..read_bytes])?;
                     let actual_rows = actual / row_bytes;
+                    let first_row = self.file_row;
+                    self.file_row += actual_rows;
+                    file_rows_left -= actual_rows;
+                    if actual_rows < to_read {
+                        let at = self.data_start as usize + self.file_row * row_bytes;
+                        let cut = (actual % row_bytes > 0).then(|| SpssError::TruncatedFile {
+                            expected: at + row_bytes,
+                            actual: at + actual % row_bytes,
+                        });
+                        self.end_of_data(cut)?;
+                    }
                     if actual_rows == 0 {
                         break;
                     }
 
+                    // Drop non-matching rows before they reach the builders
+                    let kept_rows = match &self.filter {
+                        Some(filter) => filter.compact(
+                            &mut chunk_buf,
+                            actual_rows,
+                            row_bytes,
+                            first_row,
+                            builder.case_numbers_mut(),
+                        ),
+                        None => {
+                            builder.push_case_range(first_row, actual_rows);
+                            actual_rows
+                        }
+                    };
+
                     // Process chunk column-at-a-time for better cache locality
-                    let chunk_data = &chunk_buf[..actual_rows * row_bytes];
-                    builder.push_raw_chunk(chunk_data, actual_rows, slots_per_row);
-                    rows_remaining -= actual_rows;
+                    if kept_rows > 0 {
+                        let chunk_data = &chunk_buf[..kept_rows * row_bytes];
+                        builder.push_raw_chunk(chunk_data, kept_rows, slots_per_row);
+                    }
+                    rows_remaining -= kept_rows;
                     if actual_rows < to_read {
                         break; // EOF
                     }
                 }
             }
-            ScanState::Bytecode { data, decompressor }
-            | ScanState::Zlib { data, decompressor } => {
+            state => {
                 let slots_per_row = self.dict.header.nominal_case_size as usize;
                 let row_bytes = slots_per_row * 8;
-                let data_ref = data as &[u8];
 
-                // Decompress directly into raw byte buffer (no SlotValue intermediates),
+                // With a row index (and no filter, so `n` counts file rows),
+                // decompress checkpoint intervals of in-memory bytecode in
+                // parallel.
+                if let (Some(index), None, ScanState::Bytecode { data, .. }) =
+                    (&self.row_index, &self.filter, &*state)
+                {
+                    let max_chunk_rows = (256 * 1024 * 1024 / row_bytes).max(1024);
+                    let mut remaining = n
+                        .min(max_file_rows)
+                        .min(index.n_rows().saturating_sub(self.file_row));
+                    while remaining > 0 {
+                        let chunk = remaining.min(max_chunk_rows);
+                        let raw = in_pool(self.output.pool.as_deref(), || {
+                            index.decode_rows_parallel(
+                                data,
+                                self.dict.header.bias,
+                                self.dict.numbers,
+                                slots_per_row,
+                                self.file_row,
+                                chunk,
+                            )
+                        })?;
+                        let rows = raw.len() / row_bytes;
+                        if rows == 0 {
+                            break;
+                        }
+                        builder.push_raw_chunk(&raw, rows, slots_per_row);
+                        builder.push_case_range(self.file_row, rows);
+                        self.file_row += rows;
+                        remaining -= rows.min(remaining);
+                    }
+                    // Leave the sequential decoder at the row after the batch
+                    let target = self.file_row;
+                    self.file_row = usize::MAX;
+                    state.seek_row(
+                        &mut self.sav_reader,
+                        Some(index),
+                        &mut self.file_row,
+                        target,
+                        slots_per_row,
+                    )?;
+                    return self.finish_batch(builder);
+                }
+
+                // Decompress directly into a raw byte buffer,
                 // then process column-at-a-time via push_raw_chunk with rayon parallelism.
                 let max_chunk_rows = (256 * 1024 * 1024 / row_bytes).max(1024);
                 let chunk_rows = cap.min(max_chunk_rows);
@@ -294,18 +1340,40 @@ impl<R: Read + Seek> SavScanner<R> {
                 let mut raw_buf = vec![0u8; chunk_bytes];
 
                 let mut rows_in_batch = 0;
-                for _ in 0..n {
+                let mut rows_matched = 0;
+                let mut file_rows_left = max_file_rows;
+                while rows_matched < n && file_rows_left > 0 {
                     let out_offset = rows_in_batch * row_bytes;
-                    let ok = decompressor.decompress_row_raw(
-                        data_ref,
+                    let next = state.next_row(
+                        &mut self.sav_reader,
                         slots_per_row,
                         &mut raw_buf,
                         out_offset,
-                    )?;
-                    if !ok {
+                    );
+                    let cut = match next {
+                        Ok(true) => None,
+                        Ok(false) => Some(None),
+                        Err(err) if is_truncation(&err) => Some(Some(err)),
+                        Err(err) => return Err(err),
+                    };
+                    if let Some(cut) = cut {
+                        self.end_of_data(cut)?;
                         break;
                     }
+                    let row = self.file_row;
+                    self.file_row += 1;
+                    file_rows_left -= 1;
+                    // A rejected row is simply overwritten by the next one
+                    if let Some(filter) = &self.filter
+                        && !filter.matches(&raw_buf[out_offset..out_offset + row_bytes], row)
+                    {
+                        continue;
+                    }
+                    if let Some(case_numbers) = builder.case_numbers_mut() {
+                        case_numbers.push(row as u64 + 1);
+                    }
                     rows_in_batch += 1;
+                    rows_matched += 1;
 
                     if rows_in_batch >= chunk_rows {
                         builder.push_raw_chunk(
@@ -328,6 +1396,24 @@ impl<R: Read + Seek> SavScanner<R> {
             }
         }
 
+        self.finish_batch(builder)
+    }
+
+    /// Tally the text that did not decode, then build the batch, if it has
+    /// any rows.
+    fn finish_batch(&mut self, builder: ColumnarBatchBuilder) -> Result<Option<RecordBatch>> {
+        for (name, encoding, replaced) in builder.replaced_characters() {
+            let meta = &mut self.dict.metadata;
+            let count = meta.replaced_characters.entry(name.to_string()).or_insert(0);
+            if *count == 0 {
+                meta.warnings.push(SpssWarning::new(
+                    WarningKind::LossyText,
+                    Some(name),
+                    format!("data not valid in {}", encoding.name()),
+                ));
+            }
+            *count += replaced;
+        }
         if builder.len() > 0 {
             Ok(Some(builder.finish()?))
         } else {
@@ -336,6 +1422,86 @@ impl<R: Read + Seek> SavScanner<R> {
     }
 }
 
+#[cfg(feature = "mmap")]
+impl SavScanner<std::io::Cursor<MmapSource>> {
+    /// Open a scanner over a memory-mapped file.
+    ///
+    /// Uncompressed cases are copied straight out of the mapping, bytecode
+    /// is decoded in place, and ZSAV blocks are inflated from mapped slices.
+    pub fn open_mmap(path: impl AsRef<std::path::Path>, batch_size: usize) -> Result<Self> {
+        let source_path = path.as_ref().display().to_string();
+        let map = MmapSource::open(path)?;
+        let source = std::io::Cursor::new(map.clone());
+        let limits = ReadLimits::default();
+        let mut scanner = Self::open_inner(source, batch_size, &limits, Some(map), false)?;
+        scanner.set_source_path(source_path);
+        Ok(scanner)
+    }
+}
+
+impl<R: Read> SavScanner<StreamReader<R>> {
+    /// Open a scanner over a non-seekable source (a pipe, an HTTP body, a
+    /// tar entry, ...). The reader is only ever read front to back: .zsav
+    /// blocks are inflated in file order and the block trailer is skipped.
+    ///
+    /// `read_rows`, row indexes, and other operations that need to move
+    /// backwards return an error.
+    pub fn open_stream(reader: R, batch_size: usize) -> Result<Self> {
+        let limits = ReadLimits::default();
+        Self::open_inner(StreamReader::new(reader), batch_size, &limits, None, true)
+    }
+}
+
+/// Adapts a non-seekable reader for `SavScanner`: tracks the position so
+/// forward seeks can skip bytes; seeking backwards fails.
+pub struct StreamReader<R> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> StreamReader<R> {
+    pub fn new(inner: R) -> Self {
+        StreamReader { inner, pos: 0 }
+    }
+
+    /// Unwrap the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for StreamReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for StreamReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+            SeekFrom::End(_) => None,
+        };
+        match target {
+            Some(target) if target >= self.pos => {
+                let skip = target - self.pos;
+                let skipped = std::io::copy(&mut self.by_ref().take(skip), &mut std::io::sink())?;
+                if skipped < skip {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(self.pos)
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "cannot seek backwards in a non-seekable stream",
+            )),
+        }
+    }
+}
+
 /// Read as many bytes as possible into `buf`, handling partial reads.
 /// Returns the total number of bytes read (may be less than buf.len() at EOF).
 fn read_full<R: Read + Seek>(reader: &mut SavReader<R>, buf: &mut [u8]) -> Result<usize> {
@@ -350,3 +1516,731 @@ fn read_full<R: Read + Seek>(reader: &mut SavReader<R>, buf: &mut [u8]) -> Resul
     }
     Ok(pos)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, AsArray, Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Float64Type};
+
+    use super::*;
+    use crate::float_format::{FloatFormat, NumberFormat};
+    use crate::metadata::Value;
+    use crate::test_util;
+    use crate::writer::WriteOptions;
+
+    fn scanner(compression: Compression, n: usize) -> SavScanner<Cursor<Vec<u8>>> {
+        scanner_with_blocks(compression, n, WriteOptions::default().zlib_block_size)
+    }
+
+    fn scanner_with_blocks(
+        compression: Compression,
+        n: usize,
+        zlib_block_size: usize,
+    ) -> SavScanner<Cursor<Vec<u8>>> {
+        let buf = sav_bytes(compression, n, zlib_block_size);
+        SavScanner::open(Cursor::new(buf), 10).unwrap()
+    }
+
+    fn sav_bytes(compression: Compression, n: usize, zlib_block_size: usize) -> Vec<u8> {
+        let options = WriteOptions {
+            compression,
+            zlib_block_size,
+            ..WriteOptions::default()
+        };
+        test_util::sav_bytes_with(
+            vec![
+                ("id", Arc::new(Float64Array::from_iter_values((0..n).map(|i| i as f64)))),
+                (
+                    "big",
+                    Arc::new(Float64Array::from_iter_values((0..n).map(|i| i as f64 * 1e6))),
+                ),
+            ],
+            &SpssMetadata::default(),
+            &options,
+        )
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<f64> {
+        let col = batch.column(0).as_any().downcast_ref::<Float64Array>().unwrap();
+        (0..col.len()).map(|i| col.value(i)).collect()
+    }
+
+    #[test]
+    fn test_read_rows_random_access() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut scanner = scanner(compression, 100);
+            assert_eq!(ids(&scanner.read_rows(40..43).unwrap()), vec![40.0, 41.0, 42.0]);
+            // Backwards: compressed files rewind and decode forward again.
+            assert_eq!(ids(&scanner.read_rows(5..7).unwrap()), vec![5.0, 6.0]);
+            // Streaming continues after the range.
+            assert_eq!(ids(&scanner.next_batch().unwrap().unwrap())[0], 7.0);
+            // Past the end.
+            assert_eq!(ids(&scanner.read_rows(98..120).unwrap()), vec![98.0, 99.0]);
+            assert_eq!(scanner.read_rows(200..210).unwrap().num_rows(), 0);
+        }
+    }
+
+    #[test]
+    fn test_read_rows_with_projection_and_filter() {
+        let mut scanner = scanner(Compression::Bytecode, 100);
+        scanner.select(&["big"]).unwrap();
+        scanner
+            .filter(crate::filter::Predicate::ge("id", 55.0))
+            .unwrap();
+        let batch = scanner.read_rows(50..60).unwrap();
+        assert_eq!(batch.num_columns(), 1);
+        assert_eq!(ids(&batch), (55..60).map(|i| i as f64 * 1e6).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_select_by_index_and_pattern() {
+        let mut scanner = scanner(Compression::None, 20);
+        scanner.select_by_index(&[1, 0]).unwrap();
+        let batch = scanner.read_rows(3..4).unwrap();
+        assert_eq!(batch.schema().field(0).name(), "big");
+        assert_eq!(ids(&batch), vec![3e6]);
+        assert!(scanner.select_by_index(&[2]).is_err());
+
+        scanner.select_matching(r"^i").unwrap();
+        assert_eq!(scanner.schema().fields().len(), 1);
+        assert_eq!(scanner.schema().field(0).name(), "id");
+        scanner.select_matching(r"^(id|big)$").unwrap();
+        assert_eq!(scanner.schema().fields().len(), 2);
+        assert!(scanner.select_matching(r"^q\d+").is_err());
+        assert!(scanner.select_matching(r"(").is_err());
+    }
+
+    #[test]
+    fn test_select_case_insensitive() {
+        let mut scanner = scanner(Compression::None, 20);
+        assert_eq!(scanner.select_ci(&["BIG", "Id"]).unwrap(), vec!["big", "id"]);
+        assert_eq!(ids(&scanner.read_rows(2..3).unwrap()), vec![2e6]);
+        assert!(scanner.select_ci(&["missing"]).is_err());
+        assert!(scanner.select(&["BIG"]).is_err());
+    }
+
+    #[test]
+    fn test_skip_rows() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut past_end = scanner(compression, 100);
+            past_end.skip(1_000).unwrap();
+            assert!(past_end.next_batch().unwrap().is_none());
+
+            let mut scanner = scanner(compression, 100);
+            scanner.skip(25).unwrap();
+            scanner.limit(12);
+            assert_eq!(ids(&scanner.next_batch().unwrap().unwrap())[0], 25.0);
+            scanner.skip(5).unwrap();
+            assert_eq!(ids(&scanner.next_batch().unwrap().unwrap()), vec![40.0, 41.0]);
+            assert_eq!(scanner.rows_read(), 12);
+
+            // No case count in the header to clamp to
+            let mut bytes = sav_bytes(compression, 100, 1_000);
+            bytes[80..84].copy_from_slice(&(-1i32).to_le_bytes());
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 10).unwrap();
+            scanner.skip(95).unwrap();
+            assert_eq!(ids(&scanner.next_batch().unwrap().unwrap())[0], 95.0);
+            scanner.skip(usize::MAX).unwrap();
+            assert!(scanner.next_batch().unwrap().is_none());
+            scanner.reset().unwrap();
+            scanner.skip(usize::MAX).unwrap();
+            assert!(scanner.next_batch().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_skip_past_unknown_case_count() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut bytes = sav_bytes(compression, 100, 1_000);
+            bytes[80..84].copy_from_slice(&(-1i32).to_le_bytes());
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 10).unwrap();
+            scanner.skip(150).unwrap();
+            assert!(scanner.next_batch().unwrap().is_none(), "{compression:?}");
+            assert_eq!(scanner.rows_read(), 0);
+            assert!(scanner.truncation().is_none());
+            assert!(scanner.metadata().warnings.is_empty());
+            assert_eq!(scanner.metadata().number_rows, Some(100), "{compression:?}");
+
+            // Nothing was lost: the whole file is still there after a reset
+            scanner.reset().unwrap();
+            let all: Vec<f64> = scanner.collect_all().unwrap().iter().flat_map(ids).collect();
+            assert_eq!(all, (0..100).map(f64::from).collect::<Vec<_>>());
+            assert_eq!(scanner.metadata().number_rows, Some(100));
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut scanner = scanner_with_blocks(compression, 100, 64);
+            scanner.limit(95);
+            let first: Vec<f64> = scanner.collect_all().unwrap().iter().flat_map(ids).collect();
+            assert_eq!(first.len(), 95);
+            assert!(scanner.next_batch().unwrap().is_none());
+
+            scanner.reset().unwrap();
+            assert_eq!(scanner.rows_read(), 0);
+            let second: Vec<f64> = scanner.collect_all().unwrap().iter().flat_map(ids).collect();
+            assert_eq!(second, first);
+        }
+    }
+
+    #[test]
+    fn test_tail() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut scanner = scanner_with_blocks(compression, 500, 100);
+            assert_eq!(ids(&scanner.tail(3).unwrap()), vec![497.0, 498.0, 499.0]);
+            assert!(scanner.next_batch().unwrap().is_none());
+            assert_eq!(scanner.tail(1_000).unwrap().num_rows(), 500);
+
+            if compression != Compression::None {
+                let mut scanner = scanner_with_blocks(compression, 500, 100);
+                scanner.build_row_index(50).unwrap();
+                assert_eq!(ids(&scanner.tail(2).unwrap()), vec![498.0, 499.0]);
+            }
+
+            // No case count in the header: the rows are counted first.
+            let mut bytes = sav_bytes(compression, 500, 100);
+            bytes[80..84].copy_from_slice(&(-1i32).to_le_bytes());
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 10).unwrap();
+            assert_eq!(ids(&scanner.tail(2).unwrap()), vec![498.0, 499.0]);
+        }
+    }
+
+    #[test]
+    fn test_unknown_case_count() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut bytes = sav_bytes(compression, 2_500, 1_000);
+            bytes[80..84].copy_from_slice(&(-1i32).to_le_bytes());
+            for batch_size in [7, 1_000, 100_000, usize::MAX] {
+                let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), batch_size).unwrap();
+                assert_eq!(scanner.metadata().number_rows, None);
+                let mut all = Vec::new();
+                while let Some(batch) = scanner.next_batch().unwrap() {
+                    all.extend(ids(&batch));
+                }
+                assert_eq!(all, (0..2_500).map(|i| i as f64).collect::<Vec<_>>());
+                assert!(scanner.next_batch().unwrap().is_none());
+                // Reaching the end of the data counts the cases
+                assert_eq!(scanner.metadata().number_rows, Some(2_500));
+                assert!(scanner.metadata().warnings.is_empty());
+            }
+            let scanner = SavScanner::open(Cursor::new(bytes.clone()), 10).unwrap();
+            if compression == Compression::None {
+                assert_eq!(scanner.rows_hint, Some(2_500));
+            }
+
+            let mut scanner = SavScanner::open_stream(Cursor::new(bytes.clone()), 1 << 20).unwrap();
+            assert_eq!(scanner.collect_single().unwrap().num_rows(), 2_500);
+            let (batch, meta) = crate::read_sav_from_reader(Cursor::new(bytes)).unwrap();
+            assert_eq!(batch.num_rows(), 2_500);
+            assert_eq!(meta.number_rows, Some(2_500));
+        }
+    }
+
+    #[test]
+    fn test_zsav_lazy_blocks() {
+        // Tiny blocks: rows and control blocks straddle block boundaries.
+        let mut scanner = scanner_with_blocks(Compression::Zlib, 500, 100);
+        let batches = scanner.collect_all().unwrap();
+        assert_eq!(batches.len(), 50);
+        let all: Vec<f64> = batches.iter().flat_map(ids).collect();
+        assert_eq!(all, (0..500).map(|i| i as f64).collect::<Vec<_>>());
+        if let ScanState::Zlib { blocks, .. } = &scanner.state {
+            assert!(blocks.window().len() < 100 * rayon::current_num_threads() + 64);
+        }
+
+        assert_eq!(ids(&scanner.read_rows(10..12).unwrap()), vec![10.0, 11.0]);
+        scanner.build_row_index(32).unwrap();
+        assert_eq!(ids(&scanner.read_rows(12..14).unwrap()), vec![12.0, 13.0]);
+        assert_eq!(ids(&scanner.read_rows(450..452).unwrap()), vec![450.0, 451.0]);
+        assert_eq!(ids(&scanner.read_rows(3..4).unwrap()), vec![3.0]);
+    }
+
+    #[test]
+    fn test_open_stream_without_seek() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            // Small blocks: the stream holds many consecutive zlib streams.
+            let bytes = sav_bytes(compression, 500, 1024);
+            let mut scanner = SavScanner::open_stream(&bytes[..], 64).unwrap();
+            let batches = scanner.collect_all().unwrap();
+            assert_eq!(batches.len(), 8);
+            let all: Vec<f64> = batches.iter().flat_map(ids).collect();
+            assert_eq!(all, (0..500).map(|i| i as f64).collect::<Vec<_>>());
+        }
+
+        // Operations that move backwards are rejected.
+        let bytes = sav_bytes(Compression::Zlib, 500, 1024);
+        let mut scanner = SavScanner::open_stream(&bytes[..], 64).unwrap();
+        scanner.collect_all().unwrap();
+        assert!(scanner.read_rows(0..1).is_err());
+
+        let (batch, _) = crate::read_sav_from_stream(&bytes[..]).unwrap();
+        assert_eq!(batch.num_rows(), 500);
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let bytes = sav_bytes(compression, 250, 1024);
+            let file_len = bytes.len() as u64;
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 100).unwrap();
+
+            let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let sink = Arc::clone(&calls);
+            scanner.on_progress(move |rows, total, bytes| {
+                sink.lock().unwrap().push((rows, total, bytes));
+            });
+            scanner.collect_all().unwrap();
+
+            let calls = calls.lock().unwrap();
+            let rows: Vec<_> = calls.iter().map(|c| (c.0, c.1)).collect();
+            assert_eq!(rows, vec![(100, Some(250)), (200, Some(250)), (250, Some(250))]);
+            assert!(calls.windows(2).all(|w| w[0].2 <= w[1].2));
+            assert!(calls[2].2 <= file_len);
+        }
+    }
+
+    #[test]
+    fn test_statistics_while_scanning() {
+        let options = ScanOptions::new().batch_size(30).statistics(true);
+        let bytes = sav_bytes(Compression::Bytecode, 100, 1024);
+        let mut scanner = SavScanner::open_with(Cursor::new(bytes), &options).unwrap();
+        assert_eq!(scanner.collect_all().unwrap().len(), 4);
+
+        let stats = scanner.statistics().unwrap();
+        assert_eq!(stats.rows, 100);
+        let id = &stats.columns["id"];
+        assert_eq!(id.null_count, 0);
+        assert_eq!(id.min, Some(crate::metadata::Value::Numeric(0.0)));
+        assert_eq!(id.max, Some(crate::metadata::Value::Numeric(99.0)));
+        assert_eq!(id.distinct_estimate(), 100);
+
+        scanner.reset().unwrap();
+        assert_eq!(scanner.statistics().unwrap().rows, 0);
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        for compression in [Compression::None, Compression::Bytecode] {
+            let mut bytes = sav_bytes(compression, 100, 1024);
+            bytes.truncate(bytes.len() - 5);
+
+            let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), 30).unwrap();
+            assert!(matches!(
+                scanner.collect_all(),
+                Err(SpssError::TruncatedFile { .. })
+            ));
+
+            let options = ScanOptions::new()
+                .batch_size(30)
+                .on_truncation(TruncationPolicy::Partial);
+            let mut scanner = SavScanner::open_with(Cursor::new(bytes), &options).unwrap();
+            let rows: usize = scanner
+                .collect_all()
+                .unwrap()
+                .iter()
+                .map(RecordBatch::num_rows)
+                .sum();
+            assert_eq!(rows, 99, "{compression:?}");
+            let truncation = scanner.truncation().unwrap();
+            assert_eq!((truncation.rows, truncation.expected), (99, Some(100)));
+            assert!(truncation.mid_case);
+            assert!(scanner.next_batch().unwrap().is_none());
+
+            // Rewinding forgets the cut until the data is read again
+            scanner.reset().unwrap();
+            assert!(scanner.truncation().is_none());
+            let rows: usize = scanner
+                .collect_all()
+                .unwrap()
+                .iter()
+                .map(RecordBatch::num_rows)
+                .sum();
+            assert_eq!(rows, 99, "{compression:?}");
+            assert_eq!(scanner.truncation().unwrap().rows, 99);
+        }
+    }
+
+    #[test]
+    fn test_case_count_mismatch() {
+        for compression in [Compression::None, Compression::Bytecode] {
+            for declared in [3_i32, 7] {
+                let mut bytes = sav_bytes(compression, 5, 1024);
+                bytes[80..84].copy_from_slice(&declared.to_le_bytes());
+
+                let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), 2).unwrap();
+                let rows: usize = scanner
+                    .collect_all()
+                    .unwrap()
+                    .iter()
+                    .map(RecordBatch::num_rows)
+                    .sum();
+                assert_eq!(rows, 5);
+                let warnings = &scanner.metadata().warnings;
+                assert_eq!(warnings.len(), 1, "{compression:?}");
+                assert_eq!(
+                    warnings[0].to_string(),
+                    format!("case_count: the file declares {declared} cases but the data holds 5")
+                );
+                scanner.reset().unwrap();
+                scanner.collect_all().unwrap();
+                assert_eq!(scanner.metadata().warnings.len(), 1);
+
+                let options = ScanOptions::new().batch_size(2).strict(true);
+                let mut scanner = SavScanner::open_with(Cursor::new(bytes), &options).unwrap();
+                let err = scanner.collect_all().err().unwrap();
+                assert!(matches!(err, SpssError::CaseCountMismatch { actual: 5, .. }));
+            }
+        }
+    }
+
+    #[test]
+    fn test_reset_reports_short_file_again() {
+        // Declares 10 cases but the data ends cleanly after 9
+        let mut bytes = sav_bytes(Compression::None, 10, 1024);
+        bytes.truncate(bytes.len() - 16);
+
+        let options = ScanOptions::new()
+            .batch_size(4)
+            .on_truncation(TruncationPolicy::Partial);
+        let mut scanner = SavScanner::open_with(Cursor::new(bytes.clone()), &options).unwrap();
+        for pass in 0..2 {
+            let rows: usize = scanner
+                .collect_all()
+                .unwrap()
+                .iter()
+                .map(RecordBatch::num_rows)
+                .sum();
+            assert_eq!(rows, 9, "pass {pass}");
+            let truncation = scanner.truncation().unwrap();
+            assert_eq!((truncation.rows, truncation.expected), (9, Some(10)));
+            assert!(!truncation.mid_case);
+            let warnings = &scanner.metadata().warnings;
+            assert_eq!(warnings.len(), 1, "pass {pass}: {warnings:?}");
+            assert_eq!(warnings[0].kind, WarningKind::CaseCount);
+
+            scanner.reset().unwrap();
+            assert!(scanner.truncation().is_none());
+        }
+
+        // Strict mode fails on the short count every time the data is read
+        let options = ScanOptions::new().batch_size(4).strict(true);
+        let mut scanner = SavScanner::open_with(Cursor::new(bytes), &options).unwrap();
+        for _ in 0..2 {
+            let err = scanner.collect_all().err().unwrap();
+            assert!(matches!(
+                err,
+                SpssError::CaseCountMismatch {
+                    declared: 10,
+                    actual: 9
+                }
+            ));
+            scanner.reset().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_short_cases() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Float64, true),
+            Field::new("b", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from_iter_values([0.0, 1.0, 2.0, 3.0, 4.0])),
+                Arc::new(Float64Array::from_iter_values([5.0; 5])),
+            ],
+        )
+        .unwrap();
+        let options = WriteOptions {
+            compression: Compression::Bytecode,
+            ..WriteOptions::default()
+        };
+        let mut bytes = Vec::new();
+        crate::write_sav_to_writer_with(&mut bytes, &batch, &SpssMetadata::default(), &options)
+            .unwrap();
+        // The first control block holds cases 0 to 3; end case 2 after `a`.
+        let end = [999, 0].map(i32::to_le_bytes).concat();
+        let data = bytes.windows(8).position(|w| w == end).unwrap() + 8;
+        assert_eq!(bytes[data + 5], 105);
+        bytes[data + 5] = 252;
+
+        let read = |policy| {
+            let options = ScanOptions::new().bad_rows(policy);
+            let mut scanner = SavScanner::open_with(Cursor::new(bytes.clone()), &options)?;
+            let batch = scanner.collect_single()?;
+            let a = batch.column(0).as_primitive::<Float64Type>().iter().collect::<Vec<_>>();
+            Ok::<_, SpssError>((a, scanner.metadata().warnings.clone()))
+        };
+        let (a, _) = read(BadRowPolicy::Stop).unwrap();
+        assert_eq!(a, [Some(0.0), Some(1.0)]);
+        let err = read(BadRowPolicy::Fail).err().unwrap();
+        assert!(matches!(err, SpssError::ShortCase { values: 1, expected: 2, .. }));
+        // Decoding resumes at the next control block, case 4.
+        let (a, warnings) = read(BadRowPolicy::Null).unwrap();
+        assert_eq!(a, [Some(0.0), Some(1.0), None, Some(4.0)]);
+        assert_eq!(warnings[0].kind, WarningKind::ShortCase);
+        assert_eq!(
+            warnings[0].message,
+            "the case at bytecode offset 8 ends after 1 of its 2 values; \
+             returned with every value missing"
+        );
+        let (a, _) = read(BadRowPolicy::Skip).unwrap();
+        assert_eq!(a, [Some(0.0), Some(1.0), Some(4.0)]);
+    }
+
+    #[test]
+    fn test_degenerate_files() {
+        let check = |bytes: Vec<u8>| {
+            let (batch, meta) = crate::read_sav_from_reader(Cursor::new(bytes.clone())).unwrap();
+            assert_eq!((batch.num_rows(), batch.num_columns()), (0, 0));
+            assert_eq!((meta.number_rows, meta.number_columns), (Some(0), 0));
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 2).unwrap();
+            assert_eq!(scanner.metadata().number_rows, Some(0));
+            assert!(scanner.next_batch().unwrap().is_none());
+        };
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let batch = RecordBatch::try_new_with_options(
+                Arc::new(Schema::empty()),
+                vec![],
+                &arrow::array::RecordBatchOptions::new().with_row_count(Some(3)),
+            )
+            .unwrap();
+            let options = WriteOptions {
+                compression,
+                ..WriteOptions::default()
+            };
+            let mut bytes = Vec::new();
+            crate::write_sav_to_writer_with(&mut bytes, &batch, &SpssMetadata::default(), &options)
+                .unwrap();
+            check(bytes);
+        }
+
+        // A dictionary holding nothing but a ghost segment.
+        for compression in [Compression::None, Compression::Bytecode] {
+            let header = crate::header::FileHeader {
+                magic: *b"$FL2",
+                product: "test".into(),
+                layout_code: 2,
+                nominal_case_size: 1,
+                compression,
+                weight_index: 0,
+                ncases: 2,
+                bias: 100.0,
+                creation_date: String::new(),
+                creation_time: String::new(),
+                file_label: String::new(),
+                bswap: false,
+            };
+            let mut bytes = Vec::new();
+            header.write(&mut bytes);
+            bytes.extend([2i32, -1, 0, 0, 0, 0].map(i32::to_le_bytes).concat());
+            bytes.extend(b"GHOST   ");
+            bytes.extend([999i32, 0].map(i32::to_le_bytes).concat());
+            match compression {
+                Compression::None => bytes.extend([1.0f64, 2.0].map(f64::to_le_bytes).concat()),
+                _ => bytes.extend([101u8, 102, 252, 0, 0, 0, 0, 0]),
+            }
+            check(bytes);
+        }
+
+        // Variables but no cases, declared or left unknown.
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let mut bytes = sav_bytes(compression, 0, 1024);
+            for ncases in [0i32, -1] {
+                bytes[80..84].copy_from_slice(&ncases.to_le_bytes());
+                let (batch, meta) =
+                    crate::read_sav_from_reader(Cursor::new(bytes.clone())).unwrap();
+                assert_eq!((batch.num_rows(), batch.num_columns()), (0, 2));
+                assert_eq!(meta.variable_names, ["id", "big"]);
+                assert_eq!(meta.number_rows, Some(0));
+                let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), 2).unwrap();
+                let declared = (ncases == 0).then_some(0);
+                assert_eq!(scanner.metadata().number_rows, declared);
+                assert!(scanner.next_batch().unwrap().is_none());
+                scanner.reset().unwrap();
+                assert!(scanner.next_batch().unwrap().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_untrusted_bytes() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![Some(1.0), None, Some(2.0)])),
+                Arc::new(StringArray::from(vec!["NY", "LA", ""])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels.insert("q1".into(), "Owns a car".into());
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [(Value::Numeric(1.0), "Yes".to_string())].into_iter().collect(),
+        );
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let options = WriteOptions {
+                compression,
+                ..WriteOptions::default()
+            };
+            let mut bytes = Vec::new();
+            crate::write_sav_to_writer_with(&mut bytes, &batch, &meta, &options).unwrap();
+            let (read, _) = crate::read_sav_from_bytes(&bytes).unwrap();
+            assert_eq!(read.num_rows(), 3);
+
+            let narrow = ReadLimits::untrusted().max_columns(1);
+            let err = crate::read_sav_from_bytes_with(&bytes, &narrow).err().unwrap();
+            assert!(err.to_string().contains("resource limit exceeded"), "{err}");
+
+            // Damage each byte in turn: reading must fail or succeed, never
+            // panic or allocate what the damaged lengths claim.
+            for at in 0..bytes.len() {
+                for byte in [0x00, 0x7F, 0x80, 0xFF] {
+                    let mut damaged = bytes.clone();
+                    damaged[at] = byte;
+                    let _ = crate::read_sav_from_bytes(&damaged);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dictionary_error_offsets() {
+        let bytes = sav_bytes(Compression::None, 3, 4096);
+        let big = bytes.windows(8).position(|w| w == b"BIG     ").unwrap() - 24;
+        let err = SavScanner::open(Cursor::new(bytes[..big + 30].to_vec()), 10)
+            .err()
+            .unwrap();
+        match &err {
+            SpssError::InvalidRecord {
+                record_type: 2,
+                subtype: None,
+                offset,
+                variable: Some(variable),
+                ..
+            } => assert_eq!((*offset, variable.as_str()), (big as u64, "ID")),
+            _ => panic!("{err}"),
+        }
+
+        let mut bytes = bytes;
+        let end = [999, 0].map(i32::to_le_bytes).concat();
+        let at = bytes.windows(8).position(|w| w == end).unwrap();
+        bytes[at..at + 4].copy_from_slice(&42_i32.to_le_bytes());
+        let err = SavScanner::open(Cursor::new(bytes), 10).err().unwrap();
+        assert_eq!(err.to_string(), format!("unexpected record type 42 at offset {at}"));
+    }
+
+    #[test]
+    fn test_compression_bias() {
+        let with_bias = |compression, bias: f64| {
+            let mut bytes = sav_bytes(compression, 3, 4096);
+            bytes[84..92].copy_from_slice(&bias.to_le_bytes());
+            SavScanner::open(Cursor::new(bytes), 10)
+        };
+        // Small integers are opcodes, so they decode against the bias.
+        let mut scanner = with_bias(Compression::Bytecode, 0.0).unwrap();
+        assert_eq!(ids(&scanner.next_batch().unwrap().unwrap()), [100.0, 101.0, 102.0]);
+
+        let err = with_bias(Compression::Bytecode, f64::NAN).err().unwrap();
+        assert!(matches!(err, SpssError::InvalidBias(_)), "{err}");
+        let err = with_bias(Compression::Zlib, 50.0).err().unwrap();
+        assert!(err.to_string().contains("trailer's bias -100"), "{err}");
+        // Uncompressed files do not use it.
+        assert!(with_bias(Compression::None, f64::NAN).is_ok());
+    }
+
+    #[test]
+    fn test_declared_sysmis() {
+        // A writer using NaN for system-missing and saying so in subtype 4.
+        let mut bytes = sav_bytes(Compression::None, 3, 4096);
+        let nan = f64::from_bits(0xFFF8_0000_0000_0001).to_le_bytes();
+        let record = [7, 4, 8, 3].map(i32::to_le_bytes).concat();
+        let at = bytes.windows(16).position(|w| w == record).unwrap() + 16;
+        bytes[at..at + 8].copy_from_slice(&nan);
+        let data = bytes.len() - 3 * 16;
+        bytes[data + 16..data + 24].copy_from_slice(&nan);
+
+        let mut scanner = SavScanner::open(Cursor::new(bytes), 10).unwrap();
+        let batch = scanner.next_batch().unwrap().unwrap();
+        let id = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!(id.iter().collect::<Vec<_>>(), [Some(0.0), None, Some(2.0)]);
+    }
+
+    /// A big-endian file with a numeric `X`, labelled 1 = "One", and an A8
+    /// string `S`: (1, "ab"), (2.5, "cd"), (sysmis, "").
+    fn big_endian_sav(compression: Compression, float: FloatFormat) -> Vec<u8> {
+        let numbers = NumberFormat {
+            bswap: true,
+            float,
+            specials: None,
+        };
+        let int = |buf: &mut Vec<u8>, values: &[i32]| {
+            for v in values {
+                buf.extend_from_slice(&v.to_be_bytes());
+            }
+        };
+        let mut buf = b"$FL2".to_vec();
+        buf.extend_from_slice(&[b' '; 60]);
+        int(&mut buf, &[2, 2, compression as i32, 0, 3]);
+        buf.extend_from_slice(&numbers.encode(100.0));
+        buf.extend_from_slice(&[b' '; 9 + 8 + 64 + 3]);
+        for (width, format, name) in [(0, 0x050802, b"X       "), (8, 0x010800, b"S       ")] {
+            int(&mut buf, &[2, width, 0, 0, format, format]);
+            buf.extend_from_slice(name);
+        }
+        int(&mut buf, &[3, 1]);
+        buf.extend_from_slice(&numbers.encode(1.0));
+        buf.extend_from_slice(b"\x03One    ");
+        int(&mut buf, &[4, 1, 1, 7, 3, 4, 8, 20, 0, 0, 0]);
+        int(&mut buf, &[1 + float as i32, 1, 1, 65001, 999, 0]);
+
+        let cities = [b"ab      ", b"cd      ", b"        "];
+        if compression == Compression::None {
+            let sysmis = f64::from_bits(crate::constants::SYSMIS_BITS);
+            for (x, city) in [1.0, 2.5, sysmis].iter().zip(cities) {
+                buf.extend_from_slice(&numbers.encode(*x));
+                buf.extend_from_slice(city);
+            }
+        } else {
+            buf.extend_from_slice(&[101, 253, 253, 253, 255, 254, 252, 0]);
+            buf.extend_from_slice(cities[0]);
+            buf.extend_from_slice(&numbers.encode(2.5));
+            buf.extend_from_slice(cities[1]);
+        }
+        buf
+    }
+
+    #[test]
+    fn test_big_endian_file() {
+        let formats = [FloatFormat::Ieee, FloatFormat::Ibm, FloatFormat::Vax];
+        for (compression, float) in [Compression::None, Compression::Bytecode]
+            .into_iter()
+            .flat_map(|c| formats.map(|f| (c, f)))
+        {
+            let bytes = big_endian_sav(compression, float);
+            let mut scanner = SavScanner::open(Cursor::new(bytes.clone()), 10).unwrap();
+            let labels = &scanner.metadata().variable_value_labels["X"];
+            assert_eq!(labels[&crate::metadata::Value::Numeric(1.0)], "One");
+            let batch = scanner.next_batch().unwrap().unwrap();
+            let x = batch.column(0).as_primitive::<Float64Type>();
+            assert_eq!(x.iter().collect::<Vec<_>>(), [Some(1.0), Some(2.5), None]);
+            let s = batch.column(1).as_string_view();
+            assert_eq!(s.iter().collect::<Vec<_>>(), [Some("ab"), Some("cd"), Some("")]);
+
+            let mut scanner = SavScanner::open(Cursor::new(bytes), 10).unwrap();
+            scanner.filter(Predicate::ge("X", 2.0)).unwrap();
+            assert_eq!(scanner.next_batch().unwrap().unwrap().num_rows(), 1);
+        }
+    }
+}
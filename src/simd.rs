@@ -0,0 +1,131 @@
+//! Vectorized versions of the two scans that dominate reading: trimming the
+//! padding off string values and finding system-missing values in numeric
+//! columns. With the `simd` feature on x86_64 they use SSE2 (part of every
+//! x86_64 CPU, so no runtime detection is needed); elsewhere, and without
+//! the feature, they fall back to plain loops.
+
+use crate::constants::SYSMIS_BITS;
+
+/// Whether the vectorized versions are compiled in.
+pub(crate) const ENABLED: bool = cfg!(all(feature = "simd", target_arch = "x86_64"));
+
+/// Length of `buf` without its trailing spaces and NULs.
+#[inline]
+pub(crate) fn trimmed_len(buf: &[u8]) -> usize {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        // SAFETY: SSE2 is always available on x86_64.
+        unsafe { sse2::trimmed_len(buf) }
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        scalar_trimmed_len(buf, buf.len())
+    }
+}
+
+/// Whether any of `values` is system-missing (bit-exact, so other NaNs
+/// are not).
+#[inline]
+pub(crate) fn contains_sysmis(values: &[f64]) -> bool {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        // SAFETY: SSE2 is always available on x86_64.
+        unsafe { sse2::contains_sysmis(values) }
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        values.iter().any(|v| v.to_bits() == SYSMIS_BITS)
+    }
+}
+
+/// Scan back from `end` for the last byte that is not padding.
+#[inline]
+fn scalar_trimmed_len(buf: &[u8], mut end: usize) -> usize {
+    while end > 0 && (buf[end - 1] == b' ' || buf[end - 1] == 0) {
+        end -= 1;
+    }
+    end
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    use super::{SYSMIS_BITS, scalar_trimmed_len};
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn trimmed_len(buf: &[u8]) -> usize {
+        let spaces = _mm_set1_epi8(b' ' as i8);
+        let zeros = _mm_setzero_si128();
+        let mut end = buf.len();
+        // 16 bytes at a time from the end, while whole blocks are padding
+        while end >= 16 {
+            // SAFETY: end - 16 .. end is within buf; loadu takes any alignment.
+            let block = unsafe { _mm_loadu_si128(buf.as_ptr().add(end - 16) as *const __m128i) };
+            let padding = _mm_or_si128(_mm_cmpeq_epi8(block, spaces), _mm_cmpeq_epi8(block, zeros));
+            let text = !_mm_movemask_epi8(padding) as u32 & 0xFFFF;
+            if text != 0 {
+                // The highest set bit is the last byte of text
+                return end - 16 + (31 - text.leading_zeros()) as usize + 1;
+            }
+            end -= 16;
+        }
+        scalar_trimmed_len(buf, end)
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn contains_sysmis(values: &[f64]) -> bool {
+        // SSE2 compares 32-bit lanes: a value is SYSMIS when both of its
+        // halves match
+        let sysmis = _mm_set1_epi64x(SYSMIS_BITS as i64);
+        let mut pairs = values.chunks_exact(2);
+        for pair in &mut pairs {
+            // SAFETY: pair holds 16 bytes; loadu takes any alignment.
+            let v = unsafe { _mm_loadu_si128(pair.as_ptr() as *const __m128i) };
+            let halves = _mm_movemask_ps(_mm_castsi128_ps(_mm_cmpeq_epi32(v, sysmis)));
+            if halves & 0b0011 == 0b0011 || halves & 0b1100 == 0b1100 {
+                return true;
+            }
+        }
+        pairs.remainder().iter().any(|v| v.to_bits() == SYSMIS_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trimmed_len() {
+        for len in 0..48 {
+            for text_len in 0..=len {
+                let mut buf = vec![b' '; len];
+                buf[..text_len].fill(b'x');
+                if text_len < len {
+                    buf[len - 1] = 0;
+                }
+                assert_eq!(trimmed_len(&buf), text_len, "{len} {text_len}");
+            }
+        }
+        // Padding bytes inside the text are kept
+        assert_eq!(trimmed_len(b"a \0b                     \0\0"), 4);
+    }
+
+    #[test]
+    fn test_contains_sysmis() {
+        let sysmis = f64::from_bits(SYSMIS_BITS);
+        for len in 0..9 {
+            let mut values = vec![1.5; len];
+            assert!(!contains_sysmis(&values));
+            for i in 0..len {
+                values[i] = sysmis;
+                assert!(contains_sysmis(&values), "{len} {i}");
+                values[i] = 1.5;
+            }
+        }
+        // Only one half matching is not SYSMIS, nor is another NaN
+        let low = f64::from_bits(SYSMIS_BITS & 0xFFFF_FFFF);
+        let high = f64::from_bits(SYSMIS_BITS & !1);
+        assert!(!contains_sysmis(&[low, high, f64::NAN, f64::MAX]));
+    }
+}
@@ -0,0 +1,414 @@
+//! Triple-S (SSS XML) metadata export.
+//!
+//! Triple-S is a survey interchange standard: an XML document describing the
+//! variables of a fixed-width data file. The export covers what ambers reads
+//! from a .sav dictionary — the file label, variable names and labels, value
+//! labels and multiple response sets.
+//!
+//! Column positions describe a fixed-width companion data file in which each
+//! variable occupies its print-format width, in dictionary order:
+//!
+//! - coded numerics (with value labels and whole-number codes) → `single`
+//! - other numerics → `quantity`, with a range derived from the print format
+//! - strings → `character`
+//! - DATE-like formats → `date` (YYYYMMDD), TIME-like formats → `time` (HHMMSS)
+//! - DATETIME-like formats → `character` (Triple-S has no datetime type)
+//! - dichotomy MR sets → `multiple` (one column per member)
+//! - category MR sets → `multiple` with a `<spread>` of one subfield per member
+//!
+//! Variables that belong to an MR set are written once, as part of the set,
+//! at the position of the set's first member.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use indexmap::IndexMap;
+
+use crate::constants::{SpssFormat, TemporalKind};
+use crate::error::Result;
+use crate::metadata::{MrSet, MrType, SpssMetadata, Value};
+
+/// Render the metadata as a Triple-S 2.0 XML document.
+pub fn to_sss_xml(meta: &SpssMetadata) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<sss version=\"2.0\">\n");
+    if !meta.creation_time.is_empty() {
+        let _ = writeln!(out, "  <date>{}</date>", escape(&meta.creation_time));
+    }
+    let _ = writeln!(
+        out,
+        "  <origin>ambers {}</origin>",
+        env!("CARGO_PKG_VERSION")
+    );
+    out.push_str("  <survey>\n");
+    if !meta.file_label.is_empty() {
+        let _ = writeln!(out, "    <title>{}</title>", escape(&meta.file_label));
+    }
+    out.push_str("    <record ident=\"A\">\n");
+
+    // First member of each MR set → the set, so the set is emitted in place.
+    let mut set_at: IndexMap<&str, &MrSet> = IndexMap::new();
+    let mut in_set: HashSet<&str> = HashSet::new();
+    for set in meta.mr_sets.values() {
+        if let Some(first) = set.variables.first() {
+            set_at.entry(first.as_str()).or_insert(set);
+            in_set.extend(set.variables.iter().map(String::as_str));
+        }
+    }
+
+    let mut ident = 1;
+    let mut column = 1;
+    for name in &meta.variable_names {
+        if let Some(set) = set_at.get(name.as_str()) {
+            column += write_mr_set(&mut out, meta, set, ident, column);
+            ident += 1;
+        } else if !in_set.contains(name.as_str()) {
+            column += write_variable(&mut out, meta, name, ident, column);
+            ident += 1;
+        }
+    }
+
+    out.push_str("    </record>\n");
+    out.push_str("  </survey>\n");
+    out.push_str("</sss>\n");
+    out
+}
+
+/// Write the metadata as a Triple-S 2.0 XML file.
+pub fn write_sss(path: impl AsRef<Path>, meta: &SpssMetadata) -> Result<()> {
+    std::fs::write(path, to_sss_xml(meta))?;
+    Ok(())
+}
+
+/// Triple-S variable type for a single (non-MR) variable.
+enum SssType {
+    Single,
+    Quantity { decimals: usize },
+    Character,
+    Date,
+    Time,
+}
+
+/// Write one `<variable>` element; returns the number of columns it uses.
+fn write_variable(
+    out: &mut String,
+    meta: &SpssMetadata,
+    name: &str,
+    ident: usize,
+    start: usize,
+) -> usize {
+    let format_str = meta.format(name).unwrap_or("F8.2");
+    let format = SpssFormat::parse(format_str);
+    let labels = meta.value_labels(name).filter(|l| !l.is_empty());
+
+    let (sss_type, width) = match &format {
+        Some(f) if f.format_type.is_string() => (
+            SssType::Character,
+            format_width(format_str).unwrap_or(f.width as usize).max(1),
+        ),
+        Some(f) => match f.format_type.temporal_kind() {
+            Some(TemporalKind::Date) => (SssType::Date, 8),
+            Some(TemporalKind::Duration) => (SssType::Time, 6),
+            Some(TemporalKind::Timestamp) => (SssType::Character, (f.width as usize).max(1)),
+            None if labels.is_some_and(is_coded) => {
+                let code_width = labels
+                    .into_iter()
+                    .flat_map(|l| l.keys())
+                    .map(|v| v.to_string().len())
+                    .max()
+                    .unwrap_or(1);
+                (SssType::Single, code_width.max(f.width as usize))
+            }
+            None => (
+                SssType::Quantity {
+                    decimals: f.decimals as usize,
+                },
+                (f.width as usize).max(1),
+            ),
+        },
+        None => (SssType::Quantity { decimals: 2 }, 8),
+    };
+
+    let type_name = match sss_type {
+        SssType::Single => "single",
+        SssType::Quantity { .. } => "quantity",
+        SssType::Character => "character",
+        SssType::Date => "date",
+        SssType::Time => "time",
+    };
+    let _ = writeln!(
+        out,
+        "      <variable ident=\"{ident}\" type=\"{type_name}\">"
+    );
+    write_name_label(out, name, meta.label(name).unwrap_or(""));
+    write_position(out, start, width);
+
+    match sss_type {
+        SssType::Single => {
+            out.push_str("        <values>\n");
+            for (value, label) in labels.into_iter().flatten() {
+                write_value(out, &value.to_string(), label);
+            }
+            out.push_str("        </values>\n");
+        }
+        SssType::Quantity { decimals } => {
+            let _ = writeln!(
+                out,
+                "        <values>\n          <range from=\"{}\" to=\"{}\"/>\n        </values>",
+                zero(decimals),
+                max_for_width(width, decimals)
+            );
+        }
+        SssType::Character => {
+            let _ = writeln!(out, "        <size>{width}</size>");
+        }
+        SssType::Date | SssType::Time => {}
+    }
+
+    out.push_str("      </variable>\n");
+    width
+}
+
+/// Write an MR set as one `multiple` variable; returns its column count.
+fn write_mr_set(
+    out: &mut String,
+    meta: &SpssMetadata,
+    set: &MrSet,
+    ident: usize,
+    start: usize,
+) -> usize {
+    let _ = writeln!(out, "      <variable ident=\"{ident}\" type=\"multiple\">");
+    write_name_label(out, &set.name, &set.label);
+
+    let n = set.variables.len();
+    match set.mr_type {
+        MrType::MultipleDichotomy => {
+            // Bitstring: one column per member, coded 1..n in member order.
+            write_position(out, start, n);
+            out.push_str("        <values>\n");
+            for (i, var) in set.variables.iter().enumerate() {
+                let label = meta.label(var).filter(|l| !l.is_empty()).unwrap_or(var);
+                write_value(out, &(i + 1).to_string(), label);
+            }
+            out.push_str("        </values>\n");
+            out.push_str("      </variable>\n");
+            n
+        }
+        MrType::MultipleCategory => {
+            let mut categories: IndexMap<Value, String> = IndexMap::new();
+            for var in &set.variables {
+                for (value, label) in meta.value_labels(var).into_iter().flatten() {
+                    categories
+                        .entry(value.clone())
+                        .or_insert_with(|| label.clone());
+                }
+            }
+            categories.sort_keys();
+            let width = set
+                .variables
+                .iter()
+                .filter_map(|v| meta.format(v).and_then(SpssFormat::parse))
+                .map(|f| f.width as usize)
+                .chain(categories.keys().map(|v| v.to_string().len()))
+                .max()
+                .unwrap_or(1)
+                .max(1);
+
+            write_position(out, start, n * width);
+            let _ = writeln!(out, "        <spread subfields=\"{n}\" width=\"{width}\"/>");
+            out.push_str("        <values>\n");
+            for (value, label) in &categories {
+                write_value(out, &value.to_string(), label);
+            }
+            out.push_str("        </values>\n");
+            out.push_str("      </variable>\n");
+            n * width
+        }
+    }
+}
+
+fn write_name_label(out: &mut String, name: &str, label: &str) {
+    let _ = writeln!(out, "        <name>{}</name>", escape(name));
+    let _ = writeln!(out, "        <label>{}</label>", escape(label));
+}
+
+fn write_position(out: &mut String, start: usize, width: usize) {
+    let _ = writeln!(
+        out,
+        "        <position start=\"{start}\" finish=\"{}\"/>",
+        start + width - 1
+    );
+}
+
+fn write_value(out: &mut String, code: &str, label: &str) {
+    let _ = writeln!(
+        out,
+        "          <value code=\"{}\">{}</value>",
+        escape(code),
+        escape(label)
+    );
+}
+
+/// Whether all value label codes are non-negative whole numbers, which is
+/// what Triple-S requires for a `single` variable.
+fn is_coded(labels: &IndexMap<Value, String>) -> bool {
+    labels.keys().all(|v| match v {
+        Value::Numeric(n) => n.is_finite() && n.fract() == 0.0 && *n >= 0.0,
+        Value::String(_) => false,
+    })
+}
+
+/// Width digits of a format string, not clamped to 255 (e.g. "A1000").
+fn format_width(format: &str) -> Option<usize> {
+    let digits_at = format.find(|c: char| c.is_ascii_digit())?;
+    format[digits_at..].split('.').next()?.parse().ok()
+}
+
+fn zero(decimals: usize) -> String {
+    if decimals == 0 {
+        "0".to_string()
+    } else {
+        format!("0.{}", "0".repeat(decimals))
+    }
+}
+
+/// Largest value that fits a field of `width` columns with `decimals` places.
+fn max_for_width(width: usize, decimals: usize) -> String {
+    let int_digits = width
+        .saturating_sub(decimals + usize::from(decimals > 0))
+        .max(1);
+    if decimals == 0 {
+        "9".repeat(int_digits)
+    } else {
+        format!("{}.{}", "9".repeat(int_digits), "9".repeat(decimals))
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata {
+            file_label: "Brand <tracker>".to_string(),
+            ..SpssMetadata::default()
+        };
+        for (name, format) in [
+            ("id", "F6.0"),
+            ("gender", "F1.0"),
+            ("aware_a", "F1.0"),
+            ("aware_b", "F1.0"),
+            ("score", "F5.2"),
+            ("city", "A20"),
+            ("visit", "DATE11"),
+        ] {
+            meta.variable_names.push(name.to_string());
+            meta.spss_variable_types
+                .insert(name.to_string(), format.to_string());
+        }
+        meta.variable_labels
+            .insert("gender".to_string(), "Gender".to_string());
+        meta.variable_labels
+            .insert("aware_a".to_string(), "Brand A & co".to_string());
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(1.0), "Male".to_string());
+        labels.insert(Value::Numeric(2.0), "Female".to_string());
+        meta.variable_value_labels
+            .insert("gender".to_string(), labels);
+        meta.mr_sets.insert(
+            "$aware".to_string(),
+            MrSet {
+                name: "$aware".to_string(),
+                label: "Brand awareness".to_string(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".to_string()),
+                variables: vec!["aware_a".to_string(), "aware_b".to_string()],
+            },
+        );
+        meta
+    }
+
+    #[test]
+    fn test_sss_variables_and_positions() {
+        let xml = to_sss_xml(&metadata());
+        assert!(
+            xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sss version=\"2.0\">")
+        );
+        assert!(xml.contains("<title>Brand &lt;tracker&gt;</title>"));
+
+        // id: quantity in columns 1-6.
+        assert!(xml.contains("<variable ident=\"1\" type=\"quantity\">\n        <name>id</name>"));
+        assert!(xml.contains("<position start=\"1\" finish=\"6\"/>"));
+        assert!(xml.contains("<range from=\"0\" to=\"999999\"/>"));
+
+        // gender: single in column 7 with its value labels.
+        assert!(xml.contains("<variable ident=\"2\" type=\"single\">"));
+        assert!(xml.contains("<position start=\"7\" finish=\"7\"/>"));
+        assert!(xml.contains("<value code=\"2\">Female</value>"));
+
+        // MR set replaces its members, columns 8-9.
+        assert!(
+            xml.contains("<variable ident=\"3\" type=\"multiple\">\n        <name>$aware</name>")
+        );
+        assert!(xml.contains("<position start=\"8\" finish=\"9\"/>"));
+        assert!(xml.contains("<value code=\"1\">Brand A &amp; co</value>"));
+        assert!(xml.contains("<value code=\"2\">aware_b</value>"));
+        assert!(!xml.contains("<name>aware_a</name>"));
+
+        // score F5.2 → 99.99, columns 10-14.
+        assert!(xml.contains("<range from=\"0.00\" to=\"99.99\"/>"));
+        assert!(xml.contains("<position start=\"10\" finish=\"14\"/>"));
+
+        // city A20 → character 15-34; visit → date 35-42.
+        assert!(xml.contains("<position start=\"15\" finish=\"34\"/>"));
+        assert!(xml.contains("<size>20</size>"));
+        assert!(xml.contains("<variable ident=\"6\" type=\"date\">"));
+        assert!(xml.contains("<position start=\"35\" finish=\"42\"/>"));
+        assert!(xml.ends_with("    </record>\n  </survey>\n</sss>\n"));
+    }
+
+    #[test]
+    fn test_sss_category_set_spread() {
+        let mut meta = metadata();
+        meta.mr_sets.clear();
+        meta.mr_sets.insert(
+            "$brands".to_string(),
+            MrSet {
+                name: "$brands".to_string(),
+                label: "Brands used".to_string(),
+                mr_type: MrType::MultipleCategory,
+                counted_value: None,
+                variables: vec!["aware_a".to_string(), "aware_b".to_string()],
+            },
+        );
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(12.0), "Other".to_string());
+        labels.insert(Value::Numeric(1.0), "Acme".to_string());
+        meta.variable_value_labels
+            .insert("aware_b".to_string(), labels);
+
+        let xml = to_sss_xml(&meta);
+        assert!(xml.contains("<spread subfields=\"2\" width=\"2\"/>"));
+        assert!(xml.contains("<position start=\"8\" finish=\"11\"/>"));
+        let acme = xml.find("<value code=\"1\">Acme</value>").unwrap();
+        let other = xml.find("<value code=\"12\">Other</value>").unwrap();
+        assert!(acme < other);
+    }
+}
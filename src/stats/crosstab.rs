@@ -0,0 +1,323 @@
+//! Two-way contingency tables.
+
+use std::fmt;
+
+use arrow::array::RecordBatch;
+use indexmap::IndexSet;
+
+use super::{
+    Cell, Weight, case_weights, cells, column, format_count, is_user_missing, percent, value_order,
+};
+use crate::error::{Result, SpssError};
+use crate::metadata::{SpssMetadata, Value};
+
+/// A weighted cross-tabulation of two variables, from [`crosstab`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crosstab {
+    pub row_variable: String,
+    pub col_variable: String,
+    /// Categories of the row variable.
+    pub rows: Vec<Category>,
+    /// Categories of the column variable.
+    pub cols: Vec<Category>,
+    /// Weighted counts, `counts[row][col]`.
+    pub counts: Vec<Vec<f64>>,
+    /// Unweighted counts, laid out as `counts`.
+    pub unweighted: Vec<Vec<usize>>,
+}
+
+/// One category (value) of a crosstab variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub value: Value,
+    pub label: Option<String>,
+}
+
+impl Crosstab {
+    /// Weighted total of each row.
+    pub fn row_totals(&self) -> Vec<f64> {
+        self.counts.iter().map(|row| row.iter().sum()).collect()
+    }
+
+    /// Weighted total of each column.
+    pub fn col_totals(&self) -> Vec<f64> {
+        (0..self.cols.len())
+            .map(|j| self.counts.iter().map(|row| row[j]).sum())
+            .collect()
+    }
+
+    /// Weighted number of cases in the table.
+    pub fn total(&self) -> f64 {
+        self.row_totals().iter().sum()
+    }
+
+    /// Each cell as a percentage of its row total.
+    pub fn row_percents(&self) -> Vec<Vec<f64>> {
+        self.counts
+            .iter()
+            .zip(self.row_totals())
+            .map(|(row, total)| row.iter().map(|&n| percent(n, total)).collect())
+            .collect()
+    }
+
+    /// Each cell as a percentage of its column total.
+    pub fn col_percents(&self) -> Vec<Vec<f64>> {
+        let totals = self.col_totals();
+        self.counts
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&totals)
+                    .map(|(&n, &total)| percent(n, total))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Each cell as a percentage of the table total.
+    pub fn total_percents(&self) -> Vec<Vec<f64>> {
+        let total = self.total();
+        self.counts
+            .iter()
+            .map(|row| row.iter().map(|&n| percent(n, total)).collect())
+            .collect()
+    }
+}
+
+/// Cross-tabulate `row_var` by `col_var`.
+///
+/// Only cases with valid values of both variables are counted: user-missing
+/// and system-missing values are left out, as are cases `weight` leaves
+/// out. Each variable's categories are its labelled valid values and the
+/// values found in the data, in ascending order, so empty labelled
+/// categories show as zero rows or columns. Variables are numeric or
+/// string, by name or by column name after `ScanOptions::sanitize_names`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::stats::{self, Weight};
+///
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let table = stats::crosstab(&batch, &meta, "Q1", "gender", Weight::File).unwrap();
+/// let by_gender = table.col_percents();
+/// println!("{table}");
+/// ```
+pub fn crosstab(
+    batch: &RecordBatch,
+    meta: &SpssMetadata,
+    row_var: &str,
+    col_var: &str,
+    weight: Weight,
+) -> Result<Crosstab> {
+    let (row_values, row_variable) = column(batch, meta, row_var)?;
+    let (col_values, col_variable) = column(batch, meta, col_var)?;
+    if row_variable == col_variable {
+        return Err(SpssError::InvalidVariable(format!(
+            "{row_variable}: cannot cross-tabulate a variable by itself"
+        )));
+    }
+    let weights = case_weights(batch, meta, weight)?;
+
+    let mut row_set = categories(meta, row_variable);
+    let mut col_set = categories(meta, col_variable);
+    let mut pairs = Vec::new();
+    let row_cells = cells(row_values, meta, row_variable)?;
+    let col_cells = cells(col_values, meta, col_variable)?;
+    for ((row, col), w) in row_cells.into_iter().zip(col_cells).zip(weights) {
+        if let (Cell::Valid(row), Cell::Valid(col)) = (row, col)
+            && w > 0.0
+        {
+            let (i, _) = row_set.insert_full(row);
+            let (j, _) = col_set.insert_full(col);
+            pairs.push((i, j, w));
+        }
+    }
+
+    // Sort the categories, then move each case to its sorted position.
+    let row_order = sorted(&mut row_set);
+    let col_order = sorted(&mut col_set);
+    let mut counts = vec![vec![0.0; col_set.len()]; row_set.len()];
+    let mut unweighted = vec![vec![0; col_set.len()]; row_set.len()];
+    for (i, j, w) in pairs {
+        let (i, j) = (row_order[i], col_order[j]);
+        counts[i][j] += w;
+        unweighted[i][j] += 1;
+    }
+
+    let category = |variable: &str, value: Value| {
+        let label = meta
+            .value_labels(variable)
+            .and_then(|labels| labels.get(&value))
+            .cloned();
+        Category { value, label }
+    };
+    Ok(Crosstab {
+        row_variable: row_variable.to_string(),
+        col_variable: col_variable.to_string(),
+        rows: row_set
+            .into_iter()
+            .map(|value| category(row_variable, value))
+            .collect(),
+        cols: col_set
+            .into_iter()
+            .map(|value| category(col_variable, value))
+            .collect(),
+        counts,
+        unweighted,
+    })
+}
+
+/// The labelled valid values of `variable`, to which values found in the
+/// data are added.
+fn categories(meta: &SpssMetadata, variable: &str) -> IndexSet<Value> {
+    let specs = meta
+        .variable_missing
+        .get(variable)
+        .map_or(&[][..], Vec::as_slice);
+    meta.value_labels(variable)
+        .into_iter()
+        .flatten()
+        .map(|(value, _)| value)
+        .filter(|value| !is_user_missing(specs, value))
+        .cloned()
+        .collect()
+}
+
+/// Sort `set` in ascending order, returning the new position of each
+/// value by its old position.
+fn sorted(set: &mut IndexSet<Value>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..set.len()).collect();
+    order.sort_by(|&a, &b| value_order(&set[a], &set[b]));
+    set.sort_by(value_order);
+    let mut position = vec![0; order.len()];
+    for (new, old) in order.into_iter().enumerate() {
+        position[old] = new;
+    }
+    position
+}
+
+impl fmt::Display for Crosstab {
+    // Weighted counts with column percentages, and row and column totals.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |category: &Category| match &category.label {
+            Some(label) => label.clone(),
+            None => category.value.to_string(),
+        };
+        let col_totals = self.col_totals();
+        let total = self.total();
+        let percents = self.col_percents();
+        let cell = |n: f64, p: f64| format!("{} ({p:.1}%)", format_count(n));
+
+        let mut header = vec![format!("{} \\ {}", self.row_variable, self.col_variable)];
+        header.extend(self.cols.iter().map(name));
+        header.push("Total".to_string());
+        let mut lines = vec![header];
+        for ((category, row), (percents, row_total)) in self
+            .rows
+            .iter()
+            .zip(&self.counts)
+            .zip(percents.iter().zip(self.row_totals()))
+        {
+            let mut line = vec![name(category)];
+            line.extend(row.iter().zip(percents).map(|(&n, &p)| cell(n, p)));
+            line.push(cell(row_total, percent(row_total, total)));
+            lines.push(line);
+        }
+        let mut line = vec!["Total".to_string()];
+        line.extend(col_totals.iter().map(|&n| cell(n, percent(n, n))));
+        line.push(cell(total, percent(total, total)));
+        lines.push(line);
+
+        let widths: Vec<usize> = (0..lines[0].len())
+            .map(|j| {
+                lines
+                    .iter()
+                    .map(|l| l[j].chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for (k, line) in lines.iter().enumerate() {
+            if k > 0 {
+                writeln!(f)?;
+            }
+            for (j, text) in line.iter().enumerate() {
+                if j == 0 {
+                    write!(f, "{text:<width$}", width = widths[0])?;
+                } else {
+                    write!(f, "  {text:>width$}", width = widths[j])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::metadata::MissingSpec;
+
+    #[test]
+    fn test_crosstab() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("sex", DataType::Utf8, true),
+            Field::new("w", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(2.0),
+                    Some(1.0),
+                    Some(1.0),
+                    Some(9.0),
+                    None,
+                    Some(2.0),
+                ])),
+                Arc::new(StringArray::from(vec!["f", "m", "f", "m", "f", "m"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 1.0, 1.0, 1.0, 1.0])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "sex".into(), "w".into()],
+            weight_variable: Some("w".into()),
+            ..SpssMetadata::default()
+        };
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes".to_string()),
+                (Value::Numeric(2.0), "No".to_string()),
+                (Value::Numeric(3.0), "Maybe".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+
+        let table = crosstab(&batch, &meta, "q1", "sex", Weight::File).unwrap();
+        let rows: Vec<_> = table.rows.iter().map(|c| c.label.as_deref()).collect();
+        assert_eq!(rows, [Some("Yes"), Some("No"), Some("Maybe")]);
+        let cols: Vec<_> = table.cols.iter().map(|c| c.value.to_string()).collect();
+        assert_eq!(cols, ["f", "m"]);
+        assert_eq!(table.counts, [[1.0, 2.0], [1.0, 1.0], [0.0, 0.0]]);
+        assert_eq!(table.unweighted, [[1, 1], [1, 1], [0, 0]]);
+        assert_eq!(table.total(), 5.0);
+        assert_eq!(table.col_percents()[0], [50.0, 200.0 / 3.0]);
+        assert_eq!(table.row_percents()[2], [0.0, 0.0]);
+
+        let text = table.to_string();
+        assert!(text.starts_with("q1 \\ sex"));
+        assert!(text.contains("\nYes        1 (50.0%)   2 (66.7%)   3 (60.0%)\n"));
+        assert!(text.ends_with("Total     2 (100.0%)  3 (100.0%)  5 (100.0%)"));
+    }
+}
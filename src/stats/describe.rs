@@ -0,0 +1,231 @@
+//! Weighted descriptive statistics of numeric variables.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+
+use super::{Cell, Weight, case_weights, cells, column};
+use crate::error::{Result, SpssError};
+use crate::metadata::{SpssMetadata, Value};
+
+/// Describe numeric variables of `batch`, one row per variable.
+///
+/// The result has the columns `variable`, `label`, `n` (valid cases,
+/// unweighted), `weighted_n`, `mean`, `stddev`, `min`, `p25`, `median`,
+/// `p75` and `max`. User-missing and system-missing values are left out.
+/// With no `vars`, every numeric column except the weight is described.
+///
+/// Weights are frequency weights: the standard deviation divides by the
+/// weighted count minus one, and quantiles are read off the cumulative
+/// weights, averaging the two neighbours when a quantile falls exactly
+/// between values. Statistics without enough cases are null.
+///
+/// # Example
+/// ```no_run
+/// use ambers::stats::{self, Weight};
+///
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let stats = stats::describe(&batch, &meta, &["age", "income"], Weight::File).unwrap();
+/// ```
+pub fn describe<S: AsRef<str>>(
+    batch: &RecordBatch,
+    meta: &SpssMetadata,
+    vars: &[S],
+    weight: Weight,
+) -> Result<RecordBatch> {
+    let weights = case_weights(batch, meta, weight)?;
+    let weight_name = match weight {
+        Weight::File => meta.weight_variable.as_deref(),
+        Weight::Unweighted => None,
+        Weight::Variable(name) => Some(name),
+    };
+    let vars: Vec<&str> = if vars.is_empty() {
+        batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .filter(|field| field.data_type().is_numeric())
+            .map(|field| field.name().as_str())
+            .filter(|name| {
+                let variable = meta
+                    .renamed_columns
+                    .get(*name)
+                    .map_or(*name, String::as_str);
+                Some(variable) != weight_name && Some(*name) != weight_name
+            })
+            .collect()
+    } else {
+        vars.iter().map(AsRef::as_ref).collect()
+    };
+
+    let mut names = Vec::new();
+    let mut labels = Vec::new();
+    let mut rows: Vec<Summary> = Vec::new();
+    for var in vars {
+        let (values, variable) = column(batch, meta, var)?;
+        if !values.data_type().is_numeric() {
+            return Err(SpssError::InvalidVariable(format!(
+                "{variable}: cannot describe a {} column",
+                values.data_type()
+            )));
+        }
+        let mut valid: Vec<(f64, f64)> = cells(values, meta, variable)?
+            .into_iter()
+            .zip(&weights)
+            .filter_map(|(cell, &w)| match cell {
+                Cell::Valid(Value::Numeric(x)) if w > 0.0 => Some((x, w)),
+                _ => None,
+            })
+            .collect();
+        valid.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        names.push(variable.to_string());
+        labels.push(meta.label(variable).map(str::to_string));
+        rows.push(Summary::of(&valid));
+    }
+
+    let stat = |f: fn(&Summary) -> Option<f64>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<Float64Array>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(names)),
+        Arc::new(StringArray::from(labels)),
+        Arc::new(rows.iter().map(|s| s.n as i64).collect::<Int64Array>()),
+        Arc::new(rows.iter().map(|s| s.weighted_n).collect::<Float64Array>()),
+        stat(|s| s.mean),
+        stat(|s| s.stddev),
+        stat(|s| s.quantiles[0]),
+        stat(|s| s.quantiles[1]),
+        stat(|s| s.quantiles[2]),
+        stat(|s| s.quantiles[3]),
+        stat(|s| s.quantiles[4]),
+    ];
+    let mut fields = vec![
+        Field::new("variable", DataType::Utf8, false),
+        Field::new("label", DataType::Utf8, true),
+        Field::new("n", DataType::Int64, false),
+        Field::new("weighted_n", DataType::Float64, false),
+    ];
+    for name in ["mean", "stddev", "min", "p25", "median", "p75", "max"] {
+        fields.push(Field::new(name, DataType::Float64, true));
+    }
+    let schema = Arc::new(Schema::new(fields));
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Statistics of one variable.
+struct Summary {
+    n: usize,
+    weighted_n: f64,
+    mean: Option<f64>,
+    stddev: Option<f64>,
+    /// Min, p25, median, p75, max.
+    quantiles: [Option<f64>; 5],
+}
+
+impl Summary {
+    /// Summarize `(value, weight)` pairs sorted by value, all weights > 0.
+    fn of(values: &[(f64, f64)]) -> Summary {
+        let weighted_n: f64 = values.iter().map(|(_, w)| w).sum();
+        let mean = (!values.is_empty())
+            .then(|| values.iter().map(|(x, w)| x * w).sum::<f64>() / weighted_n);
+        let stddev = mean.filter(|_| weighted_n > 1.0).map(|mean| {
+            let ss: f64 = values.iter().map(|(x, w)| w * (x - mean).powi(2)).sum();
+            (ss / (weighted_n - 1.0)).sqrt()
+        });
+        Summary {
+            n: values.len(),
+            weighted_n,
+            mean,
+            stddev,
+            quantiles: [0.0, 0.25, 0.5, 0.75, 1.0].map(|p| quantile(values, weighted_n, p)),
+        }
+    }
+}
+
+/// The `p` quantile of `(value, weight)` pairs sorted by value.
+fn quantile(values: &[(f64, f64)], total: f64, p: f64) -> Option<f64> {
+    let target = p * total;
+    let mut cumulative = 0.0;
+    for (i, &(x, w)) in values.iter().enumerate() {
+        cumulative += w;
+        let gap = cumulative - target;
+        if gap.abs() <= 1e-9 * total.max(1.0) {
+            // Exactly between this value and the next.
+            let next = values.get(i + 1).map_or(x, |&(next, _)| next);
+            return Some(if p > 0.0 { (x + next) / 2.0 } else { x });
+        }
+        if gap > 0.0 {
+            return Some(x);
+        }
+    }
+    values.last().map(|&(x, _)| x)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Float64Type, Int64Type};
+
+    use super::*;
+    use crate::metadata::MissingSpec;
+
+    #[test]
+    fn test_quantile() {
+        let unweighted: Vec<(f64, f64)> = [1.0, 2.0, 3.0, 4.0].map(|x| (x, 1.0)).to_vec();
+        assert_eq!(quantile(&unweighted, 4.0, 0.5), Some(2.5));
+        assert_eq!(quantile(&unweighted, 4.0, 0.0), Some(1.0));
+        assert_eq!(quantile(&unweighted, 4.0, 1.0), Some(4.0));
+        let weighted = [(1.0, 1.0), (2.0, 3.0)];
+        assert_eq!(quantile(&weighted, 4.0, 0.5), Some(2.0));
+        assert_eq!(quantile(&[], 0.0, 0.5), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("age", DataType::Float64, true),
+            Field::new("w", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(20.0),
+                    Some(30.0),
+                    Some(99.0),
+                    None,
+                    Some(40.0),
+                ])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 1.0, 1.0, 1.0])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata {
+            variable_names: vec!["age".into(), "w".into()],
+            weight_variable: Some("w".into()),
+            ..SpssMetadata::default()
+        };
+        meta.variable_missing
+            .insert("age".into(), vec![MissingSpec::Value(99.0)]);
+
+        let stats = describe::<&str>(&batch, &meta, &[], Weight::File).unwrap();
+        assert_eq!(stats.num_rows(), 1);
+        let value = |name: &str| {
+            let column = stats.column_by_name(name).unwrap();
+            (!column.is_null(0)).then(|| column.as_primitive::<Float64Type>().value(0))
+        };
+        let n = stats.column_by_name("n").unwrap();
+        assert_eq!(n.as_primitive::<Int64Type>().value(0), 3);
+        assert_eq!(value("weighted_n"), Some(4.0));
+        assert_eq!(value("mean"), Some(30.0));
+        assert_eq!(value("stddev"), Some((200.0f64 / 3.0).sqrt()));
+        assert_eq!(value("min"), Some(20.0));
+        assert_eq!(value("median"), Some(30.0));
+        assert_eq!(value("max"), Some(40.0));
+
+        let stats = describe(&batch, &meta, &["age"], Weight::Unweighted).unwrap();
+        let column = stats.column_by_name("mean").unwrap();
+        assert_eq!(column.as_primitive::<Float64Type>().value(0), 30.0);
+    }
+}
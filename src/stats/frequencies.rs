@@ -0,0 +1,288 @@
+//! One-way frequency tables.
+
+use std::fmt;
+
+use arrow::array::RecordBatch;
+use indexmap::IndexMap;
+
+use super::{
+    Cell, Weight, case_weights, cells, column, format_count, is_user_missing, percent, value_order,
+};
+use crate::error::Result;
+use crate::metadata::{SpssMetadata, Value};
+
+/// Frequencies of one variable, laid out like SPSS FREQUENCIES output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyTable {
+    pub variable: String,
+    pub label: Option<String>,
+    /// Valid values: every labelled value and every value in the data, in
+    /// ascending order. Labelled values that do not occur have a count of 0.
+    pub valid: Vec<FrequencyRow>,
+    /// User-missing values in ascending order, then system-missing (a row
+    /// with no value) when there is any.
+    pub missing: Vec<FrequencyRow>,
+    /// Weighted number of cases.
+    pub total: f64,
+    /// Weighted number of cases with a valid value.
+    pub valid_total: f64,
+}
+
+/// One value of a [`FrequencyTable`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyRow {
+    /// The value; `None` for system-missing.
+    pub value: Option<Value>,
+    pub label: Option<String>,
+    /// Cases, unweighted.
+    pub count: usize,
+    /// Cases, weighted.
+    pub weighted: f64,
+    /// Percent of all cases (weighted).
+    pub percent: f64,
+    /// Percent of valid cases (weighted); `None` for missing values.
+    pub valid_percent: Option<f64>,
+}
+
+/// Tabulate variable `var` of `batch`.
+///
+/// Cases are weighted by `weight`. `var` is a numeric or string variable,
+/// by name or by its column name after `ScanOptions::sanitize_names`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::stats::{self, Weight};
+///
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let table = stats::frequencies(&batch, &meta, "Q1", Weight::File).unwrap();
+/// println!("{table}");
+/// ```
+pub fn frequencies(
+    batch: &RecordBatch,
+    meta: &SpssMetadata,
+    var: &str,
+    weight: Weight,
+) -> Result<FrequencyTable> {
+    let (values, variable) = column(batch, meta, var)?;
+    let weights = case_weights(batch, meta, weight)?;
+    let specs = meta
+        .variable_missing
+        .get(variable)
+        .map_or(&[][..], Vec::as_slice);
+    let labels = meta.value_labels(variable);
+
+    // {value -> (count, weighted)}, labelled values first.
+    let mut valid: IndexMap<Value, (usize, f64)> = IndexMap::new();
+    let mut missing: IndexMap<Value, (usize, f64)> = IndexMap::new();
+    for value in labels.into_iter().flatten().map(|(value, _)| value) {
+        let counts = if is_user_missing(specs, value) {
+            &mut missing
+        } else {
+            &mut valid
+        };
+        counts.insert(value.clone(), (0, 0.0));
+    }
+    let mut system_missing = (0, 0.0);
+    for (cell, w) in cells(values, meta, variable)?.into_iter().zip(weights) {
+        if w <= 0.0 {
+            continue;
+        }
+        let entry = match cell {
+            Cell::Valid(value) => valid.entry(value).or_default(),
+            Cell::UserMissing(value) => missing.entry(value).or_default(),
+            Cell::SystemMissing => &mut system_missing,
+        };
+        entry.0 += 1;
+        entry.1 += w;
+    }
+    valid.sort_by(|a, _, b, _| value_order(a, b));
+    missing.sort_by(|a, _, b, _| value_order(a, b));
+
+    let valid_total: f64 = valid.values().map(|(_, w)| w).sum();
+    let total = valid_total + missing.values().map(|(_, w)| w).sum::<f64>() + system_missing.1;
+    let row = |value: Option<Value>, (count, weighted): (usize, f64), is_valid: bool| {
+        let label = value
+            .as_ref()
+            .and_then(|value| labels.and_then(|l| l.get(value)))
+            .cloned();
+        FrequencyRow {
+            value,
+            label,
+            count,
+            weighted,
+            percent: percent(weighted, total),
+            valid_percent: is_valid.then(|| percent(weighted, valid_total)),
+        }
+    };
+    let mut table = FrequencyTable {
+        variable: variable.to_string(),
+        label: meta.label(variable).map(str::to_string),
+        valid: valid
+            .into_iter()
+            .map(|(value, counts)| row(Some(value), counts, true))
+            .collect(),
+        missing: missing
+            .into_iter()
+            .map(|(value, counts)| row(Some(value), counts, false))
+            .collect(),
+        total,
+        valid_total,
+    };
+    if system_missing.0 > 0 {
+        table.missing.push(row(None, system_missing, false));
+    }
+    Ok(table)
+}
+
+impl fmt::Display for FrequencyTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => writeln!(f, "{}: {label}", self.variable)?,
+            None => writeln!(f, "{}", self.variable)?,
+        }
+        let value_of = |row: &FrequencyRow| {
+            let value = row
+                .value
+                .as_ref()
+                .map_or_else(|| "(system-missing)".to_string(), Value::to_string);
+            match &row.label {
+                Some(label) => format!("{value} {label}"),
+                None => value,
+            }
+        };
+        let width = self
+            .valid
+            .iter()
+            .chain(&self.missing)
+            .map(|row| value_of(row).chars().count())
+            .chain([5])
+            .max()
+            .unwrap_or(5);
+        writeln!(
+            f,
+            "  {:width$}  {:>10}  {:>7}  {:>7}",
+            "Value", "Frequency", "Percent", "Valid %"
+        )?;
+        for row in self.valid.iter().chain(&self.missing) {
+            let valid_percent = row
+                .valid_percent
+                .map_or_else(String::new, |p| format!("{p:.1}"));
+            writeln!(
+                f,
+                "  {:width$}  {:>10}  {:>7.1}  {:>7}",
+                value_of(row),
+                format_count(row.weighted),
+                row.percent,
+                valid_percent
+            )?;
+        }
+        write!(
+            f,
+            "  {:width$}  {:>10}  {:>7.1}",
+            "Total",
+            format_count(self.total),
+            if self.total > 0.0 { 100.0 } else { 0.0 }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::metadata::MissingSpec;
+
+    #[test]
+    fn test_weighted_frequencies() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+            Field::new("w", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(1.0),
+                    Some(2.0),
+                    Some(1.0),
+                    Some(9.0),
+                    None,
+                    Some(4.0),
+                ])),
+                Arc::new(StringArray::from(vec!["NY ", "LA", "NY", "", "LA", "NY"])),
+                Arc::new(Float64Array::from(vec![
+                    Some(1.5),
+                    Some(1.0),
+                    Some(0.5),
+                    Some(1.0),
+                    Some(1.0),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "city".into(), "w".into()],
+            weight_variable: Some("w".into()),
+            ..SpssMetadata::default()
+        };
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes".to_string()),
+                (Value::Numeric(2.0), "No".to_string()),
+                (Value::Numeric(3.0), "Maybe".to_string()),
+                (Value::Numeric(9.0), "Refused".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+
+        let table = frequencies(&batch, &meta, "q1", Weight::File).unwrap();
+        let valid: Vec<(String, usize, f64)> = table
+            .valid
+            .iter()
+            .map(|r| (r.value.as_ref().unwrap().to_string(), r.count, r.weighted))
+            .collect();
+        assert_eq!(
+            valid,
+            [
+                ("1".to_string(), 2, 2.0),
+                ("2".to_string(), 1, 1.0),
+                ("3".to_string(), 0, 0.0),
+            ]
+        );
+        assert_eq!(table.valid[0].label.as_deref(), Some("Yes"));
+        assert_eq!(table.total, 5.0);
+        assert_eq!(table.valid_total, 3.0);
+        assert_eq!(table.valid[0].percent, 40.0);
+        assert_eq!(table.valid[1].valid_percent, Some(100.0 / 3.0));
+        assert_eq!(table.missing.len(), 2);
+        assert_eq!(table.missing[0].value, Some(Value::Numeric(9.0)));
+        assert_eq!(table.missing[0].valid_percent, None);
+        assert_eq!(table.missing[1].value, None);
+        assert!(table.to_string().contains("1 Yes"));
+
+        let table = frequencies(&batch, &meta, "city", Weight::Unweighted).unwrap();
+        let valid: Vec<(String, usize)> = table
+            .valid
+            .iter()
+            .map(|r| (r.value.as_ref().unwrap().to_string(), r.count))
+            .collect();
+        assert_eq!(
+            valid,
+            [
+                (String::new(), 1),
+                ("LA".to_string(), 2),
+                ("NY".to_string(), 3)
+            ]
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Missing-value pattern analysis.
+
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use indexmap::IndexMap;
+
+use super::{Cell, cells, percent};
+use crate::error::Result;
+use crate::metadata::SpssMetadata;
+
+/// Missingness of a batch, from [`missing_patterns`].
+#[derive(Debug, Clone)]
+pub struct MissingReport {
+    /// One row per column: `variable`, `label`, `valid`, `system_missing`,
+    /// `user_missing` and `percent_missing`.
+    pub variables: RecordBatch,
+    /// The most common patterns, most frequent first: `pattern`,
+    /// `missing_variables`, `n_missing`, `count` and `percent`.
+    ///
+    /// A pattern has one character per column, in column order: `.` for a
+    /// valid value, `S` for system-missing and `U` for user-missing.
+    /// `missing_variables` lists the columns missing in the pattern,
+    /// separated by commas.
+    pub patterns: RecordBatch,
+}
+
+/// Count the missing values of every column of `batch` and the most common
+/// combinations of missing columns, keeping at most `max_patterns`.
+///
+/// User-missing values are those of `metadata.variable_missing`; nulls are
+/// system-missing. Counts are unweighted. Columns that are neither numeric
+/// nor string (dates, labels) have only system-missing values.
+///
+/// # Example
+/// ```no_run
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let report = ambers::stats::missing_patterns(&batch, &meta, 10).unwrap();
+/// println!("{} patterns", report.patterns.num_rows());
+/// ```
+pub fn missing_patterns(
+    batch: &RecordBatch,
+    meta: &SpssMetadata,
+    max_patterns: usize,
+) -> Result<MissingReport> {
+    let schema = batch.schema();
+    let rows = batch.num_rows();
+    let mut names = Vec::new();
+    let mut labels = Vec::new();
+    // One pattern character per column, per row.
+    let mut codes: Vec<Vec<u8>> = Vec::new();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let name = field.name().as_str();
+        let variable = meta.renamed_columns.get(name).map_or(name, String::as_str);
+        let data_type = column.data_type();
+        let column_codes: Vec<u8> = if data_type.is_numeric()
+            || matches!(
+                data_type,
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+            ) {
+            cells(column, meta, variable)?
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Valid(_) => b'.',
+                    Cell::UserMissing(_) => b'U',
+                    Cell::SystemMissing => b'S',
+                })
+                .collect()
+        } else {
+            (0..rows)
+                .map(|i| if column.is_null(i) { b'S' } else { b'.' })
+                .collect()
+        };
+        names.push(name.to_string());
+        labels.push(meta.label(variable).map(str::to_string));
+        codes.push(column_codes);
+    }
+
+    let count = |code: u8| -> Vec<i64> {
+        codes
+            .iter()
+            .map(|c| c.iter().filter(|&&b| b == code).count() as i64)
+            .collect()
+    };
+    let (valid, system, user) = (count(b'.'), count(b'S'), count(b'U'));
+    let percent_missing: Float64Array = valid
+        .iter()
+        .map(|&n| Some(percent((rows as i64 - n) as f64, rows as f64)))
+        .collect();
+    let variables = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("variable", DataType::Utf8, false),
+            Field::new("label", DataType::Utf8, true),
+            Field::new("valid", DataType::Int64, false),
+            Field::new("system_missing", DataType::Int64, false),
+            Field::new("user_missing", DataType::Int64, false),
+            Field::new("percent_missing", DataType::Float64, false),
+        ])),
+        vec![
+            Arc::new(StringArray::from(names.clone())),
+            Arc::new(StringArray::from(labels)),
+            Arc::new(Int64Array::from(valid)),
+            Arc::new(Int64Array::from(system)),
+            Arc::new(Int64Array::from(user)),
+            Arc::new(percent_missing),
+        ],
+    )?;
+
+    // {pattern -> rows}, in order of first appearance.
+    let mut counts: IndexMap<Vec<u8>, usize> = IndexMap::new();
+    for row in 0..rows {
+        let pattern: Vec<u8> = codes.iter().map(|c| c[row]).collect();
+        *counts.entry(pattern).or_default() += 1;
+    }
+    // Stable, so ties keep their first-appearance order.
+    counts.sort_by(|_, a, _, b| b.cmp(a));
+    counts.truncate(max_patterns);
+
+    let missing_variables: Vec<String> = counts
+        .keys()
+        .map(|pattern| {
+            pattern
+                .iter()
+                .zip(&names)
+                .filter(|(code, _)| **code != b'.')
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            counts
+                .keys()
+                .map(|p| Some(String::from_utf8_lossy(p).into_owned()))
+                .collect::<StringArray>(),
+        ),
+        Arc::new(StringArray::from(missing_variables)),
+        Arc::new(
+            counts
+                .keys()
+                .map(|p| Some(p.iter().filter(|&&b| b != b'.').count() as i64))
+                .collect::<Int64Array>(),
+        ),
+        Arc::new(
+            counts
+                .values()
+                .map(|&n| Some(n as i64))
+                .collect::<Int64Array>(),
+        ),
+        Arc::new(
+            counts
+                .values()
+                .map(|&n| Some(percent(n as f64, rows as f64)))
+                .collect::<Float64Array>(),
+        ),
+    ];
+    let patterns = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("pattern", DataType::Utf8, false),
+            Field::new("missing_variables", DataType::Utf8, false),
+            Field::new("n_missing", DataType::Int64, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("percent", DataType::Float64, false),
+        ])),
+        columns,
+    )?;
+    Ok(MissingReport {
+        variables,
+        patterns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+
+    use super::*;
+    use crate::metadata::MissingSpec;
+
+    #[test]
+    fn test_missing_patterns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("q1", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(1.0),
+                    None,
+                    Some(9.0),
+                    Some(2.0),
+                    None,
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("NY"),
+                    Some("LA"),
+                    None,
+                    Some("LA"),
+                    Some("NY"),
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_missing
+            .insert("q1".into(), vec![MissingSpec::Value(9.0)]);
+
+        let report = missing_patterns(&batch, &meta, 2).unwrap();
+        let ints = |batch: &RecordBatch, name: &str| -> Vec<i64> {
+            let column = batch.column_by_name(name).unwrap();
+            column.as_primitive::<Int64Type>().values().to_vec()
+        };
+        let vars = &report.variables;
+        assert_eq!(ints(vars, "valid"), [2, 4]);
+        assert_eq!(ints(vars, "system_missing"), [2, 1]);
+        assert_eq!(ints(vars, "user_missing"), [1, 0]);
+
+        let patterns = &report.patterns;
+        assert_eq!(patterns.num_rows(), 2);
+        let strings = |name: &str| -> Vec<String> {
+            let column = patterns.column_by_name(name).unwrap();
+            column
+                .as_string::<i32>()
+                .iter()
+                .map(|s| s.unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(strings("pattern"), ["..", "S."]);
+        assert_eq!(strings("missing_variables"), ["", "q1"]);
+        assert_eq!(ints(patterns, "count"), [2, 2]);
+        assert_eq!(ints(patterns, "n_missing"), [0, 1]);
+    }
+}
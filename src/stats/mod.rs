@@ -0,0 +1,229 @@
+//! Weighted survey statistics on batches read with their metadata.
+//!
+//! Functions take the batch, its `SpssMetadata` and, to weight cases, a
+//! [`Weight`]. User-missing values (per `metadata.variable_missing`) are set
+//! apart from valid values, and cases whose weight is null, user-missing,
+//! zero or negative are left out, as SPSS does.
+//!
+//! - [`frequencies`] — counts and percentages per value, labelled.
+//! - [`describe`] — mean, standard deviation and quantiles per numeric
+//!   variable, as a `RecordBatch`.
+//! - [`crosstab`] — a labelled two-way table with row and column
+//!   percentages.
+//! - [`mr_frequencies`] — responses and cases per category of a multiple
+//!   response set.
+//! - [`missing_patterns`] — system- and user-missing counts per column and
+//!   the most common combinations of missing columns.
+//! - [`ScanStatistics`] — min, max, null count and distinct-value estimate
+//!   per column, collected batch by batch (see `ScanOptions::statistics`).
+
+mod crosstab;
+mod describe;
+mod frequencies;
+mod missing;
+mod mr;
+mod profile;
+
+use std::cmp::Ordering;
+
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type};
+
+use crate::columnar::{is_missing_f64, is_missing_str};
+use crate::error::{Result, SpssError};
+use crate::metadata::{MissingSpec, SpssMetadata, Value};
+
+pub use crosstab::{Category, Crosstab, crosstab};
+pub use describe::describe;
+pub use frequencies::{FrequencyRow, FrequencyTable, frequencies};
+pub use missing::{MissingReport, missing_patterns};
+pub use mr::{MrFrequencyRow, MrFrequencyTable, mr_frequencies};
+pub use profile::{ColumnStatistics, ScanStatistics};
+
+/// How cases are weighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weight<'a> {
+    /// By the file's weight variable (`metadata.weight_variable`), if any.
+    #[default]
+    File,
+    /// Every case counts once.
+    Unweighted,
+    /// By the named variable.
+    Variable(&'a str),
+}
+
+/// One cell of a variable, classified by the variable's missing values.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Valid(Value),
+    UserMissing(Value),
+    SystemMissing,
+}
+
+/// The column of variable `name` in `batch`, and the variable's name in
+/// the metadata. `name` may be the variable name or its column name after
+/// `ScanOptions::sanitize_names`.
+fn column<'a>(
+    batch: &'a RecordBatch,
+    meta: &'a SpssMetadata,
+    name: &'a str,
+) -> Result<(&'a ArrayRef, &'a str)> {
+    if let Some(column) = batch.column_by_name(name) {
+        let variable = meta.renamed_columns.get(name).map_or(name, String::as_str);
+        return Ok((column, variable));
+    }
+    meta.renamed_columns
+        .iter()
+        .find(|(_, original)| *original == name)
+        .and_then(|(renamed, _)| batch.column_by_name(renamed))
+        .map(|column| (column, name))
+        .ok_or_else(|| SpssError::InvalidVariable(format!("{name}: not in batch")))
+}
+
+/// The weight of each row of `batch`; 0.0 for cases to leave out.
+fn case_weights(batch: &RecordBatch, meta: &SpssMetadata, weight: Weight) -> Result<Vec<f64>> {
+    let name = match weight {
+        Weight::File => meta.weight_variable.as_deref(),
+        Weight::Unweighted => None,
+        Weight::Variable(name) => Some(name),
+    };
+    let Some(name) = name else {
+        return Ok(vec![1.0; batch.num_rows()]);
+    };
+    let (column, variable) = column(batch, meta, name)?;
+    if !column.data_type().is_numeric() {
+        return Err(SpssError::InvalidVariable(format!(
+            "{variable}: weight must be numeric"
+        )));
+    }
+    let specs = meta
+        .variable_missing
+        .get(variable)
+        .map_or(&[][..], Vec::as_slice);
+    let weights = cast(column, &DataType::Float64)?;
+    Ok(weights
+        .as_primitive::<Float64Type>()
+        .iter()
+        .map(|w| match w {
+            Some(w) if w > 0.0 && !specs.iter().any(|spec| is_missing_f64(spec, w)) => w,
+            _ => 0.0,
+        })
+        .collect())
+}
+
+/// Classify each cell of a numeric or string column of `variable`.
+fn cells(column: &ArrayRef, meta: &SpssMetadata, variable: &str) -> Result<Vec<Cell>> {
+    let specs = meta
+        .variable_missing
+        .get(variable)
+        .map_or(&[][..], Vec::as_slice);
+    match column.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+            let strings = cast(column, &DataType::Utf8View)?;
+            Ok(strings
+                .as_string_view()
+                .iter()
+                .map(|v| match v {
+                    None => Cell::SystemMissing,
+                    Some(v) => {
+                        let value = Value::String(v.trim_end_matches(' ').to_string());
+                        if specs.iter().any(|spec| is_missing_str(spec, v)) {
+                            Cell::UserMissing(value)
+                        } else {
+                            Cell::Valid(value)
+                        }
+                    }
+                })
+                .collect())
+        }
+        data_type if data_type.is_numeric() => {
+            let codes = cast(column, &DataType::Float64)?;
+            Ok(codes
+                .as_primitive::<Float64Type>()
+                .iter()
+                .map(|v| match v {
+                    None => Cell::SystemMissing,
+                    Some(v) if v.is_nan() => Cell::SystemMissing,
+                    // +0.0 stands in for -0.0.
+                    Some(v) if specs.iter().any(|spec| is_missing_f64(spec, v)) => {
+                        Cell::UserMissing(Value::Numeric(v + 0.0))
+                    }
+                    Some(v) => Cell::Valid(Value::Numeric(v + 0.0)),
+                })
+                .collect())
+        }
+        other => Err(SpssError::InvalidVariable(format!(
+            "{variable}: cannot tabulate a {other} column"
+        ))),
+    }
+}
+
+/// Whether `value` is one of the variable's user-missing values.
+fn is_user_missing(specs: &[MissingSpec], value: &Value) -> bool {
+    specs.iter().any(|spec| match value {
+        Value::Numeric(v) => is_missing_f64(spec, *v),
+        Value::String(s) => is_missing_str(spec, s),
+    })
+}
+
+/// Ascending order: numbers by value, strings by bytes, numbers first.
+fn value_order(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Numeric(a), Value::Numeric(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Numeric(_), Value::String(_)) => Ordering::Less,
+        (Value::String(_), Value::Numeric(_)) => Ordering::Greater,
+    }
+}
+
+/// `part` as a percentage of `whole`; 0 when `whole` is 0.
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 {
+        100.0 * part / whole
+    } else {
+        0.0
+    }
+}
+
+/// Whole counts without decimals, weighted ones with two.
+fn format_count(n: f64) -> String {
+    if n.fract() == 0.0 {
+        format!("{n:.0}")
+    } else {
+        format!("{n:.2}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_case_weights() {
+        let schema = Arc::new(Schema::new(vec![Field::new("w", DataType::Float64, true)]));
+        let column = Float64Array::from(vec![Some(2.0), None, Some(-1.0), Some(99.0)]);
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(column)]).unwrap();
+        let mut meta = SpssMetadata {
+            weight_variable: Some("w".into()),
+            ..SpssMetadata::default()
+        };
+        meta.variable_missing
+            .insert("w".into(), vec![MissingSpec::Value(99.0)]);
+
+        assert_eq!(
+            case_weights(&batch, &meta, Weight::File).unwrap(),
+            [2.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            case_weights(&batch, &meta, Weight::Unweighted).unwrap(),
+            [1.0; 4]
+        );
+        assert!(case_weights(&batch, &meta, Weight::Variable("nope")).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Multiple response set frequencies.
+
+use std::fmt;
+
+use arrow::array::RecordBatch;
+use indexmap::IndexMap;
+
+use super::{
+    Cell, Weight, case_weights, cells, column, format_count, is_user_missing, percent, value_order,
+};
+use crate::error::{Result, SpssError};
+use crate::metadata::{MrSet, MrType, SpssMetadata, Value};
+
+/// Frequencies of a multiple response set, laid out like SPSS MULT
+/// RESPONSE output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MrFrequencyTable {
+    pub set: String,
+    pub label: String,
+    /// One row per member variable of a dichotomy set, or per value of a
+    /// category set in ascending order. The values of a category set are
+    /// the members' labelled valid values and the values found in the data.
+    pub rows: Vec<MrFrequencyRow>,
+    /// Weighted number of responses.
+    pub responses: f64,
+    /// Weighted number of cases with at least one response.
+    pub cases: f64,
+    /// Weighted number of cases with no response.
+    pub missing_cases: f64,
+}
+
+/// One response of a [`MrFrequencyTable`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MrFrequencyRow {
+    /// The member variable (dichotomy sets) or the value (category sets).
+    pub category: String,
+    /// The variable label (dichotomy sets) or the value label of the first
+    /// member that labels the value (category sets).
+    pub label: Option<String>,
+    /// Responses, unweighted.
+    pub count: usize,
+    /// Responses, weighted.
+    pub weighted: f64,
+    pub percent_of_responses: f64,
+    pub percent_of_cases: f64,
+}
+
+/// Tabulate the multiple response set `set` over `batch`.
+///
+/// For a dichotomy set, a member counts as a response when it holds the
+/// set's counted value; for a category set, each valid value of a member is
+/// a response, counted at most once per case. Missing values are never
+/// responses. Percentages of cases are of the cases with any response, so
+/// they can add up to more than 100. `set` is matched case-insensitively,
+/// with or without its leading `$`.
+///
+/// # Example
+/// ```no_run
+/// use ambers::stats::{self, Weight};
+///
+/// let (batch, meta) = ambers::read_sav("survey.sav").unwrap();
+/// let table = stats::mr_frequencies(&batch, &meta, "$brands", Weight::File).unwrap();
+/// println!("{table}");
+/// ```
+pub fn mr_frequencies(
+    batch: &RecordBatch,
+    meta: &SpssMetadata,
+    set: &str,
+    weight: Weight,
+) -> Result<MrFrequencyTable> {
+    let key = set.trim_start_matches('$');
+    let mr_set = meta
+        .mr_sets
+        .values()
+        .find(|s| s.name.trim_start_matches('$').eq_ignore_ascii_case(key))
+        .ok_or_else(|| SpssError::InvalidVariable(format!("{set}: no such MR set")))?;
+    let weights = case_weights(batch, meta, weight)?;
+
+    let mut members = Vec::new();
+    for var in &mr_set.variables {
+        let (values, variable) = column(batch, meta, var)?;
+        members.push((variable, cells(values, meta, variable)?));
+    }
+    let (categories, responses) = match mr_set.mr_type {
+        MrType::MultipleDichotomy => dichotomy_responses(mr_set, meta, &members),
+        MrType::MultipleCategory => category_responses(meta, &members),
+    };
+
+    // {category index -> (count, weighted)}
+    let mut counts = vec![(0, 0.0); categories.len()];
+    let (mut cases, mut missing_cases) = (0.0, 0.0);
+    for (row, w) in responses.into_iter().zip(weights) {
+        if w <= 0.0 {
+            continue;
+        }
+        if row.is_empty() {
+            missing_cases += w;
+            continue;
+        }
+        cases += w;
+        for i in row {
+            counts[i].0 += 1;
+            counts[i].1 += w;
+        }
+    }
+    let total: f64 = counts.iter().map(|(_, w)| w).sum();
+    let rows = categories
+        .into_iter()
+        .zip(counts)
+        .map(|((category, label), (count, weighted))| MrFrequencyRow {
+            category,
+            label,
+            count,
+            weighted,
+            percent_of_responses: percent(weighted, total),
+            percent_of_cases: percent(weighted, cases),
+        })
+        .collect();
+    Ok(MrFrequencyTable {
+        set: mr_set.name.clone(),
+        label: mr_set.label.clone(),
+        rows,
+        responses: total,
+        cases,
+        missing_cases,
+    })
+}
+
+/// Categories (name, label) and the categories each case responded to.
+type Responses = (Vec<(String, Option<String>)>, Vec<Vec<usize>>);
+
+fn dichotomy_responses(
+    mr_set: &MrSet,
+    meta: &SpssMetadata,
+    members: &[(&str, Vec<Cell>)],
+) -> Responses {
+    let counted = mr_set.counted_value.as_deref().unwrap_or("1").trim();
+    let number: Option<f64> = counted.parse().ok();
+    let is_counted = |value: &Value| match value {
+        Value::Numeric(v) => number == Some(*v),
+        Value::String(s) => s.trim_end() == counted,
+    };
+    let categories = members
+        .iter()
+        .map(|(variable, _)| {
+            (
+                variable.to_string(),
+                meta.label(variable).map(str::to_string),
+            )
+        })
+        .collect();
+    let rows = members.first().map_or(0, |(_, cells)| cells.len());
+    let responses = (0..rows)
+        .map(|row| {
+            members
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, cells))| {
+                    matches!(&cells[row], Cell::Valid(value) if is_counted(value))
+                })
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .collect();
+    (categories, responses)
+}
+
+fn category_responses(meta: &SpssMetadata, members: &[(&str, Vec<Cell>)]) -> Responses {
+    // {value -> label}
+    let mut values: IndexMap<Value, Option<String>> = IndexMap::new();
+    for (variable, _) in members {
+        let specs = meta
+            .variable_missing
+            .get(*variable)
+            .map_or(&[][..], Vec::as_slice);
+        for (value, label) in meta.value_labels(variable).into_iter().flatten() {
+            if is_user_missing(specs, value) {
+                continue;
+            }
+            values
+                .entry(value.clone())
+                .or_insert_with(|| Some(label.clone()));
+        }
+    }
+    for (_, cells) in members {
+        for cell in cells {
+            if let Cell::Valid(value) = cell
+                && !values.contains_key(value)
+            {
+                values.insert(value.clone(), None);
+            }
+        }
+    }
+    values.sort_by(|a, _, b, _| value_order(a, b));
+
+    let rows = members.first().map_or(0, |(_, cells)| cells.len());
+    let responses = (0..rows)
+        .map(|row| {
+            let mut responded: Vec<usize> = members
+                .iter()
+                .filter_map(|(_, cells)| match &cells[row] {
+                    Cell::Valid(value) => values.get_index_of(value),
+                    _ => None,
+                })
+                .collect();
+            responded.sort_unstable();
+            responded.dedup();
+            responded
+        })
+        .collect();
+    let categories = values
+        .into_iter()
+        .map(|(value, label)| (value.to_string(), label))
+        .collect();
+    (categories, responses)
+}
+
+impl fmt::Display for MrFrequencyTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "${}: {}", self.set.trim_start_matches('$'), self.label)?;
+        let name = |row: &MrFrequencyRow| match &row.label {
+            Some(label) => format!("{} {label}", row.category),
+            None => row.category.clone(),
+        };
+        let width = self
+            .rows
+            .iter()
+            .map(|row| name(row).chars().count())
+            .chain([8])
+            .max()
+            .unwrap_or(8);
+        writeln!(
+            f,
+            "  {:width$}  {:>10}  {:>11}  {:>9}",
+            "Category", "Responses", "% responses", "% cases"
+        )?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "  {:width$}  {:>10}  {:>11.1}  {:>9.1}",
+                name(row),
+                format_count(row.weighted),
+                row.percent_of_responses,
+                row.percent_of_cases
+            )?;
+        }
+        write!(
+            f,
+            "  {:width$}  {:>10}  ({} cases, {} without a response)",
+            "Total",
+            format_count(self.responses),
+            format_count(self.cases),
+            format_count(self.missing_cases)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Float64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn survey() -> (RecordBatch, SpssMetadata) {
+        let names = ["b1", "b2", "c1", "c2"];
+        let columns: [Vec<Option<f64>>; 4] = [
+            vec![Some(1.0), Some(0.0), Some(1.0), Some(0.0)],
+            vec![Some(1.0), Some(1.0), None, Some(0.0)],
+            vec![Some(3.0), Some(2.0), Some(3.0), None],
+            vec![Some(3.0), None, Some(5.0), None],
+        ];
+        let fields: Vec<Field> = names
+            .iter()
+            .map(|n| Field::new(*n, DataType::Float64, true))
+            .collect();
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns
+                .into_iter()
+                .map(|c| Arc::new(Float64Array::from(c)) as _)
+                .collect(),
+        )
+        .unwrap();
+        let mut meta = SpssMetadata {
+            variable_names: names.iter().map(|n| n.to_string()).collect(),
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels.insert("b1".into(), "Brand A".into());
+        meta.variable_value_labels.insert(
+            "c1".into(),
+            [
+                (Value::Numeric(2.0), "Two".to_string()),
+                (Value::Numeric(3.0), "Three".to_string()),
+                (Value::Numeric(4.0), "Four".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        for (name, mr_type, counted, vars) in [
+            ("brands", MrType::MultipleDichotomy, Some("1"), ["b1", "b2"]),
+            ("codes", MrType::MultipleCategory, None, ["c1", "c2"]),
+        ] {
+            meta.mr_sets.insert(
+                name.into(),
+                MrSet {
+                    name: name.into(),
+                    label: name.to_uppercase(),
+                    mr_type,
+                    counted_value: counted.map(str::to_string),
+                    variables: vars.iter().map(|v| v.to_string()).collect(),
+                },
+            );
+        }
+        (batch, meta)
+    }
+
+    #[test]
+    fn test_mr_dichotomy() {
+        let (batch, meta) = survey();
+        let table = mr_frequencies(&batch, &meta, "$Brands", Weight::Unweighted).unwrap();
+        let rows: Vec<(&str, usize)> = table
+            .rows
+            .iter()
+            .map(|r| (r.category.as_str(), r.count))
+            .collect();
+        assert_eq!(rows, [("b1", 2), ("b2", 2)]);
+        assert_eq!(table.rows[0].label.as_deref(), Some("Brand A"));
+        assert_eq!(
+            (table.responses, table.cases, table.missing_cases),
+            (4.0, 3.0, 1.0)
+        );
+        assert_eq!(table.rows[0].percent_of_responses, 50.0);
+        assert_eq!(table.rows[0].percent_of_cases, 200.0 / 3.0);
+    }
+
+    #[test]
+    fn test_mr_category() {
+        let (batch, meta) = survey();
+        let table = mr_frequencies(&batch, &meta, "codes", Weight::Unweighted).unwrap();
+        let rows: Vec<(&str, Option<&str>, usize)> = table
+            .rows
+            .iter()
+            .map(|r| (r.category.as_str(), r.label.as_deref(), r.count))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("2", Some("Two"), 1),
+                ("3", Some("Three"), 2),
+                ("4", Some("Four"), 0),
+                ("5", None, 1),
+            ]
+        );
+        assert_eq!((table.responses, table.cases), (4.0, 3.0));
+        assert!(mr_frequencies(&batch, &meta, "nope", Weight::Unweighted).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Per-column statistics accumulated batch by batch.
+
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::{can_cast_types, cast};
+use arrow::datatypes::{DataType, Float64Type};
+use indexmap::IndexMap;
+
+use crate::error::Result;
+use crate::metadata::Value;
+
+/// Registers per distinct-count sketch (2^10): about 3% error, 1 KiB per
+/// column.
+const SKETCH_BITS: u32 = 10;
+
+/// Distinct values are counted exactly up to this many.
+const EXACT_DISTINCT: usize = 1024;
+
+/// Column profiles of the batches seen so far, filled in while scanning
+/// with `ScanOptions::statistics` or fed directly with [`update`].
+///
+/// [`update`]: ScanStatistics::update
+#[derive(Debug, Clone, Default)]
+pub struct ScanStatistics {
+    /// Rows seen.
+    pub rows: usize,
+    /// {column name -> statistics}, in column order.
+    pub columns: IndexMap<String, ColumnStatistics>,
+}
+
+/// Profile of one column.
+#[derive(Debug, Clone)]
+pub struct ColumnStatistics {
+    pub null_count: usize,
+    /// Smallest value: numeric for numeric columns, else the value as
+    /// text (ISO 8601 for dates and times). `None` when all are null.
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// Hashes of the values seen, until there are too many.
+    exact: Option<HashSet<u64>>,
+    sketch: Box<[u8]>,
+}
+
+impl ColumnStatistics {
+    fn new() -> Self {
+        ColumnStatistics {
+            null_count: 0,
+            min: None,
+            max: None,
+            exact: Some(HashSet::new()),
+            sketch: vec![0; 1 << SKETCH_BITS].into_boxed_slice(),
+        }
+    }
+
+    /// Number of distinct non-null values: exact up to 1024, beyond that a
+    /// HyperLogLog estimate within a few percent.
+    pub fn distinct_estimate(&self) -> u64 {
+        if let Some(exact) = &self.exact {
+            return exact.len() as u64;
+        }
+        let m = self.sketch.len() as f64;
+        let sum: f64 = self.sketch.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.sketch.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting for small cardinalities.
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+
+    fn add_hash(&mut self, hash: u64) {
+        if let Some(exact) = &mut self.exact
+            && exact.insert(hash)
+            && exact.len() > EXACT_DISTINCT
+        {
+            self.exact = None;
+        }
+        let register = (hash >> (64 - SKETCH_BITS)) as usize;
+        let rank = ((hash << SKETCH_BITS) | (1 << (SKETCH_BITS - 1))).leading_zeros() + 1;
+        self.sketch[register] = self.sketch[register].max(rank as u8);
+    }
+
+    fn add_f64(&mut self, v: f64) {
+        if v.is_nan() {
+            return;
+        }
+        // +0.0 stands in for -0.0.
+        let v = v + 0.0;
+        let mut hasher = DefaultHasher::new();
+        Value::Numeric(v).hash(&mut hasher);
+        self.add_hash(hasher.finish());
+        if !matches!(self.min, Some(Value::Numeric(min)) if min <= v) {
+            self.min = Some(Value::Numeric(v));
+        }
+        if !matches!(self.max, Some(Value::Numeric(max)) if max >= v) {
+            self.max = Some(Value::Numeric(v));
+        }
+    }
+
+    fn add_str(&mut self, v: &str) {
+        let mut hasher = DefaultHasher::new();
+        // Hash as `Value::String` does, without allocating.
+        1_u8.hash(&mut hasher);
+        v.hash(&mut hasher);
+        self.add_hash(hasher.finish());
+        if !matches!(&self.min, Some(Value::String(min)) if min.as_str() <= v) {
+            self.min = Some(Value::String(v.to_string()));
+        }
+        if !matches!(&self.max, Some(Value::String(max)) if max.as_str() >= v) {
+            self.max = Some(Value::String(v.to_string()));
+        }
+    }
+}
+
+impl ScanStatistics {
+    /// Add the rows of `batch`. Columns are matched by name; a column first
+    /// seen in a later batch is profiled from there on.
+    pub fn update(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.rows += batch.num_rows();
+        for (field, column) in batch.schema_ref().fields().iter().zip(batch.columns()) {
+            let stats = self
+                .columns
+                .entry(field.name().clone())
+                .or_insert_with(ColumnStatistics::new);
+            stats.null_count += column.logical_null_count();
+            let data_type = column.data_type();
+            if data_type.is_numeric() && can_cast_types(data_type, &DataType::Float64) {
+                let values = cast(column, &DataType::Float64)?;
+                for v in values.as_primitive::<Float64Type>().iter().flatten() {
+                    stats.add_f64(v);
+                }
+            } else if can_cast_types(data_type, &DataType::Utf8View) {
+                let values = cast(column, &DataType::Utf8View)?;
+                for v in values.as_string_view().iter().flatten() {
+                    stats.add_str(v);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_scan_statistics() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let mut stats = ScanStatistics::default();
+        for chunk in 0..4 {
+            let xs: Vec<Option<f64>> = (0..1000)
+                .map(|i| (i % 10 != 0).then_some((chunk * 1000 + i) as f64))
+                .collect();
+            let ss: Vec<String> = (0..1000).map(|i| format!("v{}", i % 7)).collect();
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Float64Array::from(xs)),
+                    Arc::new(StringArray::from(ss)),
+                ],
+            )
+            .unwrap();
+            stats.update(&batch).unwrap();
+        }
+
+        assert_eq!(stats.rows, 4000);
+        let x = &stats.columns["x"];
+        assert_eq!(x.null_count, 400);
+        assert_eq!(x.min, Some(Value::Numeric(1.0)));
+        assert_eq!(x.max, Some(Value::Numeric(3999.0)));
+        let distinct = x.distinct_estimate() as f64;
+        assert!((distinct - 3600.0).abs() / 3600.0 < 0.1, "{distinct}");
+
+        let s = &stats.columns["s"];
+        assert_eq!(s.null_count, 0);
+        assert_eq!(s.min, Some(Value::String("v0".into())));
+        assert_eq!(s.max, Some(Value::String("v6".into())));
+        assert_eq!(s.distinct_estimate(), 7);
+    }
+}
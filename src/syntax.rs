@@ -0,0 +1,709 @@
+//! Parser for the dictionary commands of SPSS syntax (.sps) files.
+//!
+//! Vendors often deliver a bare .sav alongside a syntax file that labels it.
+//! This reads the VARIABLE LABELS, VALUE LABELS, ADD VALUE LABELS, MISSING
+//! VALUES and VARIABLE LEVEL commands of such a file into a
+//! `MetadataOverlay`; every other command is skipped.
+
+use std::path::Path;
+
+use indexmap::IndexMap;
+
+use crate::constants::{LOWEST_BITS, Measure};
+use crate::encoding;
+use crate::error::{Result, SpssError};
+use crate::metadata::{MetadataOverlay, MissingSpec, SpssMetadata, Value};
+
+/// Read a syntax file and parse it against the variables of `metadata`.
+///
+/// The file is decoded as UTF-8 if valid, otherwise in the encoding of
+/// `metadata`.
+pub fn read_sps(path: impl AsRef<Path>, metadata: &SpssMetadata) -> Result<MetadataOverlay> {
+    let bytes = std::fs::read(path)?;
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding::decode_str_lossy(
+            &bytes,
+            encoding::encoding_from_name(&metadata.file_encoding),
+        )
+        .into_owned(),
+    };
+    parse_sps(&text, metadata)
+}
+
+/// Parse syntax text against the variables of `metadata`.
+///
+/// Variable names are matched case-insensitively and `TO` ranges follow the
+/// variable order of `metadata`. Referring to an unknown variable is an error.
+pub fn parse_sps(text: &str, metadata: &SpssMetadata) -> Result<MetadataOverlay> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut overlay = MetadataOverlay::default();
+    for command in Lexer::new(text).commands()? {
+        Parser {
+            tokens: &command,
+            pos: 0,
+            metadata,
+        }
+        .command(&mut overlay)?;
+    }
+    Ok(overlay)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word(String),
+    Str(String),
+    Num(f64),
+    Slash,
+    LParen,
+    RParen,
+    Comma,
+    Equals,
+    Other(char),
+}
+
+/// Splits syntax text into commands of (line, token) pairs.
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: text.char_indices().peekable(),
+            text,
+            line: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> SpssError {
+        SpssError::Syntax {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// The text after the next character.
+    fn after_next(&mut self) -> &'a str {
+        match self.chars.peek() {
+            Some(&(i, c)) => &self.text[i + c.len_utf8()..],
+            None => "",
+        }
+    }
+
+    /// True if a '.' at the current position ends the command: it is the
+    /// last non-blank character of its line.
+    fn at_terminator(&mut self) -> bool {
+        let rest = self.after_next();
+        let line_end = rest.find('\n').unwrap_or(rest.len());
+        rest[..line_end].trim().is_empty()
+    }
+
+    fn commands(mut self) -> Result<Vec<Vec<(usize, Tok)>>> {
+        let mut commands = Vec::new();
+        let mut current: Vec<(usize, Tok)> = Vec::new();
+        let mut line_blank = true;
+        while let Some(c) = self.peek() {
+            match c {
+                '\n' => {
+                    self.bump();
+                    // A blank line also ends a command
+                    if line_blank && !current.is_empty() {
+                        commands.push(std::mem::take(&mut current));
+                    }
+                    line_blank = true;
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    self.bump();
+                    continue;
+                }
+                _ => {}
+            }
+            line_blank = false;
+
+            if c == '/' && self.after_next().starts_with('*') {
+                self.skip_block_comment();
+                continue;
+            }
+            if c == '.' && self.at_terminator() {
+                self.bump();
+                if !current.is_empty() {
+                    commands.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            if current.is_empty() && (c == '*' || self.at_comment_keyword()) {
+                self.skip_comment_command();
+                continue;
+            }
+
+            let line = self.line;
+            let tok = self.token(c)?;
+            current.push((line, tok));
+        }
+        if !current.is_empty() {
+            commands.push(current);
+        }
+        Ok(commands)
+    }
+
+    fn at_comment_keyword(&mut self) -> bool {
+        let start = match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => return false,
+        };
+        let word: String = self.text[start..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric())
+            .collect();
+        word.len() >= 4 && "COMMENT".starts_with(&word.to_ascii_uppercase())
+    }
+
+    /// Skip `/* ... */`, which may also run to the end of the line unclosed.
+    fn skip_block_comment(&mut self) {
+        self.bump();
+        self.bump();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                return;
+            }
+            self.bump();
+            if c == '*' && self.peek() == Some('/') {
+                self.bump();
+                return;
+            }
+        }
+    }
+
+    /// Skip a `*` or COMMENT command, up to its terminating period.
+    fn skip_comment_command(&mut self) {
+        let mut line_blank = false;
+        while let Some(c) = self.peek() {
+            if c == '.' && self.at_terminator() {
+                self.bump();
+                return;
+            }
+            if c == '\n' {
+                if line_blank {
+                    return;
+                }
+                line_blank = true;
+            } else if !c.is_whitespace() {
+                line_blank = false;
+            }
+            self.bump();
+        }
+    }
+
+    fn token(&mut self, c: char) -> Result<Tok> {
+        match c {
+            '\'' | '"' => self.string(),
+            '/' => self.single(Tok::Slash),
+            '(' => self.single(Tok::LParen),
+            ')' => self.single(Tok::RParen),
+            ',' => self.single(Tok::Comma),
+            '=' => self.single(Tok::Equals),
+            '-' | '+' | '.' if self.after_next().starts_with(|c: char| c.is_ascii_digit()) => {
+                self.number()
+            }
+            c if c.is_ascii_digit() => self.number(),
+            c if c.is_alphabetic() || matches!(c, '@' | '#' | '$' | '_') => Ok(self.word()),
+            c => self.single(Tok::Other(c)),
+        }
+    }
+
+    fn single(&mut self, tok: Tok) -> Result<Tok> {
+        self.bump();
+        Ok(tok)
+    }
+
+    fn word(&mut self) -> Tok {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            let continues = c.is_alphanumeric()
+                || matches!(c, '@' | '#' | '$' | '_')
+                || (c == '.' && !self.at_terminator());
+            if !continues {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        Tok::Word(word)
+    }
+
+    fn number(&mut self) -> Result<Tok> {
+        let mut text = String::new();
+        if let Some(sign @ ('-' | '+')) = self.peek() {
+            text.push(sign);
+            self.bump();
+        }
+        while let Some(c) = self.peek() {
+            let part = c.is_ascii_digit()
+                || (c == '.' && !text.contains(['.', 'e', 'E']) && !self.at_terminator())
+                || (matches!(c, 'e' | 'E') && !text.contains(['e', 'E']))
+                || (matches!(c, '-' | '+') && text.ends_with(['e', 'E']));
+            if !part {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        text.parse()
+            .map(Tok::Num)
+            .map_err(|_| self.error(format!("invalid number {text:?}")))
+    }
+
+    /// A quoted string, joining any `+`-concatenated continuations.
+    fn string(&mut self) -> Result<Tok> {
+        let mut value = String::new();
+        loop {
+            let quote = self.bump().unwrap_or('\'');
+            loop {
+                match self.bump() {
+                    Some(c) if c == quote => {
+                        if self.peek() == Some(quote) {
+                            self.bump();
+                            value.push(quote);
+                        } else {
+                            break;
+                        }
+                    }
+                    Some('\n') | None => return Err(self.error("unterminated string")),
+                    Some(c) => value.push(c),
+                }
+            }
+
+            // 'part one' + 'part two'
+            let rest = match self.chars.peek() {
+                Some(&(i, _)) => &self.text[i..],
+                None => break,
+            };
+            let continued = rest
+                .trim_start()
+                .strip_prefix('+')
+                .is_some_and(|r| r.trim_start().starts_with(['\'', '"']));
+            if !continued {
+                break;
+            }
+            while self.peek().is_some_and(|c| c.is_whitespace() || c == '+') {
+                self.bump();
+            }
+        }
+        Ok(Tok::Str(value))
+    }
+}
+
+/// Case-insensitive match of `word` against a keyword, allowing SPSS's
+/// abbreviations to three or more characters.
+fn keyword(word: &str, kw: &str) -> bool {
+    let word = word.to_ascii_uppercase();
+    word == kw || (word.len() >= 3 && kw.starts_with(&word))
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Tok)],
+    pos: usize,
+    metadata: &'a SpssMetadata,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Option<&Tok> {
+        let tok = self.tokens.get(self.pos).map(|(_, t)| t);
+        self.pos += 1;
+        tok
+    }
+
+    fn error(&self, message: impl Into<String>) -> SpssError {
+        let line = self
+            .tokens
+            .get(self.pos.min(self.tokens.len().saturating_sub(1)))
+            .map_or(0, |(line, _)| *line);
+        SpssError::Syntax {
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn at_word(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Word(w)) if keyword(w, kw))
+    }
+
+    fn eat(&mut self, tok: &Tok) -> bool {
+        if self.peek() == Some(tok) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, tok: Tok, what: &str) -> Result<()> {
+        if self.eat(&tok) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {what}")))
+        }
+    }
+
+    fn command(&mut self, overlay: &mut MetadataOverlay) -> Result<()> {
+        let words: Vec<&str> = self
+            .tokens
+            .iter()
+            .take(3)
+            .map_while(|(_, t)| match t {
+                Tok::Word(w) => Some(w.as_str()),
+                _ => None,
+            })
+            .collect();
+        match words.as_slice() {
+            [a, b, ..] if keyword(a, "VARIABLE") && keyword(b, "LABELS") => {
+                self.pos = 2;
+                self.variable_labels(overlay)
+            }
+            [a, b, ..] if keyword(a, "VARIABLE") && keyword(b, "LEVEL") => {
+                self.pos = 2;
+                self.variable_level(overlay)
+            }
+            [a, b, ..] if keyword(a, "VALUE") && keyword(b, "LABELS") => {
+                self.pos = 2;
+                self.value_labels(&mut overlay.variable_value_labels)
+            }
+            [a, b, c] if keyword(a, "ADD") && keyword(b, "VALUE") && keyword(c, "LABELS") => {
+                self.pos = 3;
+                self.value_labels(&mut overlay.added_value_labels)
+            }
+            [a, b, ..] if keyword(a, "MISSING") && keyword(b, "VALUES") => {
+                self.pos = 2;
+                self.missing_values(overlay)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolve a variable name case-insensitively to its name in the metadata.
+    fn resolve(&self, name: &str) -> Result<usize> {
+        let names = &self.metadata.variable_names;
+        names
+            .iter()
+            .position(|n| n == name)
+            .or_else(|| names.iter().position(|n| n.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| self.error(format!("unknown variable {name:?}")))
+    }
+
+    /// A variable list: names, `a TO b` ranges, or ALL.
+    fn varlist(&mut self) -> Result<Vec<String>> {
+        let names = &self.metadata.variable_names;
+        let mut vars = Vec::new();
+        while let Some(Tok::Word(word)) = self.peek() {
+            let word = word.clone();
+            self.pos += 1;
+            if word.eq_ignore_ascii_case("ALL") && self.resolve(&word).is_err() {
+                vars.extend(names.iter().cloned());
+                continue;
+            }
+            let start = self.resolve(&word)?;
+            if !self.at_word("TO") {
+                vars.push(names[start].clone());
+                continue;
+            }
+            self.pos += 1;
+            let end = match self.next() {
+                Some(Tok::Word(end)) => end.clone(),
+                _ => return Err(self.error("expected a variable name after TO")),
+            };
+            let end = self.resolve(&end)?;
+            if end < start {
+                return Err(self.error(format!(
+                    "{} precedes {} in the file",
+                    names[end], names[start]
+                )));
+            }
+            vars.extend(names[start..=end].iter().cloned());
+        }
+        if vars.is_empty() {
+            return Err(self.error("expected a variable list"));
+        }
+        Ok(vars)
+    }
+
+    fn is_string_var(&self, name: &str) -> bool {
+        self.metadata
+            .format(name)
+            .is_some_and(|f| f.starts_with(['A', 'a']))
+    }
+
+    /// A value for `var`: numeric for numeric variables, text for strings.
+    fn value(&mut self, var: &str) -> Result<Value> {
+        let string_var = self.is_string_var(var);
+        match self.next().cloned() {
+            Some(Tok::Str(s)) if string_var => Ok(Value::String(s)),
+            Some(Tok::Num(n)) if string_var => Ok(Value::String(Value::Numeric(n).to_string())),
+            Some(Tok::Num(n)) => Ok(Value::Numeric(n)),
+            Some(Tok::Str(s)) => s
+                .trim()
+                .parse()
+                .map(Value::Numeric)
+                .map_err(|_| self.error(format!("{var} is numeric but the value {s:?} is not"))),
+            _ => {
+                self.pos -= 1;
+                Err(self.error(format!("expected a value for {var}")))
+            }
+        }
+    }
+
+    fn variable_labels(&mut self, overlay: &mut MetadataOverlay) -> Result<()> {
+        while self.peek().is_some() {
+            if self.eat(&Tok::Slash) {
+                continue;
+            }
+            let vars = self.varlist()?;
+            let label = match self.next() {
+                Some(Tok::Str(label)) => label.clone(),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected a quoted label"));
+                }
+            };
+            for var in vars {
+                overlay.variable_labels.insert(var, label.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn value_labels(
+        &mut self,
+        target: &mut IndexMap<String, IndexMap<Value, String>>,
+    ) -> Result<()> {
+        while self.peek().is_some() {
+            if self.eat(&Tok::Slash) {
+                continue;
+            }
+            let vars = self.varlist()?;
+            let mut labels = IndexMap::new();
+            while matches!(self.peek(), Some(Tok::Num(_) | Tok::Str(_))) {
+                let value = self.value(&vars[0])?;
+                let label = match self.next() {
+                    Some(Tok::Str(label)) => label.clone(),
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error(format!("expected a label for value {value}")));
+                    }
+                };
+                labels.insert(value, label);
+            }
+            let string_vars = self.is_string_var(&vars[0]);
+            for var in vars {
+                if self.is_string_var(&var) != string_vars {
+                    return Err(self.error(format!(
+                        "{var}: cannot share value labels between string and numeric variables"
+                    )));
+                }
+                target.insert(var, labels.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn missing_values(&mut self, overlay: &mut MetadataOverlay) -> Result<()> {
+        while self.peek().is_some() {
+            if self.eat(&Tok::Slash) {
+                continue;
+            }
+            let vars = self.varlist()?;
+            self.expect(Tok::LParen, "'('")?;
+            let mut specs = Vec::new();
+            while !self.eat(&Tok::RParen) {
+                if self.eat(&Tok::Comma) {
+                    continue;
+                }
+                specs.push(self.missing_spec(&vars[0])?);
+            }
+            for var in vars {
+                overlay.variable_missing.insert(var, specs.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn missing_spec(&mut self, var: &str) -> Result<MissingSpec> {
+        if self.is_string_var(var) {
+            return match self.value(var)? {
+                Value::String(s) => Ok(MissingSpec::StringValue(s)),
+                Value::Numeric(n) => Ok(MissingSpec::Value(n)),
+            };
+        }
+        let lo = self.missing_bound(var)?;
+        if !self.at_word("THRU") {
+            return lo
+                .map(MissingSpec::Value)
+                .ok_or_else(|| self.error("LO/HI must be part of a THRU range"));
+        }
+        self.pos += 1;
+        let hi = self.missing_bound(var)?;
+        Ok(MissingSpec::Range {
+            lo: lo.unwrap_or(f64::from_bits(LOWEST_BITS)),
+            hi: hi.unwrap_or(f64::MAX),
+        })
+    }
+
+    /// One end of a missing value range: a number, or None for LO/HI.
+    fn missing_bound(&mut self, var: &str) -> Result<Option<f64>> {
+        if self.at_word("LOWEST")
+            || self.at_word("LO")
+            || self.at_word("HIGHEST")
+            || self.at_word("HI")
+        {
+            self.pos += 1;
+            return Ok(None);
+        }
+        match self.value(var)? {
+            Value::Numeric(n) => Ok(Some(n)),
+            Value::String(_) => unreachable!("numeric variable"),
+        }
+    }
+
+    fn variable_level(&mut self, overlay: &mut MetadataOverlay) -> Result<()> {
+        while self.peek().is_some() {
+            if self.eat(&Tok::Slash) {
+                continue;
+            }
+            let vars = self.varlist()?;
+            self.expect(Tok::LParen, "'('")?;
+            let measure = match self.next() {
+                Some(Tok::Word(w)) if keyword(w, "NOMINAL") => Measure::Nominal,
+                Some(Tok::Word(w)) if keyword(w, "ORDINAL") => Measure::Ordinal,
+                Some(Tok::Word(w)) if keyword(w, "SCALE") => Measure::Scale,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected NOMINAL, ORDINAL or SCALE"));
+                }
+            };
+            self.expect(Tok::RParen, "')'")?;
+            for var in vars {
+                overlay.variable_measure.insert(var, measure);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> SpssMetadata {
+        let mut meta = SpssMetadata::default();
+        for (name, format) in [
+            ("id", "F8.0"),
+            ("Q1", "F2.0"),
+            ("q2", "F2.0"),
+            ("q3", "F2.0"),
+            ("city", "A20"),
+        ] {
+            meta.variable_names.push(name.to_string());
+            meta.spss_variable_types
+                .insert(name.to_string(), format.to_string());
+        }
+        meta
+    }
+
+    #[test]
+    fn test_parse_dictionary_commands() {
+        let sps = "\
+* Labels for the survey delivery.
+GET FILE='survey.sav'.
+VARIABLE LABELS id 'Respondent ID'
+  /q1 \"Don't know\" + ' or refused'
+  city 'City of residence'.
+VALUE LABELS q1 TO q3 1 'Yes' 2 'No' /* inline comment */
+  /city 'NYC' 'New York'.
+ADD VAL LAB q2 9 'Refused'.
+MISSING VALUES q1 q2 (8, 9) /q3 (LO THRU 0, 99) /city ('NA').
+VARIABLE LEVEL id (SCALE) /q1 TO q3 (ORDINAL).
+FREQUENCIES q1.
+";
+        let overlay = parse_sps(sps, &metadata()).unwrap();
+
+        assert_eq!(overlay.variable_labels["Q1"], "Don't know or refused");
+        assert_eq!(overlay.variable_labels["city"], "City of residence");
+        assert_eq!(overlay.variable_value_labels.len(), 4);
+        assert_eq!(
+            overlay.variable_value_labels["q3"][&Value::Numeric(2.0)],
+            "No"
+        );
+        assert_eq!(
+            overlay.variable_value_labels["city"][&Value::String("NYC".to_string())],
+            "New York"
+        );
+        assert_eq!(
+            overlay.added_value_labels["q2"][&Value::Numeric(9.0)],
+            "Refused"
+        );
+        assert!(matches!(
+            overlay.variable_missing["q2"].as_slice(),
+            [MissingSpec::Value(8.0), MissingSpec::Value(9.0)]
+        ));
+        assert!(matches!(
+            overlay.variable_missing["q3"].as_slice(),
+            [MissingSpec::Range { lo, hi: 0.0 }, MissingSpec::Value(99.0)] if lo.to_bits() == LOWEST_BITS
+        ));
+        assert!(matches!(
+            overlay.variable_missing["city"].as_slice(),
+            [MissingSpec::StringValue(s)] if s == "NA"
+        ));
+        assert_eq!(overlay.variable_measure["id"], Measure::Scale);
+        assert_eq!(overlay.variable_measure["q2"], Measure::Ordinal);
+    }
+
+    #[test]
+    fn test_apply_overlay() {
+        let mut meta = metadata();
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(1.0), "Agree".to_string());
+        labels.insert(Value::Numeric(5.0), "Disagree".to_string());
+        meta.variable_value_labels
+            .insert("q2".to_string(), labels.clone());
+        meta.variable_value_labels.insert("q3".to_string(), labels);
+        meta.variable_missing
+            .insert("id".to_string(), vec![MissingSpec::Value(-1.0)]);
+
+        let sps =
+            "VALUE LABELS q3 1 'Yes'.\nADD VALUE LABELS q2 9 'Refused'.\nMISSING VALUES id ().";
+        meta.apply_overlay(&parse_sps(sps, &meta.clone()).unwrap());
+
+        assert_eq!(meta.value_labels("q2").unwrap().len(), 3);
+        assert_eq!(meta.value_labels("q3").unwrap().len(), 1);
+        assert!(!meta.variable_missing.contains_key("id"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let meta = metadata();
+        let err = parse_sps("VARIABLE LABELS\n  nope 'Label'.", &meta).unwrap_err();
+        assert!(matches!(err, SpssError::Syntax { line: 2, .. }));
+        assert!(parse_sps("VALUE LABELS q3 TO q1 1 'Yes'.", &meta).is_err());
+        assert!(parse_sps("VALUE LABELS q1 'x' 'Yes'.", &meta).is_err());
+        assert!(parse_sps("VARIABLE LABELS q1 'unterminated.", &meta).is_err());
+    }
+}
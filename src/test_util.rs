@@ -0,0 +1,61 @@
+//! Fixtures shared by the unit tests.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, StringArray};
+use arrow::record_batch::RecordBatch;
+
+use crate::constants::Compression;
+use crate::metadata::{SpssMetadata, Value};
+use crate::writer::WriteOptions;
+
+/// An in-memory .sav file holding `columns` (all nullable), written with
+/// `compression` and default metadata.
+pub(crate) fn sav_bytes(columns: Vec<(&str, ArrayRef)>, compression: Compression) -> Vec<u8> {
+    let options = WriteOptions {
+        compression,
+        ..WriteOptions::default()
+    };
+    sav_bytes_with(columns, &SpssMetadata::default(), &options)
+}
+
+/// Like `sav_bytes`, with the metadata and write options given.
+pub(crate) fn sav_bytes_with(
+    columns: Vec<(&str, ArrayRef)>,
+    meta: &SpssMetadata,
+    options: &WriteOptions,
+) -> Vec<u8> {
+    let columns = columns.into_iter().map(|(name, array)| (name, array, true));
+    let batch = RecordBatch::try_from_iter_with_nullable(columns).unwrap();
+    let mut buf = Vec::new();
+    crate::write_sav_to_writer_with(&mut buf, &batch, meta, options).unwrap();
+    buf
+}
+
+/// A small labelled survey: a numeric `q1` holding the values given (with
+/// a label and a value label) and a string `city` ("city number <row>"),
+/// compressed in zlib blocks of `zlib_block_size` bytes where that applies.
+pub(crate) fn survey_sav(
+    q1: impl IntoIterator<Item = f64>,
+    compression: Compression,
+    zlib_block_size: usize,
+) -> Vec<u8> {
+    let q1 = Float64Array::from_iter_values(q1);
+    let city = StringArray::from_iter_values((0..q1.len()).map(|i| format!("city number {i}")));
+    let mut meta = SpssMetadata::default();
+    meta.variable_labels
+        .insert("q1".into(), "Owns a car".into());
+    meta.variable_value_labels.insert(
+        "q1".into(),
+        [(Value::Numeric(1.0), "Yes".to_string())]
+            .into_iter()
+            .collect(),
+    );
+    let options = WriteOptions {
+        compression,
+        zlib_block_size,
+        ..WriteOptions::default()
+    };
+    let columns: Vec<(&str, ArrayRef)> = vec![("q1", Arc::new(q1)), ("city", Arc::new(city))];
+    sav_bytes_with(columns, &meta, &options)
+}
@@ -0,0 +1,279 @@
+//! Metadata lint checks: dictionary problems worth a look before a file is
+//! delivered, found by `SpssMetadata::validate`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::constants::{Measure, SpssFormat};
+use crate::metadata::{MissingSpec, SpssMetadata, Value};
+
+/// The kind of problem a [`Finding`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// A variable has no variable label.
+    MissingLabel,
+    /// Value labels name a variable that is not in the file, or have the
+    /// wrong type for their variable (string codes on a numeric variable or
+    /// the reverse).
+    OrphanValueLabels,
+    /// Two codes of one variable share the same label.
+    DuplicateLabel,
+    /// A string variable has the scale measurement level.
+    StringScale,
+    /// Missing values contradict each other: a range whose low end exceeds
+    /// its high end, or a discrete value already inside the range.
+    MissingOverlap,
+    /// A multiple response set names a variable that is not in the file.
+    UnknownMrVariable,
+}
+
+impl Check {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Check::MissingLabel => "missing_label",
+            Check::OrphanValueLabels => "orphan_value_labels",
+            Check::DuplicateLabel => "duplicate_label",
+            Check::StringScale => "string_scale",
+            Check::MissingOverlap => "missing_overlap",
+            Check::UnknownMrVariable => "unknown_mr_variable",
+        }
+    }
+}
+
+/// One problem found by `SpssMetadata::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub check: Check,
+    /// The variable concerned (the set name for `UnknownMrVariable`).
+    pub variable: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.check.as_str(),
+            self.variable,
+            self.message
+        )
+    }
+}
+
+impl SpssMetadata {
+    /// Check the dictionary for common problems (see [`Check`]).
+    ///
+    /// Findings are grouped by check, in the order the checks are listed,
+    /// and by variable order within a check. An empty result means the
+    /// metadata passed every check.
+    pub fn validate(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut push = |check, variable: &str, message: String| {
+            findings.push(Finding {
+                check,
+                variable: variable.to_string(),
+                message,
+            })
+        };
+        let is_string = |name: &str| {
+            self.format(name)
+                .and_then(SpssFormat::parse)
+                .is_some_and(|f| f.format_type.is_string())
+        };
+
+        for name in &self.variable_names {
+            if self.label(name).is_none_or(|l| l.trim().is_empty()) {
+                push(Check::MissingLabel, name, "no variable label".to_string());
+            }
+        }
+
+        for (name, labels) in &self.variable_value_labels {
+            if !self.variable_names.contains(name) {
+                push(
+                    Check::OrphanValueLabels,
+                    name,
+                    format!(
+                        "{} value labels for a variable not in the file",
+                        labels.len()
+                    ),
+                );
+                continue;
+            }
+            let string = is_string(name);
+            let mismatched = labels
+                .keys()
+                .filter(|v| matches!(v, Value::String(_)) != string)
+                .count();
+            if mismatched > 0 {
+                let (kind, var_kind) = if string {
+                    ("numeric", "string")
+                } else {
+                    ("string", "numeric")
+                };
+                push(
+                    Check::OrphanValueLabels,
+                    name,
+                    format!("{mismatched} {kind} value labels on a {var_kind} variable"),
+                );
+            }
+        }
+
+        for name in &self.variable_names {
+            let Some(labels) = self.value_labels(name) else {
+                continue;
+            };
+            let mut seen: HashMap<&str, &Value> = HashMap::new();
+            for (value, label) in labels {
+                if let Some(first) = seen.get(label.trim()) {
+                    push(
+                        Check::DuplicateLabel,
+                        name,
+                        format!("label {label:?} is used for both {first} and {value}"),
+                    );
+                } else {
+                    seen.insert(label.trim(), value);
+                }
+            }
+        }
+
+        for name in &self.variable_names {
+            if self.measure(name) == Some(Measure::Scale) && is_string(name) {
+                push(
+                    Check::StringScale,
+                    name,
+                    "string variable with scale measure".to_string(),
+                );
+            }
+        }
+
+        for name in &self.variable_names {
+            let Some(specs) = self.variable_missing.get(name) else {
+                continue;
+            };
+            for spec in specs {
+                let MissingSpec::Range { lo, hi } = spec else {
+                    continue;
+                };
+                if lo > hi {
+                    push(
+                        Check::MissingOverlap,
+                        name,
+                        format!("missing range {lo} thru {hi} is empty"),
+                    );
+                }
+                for other in specs {
+                    if let MissingSpec::Value(v) = other
+                        && (lo..=hi).contains(&v)
+                    {
+                        push(
+                            Check::MissingOverlap,
+                            name,
+                            format!("missing value {v} is inside the range {lo} thru {hi}"),
+                        );
+                    }
+                }
+            }
+        }
+
+        for set in self.mr_sets.values() {
+            for var in &set.variables {
+                if !self.variable_names.contains(var) {
+                    push(
+                        Check::UnknownMrVariable,
+                        &set.name,
+                        format!("member {var} is not in the file"),
+                    );
+                }
+            }
+        }
+
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{MrSet, MrType};
+
+    #[test]
+    fn test_validate_clean() {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        assert_eq!(meta.validate(), []);
+    }
+
+    #[test]
+    fn test_validate_findings() {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["q1".into(), "city".into()],
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("q1".into(), "Owns a car".into());
+        meta.variable_labels.insert("city".into(), "City".into());
+        meta.spss_variable_types.insert("q1".into(), "F1.0".into());
+        meta.spss_variable_types.insert("city".into(), "A20".into());
+        meta.variable_measure.insert("city".into(), Measure::Scale);
+        meta.variable_value_labels.insert(
+            "q1".into(),
+            [
+                (Value::Numeric(1.0), "Yes".to_string()),
+                (Value::Numeric(2.0), "Yes".to_string()),
+                (Value::String("x".into()), "X".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        meta.variable_value_labels.insert(
+            "gone".into(),
+            [(Value::Numeric(1.0), "A".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        meta.variable_missing.insert(
+            "q1".into(),
+            vec![
+                MissingSpec::Range { lo: 7.0, hi: 9.0 },
+                MissingSpec::Value(8.0),
+            ],
+        );
+        meta.mr_sets.insert(
+            "$cars".into(),
+            MrSet {
+                name: "$cars".into(),
+                label: "Cars".into(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".into()),
+                variables: vec!["q1".into(), "q9".into()],
+            },
+        );
+
+        let findings = meta.validate();
+        let checks: Vec<(Check, &str)> = findings
+            .iter()
+            .map(|f| (f.check, f.variable.as_str()))
+            .collect();
+        assert_eq!(
+            checks,
+            [
+                (Check::OrphanValueLabels, "q1"),
+                (Check::OrphanValueLabels, "gone"),
+                (Check::DuplicateLabel, "q1"),
+                (Check::StringScale, "city"),
+                (Check::MissingOverlap, "q1"),
+                (Check::UnknownMrVariable, "$cars"),
+            ]
+        );
+        assert_eq!(
+            findings[2].to_string(),
+            "duplicate_label: q1: label \"Yes\" is used for both 1 and 2"
+        );
+    }
+}
@@ -25,8 +25,10 @@ pub struct ValueLabelSet {
 /// Returns the value-label pairs. The caller should immediately read the
 /// following type 4 record to get the variable indices.
 pub fn parse_value_labels<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<(RawValue, Vec<u8>)>> {
-    let count = reader.read_i32()? as usize;
-    let mut labels = Vec::with_capacity(count);
+    // Each label takes at least 16 bytes: the value, the length byte and
+    // padding.
+    let count = reader.read_count(16)?;
+    let mut labels = Vec::with_capacity(count.min(io_utils::MAX_PREALLOC));
 
     for _ in 0..count {
         // Value: 8 bytes (could be numeric f64 or string bytes)
@@ -46,7 +48,11 @@ pub fn parse_value_labels<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<(Raw
 
         // We store as numeric by default; the dictionary resolution step will
         // determine if this should be string based on the linked variable types.
-        let value = RawValue::Numeric(f64::from_le_bytes(value_bytes));
+        let value = RawValue::Numeric(if reader.bswap() {
+            f64::from_be_bytes(value_bytes)
+        } else {
+            f64::from_le_bytes(value_bytes)
+        });
 
         labels.push((value, label_bytes));
     }
@@ -58,7 +64,7 @@ pub fn parse_value_labels<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<(Raw
 ///
 /// Returns 0-based variable slot indices.
 pub fn parse_value_label_variables<R: Read>(reader: &mut SavReader<R>) -> Result<Vec<usize>> {
-    let count = reader.read_i32()? as usize;
+    let count = reader.read_count(4)?;
 
     if count == 0 {
         return Err(SpssError::InvalidValueLabel(
@@ -66,7 +72,7 @@ pub fn parse_value_label_variables<R: Read>(reader: &mut SavReader<R>) -> Result
         ));
     }
 
-    let mut indices = Vec::with_capacity(count);
+    let mut indices = Vec::with_capacity(count.min(io_utils::MAX_PREALLOC));
     for _ in 0..count {
         let index = reader.read_i32()?;
         // Convert from 1-based to 0-based
@@ -1,7 +1,7 @@
 use std::io::Read;
 
-use crate::constants::{Alignment, Measure, SpssFormat, VarType};
-use crate::error::Result;
+use crate::constants::{Alignment, Measure, RECORD_TYPE_VARIABLE, SpssFormat, VarType};
+use crate::error::{Result, SpssError};
 use crate::io_utils::{self, SavReader};
 
 /// Missing value specification for a variable.
@@ -22,6 +22,32 @@ pub enum MissingValues {
     DiscreteString(Vec<Vec<u8>>),
 }
 
+impl MissingValues {
+    /// The `n_missing_values` field of a type 2 record: the value count,
+    /// negated for ranges (-2 = range, -3 = range plus one discrete value).
+    pub fn count_code(&self) -> i32 {
+        match self {
+            MissingValues::None => 0,
+            MissingValues::DiscreteNumeric(vals) => vals.len() as i32,
+            MissingValues::Range { .. } => -2,
+            MissingValues::RangeAndValue { .. } => -3,
+            MissingValues::DiscreteString(vals) => vals.len() as i32,
+        }
+    }
+
+    /// Apply `f` to each numeric value.
+    pub fn map_numbers(&mut self, f: impl Fn(f64) -> f64) {
+        match self {
+            MissingValues::DiscreteNumeric(vals) => vals.iter_mut().for_each(|v| *v = f(*v)),
+            MissingValues::Range { low, high } => (*low, *high) = (f(*low), f(*high)),
+            MissingValues::RangeAndValue { low, high, value } => {
+                (*low, *high, *value) = (f(*low), f(*high), f(*value))
+            }
+            MissingValues::None | MissingValues::DiscreteString(_) => {}
+        }
+    }
+}
+
 /// Internal representation of a parsed variable record.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -30,9 +56,11 @@ pub struct VariableRecord {
     pub slot_index: usize,
     /// SPSS variable type: 0 = numeric, -1 = ghost (continuation), >0 = string width.
     pub raw_type: i32,
-    /// Short variable name (up to 8 characters).
+    /// Short variable name (up to 8 characters), as stored. Names are
+    /// matched ignoring case, as SPSS does.
     pub short_name: String,
-    /// Long variable name (set later from subtype 13; initially same as short_name).
+    /// Long variable name (set later from subtype 13; initially the short
+    /// name upper-cased, as SPSS shows it).
     pub long_name: String,
     /// Variable label text (if present).
     pub label: Option<Vec<u8>>,
@@ -40,6 +68,9 @@ pub struct VariableRecord {
     pub print_format: Option<SpssFormat>,
     /// Write format.
     pub write_format: Option<SpssFormat>,
+    /// The packed print format, when its type is one ambers does not know.
+    /// `print_format` then holds a guess (see `SpssFormat::guess`).
+    pub unknown_format: Option<i32>,
     /// Missing value specifications.
     pub missing_values: MissingValues,
     /// Parsed variable type.
@@ -79,7 +110,7 @@ impl VariableRecord {
 
         // Variable label
         let label = if has_var_label == 1 {
-            let label_len = reader.read_i32()? as usize;
+            let label_len = reader.read_count(1)?;
             let padded_len = io_utils::round_up(label_len, 4);
             let label_bytes = reader.read_bytes(padded_len)?;
             Some(label_bytes[..label_len].to_vec())
@@ -90,8 +121,14 @@ impl VariableRecord {
         // Missing values
         let missing_values = parse_missing_values(reader, n_missing_values, &var_type)?;
 
-        // Formats
-        let print_format = SpssFormat::from_packed(print_packed);
+        // Formats; an unknown type gets a usable guess unless this is a
+        // continuation record, whose formats mean nothing
+        let mut print_format = SpssFormat::from_packed(print_packed);
+        let mut unknown_format = None;
+        if print_format.is_none() && !is_ghost {
+            print_format = Some(SpssFormat::guess(print_packed, &var_type));
+            unknown_format = Some(print_packed);
+        }
         let write_format = SpssFormat::from_packed(write_packed);
 
         // Default display width from format
@@ -100,11 +137,12 @@ impl VariableRecord {
         Ok(VariableRecord {
             slot_index,
             raw_type,
-            short_name: short_name.to_uppercase(),
             long_name: short_name.to_uppercase(), // will be overridden by subtype 13
+            short_name,
             label,
             print_format,
             write_format,
+            unknown_format,
             missing_values,
             var_type,
             is_ghost,
@@ -115,6 +153,50 @@ impl VariableRecord {
         })
     }
 
+    /// Serialize as a type 2 (variable) record, including the leading record type.
+    ///
+    /// Inverse of `parse`. `short_name` and `label` must already be in the
+    /// file encoding; the short name is space-padded to 8 bytes.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&RECORD_TYPE_VARIABLE.to_le_bytes());
+        out.extend_from_slice(&self.raw_type.to_le_bytes());
+        out.extend_from_slice(&(self.label.is_some() as i32).to_le_bytes());
+        out.extend_from_slice(&self.missing_values.count_code().to_le_bytes());
+        let print = self.print_format.as_ref().map_or(0, |f| f.to_packed());
+        let write = self.write_format.as_ref().map_or(0, |f| f.to_packed());
+        out.extend_from_slice(&print.to_le_bytes());
+        out.extend_from_slice(&write.to_le_bytes());
+        io_utils::write_padded(out, self.short_name.as_bytes(), 8, b' ');
+
+        if let Some(ref label) = self.label {
+            out.extend_from_slice(&(label.len() as i32).to_le_bytes());
+            io_utils::write_padded(out, label, io_utils::round_up(label.len(), 4), b' ');
+        }
+
+        match &self.missing_values {
+            MissingValues::None => {}
+            MissingValues::DiscreteNumeric(vals) => {
+                for v in vals {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            MissingValues::Range { low, high } => {
+                out.extend_from_slice(&low.to_le_bytes());
+                out.extend_from_slice(&high.to_le_bytes());
+            }
+            MissingValues::RangeAndValue { low, high, value } => {
+                out.extend_from_slice(&low.to_le_bytes());
+                out.extend_from_slice(&high.to_le_bytes());
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            MissingValues::DiscreteString(vals) => {
+                for v in vals {
+                    io_utils::write_padded(out, v, 8, b' ');
+                }
+            }
+        }
+    }
+
     /// Get the number of 8-byte slots this variable occupies.
     #[allow(dead_code)]
     pub fn n_slots(&self) -> usize {
@@ -123,7 +205,7 @@ impl VariableRecord {
             VarType::String(width) => {
                 // Each 8-byte slot holds 8 bytes of string data.
                 // String width rounded up to multiple of 8.
-                (width + 7) / 8
+                width.div_ceil(8)
             }
         }
     }
@@ -140,6 +222,11 @@ fn parse_missing_values<R: Read>(
 
     let abs_n = n_missing.unsigned_abs() as usize;
     let is_range = n_missing < 0;
+    if abs_n > 3 {
+        return Err(SpssError::InvalidVariable(format!(
+            "{n_missing} missing values (at most 3 are allowed)"
+        )));
+    }
 
     match var_type {
         VarType::Numeric => {
@@ -243,6 +330,44 @@ mod tests {
         assert_eq!(var.label.as_ref().unwrap(), b"Test label");
     }
 
+    #[test]
+    fn test_write_parse_roundtrip() {
+        let original = VariableRecord {
+            slot_index: 0,
+            raw_type: 0,
+            short_name: "SCORE".to_string(),
+            long_name: "SCORE".to_string(),
+            label: Some(b"Total score".to_vec()),
+            print_format: SpssFormat::from_packed((5 << 16) | (8 << 8) | 2),
+            write_format: SpssFormat::from_packed((5 << 16) | (8 << 8) | 2),
+            unknown_format: None,
+            missing_values: MissingValues::RangeAndValue {
+                low: 97.0,
+                high: 99.0,
+                value: -1.0,
+            },
+            var_type: VarType::Numeric,
+            is_ghost: false,
+            measure: Measure::Unknown,
+            display_width: 8,
+            alignment: Alignment::Unknown,
+            n_segments: 1,
+        };
+        let mut buf = Vec::new();
+        original.write(&mut buf);
+
+        let mut reader = SavReader::new(&buf[..]);
+        assert_eq!(reader.read_i32().unwrap(), RECORD_TYPE_VARIABLE);
+        let var = VariableRecord::parse(&mut reader, 0).unwrap();
+        assert_eq!(var.short_name, "SCORE");
+        assert_eq!(var.label.as_deref(), Some(&b"Total score"[..]));
+        assert_eq!(var.print_format, original.print_format);
+        assert!(matches!(
+            var.missing_values,
+            MissingValues::RangeAndValue { low: 97.0, high: 99.0, value: -1.0 }
+        ));
+    }
+
     #[test]
     fn test_ghost_variable() {
         let data = make_variable_bytes(-1, b"        ", false);
@@ -0,0 +1,155 @@
+//! Non-fatal anomalies met while reading a file's dictionary, collected in
+//! `SpssMetadata::warnings`.
+
+use std::fmt;
+
+/// The kind of anomaly a [`SpssWarning`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// A type 7 record of a subtype ambers does not interpret; its bytes
+    /// are kept in `SpssMetadata::unknown_records`.
+    UnknownRecord,
+    /// Text that is not valid in the file's encoding: the undecodable bytes
+    /// became U+FFFD. For data, reported once per variable as it is read,
+    /// with the count in `SpssMetadata::replaced_characters`.
+    LossyText,
+    /// The display record (subtype 11) has fewer or more entries than there
+    /// are variables; those without one keep the defaults.
+    DisplayEntries,
+    /// A very long string record (subtype 14) names a variable that is not
+    /// in the file, or one with fewer segment variables than its width
+    /// needs.
+    VeryLongStringSegments,
+    /// A variable was renamed because its long name clashes with an earlier
+    /// one (see `SpssMetadata::renamed_variables`).
+    RenamedVariable,
+    /// A print format of unknown type was replaced by a guess (see
+    /// `SpssMetadata::unknown_formats`).
+    UnknownFormat,
+    /// The data holds fewer or more cases than the header (or subtype 16)
+    /// declares. Found once the scanner reaches the end of the data.
+    CaseCount,
+    /// A compressed case was cut short by an end-of-data code and returned
+    /// with every value missing, or skipped (see `BadRowPolicy`).
+    ShortCase,
+    /// Two string values of a variable's value labels became the same when
+    /// normalized to NFC (`ScanOptions::normalize_nfc`); the first label
+    /// was kept.
+    MergedValueLabels,
+}
+
+impl WarningKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::UnknownRecord => "unknown_record",
+            WarningKind::LossyText => "lossy_text",
+            WarningKind::DisplayEntries => "display_entries",
+            WarningKind::VeryLongStringSegments => "very_long_string_segments",
+            WarningKind::RenamedVariable => "renamed_variable",
+            WarningKind::UnknownFormat => "unknown_format",
+            WarningKind::CaseCount => "case_count",
+            WarningKind::ShortCase => "short_case",
+            WarningKind::MergedValueLabels => "merged_value_labels",
+        }
+    }
+}
+
+/// One anomaly met while reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpssWarning {
+    pub kind: WarningKind,
+    /// The variable concerned, if it is about one.
+    pub variable: Option<String>,
+    pub message: String,
+}
+
+impl SpssWarning {
+    pub(crate) fn new(kind: WarningKind, variable: Option<&str>, message: String) -> Self {
+        SpssWarning {
+            kind,
+            variable: variable.map(str::to_string),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for SpssWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.kind.as_str())?;
+        if let Some(variable) = &self.variable {
+            write!(f, "{variable}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use crate::metadata::SpssMetadata;
+    use crate::scanner::SavScanner;
+
+    #[test]
+    fn test_warnings_collected() {
+        let schema = Arc::new(Schema::new(vec![Field::new("q1", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.0]))]).unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels
+            .insert("q1".into(), "Cafe~ visits".into());
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &meta).unwrap();
+        // Not UTF-8, which the file declares.
+        let at = buf.windows(5).position(|w| w == b"Cafe~").unwrap();
+        buf[at + 4] = 0xFF;
+        // A record of a subtype nobody knows, before the termination record.
+        let end = [999, 0].map(i32::to_le_bytes).concat();
+        let at = buf.windows(8).position(|w| w == end).unwrap();
+        let record = [7, 99, 1, 4].map(i32::to_le_bytes).concat();
+        buf.splice(at..at, record.into_iter().chain([1, 2, 3, 4]));
+
+        let (_, meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+        let warnings: Vec<String> = meta.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "unknown_record: record subtype 99 (4 bytes) is not interpreted",
+                "lossy_text: q1: label not valid in UTF-8",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replaced_characters() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("city", DataType::Utf8, true),
+            Field::new("note", DataType::Utf8, true),
+        ]));
+        let city = StringArray::from(vec!["Oslo~", "Rome~~", "Bern"]);
+        let note = StringArray::from(vec!["a\u{FFFD}", "b", "c"]);
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(city), Arc::new(note)]).unwrap();
+        let mut buf = Vec::new();
+        crate::write_sav_to_writer(&mut buf, &batch, &SpssMetadata::default()).unwrap();
+        // Not UTF-8, which the file declares; the note's U+FFFD is genuine.
+        for text in [&b"Oslo~"[..], b"Rome~~"] {
+            let at = buf.windows(text.len()).position(|w| w == text).unwrap();
+            buf[at + 4..at + text.len()].fill(0xFF);
+        }
+
+        let mut scanner = SavScanner::open(Cursor::new(buf), 1).unwrap();
+        scanner.next_batch().unwrap();
+        assert_eq!(scanner.metadata().replaced_characters["city"], 1);
+        while scanner.next_batch().unwrap().is_some() {}
+        let meta = scanner.metadata();
+        assert_eq!(meta.replaced_characters.len(), 1);
+        assert_eq!(meta.replaced_characters["city"], 3);
+        let warnings: Vec<String> = meta.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, ["lossy_text: city: data not valid in UTF-8"]);
+    }
+}
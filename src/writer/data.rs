@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, Float64Array, LargeStringArray, StringArray, StringViewArray,
+};
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+
+use crate::arrow_convert;
+use crate::compression::bytecode::BytecodeCompressor;
+use crate::compression::zlib;
+use crate::constants::{Compression, SYSMIS_BITS, VarType};
+use crate::encoding;
+use crate::error::{Result, SpssError};
+use crate::writer::layout::{WriteColumn, WriteLayout};
+use crate::writer::{BIAS, WriteOptions};
+
+/// Target size of one buffered chunk of uncompressed cases.
+const CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Read access to any of Arrow's three string array layouts.
+pub(crate) enum StrColumn<'a> {
+    Utf8(&'a StringArray),
+    LargeUtf8(&'a LargeStringArray),
+    Utf8View(&'a StringViewArray),
+}
+
+impl<'a> StrColumn<'a> {
+    /// Wrap a string array; returns None for non-string arrays.
+    pub fn new(array: &'a dyn Array) -> Option<StrColumn<'a>> {
+        match array.data_type() {
+            DataType::Utf8 => Some(StrColumn::Utf8(array.as_string())),
+            DataType::LargeUtf8 => Some(StrColumn::LargeUtf8(array.as_string())),
+            DataType::Utf8View => Some(StrColumn::Utf8View(array.as_string_view())),
+            _ => None,
+        }
+    }
+
+    /// The value at row `i`, or None if null.
+    #[inline]
+    pub fn value(&self, i: usize) -> Option<&'a str> {
+        match self {
+            StrColumn::Utf8(a) => a.is_valid(i).then(|| a.value(i)),
+            StrColumn::LargeUtf8(a) => a.is_valid(i).then(|| a.value(i)),
+            StrColumn::Utf8View(a) => a.is_valid(i).then(|| a.value(i)),
+        }
+    }
+}
+
+/// A batch column prepared for encoding into the slot buffer.
+enum ColumnData<'a> {
+    Numeric(Float64Array),
+    String(StrColumn<'a>),
+}
+
+/// Serializes the cases of RecordBatches into raw 8-byte-slot rows.
+///
+/// Cases are assembled column-at-a-time into a row-major buffer: each column
+/// is converted once per batch (numerics cast to Float64, nulls and NaN
+/// becoming SYSMIS) and then scattered into its slots in every row.
+pub(crate) struct RowEncoder {
+    layout: WriteLayout,
+    buf: Vec<u8>,
+}
+
+impl RowEncoder {
+    pub fn new(layout: WriteLayout) -> RowEncoder {
+        RowEncoder {
+            layout,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Number of cases encoded per chunk, keeping chunks near `CHUNK_BYTES`.
+    pub fn chunk_rows(&self) -> usize {
+        (CHUNK_BYTES / self.layout.row_bytes().max(1)).max(1)
+    }
+
+    /// Encode rows `start..start + len` of `batch`, returning the raw case bytes.
+    pub fn encode(&mut self, batch: &RecordBatch, start: usize, len: usize) -> Result<&[u8]> {
+        let row_bytes = self.layout.row_bytes();
+        self.buf.clear();
+        self.buf.resize(len * row_bytes, 0);
+
+        for (col, array) in self.layout.columns.iter().zip(batch.columns()) {
+            let array = array.slice(start, len);
+            match prepare_column(col, &array)? {
+                ColumnData::Numeric(values) => {
+                    let offset = col.slot_index * 8;
+                    for (row, case) in self.buf.chunks_exact_mut(row_bytes).enumerate() {
+                        let bits = if values.is_null(row) || values.value(row).is_nan() {
+                            SYSMIS_BITS
+                        } else {
+                            values.value(row).to_bits()
+                        };
+                        case[offset..offset + 8].copy_from_slice(&bits.to_le_bytes());
+                    }
+                }
+                ColumnData::String(values) => {
+                    let width = match col.var_type {
+                        VarType::String(w) => w,
+                        VarType::Numeric => 0,
+                    };
+                    for (row, case) in self.buf.chunks_exact_mut(row_bytes).enumerate() {
+                        let value = values.value(row).unwrap_or("");
+                        let bytes = encoding::encode_str(value, self.layout.encoding)?;
+                        if bytes.len() > width {
+                            return Err(SpssError::InvalidVariable(format!(
+                                "{}: value of {} bytes exceeds string width {width}",
+                                col.name,
+                                bytes.len()
+                            )));
+                        }
+                        write_string(case, col, &bytes);
+                    }
+                }
+            }
+        }
+
+        Ok(&self.buf)
+    }
+}
+
+/// Streams cases into the data section in the file's compression format.
+///
+/// Uncompressed and bytecode data go straight to the output. For .zsav the
+/// bytecode is buffered until full zlib blocks are available; the caller
+/// writes the zlib header and trailer from the returned block sizes.
+pub(crate) struct DataWriter {
+    encoder: RowEncoder,
+    compression: Compression,
+    compressor: BytecodeCompressor,
+    zlib_level: u32,
+    zlib_block_size: usize,
+    /// Bytecode not yet zlib-compressed (.zsav only).
+    pending: Vec<u8>,
+    /// (uncompressed, compressed) size of each zlib block written.
+    block_sizes: Vec<(usize, usize)>,
+    rows_written: usize,
+}
+
+impl DataWriter {
+    pub fn new(layout: WriteLayout, options: &WriteOptions) -> DataWriter {
+        DataWriter {
+            encoder: RowEncoder::new(layout),
+            compression: options.compression,
+            compressor: BytecodeCompressor::new(BIAS),
+            zlib_level: options.zlib_level,
+            zlib_block_size: options.zlib_block_size,
+            pending: Vec::new(),
+            block_sizes: Vec::new(),
+            rows_written: 0,
+        }
+    }
+
+    pub fn rows_written(&self) -> usize {
+        self.rows_written
+    }
+
+    /// Encode and write every case of `batch`. Cases are counted as each
+    /// chunk is written, so if a later chunk fails the count still matches
+    /// what is in the file.
+    pub fn write_batch<W: Write>(&mut self, out: &mut W, batch: &RecordBatch) -> Result<()> {
+        let row_bytes = self.encoder.layout.row_bytes();
+        let chunk_rows = self.encoder.chunk_rows();
+        let mut start = 0;
+        while start < batch.num_rows() {
+            let len = chunk_rows.min(batch.num_rows() - start);
+            let rows = self.encoder.encode(batch, start, len)?;
+            match self.compression {
+                Compression::None => out.write_all(rows)?,
+                Compression::Bytecode | Compression::Zlib => {
+                    if row_bytes > 0 {
+                        for row in rows.chunks_exact(row_bytes) {
+                            self.compressor.compress_row(row);
+                        }
+                    }
+                    let complete = self.compressor.take_complete();
+                    if self.compression == Compression::Bytecode {
+                        out.write_all(&complete)?;
+                    } else {
+                        self.pending.extend_from_slice(&complete);
+                        self.flush_blocks(out, false)?;
+                    }
+                }
+            }
+            start += len;
+            self.rows_written += len;
+        }
+        Ok(())
+    }
+
+    /// Write any remaining data. Returns the (uncompressed, compressed) size
+    /// of every zlib block written (empty unless writing .zsav).
+    pub fn finish<W: Write>(mut self, out: &mut W) -> Result<Vec<(usize, usize)>> {
+        let tail =
+            std::mem::replace(&mut self.compressor, BytecodeCompressor::new(BIAS)).into_data();
+        match self.compression {
+            Compression::None => {}
+            Compression::Bytecode => out.write_all(&tail)?,
+            Compression::Zlib => {
+                self.pending.extend_from_slice(&tail);
+                self.flush_blocks(out, true)?;
+            }
+        }
+        Ok(self.block_sizes)
+    }
+
+    /// Zlib-compress and write all full blocks of pending bytecode (and the
+    /// final partial block if `last`).
+    fn flush_blocks<W: Write>(&mut self, out: &mut W, last: bool) -> Result<()> {
+        let n = if last {
+            self.pending.len()
+        } else {
+            self.pending.len() / self.zlib_block_size * self.zlib_block_size
+        };
+        if n == 0 {
+            return Ok(());
+        }
+        let blocks =
+            zlib::compress_zsav_blocks(&self.pending[..n], self.zlib_block_size, self.zlib_level)?;
+        for (raw, block) in self.pending[..n].chunks(self.zlib_block_size).zip(&blocks) {
+            out.write_all(block)?;
+            self.block_sizes.push((raw.len(), block.len()));
+        }
+        self.pending.drain(..n);
+        Ok(())
+    }
+}
+
+/// Convert a column slice into the representation its SPSS type needs.
+fn prepare_column<'a>(col: &WriteColumn, array: &'a ArrayRef) -> Result<ColumnData<'a>> {
+    match col.var_type {
+        VarType::Numeric => match &col.codes {
+            Some(codes) => Ok(ColumnData::Numeric(encode_codes(col, array, codes)?)),
+            None => Ok(ColumnData::Numeric(arrow_convert::to_spss_numeric(
+                array.as_ref(),
+            )?)),
+        },
+        VarType::String(_) => StrColumn::new(array.as_ref())
+            .map(ColumnData::String)
+            .ok_or_else(|| {
+                SpssError::InvalidVariable(format!("{}: expected a string column", col.name))
+            }),
+    }
+}
+
+/// Replace the categories of a coded column with their numeric codes.
+fn encode_codes(
+    col: &WriteColumn,
+    array: &ArrayRef,
+    codes: &HashMap<String, f64>,
+) -> Result<Float64Array> {
+    let unknown = |category: &str| {
+        SpssError::InvalidValueLabel(format!(
+            "{}: category {category:?} has no code (it was not present when the dictionary was written)",
+            col.name
+        ))
+    };
+    let not_string = || {
+        SpssError::InvalidVariable(format!(
+            "{}: expected a string or dictionary column",
+            col.name
+        ))
+    };
+
+    match array.as_any_dictionary_opt() {
+        Some(dict) => {
+            let values = StrColumn::new(dict.values().as_ref()).ok_or_else(not_string)?;
+            let keys = dict.normalized_keys();
+            (0..array.len())
+                .map(|i| {
+                    let category = if dict.keys().is_valid(i) {
+                        values.value(keys[i])
+                    } else {
+                        None
+                    };
+                    match category {
+                        Some(category) => codes
+                            .get(category)
+                            .copied()
+                            .map(Some)
+                            .ok_or_else(|| unknown(category)),
+                        None => Ok(None),
+                    }
+                })
+                .collect()
+        }
+        None => {
+            let values = StrColumn::new(array.as_ref()).ok_or_else(not_string)?;
+            (0..array.len())
+                .map(|i| match values.value(i) {
+                    Some(category) => codes
+                        .get(category)
+                        .copied()
+                        .map(Some)
+                        .ok_or_else(|| unknown(category)),
+                    None => Ok(None),
+                })
+                .collect()
+        }
+    }
+}
+
+/// Copy a string value into its segments of one case, space-padding each.
+#[inline]
+fn write_string(case: &mut [u8], col: &WriteColumn, mut bytes: &[u8]) {
+    for seg in &col.segments {
+        let n = bytes.len().min(seg.data_bytes);
+        let dest = &mut case[seg.offset..seg.offset + seg.slot_bytes];
+        dest[..n].copy_from_slice(&bytes[..n]);
+        dest[n..].fill(b' ');
+        bytes = &bytes[n..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+    use crate::metadata::SpssMetadata;
+
+    #[test]
+    fn test_encode_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("n", DataType::Int32, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![Some(7), None])),
+                Arc::new(StringArray::from(vec![Some("abc"), None])),
+            ],
+        )
+        .unwrap();
+        let layout =
+            WriteLayout::from_batch(&batch, &SpssMetadata::default(), encoding_rs::UTF_8).unwrap();
+        let mut encoder = RowEncoder::new(layout);
+        let rows = encoder.encode(&batch, 0, 2).unwrap();
+
+        assert_eq!(rows.len(), 32);
+        assert_eq!(&rows[0..8], &7.0f64.to_le_bytes());
+        assert_eq!(&rows[8..16], b"abc     ");
+        assert_eq!(&rows[16..24], &SYSMIS_BITS.to_le_bytes());
+        assert_eq!(&rows[24..32], b"        ");
+    }
+}
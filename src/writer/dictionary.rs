@@ -0,0 +1,508 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use encoding_rs::Encoding;
+use indexmap::IndexMap;
+
+use crate::constants::*;
+use crate::encoding;
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::io_utils;
+use crate::metadata::{MrType, SpssMetadata, Value};
+use crate::writer::BIAS;
+use crate::writer::layout::WriteLayout;
+
+/// Maximum length of a value label in bytes (the length field is one byte).
+const MAX_VALUE_LABEL_LEN: usize = 255;
+
+/// Length of one type 6 document line.
+const DOCUMENT_LINE_LEN: usize = 80;
+
+/// Byte offset and length of the file label within the header.
+const FILE_LABEL_OFFSET: usize = 109;
+const FILE_LABEL_LEN: usize = 64;
+
+/// Serialize the file header and dictionary, up to and including the
+/// type 999 termination record. The data section follows immediately.
+pub(crate) fn write_dictionary(
+    out: &mut Vec<u8>,
+    layout: &WriteLayout,
+    metadata: &SpssMetadata,
+    ncases: Option<usize>,
+    compression: Compression,
+) -> Result<()> {
+    let (creation_date, creation_time) = timestamp_now();
+    let header_start = out.len();
+    FileHeader {
+        magic: if compression == Compression::Zlib {
+            *b"$FL3"
+        } else {
+            *b"$FL2"
+        },
+        product: format!("@(#) SPSS DATA FILE ambers {}", env!("CARGO_PKG_VERSION")),
+        layout_code: 2,
+        nominal_case_size: layout.slots_per_row as i32,
+        compression,
+        weight_index: weight_index(layout, metadata)?,
+        ncases: ncases.and_then(|n| i32::try_from(n).ok()).unwrap_or(-1),
+        bias: BIAS,
+        creation_date,
+        creation_time,
+        file_label: String::new(),
+        bswap: false,
+    }
+    .write(out);
+    // The label is written in the file's encoding, which a String can't hold
+    let (label, _) =
+        encoding::encode_prefix(&metadata.file_label, FILE_LABEL_LEN, layout.encoding)?;
+    let label_start = header_start + FILE_LABEL_OFFSET;
+    out[label_start..label_start + label.len()].copy_from_slice(&label);
+
+    for record in &layout.records {
+        record.write(out);
+    }
+
+    write_value_labels(out, layout)?;
+    write_documents(out, &metadata.notes, layout.encoding)?;
+
+    write_integer_info(out, compression, layout.encoding);
+    write_float_info(out);
+    write_date_info(out, &metadata.date_info);
+    write_mr_sets(out, layout, metadata)?;
+    write_var_display(out, layout);
+    write_long_names(out, layout)?;
+    write_very_long_strings(out, layout);
+    write_attributes(out, layout, metadata)?;
+    write_encoding(out, layout.encoding.name());
+    write_long_string_labels(out, layout)?;
+    write_long_string_missing(out, layout)?;
+
+    out.extend_from_slice(&RECORD_TYPE_DICT_TERMINATION.to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes());
+    Ok(())
+}
+
+/// 1-based slot index of the weight variable for the header (0 = unweighted).
+fn weight_index(layout: &WriteLayout, metadata: &SpssMetadata) -> Result<i32> {
+    let Some(name) = &metadata.weight_variable else {
+        return Ok(0);
+    };
+    match layout.column(name) {
+        Some(col) if col.var_type == VarType::Numeric => Ok(col.slot_index as i32 + 1),
+        Some(_) => Err(SpssError::InvalidVariable(format!(
+            "weight variable {name} must be numeric"
+        ))),
+        None => Err(SpssError::InvalidVariable(format!(
+            "weight variable {name} is not a column of the batch"
+        ))),
+    }
+}
+
+/// Write a type 7 record header followed by its data.
+fn write_info_record(out: &mut Vec<u8>, subtype: i32, size: i32, data: &[u8]) {
+    out.extend_from_slice(&RECORD_TYPE_INFO.to_le_bytes());
+    out.extend_from_slice(&subtype.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&((data.len() as i32) / size).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Type 3 + type 4 record pairs, one pair per labelled variable.
+///
+/// Only numeric variables and strings of up to 8 bytes can be labelled this
+/// way (wider strings use subtype 21); the label values must match the
+/// variable's type.
+fn write_value_labels(out: &mut Vec<u8>, layout: &WriteLayout) -> Result<()> {
+    for col in &layout.columns {
+        let labels = &col.value_labels;
+        if labels.is_empty() || matches!(col.var_type, VarType::String(w) if w > 8) {
+            continue;
+        }
+
+        out.extend_from_slice(&RECORD_TYPE_VALUE_LABEL.to_le_bytes());
+        out.extend_from_slice(&(labels.len() as i32).to_le_bytes());
+        for (value, label) in labels {
+            let mismatch = || {
+                SpssError::InvalidValueLabel(format!(
+                    "{}: label value {value} does not fit the variable type",
+                    col.name
+                ))
+            };
+            match (value, &col.var_type) {
+                (Value::Numeric(v), VarType::Numeric) => out.extend_from_slice(&v.to_le_bytes()),
+                (Value::String(s), VarType::String(_)) => {
+                    let s = encoding::encode_str(s, layout.encoding)?;
+                    if s.len() > 8 {
+                        return Err(mismatch());
+                    }
+                    io_utils::write_padded(out, &s, 8, b' ');
+                }
+                _ => return Err(mismatch()),
+            }
+            let (label, _) = encoding::encode_prefix(label, MAX_VALUE_LABEL_LEN, layout.encoding)?;
+            out.push(label.len() as u8);
+            io_utils::write_padded(
+                out,
+                &label,
+                io_utils::round_up(label.len() + 1, 8) - 1,
+                b' ',
+            );
+        }
+
+        out.extend_from_slice(&RECORD_TYPE_VALUE_LABEL_VARS.to_le_bytes());
+        out.extend_from_slice(&1i32.to_le_bytes());
+        out.extend_from_slice(&(col.slot_index as i32 + 1).to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Type 6: file notes as space-padded 80-byte lines. Notes longer than one
+/// line are wrapped at character boundaries.
+fn write_documents(out: &mut Vec<u8>, notes: &[String], enc: &'static Encoding) -> Result<()> {
+    let mut lines = Vec::new();
+    for note in notes {
+        let mut rest = note.trim_end();
+        loop {
+            let (line, used) = encoding::encode_prefix(rest, DOCUMENT_LINE_LEN, enc)?;
+            lines.push(line);
+            rest = &rest[used..];
+            if rest.is_empty() {
+                break;
+            }
+        }
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    out.extend_from_slice(&RECORD_TYPE_DOCUMENT.to_le_bytes());
+    out.extend_from_slice(&(lines.len() as i32).to_le_bytes());
+    for line in lines {
+        io_utils::write_padded(out, &line, DOCUMENT_LINE_LEN, b' ');
+    }
+    Ok(())
+}
+
+/// Subtype 3: machine integer info.
+fn write_integer_info(out: &mut Vec<u8>, compression: Compression, enc: &'static Encoding) {
+    let mut version = env!("CARGO_PKG_VERSION")
+        .split('.')
+        .map(|part| part.parse::<i32>().unwrap_or(0));
+    let fields = [
+        version.next().unwrap_or(0),
+        version.next().unwrap_or(0),
+        version.next().unwrap_or(0),
+        -1,                                     // machine code
+        1,                                      // floating point representation: IEEE 754
+        compression.to_i32(),                   // compression code
+        2,                                      // endianness: little-endian
+        encoding::code_page_from_encoding(enc), // character code
+    ];
+    let data: Vec<u8> = fields.iter().flat_map(|v| v.to_le_bytes()).collect();
+    write_info_record(out, INFO_INTEGER, 4, &data);
+}
+
+/// Subtype 4: machine floating-point info (SYSMIS, highest, lowest).
+fn write_float_info(out: &mut Vec<u8>) {
+    let data: Vec<u8> = [sysmis(), f64::MAX, f64::from_bits(0xFFEF_FFFF_FFFF_FFFE)]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    write_info_record(out, INFO_FLOAT, 8, &data);
+}
+
+/// Subtype 6: TRENDS date info, passed through from the source file.
+fn write_date_info(out: &mut Vec<u8>, groups: &[[i32; 3]]) {
+    if groups.is_empty() {
+        return;
+    }
+    let data: Vec<u8> = groups.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+    write_info_record(out, INFO_DATE_INFO, 4, &data);
+}
+
+/// Subtype 7: multiple response sets, one `$NAME=...` line per set.
+/// Member variables are referenced by their short names.
+fn write_mr_sets(out: &mut Vec<u8>, layout: &WriteLayout, metadata: &SpssMetadata) -> Result<()> {
+    let enc = layout.encoding;
+    let mut text = Vec::new();
+    for (name, set) in &metadata.mr_sets {
+        let name = name.strip_prefix('$').unwrap_or(name);
+        text.push(b'$');
+        text.extend_from_slice(&encoding::encode_str(name, enc)?);
+        text.push(b'=');
+        match set.mr_type {
+            MrType::MultipleDichotomy => {
+                let counted = set.counted_value.as_deref().ok_or_else(|| {
+                    SpssError::InvalidVariable(format!(
+                        "MR set {name}: dichotomy set has no counted value"
+                    ))
+                })?;
+                let counted = encoding::encode_str(counted, enc)?;
+                text.extend_from_slice(format!("D{} ", counted.len()).as_bytes());
+                text.extend_from_slice(&counted);
+                text.push(b' ');
+            }
+            MrType::MultipleCategory => text.extend_from_slice(b"C "),
+        }
+        let label = encoding::encode_str(&set.label, enc)?;
+        text.extend_from_slice(format!("{} ", label.len()).as_bytes());
+        text.extend_from_slice(&label);
+        for var in &set.variables {
+            let col = layout.column(var).ok_or_else(|| {
+                SpssError::InvalidVariable(format!(
+                    "MR set {name}: variable {var} is not a column of the batch"
+                ))
+            })?;
+            text.push(b' ');
+            text.extend_from_slice(col.short_name.as_bytes());
+        }
+        text.push(b'\n');
+    }
+    if !text.is_empty() {
+        write_info_record(out, INFO_MR_SETS, 1, &text);
+    }
+    Ok(())
+}
+
+/// Subtype 11: (measure, width, alignment) for every named variable record,
+/// including the segment records of very long strings.
+fn write_var_display(out: &mut Vec<u8>, layout: &WriteLayout) {
+    let data: Vec<u8> = layout
+        .named_records()
+        .flat_map(|r| {
+            [
+                r.measure.to_i32(),
+                r.display_width as i32,
+                r.alignment.to_i32(),
+            ]
+        })
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    write_info_record(out, INFO_VAR_DISPLAY, 4, &data);
+}
+
+/// Subtype 13: `SHORT=LongName` pairs separated by tabs.
+fn write_long_names(out: &mut Vec<u8>, layout: &WriteLayout) -> Result<()> {
+    let text = layout
+        .columns
+        .iter()
+        .map(|c| format!("{}={}", c.short_name, c.name))
+        .collect::<Vec<_>>()
+        .join("\t");
+    if !text.is_empty() {
+        let text = encoding::encode_str(&text, layout.encoding)?;
+        write_info_record(out, INFO_LONG_NAMES, 1, &text);
+    }
+    Ok(())
+}
+
+/// Subtype 14: `SHORT=WIDTH\0\t` entries for strings wider than 255 bytes.
+fn write_very_long_strings(out: &mut Vec<u8>, layout: &WriteLayout) {
+    let text: String = layout
+        .columns
+        .iter()
+        .filter_map(|c| match c.var_type {
+            VarType::String(w) if w > 255 => Some(format!("{}={:05}\0\t", c.short_name, w)),
+            _ => None,
+        })
+        .collect();
+    if !text.is_empty() {
+        write_info_record(out, INFO_VERY_LONG_STRINGS, 1, text.as_bytes());
+    }
+}
+
+/// Subtypes 17 and 18: file and variable attributes.
+///
+/// Each attribute is written as `name('value1'\n'value2'\n)`; variable
+/// attribute sets are `varname:` followed by the variable's attributes, with
+/// sets separated by `/`. Variables are referenced by their long names.
+/// A variable's role is written as its `$@Role` attribute, replacing any
+/// `$@Role` among its attributes.
+fn write_attributes(
+    out: &mut Vec<u8>,
+    layout: &WriteLayout,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    if !metadata.file_attributes.is_empty() {
+        let text = attribute_text(&metadata.file_attributes);
+        let text = encoding::encode_str(&text, layout.encoding)?;
+        write_info_record(out, INFO_FILE_ATTRIBUTES, 1, &text);
+    }
+
+    let mut names: Vec<&String> = metadata.variable_attributes.keys().collect();
+    names.extend(
+        metadata
+            .variable_role
+            .keys()
+            .filter(|name| !metadata.variable_attributes.contains_key(*name)),
+    );
+    let mut sets = Vec::new();
+    for name in names {
+        let mut attributes = IndexMap::new();
+        if let Some(role) = metadata.variable_role.get(name) {
+            attributes.insert("$@Role".to_string(), vec![role.code().to_string()]);
+        }
+        for (key, values) in metadata.variable_attributes.get(name).into_iter().flatten() {
+            attributes.entry(key.clone()).or_insert_with(|| values.clone());
+        }
+        if attributes.is_empty() {
+            continue;
+        }
+        if layout.column(name).is_none() {
+            return Err(SpssError::InvalidVariable(format!(
+                "attributes given for {name}, which is not a column of the batch"
+            )));
+        }
+        sets.push(format!("{name}:{}", attribute_text(&attributes)));
+    }
+    if !sets.is_empty() {
+        let text = sets.join("/");
+        let text = encoding::encode_str(&text, layout.encoding)?;
+        write_info_record(out, INFO_VAR_ATTRIBUTES, 1, &text);
+    }
+    Ok(())
+}
+
+/// Serialize one attribute set in the subtype 17/18 text form.
+fn attribute_text(attributes: &IndexMap<String, Vec<String>>) -> String {
+    let mut text = String::new();
+    for (name, values) in attributes {
+        text.push_str(name);
+        text.push('(');
+        for value in values {
+            text.push('\'');
+            text.push_str(value);
+            text.push_str("'\n");
+        }
+        text.push(')');
+    }
+    text
+}
+
+/// Subtype 20: character encoding name.
+fn write_encoding(out: &mut Vec<u8>, name: &str) {
+    write_info_record(out, INFO_ENCODING, 1, name.as_bytes());
+}
+
+/// Subtype 21: value labels of strings wider than 8 bytes. Per variable:
+/// the long name, width and label count, then each value (padded to the
+/// width) and label as length-prefixed byte strings.
+fn write_long_string_labels(out: &mut Vec<u8>, layout: &WriteLayout) -> Result<()> {
+    let enc = layout.encoding;
+    let mut data = Vec::new();
+    for col in &layout.columns {
+        let width = match col.var_type {
+            VarType::String(w) if w > 8 && !col.value_labels.is_empty() => w,
+            _ => continue,
+        };
+        let name = encoding::encode_str(&col.name, enc)?;
+        data.extend_from_slice(&(name.len() as i32).to_le_bytes());
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&(width as i32).to_le_bytes());
+        data.extend_from_slice(&(col.value_labels.len() as i32).to_le_bytes());
+        for (value, label) in &col.value_labels {
+            let encoded = match value {
+                Value::String(s) => Some(encoding::encode_str(s, enc)?),
+                Value::Numeric(_) => None,
+            };
+            let Some(value) = encoded.filter(|v| v.len() <= width) else {
+                return Err(SpssError::InvalidValueLabel(format!(
+                    "{}: label value {value} does not fit the variable type",
+                    col.name
+                )));
+            };
+            data.extend_from_slice(&(width as i32).to_le_bytes());
+            io_utils::write_padded(&mut data, &value, width, b' ');
+            let (label, _) = encoding::encode_prefix(label, MAX_VALUE_LABEL_LEN, enc)?;
+            data.extend_from_slice(&(label.len() as i32).to_le_bytes());
+            data.extend_from_slice(&label);
+        }
+    }
+    if !data.is_empty() {
+        write_info_record(out, INFO_LONG_STRING_LABELS, 1, &data);
+    }
+    Ok(())
+}
+
+/// Subtype 22: missing values of strings wider than 8 bytes. Per variable:
+/// the long name, a one-byte value count, the value length (always 8), then
+/// the values.
+fn write_long_string_missing(out: &mut Vec<u8>, layout: &WriteLayout) -> Result<()> {
+    let mut data = Vec::new();
+    for col in layout.columns.iter().filter(|c| !c.long_missing.is_empty()) {
+        let name = encoding::encode_str(&col.name, layout.encoding)?;
+        data.extend_from_slice(&(name.len() as i32).to_le_bytes());
+        data.extend_from_slice(&name);
+        data.push(col.long_missing.len() as u8);
+        data.extend_from_slice(&8i32.to_le_bytes());
+        for value in &col.long_missing {
+            io_utils::write_padded(&mut data, value, 8, b' ');
+        }
+    }
+    if !data.is_empty() {
+        write_info_record(out, INFO_LONG_STRING_MISSING, 1, &data);
+    }
+    Ok(())
+}
+
+/// Current UTC date and time in the header's
+/// "dd Mon yy" / "hh:mm:ss" forms.
+fn timestamp_now() -> (String, String) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let tod = secs.rem_euclid(86_400);
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    (
+        format!(
+            "{:02} {} {:02}",
+            day,
+            MONTHS[month as usize - 1],
+            year.rem_euclid(100)
+        ),
+        format!("{:02}:{:02}:{:02}", tod / 3600, tod % 3600 / 60, tod % 60),
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_text() {
+        let mut attributes = IndexMap::new();
+        attributes.insert("$@Role".to_string(), vec!["0".to_string()]);
+        attributes.insert(
+            "Source".to_string(),
+            vec!["wave 1".to_string(), "wave 2".to_string()],
+        );
+        assert_eq!(
+            attribute_text(&attributes),
+            "$@Role('0'\n)Source('wave 1'\n'wave 2'\n)"
+        );
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(-141_428), (1582, 10, 14));
+    }
+}
@@ -0,0 +1,648 @@
+use std::collections::{HashMap, HashSet};
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::{DataType, Schema};
+use arrow::record_batch::RecordBatch;
+use encoding_rs::Encoding;
+use indexmap::{IndexMap, IndexSet};
+
+use crate::arrow_convert;
+use crate::constants::{Alignment, FormatType, Measure, SpssFormat, TemporalKind, VarType};
+use crate::encoding;
+use crate::error::{Result, SpssError};
+use crate::metadata::{MissingSpec, SpssMetadata, Value};
+use crate::variable::{MissingValues, VariableRecord};
+use crate::writer::data::StrColumn;
+
+/// Maximum length of a long variable name in bytes.
+const MAX_NAME_LEN: usize = 64;
+
+/// Maximum length of a variable label in bytes.
+const MAX_VAR_LABEL_LEN: usize = 256;
+
+/// Words SPSS reserves as syntax keywords; never usable as variable names.
+const RESERVED_NAMES: [&str; 13] = [
+    "ALL", "AND", "BY", "EQ", "GE", "GT", "LE", "LT", "NE", "NOT", "OR", "TO", "WITH",
+];
+
+/// One segment of a string column in the row's slot buffer.
+///
+/// Ordinary strings have a single segment. Very long strings (width > 255)
+/// are split into 255-byte chunks, each stored in its own named variable
+/// record occupying 32 slots; the last segment holds the remainder.
+#[derive(Debug, Clone)]
+pub(crate) struct Segment {
+    /// Byte offset of this segment within a row.
+    pub offset: usize,
+    /// Number of value bytes stored in this segment.
+    pub data_bytes: usize,
+    /// Total bytes reserved for this segment (a multiple of 8).
+    pub slot_bytes: usize,
+}
+
+/// How one Arrow column maps onto the SPSS case layout.
+#[derive(Debug, Clone)]
+pub(crate) struct WriteColumn {
+    pub name: String,
+    pub short_name: String,
+    pub var_type: VarType,
+    /// 0-based slot index of the column's first variable record.
+    pub slot_index: usize,
+    /// Byte layout of string values (empty for numeric columns).
+    pub segments: Vec<Segment>,
+    /// Category -> numeric code for categorical columns written as codes.
+    pub codes: Option<HashMap<String, f64>>,
+    /// Value labels to write for this variable.
+    pub value_labels: IndexMap<Value, String>,
+    /// Encoded missing values of a string wider than 8 bytes, which go in
+    /// the subtype 22 record instead of the variable record.
+    pub long_missing: Vec<Vec<u8>>,
+}
+
+/// What the writer learned about a column from the data itself.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Observed {
+    /// Longest string value in bytes, once encoded (string columns).
+    pub max_len: usize,
+    /// Distinct categories in first-seen order (categorical columns).
+    pub categories: Vec<String>,
+}
+
+/// The complete variable layout of a file being written: every type 2 record
+/// in file order plus the per-column mapping used to fill the data section.
+#[derive(Debug, Clone)]
+pub(crate) struct WriteLayout {
+    pub columns: Vec<WriteColumn>,
+    /// All variable records (named, VLS segment and `-1` continuation records).
+    pub records: Vec<VariableRecord>,
+    /// Number of 8-byte slots per case.
+    pub slots_per_row: usize,
+    /// Character encoding of every string written to the file.
+    pub encoding: &'static Encoding,
+}
+
+impl WriteLayout {
+    /// Build the layout for a batch, sizing string columns without a declared
+    /// format from the longest value present and coding categorical columns
+    /// from the categories present.
+    pub fn from_batch(
+        batch: &RecordBatch,
+        metadata: &SpssMetadata,
+        encoding: &'static Encoding,
+    ) -> Result<WriteLayout> {
+        let schema = batch.schema();
+        let observed: Vec<Observed> = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, array)| {
+                let coded = is_coded(field.data_type(), metadata.value_labels(field.name()));
+                observe(array, coded, encoding)
+            })
+            .collect::<Result<_>>()?;
+        WriteLayout::new(&schema, metadata, &observed, encoding)
+    }
+
+    /// Build the layout from a schema, the metadata to write, and what was
+    /// observed in the data for each column (default if unknown).
+    ///
+    /// A string column's width is the larger of its declared format width and
+    /// the observed length, so existing widths are preserved but never truncate.
+    ///
+    /// String dictionary columns, and string columns whose metadata value
+    /// labels have numeric values, are written as numeric codes: each
+    /// category gets the code whose label matches it, and categories without
+    /// one get the next free integer code and a generated label.
+    pub fn new(
+        schema: &Schema,
+        metadata: &SpssMetadata,
+        observed: &[Observed],
+        encoding: &'static Encoding,
+    ) -> Result<WriteLayout> {
+        validate_names(schema, encoding)?;
+
+        let mut short_names = ShortNames::new();
+        let mut columns = Vec::with_capacity(schema.fields().len());
+        let mut records = Vec::with_capacity(schema.fields().len());
+        let mut slot = 0usize;
+
+        for (field, observed) in schema.fields().iter().zip(observed) {
+            let name = field.name().as_str();
+            let coded = is_coded(field.data_type(), metadata.value_labels(name));
+            let var_type =
+                column_var_type(name, field.data_type(), metadata, observed.max_len, coded)?;
+            let print_format = column_format(field.data_type(), &var_type, metadata.format(name));
+            let short_name = short_names.assign(name);
+            let slot_index = slot;
+
+            let mut value_labels = metadata.value_labels(name).cloned().unwrap_or_default();
+            let codes = coded.then(|| assign_codes(&mut value_labels, &observed.categories));
+
+            let measure = match metadata.measure(name) {
+                Some(m) if m != Measure::Unknown => m,
+                _ if matches!(var_type, VarType::String(_)) || coded => Measure::Nominal,
+                _ => Measure::Scale,
+            };
+            let alignment = match metadata.variable_alignment.get(name) {
+                Some(&a) if a != Alignment::Unknown => a,
+                _ if matches!(var_type, VarType::String(_)) => Alignment::Left,
+                _ => Alignment::Right,
+            };
+            let display_width = metadata
+                .variable_display_width
+                .get(name)
+                .copied()
+                .unwrap_or(match var_type {
+                    VarType::Numeric => print_format.width as u32,
+                    VarType::String(w) => w as u32,
+                });
+            let label = match metadata.label(name).filter(|l| !l.is_empty()) {
+                Some(l) => Some(
+                    encoding::encode_prefix(l, MAX_VAR_LABEL_LEN, encoding)?
+                        .0
+                        .into_owned(),
+                ),
+                None => None,
+            };
+            let missing_values = match metadata.variable_missing.get(name) {
+                Some(specs) => column_missing(name, specs, &var_type, encoding)?,
+                None => MissingValues::None,
+            };
+            let (missing_values, long_missing) = match (&var_type, missing_values) {
+                (VarType::String(w), MissingValues::DiscreteString(values)) if *w > 8 => {
+                    (MissingValues::None, values)
+                }
+                (_, missing) => (missing, Vec::new()),
+            };
+
+            let first = VariableRecord {
+                slot_index: slot,
+                raw_type: 0,
+                short_name: short_name.clone(),
+                long_name: name.to_string(),
+                label,
+                print_format: Some(print_format.clone()),
+                write_format: Some(print_format),
+                unknown_format: None,
+                missing_values,
+                var_type: var_type.clone(),
+                is_ghost: false,
+                measure,
+                display_width,
+                alignment,
+                n_segments: 1,
+            };
+
+            let mut segments = Vec::new();
+            match var_type {
+                VarType::Numeric => {
+                    records.push(first);
+                    slot += 1;
+                }
+                VarType::String(width) => {
+                    let n_segments = if width > 255 { width.div_ceil(252) } else { 1 };
+                    for seg in 0..n_segments {
+                        // Declared width of this segment's variable record and
+                        // the number of value bytes it actually carries.
+                        let (seg_width, data_bytes) = if n_segments == 1 {
+                            (width, width)
+                        } else if seg < n_segments - 1 {
+                            (255, 255)
+                        } else {
+                            (width - seg * 252, width.saturating_sub(seg * 255).min(255))
+                        };
+                        let n_slots = seg_width.div_ceil(8);
+                        segments.push(Segment {
+                            offset: slot * 8,
+                            data_bytes,
+                            slot_bytes: n_slots * 8,
+                        });
+
+                        let mut record = if seg == 0 {
+                            first.clone()
+                        } else {
+                            VariableRecord {
+                                slot_index: slot,
+                                short_name: short_names.assign(name),
+                                label: None,
+                                missing_values: MissingValues::None,
+                                is_ghost: true,
+                                ..first.clone()
+                            }
+                        };
+                        let fmt = SpssFormat {
+                            format_type: FormatType::A,
+                            width: seg_width.min(255) as u8,
+                            decimals: 0,
+                        };
+                        record.raw_type = seg_width.min(255) as i32;
+                        record.print_format = Some(fmt.clone());
+                        record.write_format = Some(fmt);
+                        record.n_segments = n_segments;
+                        records.push(record);
+
+                        for i in 1..n_slots {
+                            records.push(continuation_record(slot + i));
+                        }
+                        slot += n_slots;
+                    }
+                }
+            }
+
+            columns.push(WriteColumn {
+                name: name.to_string(),
+                short_name,
+                var_type,
+                slot_index,
+                segments,
+                codes,
+                value_labels,
+                long_missing,
+            });
+        }
+
+        Ok(WriteLayout {
+            columns,
+            records,
+            slots_per_row: slot,
+            encoding,
+        })
+    }
+
+    /// Size of one uncompressed case in bytes.
+    pub fn row_bytes(&self) -> usize {
+        self.slots_per_row * 8
+    }
+
+    /// The column with long name `name`.
+    pub fn column(&self, name: &str) -> Option<&WriteColumn> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Records that carry a subtype 11 display entry (everything but `-1` continuations).
+    pub fn named_records(&self) -> impl Iterator<Item = &VariableRecord> {
+        self.records.iter().filter(|r| r.raw_type != -1)
+    }
+}
+
+/// A type 2 record of type -1, continuing the previous string variable.
+fn continuation_record(slot_index: usize) -> VariableRecord {
+    VariableRecord {
+        slot_index,
+        raw_type: -1,
+        short_name: String::new(),
+        long_name: String::new(),
+        label: None,
+        print_format: None,
+        write_format: None,
+        unknown_format: None,
+        missing_values: MissingValues::None,
+        var_type: VarType::String(0),
+        is_ghost: true,
+        measure: Measure::Unknown,
+        display_width: 0,
+        alignment: Alignment::Unknown,
+        n_segments: 1,
+    }
+}
+
+/// Check that every column name is a usable SPSS variable name.
+fn validate_names(schema: &Schema, encoding: &'static Encoding) -> Result<()> {
+    let mut seen = HashSet::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let name = field.name();
+        if name.is_empty() {
+            return Err(SpssError::InvalidVariable(
+                "empty variable name".to_string(),
+            ));
+        }
+        if encoding::encode_str(name, encoding)?.len() > MAX_NAME_LEN {
+            return Err(SpssError::InvalidVariable(format!(
+                "variable name {name:?} is longer than {MAX_NAME_LEN} bytes"
+            )));
+        }
+        if !seen.insert(name.to_uppercase()) {
+            return Err(SpssError::InvalidVariable(format!(
+                "duplicate variable name {name:?} (names are case-insensitive)"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a column is written as numeric codes plus value labels.
+fn is_coded(data_type: &DataType, labels: Option<&IndexMap<Value, String>>) -> bool {
+    match data_type {
+        DataType::Dictionary(_, values) => is_string_type(values),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => labels
+            .is_some_and(|l| !l.is_empty() && l.keys().all(|v| matches!(v, Value::Numeric(_)))),
+        _ => false,
+    }
+}
+
+fn is_string_type(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+    )
+}
+
+/// Map categories to codes through the numeric value labels, appending the
+/// next free integer code (and a label) for each unlabelled category.
+fn assign_codes(
+    labels: &mut IndexMap<Value, String>,
+    categories: &[String],
+) -> HashMap<String, f64> {
+    labels.retain(|value, _| matches!(value, Value::Numeric(_)));
+
+    let mut codes = HashMap::with_capacity(labels.len() + categories.len());
+    let mut next = 1.0f64;
+    for (value, label) in labels.iter() {
+        if let Value::Numeric(code) = *value {
+            codes.entry(label.clone()).or_insert(code);
+            if code.is_finite() {
+                next = next.max(code.floor() + 1.0);
+            }
+        }
+    }
+    for category in categories {
+        if !codes.contains_key(category) {
+            codes.insert(category.clone(), next);
+            labels.insert(Value::Numeric(next), category.clone());
+            next += 1.0;
+        }
+    }
+    codes
+}
+
+/// SPSS storage type for an Arrow column.
+fn column_var_type(
+    name: &str,
+    data_type: &DataType,
+    metadata: &SpssMetadata,
+    observed_len: usize,
+    coded: bool,
+) -> Result<VarType> {
+    if coded {
+        return Ok(VarType::Numeric);
+    }
+    match data_type {
+        DataType::Null
+        | DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Date32
+        | DataType::Date64
+        | DataType::Timestamp(_, _)
+        | DataType::Duration(_)
+        | DataType::Time32(_)
+        | DataType::Time64(_) => Ok(VarType::Numeric),
+        DataType::Dictionary(_, values) if values.is_numeric() => Ok(VarType::Numeric),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+            let declared = metadata
+                .format(name)
+                .and_then(string_format_width)
+                .unwrap_or(0);
+            Ok(VarType::String(declared.max(observed_len).max(1)))
+        }
+        other => Err(SpssError::Unsupported(format!(
+            "cannot write column {name:?} of Arrow type {other}"
+        ))),
+    }
+}
+
+/// Print/write format for a column: the metadata format if it fits the
+/// storage type, otherwise a default based on the Arrow type.
+///
+/// Temporal columns only accept a declared format of the same temporal kind
+/// (e.g. ADATE10 for a Date32 column) and default to DATE11, DATETIME20 or
+/// TIME8.
+fn column_format(data_type: &DataType, var_type: &VarType, declared: Option<&str>) -> SpssFormat {
+    let format = |format_type, width, decimals| SpssFormat {
+        format_type,
+        width,
+        decimals,
+    };
+    match var_type {
+        VarType::String(width) => format(FormatType::A, (*width).min(255) as u8, 0),
+        VarType::Numeric => {
+            let temporal = arrow_convert::arrow_temporal_kind(data_type);
+            if let Some(fmt) = declared.and_then(SpssFormat::parse)
+                && !fmt.format_type.is_string()
+                && fmt.format_type.temporal_kind() == temporal
+            {
+                return fmt;
+            }
+            match (temporal, data_type) {
+                (Some(TemporalKind::Date), _) => format(FormatType::Date, 11, 0),
+                (Some(TemporalKind::Timestamp), _) => format(FormatType::DateTime, 20, 0),
+                (Some(TemporalKind::Duration), _) => format(FormatType::Time, 8, 0),
+                (None, DataType::Boolean) => format(FormatType::F, 1, 0),
+                (
+                    None,
+                    DataType::Float16 | DataType::Float32 | DataType::Float64 | DataType::Null,
+                ) => format(FormatType::F, 8, 2),
+                (None, _) => format(FormatType::F, 8, 0),
+            }
+        }
+    }
+}
+
+/// Width of a string format like "A50" or "A1000" (not clamped to 255).
+fn string_format_width(format: &str) -> Option<usize> {
+    let format = format.trim();
+    let digits_at = format.find(|c: char| c.is_ascii_digit())?;
+    if !FormatType::from_prefix(&format[..digits_at])?.is_string() {
+        return None;
+    }
+    let digits = format[digits_at..].split('.').next()?;
+    digits.parse().ok()
+}
+
+/// Convert metadata missing-value specs into the type 2 record representation.
+///
+/// Numeric variables allow up to three discrete values, or one range plus at
+/// most one discrete value. Strings allow three discrete values of at most
+/// 8 bytes (for strings wider than 8 bytes they go in subtype 22).
+fn column_missing(
+    name: &str,
+    specs: &[MissingSpec],
+    var_type: &VarType,
+    encoding: &'static Encoding,
+) -> Result<MissingValues> {
+    let invalid = |msg: &str| SpssError::InvalidVariable(format!("{name}: {msg}"));
+
+    match var_type {
+        VarType::Numeric => {
+            let mut values = Vec::new();
+            let mut ranges = Vec::new();
+            for spec in specs {
+                match spec {
+                    MissingSpec::Value(v) => values.push(*v),
+                    MissingSpec::Range { lo, hi } => ranges.push((*lo, *hi)),
+                    MissingSpec::StringValue(_) => {
+                        return Err(invalid("string missing value on a numeric variable"));
+                    }
+                }
+            }
+            match (ranges.as_slice(), values.as_slice()) {
+                ([], []) => Ok(MissingValues::None),
+                ([], vals) if vals.len() <= 3 => Ok(MissingValues::DiscreteNumeric(vals.to_vec())),
+                ([(low, high)], []) => Ok(MissingValues::Range {
+                    low: *low,
+                    high: *high,
+                }),
+                ([(low, high)], [value]) => Ok(MissingValues::RangeAndValue {
+                    low: *low,
+                    high: *high,
+                    value: *value,
+                }),
+                _ => Err(invalid(
+                    "at most three missing values, or one range plus one value, are allowed",
+                )),
+            }
+        }
+        VarType::String(_) => {
+            let mut values = Vec::new();
+            for spec in specs {
+                match spec {
+                    MissingSpec::StringValue(s) => {
+                        values.push(encoding::encode_str(s, encoding)?.into_owned())
+                    }
+                    _ => return Err(invalid("numeric missing value on a string variable")),
+                }
+            }
+            if values.len() > 3 {
+                return Err(invalid("at most three missing values are allowed"));
+            }
+            if values.is_empty() {
+                return Ok(MissingValues::None);
+            }
+            if values.iter().any(|v| v.len() > 8) {
+                return Err(invalid("string missing values are limited to 8 bytes"));
+            }
+            Ok(MissingValues::DiscreteString(values))
+        }
+    }
+}
+
+/// Collect the longest encoded string length, or for coded columns the
+/// distinct categories, of one column.
+fn observe(array: &ArrayRef, coded: bool, encoding: &'static Encoding) -> Result<Observed> {
+    let mut observed = Observed::default();
+    // A dictionary's categories are its values, used or not
+    let (values, len) = match array.as_any_dictionary_opt() {
+        Some(dict) => (dict.values().as_ref(), dict.values().len()),
+        None => (array.as_ref(), array.len()),
+    };
+    let Some(col) = StrColumn::new(values) else {
+        return Ok(observed);
+    };
+    if coded {
+        let categories: IndexSet<&str> = (0..len).filter_map(|i| col.value(i)).collect();
+        observed.categories = categories.into_iter().map(str::to_string).collect();
+    } else if encoding == encoding_rs::UTF_8 {
+        observed.max_len = (0..len)
+            .filter_map(|i| col.value(i))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+    } else {
+        for value in (0..len).filter_map(|i| col.value(i)) {
+            let n = encoding::encode_str(value, encoding)?.len();
+            observed.max_len = observed.max_len.max(n);
+        }
+    }
+    Ok(observed)
+}
+
+/// Generator for unique 8-byte short variable names.
+///
+/// Short names are what the type 2 records carry; the real (long) names are
+/// mapped onto them by the subtype 13 record.
+struct ShortNames {
+    used: HashSet<String>,
+}
+
+impl ShortNames {
+    fn new() -> ShortNames {
+        ShortNames {
+            used: RESERVED_NAMES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Derive an unused short name from `name`: uppercase ASCII letters,
+    /// digits and underscores, starting with a letter, at most 8 bytes.
+    fn assign(&mut self, name: &str) -> String {
+        let mut base: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            base.insert(0, 'V');
+        }
+        base.truncate(8);
+
+        let mut candidate = base.clone();
+        let mut n = 1u32;
+        while !self.used.insert(candidate.clone()) {
+            let suffix = n.to_string();
+            candidate = format!("{}{}", &base[..base.len().min(8 - suffix.len())], suffix);
+            n += 1;
+        }
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_names_unique() {
+        let mut names = ShortNames::new();
+        assert_eq!(names.assign("respondent_age"), "RESPONDE");
+        assert_eq!(names.assign("respondent_sex"), "RESPOND1");
+        assert_eq!(names.assign("2nd wave"), "V2NDWAVE");
+        assert_eq!(names.assign("by"), "BY1");
+    }
+
+    #[test]
+    fn test_very_long_string_segments() {
+        let schema = Schema::new(vec![arrow::datatypes::Field::new(
+            "text",
+            DataType::Utf8,
+            true,
+        )]);
+        let observed = Observed {
+            max_len: 600,
+            ..Observed::default()
+        };
+        let layout = WriteLayout::new(
+            &schema,
+            &SpssMetadata::default(),
+            &[observed],
+            encoding_rs::UTF_8,
+        )
+        .unwrap();
+
+        // 600 bytes -> 3 segments: 255 + 255 + 90 data bytes,
+        // declared widths 255, 255 and 600 - 2 * 252 = 96.
+        let col = &layout.columns[0];
+        assert_eq!(col.var_type, VarType::String(600));
+        let data: Vec<usize> = col.segments.iter().map(|s| s.data_bytes).collect();
+        assert_eq!(data, vec![255, 255, 90]);
+        assert_eq!(layout.slots_per_row, 32 + 32 + 12);
+        assert_eq!(layout.named_records().count(), 3);
+    }
+}
@@ -0,0 +1,734 @@
+//! SPSS .sav writer.
+//!
+//! The writer mirrors the reader: a `WriteLayout` maps Arrow columns onto the
+//! same `VariableRecord`s the parser produces, the dictionary is serialized
+//! record by record, and cases are assembled column-at-a-time into rows of
+//! 8-byte slots for the data section.
+
+pub(crate) mod data;
+pub(crate) mod dictionary;
+pub(crate) mod layout;
+pub(crate) mod rewrite;
+pub mod stream;
+
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::record_batch::RecordBatch;
+use encoding_rs::Encoding;
+
+use crate::arrow_convert;
+use crate::compression::zlib::{ZHeader, ZTrailer};
+use crate::constants::Compression;
+use crate::error::{Result, SpssError};
+use crate::metadata::SpssMetadata;
+use crate::writer::data::DataWriter;
+use crate::writer::layout::WriteLayout;
+
+pub use crate::writer::stream::SavWriter;
+
+/// Compression bias written to the header; bytecodes 1..=251 encode `code - BIAS`.
+pub(crate) const BIAS: f64 = 100.0;
+
+/// Options controlling how a .sav/.zsav file is written.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Data compression: `None` or `Bytecode` (.sav), or `Zlib` (.zsav).
+    pub compression: Compression,
+    /// zlib compression level for .zsav output, 0 (fastest) to 9 (smallest).
+    pub zlib_level: u32,
+    /// Uncompressed size of each zlib block in bytes (.zsav only).
+    pub zlib_block_size: usize,
+    /// Character encoding of the file, as a WHATWG label ("UTF-8",
+    /// "windows-1252", "Shift_JIS", ...). Strings, names and labels are
+    /// transcoded into it; characters it cannot represent are an error.
+    pub encoding: String,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            compression: Compression::None,
+            zlib_level: 6,
+            // Block size used by SPSS itself
+            zlib_block_size: 0x3F_F000,
+            encoding: "UTF-8".to_string(),
+        }
+    }
+}
+
+impl WriteOptions {
+    fn validate(&self) -> Result<()> {
+        if self.zlib_level > 9 {
+            return Err(SpssError::Unsupported(format!(
+                "zlib compression level {} (expected 0-9)",
+                self.zlib_level
+            )));
+        }
+        if self.zlib_block_size == 0 || self.zlib_block_size > i32::MAX as usize {
+            return Err(SpssError::Unsupported(format!(
+                "zlib block size {}",
+                self.zlib_block_size
+            )));
+        }
+        self.output_encoding()?;
+        Ok(())
+    }
+
+    /// Resolve the `encoding` label. UTF-16 and other encodings that are not
+    /// ASCII-compatible cannot be written.
+    pub(crate) fn output_encoding(&self) -> Result<&'static Encoding> {
+        let encoding = Encoding::for_label(self.encoding.trim().as_bytes())
+            .ok_or_else(|| SpssError::Encoding(format!("unknown encoding {:?}", self.encoding)))?;
+        if encoding.output_encoding() != encoding {
+            return Err(SpssError::Unsupported(format!(
+                "writing {} files",
+                encoding.name()
+            )));
+        }
+        Ok(encoding)
+    }
+}
+
+/// Write a RecordBatch and its metadata as a .sav or .zsav file.
+///
+/// The case count is known up front, so no seeking is needed; .zsav blocks
+/// are compressed into memory first so the zlib header can precede them.
+pub(crate) fn write_batch<W: Write>(
+    mut out: W,
+    batch: &RecordBatch,
+    metadata: &SpssMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    options.validate()?;
+    let layout = WriteLayout::from_batch(batch, metadata, options.output_encoding()?)?;
+
+    let mut dict = Vec::new();
+    dictionary::write_dictionary(
+        &mut dict,
+        &layout,
+        metadata,
+        Some(batch.num_rows()),
+        options.compression,
+    )?;
+
+    let mut data = DataWriter::new(layout, options);
+    if options.compression == Compression::Zlib {
+        let mut blocks = Vec::new();
+        data.write_batch(&mut blocks, batch)?;
+        let sizes = data.finish(&mut blocks)?;
+
+        let zheader_offset = dict.len() as i64;
+        let trailer = ZTrailer::for_blocks(BIAS, zheader_offset, options.zlib_block_size, &sizes);
+        zsav_header(zheader_offset, &trailer).write(&mut dict);
+        trailer.write(&mut blocks);
+        out.write_all(&dict)?;
+        out.write_all(&blocks)?;
+    } else {
+        out.write_all(&dict)?;
+        data.write_batch(&mut out, batch)?;
+        data.finish(&mut out)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a file with the dictionary described by `metadata` and no cases.
+///
+/// Column types come from the metadata formats: string formats become string
+/// variables of the declared width, date/time formats temporal variables,
+/// and everything else numeric.
+pub(crate) fn write_template<W: Write>(
+    out: W,
+    metadata: &SpssMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    if metadata.variable_names.is_empty() {
+        return Err(SpssError::InvalidVariable(
+            "metadata has no variables".to_string(),
+        ));
+    }
+    let schema = arrow_convert::metadata_to_schema(metadata);
+    let batch = RecordBatch::new_empty(Arc::new(schema));
+    write_batch(out, &batch, metadata, options)
+}
+
+/// The zlib header for a trailer whose blocks start right after the header.
+pub(crate) fn zsav_header(zheader_offset: i64, trailer: &ZTrailer) -> ZHeader {
+    let compressed_len: i64 = trailer
+        .entries
+        .iter()
+        .map(|e| e.compressed_size as i64)
+        .sum();
+    ZHeader {
+        zheader_offset,
+        ztrailer_offset: zheader_offset + 24 + compressed_len,
+        ztrailer_length: trailer.serialized_len() as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{
+        Array, AsArray, Date32Array, DictionaryArray, DurationMicrosecondArray, Float64Array,
+        Int64Array, StringArray, TimestampMicrosecondArray,
+    };
+    use arrow::datatypes::{DataType, Field, Float64Type, Int8Type, Schema, TimeUnit};
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::constants::{Measure, Role};
+    use crate::metadata::{MissingSpec, MrSet, MrType, Value};
+
+    fn roundtrip(batch: &RecordBatch, meta: &SpssMetadata) -> (RecordBatch, SpssMetadata) {
+        let mut buf = Vec::new();
+        write_batch(&mut buf, batch, meta, &WriteOptions::default()).unwrap();
+        crate::read_sav_from_reader(Cursor::new(buf)).unwrap()
+    }
+
+    #[test]
+    fn test_truncate_str() {
+        let truncate_str = |s, max| {
+            let (_, used) = crate::encoding::encode_prefix(s, max, encoding_rs::UTF_8).unwrap();
+            &s[..used]
+        };
+        assert_eq!(truncate_str("abc", 8), "abc");
+        assert_eq!(truncate_str("héllo", 2), "h");
+        assert_eq!(truncate_str("héllo", 3), "hé");
+    }
+
+    #[test]
+    fn test_roundtrip_data_and_metadata() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("respondent_id", DataType::Int64, false),
+            Field::new("score", DataType::Float64, true),
+            Field::new("city", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(Float64Array::from(vec![Some(1.5), None, Some(f64::NAN)])),
+                Arc::new(StringArray::from(vec![Some("Zürich"), None, Some("Oslo")])),
+            ],
+        )
+        .unwrap();
+
+        let mut meta = SpssMetadata {
+            file_label: "Survey wave 1".to_string(),
+            ..SpssMetadata::default()
+        };
+        meta.variable_labels
+            .insert("score".to_string(), "Satisfaction score".to_string());
+        meta.variable_measure
+            .insert("score".to_string(), Measure::Ordinal);
+        meta.variable_missing.insert(
+            "score".to_string(),
+            vec![MissingSpec::Range { lo: 97.0, hi: 99.0 }],
+        );
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(1.0), "Low".to_string());
+        labels.insert(Value::Numeric(2.0), "High".to_string());
+        meta.variable_value_labels
+            .insert("score".to_string(), labels);
+
+        let (out, out_meta) = roundtrip(&batch, &meta);
+
+        assert_eq!(out.num_rows(), 3);
+        assert_eq!(
+            out_meta.variable_names,
+            vec!["respondent_id", "score", "city"]
+        );
+        assert_eq!(out_meta.file_label, "Survey wave 1");
+        assert_eq!(out_meta.label("score"), Some("Satisfaction score"));
+        assert_eq!(out_meta.measure("score"), Some(Measure::Ordinal));
+        assert_eq!(out_meta.format("respondent_id"), Some("F8.0"));
+        assert_eq!(out_meta.format("city"), Some("A7"));
+        assert_eq!(out_meta.value_labels("score").unwrap().len(), 2);
+        assert!(matches!(
+            out_meta.variable_missing["score"][..],
+            [MissingSpec::Range { lo: 97.0, hi: 99.0 }]
+        ));
+
+        let ids = out.column(0).as_primitive::<Float64Type>();
+        assert_eq!(ids.values().to_vec(), vec![1.0, 2.0, 3.0]);
+        let scores = out.column(1).as_primitive::<Float64Type>();
+        assert_eq!(scores.value(0), 1.5);
+        assert!(scores.is_null(1) && scores.is_null(2));
+        let cities = out.column(2).as_string_view();
+        assert_eq!(cities.value(0), "Zürich");
+        assert_eq!(cities.value(1), "");
+        assert_eq!(cities.value(2), "Oslo");
+    }
+
+    #[test]
+    fn test_roundtrip_very_long_string() {
+        let long = "x".repeat(300) + &"y".repeat(300);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("note", DataType::Utf8, true),
+            Field::new("after", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![long.as_str(), "short"])),
+                Arc::new(Float64Array::from(vec![1.0, 2.0])),
+            ],
+        )
+        .unwrap();
+
+        let (out, out_meta) = roundtrip(&batch, &SpssMetadata::default());
+
+        assert_eq!(out_meta.variable_names, vec!["note", "after"]);
+        assert_eq!(out_meta.format("note"), Some("A600"));
+        let notes = out.column(0).as_string_view();
+        assert_eq!(notes.value(0), long);
+        assert_eq!(notes.value(1), "short");
+        let after = out.column(1).as_primitive::<Float64Type>();
+        assert_eq!(after.value(1), 2.0);
+    }
+
+    #[test]
+    fn test_roundtrip_temporal() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("visit", DataType::Date32, true),
+            Field::new(
+                "stamp",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("elapsed", DataType::Duration(TimeUnit::Microsecond), true),
+            Field::new("birth", DataType::Date32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Date32Array::from(vec![Some(19_723), None])),
+                Arc::new(TimestampMicrosecondArray::from(vec![
+                    Some(1_704_103_200_000_000),
+                    Some(-86_400_000_000),
+                ])),
+                Arc::new(DurationMicrosecondArray::from(vec![
+                    Some(5_400_000_000),
+                    None,
+                ])),
+                Arc::new(Date32Array::from(vec![Some(-3653), Some(0)])),
+            ],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.spss_variable_types
+            .insert("birth".to_string(), "ADATE10".to_string());
+        // A non-temporal format cannot describe a date column
+        meta.spss_variable_types
+            .insert("visit".to_string(), "F8.2".to_string());
+
+        let (out, out_meta) = roundtrip(&batch, &meta);
+
+        assert_eq!(out_meta.format("visit"), Some("DATE11"));
+        assert_eq!(out_meta.format("stamp"), Some("DATETIME20"));
+        assert_eq!(out_meta.format("elapsed"), Some("TIME8"));
+        assert_eq!(out_meta.format("birth"), Some("ADATE10"));
+        assert_eq!(out.schema().fields(), batch.schema().fields());
+        assert_eq!(out.columns(), batch.columns());
+    }
+
+    #[test]
+    fn test_roundtrip_categorical() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "region",
+                DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8)),
+                true,
+            ),
+            Field::new("agree", DataType::Utf8, true),
+        ]));
+        let region: DictionaryArray<Int8Type> =
+            vec![Some("North"), Some("South"), None, Some("North")]
+                .into_iter()
+                .collect();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(region),
+                Arc::new(StringArray::from(vec![
+                    Some("Agree"),
+                    Some("Disagree"),
+                    Some("Agree"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        // "agree" carries numeric labels, so its strings are stored as codes;
+        // "Disagree" has no label yet and gets the next free code.
+        let mut meta = SpssMetadata::default();
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(5.0), "Agree".to_string());
+        meta.variable_value_labels
+            .insert("agree".to_string(), labels);
+
+        let (out, out_meta) = roundtrip(&batch, &meta);
+
+        assert_eq!(out_meta.format("region"), Some("F8.0"));
+        assert_eq!(out_meta.measure("region"), Some(Measure::Nominal));
+        let region_labels = out_meta.value_labels("region").unwrap();
+        assert_eq!(region_labels[&Value::Numeric(1.0)], "North");
+        assert_eq!(region_labels[&Value::Numeric(2.0)], "South");
+        let agree_labels = out_meta.value_labels("agree").unwrap();
+        assert_eq!(agree_labels[&Value::Numeric(6.0)], "Disagree");
+
+        let region = out.column(0).as_primitive::<Float64Type>();
+        assert_eq!(region.value(0), 1.0);
+        assert_eq!(region.value(1), 2.0);
+        assert!(region.is_null(2));
+        assert_eq!(region.value(3), 1.0);
+        let agree = out.column(1).as_primitive::<Float64Type>();
+        assert_eq!(agree.value(0), 5.0);
+        assert_eq!(agree.value(1), 6.0);
+        assert!(agree.is_null(3));
+    }
+
+    #[test]
+    fn test_roundtrip_mr_sets_weight_and_notes() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("brand_awareness_a", DataType::Float64, true),
+            Field::new("brand_awareness_b", DataType::Float64, true),
+            Field::new("wt", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.0, 0.0])),
+                Arc::new(Float64Array::from(vec![1.0, 1.0])),
+                Arc::new(Float64Array::from(vec![0.5, 1.5])),
+            ],
+        )
+        .unwrap();
+
+        let mut meta = SpssMetadata {
+            weight_variable: Some("wt".to_string()),
+            notes: vec!["Fielded March 2024".to_string(), "n".repeat(100)],
+            ..SpssMetadata::default()
+        };
+        meta.mr_sets.insert(
+            "aware".to_string(),
+            MrSet {
+                name: "aware".to_string(),
+                label: "Brand awareness".to_string(),
+                mr_type: MrType::MultipleDichotomy,
+                counted_value: Some("1".to_string()),
+                variables: vec![
+                    "brand_awareness_a".to_string(),
+                    "brand_awareness_b".to_string(),
+                ],
+            },
+        );
+
+        let (_, out_meta) = roundtrip(&batch, &meta);
+
+        assert_eq!(out_meta.weight_variable.as_deref(), Some("wt"));
+        assert_eq!(
+            out_meta.notes,
+            vec![
+                "Fielded March 2024".to_string(),
+                "n".repeat(80),
+                "n".repeat(20)
+            ]
+        );
+        let set = &out_meta.mr_sets["aware"];
+        assert_eq!(set.label, "Brand awareness");
+        assert_eq!(set.mr_type, MrType::MultipleDichotomy);
+        assert_eq!(set.counted_value.as_deref(), Some("1"));
+        assert_eq!(
+            set.variables,
+            vec!["brand_awareness_a", "brand_awareness_b"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_string_weight() {
+        let schema = Arc::new(Schema::new(vec![Field::new("w", DataType::Utf8, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(vec!["a"]))]).unwrap();
+        let meta = SpssMetadata {
+            weight_variable: Some("w".to_string()),
+            ..SpssMetadata::default()
+        };
+        let mut buf = Vec::new();
+        assert!(write_batch(&mut buf, &batch, &meta, &WriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_windows_1252() {
+        let schema = Arc::new(Schema::new(vec![Field::new("city", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec!["Zürich", "Genève"]))],
+        )
+        .unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.variable_labels
+            .insert("city".to_string(), "Stadtgröße".to_string());
+        let options = WriteOptions {
+            encoding: "windows-1252".to_string(),
+            ..WriteOptions::default()
+        };
+
+        let mut buf = Vec::new();
+        write_batch(&mut buf, &batch, &meta, &options).unwrap();
+        assert!(buf.windows(6).any(|w| w == b"Z\xfcrich"));
+        let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+
+        assert_eq!(out_meta.file_encoding, "windows-1252");
+        assert_eq!(out_meta.variable_names, vec!["city"]);
+        assert_eq!(out_meta.label("city"), Some("Stadtgröße"));
+        // One byte per character in windows-1252
+        assert_eq!(out_meta.format("city"), Some("A6"));
+        assert_eq!(out.column(0).as_string_view().value(1), "Genève");
+    }
+
+    #[test]
+    fn test_rejects_unencodable_text() {
+        let schema = Arc::new(Schema::new(vec![Field::new("city", DataType::Utf8, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(vec!["東京"]))]).unwrap();
+        let mut buf = Vec::new();
+        let latin1 = WriteOptions {
+            encoding: "windows-1252".to_string(),
+            ..WriteOptions::default()
+        };
+        assert!(write_batch(&mut buf, &batch, &SpssMetadata::default(), &latin1).is_err());
+
+        let sjis = WriteOptions {
+            encoding: "Shift_JIS".to_string(),
+            ..WriteOptions::default()
+        };
+        write_batch(&mut buf, &batch, &SpssMetadata::default(), &sjis).unwrap();
+
+        let utf16 = WriteOptions {
+            encoding: "UTF-16LE".to_string(),
+            ..WriteOptions::default()
+        };
+        assert!(write_batch(&mut buf, &batch, &SpssMetadata::default(), &utf16).is_err());
+    }
+
+    #[test]
+    fn test_template_roundtrip() {
+        let mut meta = SpssMetadata {
+            variable_names: vec!["id".into(), "name".into(), "visit".into()],
+            ..SpssMetadata::default()
+        };
+        for (name, format) in [("id", "F6.0"), ("name", "A40"), ("visit", "ADATE10")] {
+            meta.spss_variable_types
+                .insert(name.to_string(), format.to_string());
+        }
+        meta.variable_labels
+            .insert("name".to_string(), "Respondent name".to_string());
+        let mut labels = IndexMap::new();
+        labels.insert(Value::Numeric(1.0), "First".to_string());
+        meta.variable_value_labels.insert("id".to_string(), labels);
+
+        let mut buf = Vec::new();
+        write_template(&mut buf, &meta, &WriteOptions::default()).unwrap();
+        let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+
+        assert_eq!(out.num_rows(), 0);
+        assert_eq!(out_meta.number_rows, Some(0));
+        assert_eq!(out_meta.variable_names, meta.variable_names);
+        assert_eq!(out_meta.format("id"), Some("F6.0"));
+        assert_eq!(out_meta.format("name"), Some("A40"));
+        assert_eq!(out_meta.format("visit"), Some("ADATE10"));
+        assert_eq!(out_meta.label("name"), Some("Respondent name"));
+        assert_eq!(out_meta.value_labels("id").unwrap().len(), 1);
+        assert_eq!(out.schema().field(2).data_type(), &DataType::Date32);
+
+        let empty = SpssMetadata::default();
+        assert!(write_template(Vec::new(), &empty, &WriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_long_string_labels_and_missing() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("reason", DataType::Utf8, true),
+            Field::new("essay", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["too expensive", "REFUSED"])),
+                Arc::new(StringArray::from(vec!["e".repeat(300), "n/a".to_string()])),
+            ],
+        )
+        .unwrap();
+
+        let mut meta = SpssMetadata::default();
+        let mut labels = IndexMap::new();
+        labels.insert(Value::String("too expensive".into()), "Price".to_string());
+        meta.variable_value_labels
+            .insert("reason".to_string(), labels);
+        let mut labels = IndexMap::new();
+        labels.insert(Value::String("n/a".into()), "Not answered".to_string());
+        meta.variable_value_labels
+            .insert("essay".to_string(), labels);
+        meta.variable_missing.insert(
+            "reason".to_string(),
+            vec![MissingSpec::StringValue("REFUSED".into())],
+        );
+
+        let (_, out_meta) = roundtrip(&batch, &meta);
+
+        assert_eq!(out_meta.format("reason"), Some("A13"));
+        let reason = out_meta.value_labels("reason").unwrap();
+        assert_eq!(reason[&Value::String("too expensive".into())], "Price");
+        let essay = out_meta.value_labels("essay").unwrap();
+        assert_eq!(essay[&Value::String("n/a".into())], "Not answered");
+        assert!(matches!(
+            &out_meta.variable_missing["reason"][..],
+            [MissingSpec::StringValue(s)] if s == "REFUSED"
+        ));
+    }
+
+    #[test]
+    fn test_writes_attributes() {
+        let schema = Arc::new(Schema::new(vec![Field::new("q1", DataType::Float64, true)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Float64Array::from(vec![1.0]))]).unwrap();
+        let mut meta = SpssMetadata::default();
+        meta.file_attributes
+            .insert("Project".to_string(), vec!["Omnibus".to_string()]);
+        let mut attributes = IndexMap::new();
+        attributes.insert("Provenance".to_string(), vec!["harmonized".to_string()]);
+        meta.variable_attributes
+            .insert("q1".to_string(), attributes);
+        meta.variable_role.insert("q1".to_string(), Role::Target);
+        meta.date_info = vec![[1, 12, 0]];
+
+        let mut buf = Vec::new();
+        write_batch(&mut buf, &batch, &meta, &WriteOptions::default()).unwrap();
+        let contains = |text: &[u8]| buf.windows(text.len()).any(|w| w == text);
+        assert!(contains(b"Project('Omnibus'\n)"));
+        assert!(contains(b"q1:$@Role('1'\n)Provenance('harmonized'\n)"));
+        let (out, read) = crate::read_sav_from_reader(Cursor::new(buf.clone())).unwrap();
+        assert_eq!(out.num_rows(), 1);
+        assert_eq!(read.file_attributes, meta.file_attributes);
+        assert_eq!(read.variable_attributes, meta.variable_attributes);
+        assert_eq!(read.role("q1"), Some(Role::Target));
+        assert_eq!(read.date_info, meta.date_info);
+
+        meta.variable_attributes.insert(
+            "missing".to_string(),
+            meta.variable_attributes["q1"].clone(),
+        );
+        assert!(write_batch(Vec::new(), &batch, &meta, &WriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_duplicate_names() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("age", DataType::Float64, true),
+            Field::new("AGE", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.0])),
+                Arc::new(Float64Array::from(vec![2.0])),
+            ],
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        let options = WriteOptions::default();
+        assert!(write_batch(&mut buf, &batch, &SpssMetadata::default(), &options).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_bytecode() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("code", DataType::Int64, true),
+            Field::new("weight", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![
+                    Some(1),
+                    Some(-5),
+                    None,
+                    Some(100_000),
+                ])),
+                Arc::new(Float64Array::from(vec![0.5, -0.0, 151.0, 2.0])),
+                Arc::new(StringArray::from(vec!["", "a name of sixteen", "x", ""])),
+            ],
+        )
+        .unwrap();
+        let options = WriteOptions {
+            compression: Compression::Bytecode,
+            ..WriteOptions::default()
+        };
+
+        let mut compressed = Vec::new();
+        write_batch(&mut compressed, &batch, &SpssMetadata::default(), &options).unwrap();
+        let mut plain = Vec::new();
+        write_batch(
+            &mut plain,
+            &batch,
+            &SpssMetadata::default(),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        assert!(compressed.len() < plain.len());
+
+        let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(compressed)).unwrap();
+        assert_eq!(out_meta.compression, Compression::Bytecode);
+        let (expected, _) = crate::read_sav_from_reader(Cursor::new(plain)).unwrap();
+        assert_eq!(out.columns(), expected.columns());
+        let weights = out.column(1).as_primitive::<Float64Type>();
+        assert!(weights.value(1).is_sign_negative());
+    }
+
+    #[test]
+    fn test_roundtrip_zsav_multiple_blocks() {
+        let n = 1000;
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from_iter(
+                    (0..n).map(|i| (i % 7 != 0).then_some(i as f64 * 0.5)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..n).map(|i| format!("row {i}")),
+                )),
+            ],
+        )
+        .unwrap();
+        let options = WriteOptions {
+            compression: Compression::Zlib,
+            zlib_level: 9,
+            zlib_block_size: 1024,
+            ..WriteOptions::default()
+        };
+
+        let mut buf = Vec::new();
+        write_batch(&mut buf, &batch, &SpssMetadata::default(), &options).unwrap();
+        assert_eq!(&buf[..4], b"$FL3");
+        let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+
+        assert_eq!(out_meta.compression, Compression::Zlib);
+        assert_eq!(out.num_rows(), n);
+        let xs = out.column(0).as_primitive::<Float64Type>();
+        assert!(xs.is_null(0));
+        assert_eq!(xs.value(999), 499.5);
+        assert_eq!(out.column(1).as_string_view().value(123), "row 123");
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use arrow::datatypes::{Field, Schema};
+
+use crate::arrow_convert;
+use crate::compression::zlib::{self, ZHeader};
+use crate::constants::{Compression, VarType};
+use crate::dictionary;
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::io_utils::SavReader;
+use crate::metadata::SpssMetadata;
+use crate::writer::layout::{Observed, WriteLayout};
+use crate::writer::{BIAS, dictionary as dict_writer};
+
+/// Rewrite the dictionary of an existing file from `metadata`, copying its
+/// data section byte for byte.
+///
+/// The new dictionary must describe the same case layout as the old one:
+/// variables may be renamed, relabelled, reformatted and so on, but not
+/// added, removed, reordered or changed in type or string width. Text is
+/// written in the source file's encoding, so string data stays valid. Info
+/// records the writer does not produce are not carried over.
+pub(crate) fn rewrite_dictionary<R: Read + Seek, W: Write>(
+    input: R,
+    mut out: W,
+    metadata: &SpssMetadata,
+) -> Result<()> {
+    let mut reader = SavReader::new(input);
+    let header = FileHeader::parse(&mut reader)?;
+    if header.bswap {
+        return Err(SpssError::Unsupported(
+            "rewriting big-endian files".to_string(),
+        ));
+    }
+    let compression = header.compression;
+    if compression != Compression::None && header.bias != BIAS {
+        return Err(SpssError::Unsupported(format!(
+            "rewriting compressed files with bias {}",
+            header.bias
+        )));
+    }
+    let raw = dictionary::parse_dictionary(&mut reader, &header)?;
+    let raw_types: Vec<i32> = raw.variables.iter().map(|v| v.raw_type).collect();
+    let dict = dictionary::resolve_dictionary(raw)?;
+    if !dict.numbers.is_native() {
+        return Err(SpssError::Unsupported(format!(
+            "rewriting files with {:?} floating point or non-standard system-missing",
+            dict.numbers.float
+        )));
+    }
+    let ncases = dict.ncases;
+    let data_start = reader.inner_mut().stream_position()?;
+    if dict.file_encoding.output_encoding() != dict.file_encoding {
+        return Err(SpssError::Unsupported(format!(
+            "rewriting {} files",
+            dict.file_encoding.name()
+        )));
+    }
+
+    // The source layout, under the (possibly renamed) variables of `metadata`
+    if metadata.variable_names.len() != dict.variables.len() {
+        return Err(SpssError::InvalidVariable(format!(
+            "metadata has {} variables but the file has {}; variables cannot be added or removed",
+            metadata.variable_names.len(),
+            dict.variables.len()
+        )));
+    }
+    let fields: Vec<Field> = dict
+        .variables
+        .iter()
+        .zip(&metadata.variable_names)
+        .map(|(var, name)| Field::new(name, arrow_convert::var_to_arrow_type(var), true))
+        .collect();
+    let observed: Vec<Observed> = dict
+        .variables
+        .iter()
+        .map(|var| Observed {
+            max_len: match var.var_type {
+                VarType::String(w) => w,
+                VarType::Numeric => 0,
+            },
+            ..Observed::default()
+        })
+        .collect();
+    let layout = WriteLayout::new(
+        &Schema::new(fields),
+        metadata,
+        &observed,
+        dict.file_encoding,
+    )?;
+    if !layout.records.iter().map(|r| r.raw_type).eq(raw_types) {
+        return Err(SpssError::InvalidVariable(
+            "metadata changes the variable types or string widths of the file".to_string(),
+        ));
+    }
+
+    let mut dict_bytes = Vec::new();
+    dict_writer::write_dictionary(&mut dict_bytes, &layout, metadata, ncases, compression)?;
+    out.write_all(&dict_bytes)?;
+
+    let mut input = reader;
+    if compression == Compression::Zlib {
+        // Copy the zlib blocks as they are, moving every offset in the zlib
+        // header and trailer by the change in dictionary size.
+        let zheader = zlib::read_zheader(&mut input)?;
+        let trailer = zlib::read_ztrailer(&mut input, &zheader)?;
+        trailer.check_bias(dict.header.bias)?;
+        let shift = dict_bytes.len() as i64 - data_start as i64;
+
+        let mut buf = Vec::with_capacity(24 + trailer.serialized_len());
+        ZHeader {
+            zheader_offset: zheader.zheader_offset + shift,
+            ztrailer_offset: zheader.ztrailer_offset + shift,
+            ztrailer_length: zheader.ztrailer_length,
+        }
+        .write(&mut buf);
+        out.write_all(&buf)?;
+
+        let blocks_start = data_start + 24;
+        let blocks_len = (zheader.ztrailer_offset as u64)
+            .checked_sub(blocks_start)
+            .ok_or_else(|| SpssError::Zlib("zlib trailer precedes its blocks".to_string()))?;
+        input.inner_mut().seek(SeekFrom::Start(blocks_start))?;
+        io::copy(&mut input.inner_mut().take(blocks_len), &mut out)?;
+
+        let mut trailer = trailer;
+        for entry in &mut trailer.entries {
+            entry.uncompressed_offset += shift;
+            entry.compressed_offset += shift;
+        }
+        buf.clear();
+        trailer.write(&mut buf);
+        out.write_all(&buf)?;
+    } else {
+        io::copy(input.inner_mut(), &mut out)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::metadata::Value;
+    use crate::test_util;
+
+    #[test]
+    fn test_rewrite_relabels_without_touching_data() {
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let src = test_util::survey_sav((0..500).map(|i| (i % 3) as f64), compression, 512);
+            let (_, mut meta) = crate::read_sav_from_reader(Cursor::new(src.clone())).unwrap();
+            meta.variable_names[0] = "satisfaction".to_string();
+            meta.variable_labels.insert(
+                "satisfaction".to_string(),
+                "Overall satisfaction".to_string(),
+            );
+            let mut labels = IndexMap::new();
+            labels.insert(Value::Numeric(0.0), "Low".to_string());
+            labels.insert(Value::Numeric(2.0), "High".to_string());
+            meta.variable_value_labels
+                .insert("satisfaction".to_string(), labels);
+            meta.spss_variable_types
+                .insert("satisfaction".to_string(), "F4.1".to_string());
+
+            let mut out = Vec::new();
+            rewrite_dictionary(Cursor::new(src), &mut out, &meta).unwrap();
+            let (batch, out_meta) = crate::read_sav_from_reader(Cursor::new(out)).unwrap();
+
+            assert_eq!(out_meta.compression, compression);
+            assert_eq!(out_meta.variable_names, vec!["satisfaction", "city"]);
+            assert_eq!(out_meta.label("satisfaction"), Some("Overall satisfaction"));
+            assert_eq!(out_meta.format("satisfaction"), Some("F4.1"));
+            assert_eq!(out_meta.value_labels("satisfaction").unwrap().len(), 2);
+            assert_eq!(batch.num_rows(), 500);
+            let q1 = batch.column(0).as_primitive::<Float64Type>();
+            assert_eq!(q1.value(499), 1.0);
+            assert_eq!(
+                batch.column(1).as_string_view().value(321),
+                "city number 321"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rewrite_rejects_layout_change() {
+        let src = test_util::survey_sav((0..500).map(|i| (i % 3) as f64), Compression::None, 512);
+        let (_, mut meta) = crate::read_sav_from_reader(Cursor::new(src.clone())).unwrap();
+        meta.spss_variable_types
+            .insert("city".to_string(), "A200".to_string());
+        let mut out = Vec::new();
+        assert!(rewrite_dictionary(Cursor::new(src.clone()), &mut out, &meta).is_err());
+
+        meta.variable_names.pop();
+        assert!(rewrite_dictionary(Cursor::new(src), &mut out, &meta).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+
+use crate::compression::zlib::ZTrailer;
+use crate::constants::Compression;
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::metadata::SpssMetadata;
+use crate::writer::data::DataWriter;
+use crate::writer::layout::{Observed, WriteLayout};
+use crate::writer::{BIAS, WriteOptions, dictionary, zsav_header};
+
+/// A streaming writer for SPSS .sav/.zsav files.
+///
+/// Batches are encoded and written as they arrive, so files larger than
+/// memory can be produced from a streaming Arrow source. Every batch must
+/// have the writer's schema (the same column names and types, in order);
+/// a batch that does not is rejected before any of it is written.
+///
+/// The dictionary is written with the first batch, so that batch fixes the
+/// variable widths and value labels: string columns without a declared
+/// format width in the metadata are sized from it, and categorical columns
+/// get codes and labels for the categories it holds. Later values that do
+/// not fit, or categories without a code, are rejected. `finish()` patches
+/// the case count (and, for .zsav, the zlib header) into place, which is why
+/// the output must be seekable.
+///
+/// ```no_run
+/// use ambers::{SavWriter, scan_sav};
+///
+/// let mut scanner = scan_sav("big.sav").unwrap();
+/// let meta = scanner.metadata().clone();
+/// let mut writer = SavWriter::new("copy.sav", scanner.schema(), &meta).unwrap();
+/// while let Some(batch) = scanner.next_batch().unwrap() {
+///     writer.write_batch(&batch).unwrap();
+/// }
+/// writer.finish().unwrap();
+/// ```
+pub struct SavWriter<W: Write + Seek> {
+    out: W,
+    schema: SchemaRef,
+    metadata: SpssMetadata,
+    options: WriteOptions,
+    /// Stream position of the file header.
+    start: u64,
+    /// Stream position of the zlib header (.zsav only).
+    zheader_offset: u64,
+    /// Created when the dictionary is written.
+    data: Option<DataWriter>,
+}
+
+impl SavWriter<BufWriter<File>> {
+    /// Create an uncompressed .sav file at `path`.
+    pub fn new(
+        path: impl AsRef<Path>,
+        schema: impl Into<SchemaRef>,
+        metadata: &SpssMetadata,
+    ) -> Result<Self> {
+        SavWriter::with_options(path, schema, metadata, WriteOptions::default())
+    }
+
+    /// Create a file at `path` with explicit write options.
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        schema: impl Into<SchemaRef>,
+        metadata: &SpssMetadata,
+        options: WriteOptions,
+    ) -> Result<Self> {
+        let file = File::create(path)?;
+        let out = BufWriter::with_capacity(8 * 1024 * 1024, file);
+        SavWriter::from_writer(out, schema, metadata, options)
+    }
+}
+
+impl<W: Write + Seek> SavWriter<W> {
+    /// Write to any seekable output, starting at its current position.
+    pub fn from_writer(
+        mut out: W,
+        schema: impl Into<SchemaRef>,
+        metadata: &SpssMetadata,
+        options: WriteOptions,
+    ) -> Result<Self> {
+        options.validate()?;
+        let start = out.stream_position()?;
+        Ok(SavWriter {
+            out,
+            schema: schema.into(),
+            metadata: metadata.clone(),
+            options,
+            start,
+            zheader_offset: 0,
+            data: None,
+        })
+    }
+
+    /// Number of cases written so far.
+    pub fn rows_written(&self) -> usize {
+        self.data.as_ref().map_or(0, DataWriter::rows_written)
+    }
+
+    /// Append the cases of `batch`. Its column names and types must match
+    /// the writer's schema.
+    pub fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.check_schema(batch)?;
+        if self.data.is_none() {
+            let layout =
+                WriteLayout::from_batch(batch, &self.metadata, self.options.output_encoding()?)?;
+            self.start_data(layout)?;
+        }
+        let data = self.data.as_mut().expect("dictionary written");
+        data.write_batch(&mut self.out, batch)
+    }
+
+    /// Write the end of the data section, patch the header, and flush.
+    /// Returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        if self.data.is_none() {
+            let observed = vec![Observed::default(); self.schema.fields().len()];
+            let layout = WriteLayout::new(
+                &self.schema,
+                &self.metadata,
+                &observed,
+                self.options.output_encoding()?,
+            )?;
+            self.start_data(layout)?;
+        }
+        let data = self.data.take().expect("dictionary written");
+        let ncases = data.rows_written();
+        let block_sizes = data.finish(&mut self.out)?;
+
+        if self.options.compression == Compression::Zlib {
+            let zheader_offset = (self.zheader_offset - self.start) as i64;
+            let trailer = ZTrailer::for_blocks(
+                BIAS,
+                zheader_offset,
+                self.options.zlib_block_size,
+                &block_sizes,
+            );
+            let mut buf = Vec::with_capacity(trailer.serialized_len());
+            trailer.write(&mut buf);
+            self.out.write_all(&buf)?;
+
+            buf.clear();
+            zsav_header(zheader_offset, &trailer).write(&mut buf);
+            self.out.seek(SeekFrom::Start(self.zheader_offset))?;
+            self.out.write_all(&buf)?;
+        }
+
+        let ncases = i32::try_from(ncases).unwrap_or(-1);
+        self.out
+            .seek(SeekFrom::Start(self.start + FileHeader::NCASES_OFFSET))?;
+        self.out.write_all(&ncases.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Reject a batch whose columns differ from the writer's schema.
+    fn check_schema(&self, batch: &RecordBatch) -> Result<()> {
+        let expected = self.schema.fields();
+        if batch.num_columns() != expected.len() {
+            return Err(SpssError::InvalidVariable(format!(
+                "batch has {} columns, the writer's schema has {}",
+                batch.num_columns(),
+                expected.len()
+            )));
+        }
+        let schema = batch.schema();
+        for (i, (got, want)) in schema.fields().iter().zip(expected).enumerate() {
+            if got.name() != want.name() || got.data_type() != want.data_type() {
+                return Err(SpssError::InvalidVariable(format!(
+                    "batch column {i} is {:?} ({}), the writer's schema has {:?} ({})",
+                    got.name(),
+                    got.data_type(),
+                    want.name(),
+                    want.data_type()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the header and dictionary (with an unknown case count) and, for
+    /// .zsav, a placeholder zlib header.
+    fn start_data(&mut self, layout: WriteLayout) -> Result<()> {
+        let mut dict = Vec::new();
+        dictionary::write_dictionary(
+            &mut dict,
+            &layout,
+            &self.metadata,
+            None,
+            self.options.compression,
+        )?;
+        self.out.write_all(&dict)?;
+        if self.options.compression == Compression::Zlib {
+            self.zheader_offset = self.start + dict.len() as u64;
+            self.out.write_all(&[0u8; 24])?;
+        }
+        self.data = Some(DataWriter::new(layout, &self.options));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, AsArray, Float64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Float64Type, Schema};
+
+    use super::*;
+
+    fn batch(schema: &SchemaRef, start: usize, n: usize) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float64Array::from_iter_values(
+                    (start..start + n).map(|i| i as f64),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (start..start + n).map(|i| format!("case {i}")),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_streaming_patches_case_count() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let mut meta = SpssMetadata::default();
+        meta.spss_variable_types
+            .insert("name".to_string(), "A12".to_string());
+
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let options = WriteOptions {
+                compression,
+                zlib_block_size: 256,
+                ..WriteOptions::default()
+            };
+            let mut writer =
+                SavWriter::from_writer(Cursor::new(Vec::new()), schema.clone(), &meta, options)
+                    .unwrap();
+            writer.write_batch(&batch(&schema, 0, 40)).unwrap();
+            writer.write_batch(&batch(&schema, 40, 60)).unwrap();
+            assert_eq!(writer.rows_written(), 100);
+            let buf = writer.finish().unwrap().into_inner();
+
+            let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+            assert_eq!(out_meta.number_rows, Some(100));
+            assert_eq!(out_meta.compression, compression);
+            assert_eq!(out.num_rows(), 100);
+            assert_eq!(out.column(0).as_primitive::<Float64Type>().value(99), 99.0);
+            assert_eq!(out.column(1).as_string_view().value(57), "case 57");
+        }
+    }
+
+    #[test]
+    fn test_streaming_rejects_overlong_string() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let mut writer = SavWriter::from_writer(
+            Cursor::new(Vec::new()),
+            schema.clone(),
+            &SpssMetadata::default(),
+            WriteOptions::default(),
+        )
+        .unwrap();
+        // First batch sizes "name" to 6 bytes ("case 0".."case 9")
+        writer.write_batch(&batch(&schema, 0, 10)).unwrap();
+        assert!(writer.write_batch(&batch(&schema, 10, 1)).is_err());
+    }
+
+    #[test]
+    fn test_streaming_failed_chunk_keeps_count() {
+        // A wide column makes chunks of a few hundred cases
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("wide", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let mut meta = SpssMetadata::default();
+        meta.spss_variable_types
+            .insert("wide".to_string(), "A8000".to_string());
+        meta.spss_variable_types
+            .insert("name".to_string(), "A4".to_string());
+        let batch = |names: Vec<&str>| {
+            let wide = StringArray::from_iter_values(names.iter().map(|_| "w"));
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(wide), Arc::new(StringArray::from(names))],
+            )
+            .unwrap()
+        };
+
+        for compression in [Compression::None, Compression::Bytecode, Compression::Zlib] {
+            let options = WriteOptions {
+                compression,
+                ..WriteOptions::default()
+            };
+            let mut writer =
+                SavWriter::from_writer(Cursor::new(Vec::new()), schema.clone(), &meta, options)
+                    .unwrap();
+            writer.write_batch(&batch(vec!["ok"; 10])).unwrap();
+            // Only the last case is too wide, so the chunks before it go out
+            let mut names = vec!["fine"; 2000];
+            names[1999] = "too wide";
+            assert!(writer.write_batch(&batch(names)).is_err());
+            let rows = writer.rows_written();
+            assert!(rows > 10 && rows < 2010, "{rows}");
+
+            let buf = writer.finish().unwrap().into_inner();
+            let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+            assert_eq!(out_meta.number_rows, Some(rows as i64));
+            assert_eq!(out.num_rows(), rows);
+            assert!(out_meta.warnings.is_empty(), "{:?}", out_meta.warnings);
+        }
+    }
+
+    #[test]
+    fn test_streaming_rejects_mismatched_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let renamed = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Float64, true),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let retyped = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Utf8, true),
+                Field::new("name", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["10"])),
+                Arc::new(StringArray::from(vec!["case 10"])),
+            ],
+        )
+        .unwrap();
+        let mismatched = [
+            batch(&renamed, 10, 5),
+            batch(&schema, 10, 5).project(&[0]).unwrap(),
+            retyped,
+        ];
+
+        for second in &mismatched {
+            for compression in [Compression::None, Compression::Zlib] {
+                let options = WriteOptions {
+                    compression,
+                    ..WriteOptions::default()
+                };
+                let mut writer = SavWriter::from_writer(
+                    Cursor::new(Vec::new()),
+                    schema.clone(),
+                    &SpssMetadata::default(),
+                    options,
+                )
+                .unwrap();
+                writer.write_batch(&batch(&schema, 0, 10)).unwrap();
+                let written = writer.out.get_ref().len();
+                let err = writer.write_batch(second).unwrap_err();
+                assert!(matches!(err, SpssError::InvalidVariable(_)), "{err}");
+                assert_eq!(writer.out.get_ref().len(), written);
+                assert_eq!(writer.rows_written(), 10);
+
+                // The file written so far is still complete
+                let buf = writer.finish().unwrap().into_inner();
+                let (out, _) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+                assert_eq!(out.num_rows(), 10);
+            }
+        }
+
+        // A mismatched first batch writes nothing at all
+        let mut writer = SavWriter::from_writer(
+            Cursor::new(Vec::new()),
+            schema.clone(),
+            &SpssMetadata::default(),
+            WriteOptions::default(),
+        )
+        .unwrap();
+        assert!(writer.write_batch(&mismatched[0]).is_err());
+        assert!(writer.out.get_ref().is_empty());
+    }
+
+    #[test]
+    fn test_streaming_empty() {
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Float64, true)]));
+        let writer = SavWriter::from_writer(
+            Cursor::new(Vec::new()),
+            schema,
+            &SpssMetadata::default(),
+            WriteOptions::default(),
+        )
+        .unwrap();
+        let buf = writer.finish().unwrap().into_inner();
+        let (out, out_meta) = crate::read_sav_from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(out.num_rows(), 0);
+        assert_eq!(out_meta.variable_names, vec!["x"]);
+        assert!(out.column(0).is_empty());
+    }
+}
@@ -0,0 +1,831 @@
+//! Reader for SAS transport (.xpt) files, XPORT versions 5 and 8.
+//!
+//! Like the Stata reader, the transport dictionary (the NAMESTR records) is
+//! translated into SAV variable records so metadata comes out as an
+//! `SpssMetadata` and data flows through the shared `ColumnarBatchBuilder`.
+//! Numbers are stored as big-endian IBM System/370 floats, which are
+//! converted to IEEE doubles; SAS missing values (`.`, `._`, `.A`–`.Z`)
+//! become null. SAS date, datetime and time formats become Date32,
+//! Timestamp and Duration columns. Only the first member of a
+//! multi-member library is read.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use arrow::datatypes::{Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+use crate::arrow_convert;
+use crate::columnar::{self, ColumnarBatchBuilder};
+use crate::constants::{
+    Alignment, Compression, EPOCH_1960_OFFSET_DAYS, FormatType, Measure, SECONDS_PER_DAY,
+    SYSMIS_BITS, SpssFormat, VarType,
+};
+use crate::dictionary::{self, RawDictionary, ResolvedDictionary};
+use crate::error::{Result, SpssError};
+use crate::header::FileHeader;
+use crate::metadata::SpssMetadata;
+use crate::variable::{MissingValues, VariableRecord};
+
+/// Length of every transport record.
+const RECORD_LEN: usize = 80;
+
+/// The fixed prefix of every header record.
+const HEADER_PREFIX: &[u8] = b"HEADER RECORD*******";
+
+/// SAS date/time encodings that are converted to SPSS temporal values.
+#[derive(Debug, Clone, Copy)]
+enum SasTime {
+    /// Days since 1960-01-01.
+    Date,
+    /// Seconds since 1960-01-01 00:00:00.
+    DateTime,
+    /// Seconds since midnight.
+    Time,
+}
+
+/// Where a variable lives in a transport row and how to convert it.
+struct XptColumn {
+    numeric: bool,
+    /// Byte offset and length within a row.
+    offset: usize,
+    len: usize,
+    /// First slot of the converted SAV-style case.
+    slot_index: usize,
+    time: Option<SasTime>,
+}
+
+/// One NAMESTR record.
+struct Namestr {
+    numeric: bool,
+    len: usize,
+    varnum: usize,
+    name: String,
+    label: String,
+    format: String,
+    format_width: usize,
+    format_decimals: usize,
+    justify: i16,
+    position: usize,
+}
+
+/// A streaming reader for SAS transport files.
+///
+/// Mirrors `SavScanner`: the dictionary is read on construction and data
+/// on demand via `next_batch()` or `collect_single()`, with column
+/// projection and row limits. Transport files are small by design (they
+/// are uncompressed and capped at 8-byte names in version 5), so the data
+/// section is held in memory.
+pub struct XptScanner {
+    dict: ResolvedDictionary,
+    columns: Vec<XptColumn>,
+    data: Vec<u8>,
+    row_len: usize,
+    slots_per_row: usize,
+    nobs: usize,
+    batch_size: usize,
+    projection: Option<Vec<usize>>,
+    row_limit: Option<usize>,
+    rows_read: usize,
+}
+
+impl XptScanner {
+    /// Open a scanner from a reader. Reads the whole library immediately.
+    pub fn open<R: Read>(mut reader: R, batch_size: usize) -> Result<Self> {
+        let mut file = Vec::new();
+        reader.read_to_end(&mut file)?;
+        let mut records = Records {
+            file: &file,
+            pos: 0,
+        };
+
+        let library = records.header()?;
+        let v8 = match library.kind.as_str() {
+            "LIBRARY" => false,
+            "LIBV8" => true,
+            _ => {
+                return Err(SpssError::InvalidXpt(
+                    "missing library header record".to_string(),
+                ));
+            }
+        };
+        let first = records.next()?;
+        if &first[..8] != b"SAS     " {
+            return Err(SpssError::InvalidXpt(
+                "missing SAS library record".to_string(),
+            ));
+        }
+        records.next()?; // modification time
+
+        let member = records.expect_header(if v8 { "MEMBV8" } else { "MEMBER" })?;
+        let namestr_len = member.nums[5];
+        if namestr_len != 140 && namestr_len != 136 {
+            return Err(SpssError::InvalidXpt(format!(
+                "unsupported NAMESTR length {namestr_len}"
+            )));
+        }
+        records.expect_header(if v8 { "DSCPTV8" } else { "DSCRPTR" })?;
+        let descriptor = records.next()?;
+        let created = text(&descriptor[64..80]);
+        let descriptor2 = records.next()?;
+        let data_label = text(&descriptor2[32..72]);
+
+        let nvar = records
+            .expect_header(if v8 { "NAMSTV8" } else { "NAMESTR" })?
+            .nums[1];
+        let namestr_bytes = records.take_padded(nvar * namestr_len)?;
+        let mut vars: Vec<Namestr> = namestr_bytes
+            .chunks_exact(namestr_len)
+            .map(|raw| parse_namestr(raw, v8))
+            .collect();
+
+        // Version 8 keeps names over 8 and labels over 40 characters in
+        // optional LABELV8/LABELV9 records
+        let mut next = records.header()?;
+        if next.kind == "LABELV8" || next.kind == "LABELV9" {
+            read_long_labels(
+                &mut records,
+                &mut vars,
+                next.nums[0],
+                next.kind == "LABELV9",
+            )?;
+            next = records.header()?;
+        }
+        if next.kind != "OBS" && next.kind != "OBSV8" {
+            return Err(SpssError::InvalidXpt(format!(
+                "expected OBS header record, found {}",
+                next.kind
+            )));
+        }
+
+        // The observations run to the next member or the end of the file
+        let rest = &file[records.pos..];
+        let data_len = (0..rest.len())
+            .step_by(RECORD_LEN)
+            .find(|&at| {
+                rest[at..].starts_with(HEADER_PREFIX)
+                    && rest[at + HEADER_PREFIX.len()..].starts_with(b"MEMB")
+            })
+            .unwrap_or(rest.len());
+        let data = rest[..data_len].to_vec();
+
+        // Lay the variables out as SAV-style slots
+        let mut columns = Vec::with_capacity(vars.len());
+        let mut variables = Vec::with_capacity(vars.len());
+        let mut slot_index = 0;
+        for var in &vars {
+            let time = if var.numeric {
+                sas_time(&var.format)
+            } else {
+                None
+            };
+            variables.push(variable_record(var, time, slot_index));
+            columns.push(XptColumn {
+                numeric: var.numeric,
+                offset: var.position,
+                len: var.len,
+                slot_index,
+                time,
+            });
+            slot_index += if var.numeric {
+                1
+            } else {
+                columnar::string_slots(var.len)
+            };
+        }
+        let row_len = vars.iter().map(|v| v.position + v.len).max().unwrap_or(0);
+        let nobs = count_rows(&data, row_len);
+
+        let raw = RawDictionary {
+            header: FileHeader {
+                magic: *b"$FL2",
+                product: "SAS transport".to_string(),
+                layout_code: 2,
+                nominal_case_size: slot_index as i32,
+                compression: Compression::None,
+                weight_index: 0,
+                ncases: i32::try_from(nobs).unwrap_or(-1),
+                bias: 100.0,
+                creation_date: created,
+                creation_time: String::new(),
+                file_label: data_label,
+                bswap: false,
+            },
+            variables,
+            value_label_sets: Vec::new(),
+            document_lines: Vec::new(),
+            integer_info: None,
+            float_info: None,
+            var_display: Vec::new(),
+            long_names: Vec::new(),
+            very_long_strings: Vec::new(),
+            encoding_name: Some("windows-1252".to_string()),
+            long_string_labels: Vec::new(),
+            long_string_missing: Vec::new(),
+            mr_sets: Vec::new(),
+            file_attributes: Vec::new(),
+            variable_attributes: Vec::new(),
+            date_info: Vec::new(),
+            extended_ncases: None,
+            unknown_records: Vec::new(),
+        };
+        let mut dict = dictionary::resolve_dictionary(raw)?;
+        dict.metadata.number_rows = Some(nobs as i64);
+        dict.ncases = Some(nobs);
+        dict.metadata.file_format = "xpt".to_string();
+
+        Ok(XptScanner {
+            dict,
+            columns,
+            data,
+            row_len,
+            slots_per_row: slot_index,
+            nobs,
+            batch_size,
+            projection: None,
+            row_limit: None,
+            rows_read: 0,
+        })
+    }
+
+    /// Get a reference to the file metadata.
+    pub fn metadata(&self) -> &SpssMetadata {
+        &self.dict.metadata
+    }
+
+    /// Get the Arrow schema (respects column projection if set).
+    pub fn schema(&self) -> Schema {
+        match &self.projection {
+            Some(proj) => Schema::new(
+                proj.iter()
+                    .map(|&idx| {
+                        let var = &self.dict.variables[idx];
+                        Field::new(&var.long_name, arrow_convert::var_to_arrow_type(var), true)
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            None => arrow_convert::build_schema(&self.dict),
+        }
+    }
+
+    /// Set column projection — only these columns will be read and returned.
+    /// Returns an error if any column name is not found.
+    pub fn select(&mut self, columns: &[&str]) -> Result<()> {
+        let indices = columns
+            .iter()
+            .map(|&col| {
+                self.dict
+                    .variables
+                    .iter()
+                    .position(|v| v.long_name == col)
+                    .ok_or_else(|| SpssError::InvalidVariable(format!("column not found: {col:?}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.projection = Some(indices);
+        Ok(())
+    }
+
+    /// Set a row limit — stop reading after this many rows.
+    pub fn limit(&mut self, n: usize) {
+        self.row_limit = Some(n);
+    }
+
+    /// Read the next batch of rows, returning a RecordBatch.
+    /// Returns Ok(None) when no more data is available.
+    pub fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let n = self.remaining().min(self.batch_size);
+        if n == 0 {
+            return Ok(None);
+        }
+        self.read_batch(n).map(Some)
+    }
+
+    /// Read all remaining data as a single RecordBatch.
+    pub fn collect_single(&mut self) -> Result<RecordBatch> {
+        self.read_batch(self.remaining())
+    }
+
+    /// Read all remaining data as a Vec of RecordBatches.
+    pub fn collect_all(&mut self) -> Result<Vec<RecordBatch>> {
+        let mut batches = Vec::new();
+        while let Some(batch) = self.next_batch()? {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// How many rows have been read so far.
+    pub fn rows_read(&self) -> usize {
+        self.rows_read
+    }
+
+    /// Rows left to read, honouring the row limit.
+    fn remaining(&self) -> usize {
+        let end = self
+            .row_limit
+            .map_or(self.nobs, |limit| limit.min(self.nobs));
+        end.saturating_sub(self.rows_read)
+    }
+
+    /// Read `n` rows (all available) into a RecordBatch.
+    fn read_batch(&mut self, n: usize) -> Result<RecordBatch> {
+        if n == 0 {
+            return Ok(RecordBatch::new_empty(Arc::new(self.schema())));
+        }
+        let mut builder = ColumnarBatchBuilder::new(&self.dict, self.projection.as_deref(), n);
+        let columns: Vec<&XptColumn> = match &self.projection {
+            Some(proj) => proj.iter().map(|&i| &self.columns[i]).collect(),
+            None => self.columns.iter().collect(),
+        };
+
+        let case_bytes = self.slots_per_row * 8;
+        let start = self.rows_read * self.row_len;
+        let rows = &self.data[start..start + n * self.row_len];
+        let mut cases = vec![0u8; n * case_bytes];
+        for (row, case) in rows
+            .chunks_exact(self.row_len)
+            .zip(cases.chunks_exact_mut(case_bytes))
+        {
+            for col in &columns {
+                convert(row, case, col);
+            }
+        }
+        builder.push_raw_chunk(&cases, n, self.slots_per_row);
+        self.rows_read += n;
+        builder.finish()
+    }
+}
+
+/// Copy one variable of a transport row into its slots of a SAV-style case.
+fn convert(row: &[u8], case: &mut [u8], col: &XptColumn) {
+    let field = &row[col.offset..col.offset + col.len];
+    let dest = &mut case[col.slot_index * 8..];
+    if !col.numeric {
+        columnar::write_string_slots(dest, field, col.len);
+        return;
+    }
+    let value = ibm_to_ieee(field).map(|v| match col.time {
+        Some(SasTime::Date) => (v + EPOCH_1960_OFFSET_DAYS as f64) * SECONDS_PER_DAY,
+        Some(SasTime::DateTime) => v + EPOCH_1960_OFFSET_DAYS as f64 * SECONDS_PER_DAY,
+        Some(SasTime::Time) | None => v,
+    });
+    let bits = value.map_or(SYSMIS_BITS, f64::to_bits);
+    dest[..8].copy_from_slice(&bits.to_le_bytes());
+}
+
+/// Convert a big-endian IBM System/370 hexadecimal float (truncated to
+/// 2–8 bytes) to an IEEE double. Returns None for SAS missing values.
+///
+/// IBM floats are `(-1)^sign * 0.mantissa * 16^(exponent - 64)` with a
+/// 7-bit exponent and a 56-bit mantissa.
+pub(crate) fn ibm_to_ieee(bytes: &[u8]) -> Option<f64> {
+    let mut raw = [0u8; 8];
+    let n = bytes.len().min(8);
+    raw[..n].copy_from_slice(&bytes[..n]);
+
+    // Missing values: '.', '_' or 'A'-'Z' followed by zeros
+    if raw[1..].iter().all(|&b| b == 0) && matches!(raw[0], b'.' | b'_' | b'A'..=b'Z') {
+        return None;
+    }
+
+    let mantissa = u64::from_be_bytes(raw) & 0x00FF_FFFF_FFFF_FFFF;
+    if mantissa == 0 {
+        return Some(0.0);
+    }
+    let exponent = (raw[0] & 0x7F) as i32 - 64;
+    let magnitude = mantissa as f64 * 2f64.powi(4 * exponent - 56);
+    Some(if raw[0] & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
+/// Number of observations in the data section. The last 80-byte record is
+/// padded with spaces, so trailing all-blank rows that fit inside that
+/// padding are not real observations.
+fn count_rows(data: &[u8], row_len: usize) -> usize {
+    if row_len == 0 {
+        return 0;
+    }
+    let mut rows = data.len() / row_len;
+    while rows > 0 && data.len() - (rows - 1) * row_len < RECORD_LEN {
+        let last = &data[(rows - 1) * row_len..rows * row_len];
+        if !last.iter().all(|&b| b == b' ') {
+            break;
+        }
+        rows -= 1;
+    }
+    rows
+}
+
+/// The temporal kind of a SAS format name, if any.
+fn sas_time(format: &str) -> Option<SasTime> {
+    let format = format.to_ascii_uppercase();
+    const DATE_FORMATS: &[&str] = &[
+        "DATE", "DAY", "DDMMYY", "MMDDYY", "YYMMDD", "JULIAN", "MONYY", "MONTH", "QTR", "WEEKDATE",
+        "WORDDATE", "YEAR", "YYMM", "YYQ", "E8601DA", "B8601DA", "NLDATE",
+    ];
+    const TIME_FORMATS: &[&str] = &["TIME", "HHMM", "HOUR", "MMSS", "TOD", "E8601TM", "B8601TM"];
+    if format.starts_with("DATETIME")
+        || format.starts_with("E8601DT")
+        || format.starts_with("B8601DT")
+        || format.starts_with("DTDATE")
+    {
+        Some(SasTime::DateTime)
+    } else if TIME_FORMATS.iter().any(|f| format.starts_with(f)) {
+        Some(SasTime::Time)
+    } else if DATE_FORMATS.iter().any(|f| format.starts_with(f)) {
+        Some(SasTime::Date)
+    } else {
+        None
+    }
+}
+
+/// Build the variable record a SAV dictionary would hold for this variable.
+fn variable_record(var: &Namestr, time: Option<SasTime>, slot_index: usize) -> VariableRecord {
+    let format = if !var.numeric {
+        SpssFormat {
+            format_type: FormatType::A,
+            width: var.len.min(255) as u8,
+            decimals: 0,
+        }
+    } else {
+        let (format_type, width, decimals) = match time {
+            Some(SasTime::Date) => (FormatType::Date, 11, 0),
+            Some(SasTime::DateTime) => (FormatType::DateTime, 20, 0),
+            Some(SasTime::Time) => (FormatType::Time, 8, 0),
+            None => {
+                let decimals = var.format_decimals.min(16) as u8;
+                let width = match var.format_width {
+                    0 => 8,
+                    w => w.min(40) as u8,
+                };
+                let format_type = if var.format.eq_ignore_ascii_case("COMMA") {
+                    FormatType::Comma
+                } else if var.format.eq_ignore_ascii_case("DOLLAR") {
+                    FormatType::Dollar
+                } else {
+                    FormatType::F
+                };
+                (format_type, width.max(decimals + 2), decimals)
+            }
+        };
+        SpssFormat {
+            format_type,
+            width,
+            decimals,
+        }
+    };
+    VariableRecord {
+        slot_index,
+        raw_type: if var.numeric {
+            0
+        } else {
+            var.len.min(255) as i32
+        },
+        short_name: var.name.clone(),
+        long_name: var.name.clone(),
+        label: (!var.label.is_empty()).then(|| var.label.as_bytes().to_vec()),
+        display_width: format.width as u32,
+        print_format: Some(format.clone()),
+        write_format: Some(format),
+        unknown_format: None,
+        missing_values: MissingValues::None,
+        var_type: if var.numeric {
+            VarType::Numeric
+        } else {
+            VarType::String(var.len)
+        },
+        is_ghost: false,
+        measure: Measure::Unknown,
+        alignment: match var.justify {
+            0 => Alignment::Left,
+            _ => Alignment::Right,
+        },
+        n_segments: if var.len > 255 && !var.numeric {
+            var.len.div_ceil(255)
+        } else {
+            1
+        },
+    }
+}
+
+fn be_i16(bytes: &[u8]) -> i16 {
+    i16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// Decode a blank-padded text field.
+fn text(bytes: &[u8]) -> String {
+    let decoded = crate::encoding::decode_str_lossy(bytes, encoding_rs::WINDOWS_1252);
+    decoded
+        .trim_end_matches([' ', '\0'])
+        .trim_start()
+        .to_string()
+}
+
+fn parse_namestr(raw: &[u8], v8: bool) -> Namestr {
+    let short_name = text(&raw[8..16]);
+    let long_name = if v8 && raw.len() >= 122 {
+        text(&raw[88..120])
+    } else {
+        String::new()
+    };
+    Namestr {
+        numeric: be_i16(&raw[0..2]) == 1,
+        len: be_i16(&raw[4..6]).max(0) as usize,
+        varnum: be_i16(&raw[6..8]).max(0) as usize,
+        name: if long_name.is_empty() {
+            short_name
+        } else {
+            long_name
+        },
+        label: text(&raw[16..56]),
+        format: text(&raw[56..64]),
+        format_width: be_i16(&raw[64..66]).max(0) as usize,
+        format_decimals: be_i16(&raw[66..68]).max(0) as usize,
+        justify: be_i16(&raw[68..70]),
+        position: i32::from_be_bytes([raw[84], raw[85], raw[86], raw[87]]).max(0) as usize,
+    }
+}
+
+/// Apply LABELV8 (name and label) or LABELV9 (also formats) records.
+fn read_long_labels(
+    records: &mut Records,
+    vars: &mut [Namestr],
+    count: usize,
+    with_formats: bool,
+) -> Result<()> {
+    let start = records.pos;
+    let mut pos = start;
+    let truncated = || SpssError::InvalidXpt("truncated long label record".to_string());
+    for _ in 0..count {
+        let field = |at: usize| -> Result<usize> {
+            records
+                .file
+                .get(at..at + 2)
+                .map(|b| be_i16(b).max(0) as usize)
+                .ok_or_else(truncated)
+        };
+        let varnum = field(pos)?;
+        let name_len = field(pos + 2)?;
+        let label_len = field(pos + 4)?;
+        let (format_len, informat_len) = if with_formats {
+            (field(pos + 6)?, field(pos + 8)?)
+        } else {
+            (0, 0)
+        };
+        pos += if with_formats { 10 } else { 6 };
+        let total = name_len + label_len + format_len + informat_len;
+        let body = records.file.get(pos..pos + total).ok_or_else(truncated)?;
+        pos += total;
+
+        if let Some(var) = vars.iter_mut().find(|v| v.varnum == varnum) {
+            var.name = text(&body[..name_len]);
+            var.label = text(&body[name_len..name_len + label_len]);
+            if with_formats && format_len > 0 {
+                let format = text(&body[name_len + label_len..name_len + label_len + format_len]);
+                let name_end = format
+                    .find(|c: char| c.is_ascii_digit() || c == '.')
+                    .unwrap_or(format.len());
+                var.format = format[..name_end].to_string();
+            }
+        }
+    }
+    records.pos = start + (pos - start).div_ceil(RECORD_LEN) * RECORD_LEN;
+    Ok(())
+}
+
+/// A parsed header record: its kind (e.g. "MEMBER", "OBSV8") and the six
+/// five-digit numbers that follow it.
+struct HeaderRecord {
+    kind: String,
+    nums: [usize; 6],
+}
+
+/// Cursor over the 80-byte records of a transport file.
+struct Records<'a> {
+    file: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Records<'a> {
+    fn next(&mut self) -> Result<&'a [u8]> {
+        let record = self
+            .file
+            .get(self.pos..self.pos + RECORD_LEN)
+            .ok_or_else(|| SpssError::InvalidXpt("unexpected end of file".to_string()))?;
+        self.pos += RECORD_LEN;
+        Ok(record)
+    }
+
+    /// Take `len` bytes, skipping the padding to the next record boundary.
+    fn take_padded(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .file
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| SpssError::InvalidXpt("unexpected end of file".to_string()))?;
+        self.pos += len.div_ceil(RECORD_LEN) * RECORD_LEN;
+        Ok(bytes)
+    }
+
+    fn header(&mut self) -> Result<HeaderRecord> {
+        let record = self.next()?;
+        if !record.starts_with(HEADER_PREFIX) || &record[28..48] != b"HEADER RECORD!!!!!!!" {
+            return Err(SpssError::InvalidXpt(
+                "expected a header record".to_string(),
+            ));
+        }
+        let mut nums = [0; 6];
+        for (i, num) in nums.iter_mut().enumerate() {
+            let digits = &record[48 + i * 5..53 + i * 5];
+            *num = std::str::from_utf8(digits)
+                .ok()
+                .and_then(|d| d.trim().parse().ok())
+                .unwrap_or(0);
+        }
+        Ok(HeaderRecord {
+            kind: String::from_utf8_lossy(&record[20..28])
+                .trim_end()
+                .to_string(),
+            nums,
+        })
+    }
+
+    fn expect_header(&mut self, kind: &str) -> Result<HeaderRecord> {
+        let header = self.header()?;
+        if header.kind != kind {
+            return Err(SpssError::InvalidXpt(format!(
+                "expected {kind} header record, found {}",
+                header.kind
+            )));
+        }
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{DataType, Date32Type, Float64Type};
+
+    use super::*;
+
+    fn ieee_to_ibm(value: f64) -> [u8; 8] {
+        if value == 0.0 {
+            return [0; 8];
+        }
+        let sign = if value < 0.0 { 0x80u8 } else { 0 };
+        let mut v = value.abs();
+        let mut exponent = 64i32;
+        while v >= 1.0 {
+            v /= 16.0;
+            exponent += 1;
+        }
+        while v < 1.0 / 16.0 {
+            v *= 16.0;
+            exponent -= 1;
+        }
+        let mantissa = (v * 2f64.powi(56)) as u64;
+        let mut out = mantissa.to_be_bytes();
+        out[0] = sign | exponent as u8;
+        out
+    }
+
+    fn header(kind: &str, nums: &str) -> Vec<u8> {
+        format!("HEADER RECORD*******{kind:<8}HEADER RECORD!!!!!!!{nums:<30}  ").into_bytes()
+    }
+
+    fn record(text: &str) -> Vec<u8> {
+        format!("{text:<80}").into_bytes()
+    }
+
+    fn namestr(
+        numeric: bool,
+        len: i16,
+        varnum: i16,
+        name: &str,
+        label: &str,
+        format: &str,
+        pos: i32,
+    ) -> Vec<u8> {
+        let mut n = Vec::with_capacity(140);
+        n.extend_from_slice(&(if numeric { 1i16 } else { 2 }).to_be_bytes());
+        n.extend_from_slice(&0i16.to_be_bytes());
+        n.extend_from_slice(&len.to_be_bytes());
+        n.extend_from_slice(&varnum.to_be_bytes());
+        n.extend_from_slice(format!("{name:<8}").as_bytes());
+        n.extend_from_slice(format!("{label:<40}").as_bytes());
+        n.extend_from_slice(format!("{format:<8}").as_bytes());
+        n.extend_from_slice(&[0, 8, 0, 2, 0, 1, 0, 0]);
+        n.extend_from_slice(&[b' '; 8]);
+        n.extend_from_slice(&[0; 4]);
+        n.extend_from_slice(&pos.to_be_bytes());
+        n.resize(140, 0);
+        n
+    }
+
+    /// A version 5 file with columns id (numeric), city ($6) and visit (DATE9).
+    fn xpt_v5() -> Vec<u8> {
+        let mut out = header("LIBRARY", "000000000000000000000000000000");
+        out.extend(record(
+            "SAS     SAS     SASLIB  9.4     X64_10PR                        01JAN24:10:00:00",
+        ));
+        out.extend(record("01JAN24:10:00:00"));
+        out.extend(header("MEMBER", "000000000000000001600000000140"));
+        out.extend(header("DSCRPTR", "000000000000000000000000000000"));
+        out.extend(record(
+            "SAS     VISITS  SASDATA 9.4     X64_10PR                        01JAN24:10:00:00",
+        ));
+        out.extend(record(
+            "01JAN24:10:00:00                Clinic visits                           DATA",
+        ));
+        out.extend(header("NAMESTR", "000000000300000000000000000000"));
+        let mut namestrs = Vec::new();
+        namestrs.extend(namestr(true, 8, 1, "ID", "Subject ID", "", 0));
+        namestrs.extend(namestr(false, 6, 2, "CITY", "", "$", 8));
+        namestrs.extend(namestr(true, 8, 3, "VISIT", "Visit date", "DATE", 14));
+        namestrs.resize(namestrs.len().div_ceil(80) * 80, b' ');
+        out.extend(namestrs);
+        out.extend(header("OBS", "000000000000000000000000000000"));
+
+        let mut data = Vec::new();
+        for (id, city, visit) in [(1.5, "Oslo  ", Some(366.0)), (-2.0, "Lima  ", None)] {
+            data.extend(ieee_to_ibm(id));
+            data.extend_from_slice(city.as_bytes());
+            match visit {
+                Some(days) => data.extend(ieee_to_ibm(days)),
+                None => data.extend_from_slice(b".\0\0\0\0\0\0\0"),
+            }
+        }
+        data.resize(data.len().div_ceil(80) * 80, b' ');
+        out.extend(data);
+        out
+    }
+
+    #[test]
+    fn test_ibm_to_ieee() {
+        for value in [1.0, -1.0, 0.1, 100.0, 123456.789, -0.000123, 1e60] {
+            let converted = ibm_to_ieee(&ieee_to_ibm(value)).unwrap();
+            assert!((converted - value).abs() <= value.abs() * 1e-15, "{value}");
+        }
+        assert_eq!(ibm_to_ieee(&[0x41, 0x10, 0, 0, 0, 0, 0, 0]), Some(1.0));
+        assert_eq!(
+            ibm_to_ieee(&[0xC2, 0x76, 0xA0, 0, 0, 0, 0, 0]),
+            Some(-118.625)
+        );
+        // Truncated to 4 bytes
+        assert_eq!(ibm_to_ieee(&[0x41, 0x10, 0, 0]), Some(1.0));
+        assert_eq!(ibm_to_ieee(b".\0\0\0\0\0\0\0"), None);
+        assert_eq!(ibm_to_ieee(b"A\0\0\0\0\0\0\0"), None);
+        assert_eq!(ibm_to_ieee(&[0; 8]), Some(0.0));
+    }
+
+    #[test]
+    fn test_read_xpt_v5() {
+        let mut scanner = XptScanner::open(Cursor::new(xpt_v5()), 100).unwrap();
+        let meta = scanner.metadata().clone();
+        assert_eq!(meta.file_format, "xpt");
+        assert_eq!(meta.file_label, "Clinic visits");
+        assert_eq!(meta.number_rows, Some(2));
+        assert_eq!(meta.variable_names, vec!["ID", "CITY", "VISIT"]);
+        assert_eq!(meta.label("VISIT"), Some("Visit date"));
+        assert_eq!(meta.format("CITY"), Some("A6"));
+        assert_eq!(meta.format("VISIT"), Some("DATE11"));
+
+        let batch = scanner.collect_single().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let id = batch.column(0).as_primitive::<Float64Type>();
+        assert_eq!(id.values().to_vec(), vec![1.5, -2.0]);
+        assert_eq!(batch.column(1).as_string_view().value(1), "Lima");
+        assert_eq!(batch.column(2).data_type(), &DataType::Date32);
+        let visit = batch.column(2).as_primitive::<Date32Type>();
+        assert_eq!(visit.value(0), -3653 + 366);
+        assert!(visit.is_null(1));
+    }
+
+    #[test]
+    fn test_select_and_limit() {
+        let mut scanner = XptScanner::open(Cursor::new(xpt_v5()), 1).unwrap();
+        scanner.select(&["CITY"]).unwrap();
+        let batches = scanner.collect_all().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].column(0).as_string_view().value(0), "Oslo");
+
+        assert!(XptScanner::open(Cursor::new(b"not a transport file".to_vec()), 1).is_err());
+    }
+
+    #[test]
+    fn test_count_rows_ignores_padding() {
+        // 3 rows of 10 bytes padded to 80: the blank "rows" are padding
+        let mut data = b"aaaaaaaaaabbbbbbbbbbcccccccccc".to_vec();
+        data.resize(80, b' ');
+        assert_eq!(count_rows(&data, 10), 3);
+        // A 100-byte row fills two records and leaves 60 bytes of padding
+        let mut data = vec![b'x'; 100];
+        data.resize(160, b' ');
+        assert_eq!(count_rows(&data, 100), 1);
+    }
+}
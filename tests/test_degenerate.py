@@ -0,0 +1,57 @@
+"""Files without variables or without cases.
+
+Run with:
+    pytest tests/test_degenerate.py -v
+"""
+
+import struct
+
+import polars as pl
+import pytest
+
+import ambers
+
+
+def write(path, df, ncases=None):
+    """Write df with ambers, optionally overriding the header case count."""
+    ambers.write_sav(df, None, str(path))
+    if ncases is not None:
+        data = bytearray(path.read_bytes())
+        data[80:84] = struct.pack("<i", ncases)
+        path.write_bytes(bytes(data))
+    return path
+
+
+@pytest.mark.parametrize("ncases", [None, 3, -1])
+def test_no_variables(tmp_path, ncases):
+    """No variables: no rows either, whatever the header claims."""
+    path = write(tmp_path / "empty.sav", pl.DataFrame(), ncases)
+
+    df, meta = ambers.read_sav(path)
+    assert df.shape == (0, 0)
+    assert meta.number_columns == 0
+    assert meta.number_rows == 0
+    assert ambers.read_sav_metadata(path).number_rows == 0
+
+    lf, _ = ambers.scan_sav(path)
+    assert lf.collect().shape == (0, 0)
+
+
+@pytest.mark.parametrize("ncases", [None, -1])
+def test_no_cases(tmp_path, ncases):
+    """Variables but no cases: an empty frame that keeps the schema."""
+    df_write = pl.DataFrame(
+        {"id": pl.Series([], dtype=pl.Float64), "name": pl.Series([], dtype=pl.String)}
+    )
+    path = write(tmp_path / "no_cases.sav", df_write, ncases)
+
+    df, meta = ambers.read_sav(path)
+    assert df.shape == (0, 2)
+    assert df.columns == ["id", "name"]
+    assert df.schema["id"] == pl.Float64
+    assert meta.variable_names == ["id", "name"]
+    # An unknown case count stays unknown in the metadata read up front
+    assert meta.number_rows == (None if ncases == -1 else 0)
+
+    lf, _ = ambers.scan_sav(path)
+    assert lf.collect().shape == (0, 2)
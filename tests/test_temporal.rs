@@ -3,6 +3,7 @@ use arrow::array::Array;
 use arrow::datatypes::DataType;
 
 #[test]
+#[allow(clippy::manual_range_contains)]
 fn test_temporal_types_real_file() {
     let path = std::env::var("SAV_TEST_FILE")
         .unwrap_or_else(|_| "test_data/test_2_medium.sav".to_string());
@@ -0,0 +1,130 @@
+"""Round trip through write_sav: write a frame and its metadata, read it
+back with read_sav, and compare.
+
+Run with:
+    pytest tests/test_write_sav.py -v
+    pytest tests/test_write_sav.py -v --sav-file path/to/file.sav
+"""
+
+import datetime
+import json
+
+import polars as pl
+import pytest
+from polars.testing import assert_frame_equal
+
+import ambers
+
+
+def sample_frame():
+    return pl.DataFrame(
+        {
+            "id": [1.0, 2.0, 3.0, 4.0],
+            "score": [12.5, None, 7.25, 99.0],
+            "city": ["Oslo", "Lyon", "Bergen", "Zürich"],
+            "born": [
+                datetime.date(1980, 1, 31),
+                datetime.date(1999, 12, 1),
+                None,
+                datetime.date(2024, 2, 29),
+            ],
+        }
+    )
+
+
+def sample_metadata():
+    doc = {
+        "format_version": 1,
+        "file": {"label": "Round trip", "notes": ["written by the test suite"]},
+        "variables": [
+            {"name": "id", "label": "Respondent", "format": "F8.0", "measure": "nominal"},
+            {
+                "name": "score",
+                "label": "Score",
+                "format": "F8.2",
+                "measure": "scale",
+                "missing": [{"value": -9}],
+                "value_labels": [{"value": 99, "label": "Top score"}],
+            },
+            {
+                "name": "city",
+                "label": "City of residence",
+                "format": "A12",
+                "missing": [{"string": "N/A"}],
+            },
+            {"name": "born", "label": "Date of birth", "format": "DATE11"},
+        ],
+    }
+    return ambers.SpssMetadata.from_json(json.dumps(doc))
+
+
+@pytest.mark.parametrize(
+    "suffix, compression",
+    [(".sav", None), (".sav", "bytecode"), (".zsav", None)],
+)
+def test_write_read_roundtrip(tmp_path, suffix, compression):
+    df = sample_frame()
+    meta = sample_metadata()
+    path = tmp_path / f"roundtrip{suffix}"
+
+    ambers.write_sav(df, meta, str(path), compression=compression)
+    df_back, meta_back = ambers.read_sav(str(path))
+
+    # Data
+    assert_frame_equal(df_back, df)
+
+    # File-level metadata
+    assert meta_back.file_label == "Round trip"
+    assert meta_back.notes == ["written by the test suite"]
+    assert meta_back.number_rows == 4
+    assert meta_back.number_columns == 4
+    assert meta_back.compression == (compression or ("zlib" if suffix == ".zsav" else "none"))
+
+    # Variable metadata
+    assert meta_back.variable_names == ["id", "score", "city", "born"]
+    assert meta_back.variable_labels == {
+        "id": "Respondent",
+        "score": "Score",
+        "city": "City of residence",
+        "born": "Date of birth",
+    }
+    assert meta_back.spss_variable_types == {
+        "id": "F8.0",
+        "score": "F8.2",
+        "city": "A12",
+        "born": "DATE11",
+    }
+    assert meta_back.variable_measure["id"] == "nominal"
+    assert meta_back.variable_measure["score"] == "scale"
+    assert meta_back.variable_value_labels["score"] == {99.0: "Top score"}
+    assert meta_back.variable_missing["score"] == [{"type": "value", "value": -9.0}]
+    assert meta_back.variable_missing["city"] == [{"type": "string_value", "value": "N/A"}]
+
+
+def test_write_defaults_without_metadata(tmp_path):
+    df = sample_frame()
+    path = tmp_path / "plain.sav"
+
+    ambers.write_sav(df, None, str(path))
+    df_back, meta_back = ambers.read_sav(str(path))
+
+    assert_frame_equal(df_back, df)
+    assert meta_back.variable_names == df.columns
+    assert meta_back.variable_labels == {}
+
+
+def test_rewrite_existing_file(tmp_path, sav_file):
+    """Read a real file, write it back with its metadata, and read it again."""
+    df, meta = ambers.read_sav(sav_file)
+    path = tmp_path / "rewritten.zsav"
+
+    ambers.write_sav(df, meta, str(path))
+    df_back, meta_back = ambers.read_sav(str(path))
+
+    assert_frame_equal(df_back, df)
+    diff = meta.diff(meta_back, print_output=False)
+    assert diff.variables_only_in_self == []
+    assert diff.variables_only_in_other == []
+    assert diff.variable_labels == []
+    assert diff.variable_value_labels == []
+    assert diff.variable_missing == []